@@ -8,6 +8,7 @@ use std::fmt::{Display, Formatter, Result};
 #[derive(Debug)]
 pub enum QueryContextError {
     InvalidDate(String),
+    InvalidDuration(String),
 }
 
 impl Display for QueryContextError {
@@ -17,6 +18,10 @@ impl Display for QueryContextError {
                 fmt,
                 "Invalid date provided: {date}! Must be in format YYYY-MM-DD."
             ),
+            QueryContextError::InvalidDuration(duration) => write!(
+                fmt,
+                "Invalid duration provided: {duration}! Must be a positive number followed by d, w, mo, or y, i.e. 30d."
+            ),
         }
     }
 }
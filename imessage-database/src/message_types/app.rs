@@ -9,7 +9,7 @@ use plist::Value;
 
 use crate::{
     error::plist::PlistParseError,
-    message_types::variants::BalloonProvider,
+    message_types::variants::{BalloonProvider, CustomBalloon},
     util::plist::{get_string_from_dict, get_string_from_nested_dict},
 };
 
@@ -82,12 +82,48 @@ impl AppMessage<'_> {
 
         map
     }
+
+    /// Summarize the message on one line, i.e. `Apple Pay: $20 Request`
+    ///
+    /// The summary starts with the name of the app, followed by the most descriptive text in the payload
+    /// with line breaks and non-breaking spaces replaced by spaces.
+    /// Third party apps are named by the payload, falling back to the bundle ID.
+    pub fn summary(&self, balloon: &CustomBalloon) -> String {
+        let name = match balloon {
+            CustomBalloon::Application(bundle_id) if balloon.name() == *bundle_id => {
+                self.app_name.unwrap_or(bundle_id)
+            }
+            _ => balloon.name(),
+        };
+
+        let details = match balloon {
+            // The subcaption holds the amount and kind of transaction, i.e. `$20 Request`
+            CustomBalloon::ApplePay => self.subcaption.or(self.ldtext).map(str::to_string),
+            _ => self.ldtext.map(str::to_string).or_else(|| {
+                let parts: Vec<&str> = [self.title, self.subtitle, self.caption, self.subcaption]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                (!parts.is_empty()).then(|| parts.join(" - "))
+            }),
+        };
+
+        match details.map(|details| details.replace(['\n', '\u{a0}'], " ")) {
+            // System messages often already start with the name of the app, i.e. `Check In: Timer Started`
+            Some(details) if details.starts_with(name) => details,
+            Some(details) => format!("{name}: {details}"),
+            None => name.to_string(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        message_types::{app::AppMessage, variants::BalloonProvider},
+        message_types::{
+            app::AppMessage,
+            variants::{BalloonProvider, CustomBalloon},
+        },
         util::plist::parse_plist,
     };
     use plist::Value;
@@ -405,4 +441,101 @@ mod tests {
 
         assert_eq!(balloon, expected);
     }
+
+    #[test]
+    fn can_summarize_apple_pay() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/app_message/Sent265.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_plist(&plist).unwrap();
+
+        let balloon = AppMessage::from_map(&parsed).unwrap();
+
+        assert_eq!(
+            balloon.summary(&CustomBalloon::ApplePay),
+            "Apple Pay: $265 Payment"
+        );
+    }
+
+    #[test]
+    fn can_summarize_apple_pay_recurring() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/app_message/ApplePayRecurring.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_plist(&plist).unwrap();
+
+        let balloon = AppMessage::from_map(&parsed).unwrap();
+
+        assert_eq!(
+            balloon.summary(&CustomBalloon::ApplePay),
+            "Apple Pay: Sending you $1 weekly starting Nov 18, 2023"
+        );
+    }
+
+    #[test]
+    fn can_summarize_check_in() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/app_message/CheckinTimer.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_plist(&plist).unwrap();
+
+        let balloon = AppMessage::from_map(&parsed).unwrap();
+
+        assert_eq!(
+            balloon.summary(&CustomBalloon::CheckIn),
+            "Check In: Timer Started"
+        );
+    }
+
+    #[test]
+    fn can_summarize_third_party_app() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/app_message/OpenTableInvited.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_plist(&plist).unwrap();
+
+        let balloon = AppMessage::from_map(&parsed).unwrap();
+
+        assert_eq!(
+            balloon.summary(&CustomBalloon::Application("com.opentable.OpenTable")),
+            "OpenTable: Rusty Grill - Boise - Reservation Confirmed - Table for 4 people Sunday, October 17 at 7:45 PM - You're invited! Tap to accept."
+        );
+    }
+
+    #[test]
+    fn can_summarize_unknown_app() {
+        let balloon = AppMessage {
+            image: None,
+            url: None,
+            title: None,
+            subtitle: None,
+            caption: None,
+            subcaption: None,
+            trailing_caption: None,
+            trailing_subcaption: None,
+            app_name: None,
+            ldtext: None,
+        };
+
+        assert_eq!(
+            balloon.summary(&CustomBalloon::Application("com.example.Game")),
+            "com.example.Game"
+        );
+        assert_eq!(
+            balloon.summary(&CustomBalloon::Application("com.apple.messages.Polls")),
+            "Polls"
+        );
+    }
 }
@@ -16,9 +16,22 @@ pub fn from_payload(payload: &[u8]) -> Option<DigitalTouch> {
     Some(msg.TouchKind.enum_value_or_default())
 }
 
+/// A readable name for the kind of a [`DigitalTouch`], i.e. `sketch`
+pub fn kind_name(kind: &DigitalTouch) -> &'static str {
+    match kind {
+        DigitalTouch::Unknown => "unknown",
+        DigitalTouch::Tap => "tap",
+        // Heartbreaks are heartbeats that are held down
+        DigitalTouch::Heartbeat => "heartbeat",
+        DigitalTouch::Sketch => "sketch",
+        DigitalTouch::Kiss => "kiss",
+        DigitalTouch::Fireball => "fireball",
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::message_types::digital_touch::{from_payload, DigitalTouch};
+    use crate::message_types::digital_touch::{from_payload, kind_name, DigitalTouch};
 
     use std::env::current_dir;
     use std::fs::File;
@@ -94,6 +107,12 @@ mod tests {
         assert_eq!(expected, Some(DigitalTouch::Kiss));
     }
 
+    #[test]
+    fn can_name_kind() {
+        assert_eq!(kind_name(&DigitalTouch::Sketch), "sketch");
+        assert_eq!(kind_name(&DigitalTouch::Unknown), "unknown");
+    }
+
     #[test]
     fn can_parse_fireball() {
         let protobuf_path = current_dir()
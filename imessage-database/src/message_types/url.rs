@@ -41,6 +41,25 @@ pub struct URLMessage<'a> {
     pub placeholder: bool,
 }
 
+/// Where the preview image shown in a link's bubble is stored
+#[derive(Debug, PartialEq, Eq)]
+pub enum PreviewImage<'a> {
+    /// One of the message's attachments, stored in a `.pluginPayloadAttachment` file
+    Attachment {
+        /// The index of the attachment in the message's attachments
+        index: usize,
+        /// The MIME type of the image, which the attachment does not record
+        mime_type: Option<&'a str>,
+    },
+    /// Image data stored in the payload itself
+    Embedded {
+        /// The bytes of the image file
+        data: &'a [u8],
+        /// The MIME type of the image
+        mime_type: Option<&'a str>,
+    },
+}
+
 impl<'a> BalloonProvider<'a> for URLMessage<'a> {
     fn from_map(payload: &'a Value) -> Result<Self, PlistParseError> {
         let url_metadata = URLMessage::get_body(payload)?;
@@ -126,6 +145,34 @@ impl<'a> URLMessage<'a> {
             .collect()
     }
 
+    /// Get where the preview image of a link is stored, if the payload has one
+    ///
+    /// The page's image is preferred; pages without a usable one fall back to their icon.
+    ///
+    /// Older payloads store the image's bytes under `data`; recent ones point to one of the message's
+    /// attachments with a `richLinkImageAttachmentSubstituteIndex`.
+    pub fn get_preview_image(payload: &'a Value) -> Option<PreviewImage<'a>> {
+        let body = URLMessage::get_body(payload).ok()?.as_dictionary()?;
+        ["image", "icon"].into_iter().find_map(|key| {
+            let image = body.get(key)?;
+            let mime_type = get_string_from_dict(image, "MIMEType");
+            let image = image.as_dictionary()?;
+            if let Some(data) = image
+                .get("data")
+                .and_then(Value::as_data)
+                .filter(|data| !data.is_empty())
+            {
+                return Some(PreviewImage::Embedded { data, mime_type });
+            }
+            // Parsing the payload stores the attachment index under the name of the image it belongs to
+            let index = image.get(key).and_then(Value::as_unsigned_integer)?;
+            Some(PreviewImage::Attachment {
+                index: usize::try_from(index).ok()?,
+                mime_type,
+            })
+        })
+    }
+
     /// Get the redirected URL from a URL message, falling back to the original URL, if it exists
     pub fn get_url(&self) -> Option<&str> {
         self.url.or(self.original_url)
@@ -135,10 +182,13 @@ impl<'a> URLMessage<'a> {
 #[cfg(test)]
 mod url_tests {
     use crate::{
-        message_types::{url::URLMessage, variants::BalloonProvider},
+        message_types::{
+            url::{PreviewImage, URLMessage},
+            variants::BalloonProvider,
+        },
         util::plist::parse_plist,
     };
-    use plist::Value;
+    use plist::{Dictionary, Value};
     use std::env::current_dir;
     use std::fs::File;
 
@@ -231,6 +281,91 @@ mod url_tests {
         assert_eq!(balloon, expected);
     }
 
+    #[test]
+    fn test_get_preview_image_attachment() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/url_message/Twitter.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_plist(&plist).unwrap();
+
+        let preview = URLMessage::get_preview_image(&parsed);
+        assert!(matches!(
+            preview,
+            Some(PreviewImage::Attachment { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_get_preview_image_icon() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/url_message/URL.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_plist(&plist).unwrap();
+
+        let preview = URLMessage::get_preview_image(&parsed);
+        assert!(matches!(
+            preview,
+            Some(PreviewImage::Attachment { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_get_preview_image_embedded() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/url_message/MetadataURL.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_plist(&plist).unwrap();
+
+        let Some(PreviewImage::Embedded { data, mime_type }) =
+            URLMessage::get_preview_image(&parsed)
+        else {
+            panic!("Expected an embedded preview image");
+        };
+        assert_eq!(data.len(), 48);
+        assert_eq!(mime_type, Some("image/png"));
+    }
+
+    #[test]
+    fn test_get_preview_image_unusable_image() {
+        let mut image = Dictionary::new();
+        image.insert("MIMEType".to_string(), Value::from("image/jpeg"));
+        let mut icon = Dictionary::new();
+        icon.insert("icon".to_string(), Value::from(2u64));
+        let mut metadata = Dictionary::new();
+        metadata.insert("image".to_string(), Value::Dictionary(image));
+        metadata.insert("icon".to_string(), Value::Dictionary(icon));
+        let mut root = Dictionary::new();
+        root.insert("richLinkMetadata".to_string(), Value::Dictionary(metadata));
+        let parsed = Value::Dictionary(root);
+
+        assert!(matches!(
+            URLMessage::get_preview_image(&parsed),
+            Some(PreviewImage::Attachment { index: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_get_preview_image_missing() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/url_message/Reminder.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_plist(&plist).unwrap();
+
+        assert_eq!(URLMessage::get_preview_image(&parsed), None);
+    }
+
     #[test]
     fn test_parse_url_reminder() {
         let plist_path = current_dir()
@@ -98,6 +98,31 @@ pub enum CustomBalloon<'a> {
     FindMy,
 }
 
+/// Names of apps that do not always name themselves in their payloads, keyed by bundle ID
+const KNOWN_APPS: [(&str, &str); 1] = [("com.apple.messages.Polls", "Polls")];
+
+impl CustomBalloon<'_> {
+    /// Get a readable name for the app that generated the balloon
+    ///
+    /// Third party apps that are not known are named by their bundle ID.
+    pub fn name(&self) -> &str {
+        match self {
+            CustomBalloon::Application(bundle_id) => KNOWN_APPS
+                .iter()
+                .find(|(id, _)| id == bundle_id)
+                .map_or(bundle_id, |(_, name)| name),
+            CustomBalloon::URL => "URL",
+            CustomBalloon::Handwriting => "Handwriting",
+            CustomBalloon::DigitalTouch => "Digital Touch",
+            CustomBalloon::ApplePay => "Apple Pay",
+            CustomBalloon::Fitness => "Fitness",
+            CustomBalloon::Slideshow => "Photos",
+            CustomBalloon::CheckIn => "Check In",
+            CustomBalloon::FindMy => "Find My",
+        }
+    }
+}
+
 /// URL Message Types
 ///
 /// Apple sometimes overloads `com.apple.messages.URLBalloonProvider` with
@@ -132,6 +157,46 @@ pub enum Announcement<'a> {
     Unknown(&'a i32),
 }
 
+/// System Event Message Types
+///
+/// System events are records the thread keeps of things that happened outside of it, i.e. FaceTime calls
+#[derive(Debug, PartialEq, Eq)]
+pub enum SystemEvent {
+    /// A FaceTime call, which `chat.db` records without its duration
+    FaceTime(CallDirection),
+    /// Types that are not decoded yet, with the `item_type` of the message
+    Unknown(i32),
+}
+
+impl Display for SystemEvent {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SystemEvent::FaceTime(direction) => write!(fmt, "FaceTime call, {direction}"),
+            SystemEvent::Unknown(item_type) => {
+                write!(fmt, "Unsupported system event (type {item_type})")
+            }
+        }
+    }
+}
+
+/// The direction of a call, from the point of view of the database owner
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CallDirection {
+    /// Someone else started the call
+    Incoming,
+    /// The database owner started the call
+    Outgoing,
+}
+
+impl Display for CallDirection {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallDirection::Incoming => write!(fmt, "incoming"),
+            CallDirection::Outgoing => write!(fmt, "outgoing"),
+        }
+    }
+}
+
 /// Message variant container
 ///
 /// Messages can exist as one of many different variants, this encapsulates
@@ -5,7 +5,8 @@
 use rusqlite::{Connection, Error, Result, Row, Statement};
 use sha1::{Digest, Sha1};
 use std::{
-    fs::File,
+    fmt::{Display, Formatter},
+    fs::{canonicalize, File},
     io::Read,
     path::{Path, PathBuf},
 };
@@ -15,11 +16,11 @@ use crate::{
     message_types::sticker::{get_sticker_effect, StickerEffect},
     tables::{
         messages::Message,
-        table::{Table, ATTACHMENT},
+        table::{Table, ATTACHMENT, MESSAGE, MESSAGE_ATTACHMENT_JOIN},
     },
     util::{
         dates::TIMESTAMP_FACTOR,
-        dirs::home,
+        dirs::expand_home,
         output::{done_processing, processing},
         platform::Platform,
         query_context::QueryContext,
@@ -56,12 +57,68 @@ impl MediaType<'_> {
             MediaType::Unknown => String::new(),
         }
     }
+
+    /// Infer the media type of a file from its extension, for attachments that do not store a MIME type
+    ///
+    /// Extensions are not case sensitive; unrecognized extensions are [`MediaType::Unknown`].
+    pub fn from_extension(extension: &str) -> MediaType<'static> {
+        match extension.to_lowercase().as_str() {
+            "heic" => MediaType::Image("heic"),
+            "heics" => MediaType::Image("heics"),
+            "png" => MediaType::Image("png"),
+            "jpg" | "jpeg" => MediaType::Image("jpeg"),
+            "gif" => MediaType::Image("gif"),
+            "tif" | "tiff" => MediaType::Image("tiff"),
+            "webp" => MediaType::Image("webp"),
+            "svg" => MediaType::Image("svg+xml"),
+            "mov" => MediaType::Video("quicktime"),
+            "mp4" => MediaType::Video("mp4"),
+            "m4v" => MediaType::Video("x-m4v"),
+            "caf" => MediaType::Audio("x-caf"),
+            "m4a" => MediaType::Audio("x-m4a"),
+            "mp3" => MediaType::Audio("mpeg"),
+            "amr" => MediaType::Audio("amr"),
+            "txt" => MediaType::Text("plain"),
+            "vcf" => MediaType::Text("vcard"),
+            "pdf" => MediaType::Application("pdf"),
+            _ => MediaType::Unknown,
+        }
+    }
+
+    /// Get the extension of a file with this media type, the reverse of [`MediaType::from_extension`]
+    ///
+    /// Media types without a known extension return [`None`].
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            MediaType::Image("heic") => Some("heic"),
+            MediaType::Image("heics") => Some("heics"),
+            MediaType::Image("png") => Some("png"),
+            MediaType::Image("jpeg" | "jpg") => Some("jpeg"),
+            MediaType::Image("gif") => Some("gif"),
+            MediaType::Image("tiff") => Some("tiff"),
+            MediaType::Image("webp") => Some("webp"),
+            MediaType::Image("svg+xml") => Some("svg"),
+            MediaType::Video("quicktime") => Some("mov"),
+            MediaType::Video("mp4") => Some("mp4"),
+            MediaType::Video("x-m4v") => Some("m4v"),
+            MediaType::Audio("x-caf") => Some("caf"),
+            MediaType::Audio("x-m4a") => Some("m4a"),
+            MediaType::Audio("mpeg") => Some("mp3"),
+            MediaType::Audio("amr") => Some("amr"),
+            MediaType::Text("plain") => Some("txt"),
+            MediaType::Text("vcard") => Some("vcf"),
+            MediaType::Application("pdf") => Some("pdf"),
+            _ => None,
+        }
+    }
 }
 
 /// Represents a single row in the `attachment` table.
 #[derive(Debug)]
 pub struct Attachment {
     pub rowid: i32,
+    /// The attachment's globally unique identifier
+    pub guid: Option<String>,
     /// The path to the file on disk
     pub filename: Option<String>,
     /// The [Uniform Type Identifier](https://developer.apple.com/library/archive/documentation/FileManagement/Conceptual/understanding_utis/understand_utis_intro/understand_utis_intro.html)
@@ -79,12 +136,15 @@ pub struct Attachment {
     pub emoji_description: Option<String>,
     /// Auxiliary data to denote that an attachment has been copied
     pub copied_path: Option<PathBuf>,
+    /// Auxiliary data to denote that the original file was also copied next to a converted attachment
+    pub original_path: Option<PathBuf>,
 }
 
 impl Table for Attachment {
     fn from_row(row: &Row) -> Result<Attachment> {
         Ok(Attachment {
             rowid: row.get("rowid")?,
+            guid: row.get("guid").unwrap_or(None),
             filename: row.get("filename").unwrap_or(None),
             uti: row.get("uti").unwrap_or(None),
             mime_type: row.get("mime_type").unwrap_or(None),
@@ -94,6 +154,7 @@ impl Table for Attachment {
             hide_attachment: row.get("hide_attachment").unwrap_or(0),
             emoji_description: row.get("emoji_image_short_description").unwrap_or(None),
             copied_path: None,
+            original_path: None,
         })
     }
 
@@ -138,6 +199,21 @@ impl Attachment {
         Ok(out_l)
     }
 
+    /// Get the attachment with the given GUID, if it exists
+    ///
+    /// Some attachments, like group photos, are not sent in a message, so they are found by their GUID instead
+    pub fn from_guid(db: &Connection, guid: &str) -> Result<Option<Attachment>, TableError> {
+        let mut statement = db
+            .prepare(&format!("SELECT * FROM {ATTACHMENT} WHERE guid = ?1"))
+            .map_err(TableError::Attachment)?;
+
+        let mut iter = statement
+            .query_map([guid], |row| Ok(Attachment::from_row(row)))
+            .map_err(TableError::Attachment)?;
+
+        iter.next().map(Attachment::extract).transpose()
+    }
+
     /// Get the media type of an attachment
     pub fn mime_type(&'_ self) -> MediaType<'_> {
         match &self.mime_type {
@@ -308,17 +384,34 @@ impl Attachment {
         db_path: &Path,
         custom_attachment_root: Option<&str>,
     ) -> Option<String> {
-        if let Some(mut path_str) = self.filename.clone() {
-            // Apply custom attachment path
-            if let Some(custom_attachment_path) = custom_attachment_root {
-                path_str = path_str.replace(DEFAULT_ATTACHMENT_ROOT, custom_attachment_path);
-            }
-            return match platform {
-                Platform::macOS => Some(Attachment::gen_macos_attachment(&path_str)),
-                Platform::iOS => Attachment::gen_ios_attachment(&path_str, db_path),
-            };
-        }
-        None
+        Attachment::resolve_path(
+            self.filename.as_deref()?,
+            platform,
+            db_path,
+            custom_attachment_root,
+        )
+    }
+
+    /// Resolve a path from the `filename` column to the file on disk
+    ///
+    /// On macOS, a leading `~` is expanded to the user's home directory. If a custom attachment root is provided,
+    /// paths under [`DEFAULT_ATTACHMENT_ROOT`] are moved to it and relative paths are resolved against it.
+    /// Paths to files that exist are canonicalized, so the same file always resolves to the same path.
+    fn resolve_path(
+        filename: &str,
+        platform: &Platform,
+        db_path: &Path,
+        custom_attachment_root: Option<&str>,
+    ) -> Option<String> {
+        let path = match platform {
+            Platform::macOS => Attachment::gen_macos_attachment(filename, custom_attachment_root),
+            Platform::iOS => Attachment::gen_ios_attachment(filename, db_path)?,
+        };
+        Some(
+            canonicalize(&path)
+                .map(|canonical| canonical.to_string_lossy().to_string())
+                .unwrap_or(path),
+        )
     }
 
     /// Emit diagnostic data for the Attachments table
@@ -340,56 +433,80 @@ impl Attachment {
     ///
     /// let db_path = default_db_path();
     /// let conn = get_connection(&db_path).unwrap();
-    /// Attachment::run_diagnostic(&conn, &db_path, &Platform::macOS);
+    /// Attachment::run_diagnostic(&conn, &db_path, &Platform::macOS, None);
     /// ```
     ///
     /// `db_path` is the path to the root of the backup directory.
     /// This is the same path used by [`get_connection()`](crate::tables::table::get_connection).
+    /// Files are looked for under `custom_attachment_root`, if provided, as they are when exporting.
     pub fn run_diagnostic(
         db: &Connection,
         db_path: &Path,
         platform: &Platform,
+        custom_attachment_root: Option<&str>,
     ) -> Result<(), TableError> {
         processing();
+        let data = Attachment::diagnostic(db, db_path, platform, custom_attachment_root)?;
+        done_processing();
+        print!("{data}");
+        Ok(())
+    }
+
+    /// Collect diagnostic data for the Attachments table without printing anything
+    ///
+    /// `db_path` is the path to the root of the backup directory.
+    /// This is the same path used by [`get_connection()`](crate::tables::table::get_connection).
+    pub fn diagnostic(
+        db: &Connection,
+        db_path: &Path,
+        platform: &Platform,
+        custom_attachment_root: Option<&str>,
+    ) -> Result<AttachmentDiagnostic, TableError> {
         let mut total_attachments = 0;
         let mut null_attachments = 0;
+        let mut expired_audio = 0;
         let mut size_on_disk: u64 = 0;
         let mut statement_paths = db
-            .prepare(&format!("SELECT filename FROM {ATTACHMENT}"))
+            .prepare(&format!(
+                "SELECT
+                     a.filename,
+                     EXISTS (
+                         SELECT 1 FROM {MESSAGE_ATTACHMENT_JOIN} j
+                         JOIN {MESSAGE} m ON m.ROWID = j.message_id
+                         WHERE j.attachment_id = a.ROWID AND m.is_audio_message = 1
+                     )
+                 FROM {ATTACHMENT} a"
+            ))
             .map_err(TableError::Attachment)?;
         let paths = statement_paths
-            .query_map([], |r| Ok(r.get(0)))
+            .query_map([], |r| Ok((r.get(0), r.get(1).unwrap_or(false))))
             .map_err(TableError::Attachment)?;
 
         let missing_files = paths
             .filter_map(Result::ok)
-            .filter(|path: &Result<String, Error>| {
+            .filter(|(path, is_audio_message): &(Result<String, Error>, bool)| {
                 // Keep track of the number of attachments in the table
                 total_attachments += 1;
                 if let Ok(filepath) = path {
-                    match platform {
-                        Platform::macOS => {
-                            let path = Attachment::gen_macos_attachment(filepath);
-                            let file = Path::new(&path);
-                            if let Ok(metadata) = file.metadata() {
-                                size_on_disk += metadata.len();
-                            }
-                            !file.exists()
+                    if let Some(parsed_path) = Attachment::resolve_path(
+                        filepath,
+                        platform,
+                        db_path,
+                        custom_attachment_root,
+                    ) {
+                        let file = Path::new(&parsed_path);
+                        if let Ok(metadata) = file.metadata() {
+                            size_on_disk += metadata.len();
                         }
-                        Platform::iOS => {
-                            if let Some(parsed_path) =
-                                Attachment::gen_ios_attachment(filepath, db_path)
-                            {
-                                let file = Path::new(&parsed_path);
-                                if let Ok(metadata) = file.metadata() {
-                                    size_on_disk += metadata.len();
-                                }
-                                return !file.exists();
-                            }
-                            // This hits if the attachment path doesn't get generated
-                            true
+                        // Audio messages are deleted when they expire, so their files are gone by design
+                        if !file.exists() && *is_audio_message {
+                            expired_audio += 1;
+                            return false;
                         }
+                        return !file.exists();
                     }
+                    // This hits if the attachment path doesn't get generated
+                    true
                 } else {
                     // This hits if there is no path provided for the current attachment
                     null_attachments += 1;
@@ -401,40 +518,32 @@ impl Attachment {
         let total_bytes =
             Attachment::get_total_attachment_bytes(db, &QueryContext::default()).unwrap_or(0);
 
-        done_processing();
-
-        if total_attachments > 0 {
-            println!("\rAttachment diagnostic data:");
-            println!("    Total attachments: {total_attachments}");
-            println!(
-                "        Data referenced in table: {}",
-                format_file_size(total_bytes)
-            );
-            println!(
-                "        Data present on disk: {}",
-                format_file_size(size_on_disk)
-            );
-            if missing_files > 0 && total_attachments > 0 {
-                println!(
-                    "    Missing files: {missing_files:?} ({:.0}%)",
-                    (missing_files as f64 / total_attachments as f64) * 100f64
-                );
-                println!("        No path provided: {null_attachments}");
-                println!(
-                    "        No file located: {}",
-                    missing_files.saturating_sub(null_attachments)
-                );
-            }
-        }
-        Ok(())
+        Ok(AttachmentDiagnostic {
+            total_attachments,
+            total_bytes,
+            bytes_on_disk: size_on_disk,
+            missing_files,
+            no_path_provided: null_attachments,
+            expired_audio,
+        })
     }
 
     /// Generate a macOS path for an attachment
-    fn gen_macos_attachment(path: &str) -> String {
-        if path.starts_with('~') {
-            return path.replacen('~', &home(), 1);
+    fn gen_macos_attachment(path: &str, custom_attachment_root: Option<&str>) -> String {
+        let path = expand_home(path);
+        if let Some(root) = custom_attachment_root.map(expand_home) {
+            let default_root = expand_home(DEFAULT_ATTACHMENT_ROOT);
+            let relative = match Path::new(&path).strip_prefix(&default_root) {
+                Ok(relative) => relative,
+                Err(_) if Path::new(&path).is_relative() => Path::new(&path),
+                Err(_) => return path,
+            };
+            return Path::new(&root)
+                .join(relative)
+                .to_string_lossy()
+                .to_string();
         }
-        path.to_string()
+        path
     }
 
     /// Generate an iOS path for an attachment
@@ -450,24 +559,91 @@ impl Attachment {
     }
 }
 
+/// Diagnostic data for the `attachment` table
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct AttachmentDiagnostic {
+    /// The number of attachments in the table
+    pub total_attachments: usize,
+    /// The number of bytes the table says its attachments use
+    pub total_bytes: u64,
+    /// The number of bytes the attachments that were found use on disk
+    pub bytes_on_disk: u64,
+    /// The number of attachments whose files are missing
+    pub missing_files: usize,
+    /// The number of missing attachments that have no path in the table
+    pub no_path_provided: usize,
+    /// The number of audio messages whose files were deleted when they expired, which are not counted as missing
+    pub expired_audio: usize,
+}
+
+impl AttachmentDiagnostic {
+    /// The number of missing attachments that have a path, but no file at that path
+    pub fn no_file_located(&self) -> usize {
+        self.missing_files.saturating_sub(self.no_path_provided)
+    }
+}
+
+impl Display for AttachmentDiagnostic {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.total_attachments > 0 {
+            writeln!(fmt, "\rAttachment diagnostic data:")?;
+            writeln!(fmt, "    Total attachments: {}", self.total_attachments)?;
+            writeln!(
+                fmt,
+                "        Data referenced in table: {}",
+                format_file_size(self.total_bytes)
+            )?;
+            writeln!(
+                fmt,
+                "        Data present on disk: {}",
+                format_file_size(self.bytes_on_disk)
+            )?;
+            if self.missing_files > 0 {
+                writeln!(
+                    fmt,
+                    "    Missing files: {:?} ({:.0}%)",
+                    self.missing_files,
+                    (self.missing_files as f64 / self.total_attachments as f64) * 100f64
+                )?;
+                writeln!(fmt, "        No path provided: {}", self.no_path_provided)?;
+                writeln!(fmt, "        No file located: {}", self.no_file_located())?;
+            }
+            if self.expired_audio > 0 {
+                writeln!(fmt, "    Expired audio messages: {}", self.expired_audio)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
         tables::{
-            attachment::{Attachment, MediaType, DEFAULT_ATTACHMENT_ROOT},
+            attachment::{Attachment, AttachmentDiagnostic, MediaType, DEFAULT_ATTACHMENT_ROOT},
             table::get_connection,
         },
-        util::{dirs::default_db_path, platform::Platform, query_context::QueryContext},
+        util::{
+            dirs::{default_db_path, expand_home},
+            platform::Platform,
+            query_context::QueryContext,
+        },
     };
 
+    use rusqlite::Connection;
+
     use std::{
         collections::BTreeSet,
+        env::temp_dir,
+        fs::{canonicalize, create_dir_all, remove_dir_all, write},
         path::{Path, PathBuf},
+        process,
     };
 
     fn sample_attachment() -> Attachment {
         Attachment {
             rowid: 1,
+            guid: None,
             filename: Some("a/b/c.png".to_string()),
             uti: Some("public.png".to_string()),
             mime_type: Some("image/png".to_string()),
@@ -477,6 +653,7 @@ mod tests {
             hide_attachment: 0,
             emoji_description: None,
             copied_path: None,
+            original_path: None,
         }
     }
 
@@ -533,6 +710,36 @@ mod tests {
         assert_eq!(attachment.mime_type(), MediaType::Unknown);
     }
 
+    #[test]
+    fn can_get_media_type_from_extension() {
+        assert_eq!(MediaType::from_extension("heic"), MediaType::Image("heic"));
+        assert_eq!(MediaType::from_extension("HEIC"), MediaType::Image("heic"));
+        assert_eq!(
+            MediaType::from_extension("mov"),
+            MediaType::Video("quicktime")
+        );
+        assert_eq!(MediaType::from_extension("caf"), MediaType::Audio("x-caf"));
+    }
+
+    #[test]
+    fn cant_get_media_type_from_unknown_extension() {
+        assert_eq!(MediaType::from_extension("bloop"), MediaType::Unknown);
+    }
+
+    #[test]
+    fn can_get_extension_from_media_type() {
+        assert_eq!(MediaType::Image("svg+xml").extension(), Some("svg"));
+        assert_eq!(MediaType::Image("jpeg").extension(), Some("jpeg"));
+        assert_eq!(MediaType::Video("quicktime").extension(), Some("mov"));
+        assert_eq!(MediaType::from_extension("m4a").extension(), Some("m4a"));
+    }
+
+    #[test]
+    fn cant_get_extension_from_unknown_media_type() {
+        assert_eq!(MediaType::Image("x-bloop").extension(), None);
+        assert_eq!(MediaType::Unknown.extension(), None);
+    }
+
     #[test]
     fn can_get_filename() {
         let attachment = sample_attachment();
@@ -612,6 +819,70 @@ mod tests {
             .ends_with("c~d.png"));
     }
 
+    #[test]
+    fn can_get_resolved_path_macos_home_custom() {
+        let root = temp_dir().join(format!("imessage-attachment-root-{}", process::id()));
+        create_dir_all(root.join("a/b")).unwrap();
+        write(root.join("a/b/c.png"), "").unwrap();
+
+        let db_path = PathBuf::from("fake_root");
+        let mut attachment = sample_attachment();
+        attachment.filename = Some(format!("{}/a/b/c.png", DEFAULT_ATTACHMENT_ROOT));
+
+        assert_eq!(
+            attachment.resolved_attachment_path(
+                &Platform::macOS,
+                &db_path,
+                Some(root.to_str().unwrap())
+            ),
+            Some(
+                canonicalize(root.join("a/b/c.png"))
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            )
+        );
+        remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn can_get_resolved_path_macos_home_expanded() {
+        let db_path = PathBuf::from("fake_root");
+        let mut attachment = sample_attachment();
+        attachment.filename = Some(format!(
+            "{}/a/b/c.png",
+            expand_home(DEFAULT_ATTACHMENT_ROOT)
+        ));
+
+        assert_eq!(
+            attachment.resolved_attachment_path(&Platform::macOS, &db_path, Some("custom/root")),
+            Some("custom/root/a/b/c.png".to_string())
+        );
+    }
+
+    #[test]
+    fn can_get_resolved_path_macos_relative_custom() {
+        let db_path = PathBuf::from("fake_root");
+        let attachment = sample_attachment();
+
+        assert_eq!(
+            attachment.resolved_attachment_path(&Platform::macOS, &db_path, Some("custom/root")),
+            Some("custom/root/a/b/c.png".to_string())
+        );
+    }
+
+    #[test]
+    fn can_get_resolved_path_macos_absolute_custom() {
+        let db_path = PathBuf::from("fake_root");
+        let mut attachment = sample_attachment();
+        attachment.filename = Some("/elsewhere/a/b/c.png".to_string());
+
+        assert_eq!(
+            attachment.resolved_attachment_path(&Platform::macOS, &db_path, Some("custom/root")),
+            Some("/elsewhere/a/b/c.png".to_string())
+        );
+    }
+
     #[test]
     fn can_get_resolved_path_ios() {
         let db_path = PathBuf::from("fake_root");
@@ -768,4 +1039,75 @@ mod tests {
 
         assert_eq!(attachment.file_size(), String::from("8388608.00 TB"));
     }
+
+    #[test]
+    fn can_display_attachment_diagnostic() {
+        let diagnostic = AttachmentDiagnostic {
+            total_attachments: 4,
+            total_bytes: 2048,
+            bytes_on_disk: 1024,
+            missing_files: 2,
+            no_path_provided: 1,
+            expired_audio: 1,
+        };
+
+        assert_eq!(diagnostic.no_file_located(), 1);
+        assert_eq!(
+            diagnostic.to_string(),
+            "\rAttachment diagnostic data:\n    Total attachments: 4\n        Data referenced in table: 2.00 KB\n        Data present on disk: 1024.00 B\n    Missing files: 2 (50%)\n        No path provided: 1\n        No file located: 1\n    Expired audio messages: 1\n"
+        );
+    }
+
+    #[test]
+    fn can_count_expired_audio_separately() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE attachment (ROWID INTEGER PRIMARY KEY, filename TEXT, total_bytes INTEGER);
+             CREATE TABLE message (ROWID INTEGER PRIMARY KEY, is_audio_message INTEGER DEFAULT 0);
+             CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER);
+             INSERT INTO attachment VALUES (1, '/missing/Audio Message.caf', 100), (2, '/missing/photo.png', 100);
+             INSERT INTO message VALUES (1, 1), (2, 0);
+             INSERT INTO message_attachment_join VALUES (1, 1), (2, 2);",
+        )
+        .unwrap();
+
+        let actual =
+            Attachment::diagnostic(&db, Path::new("chat.db"), &Platform::macOS, None).unwrap();
+
+        assert_eq!(actual.total_attachments, 2);
+        assert_eq!(actual.missing_files, 1);
+        assert_eq!(actual.expired_audio, 1);
+    }
+
+    #[test]
+    fn can_find_attachments_under_custom_root() {
+        let root = temp_dir().join(format!("imessage-diagnostic-root-{}", process::id()));
+        create_dir_all(&root).unwrap();
+        write(root.join("photo.png"), [0u8; 10]).unwrap();
+
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(&format!(
+            "CREATE TABLE attachment (ROWID INTEGER PRIMARY KEY, filename TEXT, total_bytes INTEGER);
+             CREATE TABLE message (ROWID INTEGER PRIMARY KEY, is_audio_message INTEGER DEFAULT 0);
+             CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER);
+             INSERT INTO attachment VALUES (1, '{DEFAULT_ATTACHMENT_ROOT}/photo.png', 10);"
+        ))
+        .unwrap();
+        let db_path = Path::new("chat.db");
+
+        let actual = Attachment::diagnostic(&db, db_path, &Platform::macOS, root.to_str()).unwrap();
+        assert_eq!(actual.missing_files, 0);
+        assert_eq!(actual.bytes_on_disk, 10);
+
+        // Without the custom root, the file is looked for in the default location
+        let actual = Attachment::diagnostic(&db, db_path, &Platform::macOS, None).unwrap();
+        assert_eq!(actual.missing_files, 1);
+
+        remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn can_display_empty_attachment_diagnostic() {
+        assert_eq!(AttachmentDiagnostic::default().to_string(), "");
+    }
 }
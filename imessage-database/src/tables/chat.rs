@@ -4,22 +4,39 @@
 
 use std::collections::HashMap;
 
-use rusqlite::{Connection, Error, Result, Row, Statement};
+use plist::Value;
+use rusqlite::{Connection, DatabaseName, Error, Result, Row, Statement};
 
 use crate::{
     error::table::TableError,
-    tables::table::{Cacheable, Table, CHAT},
+    tables::table::{Cacheable, Table, CHAT, CHAT_MESSAGE_JOIN, CHAT_PROPERTIES, MESSAGE},
 };
 
+/// The key in a chat's properties that holds the GUID of its group photo attachment
+const GROUP_PHOTO_GUID: &str = "groupPhotoGuid";
+
 /// Represents a single row in the `chat` table.
 #[derive(Debug)]
 pub struct Chat {
     pub rowid: i32,
     pub chat_identifier: String,
+    /// The globally unique ID of the chat, i.e. `iMessage;+;chat123456789`
+    pub guid: Option<String>,
     /// The service the chat used, i.e. iMessage, SMS, IRC, etc.
     pub service_name: Option<String>,
     /// Optional custom name created created for the chat
     pub display_name: Option<String>,
+    /// The date the chat was last read, or `None` if the database does not store it
+    pub last_read: Option<i64>,
+}
+
+/// How much of a chat the database owner has read
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct ReadState {
+    /// The number of messages received in the chat that have not been read
+    pub unread: usize,
+    /// The date the most recently read message in the chat was read, or `None` if none were
+    pub last_read: Option<i64>,
 }
 
 impl Table for Chat {
@@ -27,8 +44,13 @@ impl Table for Chat {
         Ok(Chat {
             rowid: row.get("rowid")?,
             chat_identifier: row.get("chat_identifier")?,
+            guid: row.get("guid").unwrap_or(None),
             service_name: row.get("service_name")?,
             display_name: row.get("display_name").unwrap_or(None),
+            last_read: row
+                .get("last_read_message_timestamp")
+                .ok()
+                .filter(|date: &i64| *date != 0),
         })
     }
 
@@ -102,4 +124,150 @@ impl Chat {
             None => None,
         }
     }
+
+    /// Get a chat's plist from the `properties` BLOB column
+    ///
+    /// Calling this hits the database, so it is expensive and should
+    /// only get invoked when needed.
+    pub fn properties(&self, db: &Connection) -> Option<Value> {
+        let blob = db
+            .blob_open(
+                DatabaseName::Main,
+                CHAT,
+                CHAT_PROPERTIES,
+                self.rowid as i64,
+                true,
+            )
+            .ok()?;
+        Value::from_reader(blob).ok()
+    }
+
+    /// Get the GUID of the attachment that contains the chat's group photo, if one was set
+    ///
+    /// Calling this hits the database, so it is expensive and should
+    /// only get invoked when needed.
+    pub fn group_photo_guid(&self, db: &Connection) -> Option<String> {
+        group_photo_guid(&self.properties(db)?).map(String::from)
+    }
+
+    /// Count the unread messages in each chat and find when each chat was last read from its messages
+    ///
+    /// Only messages the database owner received count as unread; tapbacks and group events are skipped.
+    /// Chats without messages are not in the map.
+    pub fn read_states(db: &Connection) -> Result<HashMap<i32, ReadState>, TableError> {
+        let mut states = HashMap::new();
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT
+                     c.chat_id,
+                     SUM(m.is_from_me = 0 AND m.is_read = 0 AND m.item_type = 0 AND COALESCE(m.associated_message_type, 0) = 0),
+                     MAX(m.date_read)
+                 FROM {MESSAGE} as m
+                 INNER JOIN {CHAT_MESSAGE_JOIN} as c ON m.rowid = c.message_id
+                 GROUP BY c.chat_id"
+            ))
+            .map_err(TableError::Chat)?;
+
+        let rows = statement
+            .query_map([], |row| {
+                let unread: Option<i64> = row.get(1)?;
+                let last_read: Option<i64> = row.get(2)?;
+                Ok((
+                    row.get(0)?,
+                    ReadState {
+                        unread: usize::try_from(unread.unwrap_or_default()).unwrap_or_default(),
+                        last_read: last_read.filter(|date| *date != 0),
+                    },
+                ))
+            })
+            .map_err(TableError::Chat)?;
+
+        for row in rows {
+            let (chat_id, state) = row.map_err(TableError::Chat)?;
+            states.insert(chat_id, state);
+        }
+        Ok(states)
+    }
+}
+
+/// Read the group photo's attachment GUID from a chat's properties
+fn group_photo_guid(properties: &Value) -> Option<&str> {
+    properties
+        .as_dictionary()?
+        .get(GROUP_PHOTO_GUID)?
+        .as_string()
+        .filter(|guid| !guid.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use plist::{Dictionary, Value};
+    use rusqlite::Connection;
+
+    use crate::tables::chat::{group_photo_guid, Chat, ReadState};
+
+    fn properties(key: &str, value: &str) -> Value {
+        let mut dictionary = Dictionary::new();
+        dictionary.insert(key.to_string(), Value::String(value.to_string()));
+        Value::Dictionary(dictionary)
+    }
+
+    #[test]
+    fn can_get_group_photo_guid() {
+        let properties = properties(
+            "groupPhotoGuid",
+            "at_0_F0B9B4C8-0E2E-4B5C-9F2E-7F8B0D3A1C2E",
+        );
+        assert_eq!(
+            group_photo_guid(&properties),
+            Some("at_0_F0B9B4C8-0E2E-4B5C-9F2E-7F8B0D3A1C2E")
+        );
+    }
+
+    #[test]
+    fn cant_get_group_photo_guid_missing() {
+        let properties = properties("shouldForceToSMS", "false");
+        assert_eq!(group_photo_guid(&properties), None);
+    }
+
+    #[test]
+    fn cant_get_group_photo_guid_empty() {
+        let properties = properties("groupPhotoGuid", "");
+        assert_eq!(group_photo_guid(&properties), None);
+    }
+
+    #[test]
+    fn can_get_read_states() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(concat!(
+            "CREATE TABLE message (ROWID INTEGER PRIMARY KEY, is_from_me INTEGER, is_read INTEGER, item_type INTEGER, associated_message_type INTEGER, date_read INTEGER);",
+            "CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);",
+            // Chat 1: one read and one unread message, a message from the owner, and an unread tapback
+            "INSERT INTO message VALUES (1, 0, 1, 0, 0, 100), (2, 0, 0, 0, 0, 0), (3, 1, 0, 0, 0, 0), (4, 0, 0, 0, 2000, 0);",
+            // Chat 2: nothing has been read
+            "INSERT INTO message VALUES (5, 0, 0, 0, NULL, 0);",
+            "INSERT INTO chat_message_join VALUES (1, 1), (1, 2), (1, 3), (1, 4), (2, 5);",
+        ))
+        .unwrap();
+
+        let expected = HashMap::from([
+            (
+                1,
+                ReadState {
+                    unread: 1,
+                    last_read: Some(100),
+                },
+            ),
+            (
+                2,
+                ReadState {
+                    unread: 1,
+                    last_read: None,
+                },
+            ),
+        ]);
+        assert_eq!(Chat::read_states(&db).unwrap(), expected);
+    }
 }
@@ -2,14 +2,16 @@
  This module represents the chat to handle join table.
 */
 
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::{Display, Formatter},
+};
 
 use crate::{
     error::table::TableError,
     tables::table::{
         Cacheable, Deduplicate, Diagnostic, Table, CHAT_HANDLE_JOIN, CHAT_MESSAGE_JOIN,
     },
-    util::output::{done_processing, processing},
 };
 use rusqlite::{Connection, Error, Result, Row, Statement};
 
@@ -114,7 +116,9 @@ impl Deduplicate for ChatToHandle {
 }
 
 impl Diagnostic for ChatToHandle {
-    /// Emit diagnostic data for the Chat to Handle join table
+    type Data = ChatToHandleDiagnostic;
+
+    /// Collect diagnostic data for the Chat to Handle join table
     ///
     /// Get the number of chats referenced in the messages table
     /// that do not exist in this join table:
@@ -129,9 +133,7 @@ impl Diagnostic for ChatToHandle {
     /// let conn = get_connection(&db_path).unwrap();
     /// ChatToHandle::run_diagnostic(&conn);
     /// ```
-    fn run_diagnostic(db: &Connection) -> Result<(), TableError> {
-        processing();
-
+    fn diagnostic(db: &Connection) -> Result<ChatToHandleDiagnostic, TableError> {
         // Get the Chat IDs that are associated with messages
         let mut statement_message_chats = db
             .prepare(&format!("SELECT DISTINCT chat_id from {CHAT_MESSAGE_JOIN}"))
@@ -160,17 +162,34 @@ impl Diagnostic for ChatToHandle {
             }
         });
 
-        done_processing();
-
-        // Find the set difference and emit
+        // Find the set difference
         let chats_with_no_handles = unique_chats_from_messages
             .difference(&unique_chats_from_handles)
             .count();
-        if chats_with_no_handles > 0 {
-            println!("Thread diagnostic data:");
-            println!("    Chats with no handles: {chats_with_no_handles:?}");
-        }
 
+        Ok(ChatToHandleDiagnostic {
+            chats_with_no_handles,
+        })
+    }
+}
+
+/// Diagnostic data for the `chat_handle_join` table
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ChatToHandleDiagnostic {
+    /// The number of chats referenced by messages that have no handles
+    pub chats_with_no_handles: usize,
+}
+
+impl Display for ChatToHandleDiagnostic {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.chats_with_no_handles > 0 {
+            writeln!(fmt, "Thread diagnostic data:")?;
+            writeln!(
+                fmt,
+                "    Chats with no handles: {}",
+                self.chats_with_no_handles
+            )?;
+        }
         Ok(())
     }
 }
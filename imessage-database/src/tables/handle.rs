@@ -3,12 +3,14 @@
 */
 
 use rusqlite::{Connection, Error, Result, Row, Statement};
-use std::collections::{BTreeSet, HashMap};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::{Display, Formatter},
+};
 
 use crate::{
     error::table::TableError,
-    tables::table::{Cacheable, Deduplicate, Diagnostic, Table, HANDLE, ME},
-    util::output::{done_processing, processing},
+    tables::table::{Cacheable, Deduplicate, Diagnostic, Table, HANDLE, ME, MESSAGE},
 };
 
 /// Represents a single row in the `handle` table.
@@ -59,24 +61,7 @@ impl Cacheable for Handle {
     /// let chatrooms = Handle::cache(&conn);
     /// ```
     fn cache(db: &Connection) -> Result<HashMap<Self::K, Self::V>, TableError> {
-        // Create cache for user IDs
-        let mut map = HashMap::new();
-        // Handle ID 0 is self in group chats
-        map.insert(0, ME.to_string());
-
-        // Create query
-        let mut statement = Handle::get(db)?;
-
-        // Execute query to build the Handles
-        let handles = statement
-            .query_map([], |row| Ok(Handle::from_row(row)))
-            .map_err(TableError::Handle)?;
-
-        // Iterate over the handles and update the map
-        for handle in handles {
-            let contact = Handle::extract(handle)?;
-            map.insert(contact.rowid, contact.id);
-        }
+        let mut map = Handle::cache_ids(db)?;
 
         // Condense contacts that share person_centric_id so their IDs map to the same strings
         let dupe_contacts = Handle::get_person_id_map(db)?;
@@ -126,7 +111,9 @@ impl Deduplicate for Handle {
 }
 
 impl Diagnostic for Handle {
-    /// Emit diagnostic data for the Handles table
+    type Data = HandleDiagnostic;
+
+    /// Collect diagnostic data for the Handles table
     ///
     /// Get the number of handles that are duplicated
     ///
@@ -147,35 +134,97 @@ impl Diagnostic for Handle {
     /// let conn = get_connection(&db_path).unwrap();
     /// Handle::run_diagnostic(&conn);
     /// ```
-    fn run_diagnostic(db: &Connection) -> Result<(), TableError> {
+    fn diagnostic(db: &Connection) -> Result<HandleDiagnostic, TableError> {
         let query = concat!(
             "SELECT COUNT(DISTINCT person_centric_id) ",
             "FROM handle ",
             "WHERE person_centric_id NOT NULL"
         );
 
-        if let Ok(mut rows) = db.prepare(query).map_err(TableError::Handle) {
-            processing();
+        let contacts_with_multiple_ids = match db.prepare(query) {
+            Ok(mut rows) => {
+                let count_dupes: Option<i32> = rows
+                    .query_row([], |r| r.get(0))
+                    .map_err(TableError::Handle)?;
+                count_dupes.and_then(|dupes| usize::try_from(dupes).ok())
+            }
+            Err(_) => None,
+        };
 
-            let count_dupes: Option<i32> = rows
-                .query_row([], |r| r.get(0))
-                .map_err(TableError::Handle)?;
+        Ok(HandleDiagnostic {
+            contacts_with_multiple_ids,
+        })
+    }
+}
 
-            done_processing();
+/// Diagnostic data for the `handle` table
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HandleDiagnostic {
+    /// The number of contacts that have more than one ID, or `None` if the database does not store `person_centric_id`
+    pub contacts_with_multiple_ids: Option<usize>,
+}
 
-            if let Some(dupes) = count_dupes {
-                if dupes > 0 {
-                    println!("Handle diagnostic data:");
-                    println!("    Contacts with more than one ID: {dupes}");
-                }
-            }
+impl Display for HandleDiagnostic {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(dupes) = self.contacts_with_multiple_ids.filter(|dupes| *dupes > 0) {
+            writeln!(fmt, "Handle diagnostic data:")?;
+            writeln!(fmt, "    Contacts with more than one ID: {dupes}")?;
         }
-
         Ok(())
     }
 }
 
 impl Handle {
+    /// Generate a `HashMap` for looking up contacts by their IDs, without collapsing duplicate contacts
+    ///
+    /// Each handle maps to its own phone number or email address, even if the database knows it belongs
+    /// to the same person as another handle.
+    pub fn cache_ids(db: &Connection) -> Result<HashMap<i32, String>, TableError> {
+        // Create cache for user IDs
+        let mut map = HashMap::new();
+        // Handle ID 0 is self in group chats
+        map.insert(0, ME.to_string());
+
+        // Create query
+        let mut statement = Handle::get(db)?;
+
+        // Execute query to build the Handles
+        let handles = statement
+            .query_map([], |row| Ok(Handle::from_row(row)))
+            .map_err(TableError::Handle)?;
+
+        // Iterate over the handles and update the map
+        for handle in handles {
+            let contact = Handle::extract(handle)?;
+            map.insert(contact.rowid, contact.id);
+        }
+
+        Ok(map)
+    }
+
+    /// Get the `ROWID`s of the handles that belong to the database owner
+    ///
+    /// The owner's addresses are stored on each message as the `destination_caller_id`. A handle only has one of
+    /// those addresses if the owner messaged themselves, so the chats these handles are in are notes to self.
+    /// If the database does not store the `destination_caller_id`, no handles are found.
+    pub fn get_self_handles(db: &Connection) -> Result<HashSet<i32>, TableError> {
+        let mut self_handles = HashSet::new();
+        let query = format!(
+            "SELECT DISTINCT h.rowid FROM {HANDLE} h INNER JOIN (SELECT DISTINCT destination_caller_id FROM {MESSAGE}) m ON m.destination_caller_id = h.id"
+        );
+
+        if let Ok(mut statement) = db.prepare(&query) {
+            let handles = statement
+                .query_map([], |row| row.get(0))
+                .map_err(TableError::Handle)?;
+            for handle in handles {
+                self_handles.insert(handle.map_err(TableError::Handle)?);
+            }
+        }
+
+        Ok(self_handles)
+    }
+
     /// The handles table does not have a lot of information and can have many duplicate values.
     ///
     /// This method generates a hashmap of each separate item in this table to a combined string
@@ -246,8 +295,35 @@ impl Handle {
 #[cfg(test)]
 mod tests {
     use crate::tables::{handle::Handle, table::Deduplicate};
+    use rusqlite::Connection;
     use std::collections::{HashMap, HashSet};
 
+    #[test]
+    fn can_get_self_handles() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(concat!(
+            "CREATE TABLE handle (ROWID INTEGER PRIMARY KEY, id TEXT);",
+            "CREATE TABLE message (ROWID INTEGER PRIMARY KEY, destination_caller_id TEXT);",
+            "INSERT INTO handle VALUES (1, '+15555550101'), (2, '+15555550100');",
+            "INSERT INTO message VALUES (1, '+15555550100'), (2, '+15555550100'), (3, NULL);",
+        ))
+        .unwrap();
+
+        assert_eq!(Handle::get_self_handles(&db).unwrap(), HashSet::from([2]));
+    }
+
+    #[test]
+    fn can_get_no_self_handles_without_caller_id() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(concat!(
+            "CREATE TABLE handle (ROWID INTEGER PRIMARY KEY, id TEXT);",
+            "CREATE TABLE message (ROWID INTEGER PRIMARY KEY);",
+        ))
+        .unwrap();
+
+        assert!(Handle::get_self_handles(&db).unwrap().is_empty());
+    }
+
     #[test]
     fn test_can_dedupe() {
         let mut input: HashMap<i32, String> = HashMap::new();
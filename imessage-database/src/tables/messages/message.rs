@@ -2,9 +2,15 @@
  This module represents common (but not all) columns in the `message` table.
 */
 
-use std::{collections::HashMap, io::Read};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    io::Read,
+};
 
 use chrono::{offset::Local, DateTime};
+use log::warn;
 use plist::Value;
 use rusqlite::{blob::Blob, Connection, Error, Result, Row, Statement};
 
@@ -13,13 +19,18 @@ use crate::{
     message_types::{
         edited::{EditStatus, EditedMessage},
         expressives::{BubbleEffect, Expressive, ScreenEffect},
-        variants::{Announcement, BalloonProvider, CustomBalloon, Tapback, Variant},
+        text_effects::TextEffect,
+        variants::{
+            Announcement, BalloonProvider, CallDirection, CustomBalloon, SystemEvent, Tapback,
+            Variant,
+        },
     },
     tables::{
         messages::{
             body::{parse_body_legacy, parse_body_typedstream},
-            models::{BubbleComponent, Service},
+            models::{BubbleComponent, DeliveryStatus, Mention, Service},
         },
+        schema::SchemaVersion,
         table::{
             Cacheable, Diagnostic, Table, ATTRIBUTED_BODY, CHAT_MESSAGE_JOIN, MESSAGE,
             MESSAGE_ATTACHMENT_JOIN, MESSAGE_PAYLOAD, MESSAGE_SUMMARY_INFO, RECENTLY_DELETED,
@@ -27,7 +38,6 @@ use crate::{
     },
     util::{
         dates::{get_local_time, readable_diff},
-        output::{done_processing, processing},
         query_context::QueryContext,
         streamtyped,
         typedstream::{models::Archivable, parser::TypedStreamReader},
@@ -35,7 +45,7 @@ use crate::{
 };
 
 /// The required columns, interpolated into the most recent schema due to performance considerations
-const COLS: &str = "rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, chat_id";
+const COLS: &str = "rowid, guid, text, service, handle_id, destination_caller_id, subject, date, date_read, date_delivered, is_from_me, is_read, error, item_type, other_handle, share_status, share_direction, group_title, group_action_type, associated_message_guid, associated_message_type, balloon_bundle_id, expressive_send_style_id, thread_originator_guid, thread_originator_part, date_edited, service_center, was_downgraded, is_audio_message, chat_id";
 
 /// Represents a single row in the `message` table.
 #[derive(Debug)]
@@ -62,6 +72,8 @@ pub struct Message {
     pub is_from_me: bool,
     /// `true` if the message was read by the recipient, else `false`
     pub is_read: bool,
+    /// Nonzero if the message failed to send
+    pub error: i32,
     /// Intermediate data for determining the [`Variant`] of a message
     pub item_type: i32,
     /// Optional handle for the recipient of a message that includes shared content
@@ -88,6 +100,12 @@ pub struct Message {
     pub thread_originator_part: Option<String>,
     /// The date the message was most recently edited
     pub date_edited: i64,
+    /// The address of the SMS service center that relayed the message, if it was sent over SMS
+    pub service_center: Option<String>,
+    /// `true` if the message was sent over SMS because it could not be sent as an iMessage, else `false`
+    pub was_downgraded: bool,
+    /// `true` if the message is an audio message recorded in Messages, whose file is deleted when it expires unless it is kept
+    pub is_audio_message: bool,
     /// If present, this is the emoji associated with a custom emoji tapback
     pub associated_message_emoji: Option<String>,
     /// The [`identifier`](crate::tables::chat::Chat::chat_identifier) of the chat the message belongs to
@@ -119,6 +137,7 @@ impl Default for Message {
             date_delivered: 0,
             is_from_me: false,
             is_read: false,
+            error: 0,
             item_type: 0,
             other_handle: 0,
             share_status: false,
@@ -132,6 +151,9 @@ impl Default for Message {
             thread_originator_guid: None,
             thread_originator_part: None,
             date_edited: 0,
+            service_center: None,
+            was_downgraded: false,
+            is_audio_message: false,
             associated_message_emoji: None,
             chat_id: None,
             num_attachments: 0,
@@ -145,10 +167,17 @@ impl Default for Message {
 
 impl Table for Message {
     fn from_row(row: &Row) -> Result<Message> {
+        let guid: String = row.get("guid")?;
+        let text = row
+            .get_ref("text")
+            .ok()
+            .and_then(|text| text.as_bytes_or_null().ok().flatten())
+            .map(|text| decode_text(text, &guid));
+
         Ok(Message {
             rowid: row.get("rowid")?,
-            guid: row.get("guid")?,
-            text: row.get("text").unwrap_or(None),
+            guid,
+            text,
             service: row.get("service").unwrap_or(None),
             handle_id: row.get("handle_id").unwrap_or(None),
             destination_caller_id: row.get("destination_caller_id").unwrap_or(None),
@@ -158,6 +187,7 @@ impl Table for Message {
             date_delivered: row.get("date_delivered").unwrap_or(0),
             is_from_me: row.get("is_from_me")?,
             is_read: row.get("is_read")?,
+            error: row.get("error").unwrap_or(0),
             item_type: row.get("item_type").unwrap_or_default(),
             other_handle: row.get("other_handle").unwrap_or_default(),
             share_status: row.get("share_status").unwrap_or(false),
@@ -171,6 +201,9 @@ impl Table for Message {
             thread_originator_guid: row.get("thread_originator_guid").unwrap_or(None),
             thread_originator_part: row.get("thread_originator_part").unwrap_or(None),
             date_edited: row.get("date_edited").unwrap_or(0),
+            service_center: row.get("service_center").unwrap_or(None),
+            was_downgraded: row.get("was_downgraded").unwrap_or(false),
+            is_audio_message: row.get("is_audio_message").unwrap_or(false),
             associated_message_emoji: row.get("associated_message_emoji").unwrap_or(None),
             chat_id: row.get("chat_id").unwrap_or(None),
             num_attachments: row.get("num_attachments")?,
@@ -181,54 +214,12 @@ impl Table for Message {
         })
     }
 
-    /// Convert data from the messages table to native Rust data structures, falling back to
-    /// more compatible queries to ensure compatibility with older database schemas
+    /// Convert data from the messages table to native Rust data structures, using the
+    /// query variant that matches the schema of the database
     fn get(db: &Connection) -> Result<Statement, TableError> {
-        // If the database has `chat_recoverable_message_join`, we can restore some deleted messages.
-        // If database has `thread_originator_guid`, we can parse replies, otherwise default to 0
-        db.prepare(&format!(
-            // macOS Ventura+ and i0S 16+ schema, interpolated with required columns for performance
-            "SELECT
-                 {COLS},
-                 c.chat_id,
-                 (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
-                 (SELECT b.chat_id FROM {RECENTLY_DELETED} b WHERE m.ROWID = b.message_id) as deleted_from,
-                 (SELECT COUNT(*) FROM {MESSAGE} m2 WHERE m2.thread_originator_guid = m.guid) as num_replies
-             FROM
-                 message as m
-             LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
-             ORDER BY
-                 m.date;
-            "
-        )).or_else(|_| db.prepare(&format!(
-            // macOS Big Sur to Monterey, iOS 14 to iOS 15 schema
-            "SELECT
-                 *,
-                 c.chat_id,
-                 (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
-                 NULL as deleted_from,
-                 (SELECT COUNT(*) FROM {MESSAGE} m2 WHERE m2.thread_originator_guid = m.guid) as num_replies
-             FROM
-                 message as m
-             LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
-             ORDER BY
-                 m.date;
-            "
-        ))).or_else(|_| db.prepare(&format!(
-            // macOS Catalina, iOS 13 and older 
-            "SELECT
-                 *,
-                 c.chat_id,
-                 (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
-                 NULL as deleted_from,
-                 0 as num_replies
-             FROM
-                 message as m
-             LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
-             ORDER BY
-                 m.date;
-            "
-        ))).map_err(TableError::Messages)
+        let schema = SchemaVersion::detect(db)?;
+        db.prepare(&Self::select_messages(schema, ""))
+            .map_err(TableError::Messages)
     }
 
     fn extract(message: Result<Result<Self, Error>, Error>) -> Result<Self, TableError> {
@@ -240,7 +231,9 @@ impl Table for Message {
 }
 
 impl Diagnostic for Message {
-    /// Emit diagnostic data for the Messages table
+    type Data = MessageDiagnostic;
+
+    /// Collect diagnostic data for the Messages table
     ///
     /// # Example:
     ///
@@ -253,8 +246,7 @@ impl Diagnostic for Message {
     /// let conn = get_connection(&db_path).unwrap();
     /// Message::run_diagnostic(&conn);
     /// ```
-    fn run_diagnostic(db: &Connection) -> Result<(), TableError> {
-        processing();
+    fn diagnostic(db: &Connection) -> Result<MessageDiagnostic, TableError> {
         let mut messages_without_chat = db
             .prepare(&format!(
                 "
@@ -271,7 +263,7 @@ impl Diagnostic for Message {
             ))
             .map_err(TableError::Messages)?;
 
-        let num_dangling: i32 = messages_without_chat
+        let num_dangling: usize = messages_without_chat
             .query_row([], |r| r.get(0))
             .unwrap_or(0);
 
@@ -292,7 +284,7 @@ impl Diagnostic for Message {
             ))
             .map_err(TableError::Messages)?;
 
-        let messages_in_more_than_one_chat: i32 = messages_in_more_than_one_chat_q
+        let messages_in_more_than_one_chat: usize = messages_in_more_than_one_chat_q
             .query_row([], |r| r.get(0))
             .unwrap_or(0);
 
@@ -307,19 +299,44 @@ impl Diagnostic for Message {
             ))
             .map_err(TableError::Messages)?;
 
-        let total_messages: i64 = messages_count.query_row([], |r| r.get(0)).unwrap_or(0);
+        let total_messages: usize = messages_count.query_row([], |r| r.get(0)).unwrap_or(0);
 
-        done_processing();
+        Ok(MessageDiagnostic {
+            total_messages,
+            messages_without_chat: num_dangling,
+            messages_in_multiple_chats: messages_in_more_than_one_chat,
+        })
+    }
+}
 
-        println!("Message diagnostic data:");
-        println!("    Total messages: {total_messages}");
-        if num_dangling > 0 {
-            println!("    Messages not associated with a chat: {num_dangling}");
+/// Diagnostic data for the `message` table
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MessageDiagnostic {
+    /// The number of messages in the table
+    pub total_messages: usize,
+    /// The number of messages that are not associated with a chat
+    pub messages_without_chat: usize,
+    /// The number of messages that belong to more than one chat
+    pub messages_in_multiple_chats: usize,
+}
+
+impl Display for MessageDiagnostic {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(fmt, "Message diagnostic data:")?;
+        writeln!(fmt, "    Total messages: {}", self.total_messages)?;
+        if self.messages_without_chat > 0 {
+            writeln!(
+                fmt,
+                "    Messages not associated with a chat: {}",
+                self.messages_without_chat
+            )?;
         }
-        if messages_in_more_than_one_chat > 0 {
-            println!(
-                "    Messages belonging to more than one chat: {messages_in_more_than_one_chat}"
-            );
+        if self.messages_in_multiple_chats > 0 {
+            writeln!(
+                fmt,
+                "    Messages belonging to more than one chat: {}",
+                self.messages_in_multiple_chats
+            )?;
         }
         Ok(())
     }
@@ -346,18 +363,11 @@ impl Cacheable for Message {
         // Create cache for user IDs
         let mut map: HashMap<Self::K, Self::V> = HashMap::new();
 
-        // Create query, independent of table schema
-        let statement = db.prepare(&format!(
-            "SELECT 
-                 *, 
-                 c.chat_id, 
-                 (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
-                 (SELECT COUNT(*) FROM {MESSAGE} m2 WHERE m2.thread_originator_guid = m.guid) as num_replies
-             FROM 
-                 message as m 
-             LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
-             WHERE m.associated_message_guid NOT NULL
-            "
+        // Create query for the schema of the database
+        let schema = SchemaVersion::detect(db)?;
+        let statement = db.prepare(&Self::select_messages(
+            schema,
+            "WHERE m.associated_message_guid NOT NULL",
         ));
 
         if let Ok(mut statement) = statement {
@@ -396,6 +406,20 @@ impl Cacheable for Message {
     }
 }
 
+/// Decode the `text` column of a message, replacing invalid UTF-8 sequences with `U+FFFD`
+///
+/// Some legacy SMS rows contain text that is not valid UTF-8; rather than dropping the text,
+/// the readable parts are kept and the message's GUID is logged.
+pub(crate) fn decode_text(text: &[u8], guid: &str) -> String {
+    match String::from_utf8_lossy(text) {
+        Cow::Borrowed(text) => text.to_string(),
+        Cow::Owned(text) => {
+            warn!("Message {guid} contains invalid UTF-8 text; invalid bytes were replaced");
+            text
+        }
+    }
+}
+
 impl Message {
     /// Generate the text of a message, deserializing it as [`typedstream`](crate::util::typedstream) (and falling back to [`streamtyped`]) data if necessary.
     pub fn generate_text<'a>(&'a mut self, db: &'a Connection) -> Result<&'a str, MessageError> {
@@ -487,6 +511,34 @@ impl Message {
         parse_body_legacy(self)
     }
 
+    /// Get the contacts mentioned in the message's text. If the text has not been captured with [`Self::generate_text()`], the vector will be empty.
+    ///
+    /// Indexes count characters, not bytes, so they can be used by consumers that do not index strings by UTF-8 byte.
+    pub fn mentions(&self) -> Vec<Mention<'_>> {
+        let Some(text) = &self.text else {
+            return vec![];
+        };
+        let char_index = |byte_idx: usize| text.get(..byte_idx).map_or(0, |s| s.chars().count());
+
+        self.body()
+            .into_iter()
+            .filter_map(|component| match component {
+                BubbleComponent::Text(attributes) => Some(attributes),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|attribute| match attribute.effect {
+                TextEffect::Mention(handle) => Some(Mention {
+                    text: text.get(attribute.start..attribute.end)?,
+                    handle,
+                    start: char_index(attribute.start),
+                    end: char_index(attribute.end),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Calculates the date a message was written to the database.
     ///
     /// This field is stored as a unix timestamp with an epoch of `2001-01-01 00:00:00` in the local time zone
@@ -538,6 +590,27 @@ impl Message {
         None
     }
 
+    /// Determine whether a message was sent, delivered, read, or failed to send
+    ///
+    /// Messages we received have already been delivered, so they are either [`DeliveryStatus::Delivered`] or [`DeliveryStatus::Read`].
+    pub fn delivery_status(&self) -> DeliveryStatus {
+        if self.is_from_me {
+            if self.error != 0 {
+                DeliveryStatus::Failed
+            } else if self.date_read != 0 {
+                DeliveryStatus::Read
+            } else if self.date_delivered != 0 {
+                DeliveryStatus::Delivered
+            } else {
+                DeliveryStatus::Sent
+            }
+        } else if self.is_read || self.date_read != 0 {
+            DeliveryStatus::Read
+        } else {
+            DeliveryStatus::Delivered
+        }
+    }
+
     /// `true` if the message is a response to a thread, else `false`
     pub fn is_reply(&self) -> bool {
         self.thread_originator_guid.is_some()
@@ -684,6 +757,12 @@ impl Message {
                     .join(", "),
             ));
         }
+        if let Some(from_me) = context.from_me {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            filters.push_str(&format!("    m.is_from_me = {}", i32::from(from_me)));
+        }
 
         if !filters.is_empty() {
             return format!(
@@ -753,38 +832,50 @@ impl Message {
         }
 
         let filters = Self::generate_filter_statement(context);
+        let schema = SchemaVersion::detect(db)?;
+        db.prepare(&Self::select_messages(schema, &filters))
+            .map_err(TableError::Messages)
+    }
 
-        // If database has `thread_originator_guid`, we can parse replies, otherwise default to 0
-        Ok(db.prepare(&format!(
-                "SELECT
-                     *,
-                     c.chat_id,
-                     (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
-                     (SELECT b.chat_id FROM {RECENTLY_DELETED} b WHERE m.ROWID = b.message_id) as deleted_from,
-                     (SELECT COUNT(*) FROM {MESSAGE} m2 WHERE m2.thread_originator_guid = m.guid) as num_replies
-                 FROM
-                     message as m
-                 LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
-                 {filters}
-                 ORDER BY
-                     m.date;
-                "
-            ))
-            .unwrap_or(db.prepare(&format!(
-                "SELECT
-                     *,
-                     c.chat_id,
-                     (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
-                     (SELECT NULL) as deleted_from,
-                     (SELECT 0) as num_replies
-                 FROM
-                     message as m
-                 LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
-                 {filters}
-                 ORDER BY
-                     m.date;
-                "
-            )).map_err(TableError::Messages)?))
+    /// Build the query that selects messages matching `filters`, using the columns available in the `schema`
+    ///
+    /// Older schemas select every column, since `from_row` skips the ones that are missing.
+    /// Replies and recently deleted messages are omitted if the schema does not store them.
+    ///
+    /// A message that belongs to more than one chat is selected once per chat. Those rows are adjacent
+    /// and ordered by chat ID, so exporters that only write the first row use the oldest chat.
+    fn select_messages(schema: SchemaVersion, filters: &str) -> String {
+        // The most recent schema is interpolated with the required columns for performance
+        let columns = match schema {
+            SchemaVersion::Ventura => COLS,
+            SchemaVersion::BigSur | SchemaVersion::Catalina => "*",
+        };
+        let deleted_from = if schema.has_recently_deleted() {
+            format!("(SELECT b.chat_id FROM {RECENTLY_DELETED} b WHERE m.ROWID = b.message_id)")
+        } else {
+            String::from("NULL")
+        };
+        let num_replies = if schema.has_replies() {
+            format!("(SELECT COUNT(*) FROM {MESSAGE} m2 WHERE m2.thread_originator_guid = m.guid)")
+        } else {
+            String::from("0")
+        };
+
+        format!(
+            "SELECT
+                 {columns},
+                 c.chat_id,
+                 (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
+                 {deleted_from} as deleted_from,
+                 {num_replies} as num_replies
+             FROM
+                 message as m
+             LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
+             {filters}
+             ORDER BY
+                 m.date, m.ROWID, c.chat_id;
+            "
+        )
     }
 
     /// See [`Tapback`] for details on this data.
@@ -805,6 +896,16 @@ impl Message {
         None
     }
 
+    /// If the message is a sticker placed on another message, the GUID of that message
+    ///
+    /// Placed stickers are not reactions, but they use the same associated message fields to point to their target.
+    pub fn placed_on(&self) -> Option<&str> {
+        if self.is_sticker() {
+            return self.clean_associated_guid().map(|(_, guid)| guid);
+        }
+        None
+    }
+
     /// Parse the index of a tapback from it's associated GUID field
     fn tapback_index(&self) -> usize {
         match self.clean_associated_guid() {
@@ -814,30 +915,24 @@ impl Message {
     }
 
     /// Build a `HashMap` of message component index to messages that react to that component
+    ///
+    /// The `schema` is detected once with [`SchemaVersion::detect()`], since this is called for every message.
     pub fn get_tapbacks(
         &self,
         db: &Connection,
+        schema: SchemaVersion,
         tapbacks: &HashMap<String, Vec<String>>,
     ) -> Result<HashMap<usize, Vec<Self>>, TableError> {
         let mut out_h: HashMap<usize, Vec<Self>> = HashMap::new();
         if let Some(rxs) = tapbacks.get(&self.guid) {
             let filter: Vec<String> = rxs.iter().map(|guid| format!("\"{guid}\"")).collect();
             // Create query
-            let mut statement = db.prepare(&format!(
-                "SELECT 
-                        *, 
-                        c.chat_id, 
-                        (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
-                        (SELECT COUNT(*) FROM {MESSAGE} m2 WHERE m2.thread_originator_guid = m.guid) as num_replies
-                    FROM 
-                        message as m 
-                    LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id
-                    WHERE m.guid IN ({})
-                    ORDER BY 
-                        m.date;
-                    ",
-                filter.join(",")
-            )).map_err(TableError::Messages)?;
+            let mut statement = db
+                .prepare(&Self::select_messages(
+                    schema,
+                    &format!("WHERE m.guid IN ({})", filter.join(",")),
+                ))
+                .map_err(TableError::Messages)?;
 
             // Execute query to build the Handles
             let messages = statement
@@ -860,26 +955,23 @@ impl Message {
     }
 
     /// Build a `HashMap` of message component index to messages that reply to that component
-    pub fn get_replies(&self, db: &Connection) -> Result<HashMap<usize, Vec<Self>>, TableError> {
+    ///
+    /// The `schema` is detected once with [`SchemaVersion::detect()`], since this is called for every message.
+    pub fn get_replies(
+        &self,
+        db: &Connection,
+        schema: SchemaVersion,
+    ) -> Result<HashMap<usize, Vec<Self>>, TableError> {
         let mut out_h: HashMap<usize, Vec<Self>> = HashMap::new();
 
         // No need to hit the DB if we know we don't have replies
         if self.has_replies() {
-            let mut statement = db.prepare(&format!(
-                "SELECT 
-                     *, 
-                     c.chat_id, 
-                     (SELECT COUNT(*) FROM {MESSAGE_ATTACHMENT_JOIN} a WHERE m.ROWID = a.message_id) as num_attachments,
-                     (SELECT COUNT(*) FROM {MESSAGE} m2 WHERE m2.thread_originator_guid = m.guid) as num_replies
-                 FROM 
-                     message as m 
-                 LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id 
-                 WHERE m.thread_originator_guid = \"{}\"
-                 ORDER BY 
-                     m.date;
-                ", self.guid
-            ))
-            .map_err(TableError::Messages)?;
+            let mut statement = db
+                .prepare(&Self::select_messages(
+                    schema,
+                    &format!("WHERE m.thread_originator_guid = \"{}\"", self.guid),
+                ))
+                .map_err(TableError::Messages)?;
 
             let iter = statement
                 .query_map([], |row| Ok(Message::from_row(row)))
@@ -900,6 +992,33 @@ impl Message {
         Ok(out_h)
     }
 
+    /// Get the message a reply responded to, if it is in the same chat as the reply
+    ///
+    /// Messages that are not replies, or whose original message was deleted, return `None`.
+    /// The `schema` is detected once with [`SchemaVersion::detect()`], since this is called for every message.
+    pub fn get_thread_originator(
+        &self,
+        db: &Connection,
+        schema: SchemaVersion,
+    ) -> Result<Option<Self>, TableError> {
+        let Some(originator_guid) = &self.thread_originator_guid else {
+            return Ok(None);
+        };
+        let mut filters = format!("WHERE m.guid = \"{originator_guid}\"");
+        if let Some(chat_id) = self.chat_id {
+            filters.push_str(&format!(" AND c.chat_id = {chat_id}"));
+        }
+
+        let mut statement = db
+            .prepare(&Self::select_messages(schema, &filters))
+            .map_err(TableError::Messages)?;
+        let mut iter = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(TableError::Messages)?;
+
+        iter.next().map(Self::extract).transpose()
+    }
+
     /// Parse the App's Bundle ID out of the Balloon's Bundle ID
     ///
     /// For example, a Bundle ID like `com.apple.messages.MSMessageExtensionBalloonPlugin:0000000000:com.apple.SafetyMonitorApp.SafetyMonitorMessages`
@@ -1018,6 +1137,24 @@ impl Message {
         }
     }
 
+    /// Determine the system event a message records, if it records one
+    ///
+    /// Messages with an `item_type` that is not otherwise handled, and that have no text or attachments,
+    /// are [`SystemEvent::Unknown`] so they are not exported as blank messages.
+    pub fn get_system_event(&self) -> Option<SystemEvent> {
+        match self.item_type {
+            // Normal messages and shared locations
+            0 | 4 => None,
+            6 => Some(SystemEvent::FaceTime(if self.is_from_me() {
+                CallDirection::Outgoing
+            } else {
+                CallDirection::Incoming
+            })),
+            _ if self.is_announcement() || self.text.is_some() || self.has_attachments() => None,
+            other => Some(SystemEvent::Unknown(other)),
+        }
+    }
+
     /// Determine the service the message was sent from, i.e. iMessage, SMS, IRC, etc.
     pub fn service(&self) -> Service {
         Service::from(self.service.as_deref())
@@ -1147,6 +1284,7 @@ impl Message {
             date_delivered: i64::default(),
             is_from_me: false,
             is_read: false,
+            error: 0,
             item_type: 0,
             other_handle: 0,
             share_status: false,
@@ -1160,6 +1298,9 @@ impl Message {
             thread_originator_guid: None,
             thread_originator_part: None,
             date_edited: 0,
+            service_center: None,
+            was_downgraded: false,
+            is_audio_message: false,
             associated_message_emoji: None,
             chat_id: None,
             num_attachments: 0,
@@ -71,6 +71,30 @@ impl Display for Service<'_> {
     }
 }
 
+/// Defines how far a [`Message`](crate::tables::messages::Message) got on its way to the recipient.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DeliveryStatus {
+    /// The message was sent, but not yet delivered
+    Sent,
+    /// The message reached the recipient's device
+    Delivered,
+    /// The recipient read the message
+    Read,
+    /// The message could not be sent
+    Failed,
+}
+
+impl Display for DeliveryStatus {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            DeliveryStatus::Sent => write!(fmt, "sent"),
+            DeliveryStatus::Delivered => write!(fmt, "delivered"),
+            DeliveryStatus::Read => write!(fmt, "read"),
+            DeliveryStatus::Failed => write!(fmt, "failed"),
+        }
+    }
+}
+
 /// Defines ranges of text and associated attributes parsed from [`typedstream`](crate::util::typedstream) `attributedBody` data.
 ///
 /// Ranges specify locations attributes applied to specific portions of a [`Message`](crate::tables::messages::Message)'s [`text`](crate::tables::messages::Message::text). For example, given message text with a [`Mention`](TextEffect::Mention) like:
@@ -107,6 +131,19 @@ impl<'a> TextAttributes<'a> {
     }
 }
 
+/// A contact [mentioned](https://support.apple.com/guide/messages/mention-a-person-icht306ee34b/mac) in the text of a message
+#[derive(Debug, PartialEq, Eq)]
+pub struct Mention<'a> {
+    /// The text of the mention as it appears in the message, generally the contact's name
+    pub text: &'a str,
+    /// The handle the mention refers to, i.e. a phone number or email address
+    pub handle: &'a str,
+    /// The index of the first character of the mention in the message text
+    pub start: usize,
+    /// The index of the character after the end of the mention in the message text
+    pub end: usize,
+}
+
 /// Representation of attachment metadata used for rendering message body in a conversation feed.
 #[derive(Debug, PartialEq, Default)]
 pub struct AttachmentMeta<'a> {
@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod tests {
+    use crate::tables::messages::{models::DeliveryStatus, Message};
+
+    #[test]
+    fn can_get_delivery_status_sent() {
+        let mut message = Message::blank();
+        message.is_from_me = true;
+
+        assert_eq!(message.delivery_status(), DeliveryStatus::Sent);
+    }
+
+    #[test]
+    fn can_get_delivery_status_delivered() {
+        let mut message = Message::blank();
+        message.is_from_me = true;
+        // May 17, 2022  8:29:42 PM
+        message.date_delivered = 674526582885055488;
+
+        assert_eq!(message.delivery_status(), DeliveryStatus::Delivered);
+    }
+
+    #[test]
+    fn can_get_delivery_status_read() {
+        let mut message = Message::blank();
+        message.is_from_me = true;
+        // May 17, 2022  8:29:42 PM
+        message.date_delivered = 674526582885055488;
+        // May 17, 2022  9:30:31 PM
+        message.date_read = 674530231992568192;
+
+        assert_eq!(message.delivery_status(), DeliveryStatus::Read);
+    }
+
+    #[test]
+    fn can_get_delivery_status_failed() {
+        let mut message = Message::blank();
+        message.is_from_me = true;
+        message.error = 22;
+
+        assert_eq!(message.delivery_status(), DeliveryStatus::Failed);
+    }
+
+    #[test]
+    fn can_get_delivery_status_received() {
+        let mut message = Message::blank();
+
+        assert_eq!(message.delivery_status(), DeliveryStatus::Delivered);
+
+        message.is_read = true;
+        assert_eq!(message.delivery_status(), DeliveryStatus::Read);
+    }
+}
@@ -58,4 +58,30 @@ mod tests {
 
         assert_eq!(None, m.clean_associated_guid());
     }
+
+    #[test]
+    fn can_get_placed_on() {
+        let mut m = Message::blank();
+        m.associated_message_type = Some(1000);
+        m.associated_message_guid = Some("p:1/A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+
+        assert_eq!(Some("A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A"), m.placed_on());
+    }
+
+    #[test]
+    fn cant_get_placed_on_tapback() {
+        let mut m = Message::blank();
+        m.associated_message_type = Some(2000);
+        m.associated_message_guid = Some("p:1/A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+
+        assert_eq!(None, m.placed_on());
+    }
+
+    #[test]
+    fn cant_get_placed_on_sticker_message() {
+        let mut m = Message::blank();
+        m.associated_message_type = Some(1000);
+
+        assert_eq!(None, m.placed_on());
+    }
 }
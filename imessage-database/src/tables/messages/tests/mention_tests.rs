@@ -0,0 +1,64 @@
+#[cfg(test)]
+mod tests {
+    use std::{env::current_dir, fs::File, io::Read};
+
+    use crate::{
+        tables::messages::{models::Mention, Message},
+        util::typedstream::parser::TypedStreamReader,
+    };
+
+    fn mention_message(text: &str) -> Message {
+        let mut message = Message::blank();
+        message.text = Some(text.to_string());
+
+        let typedstream_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/typedstream/Mention");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        message.components = parser.parse().ok();
+        message
+    }
+
+    #[test]
+    fn can_get_mentions() {
+        let message = mention_message("Test Dad ");
+
+        assert_eq!(
+            message.mentions(),
+            vec![Mention {
+                text: "Dad",
+                handle: "+15558675309",
+                start: 5,
+                end: 8,
+            }]
+        );
+    }
+
+    #[test]
+    fn can_get_mentions_multibyte() {
+        // The ranges are the same, but the text before the mention is two bytes per character
+        let message = mention_message("Tést Dåd ");
+
+        assert_eq!(
+            message.mentions(),
+            vec![Mention {
+                text: "Dåd",
+                handle: "+15558675309",
+                start: 5,
+                end: 8,
+            }]
+        );
+    }
+
+    #[test]
+    fn cant_get_mentions_no_text() {
+        let message = Message::blank();
+
+        assert!(message.mentions().is_empty());
+    }
+}
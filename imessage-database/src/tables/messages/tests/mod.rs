@@ -1,6 +1,10 @@
 mod bundle_id_tests;
 mod date_tests;
+mod delivery_tests;
 mod edited_tests;
 mod expressive_tests;
 mod guid_tests;
+mod mention_tests;
 mod query_tests;
+mod system_event_tests;
+mod text_tests;
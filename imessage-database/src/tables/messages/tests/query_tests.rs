@@ -2,7 +2,16 @@
 mod tests {
     use std::{collections::BTreeSet, env::set_var};
 
-    use crate::{tables::messages::Message, util::query_context::QueryContext};
+    use rusqlite::Connection;
+
+    use crate::{
+        tables::{
+            messages::Message,
+            schema::SchemaVersion,
+            table::{Cacheable, Table},
+        },
+        util::query_context::QueryContext,
+    };
 
     #[test]
     fn can_generate_filter_statement_empty() {
@@ -73,6 +82,28 @@ mod tests {
         assert_eq!(statement, " WHERE\n                     m.date >= 599558400000000000 AND     m.date <= 602323200000000000 AND     c.chat_id IN (1, 2, 3)")
     }
 
+    #[test]
+    fn can_generate_filter_statement_from_me() {
+        let mut context = QueryContext::default();
+        context.set_from_me(true);
+
+        let statement = Message::generate_filter_statement(&context);
+        assert_eq!(statement, " WHERE\n                     m.is_from_me = 1")
+    }
+
+    #[test]
+    fn can_generate_filter_statement_chat_ids_not_from_me() {
+        let mut context = QueryContext::default();
+        context.set_selected_chat_ids(BTreeSet::from([1, 2, 3]));
+        context.set_from_me(false);
+
+        let statement = Message::generate_filter_statement(&context);
+        assert_eq!(
+            statement,
+            " WHERE\n                     c.chat_id IN (1, 2, 3) AND     m.is_from_me = 0"
+        )
+    }
+
     #[test]
     fn can_create_invalid_start() {
         let mut context = QueryContext::default();
@@ -92,4 +123,120 @@ mod tests {
         let statement = Message::generate_filter_statement(&context);
         assert_eq!(statement, "");
     }
+
+    /// Create an in-memory database with the tables used to query messages on macOS Catalina
+    fn fake_catalina_db() -> Connection {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE message (
+                 ROWID INTEGER PRIMARY KEY,
+                 guid TEXT,
+                 text TEXT,
+                 date INTEGER,
+                 is_from_me INTEGER,
+                 is_read INTEGER,
+                 associated_message_guid TEXT,
+                 associated_message_type INTEGER
+             );
+             CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);
+             CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER);
+             INSERT INTO message VALUES (1, 'A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A', 'Hello', 1, 0, 1, NULL, 0);
+             INSERT INTO message VALUES (2, 'B44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A', NULL, 2, 1, 1, 'p:0/A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A', 2000);
+             INSERT INTO chat_message_join VALUES (1, 1), (1, 2);",
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn can_stream_rows_catalina() {
+        let db = fake_catalina_db();
+        let mut context = QueryContext::default();
+        context.set_selected_chat_ids(BTreeSet::from([1]));
+
+        let mut statement = Message::stream_rows(&db, &context).unwrap();
+        let messages: Vec<Message> = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .unwrap()
+            .map(|message| Message::extract(message).unwrap())
+            .collect();
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].num_replies, 0);
+        assert_eq!(messages[0].deleted_from, None);
+        assert_eq!(messages[0].chat_id, Some(1));
+    }
+
+    /// Create an in-memory database with a reply to a message, on macOS Big Sur
+    fn fake_big_sur_db() -> Connection {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE message (
+                 ROWID INTEGER PRIMARY KEY,
+                 guid TEXT,
+                 text TEXT,
+                 date INTEGER,
+                 is_from_me INTEGER,
+                 is_read INTEGER,
+                 thread_originator_guid TEXT,
+                 thread_originator_part TEXT
+             );
+             CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);
+             CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER);
+             INSERT INTO message VALUES (1, 'A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A', 'Hello', 1, 0, 1, NULL, NULL);
+             INSERT INTO message VALUES (2, 'B44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A', 'Hi!', 2, 1, 1, 'A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A', '0:0:5');
+             INSERT INTO chat_message_join VALUES (1, 1), (1, 2);",
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn can_get_thread_originator() {
+        let db = fake_big_sur_db();
+        let mut reply = Message::blank();
+        reply.thread_originator_guid = Some("A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+        reply.chat_id = Some(1);
+
+        let originator = reply
+            .get_thread_originator(&db, SchemaVersion::BigSur)
+            .unwrap()
+            .unwrap();
+        assert_eq!(originator.rowid, 1);
+        assert_eq!(originator.text.as_deref(), Some("Hello"));
+    }
+
+    #[test]
+    fn cant_get_thread_originator_from_other_chat() {
+        let db = fake_big_sur_db();
+        let mut reply = Message::blank();
+        reply.thread_originator_guid = Some("A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+        reply.chat_id = Some(2);
+
+        assert!(reply
+            .get_thread_originator(&db, SchemaVersion::BigSur)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn cant_get_thread_originator_not_reply() {
+        let db = fake_big_sur_db();
+
+        assert!(Message::blank()
+            .get_thread_originator(&db, SchemaVersion::BigSur)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn can_cache_tapbacks_catalina() {
+        let db = fake_catalina_db();
+
+        let tapbacks = Message::cache(&db).unwrap();
+        assert_eq!(
+            tapbacks["A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A"][&0][0].rowid,
+            2
+        );
+    }
 }
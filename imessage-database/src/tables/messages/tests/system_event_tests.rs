@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        message_types::variants::{CallDirection, SystemEvent},
+        tables::messages::Message,
+    };
+
+    #[test]
+    fn can_get_system_event_facetime_outgoing() {
+        let mut message = Message::blank();
+        message.item_type = 6;
+        message.is_from_me = true;
+
+        let event = message.get_system_event().unwrap();
+        assert_eq!(event, SystemEvent::FaceTime(CallDirection::Outgoing));
+        assert_eq!(event.to_string(), "FaceTime call, outgoing");
+    }
+
+    #[test]
+    fn can_get_system_event_facetime_incoming() {
+        let mut message = Message::blank();
+        message.item_type = 6;
+
+        assert_eq!(
+            message.get_system_event(),
+            Some(SystemEvent::FaceTime(CallDirection::Incoming))
+        );
+    }
+
+    #[test]
+    fn can_get_system_event_unknown() {
+        let mut message = Message::blank();
+        message.item_type = 3;
+
+        let event = message.get_system_event().unwrap();
+        assert_eq!(event, SystemEvent::Unknown(3));
+        assert_eq!(event.to_string(), "Unsupported system event (type 3)");
+    }
+
+    #[test]
+    fn cant_get_system_event_normal() {
+        let message = Message::blank();
+        assert_eq!(message.get_system_event(), None);
+    }
+
+    #[test]
+    fn cant_get_system_event_shared_location() {
+        let mut message = Message::blank();
+        message.item_type = 4;
+        assert_eq!(message.get_system_event(), None);
+    }
+
+    #[test]
+    fn cant_get_system_event_announcement() {
+        let mut message = Message::blank();
+        message.item_type = 2;
+        message.group_title = Some("Book Club".to_string());
+        assert_eq!(message.get_system_event(), None);
+    }
+}
@@ -0,0 +1,32 @@
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::tables::messages::message::decode_text;
+
+    #[test]
+    fn can_decode_valid_text() {
+        assert_eq!(decode_text("Hello 🌍".as_bytes(), "GUID"), "Hello 🌍");
+    }
+
+    #[test]
+    fn can_decode_malformed_text() {
+        // A truncated 4-byte sequence followed by a lone continuation byte
+        let text = b"Hello \xF0\x9F\x8C world\x80!";
+        assert_eq!(decode_text(text, "GUID"), "Hello \u{FFFD} world\u{FFFD}!");
+    }
+
+    #[test]
+    fn can_decode_malformed_text_column() {
+        let db = Connection::open_in_memory().unwrap();
+        let text: Vec<u8> = db
+            .query_row("SELECT CAST(X'4869FF21' AS TEXT)", [], |row| {
+                Ok(row.get_ref(0)?.as_bytes()?.to_vec())
+            })
+            .unwrap();
+
+        // SQLite stores the malformed text as-is, so it cannot be read as a `String`
+        assert!(String::from_utf8(text.clone()).is_err());
+        assert_eq!(decode_text(&text, "GUID"), "Hi\u{FFFD}!");
+    }
+}
@@ -0,0 +1,152 @@
+/*!
+ This module detects which version of the iMessage database schema a database uses.
+
+ Apple adds tables and columns with new versions of macOS and iOS, so queries that read newer data
+ must use simpler variants on older databases.
+*/
+
+use std::fmt::Display;
+
+use rusqlite::Connection;
+
+use crate::{
+    error::table::TableError,
+    tables::table::{MESSAGE, RECENTLY_DELETED},
+};
+
+/// The column that links a reply to the message that started its thread
+const THREAD_ORIGINATOR_GUID: &str = "thread_originator_guid";
+
+/// Represents the versions of the iMessage database schema, from oldest to newest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SchemaVersion {
+    /// macOS Catalina, iOS 13 and older, which do not store replies
+    Catalina,
+    /// macOS Big Sur to Monterey, iOS 14 to iOS 15, which do not store recently deleted messages
+    BigSur,
+    /// macOS Ventura+ and iOS 16+
+    Ventura,
+}
+
+impl SchemaVersion {
+    /// Determine the schema version of a database from the tables and columns it contains
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::{schema::SchemaVersion, table::get_connection};
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let schema = SchemaVersion::detect(&conn);
+    /// ```
+    pub fn detect(db: &Connection) -> Result<Self, TableError> {
+        if !has_column(db, MESSAGE, THREAD_ORIGINATOR_GUID)? {
+            return Ok(SchemaVersion::Catalina);
+        }
+        if !has_table(db, RECENTLY_DELETED)? {
+            return Ok(SchemaVersion::BigSur);
+        }
+        Ok(SchemaVersion::Ventura)
+    }
+
+    /// `true` if the schema stores threaded replies, else `false`
+    pub fn has_replies(&self) -> bool {
+        *self >= SchemaVersion::BigSur
+    }
+
+    /// `true` if the schema stores recently deleted messages, else `false`
+    pub fn has_recently_deleted(&self) -> bool {
+        *self >= SchemaVersion::Ventura
+    }
+}
+
+impl Display for SchemaVersion {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaVersion::Catalina => write!(fmt, "macOS Catalina or iOS 13 and older"),
+            SchemaVersion::BigSur => write!(fmt, "macOS Big Sur to Monterey or iOS 14 to iOS 15"),
+            SchemaVersion::Ventura => write!(fmt, "macOS Ventura+ or iOS 16+"),
+        }
+    }
+}
+
+/// Get the `user_version` stored in the header of the database
+pub fn get_user_version(db: &Connection) -> Result<i64, TableError> {
+    db.query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(TableError::Schema)
+}
+
+/// `true` if the database contains the table, else `false`
+fn has_table(db: &Connection, table: &str) -> Result<bool, TableError> {
+    db.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [table],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+    .map_err(TableError::Schema)
+}
+
+/// `true` if the table contains the column, else `false`
+fn has_column(db: &Connection, table: &str, column: &str) -> Result<bool, TableError> {
+    db.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+        [table, column],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+    .map_err(TableError::Schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::tables::schema::{get_user_version, SchemaVersion};
+
+    /// Create an in-memory database with the given tables
+    fn fake_db(schema: &str) -> Connection {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(schema).unwrap();
+        db
+    }
+
+    #[test]
+    fn can_detect_catalina() {
+        let db = fake_db("CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT);");
+        assert_eq!(SchemaVersion::detect(&db).unwrap(), SchemaVersion::Catalina);
+    }
+
+    #[test]
+    fn can_detect_big_sur() {
+        let db = fake_db(
+            "CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT, thread_originator_guid TEXT);",
+        );
+        assert_eq!(SchemaVersion::detect(&db).unwrap(), SchemaVersion::BigSur);
+    }
+
+    #[test]
+    fn can_detect_ventura() {
+        let db = fake_db(
+            "CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT, thread_originator_guid TEXT);
+             CREATE TABLE chat_recoverable_message_join (chat_id INTEGER, message_id INTEGER);",
+        );
+        assert_eq!(SchemaVersion::detect(&db).unwrap(), SchemaVersion::Ventura);
+    }
+
+    #[test]
+    fn can_compare_features() {
+        assert!(!SchemaVersion::Catalina.has_replies());
+        assert!(SchemaVersion::BigSur.has_replies());
+        assert!(!SchemaVersion::BigSur.has_recently_deleted());
+        assert!(SchemaVersion::Ventura.has_recently_deleted());
+    }
+
+    #[test]
+    fn can_get_user_version() {
+        let db = fake_db("PRAGMA user_version = 16000;");
+        assert_eq!(get_user_version(&db).unwrap(), 16000);
+    }
+}
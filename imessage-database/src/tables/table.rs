@@ -2,11 +2,21 @@
  This module defines traits for table representations and stores some shared table constants.
 */
 
-use std::{collections::HashMap, fs::metadata, path::Path};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::{create_dir_all, metadata, remove_file, File},
+    io::Read,
+    iter::once,
+    path::{Path, PathBuf},
+};
 
 use rusqlite::{Connection, Error, OpenFlags, Result, Row, Statement};
 
-use crate::error::table::TableError;
+use crate::{
+    error::table::TableError,
+    util::output::{done_processing, processing},
+};
 
 /// Defines behavior for SQL Table data
 pub trait Table {
@@ -38,11 +48,82 @@ pub trait Deduplicate {
 
 /// Defines behavior for printing diagnostic information for a table
 pub trait Diagnostic {
+    /// The diagnostic data collected about the table, which displays as the text [`Diagnostic::run_diagnostic`] prints
+    type Data: Display;
+
+    /// Collect diagnostic data about the table without printing anything
+    fn diagnostic(db: &Connection) -> Result<Self::Data, TableError>;
+
     /// Emit diagnostic data about the table to `stdout`
-    fn run_diagnostic(db: &Connection) -> Result<(), TableError>;
+    fn run_diagnostic(db: &Connection) -> Result<(), TableError> {
+        processing();
+        let data = Self::diagnostic(db)?;
+        done_processing();
+        print!("{data}");
+        Ok(())
+    }
+}
+
+/// Represents how the iMessage `SQLite` database is opened; it is never opened for writing
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseAccess {
+    /// Open the database with `mode=ro`
+    ///
+    /// Messages that are still in the write-ahead log (`chat.db-wal`) are read, but the database
+    /// cannot be read while another process holds an exclusive lock on it.
+    #[default]
+    ReadOnly,
+    /// Open the database with `mode=ro&immutable=1`
+    ///
+    /// `SQLite` does not take locks or read the write-ahead log, so the database can be read while
+    /// Messages is using it. Messages that are only in `chat.db-wal` are missing, and if the database
+    /// changes while it is being read, queries may return errors or incorrect results.
+    Immutable,
+}
+
+impl DatabaseAccess {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(access: &str) -> Option<Self> {
+        match access.to_lowercase().as_str() {
+            "ro" => Some(Self::ReadOnly),
+            "immutable" => Some(Self::Immutable),
+            _ => None,
+        }
+    }
+}
+
+impl Display for DatabaseAccess {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DatabaseAccess::ReadOnly => write!(fmt, "ro"),
+            DatabaseAccess::Immutable => write!(fmt, "immutable"),
+        }
+    }
 }
 
-/// Get a connection to the iMessage `SQLite` database
+/// Build the `SQLite` URI that opens a database as immutable
+///
+/// Characters that `SQLite` reads as URI delimiters are escaped, and Windows paths get the leading
+/// slash `SQLite` expects before a drive letter.
+fn immutable_uri(path: &Path) -> String {
+    let mut uri = String::from("file:");
+    let path = path.to_string_lossy().replace('\\', "/");
+    if !path.starts_with('/') && Path::new(&path).is_absolute() {
+        uri.push('/');
+    }
+    for character in path.chars() {
+        match character {
+            '%' => uri.push_str("%25"),
+            '?' => uri.push_str("%3f"),
+            '#' => uri.push_str("%23"),
+            _ => uri.push(character),
+        }
+    }
+    uri.push_str("?mode=ro&immutable=1");
+    uri
+}
+
+/// Get a read-only connection to the iMessage `SQLite` database
 // # Example:
 ///
 /// ```
@@ -55,8 +136,36 @@ pub trait Diagnostic {
 /// let connection = get_connection(&db_path);
 /// ```
 pub fn get_connection(path: &Path) -> Result<Connection, TableError> {
+    get_connection_with_access(path, DatabaseAccess::ReadOnly)
+}
+
+/// Get a connection to the iMessage `SQLite` database, opened as described by [`DatabaseAccess`]
+// # Example:
+///
+/// ```
+/// use imessage_database::{
+///     util::dirs::default_db_path,
+///     tables::table::{get_connection_with_access, DatabaseAccess}
+/// };
+///
+/// let db_path = default_db_path();
+/// let connection = get_connection_with_access(&db_path, DatabaseAccess::Immutable);
+/// ```
+pub fn get_connection_with_access(
+    path: &Path,
+    access: DatabaseAccess,
+) -> Result<Connection, TableError> {
     if path.exists() && path.is_file() {
-        return match Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY) {
+        let connection = match access {
+            DatabaseAccess::ReadOnly => {
+                Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            }
+            DatabaseAccess::Immutable => Connection::open_with_flags(
+                immutable_uri(path),
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI,
+            ),
+        };
+        return match connection {
             Ok(res) => Ok(res),
             Err(why) => Err(
                 TableError::CannotConnect(
@@ -80,6 +189,57 @@ pub fn get_connection(path: &Path) -> Result<Connection, TableError> {
     )))
 }
 
+/// Copy the iMessage `SQLite` database into `destination`, returning the path to the copy
+///
+/// The copy is written by `SQLite` with `VACUUM INTO` in a single read transaction, so it is consistent even
+/// if Messages writes to the database while it is copied. Messages that `SQLite` has not yet moved from
+/// `chat.db-wal` into the database are included, and the export does not hold locks on the database
+/// Messages is using.
+// # Example:
+///
+/// ```
+/// use std::env::temp_dir;
+/// use imessage_database::{
+///     util::dirs::default_db_path,
+///     tables::table::snapshot_database
+/// };
+///
+/// let db_path = default_db_path();
+/// let snapshot = snapshot_database(&db_path, &temp_dir().join("imessage-snapshot"));
+/// ```
+pub fn snapshot_database(path: &Path, destination: &Path) -> Result<PathBuf, TableError> {
+    let Some(file_name) = path.file_name().filter(|_| path.is_file()) else {
+        return Err(TableError::CannotConnect(format!(
+            "Database not found at {}",
+            &path.to_str().unwrap_or("Unknown")
+        )));
+    };
+    create_dir_all(destination).map_err(TableError::CannotRead)?;
+
+    let snapshot = destination.join(file_name);
+    // `VACUUM INTO` does not replace an existing database, and a log left over from an earlier copy would be applied to this one
+    for stale in once("")
+        .chain(WAL_SUFFIXES)
+        .map(|suffix| with_suffix(&snapshot, suffix))
+    {
+        if stale.exists() {
+            remove_file(&stale).map_err(TableError::CannotRead)?;
+        }
+    }
+
+    get_connection(path)?
+        .execute("VACUUM INTO ?1", [snapshot.to_string_lossy()])
+        .map_err(|why| TableError::CannotConnect(format!("Unable to copy chat database: {why}")))?;
+    Ok(snapshot)
+}
+
+/// Append `suffix` to the file name at the end of `path`, i.e. `chat.db` to `chat.db-wal`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
 /// Get the size of the database on the disk
 // # Example:
 ///
@@ -96,6 +256,29 @@ pub fn get_db_size(path: &Path) -> Result<u64, TableError> {
     Ok(metadata(path).map_err(TableError::CannotRead)?.len())
 }
 
+/// Get the size of the database and its write-ahead log files, i.e. the most space [`snapshot_database()`] needs
+pub fn get_snapshot_size(path: &Path) -> Result<u64, TableError> {
+    WAL_SUFFIXES
+        .iter()
+        .map(|suffix| with_suffix(path, suffix))
+        .filter(|sidecar| sidecar.is_file())
+        .try_fold(get_db_size(path)?, |total, sidecar| {
+            Ok(total + get_db_size(&sidecar)?)
+        })
+}
+
+/// `true` if the file at `path` is not a plain `SQLite` database, i.e. because it is encrypted
+///
+/// Every `SQLite` database starts with the same header, but encrypted databases, like `SQLCipher` databases
+/// or the message database in an encrypted iOS backup, do not. Files that are too short to have a header
+/// or cannot be read are not flagged, since opening them reports why.
+pub fn is_encrypted(path: &Path) -> bool {
+    let mut header = [0; SQLITE_HEADER.len()];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|()| &header != SQLITE_HEADER)
+}
+
 // Table Names
 /// Handle table name
 pub const HANDLE: &str = "handle";
@@ -121,6 +304,8 @@ pub const MESSAGE_PAYLOAD: &str = "payload_data";
 pub const MESSAGE_SUMMARY_INFO: &str = "message_summary_info";
 /// The attributedBody column contains a message's body text with any other attributes
 pub const ATTRIBUTED_BODY: &str = "attributedBody";
+/// The properties column contains chat settings, like the group photo
+pub const CHAT_PROPERTIES: &str = "properties";
 
 // Default information
 /// Name used for messages sent by the database owner in a first-person context
@@ -129,10 +314,16 @@ pub const ME: &str = "Me";
 pub const YOU: &str = "You";
 /// Name used for contacts or chats where the name cannot be discovered
 pub const UNKNOWN: &str = "Unknown";
+/// Name used for a conversation the database owner has with themselves
+pub const NOTES_TO_SELF: &str = "Notes to Self";
 /// Default location for the Messages database on macOS
 pub const DEFAULT_PATH_MACOS: &str = "Library/Messages/chat.db";
 /// Default location for the Messages database in an unencrypted iOS backup
 pub const DEFAULT_PATH_IOS: &str = "3d/3d0d7e5fb2ce288813306e4d4636395e047a3d28";
+/// The first bytes of every unencrypted `SQLite` database
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+/// Suffixes of the files `SQLite` keeps beside a database that uses a write-ahead log
+const WAL_SUFFIXES: [&str; 2] = ["-wal", "-shm"];
 /// Chat name reserved for messages that do not belong to a chat in the table
 pub const ORPHANED: &str = "orphaned";
 /// Maximum length a filename can be
@@ -141,3 +332,158 @@ pub const MAX_LENGTH: usize = 235;
 pub const FITNESS_RECEIVER: &str = "$(kIMTranscriptPluginBreadcrumbTextReceiverIdentifier)";
 /// Name for attachments directory in exports
 pub const ATTACHMENTS_DIR: &str = "attachments";
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, write},
+        path::Path,
+    };
+
+    use rusqlite::Connection;
+
+    use crate::tables::{
+        chat::Chat,
+        table::{
+            get_connection_with_access, get_db_size, get_snapshot_size, immutable_uri,
+            is_encrypted, snapshot_database, with_suffix, Cacheable, DatabaseAccess, Table, CHAT,
+        },
+    };
+
+    /// Write a database with a single chat to `dir`, returning its path
+    fn fake_db(dir: &Path) -> std::path::PathBuf {
+        let _ = remove_dir_all(dir);
+        create_dir_all(dir).unwrap();
+        let path = dir.join("chat?#%.db");
+        let db = Connection::open(&path).unwrap();
+        db.execute_batch(&format!(
+            "CREATE TABLE {CHAT} (rowid INTEGER PRIMARY KEY, chat_identifier TEXT, service_name TEXT, display_name TEXT);
+             INSERT INTO {CHAT} VALUES (1, '+15558675309', 'iMessage', 'Family');"
+        ))
+        .unwrap();
+        path
+    }
+
+    #[test]
+    fn can_parse_access_any_case() {
+        assert_eq!(
+            DatabaseAccess::from_cli("RO"),
+            Some(DatabaseAccess::ReadOnly)
+        );
+        assert_eq!(
+            DatabaseAccess::from_cli("Immutable"),
+            Some(DatabaseAccess::Immutable)
+        );
+        assert_eq!(DatabaseAccess::from_cli("rw"), None);
+    }
+
+    #[test]
+    fn can_build_immutable_uri() {
+        assert_eq!(
+            immutable_uri(Path::new("/Users/me/Library/Messages/chat.db")),
+            "file:/Users/me/Library/Messages/chat.db?mode=ro&immutable=1"
+        );
+        assert_eq!(
+            immutable_uri(Path::new("/tmp/chat?#%.db")),
+            "file:/tmp/chat%3f%23%25.db?mode=ro&immutable=1"
+        );
+    }
+
+    #[test]
+    fn can_cache_and_stream_immutable() {
+        let dir = temp_dir().join("imessage-immutable-db");
+        let path = fake_db(&dir);
+        let db = get_connection_with_access(&path, DatabaseAccess::Immutable).unwrap();
+
+        // Cached queries
+        let chats = Chat::cache(&db).unwrap();
+        assert_eq!(chats.get(&1).unwrap().display_name(), Some("Family"));
+
+        // Streamed queries
+        let mut statement = Chat::get(&db).unwrap();
+        let streamed: Vec<Chat> = statement
+            .query_map([], |row| Ok(Chat::from_row(row)))
+            .unwrap()
+            .map(|chat| Chat::extract(chat).unwrap())
+            .collect();
+        assert_eq!(streamed.len(), 1);
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cant_write_with_any_access() {
+        let dir = temp_dir().join("imessage-readonly-db");
+        let path = fake_db(&dir);
+
+        for access in [DatabaseAccess::ReadOnly, DatabaseAccess::Immutable] {
+            let db = get_connection_with_access(&path, access).unwrap();
+            assert!(db.execute(&format!("DELETE FROM {CHAT}"), []).is_err());
+        }
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_snapshot_messages_in_wal() {
+        let dir = temp_dir().join("imessage-snapshot-wal");
+        let path = fake_db(&dir);
+
+        // Keep the connection open so the new chat stays in the write-ahead log
+        let live = Connection::open(&path).unwrap();
+        live.execute_batch(&format!(
+            "PRAGMA journal_mode=WAL;
+             PRAGMA wal_autocheckpoint=0;
+             INSERT INTO {CHAT} VALUES (2, '+15551234567', 'iMessage', 'Friends');"
+        ))
+        .unwrap();
+        assert!(with_suffix(&path, "-wal").exists());
+
+        let size = get_db_size(&path).unwrap()
+            + get_db_size(&with_suffix(&path, "-wal")).unwrap()
+            + get_db_size(&with_suffix(&path, "-shm")).unwrap();
+        assert_eq!(get_snapshot_size(&path).unwrap(), size);
+
+        let snapshot = snapshot_database(&path, &dir.join("snapshot")).unwrap();
+        let db = get_connection_with_access(&snapshot, DatabaseAccess::ReadOnly).unwrap();
+        let chats = Chat::cache(&db).unwrap();
+        assert_eq!(chats.get(&2).unwrap().display_name(), Some("Friends"));
+
+        // A second copy replaces the first
+        drop(db);
+        assert!(snapshot_database(&path, &dir.join("snapshot")).is_ok());
+
+        drop(live);
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_detect_encrypted_database() {
+        let dir = temp_dir().join("imessage-encrypted");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        // Encrypted pages look like random bytes, including the first page
+        let path = dir.join("chat.db");
+        write(&path, [0x5Au8; 4096]).unwrap();
+
+        assert!(is_encrypted(&path));
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cant_detect_encrypted_plain_database() {
+        let dir = temp_dir().join("imessage-not-encrypted");
+        let path = fake_db(&dir);
+
+        assert!(!is_encrypted(&path));
+        assert!(!is_encrypted(&dir.join("missing.db")));
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cant_snapshot_missing_database() {
+        let dir = temp_dir().join("imessage-snapshot-missing");
+        assert!(snapshot_database(&dir.join("chat.db"), &dir.join("snapshot")).is_err());
+    }
+}
@@ -4,12 +4,17 @@
  Most dates are stored as nanosecond-precision unix timestamps with an epoch of `1/1/2001 00:00:00` in the local time zone.
 */
 
-use chrono::{DateTime, Duration, Local, TimeZone, Utc};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Duration, FixedOffset, Local, TimeZone, Utc,
+};
 
 use crate::error::message::MessageError;
 
 const SEPARATOR: &str = ", ";
 pub const TIMESTAMP_FACTOR: i64 = 1000000000;
+/// The [`strftime`](chrono::format::strftime) pattern dates are formatted with unless another is given
+pub const DEFAULT_DATE_FORMAT: &str = "%b %d, %Y %l:%M:%S %p";
 
 /// Get the date offset for the iMessage Database
 ///
@@ -44,12 +49,77 @@ pub fn get_local_time(date_stamp: &i64, offset: &i64) -> Result<DateTime<Local>,
 /// println!("{date}");
 /// ```
 pub fn format(date: &Result<DateTime<Local>, MessageError>) -> String {
+    format_as(date, DEFAULT_DATE_FORMAT)
+}
+
+/// Format a date from the iMessage table with a [`strftime`](chrono::format::strftime) pattern
+///
+/// The pattern must be valid, as checked by [`is_valid_format`].
+///
+/// # Example:
+///
+/// ```
+/// use chrono::offset::Local;
+/// use imessage_database::util::dates::format_as;
+///
+/// let date = format_as(&Ok(Local::now()), "%d/%m/%Y %H:%M");
+/// println!("{date}");
+/// ```
+pub fn format_as(date: &Result<DateTime<Local>, MessageError>, pattern: &str) -> String {
     match date {
-        Ok(d) => DateTime::format(d, "%b %d, %Y %l:%M:%S %p").to_string(),
+        Ok(d) => DateTime::format(d, pattern).to_string(),
         Err(why) => why.to_string(),
     }
 }
 
+/// `true` if `pattern` is a [`strftime`](chrono::format::strftime) pattern that dates can be formatted with, else `false`
+pub fn is_valid_format(pattern: &str) -> bool {
+    !pattern.is_empty() && !StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error))
+}
+
+/// Parse a fixed offset from UTC, like `+05:30`, `-0800`, `+09`, or `UTC`
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::dates::parse_utc_offset;
+///
+/// let offset = parse_utc_offset("-08:00").unwrap();
+/// assert_eq!(offset.local_minus_utc(), -8 * 3600);
+/// ```
+pub fn parse_utc_offset(offset: &str) -> Option<FixedOffset> {
+    if offset.eq_ignore_ascii_case("utc") || offset.eq_ignore_ascii_case("z") {
+        return FixedOffset::east_opt(0);
+    }
+
+    let (sign, rest) = match offset.as_bytes().first()? {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+    if !rest.is_ascii() {
+        return None;
+    }
+    let (hours, minutes) = match (rest.len(), rest.get(2..3)) {
+        (2, _) => (rest, "00"),
+        (4, _) => (&rest[..2], &rest[2..]),
+        (5, Some(":")) => (&rest[..2], &rest[3..]),
+        _ => return None,
+    };
+    if !hours
+        .bytes()
+        .chain(minutes.bytes())
+        .all(|byte| byte.is_ascii_digit())
+    {
+        return None;
+    }
+    let (hours, minutes) = (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?);
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
 /// Generate a readable diff from two local timestamps.
 ///
 /// # Example:
@@ -129,7 +199,7 @@ pub fn readable_diff(
 mod tests {
     use crate::{
         error::message::MessageError,
-        util::dates::{format, readable_diff},
+        util::dates::{format, format_as, is_valid_format, parse_utc_offset, readable_diff},
     };
     use chrono::prelude::*;
 
@@ -151,6 +221,24 @@ mod tests {
         assert_eq!(format(&date), "May 20, 2020 10:10:11 AM");
     }
 
+    #[test]
+    fn can_format_date_with_pattern() {
+        let date = Local
+            .with_ymd_and_hms(2020, 5, 20, 9, 10, 11)
+            .single()
+            .ok_or(MessageError::InvalidTimestamp(0));
+        assert_eq!(format_as(&date, "%d/%m/%Y %H:%M"), "20/05/2020 09:10");
+    }
+
+    #[test]
+    fn can_validate_format() {
+        assert!(is_valid_format("%d/%m/%Y"));
+        assert!(is_valid_format("%Y-%m-%dT%H:%M:%S"));
+        assert!(!is_valid_format("%Q"));
+        assert!(!is_valid_format("%"));
+        assert!(!is_valid_format(""));
+    }
+
     #[test]
     fn cant_format_diff_backwards() {
         let end = Ok(Local.with_ymd_and_hms(2020, 5, 20, 9, 10, 11).unwrap());
@@ -256,4 +344,36 @@ mod tests {
         let end = Ok(Local.with_ymd_and_hms(2020, 5, 20, 9, 10, 11).unwrap());
         assert_eq!(readable_diff(start, end), Some("".to_owned()));
     }
+
+    #[test]
+    fn can_parse_utc_offset() {
+        assert_eq!(
+            parse_utc_offset("+05:30").map(|offset| offset.local_minus_utc()),
+            Some(5 * 3600 + 30 * 60)
+        );
+        assert_eq!(
+            parse_utc_offset("-0800").map(|offset| offset.local_minus_utc()),
+            Some(-8 * 3600)
+        );
+        assert_eq!(
+            parse_utc_offset("+09").map(|offset| offset.local_minus_utc()),
+            Some(9 * 3600)
+        );
+        assert_eq!(
+            parse_utc_offset("UTC").map(|offset| offset.local_minus_utc()),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn cant_parse_invalid_utc_offset() {
+        assert_eq!(parse_utc_offset(""), None);
+        assert_eq!(parse_utc_offset("05:30"), None);
+        assert_eq!(parse_utc_offset("+5:30"), None);
+        assert_eq!(parse_utc_offset("+24:00"), None);
+        assert_eq!(parse_utc_offset("+05:60"), None);
+        assert_eq!(parse_utc_offset("+05:3:0"), None);
+        assert_eq!(parse_utc_offset("+1é2"), None);
+        assert_eq!(parse_utc_offset("America/New_York"), None);
+    }
 }
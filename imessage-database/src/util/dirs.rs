@@ -20,6 +20,25 @@ pub fn home() -> String {
     var("HOME").unwrap_or_default()
 }
 
+/// Expand a leading `~` in a path to the user's home directory
+///
+/// Only `~` on its own or followed by `/` is expanded; other users' directories, like `~other/`, are left as-is.
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::dirs::expand_home;
+///
+/// let path = expand_home("~/Library/Messages/chat.db");
+/// println!("{path}");
+/// ```
+pub fn expand_home(path: &str) -> String {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("{}{rest}", home()),
+        _ => path.to_string(),
+    }
+}
+
 /// Get the default path the macOS iMessage database is located at (macOS only)
 ///
 /// # Example:
@@ -33,3 +52,21 @@ pub fn home() -> String {
 pub fn default_db_path() -> PathBuf {
     PathBuf::from(format!("{}/{DEFAULT_PATH_MACOS}", home()))
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::util::dirs::{expand_home, home};
+
+    #[test]
+    fn can_expand_home() {
+        assert_eq!(expand_home("~"), home());
+        assert_eq!(expand_home("~/a/b.png"), format!("{}/a/b.png", home()));
+    }
+
+    #[test]
+    fn cant_expand_other_paths() {
+        assert_eq!(expand_home("/a/~/b.png"), "/a/~/b.png");
+        assert_eq!(expand_home("a/b.png"), "a/b.png");
+        assert_eq!(expand_home("~other/b.png"), "~other/b.png");
+    }
+}
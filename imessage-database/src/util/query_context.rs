@@ -3,7 +3,7 @@
 */
 use std::collections::BTreeSet;
 
-use chrono::prelude::*;
+use chrono::{prelude::*, Days, Months};
 
 use crate::{
     error::query_context::QueryContextError,
@@ -21,6 +21,8 @@ pub struct QueryContext {
     pub selected_handle_ids: Option<BTreeSet<i32>>,
     /// Selected chat IDs
     pub selected_chat_ids: Option<BTreeSet<i32>>,
+    /// If set, only messages sent by the database owner (`true`) or received by them (`false`) will be included.
+    pub from_me: Option<bool>,
 }
 
 impl QueryContext {
@@ -34,7 +36,25 @@ impl QueryContext {
     /// context.set_start("2023-01-01");
     /// ```
     pub fn set_start(&mut self, start: &str) -> Result<(), QueryContextError> {
-        let timestamp = QueryContext::sanitize_date(start)
+        self.set_start_in(start, &Local)
+    }
+
+    /// Populate a [`QueryContext`] with a start date at midnight in `timezone` instead of this computer's time zone
+    /// # Example:
+    ///
+    /// ```
+    /// use chrono::FixedOffset;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_start_in("2023-01-01", &FixedOffset::west_opt(8 * 3600).unwrap());
+    /// ```
+    pub fn set_start_in<Tz: TimeZone>(
+        &mut self,
+        start: &str,
+        timezone: &Tz,
+    ) -> Result<(), QueryContextError> {
+        let timestamp = QueryContext::sanitize_date(start, timezone)
             .ok_or(QueryContextError::InvalidDate(start.to_string()))?;
         self.start = Some(timestamp);
         Ok(())
@@ -50,12 +70,49 @@ impl QueryContext {
     /// context.set_end("2023-01-01");
     /// ```
     pub fn set_end(&mut self, end: &str) -> Result<(), QueryContextError> {
-        let timestamp = QueryContext::sanitize_date(end)
+        self.set_end_in(end, &Local)
+    }
+
+    /// Populate a [`QueryContext`] with an end date at midnight in `timezone` instead of this computer's time zone
+    /// # Example:
+    ///
+    /// ```
+    /// use chrono::FixedOffset;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_end_in("2023-01-01", &FixedOffset::west_opt(8 * 3600).unwrap());
+    /// ```
+    pub fn set_end_in<Tz: TimeZone>(
+        &mut self,
+        end: &str,
+        timezone: &Tz,
+    ) -> Result<(), QueryContextError> {
+        let timestamp = QueryContext::sanitize_date(end, timezone)
             .ok_or(QueryContextError::InvalidDate(end.to_string()))?;
         self.end = Some(timestamp);
         Ok(())
     }
 
+    /// Populate a [`QueryContext`] with a start date relative to the current time
+    ///
+    /// Durations are a positive number followed by a unit: `d` (days), `w` (weeks), `mo` (months), or `y` (years).
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_since("30d");
+    /// ```
+    pub fn set_since(&mut self, since: &str) -> Result<(), QueryContextError> {
+        let start = QueryContext::subtract_duration(since, Local::now())
+            .ok_or(QueryContextError::InvalidDuration(since.to_string()))?;
+        self.start = Some(QueryContext::local_to_stamp(&start));
+        Ok(())
+    }
+
     /// Populate a [`QueryContext`] with a list of handle IDs to select
     ///
     /// # Example:
@@ -86,8 +143,24 @@ impl QueryContext {
         self.selected_chat_ids = (!selected_chat_ids.is_empty()).then_some(selected_chat_ids);
     }
 
-    /// Ensure a date string is valid
-    fn sanitize_date(date: &str) -> Option<i64> {
+    /// Populate a [`QueryContext`] with the direction of the messages to select
+    ///
+    /// `true` selects messages sent by the database owner, `false` selects messages they received.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_from_me(true);
+    /// ```
+    pub fn set_from_me(&mut self, from_me: bool) {
+        self.from_me = Some(from_me);
+    }
+
+    /// Ensure a date string is valid, and get the timestamp of midnight on that date in `timezone`
+    fn sanitize_date<Tz: TimeZone>(date: &str, timezone: &Tz) -> Option<i64> {
         if date.len() < 9 {
             return None;
         }
@@ -112,10 +185,35 @@ impl QueryContext {
             return None;
         }
 
-        let local = Local.with_ymd_and_hms(year, month, day, 0, 0, 0).single()?;
-        let stamp = local.timestamp_nanos_opt().unwrap_or(0);
+        let local = timezone
+            .with_ymd_and_hms(year, month, day, 0, 0, 0)
+            .single()?;
 
-        Some(stamp - (get_offset() * TIMESTAMP_FACTOR))
+        Some(QueryContext::local_to_stamp(&local))
+    }
+
+    /// Subtract a duration like `30d` or `6mo` from a date
+    fn subtract_duration(duration: &str, from: DateTime<Local>) -> Option<DateTime<Local>> {
+        // Split the amount from the unit
+        let split = duration.find(|c: char| !c.is_ascii_digit())?;
+        let amount = duration.get(..split)?.parse::<u32>().ok()?;
+        if amount == 0 {
+            return None;
+        }
+
+        match duration.get(split..)? {
+            "d" => from.checked_sub_days(Days::new(amount.into())),
+            "w" => from.checked_sub_days(Days::new(u64::from(amount) * 7)),
+            "mo" => from.checked_sub_months(Months::new(amount)),
+            "y" => from.checked_sub_months(Months::new(amount.checked_mul(12)?)),
+            _ => None,
+        }
+    }
+
+    /// Convert a date to a timestamp with the iMessage database's epoch
+    fn local_to_stamp<Tz: TimeZone>(local: &DateTime<Tz>) -> i64 {
+        let stamp = local.timestamp_nanos_opt().unwrap_or(0);
+        stamp - (get_offset() * TIMESTAMP_FACTOR)
     }
 
     /// Determine if the current `QueryContext` has any filters present
@@ -135,6 +233,7 @@ impl QueryContext {
             || self.end.is_some()
             || self.selected_chat_ids.is_some()
             || self.selected_handle_ids.is_some()
+            || self.from_me.is_some()
     }
 }
 
@@ -228,6 +327,29 @@ mod use_tests {
         assert!(context.end.is_some());
         assert!(context.has_filters());
     }
+
+    #[test]
+    fn can_create_start_end_in_timezone() {
+        // Set timezone to PST, which the dates should not use
+        set_var("TZ", "PST");
+
+        let mut context = QueryContext::default();
+        let utc = FixedOffset::east_opt(0).unwrap();
+        context.set_start_in("2020-01-01", &utc).unwrap();
+        context.set_end_in("2020-02-02", &utc).unwrap();
+
+        let start = DateTime::from_timestamp(
+            (context.start.unwrap() / TIMESTAMP_FACTOR) + get_offset(),
+            0,
+        )
+        .unwrap();
+        let end =
+            DateTime::from_timestamp((context.end.unwrap() / TIMESTAMP_FACTOR) + get_offset(), 0)
+                .unwrap();
+
+        assert_eq!(start, Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap());
+        assert_eq!(end, Utc.with_ymd_and_hms(2020, 2, 2, 0, 0, 0).unwrap());
+    }
 }
 
 #[cfg(test)]
@@ -293,49 +415,148 @@ mod id_tests {
     }
 }
 
+#[cfg(test)]
+mod direction_tests {
+    use crate::util::query_context::QueryContext;
+
+    #[test]
+    fn can_set_from_me() {
+        let mut qc = QueryContext::default();
+        qc.set_from_me(true);
+
+        assert_eq!(qc.from_me, Some(true));
+        assert!(qc.has_filters());
+    }
+
+    #[test]
+    fn can_set_not_from_me() {
+        let mut qc = QueryContext::default();
+        qc.set_from_me(false);
+
+        assert_eq!(qc.from_me, Some(false));
+        assert!(qc.has_filters());
+    }
+}
+
 #[cfg(test)]
 mod sanitize_tests {
+    use chrono::Local;
+
     use crate::util::query_context::QueryContext;
 
     #[test]
     fn can_sanitize_good() {
-        let res = QueryContext::sanitize_date("2020-01-01");
+        let res = QueryContext::sanitize_date("2020-01-01", &Local);
         assert!(res.is_some());
     }
 
     #[test]
     fn can_reject_bad_short() {
-        let res = QueryContext::sanitize_date("1-1-20");
+        let res = QueryContext::sanitize_date("1-1-20", &Local);
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_bad_order() {
-        let res = QueryContext::sanitize_date("01-01-2020");
+        let res = QueryContext::sanitize_date("01-01-2020", &Local);
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_bad_month() {
-        let res = QueryContext::sanitize_date("2020-31-01");
+        let res = QueryContext::sanitize_date("2020-31-01", &Local);
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_bad_day() {
-        let res = QueryContext::sanitize_date("2020-01-32");
+        let res = QueryContext::sanitize_date("2020-01-32", &Local);
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_bad_data() {
-        let res = QueryContext::sanitize_date("2020-AB-CD");
+        let res = QueryContext::sanitize_date("2020-AB-CD", &Local);
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_wrong_hyphen() {
-        let res = QueryContext::sanitize_date("2020–01–01");
+        let res = QueryContext::sanitize_date("2020–01–01", &Local);
         assert!(res.is_none());
     }
 }
+
+#[cfg(test)]
+mod duration_tests {
+    use chrono::prelude::*;
+
+    use crate::util::query_context::QueryContext;
+
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 3, 31, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn can_subtract_days() {
+        let res = QueryContext::subtract_duration("30d", now());
+        assert_eq!(res, Local.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).single());
+    }
+
+    #[test]
+    fn can_subtract_weeks() {
+        let res = QueryContext::subtract_duration("2w", now());
+        assert_eq!(res, Local.with_ymd_and_hms(2024, 3, 17, 12, 0, 0).single());
+    }
+
+    #[test]
+    fn can_subtract_months() {
+        // February has no 31st, so the date is clamped to the end of the month
+        let res = QueryContext::subtract_duration("1mo", now());
+        assert_eq!(res, Local.with_ymd_and_hms(2024, 2, 29, 12, 0, 0).single());
+    }
+
+    #[test]
+    fn can_subtract_years() {
+        let res = QueryContext::subtract_duration("1y", now());
+        assert_eq!(res, Local.with_ymd_and_hms(2023, 3, 31, 12, 0, 0).single());
+    }
+
+    #[test]
+    fn can_set_since() {
+        let mut context = QueryContext::default();
+        context.set_since("6mo").unwrap();
+        assert!(context.start.is_some());
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_reject_zero() {
+        assert!(QueryContext::subtract_duration("0d", now()).is_none());
+    }
+
+    #[test]
+    fn can_reject_negative() {
+        assert!(QueryContext::subtract_duration("-1d", now()).is_none());
+    }
+
+    #[test]
+    fn can_reject_ambiguous_unit() {
+        assert!(QueryContext::subtract_duration("1m", now()).is_none());
+    }
+
+    #[test]
+    fn can_reject_missing_unit() {
+        assert!(QueryContext::subtract_duration("30", now()).is_none());
+    }
+
+    #[test]
+    fn can_reject_missing_amount() {
+        assert!(QueryContext::subtract_duration("d", now()).is_none());
+    }
+
+    #[test]
+    fn can_reject_fractional() {
+        assert!(QueryContext::subtract_duration("1.5y", now()).is_none());
+    }
+}
@@ -26,9 +26,43 @@ pub fn format_file_size(total_bytes: u64) -> String {
     format!("{bytes:.2} {}", UNITS[index])
 }
 
+/// Parse a human readable file size, like `50MB` or `1.5 GB`, into an amount of bytes
+///
+/// Units are case insensitive and use the same base as [`format_file_size`]; a number without a unit is in bytes.
+///
+/// # Example:
+///
+/// ```
+/// use imessage_database::util::size::parse_file_size;
+///
+/// let size = parse_file_size("50MB");
+/// assert_eq!(size, Some(52428800));
+/// ```
+pub fn parse_file_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let unit = unit.trim().to_uppercase();
+    let power = if unit.is_empty() {
+        0
+    } else {
+        UNITS.iter().position(|candidate| *candidate == unit)?
+    };
+
+    let bytes = number * DIVISOR.powi(power as i32);
+    if bytes.is_finite() && bytes <= u64::MAX as f64 {
+        Some(bytes.round() as u64)
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::util::size::format_file_size;
+    use crate::util::size::{format_file_size, parse_file_size};
 
     #[test]
     fn can_get_file_size_bytes() {
@@ -58,4 +92,28 @@ mod tests {
         let expected = format_file_size(u64::MAX);
         assert_eq!(expected, String::from("16777216.00 TB"));
     }
+
+    #[test]
+    fn can_parse_file_size_units() {
+        assert_eq!(parse_file_size("100"), Some(100));
+        assert_eq!(parse_file_size("100B"), Some(100));
+        assert_eq!(parse_file_size("2KB"), Some(2048));
+        assert_eq!(parse_file_size("50MB"), Some(52428800));
+        assert_eq!(parse_file_size("1GB"), Some(1073741824));
+    }
+
+    #[test]
+    fn can_parse_file_size_any_case_and_spacing() {
+        assert_eq!(parse_file_size("50mb"), Some(52428800));
+        assert_eq!(parse_file_size(" 1.5 GB "), Some(1610612736));
+    }
+
+    #[test]
+    fn cant_parse_invalid_file_size() {
+        assert_eq!(parse_file_size(""), None);
+        assert_eq!(parse_file_size("MB"), None);
+        assert_eq!(parse_file_size("50 parsecs"), None);
+        assert_eq!(parse_file_size("-5MB"), None);
+        assert_eq!(parse_file_size("1.2.3KB"), None);
+    }
 }
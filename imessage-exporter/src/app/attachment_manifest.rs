@@ -0,0 +1,196 @@
+/*!
+ Contains the CSV manifest that records what happened to each exported attachment.
+*/
+
+use std::{collections::HashSet, fmt::Display, fs::File, path::Path};
+
+use csv::Writer;
+
+use crate::app::error::RuntimeError;
+
+/// Name of the file in the export directory that lists every attachment
+pub const ATTACHMENT_MANIFEST: &str = "attachment_manifest.csv";
+
+/// Column names of the manifest, in order
+const HEADER: [&str; 11] = [
+    "source_path",
+    "mime_type",
+    "chat",
+    "message_guid",
+    "status",
+    "output_path",
+    "output_mime_type",
+    "reason",
+    "attachment_id",
+    "source_size",
+    "output_size",
+];
+
+/// Represents what happened to an attachment during the export
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AttachmentStatus {
+    /// Copied without altering it
+    Copied,
+    /// Converted to a more compatible format
+    Converted,
+    /// Not copied, because attachments are not being copied or it was already copied
+    Skipped,
+    /// Could not be copied
+    Failed,
+    /// Not copied, because it is an audio message whose file was deleted when it expired
+    Expired,
+}
+
+impl Display for AttachmentStatus {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttachmentStatus::Copied => write!(fmt, "copied"),
+            AttachmentStatus::Converted => write!(fmt, "converted"),
+            AttachmentStatus::Skipped => write!(fmt, "skipped"),
+            AttachmentStatus::Failed => write!(fmt, "failed"),
+            AttachmentStatus::Expired => write!(fmt, "expired"),
+        }
+    }
+}
+
+/// A single row of the manifest
+#[derive(Debug, PartialEq, Eq)]
+pub struct AttachmentRecord<'a> {
+    /// `ROWID` of the attachment
+    pub attachment_id: i32,
+    /// Where the attachment was read from, if it could be resolved
+    pub source_path: Option<&'a str>,
+    /// The attachment's original MIME type
+    pub mime_type: Option<&'a str>,
+    /// Name of the conversation the attachment was sent in
+    pub chat: &'a str,
+    /// GUID of the message the attachment belongs to
+    pub message_guid: &'a str,
+    /// What happened to the attachment
+    pub status: AttachmentStatus,
+    /// Where the attachment was written to, if anywhere
+    pub output_path: Option<&'a str>,
+    /// The MIME type of the written attachment
+    pub output_mime_type: Option<&'a str>,
+    /// Why the attachment failed or could not be converted
+    pub reason: Option<&'a str>,
+    /// Size of the original attachment in bytes, if it could be read
+    pub source_size: Option<u64>,
+    /// Size of the written attachment in bytes, if anything was written
+    pub output_size: Option<u64>,
+}
+
+/// Writes one row per attachment to [`ATTACHMENT_MANIFEST`]
+pub struct AttachmentManifest {
+    writer: Writer<File>,
+    /// Attachments rendered more than once, i.e. in replies, are only recorded the first time
+    recorded: HashSet<i32>,
+}
+
+impl AttachmentManifest {
+    /// Create the manifest in `export_path`, replacing any manifest a previous export wrote
+    pub fn create(export_path: &Path) -> Result<Self, RuntimeError> {
+        let path = export_path.join(ATTACHMENT_MANIFEST);
+        let file = File::create(&path).map_err(|err| RuntimeError::CreateError(err, path))?;
+        let mut writer = Writer::from_writer(file);
+        writer
+            .write_record(HEADER)
+            .map_err(|why| RuntimeError::DiskError(why.into()))?;
+
+        Ok(AttachmentManifest {
+            writer,
+            recorded: HashSet::new(),
+        })
+    }
+
+    /// Add a row for an attachment, unless it was already recorded
+    pub fn record(&mut self, record: &AttachmentRecord) -> Result<(), RuntimeError> {
+        if !self.recorded.insert(record.attachment_id) {
+            return Ok(());
+        }
+
+        let sizes = [record.source_size, record.output_size]
+            .map(|size| size.map(|size| size.to_string()).unwrap_or_default());
+        self.writer
+            .write_record([
+                record.source_path.unwrap_or_default(),
+                record.mime_type.unwrap_or_default(),
+                record.chat,
+                record.message_guid,
+                &record.status.to_string(),
+                record.output_path.unwrap_or_default(),
+                record.output_mime_type.unwrap_or_default(),
+                record.reason.unwrap_or_default(),
+                &record.attachment_id.to_string(),
+                &sizes[0],
+                &sizes[1],
+            ])
+            .map_err(|why| RuntimeError::DiskError(why.into()))
+    }
+
+    /// Write any buffered rows to disk
+    pub fn flush(&mut self) -> Result<(), RuntimeError> {
+        self.writer.flush().map_err(RuntimeError::DiskError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read_to_string, remove_dir_all},
+    };
+
+    use crate::app::attachment_manifest::{
+        AttachmentManifest, AttachmentRecord, AttachmentStatus, ATTACHMENT_MANIFEST,
+    };
+
+    #[test]
+    fn can_write_manifest() {
+        let dir = temp_dir().join("imessage-attachment-manifest");
+        create_dir_all(&dir).unwrap();
+
+        let mut manifest = AttachmentManifest::create(&dir).unwrap();
+        let converted = AttachmentRecord {
+            attachment_id: 1,
+            source_path: Some("/Attachments/IMG_0001.HEIC"),
+            mime_type: Some("image/heic"),
+            chat: "Grandma",
+            message_guid: "GUID-1",
+            status: AttachmentStatus::Converted,
+            output_path: Some("attachments/1/1.jpeg"),
+            output_mime_type: Some("image/jpeg"),
+            reason: None,
+            source_size: Some(2048),
+            output_size: Some(1024),
+        };
+        manifest.record(&converted).unwrap();
+        // Attachments that are rendered twice are only recorded once
+        manifest.record(&converted).unwrap();
+        manifest
+            .record(&AttachmentRecord {
+                attachment_id: 2,
+                source_path: Some("/Attachments/Audio Message.caf"),
+                mime_type: Some("audio/x-caf"),
+                chat: "Grandma",
+                message_guid: "GUID-2",
+                status: AttachmentStatus::Copied,
+                output_path: Some("attachments/1/2.caf"),
+                output_mime_type: Some("audio/x-caf"),
+                reason: Some("afconvert failed: \"bad input\", exit status: 1"),
+                source_size: Some(512),
+                output_size: None,
+            })
+            .unwrap();
+        manifest.flush().unwrap();
+
+        assert_eq!(
+            read_to_string(dir.join(ATTACHMENT_MANIFEST)).unwrap(),
+            "source_path,mime_type,chat,message_guid,status,output_path,output_mime_type,reason,attachment_id,source_size,output_size\n\
+             /Attachments/IMG_0001.HEIC,image/heic,Grandma,GUID-1,converted,attachments/1/1.jpeg,image/jpeg,,1,2048,1024\n\
+             /Attachments/Audio Message.caf,audio/x-caf,Grandma,GUID-2,copied,attachments/1/2.caf,audio/x-caf,\"afconvert failed: \"\"bad input\"\", exit status: 1\",2,512,\n"
+        );
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
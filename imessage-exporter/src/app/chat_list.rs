@@ -0,0 +1,151 @@
+/*!
+ Contains routines for reading user-provided lists of conversations to export.
+*/
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::read_to_string,
+    path::Path,
+};
+
+use log::warn;
+
+use imessage_database::tables::chat::Chat;
+
+use crate::app::error::RuntimeError;
+
+/// Read a list of chat identifiers from a file
+///
+/// Each line holds one chat GUID or identifier, i.e. `iMessage;+;chat123456789` or `+15558675309`.
+/// Blank lines and lines starting with `#` are ignored.
+pub fn read_chat_list(path: &Path) -> Result<Vec<String>, RuntimeError> {
+    let contents = read_to_string(path).map_err(RuntimeError::DiskError)?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// Match the entries in a chat list to chat IDs, warning about entries that match no chat
+///
+/// An entry matches a chat if it is the chat's GUID or identifier. Chats that
+/// are duplicates of a matched chat, according to `real_chatrooms`, are included too.
+pub fn resolve_chat_list(
+    chat_list: &[String],
+    chatrooms: &HashMap<i32, Chat>,
+    real_chatrooms: &HashMap<i32, i32>,
+) -> BTreeSet<i32> {
+    let mut matched_chatrooms = BTreeSet::new();
+
+    for identifier in chat_list {
+        let matches: Vec<i32> = chatrooms
+            .values()
+            .filter(|chat| {
+                chat.chat_identifier == *identifier || chat.guid.as_deref() == Some(identifier)
+            })
+            .map(|chat| chat.rowid)
+            .collect();
+        if matches.is_empty() {
+            warn!("Chat list entry `{identifier}` does not match any conversation!");
+        }
+        matched_chatrooms.extend(matches);
+    }
+
+    let real_matches: BTreeSet<i32> = matched_chatrooms
+        .iter()
+        .filter_map(|chat_id| real_chatrooms.get(chat_id))
+        .copied()
+        .collect();
+
+    chatrooms
+        .keys()
+        .filter(|chat_id| {
+            matched_chatrooms.contains(chat_id)
+                || real_chatrooms
+                    .get(chat_id)
+                    .is_some_and(|real_id| real_matches.contains(real_id))
+        })
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeSet, HashMap},
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, write},
+    };
+
+    use imessage_database::tables::chat::Chat;
+
+    use crate::app::chat_list::{read_chat_list, resolve_chat_list};
+
+    fn fake_chat(rowid: i32, chat_identifier: &str, guid: &str) -> Chat {
+        Chat {
+            rowid,
+            chat_identifier: chat_identifier.to_string(),
+            guid: Some(guid.to_string()),
+            service_name: Some(String::new()),
+            display_name: None,
+            last_read: None,
+        }
+    }
+
+    #[test]
+    fn can_read_chat_list() {
+        let dir = temp_dir().join("imessage-chat-list");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("chats.txt");
+        write(
+            &path,
+            "# Family\n+15558675309\n\n  iMessage;+;chat123456789  \n",
+        )
+        .unwrap();
+
+        let actual = read_chat_list(&path).unwrap();
+        let expected = vec![
+            "+15558675309".to_string(),
+            "iMessage;+;chat123456789".to_string(),
+        ];
+        assert_eq!(actual, expected);
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_resolve_chat_list() {
+        let chatrooms = HashMap::from([
+            (1, fake_chat(1, "+15558675309", "iMessage;-;+15558675309")),
+            (2, fake_chat(2, "chat123456789", "iMessage;+;chat123456789")),
+            (
+                3,
+                fake_chat(3, "steve@apple.com", "iMessage;-;steve@apple.com"),
+            ),
+            // A duplicate of chatroom 1
+            (4, fake_chat(4, "+15558675309", "SMS;-;+15558675309")),
+        ]);
+        let real_chatrooms = HashMap::from([(1, 0), (2, 1), (3, 2), (4, 0)]);
+        let chat_list = vec![
+            "iMessage;-;+15558675309".to_string(),
+            "chat123456789".to_string(),
+            "nobody@example.com".to_string(),
+        ];
+
+        let actual = resolve_chat_list(&chat_list, &chatrooms, &real_chatrooms);
+        assert_eq!(actual, BTreeSet::from([1, 2, 4]));
+    }
+
+    #[test]
+    fn cant_resolve_invalid_chat_list() {
+        let chatrooms =
+            HashMap::from([(1, fake_chat(1, "+15558675309", "iMessage;-;+15558675309"))]);
+        let real_chatrooms = HashMap::from([(1, 0)]);
+        let chat_list = vec!["nobody@example.com".to_string()];
+
+        let actual = resolve_chat_list(&chat_list, &chatrooms, &real_chatrooms);
+        assert!(actual.is_empty());
+    }
+}
@@ -0,0 +1,406 @@
+/*!
+ Contains the `checksums.txt` file that lists the SHA-256 digest of each file an export wrote.
+
+ Digests are computed as described by [FIPS 180-4](https://csrc.nist.gov/pubs/fips/180-4/upd1/final) and listed in
+ the format `sha256sum` reads, so an export can be checked later with `sha256sum -c checksums.txt` from its directory.
+*/
+
+use std::{
+    collections::BTreeMap,
+    fs::{read_dir, File},
+    io::{BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::app::error::RuntimeError;
+
+/// Name of the file in the export directory that lists the digest of every exported file
+pub const CHECKSUMS: &str = "checksums.txt";
+
+/// Amount of a file read into memory at once while it is hashed
+const CHUNK_SIZE: usize = 1 << 16;
+
+/// Initial hash value: the first 32 bits of the fractional parts of the square roots of the first 8 primes
+const INITIAL_STATE: [u32; 8] = [
+    0x6A09_E667,
+    0xBB67_AE85,
+    0x3C6E_F372,
+    0xA54F_F53A,
+    0x510E_527F,
+    0x9B05_688C,
+    0x1F83_D9AB,
+    0x5BE0_CD19,
+];
+
+/// Round constants: the first 32 bits of the fractional parts of the cube roots of the first 64 primes
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428A_2F98,
+    0x7137_4491,
+    0xB5C0_FBCF,
+    0xE9B5_DBA5,
+    0x3956_C25B,
+    0x59F1_11F1,
+    0x923F_82A4,
+    0xAB1C_5ED5,
+    0xD807_AA98,
+    0x1283_5B01,
+    0x2431_85BE,
+    0x550C_7DC3,
+    0x72BE_5D74,
+    0x80DE_B1FE,
+    0x9BDC_06A7,
+    0xC19B_F174,
+    0xE49B_69C1,
+    0xEFBE_4786,
+    0x0FC1_9DC6,
+    0x240C_A1CC,
+    0x2DE9_2C6F,
+    0x4A74_84AA,
+    0x5CB0_A9DC,
+    0x76F9_88DA,
+    0x983E_5152,
+    0xA831_C66D,
+    0xB003_27C8,
+    0xBF59_7FC7,
+    0xC6E0_0BF3,
+    0xD5A7_9147,
+    0x06CA_6351,
+    0x1429_2967,
+    0x27B7_0A85,
+    0x2E1B_2138,
+    0x4D2C_6DFC,
+    0x5338_0D13,
+    0x650A_7354,
+    0x766A_0ABB,
+    0x81C2_C92E,
+    0x9272_2C85,
+    0xA2BF_E8A1,
+    0xA81A_664B,
+    0xC24B_8B70,
+    0xC76C_51A3,
+    0xD192_E819,
+    0xD699_0624,
+    0xF40E_3585,
+    0x106A_A070,
+    0x19A4_C116,
+    0x1E37_6C08,
+    0x2748_774C,
+    0x34B0_BCB5,
+    0x391C_0CB3,
+    0x4ED8_AA4A,
+    0x5B9C_CA4F,
+    0x682E_6FF3,
+    0x748F_82EE,
+    0x78A5_636F,
+    0x84C8_7814,
+    0x8CC7_0208,
+    0x90BE_FFFA,
+    0xA450_6CEB,
+    0xBEF9_A3F7,
+    0xC671_78F2,
+];
+
+/// A streaming SHA-256 hasher
+struct Sha256 {
+    state: [u32; 8],
+    /// Input that does not yet fill a 64 byte block
+    buffer: Vec<u8>,
+    /// Number of bytes hashed so far
+    length: u64,
+}
+
+impl Sha256 {
+    fn new() -> Self {
+        Sha256 {
+            state: INITIAL_STATE,
+            buffer: Vec::with_capacity(64),
+            length: 0,
+        }
+    }
+
+    /// Add more data to the digest
+    fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u64;
+        if !self.buffer.is_empty() {
+            let needed = 64 - self.buffer.len();
+            let taken = needed.min(data.len());
+            self.buffer.extend_from_slice(&data[..taken]);
+            data = &data[taken..];
+            if self.buffer.len() < 64 {
+                return;
+            }
+            let block = std::mem::take(&mut self.buffer);
+            self.compress(&block);
+        }
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            self.compress(block);
+        }
+        self.buffer.extend_from_slice(blocks.remainder());
+    }
+
+    /// Pad the input and return the digest
+    fn finalize(mut self) -> [u8; 32] {
+        let bit_length = self.length.wrapping_mul(8);
+        let mut padding = vec![0x80];
+        let used = (self.buffer.len() + 1) % 64;
+        padding.resize(1 + (120 - used) % 64, 0);
+        padding.extend_from_slice(&bit_length.to_be_bytes());
+        // The length was already counted, so it is not changed by the padding
+        let length = self.length;
+        self.update(&padding);
+        self.length = length;
+
+        let mut digest = [0; 32];
+        for (bytes, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    /// Mix a 64 byte block into the state
+    fn compress(&mut self, block: &[u8]) {
+        let mut schedule = [0u32; 64];
+        for (word, bytes) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for idx in 16..64 {
+            let s0 = schedule[idx - 15].rotate_right(7)
+                ^ schedule[idx - 15].rotate_right(18)
+                ^ (schedule[idx - 15] >> 3);
+            let s1 = schedule[idx - 2].rotate_right(17)
+                ^ schedule[idx - 2].rotate_right(19)
+                ^ (schedule[idx - 2] >> 10);
+            schedule[idx] = schedule[idx - 16]
+                .wrapping_add(s0)
+                .wrapping_add(schedule[idx - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for (constant, word) in ROUND_CONSTANTS.iter().zip(schedule) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let temp1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(*constant)
+                .wrapping_add(word);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(majority);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+
+        for (state, word) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *state = state.wrapping_add(word);
+        }
+    }
+}
+
+/// Format a digest as lowercase hexadecimal
+fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Compute the SHA-256 digest of the file at `path`, reading it a chunk at a time
+fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        match file.read(&mut chunk)? {
+            0 => break,
+            read => hasher.update(&chunk[..read]),
+        }
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Build a line of [`CHECKSUMS`] like `sha256sum` writes it
+///
+/// Names that contain a backslash or a line break are escaped, and the line starts with a backslash to say so.
+fn format_line(digest: &str, name: &str) -> String {
+    if name.contains(['\\', '\n', '\r']) {
+        let escaped = name
+            .replace('\\', "\\\\")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r");
+        return format!("\\{digest}  {escaped}\n");
+    }
+    format!("{digest}  {name}\n")
+}
+
+/// Get the name of a file in [`CHECKSUMS`]: its path relative to the export directory
+fn name(export_path: &Path, path: &Path) -> String {
+    path.strip_prefix(export_path)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// The digest of each file an export wrote, keyed by its path relative to the export directory
+#[derive(Debug, Default)]
+pub struct Checksums {
+    digests: BTreeMap<String, String>,
+}
+
+impl Checksums {
+    /// Hash a file the export wrote, replacing the digest of a file at the same path
+    pub fn add(&mut self, export_path: &Path, path: &Path) -> Result<(), RuntimeError> {
+        let digest =
+            sha256_file(path).map_err(|err| RuntimeError::CreateError(err, path.to_path_buf()))?;
+        self.digests.insert(name(export_path, path), digest);
+        Ok(())
+    }
+
+    /// Hash every file in `dir` and its subdirectories, except [`CHECKSUMS`] and files that were already hashed
+    ///
+    /// Attachments hashed as they were copied are not read again, but any left by a previous export are still listed.
+    pub fn add_dir(&mut self, export_path: &Path, dir: &Path) -> Result<(), RuntimeError> {
+        for entry in read_dir(dir).map_err(RuntimeError::DiskError)? {
+            let path = entry.map_err(RuntimeError::DiskError)?.path();
+            if path.is_dir() {
+                self.add_dir(export_path, &path)?;
+            } else if path != export_path.join(CHECKSUMS) && !self.contains(export_path, &path) {
+                self.add(export_path, &path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Determine if the file at `path` was already hashed
+    fn contains(&self, export_path: &Path, path: &Path) -> bool {
+        self.digests.contains_key(&name(export_path, path))
+    }
+
+    /// Write [`CHECKSUMS`] to the root of the export, replacing any file a previous export wrote
+    pub fn write(&self, export_path: &Path) -> Result<(), RuntimeError> {
+        let path = export_path.join(CHECKSUMS);
+        let file = File::create(&path).map_err(|err| RuntimeError::CreateError(err, path))?;
+        let mut writer = BufWriter::new(file);
+        for (name, digest) in &self.digests {
+            writer
+                .write_all(format_line(digest, name).as_bytes())
+                .map_err(RuntimeError::DiskError)?;
+        }
+        writer.flush().map_err(RuntimeError::DiskError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read_to_string, remove_dir_all, write},
+    };
+
+    use crate::app::checksums::{format_line, to_hex, Checksums, Sha256, CHECKSUMS};
+
+    fn sha256(data: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(data);
+        to_hex(&hasher.finalize())
+    }
+
+    #[test]
+    fn can_hash_empty() {
+        assert_eq!(
+            sha256(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn can_hash_short() {
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn can_hash_two_blocks() {
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn can_hash_in_pieces() {
+        let data = vec![b'a'; 1000];
+        let mut hasher = Sha256::new();
+        for piece in data.chunks(7) {
+            hasher.update(piece);
+        }
+        assert_eq!(to_hex(&hasher.finalize()), sha256(&data));
+        assert_eq!(
+            sha256(&data),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+    }
+
+    #[test]
+    fn can_format_line() {
+        assert_eq!(format_line("ab", "a b.txt"), "ab  a b.txt\n");
+        assert_eq!(format_line("ab", "a\\b\nc"), "\\ab  a\\\\b\\nc\n");
+    }
+
+    #[test]
+    fn can_write_checksums() {
+        let dir = temp_dir().join("imessage-checksums");
+        let attachments = dir.join("attachments");
+        create_dir_all(&attachments).unwrap();
+        write(dir.join("chat.txt"), "abc").unwrap();
+        write(attachments.join("1.png"), "").unwrap();
+        write(dir.join(CHECKSUMS), "stale").unwrap();
+
+        let mut checksums = Checksums::default();
+        checksums.add(&dir, &attachments.join("1.png")).unwrap();
+        checksums.add_dir(&dir, &dir).unwrap();
+        checksums.write(&dir).unwrap();
+
+        let actual = read_to_string(dir.join(CHECKSUMS)).unwrap();
+        let expected = format!(
+            "{}  attachments/1.png\n{}  chat.txt\n",
+            sha256(b""),
+            sha256(b"abc")
+        );
+        assert_eq!(actual, expected);
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_write_checksums_previous_attachments() {
+        let dir = temp_dir().join("imessage-checksums-previous");
+        let attachments = dir.join("attachments");
+        create_dir_all(&attachments).unwrap();
+        write(dir.join("chat.txt"), "abc").unwrap();
+        // Copied by a previous export, so not hashed as it was copied
+        write(attachments.join("1.png"), "a").unwrap();
+
+        let mut checksums = Checksums::default();
+        checksums.add_dir(&dir, &dir).unwrap();
+        checksums.write(&dir).unwrap();
+
+        let actual = read_to_string(dir.join(CHECKSUMS)).unwrap();
+        let expected = format!(
+            "{}  attachments/1.png\n{}  chat.txt\n",
+            sha256(b"a"),
+            sha256(b"abc")
+        );
+        assert_eq!(actual, expected);
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
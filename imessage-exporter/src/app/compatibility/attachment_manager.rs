@@ -3,20 +3,37 @@
 */
 
 use std::{
+    collections::{HashMap, HashSet},
     fmt::Display,
     fs::{create_dir_all, metadata, write},
+    io::{stderr, stdin},
+    num::NonZeroUsize,
     path::{Path, PathBuf},
+    thread::available_parallelism,
 };
 
+use log::warn;
+
 use crate::app::{
+    attachment_manifest::{AttachmentRecord, AttachmentStatus},
     compatibility::{
         converters::{
-            audio::audio_copy_convert, common::copy_raw, image::image_copy_convert,
-            sticker::sticker_copy_convert, video::video_copy_convert,
+            audio::audio_copy_convert,
+            common::{copy_fallback, write_complete, CopyOutcome},
+            gif::gif_copy_convert,
+            image::{image_copy_convert, render_svg},
+            sticker::sticker_copy_convert,
+            thumbnail::thumbnail,
+            video::video_copy_convert,
+        },
+        models::{
+            AudioConverter, Converter, GifOptimizer, ImageConverter, ImageType, VideoConverter,
+            VideoProbe,
         },
-        models::{AudioConverter, Converter, ImageConverter, VideoConverter},
+        prompt::{prompt_conversion_failure, ConversionChoice},
     },
     runtime::Config,
+    sanitizers::sanitize_filename,
 };
 
 use imessage_database::{
@@ -24,7 +41,9 @@ use imessage_database::{
     tables::{
         attachment::{Attachment, MediaType},
         messages::Message,
+        table::ORPHANED,
     },
+    util::size::format_file_size,
 };
 
 use filetime::{set_file_times, FileTime};
@@ -35,6 +54,22 @@ pub struct AttachmentManager {
     pub image_converter: Option<ImageConverter>,
     pub audio_converter: Option<AudioConverter>,
     pub video_converter: Option<VideoConverter>,
+    /// Program used to optimize GIFs, if requested
+    pub gif_optimizer: Option<GifOptimizer>,
+    /// Program used to read when videos were recorded, if requested
+    pub video_probe: Option<VideoProbe>,
+    /// Attachments larger than this many bytes are not copied, if set
+    pub max_size: Option<u64>,
+    /// Only attachments of these kinds are copied, if set
+    pub only_kinds: Option<Vec<AttachmentKind>>,
+    /// If true, copy the original file next to converted stickers
+    pub keep_originals: bool,
+    /// If true, ask whether to retry, skip, or abort when an attachment cannot be converted
+    pub interactive: bool,
+    /// The most converter processes to run at once
+    pub threads: usize,
+    /// How copied attachments are named
+    pub naming: AttachmentNaming,
 }
 
 impl AttachmentManager {
@@ -44,6 +79,14 @@ impl AttachmentManager {
             image_converter: ImageConverter::determine(),
             audio_converter: AudioConverter::determine(),
             video_converter: VideoConverter::determine(),
+            gif_optimizer: None,
+            video_probe: None,
+            max_size: None,
+            only_kinds: None,
+            keep_originals: false,
+            interactive: false,
+            threads: available_parallelism().map_or(1, NonZeroUsize::get),
+            naming: AttachmentNaming::default(),
         }
     }
 }
@@ -80,11 +123,7 @@ impl AttachmentManager {
     ) -> Option<PathBuf> {
         if !matches!(self.mode, AttachmentManagerMode::Disabled) {
             // Create a path to copy the file to
-            let mut to = config.attachment_path();
-
-            // Add the subdirectory
-            let sub_dir = config.conversation_attachment_path(message.chat_id);
-            to.push(sub_dir);
+            let mut to = config.message_attachment_dir(message);
 
             // Add the filename
             // Each handwriting has a unique id, so cache then all in the same place
@@ -92,7 +131,19 @@ impl AttachmentManager {
 
             // Set the new file's extension to svg
             to.set_extension("svg");
-            if to.exists() {
+
+            // Converting modes render the svg to a static image that displays everywhere
+            let converter = match self.mode {
+                AttachmentManagerMode::Basic | AttachmentManagerMode::Full => {
+                    self.image_converter.as_ref()
+                }
+                _ => None,
+            };
+            let rendered = to.with_extension(ImageType::Png.to_str());
+            if converter.is_some() && rendered.exists() {
+                return Some(rendered);
+            }
+            if converter.is_none() && to.exists() {
                 return Some(to);
             }
 
@@ -100,27 +151,78 @@ impl AttachmentManager {
             if let Some(folder) = to.parent() {
                 if !folder.exists() {
                     if let Err(why) = create_dir_all(folder) {
-                        eprintln!("Unable to create {folder:?}: {why}");
+                        config.record_error(format!("Unable to create {folder:?}: {why}"));
                     }
                 }
             }
 
             // Attempt the svg render
-            if let Err(why) = write(to.to_str()?, handwriting.render_svg()) {
-                eprintln!("Unable to write to {to:?}: {why}");
+            if let Err(why) = write_complete(&to, |partial| {
+                write(partial, handwriting.render_svg())
+                    .map_err(|why| format!("Unable to write to {to:?}: {why}"))
+            }) {
+                config.record_error(why);
             };
 
             // Update file metadata
             update_file_metadata(&to, &to, message, config);
 
+            // Keep the svg if it cannot be rendered
+            if let Some(converter) = converter {
+                match render_svg(&to, &rendered, converter) {
+                    Ok(()) => {
+                        update_file_metadata(&rendered, &rendered, message, config);
+                        return Some(rendered);
+                    }
+                    Err(why) => warn!("Unable to render {to:?}: {why}"),
+                }
+            }
+
             return Some(to);
         }
         None
     }
 
+    /// Handle a link preview image stored inside a message's payload, writing it to a file
+    pub fn handle_preview_image(
+        &self,
+        message: &Message,
+        data: &[u8],
+        extension: &str,
+        config: &Config,
+    ) -> Option<PathBuf> {
+        if matches!(self.mode, AttachmentManagerMode::Disabled) {
+            return None;
+        }
+        // Each message has a single preview, so name it after the message
+        let mut to = config.message_attachment_dir(message);
+        to.push(format!("{}-preview", sanitize_filename(&message.guid)));
+        to.set_extension(extension);
+
+        // A message rendered more than once, i.e. as a reply, only needs its preview written once
+        if !to.exists() {
+            if let Some(folder) = to.parent() {
+                if let Err(why) = create_dir_all(folder) {
+                    config.record_error(format!("Unable to create {folder:?}: {why}"));
+                }
+            }
+            if let Err(why) = write_complete(&to, |partial| {
+                write(partial, data).map_err(|why| format!("Unable to write to {to:?}: {why}"))
+            }) {
+                config.record_error(why);
+                return None;
+            }
+            update_file_metadata(&to, &to, message, config);
+        }
+        config.record_checksum(&to);
+        Some(to)
+    }
+
     /// Handle an attachment, copying and converting if requested
     ///
     /// If copied, update attachment's `copied_path` and `mime_type`
+    ///
+    /// If `--attachment-manifest` is enabled, record what happened to the attachment
     pub fn handle_attachment<'a>(
         &'a self,
         message: &Message,
@@ -132,115 +234,316 @@ impl AttachmentManager {
             &config.options.platform,
             &config.options.db_path,
             config.options.attachment_root.as_deref(),
-        )?;
-
-        if !matches!(self.mode, AttachmentManagerMode::Disabled) {
-            let from = Path::new(&attachment_path);
+        );
 
-            // Ensure the file exists at the specified location
-            if !from.exists() {
-                eprintln!("Attachment not found at specified path: {from:?}");
-                return None;
+        // Keep what we know about the original attachment, since converting it changes its `mime_type`
+        let attachment_id = attachment.rowid;
+        let mime_type = attachment.mime_type.clone();
+        let record = |status: AttachmentStatus,
+                      output_path: Option<&Path>,
+                      output_mime_type: Option<&str>,
+                      reason: Option<&str>| {
+            if !config.options.attachment_manifest {
+                return;
             }
+            // Check for orphans first so missing chats are not logged again
+            let chat = match (config.is_orphaned(message), config.conversation(message)) {
+                (false, Some((chatroom, _))) => config.filename(chatroom),
+                _ => String::from(ORPHANED),
+            };
+            let file_size = |path: &Path| metadata(path).ok().map(|metadata| metadata.len());
+            let source_size = attachment_path
+                .as_deref()
+                .map(Path::new)
+                .and_then(file_size);
+            let output_size = output_path.and_then(file_size);
+            // Paths in the manifest are relative to the export directory, not to the file that links to them
+            let output_path = output_path.map(|path| {
+                path.strip_prefix(&config.options.export_path)
+                    .unwrap_or(path)
+                    .display()
+                    .to_string()
+            });
+            config.record_attachment(&AttachmentRecord {
+                attachment_id,
+                source_path: attachment_path.as_deref(),
+                mime_type: mime_type.as_deref(),
+                chat: &chat,
+                message_guid: &message.guid,
+                status,
+                output_path: output_path.as_deref(),
+                output_mime_type,
+                reason,
+                source_size,
+                output_size,
+            });
+        };
 
-            // Create a path to copy the file to
-            let mut to = config.attachment_path();
+        let Some(attachment_path) = &attachment_path else {
+            record(
+                AttachmentStatus::Failed,
+                None,
+                None,
+                Some("Attachment has no path"),
+            );
+            return None;
+        };
+
+        if matches!(self.mode, AttachmentManagerMode::Disabled) {
+            record(AttachmentStatus::Skipped, None, None, None);
+            return Some(());
+        }
 
-            // Add the subdirectory
-            let sub_dir = config.conversation_attachment_path(message.chat_id);
-            to.push(sub_dir);
+        // Leave large attachments where they are, linking to the original like an export that does not copy them
+        if let Some(max_size) = self.too_large(attachment) {
+            record(
+                AttachmentStatus::Skipped,
+                None,
+                None,
+                Some(&format!(
+                    "Attachment is {}, larger than the {} limit",
+                    attachment.file_size(),
+                    format_file_size(max_size)
+                )),
+            );
+            return Some(());
+        }
 
-            // Add a stable filename
-            to.push(attachment.rowid.to_string());
+        // Leave attachments of other kinds where they are, if only some kinds are requested
+        if !self.is_wanted_kind(attachment) {
+            record(
+                AttachmentStatus::Skipped,
+                None,
+                None,
+                Some("Attachment is not one of the requested kinds"),
+            );
+            return Some(());
+        }
 
-            // Set the new file's extension to the original one
-            to.set_extension(attachment.extension()?);
+        let from = Path::new(attachment_path);
 
-            // If the same file was referenced more than once, i.e. in a reply or response that we render twice, escape early
-            if to.exists() {
-                attachment.copied_path = Some(to);
-                return Some(());
+        // Ensure the file exists at the specified location
+        if !from.exists() {
+            let (status, reason) = missing_file(message);
+            if status == AttachmentStatus::Failed {
+                warn!("{reason}: {from:?}");
             }
+            record(status, None, None, Some(reason));
+            return None;
+        }
 
-            // If we convert the attachment, we need to update the media type
-            let mut new_media_type: Option<MediaType> = None;
-
-            match attachment.mime_type() {
-                MediaType::Image(_) => {
-                    match self.mode {
-                        AttachmentManagerMode::Basic | AttachmentManagerMode::Full => {
-                            match &self.image_converter {
-                                Some(converter) => {
-                                    if attachment.is_sticker {
-                                        new_media_type = sticker_copy_convert(
-                                            from,
-                                            &mut to,
-                                            converter,
-                                            &self.video_converter,
-                                            attachment.mime_type(),
-                                        );
-                                    } else {
-                                        new_media_type = image_copy_convert(
-                                            from,
-                                            &mut to,
-                                            converter,
-                                            attachment.mime_type(),
-                                        );
-                                    }
-                                }
-                                None => copy_raw(from, &to),
-                            }
-                        }
-                        AttachmentManagerMode::Clone => copy_raw(from, &to),
-                        AttachmentManagerMode::Disabled => unreachable!(),
-                    };
+        // Create a path to copy the file to
+        let mut to = config.message_attachment_dir(message);
+
+        // Add a filename that stays the same wherever the attachment is rendered
+        let name = config
+            .attachment_names
+            .borrow_mut()
+            .name(self.naming, &to, attachment);
+        to.push(name);
+
+        // Set the new file's extension to the original one
+        let Some(extension) = attachment.extension() else {
+            record(
+                AttachmentStatus::Failed,
+                None,
+                None,
+                Some("Attachment has no file extension"),
+            );
+            return None;
+        };
+        to.set_extension(extension);
+
+        // If the same file was referenced more than once, i.e. in a reply or response that we render twice, escape early
+        if to.exists() {
+            record(
+                AttachmentStatus::Skipped,
+                Some(&to),
+                None,
+                Some("Attachment was already copied"),
+            );
+            config.record_checksum(&to);
+            attachment.copied_path = Some(to);
+            return Some(());
+        }
+
+        // If we convert the attachment, we need to update the media type
+        let mut outcome = self.copy_convert(attachment, from, &mut to);
+
+        // In interactive mode, let the user decide what to do about conversions that fell back to copying
+        while let CopyOutcome::Fallback(why) = &outcome {
+            if !self.interactive {
+                break;
+            }
+            match prompt_conversion_failure(from, why, &mut stdin().lock(), &mut stderr()) {
+                ConversionChoice::Retry => {
+                    to.set_extension(extension);
+                    outcome = self.copy_convert(attachment, from, &mut to);
+                }
+                ConversionChoice::Skip => break,
+                ConversionChoice::Abort => {
+                    config.abort();
+                    break;
                 }
-                MediaType::Video(_) => match self.mode {
-                    AttachmentManagerMode::Full => match &self.video_converter {
-                        Some(converter) => {
-                            new_media_type = video_copy_convert(
-                                from,
-                                &mut to,
-                                converter,
-                                attachment.mime_type(),
-                            );
-                        }
-                        None => copy_raw(from, &to),
-                    },
-                    AttachmentManagerMode::Clone | AttachmentManagerMode::Basic => {
-                        copy_raw(from, &to)
-                    }
-                    AttachmentManagerMode::Disabled => unreachable!(),
-                },
-                MediaType::Audio(_) => match self.mode {
-                    AttachmentManagerMode::Full => match &self.audio_converter {
-                        Some(converter) => {
-                            new_media_type = audio_copy_convert(
-                                from,
-                                &mut to,
-                                converter,
-                                attachment.mime_type(),
-                            );
-                        }
-                        None => copy_raw(from, &to),
-                    },
-                    AttachmentManagerMode::Clone | AttachmentManagerMode::Basic => {
-                        copy_raw(from, &to)
-                    }
-                    AttachmentManagerMode::Disabled => unreachable!(),
-                },
-                _ => copy_raw(from, &to),
             }
+        }
 
-            // Update file metadata
-            update_file_metadata(from, &to, message, config);
-            attachment.copied_path = Some(to);
-            if let Some(media_type) = new_media_type {
-                attachment.mime_type = Some(media_type.as_mime_type())
+        // Converters fall back to copying, so if nothing exists at the destination, both failed
+        if let CopyOutcome::Failed(why) = &outcome {
+            record(AttachmentStatus::Failed, None, None, Some(why));
+            config.record_error(format!("Unable to copy attachment {from:?} to {to:?}"));
+            return None;
+        }
+
+        // Update file metadata
+        update_file_metadata(from, &to, message, config);
+        match &outcome {
+            CopyOutcome::Converted(media_type)
+            | CopyOutcome::ConvertedWithOriginal(media_type, _) => {
+                let new_mime_type = media_type.as_mime_type();
+                record(
+                    AttachmentStatus::Converted,
+                    Some(&to),
+                    Some(&new_mime_type),
+                    None,
+                );
+                attachment.mime_type = Some(new_mime_type);
+            }
+            CopyOutcome::Fallback(why) => {
+                record(
+                    AttachmentStatus::Copied,
+                    Some(&to),
+                    mime_type.as_deref(),
+                    Some(why),
+                );
             }
+            _ => record(
+                AttachmentStatus::Copied,
+                Some(&to),
+                mime_type.as_deref(),
+                None,
+            ),
         }
+        config.record_checksum(&to);
+        if let CopyOutcome::ConvertedWithOriginal(_, original) = outcome {
+            update_file_metadata(from, &original, message, config);
+            config.record_checksum(&original);
+            attachment.original_path = Some(original);
+        }
+        attachment.copied_path = Some(to);
         Some(())
     }
+
+    /// Get the size limit an attachment exceeds, or `None` if it is small enough to copy
+    ///
+    /// The size stored in the database is used, so the file does not need to be read.
+    fn too_large(&self, attachment: &Attachment) -> Option<u64> {
+        self.max_size
+            .filter(|max_size| u64::try_from(attachment.total_bytes).unwrap_or(0) > *max_size)
+    }
+
+    /// `true` if the attachment is one of the kinds to copy, or if every kind is copied
+    fn is_wanted_kind(&self, attachment: &Attachment) -> bool {
+        match &self.only_kinds {
+            Some(kinds) => {
+                let media_type = media_type(attachment);
+                kinds.iter().any(|kind| kind.matches(&media_type))
+            }
+            None => true,
+        }
+    }
+
+    /// Generate a thumbnail for a copied image attachment, returning its path
+    ///
+    /// Stickers and attachments that are not images do not get thumbnails.
+    pub fn handle_thumbnail(&self, attachment: &Attachment, size: u32) -> Option<PathBuf> {
+        if attachment.is_sticker || !matches!(media_type(attachment), MediaType::Image(_)) {
+            return None;
+        }
+        thumbnail(
+            attachment.copied_path.as_ref()?,
+            self.image_converter.as_ref()?,
+            size,
+        )
+    }
+
+    /// Copy an attachment, routing it to the converter for its [`MediaType`]
+    ///
+    /// Attachments that have no converter in the current mode are copied without altering them.
+    /// Returns a [`CopyOutcome`] that describes whether the attachment was converted.
+    pub fn copy_convert(
+        &self,
+        attachment: &Attachment,
+        from: &Path,
+        to: &mut PathBuf,
+    ) -> CopyOutcome {
+        match (media_type(attachment), &self.mode) {
+            (
+                MediaType::Image("gif" | "GIF"),
+                AttachmentManagerMode::Basic | AttachmentManagerMode::Full,
+            ) if !attachment.is_sticker => {
+                if let Some(optimizer) = &self.gif_optimizer {
+                    return gif_copy_convert(from, to, optimizer);
+                }
+            }
+            (
+                media_type @ MediaType::Image(_),
+                AttachmentManagerMode::Basic | AttachmentManagerMode::Full,
+            ) => {
+                if let Some(converter) = &self.image_converter {
+                    if attachment.is_sticker {
+                        return sticker_copy_convert(
+                            from,
+                            to,
+                            converter,
+                            &self.video_converter,
+                            media_type,
+                            self.keep_originals,
+                            self.threads,
+                        );
+                    }
+                    return image_copy_convert(from, to, converter, media_type);
+                }
+            }
+            (media_type @ MediaType::Video(_), AttachmentManagerMode::Full) => {
+                if let Some(converter) = &self.video_converter {
+                    return video_copy_convert(from, to, converter, media_type);
+                }
+            }
+            (media_type @ MediaType::Audio(_), AttachmentManagerMode::Full) => {
+                if let Some(converter) = &self.audio_converter {
+                    return audio_copy_convert(from, to, converter, media_type);
+                }
+            }
+            _ => {}
+        }
+
+        copy_fallback(from, to, None)
+    }
+}
+
+/// Describe an attachment whose file is missing
+///
+/// Audio messages are deleted when they expire unless they are kept, so their files are gone by design.
+fn missing_file(message: &Message) -> (AttachmentStatus, &'static str) {
+    if message.is_audio_message {
+        (AttachmentStatus::Expired, "Audio message expired")
+    } else {
+        (
+            AttachmentStatus::Failed,
+            "Attachment not found at specified path",
+        )
+    }
+}
+
+/// Get the media type of an attachment, inferring it from the file extension if the database does not store one
+fn media_type(attachment: &Attachment) -> MediaType<'_> {
+    match attachment.mime_type() {
+        MediaType::Unknown => attachment
+            .extension()
+            .map_or(MediaType::Unknown, MediaType::from_extension),
+        media_type => media_type,
+    }
 }
 
 /// Represents different ways the app can interact with attachment data
@@ -286,6 +589,142 @@ impl Display for AttachmentManagerMode {
     }
 }
 
+/// Represents the kinds of attachments that can be copied on their own
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AttachmentKind {
+    Image,
+    Video,
+    Audio,
+}
+
+impl AttachmentKind {
+    /// Create an instance of the enum given user input
+    pub fn from_cli(kind: &str) -> Option<Self> {
+        match kind.to_lowercase().as_str() {
+            "image" => Some(Self::Image),
+            "video" => Some(Self::Video),
+            "audio" => Some(Self::Audio),
+            _ => None,
+        }
+    }
+
+    /// `true` if a file of `media_type` is this kind of attachment, else `false`
+    fn matches(&self, media_type: &MediaType) -> bool {
+        matches!(
+            (self, media_type),
+            (Self::Image, MediaType::Image(_))
+                | (Self::Video, MediaType::Video(_))
+                | (Self::Audio, MediaType::Audio(_))
+        )
+    }
+}
+
+impl Display for AttachmentKind {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttachmentKind::Image => write!(fmt, "image"),
+            AttachmentKind::Video => write!(fmt, "video"),
+            AttachmentKind::Audio => write!(fmt, "audio"),
+        }
+    }
+}
+
+/// Represents how copied attachments are named
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy)]
+pub enum AttachmentNaming {
+    /// Name attachments by their row ID, i.e. `123.jpeg`
+    #[default]
+    Id,
+    /// Name attachments by their GUID
+    Guid,
+    /// Name attachments like the file that was sent, numbering duplicate names in the same directory
+    Original,
+    /// Number attachments in the order they are copied, starting over in each conversation
+    Sequential,
+}
+
+impl AttachmentNaming {
+    /// Create an instance of the enum given user input
+    pub fn from_cli(naming: &str) -> Option<Self> {
+        match naming.to_lowercase().as_str() {
+            "id" => Some(Self::Id),
+            "guid" => Some(Self::Guid),
+            "original" => Some(Self::Original),
+            "sequential" => Some(Self::Sequential),
+            _ => None,
+        }
+    }
+}
+
+impl Display for AttachmentNaming {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttachmentNaming::Id => write!(fmt, "id"),
+            AttachmentNaming::Guid => write!(fmt, "guid"),
+            AttachmentNaming::Original => write!(fmt, "original"),
+            AttachmentNaming::Sequential => write!(fmt, "sequential"),
+        }
+    }
+}
+
+/// Remembers the names given to copied attachments during an export
+///
+/// An attachment keeps its name wherever it is rendered, i.e. in a reply or in another export format.
+#[derive(Debug, Default)]
+pub struct AttachmentNames {
+    /// The name, without an extension, given to each attachment, by row ID
+    names: HashMap<i32, String>,
+    /// The paths, without extensions, of the names already given
+    taken: HashSet<PathBuf>,
+    /// The number of attachments named sequentially in each directory
+    counts: HashMap<PathBuf, usize>,
+}
+
+impl AttachmentNames {
+    /// Get the name, without an extension, of an attachment copied to `dir`
+    pub fn name(
+        &mut self,
+        naming: AttachmentNaming,
+        dir: &Path,
+        attachment: &Attachment,
+    ) -> String {
+        if let Some(name) = self.names.get(&attachment.rowid) {
+            return name.clone();
+        }
+
+        let name = match naming {
+            AttachmentNaming::Id => attachment.rowid.to_string(),
+            AttachmentNaming::Guid => match &attachment.guid {
+                Some(guid) => sanitize_filename(guid),
+                None => attachment.rowid.to_string(),
+            },
+            AttachmentNaming::Original => {
+                let stem = Path::new(attachment.filename())
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .map(sanitize_filename)
+                    .unwrap_or_else(|| attachment.rowid.to_string());
+                let mut name = stem.clone();
+                let mut copy = 1;
+                while self.taken.contains(&dir.join(&name)) {
+                    copy += 1;
+                    name = format!("{stem}-{copy}");
+                }
+                name
+            }
+            AttachmentNaming::Sequential => {
+                let count = self.counts.entry(dir.to_path_buf()).or_default();
+                *count += 1;
+                count.to_string()
+            }
+        };
+
+        self.taken.insert(dir.join(&name));
+        self.names.insert(attachment.rowid, name.clone());
+        name
+    }
+}
+
 /// Update the metadata of a copied file, falling back to the original file's metadata if necessary
 fn update_file_metadata(from: &Path, to: &Path, message: &Message, config: &Config) {
     // Update file metadata
@@ -300,7 +739,323 @@ fn update_file_metadata(from: &Path, to: &Path, message: &Message, config: &Conf
         let atime = FileTime::from_last_access_time(&metadata);
 
         if let Err(why) = set_file_times(to, atime, mtime) {
-            eprintln!("Unable to update {to:?} metadata: {why}");
+            config.record_error(format!("Unable to update {to:?} metadata: {why}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read, remove_dir_all, write},
+        path::Path,
+    };
+
+    use imessage_database::tables::attachment::{Attachment, MediaType};
+
+    use crate::{
+        app::{
+            attachment_manifest::AttachmentStatus,
+            compatibility::{
+                attachment_manager::{
+                    media_type, missing_file, AttachmentKind, AttachmentManager,
+                    AttachmentManagerMode, AttachmentNames, AttachmentNaming,
+                },
+                converters::common::CopyOutcome,
+                models::ImageConverter,
+            },
+            export_type::ExportType,
+        },
+        Config, Options,
+    };
+
+    fn null_mime_attachment(filename: &str) -> Attachment {
+        Attachment {
+            rowid: 1,
+            guid: None,
+            filename: Some(filename.to_string()),
+            uti: None,
+            mime_type: None,
+            transfer_name: None,
+            total_bytes: 100,
+            is_sticker: false,
+            hide_attachment: 0,
+            emoji_description: None,
+            copied_path: None,
+            original_path: None,
         }
     }
+
+    #[test]
+    fn can_infer_media_type_heic() {
+        let attachment = null_mime_attachment("a/b/IMG_0001.HEIC");
+        assert_eq!(media_type(&attachment), MediaType::Image("heic"));
+    }
+
+    #[test]
+    fn can_prefer_stored_media_type() {
+        let mut attachment = null_mime_attachment("a/b/IMG_0001.HEIC");
+        attachment.mime_type = Some("image/png".to_string());
+        assert_eq!(media_type(&attachment), MediaType::Image("png"));
+    }
+
+    #[test]
+    fn cant_infer_media_type_unknown_extension() {
+        let attachment = null_mime_attachment("a/b/file.bloop");
+        assert_eq!(media_type(&attachment), MediaType::Unknown);
+    }
+
+    #[test]
+    fn can_mark_missing_audio_message_expired() {
+        let mut message = Config::fake_message();
+        message.is_audio_message = true;
+
+        assert_eq!(
+            missing_file(&message),
+            (AttachmentStatus::Expired, "Audio message expired")
+        );
+    }
+
+    #[test]
+    fn cant_mark_missing_attachment_expired() {
+        let message = Config::fake_message();
+
+        assert_eq!(missing_file(&message).0, AttachmentStatus::Failed);
+    }
+
+    #[test]
+    fn can_skip_attachment_over_max_size() {
+        let mut manager = AttachmentManager::from(AttachmentManagerMode::Clone);
+        manager.max_size = Some(50);
+        let attachment = null_mime_attachment("a/b/IMG_0001.HEIC");
+
+        assert_eq!(manager.too_large(&attachment), Some(50));
+    }
+
+    #[test]
+    fn can_keep_attachment_at_max_size() {
+        let mut manager = AttachmentManager::from(AttachmentManagerMode::Clone);
+        manager.max_size = Some(100);
+        let attachment = null_mime_attachment("a/b/IMG_0001.HEIC");
+
+        assert_eq!(manager.too_large(&attachment), None);
+    }
+
+    #[test]
+    fn can_keep_attachment_without_max_size() {
+        let manager = AttachmentManager::from(AttachmentManagerMode::Clone);
+        let attachment = null_mime_attachment("a/b/IMG_0001.HEIC");
+
+        assert_eq!(manager.too_large(&attachment), None);
+    }
+
+    #[test]
+    fn can_parse_kind_any_case() {
+        assert_eq!(
+            AttachmentKind::from_cli("Video"),
+            Some(AttachmentKind::Video)
+        );
+        assert_eq!(
+            AttachmentKind::from_cli("audio"),
+            Some(AttachmentKind::Audio)
+        );
+        assert_eq!(AttachmentKind::from_cli("sticker"), None);
+    }
+
+    #[test]
+    fn can_keep_attachment_of_wanted_kind() {
+        let mut manager = AttachmentManager::from(AttachmentManagerMode::Clone);
+        manager.only_kinds = Some(vec![AttachmentKind::Video, AttachmentKind::Image]);
+        let attachment = null_mime_attachment("a/b/IMG_0001.HEIC");
+
+        assert!(manager.is_wanted_kind(&attachment));
+    }
+
+    #[test]
+    fn can_skip_attachment_of_other_kind() {
+        let mut manager = AttachmentManager::from(AttachmentManagerMode::Clone);
+        manager.only_kinds = Some(vec![AttachmentKind::Audio]);
+        let attachment = null_mime_attachment("a/b/IMG_0001.HEIC");
+
+        assert!(!manager.is_wanted_kind(&attachment));
+    }
+
+    #[test]
+    fn can_keep_attachment_without_kinds() {
+        let manager = AttachmentManager::from(AttachmentManagerMode::Clone);
+        let attachment = null_mime_attachment("a/b/file.bloop");
+
+        assert!(manager.is_wanted_kind(&attachment));
+    }
+
+    #[test]
+    fn can_parse_attachment_naming() {
+        assert_eq!(
+            AttachmentNaming::from_cli("Original"),
+            Some(AttachmentNaming::Original)
+        );
+        assert_eq!(
+            AttachmentNaming::from_cli("sequential"),
+            Some(AttachmentNaming::Sequential)
+        );
+        assert_eq!(AttachmentNaming::from_cli("random"), None);
+    }
+
+    #[test]
+    fn can_name_attachment_by_id() {
+        let mut names = AttachmentNames::default();
+        let attachment = null_mime_attachment("a/b/IMG_0001.HEIC");
+
+        assert_eq!(
+            names.name(AttachmentNaming::Id, Path::new("chat"), &attachment),
+            "1"
+        );
+    }
+
+    #[test]
+    fn can_name_attachment_by_guid() {
+        let mut names = AttachmentNames::default();
+        let mut attachment = null_mime_attachment("a/b/IMG_0001.HEIC");
+        attachment.guid = Some("at_0_A1B2C3".to_string());
+
+        assert_eq!(
+            names.name(AttachmentNaming::Guid, Path::new("chat"), &attachment),
+            "at_0_A1B2C3"
+        );
+    }
+
+    #[test]
+    fn can_name_attachment_original_with_duplicates() {
+        let mut names = AttachmentNames::default();
+        let first = null_mime_attachment("a/b/IMG_0001.HEIC");
+        let mut second = null_mime_attachment("c/d/IMG_0001.HEIC");
+        second.rowid = 2;
+        let mut other_chat = null_mime_attachment("e/f/IMG_0001.HEIC");
+        other_chat.rowid = 3;
+
+        let dir = Path::new("chat");
+        assert_eq!(
+            names.name(AttachmentNaming::Original, dir, &first),
+            "IMG_0001"
+        );
+        assert_eq!(
+            names.name(AttachmentNaming::Original, dir, &second),
+            "IMG_0001-2"
+        );
+        assert_eq!(
+            names.name(AttachmentNaming::Original, Path::new("other"), &other_chat),
+            "IMG_0001"
+        );
+    }
+
+    #[test]
+    fn can_name_attachment_sequential_per_directory() {
+        let mut names = AttachmentNames::default();
+        let first = null_mime_attachment("a/b/IMG_0001.HEIC");
+        let mut second = null_mime_attachment("a/b/IMG_0002.HEIC");
+        second.rowid = 2;
+        let mut other_chat = null_mime_attachment("a/b/IMG_0003.HEIC");
+        other_chat.rowid = 3;
+
+        let dir = Path::new("chat");
+        assert_eq!(names.name(AttachmentNaming::Sequential, dir, &first), "1");
+        assert_eq!(names.name(AttachmentNaming::Sequential, dir, &second), "2");
+        assert_eq!(
+            names.name(
+                AttachmentNaming::Sequential,
+                Path::new("other"),
+                &other_chat
+            ),
+            "1"
+        );
+    }
+
+    #[test]
+    fn can_keep_attachment_name_when_rendered_again() {
+        let mut names = AttachmentNames::default();
+        let attachment = null_mime_attachment("a/b/IMG_0001.HEIC");
+
+        let dir = Path::new("chat");
+        assert_eq!(
+            names.name(AttachmentNaming::Original, dir, &attachment),
+            "IMG_0001"
+        );
+        assert_eq!(
+            names.name(AttachmentNaming::Original, dir, &attachment),
+            "IMG_0001"
+        );
+    }
+
+    #[test]
+    fn can_write_preview_image() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.export_path = temp_dir().join("imessage-preview-image");
+        options.attachment_manager = AttachmentManager::from(AttachmentManagerMode::Clone);
+        let app = Config::fake_app(options);
+        let message = Config::fake_message();
+
+        let path = app
+            .options
+            .attachment_manager
+            .handle_preview_image(&message, &[1, 2, 3], "png", &app)
+            .unwrap();
+        assert_eq!(path.extension().unwrap(), "png");
+        assert_eq!(read(&path).unwrap(), [1, 2, 3]);
+
+        // Rendering the message again keeps the same file
+        assert_eq!(
+            app.options
+                .attachment_manager
+                .handle_preview_image(&message, &[1, 2, 3], "png", &app),
+            Some(path)
+        );
+        remove_dir_all(&app.options.export_path).unwrap();
+    }
+
+    #[test]
+    fn cant_write_preview_image_disabled() {
+        let app = Config::fake_app(Options::fake_options(ExportType::Html));
+        let message = Config::fake_message();
+
+        assert!(app
+            .options
+            .attachment_manager
+            .handle_preview_image(&message, &[1, 2, 3], "png", &app)
+            .is_none());
+    }
+
+    #[test]
+    fn can_fall_back_when_conversion_fails() {
+        let dir = temp_dir().join("imessage-copy-convert-fallback");
+        create_dir_all(&dir).unwrap();
+        // Not a real HEIC, so no converter can read it
+        let from = dir.join("IMG_0001.HEIC");
+        write(&from, b"not an image").unwrap();
+
+        let mut manager = AttachmentManager::from(AttachmentManagerMode::Basic);
+        manager.image_converter = Some(ImageConverter::Imagemagick);
+        let mut attachment = null_mime_attachment("IMG_0001.HEIC");
+        attachment.mime_type = Some("image/heic".to_string());
+
+        let mut to = dir.join("out").join("1.HEIC");
+        let outcome = manager.copy_convert(&attachment, &from, &mut to);
+
+        assert!(matches!(outcome, CopyOutcome::Fallback(_)));
+        assert_eq!(read(&to).unwrap(), b"not an image");
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cant_copy_convert_missing_file() {
+        let dir = temp_dir().join("imessage-copy-convert-missing");
+        let manager = AttachmentManager::from(AttachmentManagerMode::Basic);
+        let attachment = null_mime_attachment("IMG_0001.png");
+
+        let mut to = dir.join("1.png");
+        let outcome = manager.copy_convert(&attachment, &dir.join("IMG_0001.png"), &mut to);
+
+        assert!(matches!(outcome, CopyOutcome::Failed(_)));
+        assert!(!to.exists());
+    }
 }
@@ -4,10 +4,14 @@
 
 use std::path::{Path, PathBuf};
 
+use log::warn;
+
 use imessage_database::tables::attachment::MediaType;
 
 use crate::app::compatibility::{
-    converters::common::{copy_raw, ensure_paths, run_command},
+    converters::common::{
+        check_output, copy_fallback, ensure_paths, run_command, write_complete, CopyOutcome,
+    },
     models::{AudioConverter, AudioType, Converter},
 };
 
@@ -20,31 +24,39 @@ pub(crate) fn audio_copy_convert(
     to: &mut PathBuf,
     converter: &AudioConverter,
     mime_type: MediaType,
-) -> Option<MediaType<'static>> {
+) -> CopyOutcome {
+    let mut conversion_error = None;
     if matches!(
         mime_type,
-        MediaType::Audio("caf") | MediaType::Audio("CAF") | MediaType::Audio("x-caf; codecs=opus")
+        MediaType::Audio("caf")
+            | MediaType::Audio("CAF")
+            | MediaType::Audio("x-caf")
+            | MediaType::Audio("x-caf; codecs=opus")
     ) {
         let output_type = AudioType::Mp4;
         // Update extension for conversion
         to.set_extension(output_type.to_str());
-        if convert_caf(from, to, converter).is_none() {
-            eprintln!("Unable to convert {from:?}");
-        } else {
-            return Some(MediaType::Audio(output_type.to_str()));
+        match convert_caf(from, to, converter) {
+            Ok(()) => return CopyOutcome::Converted(MediaType::Audio(output_type.to_str())),
+            Err(why) => {
+                warn!("Unable to convert {from:?}: {why}");
+                conversion_error = Some(why);
+            }
         }
     }
-    copy_raw(from, to);
-    None
+    copy_fallback(from, to, conversion_error)
 }
 
-fn convert_caf(from: &Path, to: &Path, converter: &AudioConverter) -> Option<()> {
-    let (from_path, to_path) = ensure_paths(from, to)?;
+fn convert_caf(from: &Path, to: &Path, converter: &AudioConverter) -> Result<(), String> {
+    write_complete(to, |partial| {
+        let (from_path, to_path) = ensure_paths(from, partial)?;
 
-    let args = match converter {
-        AudioConverter::AfConvert => vec!["-f", "mp4f", "-d", "aac", "-v", from_path, to_path],
-        AudioConverter::Ffmpeg => vec!["-i", from_path, to_path],
-    };
+        let args = match converter {
+            AudioConverter::AfConvert => vec!["-f", "mp4f", "-d", "aac", "-v", from_path, to_path],
+            AudioConverter::Ffmpeg => vec!["-i", from_path, to_path],
+        };
 
-    run_command(converter.name(), args)
+        run_command(converter.name(), args)?;
+        check_output(partial, None)
+    })
 }
@@ -3,66 +3,511 @@
 */
 
 use std::{
-    fs::{copy, create_dir_all},
-    path::Path,
-    process::{Command, Stdio},
+    fs::{copy, create_dir_all, remove_dir_all, remove_file, rename, File},
+    io::Read,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    thread::scope,
 };
 
-/// Run a command, ignoring output; returning [`None`] on failure.
-pub(super) fn run_command(command: &str, args: Vec<&str>) -> Option<()> {
+use log::warn;
+
+use imessage_database::tables::attachment::MediaType;
+
+use crate::app::compatibility::models::ImageType;
+
+/// Converted files smaller than this many bytes are assumed to be broken
+const MIN_OUTPUT_SIZE: u64 = 64;
+
+/// The number of times to try copying a file before giving up
+const COPY_ATTEMPTS: usize = 3;
+
+/// Run a command, ignoring its output; on failure, return why, including anything it wrote to `stderr`
+pub(super) fn run_command(command: &str, args: Vec<&str>) -> Result<(), String> {
     match Command::new(command)
         .args(args)
         .stdout(Stdio::null())
-        .stderr(Stdio::null())
+        .stderr(Stdio::piped())
         .stdin(Stdio::null())
-        .spawn()
+        .output()
     {
-        Ok(mut convert) => match convert.wait() {
-            Ok(_) => Some(()),
-            Err(why) => {
-                eprintln!("Conversion failed: {why}");
-                None
-            }
-        },
-        Err(why) => {
-            eprintln!("Conversion failed: {why}");
-            None
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(format!(
+            "{command} failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+        Err(why) => Err(format!("Unable to run {command}: {why}")),
+    }
+}
+
+/// Run a command and return what it wrote to `stdout`; on failure, return why, including anything it wrote to `stderr`
+pub(super) fn command_output(command: &str, args: Vec<&str>) -> Result<String, String> {
+    match Command::new(command)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .stdin(Stdio::null())
+        .output()
+    {
+        Ok(output) if output.status.success() => {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        }
+        Ok(output) => Err(format!(
+            "{command} failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+        Err(why) => Err(format!("Unable to run {command}: {why}")),
+    }
+}
+
+/// Run `task` on each item, using at most `threads` threads at once
+///
+/// With a single thread, the items run in order on the current thread. On failure, return why.
+pub(super) fn run_parallel<T: Sync>(
+    items: &[T],
+    threads: usize,
+    task: impl Fn(&T) -> Result<(), String> + Sync,
+) -> Result<(), String> {
+    if threads <= 1 || items.len() <= 1 {
+        return items.iter().try_for_each(task);
+    }
+
+    let task = &task;
+    let chunk_size = items.len().div_ceil(threads);
+    scope(|scope| {
+        let workers: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().try_for_each(task)))
+            .collect();
+        workers.into_iter().try_for_each(|worker| {
+            worker
+                .join()
+                .unwrap_or_else(|_| Err("A converter thread panicked".to_string()))
+        })
+    })
+}
+
+/// A directory for intermediate files that is removed when dropped
+///
+/// Because cleanup happens on [`Drop`], the directory is removed on early returns and
+/// when a conversion panics.
+pub(super) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create the directory, including any missing parents
+    pub(super) fn new(path: PathBuf) -> Result<Self, String> {
+        create_dir_all(&path).map_err(|why| format!("Unable to create {path:?}: {why}"))?;
+        Ok(TempDir { path })
+    }
+
+    /// Get the path to the directory
+    pub(super) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Err(why) = remove_dir_all(&self.path) {
+            warn!("Unable to remove {:?}: {why}", self.path);
         }
     }
 }
 
 /// Get the path details formatted for a CLI argument and ensure the directory tree exists
-pub(super) fn ensure_paths<'a>(from: &'a Path, to: &'a Path) -> Option<(&'a str, &'a str)> {
+pub(super) fn ensure_paths<'a>(from: &'a Path, to: &'a Path) -> Result<(&'a str, &'a str), String> {
     // Get the path we want to copy from
-    let from_path = from.to_str()?;
+    let from_path = from
+        .to_str()
+        .ok_or(format!("{from:?} is not a valid UTF-8 path"))?;
 
     // Get the path we want to write to
-    let to_path = to.to_str()?;
+    let to_path = to
+        .to_str()
+        .ok_or(format!("{to:?} is not a valid UTF-8 path"))?;
 
     // Ensure the directory tree exists
     if let Some(folder) = to.parent() {
         if !folder.exists() {
-            if let Err(why) = create_dir_all(folder) {
-                eprintln!("Unable to create {folder:?}: {why}");
-                return None;
+            create_dir_all(folder).map_err(|why| format!("Unable to create {folder:?}: {why}"))?;
+        }
+    }
+    Ok((from_path, to_path))
+}
+
+/// Get the path a file is written to before it is complete, i.e. `.1.<pid>.partial.jpeg` for `1.jpeg`
+///
+/// The file is hidden and named for this process, so two exports writing to the same directory
+/// never share one. The extension is kept because converters choose the output format from it.
+pub(crate) fn partial_path(to: &Path) -> PathBuf {
+    let stem = to.file_stem().unwrap_or_default().to_string_lossy();
+    let pid = process::id();
+    match to.extension() {
+        Some(extension) => to.with_file_name(format!(
+            ".{stem}.{pid}.partial.{}",
+            extension.to_string_lossy()
+        )),
+        None => to.with_file_name(format!(".{stem}.{pid}.partial")),
+    }
+}
+
+/// Write a file to its [`partial_path()`], moving it to `to` only once `write` succeeds
+///
+/// A file at `to` is always complete, so an export that is interrupted while writing
+/// leaves only a partial file behind, which the next run replaces instead of skipping.
+pub(crate) fn write_complete(
+    to: &Path,
+    write: impl FnOnce(&Path) -> Result<(), String>,
+) -> Result<(), String> {
+    let partial = partial_path(to);
+    // Remove anything left by an earlier run with the same process ID, since some converters refuse to overwrite files
+    if partial.exists() {
+        remove_file(&partial).map_err(|why| format!("Unable to remove {partial:?}: {why}"))?;
+    }
+
+    match write(&partial) {
+        Ok(()) => rename(&partial, to)
+            .map_err(|why| format!("Unable to move {partial:?} to {to:?}: {why}")),
+        Err(why) => {
+            if partial.exists() {
+                if let Err(remove_why) = remove_file(&partial) {
+                    warn!("Unable to remove {partial:?}: {remove_why}");
+                }
             }
+            Err(why)
         }
     }
-    Some((from_path, to_path))
 }
 
-/// Copy a file without altering it
-pub(crate) fn copy_raw(from: &Path, to: &Path) {
+/// Ensure a converter wrote a usable file, since some exit successfully after writing an empty or truncated one
+///
+/// Images must also start with the [`signature`](ImageType::signature) of their type.
+pub(super) fn check_output(path: &Path, image_type: Option<&ImageType>) -> Result<(), String> {
+    let mut file =
+        File::open(path).map_err(|why| format!("Converter did not write {path:?}: {why}"))?;
+    let size = file
+        .metadata()
+        .map_err(|why| format!("Unable to read {path:?}: {why}"))?
+        .len();
+    if size < MIN_OUTPUT_SIZE {
+        return Err(format!(
+            "Converter wrote {size} bytes to {path:?}, which is too small to be complete"
+        ));
+    }
+
+    if let Some(image_type) = image_type {
+        let signature = image_type.signature();
+        let mut header = vec![0; signature.len()];
+        file.read_exact(&mut header)
+            .map_err(|why| format!("Unable to read {path:?}: {why}"))?;
+        if header != signature {
+            return Err(format!(
+                "Converter wrote {path:?}, but it is not a valid {} file",
+                image_type.to_str()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Run `task` until it succeeds, at most `attempts` times; on failure, return why the last attempt failed
+fn retry(attempts: usize, mut task: impl FnMut() -> Result<(), String>) -> Result<(), String> {
+    let mut result = task();
+    for attempt in 1..attempts {
+        match result {
+            Ok(()) => break,
+            Err(why) => {
+                warn!("{why}, retrying ({attempt}/{})", attempts - 1);
+                result = task();
+            }
+        }
+    }
+    result
+}
+
+/// Copy a file without altering it, trying again up to [`COPY_ATTEMPTS`] times if the copy fails
+pub(crate) fn copy_raw(from: &Path, to: &Path) -> Result<(), String> {
     // Ensure the directory tree exists
     if let Some(folder) = to.parent() {
         if !folder.exists() {
             if let Err(why) = create_dir_all(folder) {
-                eprintln!("Unable to create {folder:?}: {why}");
+                warn!("Unable to create {folder:?}: {why}");
             }
         }
     }
 
-    if let Err(why) = copy(from, to) {
-        eprintln!("Unable to copy {from:?} to {to:?}: {why}");
+    retry(COPY_ATTEMPTS, || {
+        write_complete(to, |partial| {
+            copy(from, partial)
+                .map(|_| ())
+                .map_err(|why| format!("Unable to copy {from:?} to {to:?}: {why}"))
+        })
+    })
+}
+
+/// Copy a file without altering it because it could not be converted, or did not need to be
+pub(crate) fn copy_fallback(
+    from: &Path,
+    to: &Path,
+    conversion_error: Option<String>,
+) -> CopyOutcome {
+    match (copy_raw(from, to), conversion_error) {
+        (Err(why), _) => {
+            warn!("{why}");
+            CopyOutcome::Failed(why)
+        }
+        (Ok(()), Some(why)) => CopyOutcome::Fallback(why),
+        (Ok(()), None) => CopyOutcome::Copied,
+    }
+}
+
+/// Represents what happened when an attachment was copied
+#[derive(Debug, PartialEq, Eq)]
+pub enum CopyOutcome {
+    /// The attachment was copied without altering it
+    Copied,
+    /// The attachment was converted to a new [`MediaType`]
+    Converted(MediaType<'static>),
+    /// The attachment was converted to a new [`MediaType`] and the original was copied to the given path
+    ConvertedWithOriginal(MediaType<'static>, PathBuf),
+    /// The attachment could not be converted for the given reason, so it was copied without altering it
+    Fallback(String),
+    /// The attachment could not be copied for the given reason
+    Failed(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read_to_string, remove_dir_all, remove_file, write},
+        panic::catch_unwind,
+        path::{Path, PathBuf},
+        process,
+        sync::Mutex,
+    };
+
+    use crate::app::compatibility::{
+        converters::common::{
+            check_output, copy_raw, partial_path, retry, run_parallel, write_complete, TempDir,
+        },
+        models::ImageType,
     };
+
+    #[test]
+    fn can_remove_temp_dir() {
+        let path = temp_dir().join("imessage-temp-dir-drop");
+        {
+            let tmp = TempDir::new(path.clone()).unwrap();
+            write(tmp.path().join("frame_0001.png"), []).unwrap();
+            assert!(path.exists());
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn can_remove_temp_dir_after_panic() {
+        let path = temp_dir().join("imessage-temp-dir-panic");
+        let result = catch_unwind(|| {
+            let tmp = TempDir::new(path.clone()).unwrap();
+            write(tmp.path().join("frame_0001.png"), []).unwrap();
+            panic!("Conversion failed");
+        });
+
+        assert!(result.is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn can_get_partial_path() {
+        assert_eq!(
+            partial_path(Path::new("out/1.jpeg")),
+            PathBuf::from(format!("out/.1.{}.partial.jpeg", process::id()))
+        );
+        assert_eq!(
+            partial_path(Path::new("out/1")),
+            PathBuf::from(format!("out/.1.{}.partial", process::id()))
+        );
+    }
+
+    #[test]
+    fn can_copy_raw_complete() {
+        let dir = temp_dir().join("imessage-copy-raw-complete");
+        let from = dir.join("from.txt");
+        let to = dir.join("out/1.txt");
+        create_dir_all(&dir).unwrap();
+        write(&from, "hello").unwrap();
+
+        copy_raw(&from, &to).unwrap();
+
+        assert_eq!(read_to_string(&to).unwrap(), "hello");
+        assert!(!partial_path(&to).exists());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cant_write_complete_failure() {
+        let dir = temp_dir().join("imessage-write-complete-failure");
+        let to = dir.join("1.txt");
+        create_dir_all(&dir).unwrap();
+
+        // A write that fails part way through leaves nothing behind
+        let actual = write_complete(&to, |partial| {
+            write(partial, "hel").unwrap();
+            Err("Interrupted".to_string())
+        });
+
+        assert_eq!(actual, Err("Interrupted".to_string()));
+        assert!(!to.exists());
+        assert!(!partial_path(&to).exists());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cant_write_complete_interrupted() {
+        let dir = temp_dir().join("imessage-write-complete-interrupted");
+        let to = dir.join("1.txt");
+        create_dir_all(&dir).unwrap();
+
+        // A write that never returns, as when the process is killed, leaves only the partial file
+        let interrupted = catch_unwind(|| {
+            write_complete(&to, |partial| {
+                write(partial, "hel").unwrap();
+                panic!("Interrupted");
+            })
+        });
+
+        assert!(interrupted.is_err());
+        assert!(!to.exists());
+        assert_eq!(read_to_string(partial_path(&to)).unwrap(), "hel");
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_retry() {
+        let mut calls = 0;
+        let actual = retry(3, || {
+            calls += 1;
+            if calls < 2 {
+                Err("Busy".to_string())
+            } else {
+                Ok(())
+            }
+        });
+
+        assert_eq!(actual, Ok(()));
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn cant_retry_forever() {
+        let mut calls = 0;
+        let actual = retry(3, || {
+            calls += 1;
+            Err(format!("Failed {calls}"))
+        });
+
+        assert_eq!(actual, Err("Failed 3".to_string()));
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn can_write_complete_after_interrupted_run() {
+        let dir = temp_dir().join("imessage-write-complete-resume");
+        let to = dir.join("1.txt");
+        create_dir_all(&dir).unwrap();
+
+        // A run that was killed mid-copy leaves a partial file, but nothing at the destination
+        write(partial_path(&to), "hel").unwrap();
+        assert!(!to.exists());
+
+        write_complete(&to, |partial| {
+            assert!(!partial.exists());
+            write(partial, "hello").map_err(|why| why.to_string())
+        })
+        .unwrap();
+
+        assert_eq!(read_to_string(&to).unwrap(), "hello");
+        assert!(!partial_path(&to).exists());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_run_serial() {
+        let seen = Mutex::new(vec![]);
+        run_parallel(&[1, 2, 3, 4], 1, |item| {
+            seen.lock().unwrap().push(*item);
+            Ok(())
+        })
+        .unwrap();
+
+        // A single thread keeps the original order
+        assert_eq!(seen.into_inner().unwrap(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn can_run_parallel() {
+        let seen = Mutex::new(vec![]);
+        run_parallel(&[1, 2, 3, 4, 5], 3, |item| {
+            seen.lock().unwrap().push(*item);
+            Ok(())
+        })
+        .unwrap();
+
+        let mut seen = seen.into_inner().unwrap();
+        seen.sort();
+        assert_eq!(seen, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn cant_run_parallel_failure() {
+        let actual = run_parallel(&[1, 2, 3, 4], 2, |item| match item {
+            3 => Err("Unable to convert 3".to_string()),
+            _ => Ok(()),
+        });
+
+        assert_eq!(actual, Err("Unable to convert 3".to_string()));
+    }
+
+    #[test]
+    fn can_check_complete_output() {
+        let path = temp_dir().join("imessage-check-output-complete.png");
+        let mut png = ImageType::Png.signature().to_vec();
+        png.resize(128, 0);
+        write(&path, &png).unwrap();
+
+        assert!(check_output(&path, Some(&ImageType::Png)).is_ok());
+        assert!(check_output(&path, None).is_ok());
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn cant_check_empty_output() {
+        let path = temp_dir().join("imessage-check-output-empty.jpeg");
+        write(&path, "").unwrap();
+
+        assert!(check_output(&path, None).is_err());
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn cant_check_output_wrong_type() {
+        let path = temp_dir().join("imessage-check-output-wrong.gif");
+        let mut png = ImageType::Png.signature().to_vec();
+        png.resize(128, 0);
+        write(&path, &png).unwrap();
+
+        assert!(check_output(&path, Some(&ImageType::Gif)).is_err());
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn cant_check_missing_output() {
+        let path = temp_dir().join("imessage-check-output-missing.mp4");
+        assert!(check_output(&path, None).is_err());
+    }
 }
@@ -0,0 +1,114 @@
+/*!
+ Defines routines for optimizing animated GIF files.
+*/
+
+use std::{fs::metadata, path::Path};
+
+use log::warn;
+
+use imessage_database::tables::attachment::MediaType;
+
+use crate::app::compatibility::{
+    converters::common::{
+        check_output, copy_fallback, ensure_paths, run_command, write_complete, CopyOutcome,
+    },
+    models::{Converter, GifOptimizer, ImageType},
+};
+
+/// Copy a GIF file, optimizing it if possible
+///
+/// - Optimized files keep their animation and transparency
+/// - If optimizing does not make the file smaller, the original is copied instead
+/// - Fallback to the original file
+pub(crate) fn gif_copy_convert(from: &Path, to: &Path, optimizer: &GifOptimizer) -> CopyOutcome {
+    let conversion_error = match optimize_gif(from, to, optimizer) {
+        Ok(()) if file_size(to) < file_size(from) => {
+            return CopyOutcome::Converted(MediaType::Image(ImageType::Gif.to_str()))
+        }
+        Ok(()) => None,
+        Err(why) => {
+            warn!("Unable to optimize {from:?}: {why}");
+            Some(why)
+        }
+    };
+
+    // Fallback
+    copy_fallback(from, to, conversion_error)
+}
+
+/// Get the size of a file in bytes, where files that cannot be read are as large as possible
+fn file_size(path: &Path) -> u64 {
+    metadata(path).map_or(u64::MAX, |metadata| metadata.len())
+}
+
+/// Reduce the size of a GIF file by optimizing its frames and reducing its palette
+fn optimize_gif(from: &Path, to: &Path, optimizer: &GifOptimizer) -> Result<(), String> {
+    write_complete(to, |partial| {
+        let (from_path, to_path) = ensure_paths(from, partial)?;
+        run_command(
+            optimizer.name(),
+            optimize_args(optimizer, from_path, to_path),
+        )?;
+        check_output(partial, Some(&ImageType::Gif))
+    })
+}
+
+/// Build the arguments that optimize a GIF file
+///
+/// Docs: <https://www.lcdf.org/gifsicle/man.html> (or `man gifsicle`)
+///
+/// `ffmpeg` generates a new palette from every frame, reserving an entry for transparent pixels,
+/// and only redraws the parts of each frame that changed.
+fn optimize_args<'a>(
+    optimizer: &GifOptimizer,
+    from_path: &'a str,
+    to_path: &'a str,
+) -> Vec<&'a str> {
+    match optimizer {
+        GifOptimizer::Gifsicle => vec!["-O3", "--lossy=80", from_path, "-o", to_path],
+        GifOptimizer::Ffmpeg => vec![
+            "-loglevel",
+            "error",
+            "-y",
+            "-i",
+            from_path,
+            "-filter_complex",
+            "[0:v]split[a][b];[a]palettegen=reserve_transparent=1:stats_mode=diff[p];[b][p]paletteuse=dither=bayer:diff_mode=rectangle",
+            to_path,
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+
+    use crate::app::compatibility::{
+        converters::gif::{file_size, optimize_args},
+        models::GifOptimizer,
+    };
+
+    #[test]
+    fn can_build_gifsicle_args() {
+        assert_eq!(
+            optimize_args(&GifOptimizer::Gifsicle, "in.gif", "out.gif"),
+            vec!["-O3", "--lossy=80", "in.gif", "-o", "out.gif"]
+        );
+    }
+
+    #[test]
+    fn can_build_ffmpeg_args() {
+        let args = optimize_args(&GifOptimizer::Ffmpeg, "in.gif", "out.gif");
+
+        assert_eq!(args[4], "in.gif");
+        assert!(args[6].contains("reserve_transparent=1"));
+        assert_eq!(args.last(), Some(&"out.gif"));
+    }
+
+    #[test]
+    fn can_get_missing_file_size() {
+        let path = temp_dir().join("imessage-gif-missing.gif");
+
+        assert_eq!(file_size(&path), u64::MAX);
+    }
+}
@@ -4,10 +4,14 @@
 
 use std::path::{Path, PathBuf};
 
+use log::warn;
+
 use imessage_database::tables::attachment::MediaType;
 
 use crate::app::compatibility::{
-    converters::common::{copy_raw, ensure_paths, run_command},
+    converters::common::{
+        check_output, copy_fallback, ensure_paths, run_command, write_complete, CopyOutcome,
+    },
     models::{Converter, ImageConverter, ImageType},
 };
 
@@ -20,7 +24,8 @@ pub(crate) fn image_copy_convert(
     to: &mut PathBuf,
     converter: &ImageConverter,
     mime_type: MediaType,
-) -> Option<MediaType<'static>> {
+) -> CopyOutcome {
+    let mut conversion_error = None;
     if matches!(
         mime_type,
         MediaType::Image("heic") | MediaType::Image("HEIC")
@@ -28,19 +33,25 @@ pub(crate) fn image_copy_convert(
         let output_type = ImageType::Jpeg;
         // Update extension for conversion
         to.set_extension(output_type.to_str());
-        if convert_heic(from, to, converter, &output_type).is_none() {
-            eprintln!("Unable to convert {from:?}");
-        } else {
-            return Some(MediaType::Image(output_type.to_str()));
+        match convert_image(from, to, converter, &output_type) {
+            Ok(()) => return CopyOutcome::Converted(MediaType::Image(output_type.to_str())),
+            Err(why) => {
+                warn!("Unable to convert {from:?}: {why}");
+                conversion_error = Some(why);
+            }
         }
     }
 
     // Fallback
-    copy_raw(from, to);
-    None
+    copy_fallback(from, to, conversion_error)
+}
+
+/// Render an SVG image file to a static `PNG`, for formats that cannot draw vector images
+pub(crate) fn render_svg(from: &Path, to: &Path, converter: &ImageConverter) -> Result<(), String> {
+    convert_image(from, to, converter, &ImageType::Png)
 }
 
-/// Convert a HEIC image file to the provided format
+/// Convert a HEIC or SVG image file to the provided format
 ///
 /// This uses the macOS builtin `sips` program
 ///
@@ -50,25 +61,28 @@ pub(crate) fn image_copy_convert(
 /// of failing, `sips` will create a file called `fake` in `/`. Subsequent writes
 /// by `sips` to the same location will not fail, but since it is a file instead
 /// of a directory, this will fail for non-`sips` copies.
-fn convert_heic(
+fn convert_image(
     from: &Path,
     to: &Path,
     converter: &ImageConverter,
     output_image_type: &ImageType,
-) -> Option<()> {
-    let (from_path, to_path) = ensure_paths(from, to)?;
+) -> Result<(), String> {
+    write_complete(to, |partial| {
+        let (from_path, to_path) = ensure_paths(from, partial)?;
 
-    let args = match converter {
-        ImageConverter::Sips => vec![
-            "-s",
-            "format",
-            output_image_type.to_str(),
-            from_path,
-            "-o",
-            to_path,
-        ],
-        ImageConverter::Imagemagick => vec![from_path, to_path],
-    };
+        let args = match converter {
+            ImageConverter::Sips => vec![
+                "-s",
+                "format",
+                output_image_type.to_str(),
+                from_path,
+                "-o",
+                to_path,
+            ],
+            ImageConverter::Imagemagick => vec![from_path, to_path],
+        };
 
-    run_command(converter.name(), args)
+        run_command(converter.name(), args)?;
+        check_output(partial, Some(output_image_type))
+    })
 }
@@ -0,0 +1,156 @@
+/*!
+ Defines routines for reading when a photo or video was created, which can differ from when it was sent.
+*/
+
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use log::warn;
+
+use imessage_database::tables::attachment::MediaType;
+
+use crate::app::compatibility::{
+    converters::common::command_output,
+    models::{Converter, ImageConverter, VideoProbe},
+};
+
+/// The pattern `EXIF` dates are stored in, like `2022:05:17 20:29:42`
+const EXIF_DATE_FORMAT: &str = "%Y:%m:%d %H:%M:%S";
+
+/// When a photo or video was created, according to its own metadata
+#[derive(Debug, PartialEq, Eq)]
+pub enum MediaCreated {
+    /// Photos store the time on the camera's clock, without a time zone
+    Camera(NaiveDateTime),
+    /// Videos store the time in UTC
+    Utc(DateTime<Utc>),
+}
+
+/// Read when the photo or video at `path` was created
+///
+/// Other kinds of files, files without a creation date, and files the tools cannot read have no date.
+pub(crate) fn media_created(
+    path: &Path,
+    media_type: &MediaType,
+    image_converter: Option<&ImageConverter>,
+    video_probe: Option<&VideoProbe>,
+) -> Option<MediaCreated> {
+    let path = path.to_str()?;
+    let output = match (media_type, image_converter, video_probe) {
+        (MediaType::Image(_), Some(converter), _) => {
+            command_output(converter.name(), image_date_args(converter, path))
+        }
+        (MediaType::Video(_), _, Some(probe)) => {
+            command_output(probe.name(), video_date_args(probe, path))
+        }
+        _ => return None,
+    };
+
+    match output {
+        Ok(output) => match media_type {
+            MediaType::Image(_) => parse_image_date(&output).map(MediaCreated::Camera),
+            _ => parse_video_date(&output).map(MediaCreated::Utc),
+        },
+        Err(why) => {
+            warn!("Unable to read the creation date of {path}: {why}");
+            None
+        }
+    }
+}
+
+/// Build the arguments that print the date a photo was taken
+///
+/// Docs: <https://www.unix.com/man-page/osx/1/sips/> (or `sips --help properties`)
+///
+/// Docs: <https://imagemagick.org/script/escape.php>
+fn image_date_args<'a>(converter: &ImageConverter, path: &'a str) -> Vec<&'a str> {
+    match converter {
+        ImageConverter::Sips => vec!["-g", "creation", path],
+        ImageConverter::Imagemagick => {
+            vec!["identify", "-format", "%[EXIF:DateTimeOriginal]\n", path]
+        }
+    }
+}
+
+/// Build the arguments that print the date a video was recorded
+///
+/// Docs: <https://ffmpeg.org/ffprobe.html>
+fn video_date_args<'a>(probe: &VideoProbe, path: &'a str) -> Vec<&'a str> {
+    match probe {
+        VideoProbe::Ffprobe => vec![
+            "-v",
+            "error",
+            "-show_entries",
+            "format_tags=creation_time",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+            path,
+        ],
+    }
+}
+
+/// Parse the date a photo was taken from the output of an image tool
+///
+/// `sips` labels the date `creation:`; `magick` prints one line per frame, so the first date is used.
+fn parse_image_date(output: &str) -> Option<NaiveDateTime> {
+    output
+        .lines()
+        .map(|line| line.trim().trim_start_matches("creation:").trim())
+        .find_map(|line| NaiveDateTime::parse_from_str(line, EXIF_DATE_FORMAT).ok())
+}
+
+/// Parse the date a video was recorded from the output of a video tool, like `2022-05-18T03:29:42.000000Z`
+fn parse_video_date(output: &str) -> Option<DateTime<Utc>> {
+    output
+        .lines()
+        .find_map(|line| DateTime::parse_from_rfc3339(line.trim()).ok())
+        .map(|date| date.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    use crate::app::compatibility::converters::metadata::{parse_image_date, parse_video_date};
+
+    #[test]
+    fn can_parse_sips_date() {
+        let output = "/tmp/IMG_0001.HEIC\n  creation: 2022:05:17 20:29:42\n";
+        assert_eq!(
+            parse_image_date(output),
+            NaiveDate::from_ymd_opt(2022, 5, 17).and_then(|date| date.and_hms_opt(20, 29, 42))
+        );
+    }
+
+    #[test]
+    fn can_parse_imagemagick_date() {
+        let output = "2022:05:17 20:29:42\n2022:05:17 20:29:42\n";
+        assert_eq!(
+            parse_image_date(output),
+            NaiveDate::from_ymd_opt(2022, 5, 17).and_then(|date| date.and_hms_opt(20, 29, 42))
+        );
+    }
+
+    #[test]
+    fn cant_parse_missing_image_date() {
+        assert_eq!(
+            parse_image_date("/tmp/IMG_0001.PNG\n  creation: <nil>\n"),
+            None
+        );
+        assert_eq!(parse_image_date(""), None);
+    }
+
+    #[test]
+    fn can_parse_video_date() {
+        assert_eq!(
+            parse_video_date("2022-05-18T03:29:42.000000Z\n"),
+            Utc.with_ymd_and_hms(2022, 5, 18, 3, 29, 42).single()
+        );
+    }
+
+    #[test]
+    fn cant_parse_missing_video_date() {
+        assert_eq!(parse_video_date(""), None);
+        assert_eq!(parse_video_date("N/A\n"), None);
+    }
+}
@@ -4,6 +4,9 @@
 
 pub mod audio;
 pub mod common;
+pub mod gif;
 pub mod image;
+pub mod metadata;
 pub mod sticker;
+pub mod thumbnail;
 pub mod video;
@@ -3,14 +3,19 @@
 */
 
 use std::{
-    fs::{create_dir_all, read_dir, remove_dir_all},
+    fs::read_dir,
     path::{Path, PathBuf},
 };
 
+use log::warn;
+
 use imessage_database::tables::attachment::MediaType;
 
 use crate::app::compatibility::{
-    converters::common::{copy_raw, ensure_paths, run_command},
+    converters::common::{
+        check_output, copy_fallback, copy_raw, ensure_paths, run_command, run_parallel,
+        write_complete, CopyOutcome, TempDir,
+    },
     models::{Converter, ImageConverter, ImageType, VideoConverter},
 };
 
@@ -19,13 +24,21 @@ use crate::app::compatibility::{
 /// - Sticker `HEIC` files convert to `PNG`
 /// - Sticker `HEICS` files convert to `GIF`
 /// - Fallback to the original format
+///
+/// If `keep_original` is set, converted stickers are also copied in their original format
+///
+/// Animated stickers are converted using up to `threads` converter processes at once
 pub(crate) fn sticker_copy_convert(
     from: &Path,
     to: &mut PathBuf,
     image_converter: &ImageConverter,
     video_converter: &Option<VideoConverter>,
     mime_type: MediaType,
-) -> Option<MediaType<'static>> {
+    keep_original: bool,
+    threads: usize,
+) -> CopyOutcome {
+    let mut conversion_error = None;
+
     // Determine the output type of the sticker
     let output_type: Option<ImageType> = match mime_type {
         // Normal stickers get converted to png
@@ -42,22 +55,45 @@ pub(crate) fn sticker_copy_convert(
         // Fall back to the normal converter if this fails
         if matches!(output_type, ImageType::Gif) {
             if let Some(video_converter) = video_converter {
-                if convert_heics(from, to, video_converter).is_some() {
-                    return Some(MediaType::Image(output_type.to_str()));
+                match convert_heics(from, to, video_converter, threads) {
+                    Ok(()) => return converted(from, to, &output_type, keep_original),
+                    Err(why) => warn!("Unable to convert {from:?} to an animation: {why}"),
                 }
             }
         }
 
         // Standard `HEIC` converter
-        if convert_heic(from, to, image_converter, &output_type).is_none() {
-            eprintln!("Unable to convert {from:?}");
-        } else {
-            return Some(MediaType::Image(output_type.to_str()));
+        match convert_heic(from, to, image_converter, &output_type) {
+            Ok(()) => return converted(from, to, &output_type, keep_original),
+            Err(why) => {
+                warn!("Unable to convert {from:?}: {why}");
+                conversion_error = Some(why);
+            }
         }
     }
 
-    copy_raw(from, to);
-    None
+    copy_fallback(from, to, conversion_error)
+}
+
+/// Describe a successful conversion, copying the original next to the converted file if requested
+fn converted(from: &Path, to: &Path, output_type: &ImageType, keep_original: bool) -> CopyOutcome {
+    let media_type = MediaType::Image(output_type.to_str());
+    match keep_original.then(|| copy_original(from, to)).flatten() {
+        Some(original) => CopyOutcome::ConvertedWithOriginal(media_type, original),
+        None => CopyOutcome::Converted(media_type),
+    }
+}
+
+/// Copy the original sticker next to its converted file, i.e. `1.png` and `1.heic`
+fn copy_original(from: &Path, to: &Path) -> Option<PathBuf> {
+    let original = to.with_extension(from.extension()?);
+    match copy_raw(from, &original) {
+        Ok(()) => Some(original),
+        Err(why) => {
+            warn!("Unable to keep original sticker: {why}");
+            None
+        }
+    }
 }
 
 /// Convert a HEIC sticker file to the provided format
@@ -81,133 +117,249 @@ fn convert_heic(
     to: &Path,
     converter: &ImageConverter,
     output_image_type: &ImageType,
-) -> Option<()> {
-    let (from_path, to_path) = ensure_paths(from, to)?;
-
-    match converter {
-        ImageConverter::Sips => {
-            let args = vec![
-                "-s",
-                "format",
-                output_image_type.to_str(),
-                from_path,
-                "-o",
-                to_path,
-            ];
-            run_command(converter.name(), args)
-        }
-        ImageConverter::Imagemagick => {
-            let formatted_from = format!("{from_path}[0]");
-            let args = vec![&formatted_from, to_path];
-            run_command(converter.name(), args)
-        }
-    }
+) -> Result<(), String> {
+    write_complete(to, |partial| {
+        let (from_path, to_path) = ensure_paths(from, partial)?;
+
+        match converter {
+            ImageConverter::Sips => {
+                let args = vec![
+                    "-s",
+                    "format",
+                    output_image_type.to_str(),
+                    from_path,
+                    "-o",
+                    to_path,
+                ];
+                run_command(converter.name(), args)
+            }
+            ImageConverter::Imagemagick => {
+                let formatted_from = format!("{from_path}[0]");
+                let args = vec![&formatted_from, to_path];
+                run_command(converter.name(), args)
+            }
+        }?;
+        check_output(partial, Some(output_image_type))
+    })
 }
 
-fn convert_heics(from: &Path, to: &Path, video_converter: &VideoConverter) -> Option<()> {
-    let (from_path, to_path) = ensure_paths(from, to)?;
+/// Frames are merged with their transparency masks on up to `threads` threads at once
+fn convert_heics(
+    from: &Path,
+    to: &Path,
+    video_converter: &VideoConverter,
+    threads: usize,
+) -> Result<(), String> {
+    write_complete(to, |partial| {
+        let (from_path, to_path) = ensure_paths(from, partial)?;
 
-    // Frames per second in the original sticker, generated by Apple
-    let fps = 10;
+        // Frames per second in the original sticker, generated by Apple
+        let fps = 10;
 
-    // Directory to store intermediate renders
-    let tmp_path = PathBuf::from("/tmp/imessage");
-    // Ensure the temp directory tree exists
-    if !tmp_path.exists() {
-        if let Err(why) = create_dir_all(&tmp_path) {
-            eprintln!("Unable to create {tmp_path:?}: {why}");
-            return None;
-        }
-    }
-    let tmp = tmp_path.to_str()?;
-
-    match video_converter {
-        VideoConverter::Ffmpeg => {
-            // HEICS format contains 4 video streams
-            // The first one is the first still
-            // Stream #0:0[0x1]: Video: hevc (Main) (hvc1 / 0x31637668), yuv420p(tv, smpte170m/unknown/unknown), 524x600, 1 fps, 1 tbr, 1 tbn (default)
-            // The second one is the alpha mask for the first still
-            // Stream #0:1[0x2]: Video: hevc (Rext) (hvc1 / 0x31637668), gray(pc), 524x600, 1 fps, 1 tbr, 1 tbn
-
-            // The third stream is the video data
-            // Stream #0:2[0x1](und): Video: hevc (Main) (hvc1 / 0x31637668), yuv420p(tv, smpte170m/unknown/unknown), 524x600, 1370 kb/s, 22.98 fps, 30 tbr, 600 tbn (default)
-            run_command(
-                video_converter.name(),
-                vec![
-                    "-i",
-                    from_path,
-                    "-map",
-                    "0:2",
-                    "-y",
-                    &format!("{tmp}/frame_%04d.png"),
-                ],
-            )?;
-
-            // The fourth stream is the alpha mask
-            // Stream #0:3[0x2](und): Video: hevc (Rext) (hvc1 / 0x31637668), gray(pc), 524x600, 426 kb/s, 22.98 fps, 30 tbr, 600 tbn (default)
-            run_command(
-                video_converter.name(),
-                vec![
-                    "-i",
-                    from_path,
-                    "-map",
-                    "0:3",
-                    "-y",
-                    &format!("{tmp}/alpha_%04d.png"),
-                ],
-            )?;
-
-            // This step applies the transparency mask to the images
-            let files = read_dir(tmp).ok()?;
-            let num_frames = &files.into_iter().count() / 2;
-            (0..num_frames).try_for_each(|item| {
+        // Directory to store intermediate renders, removed when the conversion ends
+        let tmp_dir = TempDir::new(PathBuf::from("/tmp/imessage"))?;
+        let tmp = tmp_dir
+            .path()
+            .to_str()
+            .ok_or(format!("{:?} is not a valid UTF-8 path", tmp_dir.path()))?;
+
+        match video_converter {
+            VideoConverter::Ffmpeg => {
+                // HEICS format contains 4 video streams
+                // The first one is the first still
+                // Stream #0:0[0x1]: Video: hevc (Main) (hvc1 / 0x31637668), yuv420p(tv, smpte170m/unknown/unknown), 524x600, 1 fps, 1 tbr, 1 tbn (default)
+                // The second one is the alpha mask for the first still
+                // Stream #0:1[0x2]: Video: hevc (Rext) (hvc1 / 0x31637668), gray(pc), 524x600, 1 fps, 1 tbr, 1 tbn
+
+                // The third stream is the video data
+                // Stream #0:2[0x1](und): Video: hevc (Main) (hvc1 / 0x31637668), yuv420p(tv, smpte170m/unknown/unknown), 524x600, 1370 kb/s, 22.98 fps, 30 tbr, 600 tbn (default)
                 run_command(
                     video_converter.name(),
                     vec![
                         "-i",
-                        &format!("{tmp}/frame_{:04}.png", item),
+                        from_path,
+                        "-map",
+                        "0:2",
+                        "-y",
+                        &format!("{tmp}/frame_%04d.png"),
+                    ],
+                )?;
+
+                // The fourth stream is the alpha mask
+                // Stream #0:3[0x2](und): Video: hevc (Rext) (hvc1 / 0x31637668), gray(pc), 524x600, 426 kb/s, 22.98 fps, 30 tbr, 600 tbn (default)
+                run_command(
+                    video_converter.name(),
+                    vec![
                         "-i",
-                        &format!("{tmp}/alpha_{:04}.png", item),
-                        "-filter_complex",
-                        "[1:v]format=gray,geq=lum='p(X,Y)':a='p(X,Y)'[mask];[0:v][mask]alphamerge",
-                        &format!("{tmp}/merged_{:04}.png", item),
+                        from_path,
+                        "-map",
+                        "0:3",
+                        "-y",
+                        &format!("{tmp}/alpha_%04d.png"),
                     ],
-                )
-            })?;
-
-            // Once we have the transparent frames,
-            // we use the first frame to generate a transparency palette
-            run_command(
-                video_converter.name(),
-                vec![
-                    "-i",
-                    &format!("{tmp}/merged_0001.png"),
-                    "-vf",
-                    "palettegen=reserve_transparent=1",
-                    &format!("{tmp}/palette.png"),
-                ],
-            )?;
-
-            // Create the gif from the parts we parsed above
-            run_command(
-                video_converter.name(),
-                vec![
-                    "-i",
-                    &format!("{tmp}/merged_%04d.png"),
-                    "-i",
-                    &format!("{tmp}/palette.png"),
-                    "-lavfi",
-                    &format!("fps={fps},paletteuse=alpha_threshold=128"),
-                    "-gifflags",
-                    "-offsetting",
-                    to_path,
-                ],
-            )?;
+                )?;
+
+                // This step applies the transparency mask to the images
+                let files = read_dir(tmp).map_err(|why| format!("Unable to read {tmp}: {why}"))?;
+                let num_frames = &files.into_iter().count() / 2;
+                let frames: Vec<usize> = (0..num_frames).collect();
+                run_parallel(&frames, threads, |item| {
+                    run_command(
+                        video_converter.name(),
+                        vec![
+                            "-i",
+                            &format!("{tmp}/frame_{:04}.png", item),
+                            "-i",
+                            &format!("{tmp}/alpha_{:04}.png", item),
+                            "-filter_complex",
+                            "[1:v]format=gray,geq=lum='p(X,Y)':a='p(X,Y)'[mask];[0:v][mask]alphamerge",
+                            &format!("{tmp}/merged_{:04}.png", item),
+                        ],
+                    )
+                })?;
+
+                // Once we have the transparent frames,
+                // we use the first frame to generate a transparency palette
+                run_command(
+                    video_converter.name(),
+                    vec![
+                        "-i",
+                        &format!("{tmp}/merged_0001.png"),
+                        "-vf",
+                        "palettegen=reserve_transparent=1",
+                        &format!("{tmp}/palette.png"),
+                    ],
+                )?;
+
+                // Create the gif from the parts we parsed above
+                run_command(
+                    video_converter.name(),
+                    vec![
+                        "-i",
+                        &format!("{tmp}/merged_%04d.png"),
+                        "-i",
+                        &format!("{tmp}/palette.png"),
+                        "-lavfi",
+                        &format!("fps={fps},paletteuse=alpha_threshold=128"),
+                        "-gifflags",
+                        "-offsetting",
+                        to_path,
+                    ],
+                )?;
+
+                check_output(partial, Some(&ImageType::Gif))
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::{current_dir, temp_dir},
+        fs::{create_dir_all, read, remove_dir_all, write},
+        path::Path,
+    };
+
+    use super::{convert_heic, copy_original};
+    use crate::app::compatibility::models::{exists, Converter, ImageConverter, ImageType};
+
+    const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    const FIXTURES: [&str; 5] = [
+        "no_effect.heic",
+        "outline.heic",
+        "puffy.heic",
+        "shiny.heic",
+        "comic.heic",
+    ];
 
-            // Remove all of the generated files
-            remove_dir_all(tmp).ok()?;
+    /// Read the width and height from a PNG file's `IHDR` chunk, or [`None`] if the file is not a valid PNG
+    fn png_dimensions(path: &Path) -> Option<(u32, u32)> {
+        let bytes = read(path).ok()?;
+        if bytes.len() < 24 || bytes[..8] != PNG_SIGNATURE || &bytes[12..16] != b"IHDR" {
+            return None;
+        }
+        let width = u32::from_be_bytes(bytes[16..20].try_into().ok()?);
+        let height = u32::from_be_bytes(bytes[20..24].try_into().ok()?);
+        Some((width, height))
+    }
+
+    #[test]
+    fn can_read_png_dimensions() {
+        let png = current_dir()
+            .unwrap()
+            .join("src/exporters/resources/attachments/shark.png");
+        assert!(png_dimensions(&png).is_some());
+    }
 
-            Some(())
+    #[test]
+    fn cant_read_png_dimensions_heic() {
+        let heic = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/stickers/no_effect.heic");
+        assert!(png_dimensions(&heic).is_none());
+    }
+
+    #[test]
+    fn can_copy_original() {
+        let dir = temp_dir().join("imessage-sticker-original");
+        create_dir_all(&dir).unwrap();
+        let from = dir.join("sticker.heic");
+        write(&from, b"heic").unwrap();
+
+        let original = copy_original(&from, &dir.join("out/1.png"));
+        assert_eq!(original, Some(dir.join("out/1.heic")));
+        assert_eq!(read(dir.join("out/1.heic")).unwrap(), b"heic");
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_convert_sticker_with_sips_and_imagemagick() {
+        // Both converters are required to compare their output
+        let converters = [ImageConverter::Sips, ImageConverter::Imagemagick];
+        if let Some(missing) = converters.iter().find(|c| !exists(c.name())) {
+            eprintln!(
+                "Skipping sticker parity test: `{}` is not installed",
+                missing.name()
+            );
+            return;
         }
+
+        let fixture_dir = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/stickers");
+        let output_dir = temp_dir().join("imessage-sticker-parity");
+
+        for fixture in FIXTURES {
+            let from = fixture_dir.join(fixture);
+            let mut dimensions = vec![];
+
+            for converter in &converters {
+                let mut to = output_dir.join(converter.name()).join(fixture);
+                to.set_extension(ImageType::Png.to_str());
+                create_dir_all(to.parent().unwrap()).unwrap();
+
+                assert!(convert_heic(&from, &to, converter, &ImageType::Png).is_ok());
+                let size = png_dimensions(&to);
+                assert!(
+                    size.is_some(),
+                    "{} did not produce a valid PNG for {fixture}",
+                    converter.name()
+                );
+                dimensions.push(size);
+            }
+
+            assert_eq!(
+                dimensions[0], dimensions[1],
+                "Dimensions differ for {fixture}"
+            );
+        }
+
+        remove_dir_all(output_dir).unwrap();
     }
 }
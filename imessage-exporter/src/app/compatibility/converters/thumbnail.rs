@@ -0,0 +1,125 @@
+/*!
+ Defines routines for generating image thumbnails.
+*/
+
+use std::path::{Path, PathBuf};
+
+use log::warn;
+
+use crate::app::compatibility::{
+    converters::common::{check_output, ensure_paths, run_command, write_complete},
+    models::{Converter, ImageConverter, ImageType},
+};
+
+/// Generate a thumbnail next to a copied image, returning its path
+///
+/// - `PNG` files keep their format to preserve transparency
+/// - All other images convert to `JPEG`
+/// - Existing thumbnails are reused
+pub(crate) fn thumbnail(image: &Path, converter: &ImageConverter, size: u32) -> Option<PathBuf> {
+    let output_type = match image.extension()?.to_str()?.to_lowercase().as_str() {
+        "png" => ImageType::Png,
+        _ => ImageType::Jpeg,
+    };
+
+    let mut to = image.with_file_name(format!("{}_thumbnail", image.file_stem()?.to_str()?));
+    to.set_extension(output_type.to_str());
+
+    if !to.exists() {
+        let generated = write_complete(&to, |partial| {
+            let (from_path, to_path) = ensure_paths(image, partial)?;
+            let args = thumbnail_args(converter, size, &output_type, from_path, to_path);
+            run_command(converter.name(), args.iter().map(String::as_str).collect())?;
+            check_output(partial, Some(&output_type))
+        });
+        if let Err(why) = generated {
+            warn!("Unable to generate a thumbnail for {image:?}: {why}");
+            return None;
+        }
+    }
+
+    to.exists().then_some(to)
+}
+
+/// Build the arguments that scale an image down so that neither side is larger than `size`
+///
+/// Docs: <https://www.unix.com/man-page/osx/1/sips/> (or `man sips`)
+///
+/// `magick` only takes the first frame of animated images, and the trailing `>` keeps
+/// it from enlarging images that are already smaller than the thumbnail.
+fn thumbnail_args(
+    converter: &ImageConverter,
+    size: u32,
+    output_image_type: &ImageType,
+    from_path: &str,
+    to_path: &str,
+) -> Vec<String> {
+    match converter {
+        ImageConverter::Sips => vec![
+            "-Z".to_string(),
+            size.to_string(),
+            "-s".to_string(),
+            "format".to_string(),
+            output_image_type.to_str().to_string(),
+            from_path.to_string(),
+            "-o".to_string(),
+            to_path.to_string(),
+        ],
+        ImageConverter::Imagemagick => vec![
+            format!("{from_path}[0]"),
+            "-thumbnail".to_string(),
+            format!("{size}x{size}>"),
+            to_path.to_string(),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::app::compatibility::{
+        converters::thumbnail::{thumbnail, thumbnail_args},
+        models::{ImageConverter, ImageType},
+    };
+
+    #[test]
+    fn can_build_sips_args() {
+        assert_eq!(
+            thumbnail_args(
+                &ImageConverter::Sips,
+                256,
+                &ImageType::Jpeg,
+                "a.heic",
+                "b.jpeg"
+            ),
+            vec!["-Z", "256", "-s", "format", "jpeg", "a.heic", "-o", "b.jpeg"]
+        );
+    }
+
+    #[test]
+    fn can_build_imagemagick_args() {
+        assert_eq!(
+            thumbnail_args(
+                &ImageConverter::Imagemagick,
+                128,
+                &ImageType::Png,
+                "a.png",
+                "b.png"
+            ),
+            vec!["a.png[0]", "-thumbnail", "128x128>", "b.png"]
+        );
+    }
+
+    #[test]
+    fn cant_thumbnail_without_extension() {
+        assert_eq!(
+            thumbnail(
+                Path::new("/tmp/no_extension"),
+                &ImageConverter::Imagemagick,
+                256
+            ),
+            None
+        );
+    }
+}
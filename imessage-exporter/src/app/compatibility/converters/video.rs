@@ -4,10 +4,14 @@
 
 use std::path::{Path, PathBuf};
 
+use log::warn;
+
 use imessage_database::tables::attachment::MediaType;
 
 use crate::app::compatibility::{
-    converters::common::{copy_raw, ensure_paths, run_command},
+    converters::common::{
+        check_output, copy_fallback, ensure_paths, run_command, write_complete, CopyOutcome,
+    },
     models::{Converter, VideoConverter, VideoType},
 };
 
@@ -20,7 +24,8 @@ pub(crate) fn video_copy_convert(
     to: &mut PathBuf,
     converter: &VideoConverter,
     mime_type: MediaType,
-) -> Option<MediaType<'static>> {
+) -> CopyOutcome {
+    let mut conversion_error = None;
     if matches!(
         mime_type,
         MediaType::Video("mov") | MediaType::Video("MOV") | MediaType::Video("quicktime")
@@ -28,23 +33,27 @@ pub(crate) fn video_copy_convert(
         let output_type = VideoType::Mp4;
         // Update extension for conversion
         to.set_extension(output_type.to_str());
-        if convert_mov(from, to, converter).is_none() {
-            eprintln!("Unable to convert {from:?}");
-        } else {
-            return Some(MediaType::Video(output_type.to_str()));
+        match convert_mov(from, to, converter) {
+            Ok(()) => return CopyOutcome::Converted(MediaType::Video(output_type.to_str())),
+            Err(why) => {
+                warn!("Unable to convert {from:?}: {why}");
+                conversion_error = Some(why);
+            }
         }
     }
 
     // Fallback
-    copy_raw(from, to);
-    None
+    copy_fallback(from, to, conversion_error)
 }
 
-fn convert_mov(from: &Path, to: &Path, converter: &VideoConverter) -> Option<()> {
-    let (from_path, to_path) = ensure_paths(from, to)?;
+fn convert_mov(from: &Path, to: &Path, converter: &VideoConverter) -> Result<(), String> {
+    write_complete(to, |partial| {
+        let (from_path, to_path) = ensure_paths(from, partial)?;
 
-    let args = match converter {
-        VideoConverter::Ffmpeg => vec!["-i", from_path, to_path],
-    };
-    run_command(converter.name(), args)
+        let args = match converter {
+            VideoConverter::Ffmpeg => vec!["-i", from_path, to_path],
+        };
+        run_command(converter.name(), args)?;
+        check_output(partial, None)
+    })
 }
@@ -5,3 +5,4 @@
 pub mod attachment_manager;
 pub mod converters;
 pub mod models;
+pub mod prompt;
@@ -7,6 +7,8 @@ use std::{
     process::{Command, Stdio},
 };
 
+use log::warn;
+
 pub trait Converter {
     /// Determine the converter type for the current shell environment
     fn determine() -> Option<Self>
@@ -34,6 +36,15 @@ impl ImageType {
             Self::Png => "png",
         }
     }
+
+    /// The bytes every file of this type starts with
+    pub fn signature(&self) -> &'static [u8] {
+        match self {
+            Self::Jpeg => &[0xFF, 0xD8, 0xFF],
+            Self::Gif => b"GIF8",
+            Self::Png => &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A],
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -78,7 +89,7 @@ impl Converter for ImageConverter {
         if exists(ImageConverter::Imagemagick.name()) {
             return Some(ImageConverter::Imagemagick);
         }
-        eprintln!("No HEIC converter found, image attachments will not be converted!");
+        warn!("No HEIC converter found, image attachments will not be converted!");
         None
     }
 
@@ -112,7 +123,7 @@ impl Converter for AudioConverter {
         if exists(AudioConverter::Ffmpeg.name()) {
             return Some(AudioConverter::Ffmpeg);
         }
-        eprintln!("No CAF converter found, audio attachments will not be converted!");
+        warn!("No CAF converter found, audio attachments will not be converted!");
         None
     }
 
@@ -141,7 +152,7 @@ impl Converter for VideoConverter {
         if exists(VideoConverter::Ffmpeg.name()) {
             return Some(VideoConverter::Ffmpeg);
         }
-        eprintln!("No MOV converter found, video attachments will not be converted!");
+        warn!("No MOV converter found, video attachments will not be converted!");
         None
     }
 
@@ -158,9 +169,70 @@ impl Display for VideoConverter {
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+/// Program used to optimize animated GIFs
+pub enum GifOptimizer {
+    Gifsicle,
+    Ffmpeg,
+}
+
+impl Converter for GifOptimizer {
+    fn determine() -> Option<GifOptimizer> {
+        if exists(GifOptimizer::Gifsicle.name()) {
+            return Some(GifOptimizer::Gifsicle);
+        }
+        if exists(GifOptimizer::Ffmpeg.name()) {
+            return Some(GifOptimizer::Ffmpeg);
+        }
+        warn!("No GIF optimizer found, GIF attachments will not be optimized!");
+        None
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            GifOptimizer::Gifsicle => "gifsicle",
+            GifOptimizer::Ffmpeg => "ffmpeg",
+        }
+    }
+}
+
+impl Display for GifOptimizer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+/// Program used to read the metadata of videos
+pub enum VideoProbe {
+    Ffprobe,
+}
+
+impl Converter for VideoProbe {
+    fn determine() -> Option<VideoProbe> {
+        if exists(VideoProbe::Ffprobe.name()) {
+            return Some(VideoProbe::Ffprobe);
+        }
+        warn!("No video metadata reader found, video creation dates will not be read!");
+        None
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            VideoProbe::Ffprobe => "ffprobe",
+        }
+    }
+}
+
+impl Display for VideoProbe {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
 /// Determine if a shell program exists on the system
 #[cfg(not(target_family = "windows"))]
-fn exists(name: &str) -> bool {
+pub(crate) fn exists(name: &str) -> bool {
     if let Ok(process) = Command::new("type")
         .args(vec![name])
         .stdout(Stdio::null())
@@ -177,7 +249,7 @@ fn exists(name: &str) -> bool {
 
 /// Determine if a shell program exists on the system
 #[cfg(target_family = "windows")]
-fn exists(name: &str) -> bool {
+pub(crate) fn exists(name: &str) -> bool {
     Command::new("where")
         .arg(name)
         .output()
@@ -0,0 +1,115 @@
+/*!
+ Defines the prompt shown when an attachment cannot be converted in interactive mode.
+*/
+
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+};
+
+/// Represents what the user wants to do with an attachment that could not be converted
+#[derive(PartialEq, Eq, Debug)]
+pub enum ConversionChoice {
+    /// Run the converter again
+    Retry,
+    /// Keep the copy of the original file
+    Skip,
+    /// Stop the export
+    Abort,
+}
+
+impl ConversionChoice {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_input(input: &str) -> Option<Self> {
+        match input.trim().to_lowercase().as_str() {
+            "r" | "retry" => Some(Self::Retry),
+            "s" | "skip" => Some(Self::Skip),
+            "a" | "abort" => Some(Self::Abort),
+            _ => None,
+        }
+    }
+}
+
+/// Show why an attachment could not be converted and ask the user what to do about it
+///
+/// Invalid answers are asked again. If `input` is closed, the attachment is skipped, which is
+/// what non-interactive exports do.
+pub fn prompt_conversion_failure(
+    from: &Path,
+    why: &str,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> ConversionChoice {
+    let _ = writeln!(output, "Unable to convert {from:?}:\n{why}");
+    loop {
+        let _ = write!(
+            output,
+            "[r]etry, [s]kip and copy the original, or [a]bort the export? "
+        );
+        let _ = output.flush();
+
+        let mut answer = String::new();
+        match input.read_line(&mut answer) {
+            Ok(0) | Err(_) => return ConversionChoice::Skip,
+            Ok(_) => {
+                if let Some(choice) = ConversionChoice::from_input(&answer) {
+                    return choice;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, path::Path};
+
+    use crate::app::compatibility::prompt::{prompt_conversion_failure, ConversionChoice};
+
+    #[test]
+    fn can_parse_choice_any_case() {
+        assert_eq!(
+            ConversionChoice::from_input("R\n"),
+            Some(ConversionChoice::Retry)
+        );
+        assert_eq!(
+            ConversionChoice::from_input("skip"),
+            Some(ConversionChoice::Skip)
+        );
+        assert_eq!(
+            ConversionChoice::from_input(" Abort "),
+            Some(ConversionChoice::Abort)
+        );
+    }
+
+    #[test]
+    fn cant_parse_invalid_choice() {
+        assert_eq!(ConversionChoice::from_input("x"), None);
+        assert_eq!(ConversionChoice::from_input(""), None);
+    }
+
+    #[test]
+    fn can_prompt_until_valid() {
+        let mut input = Cursor::new("maybe\n\nretry\n");
+        let mut output = vec![];
+        let choice = prompt_conversion_failure(
+            Path::new("a.heic"),
+            "sips: unable to render",
+            &mut input,
+            &mut output,
+        );
+
+        assert_eq!(choice, ConversionChoice::Retry);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("sips: unable to render"));
+        assert_eq!(output.matches("[r]etry").count(), 3);
+    }
+
+    #[test]
+    fn can_skip_closed_input() {
+        let mut input = Cursor::new("");
+        let choice = prompt_conversion_failure(Path::new("a.heic"), "why", &mut input, &mut vec![]);
+
+        assert_eq!(choice, ConversionChoice::Skip);
+    }
+}
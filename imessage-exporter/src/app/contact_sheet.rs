@@ -0,0 +1,115 @@
+/*!
+ Contains the data written to the page that lists the people in a group conversation when `--contact-sheets` is enabled.
+*/
+
+use std::collections::{BTreeMap, HashSet};
+
+/// What is known about one person in a conversation
+#[derive(Debug, PartialEq, Eq)]
+pub struct Participant {
+    /// The name the person is shown with in the conversation
+    pub name: String,
+    /// The phone number or email address the person messaged from
+    pub handle: String,
+    /// The number of messages the person sent, not counting tapbacks
+    pub messages: usize,
+    /// The date of the person's first message, if they sent any
+    pub first_date: Option<i64>,
+    /// The date of the person's last message, if they sent any
+    pub last_date: Option<i64>,
+}
+
+/// The people in a conversation, accumulated as its messages are exported
+#[derive(Debug, Default)]
+pub struct ContactSheet {
+    /// Map of each person's handle to what is known about them
+    participants: BTreeMap<String, Participant>,
+    /// The chats whose members were already added, since several chats can be written to one conversation
+    chats: HashSet<i32>,
+}
+
+impl ContactSheet {
+    /// Remember that the members of `chat_id` were added, returning `true` if they were not added before
+    pub fn add_chat(&mut self, chat_id: i32) -> bool {
+        self.chats.insert(chat_id)
+    }
+
+    /// Add a person to the conversation, even if they never send a message in it
+    pub fn add_participant(&mut self, name: &str, handle: &str) -> &mut Participant {
+        self.participants
+            .entry(handle.to_string())
+            .or_insert_with(|| Participant {
+                name: name.to_string(),
+                handle: handle.to_string(),
+                messages: 0,
+                first_date: None,
+                last_date: None,
+            })
+    }
+
+    /// Count a message a person sent at `date`
+    pub fn add_message(&mut self, name: &str, handle: &str, date: i64) {
+        let participant = self.add_participant(name, handle);
+        participant.messages += 1;
+        participant.first_date = Some(participant.first_date.map_or(date, |first| first.min(date)));
+        participant.last_date = Some(participant.last_date.map_or(date, |last| last.max(date)));
+    }
+
+    /// Get the people in the conversation, starting with whoever sent the most messages
+    ///
+    /// People who sent the same number of messages are ordered by name.
+    pub fn participants(&self) -> Vec<&Participant> {
+        let mut participants: Vec<&Participant> = self.participants.values().collect();
+        participants.sort_by(|a, b| b.messages.cmp(&a.messages).then(a.name.cmp(&b.name)));
+        participants
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::contact_sheet::{ContactSheet, Participant};
+
+    #[test]
+    fn can_add_chat_once() {
+        let mut sheet = ContactSheet::default();
+        assert!(sheet.add_chat(1));
+        assert!(!sheet.add_chat(1));
+        assert!(sheet.add_chat(2));
+    }
+
+    #[test]
+    fn can_count_messages() {
+        let mut sheet = ContactSheet::default();
+        sheet.add_participant("Carol", "+15555550103");
+        sheet.add_message("Bob", "+15555550102", 300);
+        sheet.add_message("Alice", "+15555550101", 200);
+        sheet.add_message("Bob", "+15555550102", 100);
+
+        assert_eq!(
+            sheet.participants(),
+            [
+                &Participant {
+                    name: "Bob".to_string(),
+                    handle: "+15555550102".to_string(),
+                    messages: 2,
+                    first_date: Some(100),
+                    last_date: Some(300),
+                },
+                &Participant {
+                    name: "Alice".to_string(),
+                    handle: "+15555550101".to_string(),
+                    messages: 1,
+                    first_date: Some(200),
+                    last_date: Some(200),
+                },
+                &Participant {
+                    name: "Carol".to_string(),
+                    handle: "+15555550103".to_string(),
+                    messages: 0,
+                    first_date: None,
+                    last_date: None,
+                },
+            ]
+        );
+    }
+}
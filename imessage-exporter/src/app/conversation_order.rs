@@ -0,0 +1,127 @@
+/*!
+ Contains data structures used to decide the order conversations are written in.
+*/
+
+use std::{cmp::Ordering, fmt::Display};
+
+/// Represents the key conversations are sorted by
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy)]
+pub enum ConversationOrder {
+    /// Alphabetically by file name
+    Name,
+    /// Most recent message first
+    #[default]
+    Recency,
+    /// Most messages first
+    MessageCount,
+}
+
+/// What is known about a conversation when deciding its place in the output
+#[derive(Debug)]
+pub struct ConversationStats<'a> {
+    /// The name of the conversation's export file
+    pub name: &'a str,
+    /// The date of the conversation's most recent message
+    pub last_date: i64,
+    /// The number of messages in the conversation
+    pub messages: usize,
+}
+
+impl ConversationOrder {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(order: &str) -> Option<Self> {
+        match order.to_lowercase().as_str() {
+            "name" => Some(Self::Name),
+            "recency" => Some(Self::Recency),
+            "message-count" => Some(Self::MessageCount),
+            _ => None,
+        }
+    }
+
+    /// Compare two conversations, where the conversation written first is [`Ordering::Less`]
+    ///
+    /// Conversations that tie are ordered by name, so the output does not change between runs.
+    pub fn compare(&self, a: &ConversationStats, b: &ConversationStats) -> Ordering {
+        let by_name = a.name.cmp(b.name);
+        match self {
+            ConversationOrder::Name => by_name,
+            ConversationOrder::Recency => b.last_date.cmp(&a.last_date).then(by_name),
+            ConversationOrder::MessageCount => b.messages.cmp(&a.messages).then(by_name),
+        }
+    }
+}
+
+impl Display for ConversationOrder {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConversationOrder::Name => write!(fmt, "name"),
+            ConversationOrder::Recency => write!(fmt, "recency"),
+            ConversationOrder::MessageCount => write!(fmt, "message-count"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::conversation_order::{ConversationOrder, ConversationStats};
+
+    fn stats() -> Vec<ConversationStats<'static>> {
+        vec![
+            ConversationStats {
+                name: "Bob",
+                last_date: 200,
+                messages: 5,
+            },
+            ConversationStats {
+                name: "Alice",
+                last_date: 100,
+                messages: 10,
+            },
+            ConversationStats {
+                name: "Carol",
+                last_date: 300,
+                messages: 5,
+            },
+        ]
+    }
+
+    fn sorted(order: ConversationOrder) -> Vec<&'static str> {
+        let mut stats = stats();
+        stats.sort_by(|a, b| order.compare(a, b));
+        stats.iter().map(|stats| stats.name).collect()
+    }
+
+    #[test]
+    fn can_parse_order_any_case() {
+        assert_eq!(
+            ConversationOrder::from_cli("Name"),
+            Some(ConversationOrder::Name)
+        );
+        assert_eq!(
+            ConversationOrder::from_cli("message-count"),
+            Some(ConversationOrder::MessageCount)
+        );
+        assert_eq!(ConversationOrder::from_cli("size"), None);
+    }
+
+    #[test]
+    fn can_sort_by_name() {
+        assert_eq!(sorted(ConversationOrder::Name), ["Alice", "Bob", "Carol"]);
+    }
+
+    #[test]
+    fn can_sort_by_recency() {
+        assert_eq!(
+            sorted(ConversationOrder::Recency),
+            ["Carol", "Bob", "Alice"]
+        );
+    }
+
+    #[test]
+    fn can_sort_by_message_count_then_name() {
+        assert_eq!(
+            sorted(ConversationOrder::MessageCount),
+            ["Alice", "Bob", "Carol"]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+/*!
+ Contains routines used to embed files in exported documents as [data URIs](https://www.rfc-editor.org/rfc/rfc2397).
+*/
+
+use std::{fs::read, path::Path};
+
+use imessage_database::util::size::format_file_size;
+
+/// Largest file embedded in a single file archive; larger files are left out to keep the archive small enough to open
+pub const MAX_EMBEDDED_SIZE: u64 = 10 * 1024 * 1024;
+
+/// The alphabet used to encode data as base64, from [RFC 4648](https://www.rfc-editor.org/rfc/rfc4648#section-4)
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes as padded base64
+pub fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (idx, byte)| {
+            group | (u32::from(*byte) << (16 - 8 * idx))
+        });
+        // Each group of 3 bytes becomes 4 characters, padded if the group is short
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                out.push(BASE64_ALPHABET[((group >> (18 - 6 * idx)) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Read a file into a data URI with the given MIME type
+///
+/// Files larger than [`MAX_EMBEDDED_SIZE`] are not read; the error describes why the file was left out.
+pub fn data_uri(path: &Path, mime_type: &str) -> Result<String, String> {
+    let size = path
+        .metadata()
+        .map_err(|why| format!("Unable to read {path:?}: {why}"))?
+        .len();
+    if size > MAX_EMBEDDED_SIZE {
+        return Err(format!(
+            "{} is larger than the {} embedding limit",
+            format_file_size(size),
+            format_file_size(MAX_EMBEDDED_SIZE)
+        ));
+    }
+    let bytes = read(path).map_err(|why| format!("Unable to read {path:?}: {why}"))?;
+    Ok(format!("data:{mime_type};base64,{}", base64(&bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{remove_file, write},
+    };
+
+    use crate::app::data_uri::{base64, data_uri};
+
+    #[test]
+    fn can_encode_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xFF, 0xFE, 0xFD]), "//79");
+    }
+
+    #[test]
+    fn can_build_data_uri() {
+        let path = temp_dir().join("imessage-data-uri.bin");
+        write(&path, "hello").unwrap();
+
+        assert_eq!(
+            data_uri(&path, "text/plain").unwrap(),
+            "data:text/plain;base64,aGVsbG8="
+        );
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn cant_build_data_uri_missing_file() {
+        let path = temp_dir().join("imessage-data-uri-missing.bin");
+        assert!(data_uri(&path, "text/plain").is_err());
+    }
+}
@@ -0,0 +1,97 @@
+/*!
+ Contains data structures used to decide which contacts and chats are treated as the same one.
+*/
+
+use std::{collections::HashMap, fmt::Display};
+
+use imessage_database::tables::table::Deduplicate;
+
+/// Represents how aggressively duplicate contacts and chats are merged
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy)]
+pub enum DedupeStrategy {
+    /// Keep every handle and chat separate
+    None,
+    /// Merge handles with the same phone number or email address, and chats with the same participants
+    Exact,
+    /// Also merge the phone numbers and email addresses that belong to the same contact
+    #[default]
+    Contact,
+}
+
+impl DedupeStrategy {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(strategy: &str) -> Option<Self> {
+        match strategy.to_lowercase().as_str() {
+            "none" => Some(Self::None),
+            "exact" => Some(Self::Exact),
+            "contact" => Some(Self::Contact),
+            _ => None,
+        }
+    }
+
+    /// Map each ID in `duplicated_data` to the ID of the contact or chat it is a duplicate of
+    ///
+    /// When nothing is merged, each ID maps to itself.
+    pub fn dedupe<D: Deduplicate>(
+        &self,
+        duplicated_data: &HashMap<i32, D::T>,
+    ) -> HashMap<i32, i32> {
+        match self {
+            DedupeStrategy::None => duplicated_data.keys().map(|id| (*id, *id)).collect(),
+            DedupeStrategy::Exact | DedupeStrategy::Contact => D::dedupe(duplicated_data),
+        }
+    }
+}
+
+impl Display for DedupeStrategy {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DedupeStrategy::None => write!(fmt, "none"),
+            DedupeStrategy::Exact => write!(fmt, "exact"),
+            DedupeStrategy::Contact => write!(fmt, "contact"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashMap};
+
+    use imessage_database::tables::{chat_handle::ChatToHandle, handle::Handle};
+
+    use crate::app::dedupe_strategy::DedupeStrategy;
+
+    #[test]
+    fn can_parse_strategy_any_case() {
+        assert_eq!(DedupeStrategy::from_cli("None"), Some(DedupeStrategy::None));
+        assert_eq!(
+            DedupeStrategy::from_cli("contact"),
+            Some(DedupeStrategy::Contact)
+        );
+        assert_eq!(DedupeStrategy::from_cli("fuzzy"), None);
+    }
+
+    #[test]
+    fn can_keep_duplicates_separate() {
+        let handles = HashMap::from([
+            (1, "+15558675309".to_string()),
+            (2, "+15558675309".to_string()),
+        ]);
+        assert_eq!(
+            DedupeStrategy::None.dedupe::<Handle>(&handles),
+            HashMap::from([(1, 1), (2, 2)])
+        );
+    }
+
+    #[test]
+    fn can_merge_duplicates() {
+        let chats = HashMap::from([
+            (1, BTreeSet::from([10, 11])),
+            (2, BTreeSet::from([10, 11])),
+            (3, BTreeSet::from([12])),
+        ]);
+        let merged = DedupeStrategy::Exact.dedupe::<ChatToHandle>(&chats);
+        assert_eq!(merged[&1], merged[&2]);
+        assert_ne!(merged[&1], merged[&3]);
+    }
+}
@@ -0,0 +1,114 @@
+/*!
+ Contains data structures used to select messages by who sent them.
+*/
+
+use std::fmt::Display;
+
+use imessage_database::util::query_context::QueryContext;
+
+/// Represents which side of a conversation to export
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy)]
+pub enum MessageDirection {
+    /// Only messages sent by the database owner
+    Sent,
+    /// Only messages received by the database owner
+    Received,
+    /// Every message
+    #[default]
+    Both,
+}
+
+impl MessageDirection {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(direction: &str) -> Option<Self> {
+        match direction.to_lowercase().as_str() {
+            "sent" => Some(Self::Sent),
+            "received" => Some(Self::Received),
+            "both" => Some(Self::Both),
+            _ => None,
+        }
+    }
+
+    /// Add the direction to the filters in a [`QueryContext`], so only messages in this direction are queried and counted
+    pub fn apply(&self, query_context: &mut QueryContext) {
+        match self {
+            MessageDirection::Sent => query_context.set_from_me(true),
+            MessageDirection::Received => query_context.set_from_me(false),
+            MessageDirection::Both => {}
+        }
+    }
+}
+
+impl Display for MessageDirection {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MessageDirection::Sent => write!(fmt, "sent"),
+            MessageDirection::Received => write!(fmt, "received"),
+            MessageDirection::Both => write!(fmt, "both"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use imessage_database::{
+        tables::{messages::Message, table::get_connection},
+        util::query_context::QueryContext,
+    };
+
+    use crate::app::direction::MessageDirection;
+
+    /// Count the messages in the fixture database that are in `direction`
+    fn fixture_count(direction: MessageDirection) -> u64 {
+        let db_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+        let db = get_connection(&db_path).unwrap();
+
+        let mut query_context = QueryContext::default();
+        direction.apply(&mut query_context);
+        Message::get_count(&db, &query_context).unwrap()
+    }
+
+    #[test]
+    fn can_parse_direction() {
+        assert_eq!(
+            MessageDirection::from_cli("sent"),
+            Some(MessageDirection::Sent)
+        );
+        assert_eq!(
+            MessageDirection::from_cli("Received"),
+            Some(MessageDirection::Received)
+        );
+        assert_eq!(
+            MessageDirection::from_cli("both"),
+            Some(MessageDirection::Both)
+        );
+        assert_eq!(MessageDirection::from_cli("outgoing"), None);
+    }
+
+    #[test]
+    fn can_count_sent() {
+        assert_eq!(fixture_count(MessageDirection::Sent), 4);
+    }
+
+    #[test]
+    fn can_count_received() {
+        assert_eq!(fixture_count(MessageDirection::Received), 6);
+    }
+
+    #[test]
+    fn can_count_both() {
+        assert_eq!(fixture_count(MessageDirection::Both), 10);
+    }
+
+    #[test]
+    fn can_apply_both_without_filters() {
+        let mut query_context = QueryContext::default();
+        MessageDirection::Both.apply(&mut query_context);
+        assert!(!query_context.has_filters());
+    }
+}
@@ -0,0 +1,240 @@
+/*!
+ Contains data structures used to write exported text in encodings other than UTF-8.
+*/
+
+use std::{fmt::Display, str::from_utf8};
+
+/// The byte order mark that identifies a file as UTF-8
+pub const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+/// Represents the text encoding exported files are written in
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum OutputEncoding {
+    /// UTF-8, which can represent any character
+    #[default]
+    Utf8,
+    /// Little-endian UTF-16, starting with a byte order mark
+    Utf16Le,
+    /// Big-endian UTF-16, starting with a byte order mark
+    Utf16Be,
+    /// ISO-8859-1, which can only represent the first 256 Unicode code points
+    Latin1,
+}
+
+impl OutputEncoding {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(encoding: &str) -> Option<Self> {
+        match encoding.to_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Self::Utf8),
+            "utf-16le" | "utf16le" => Some(Self::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Self::Utf16Be),
+            "latin1" | "latin-1" | "iso-8859-1" => Some(Self::Latin1),
+            _ => None,
+        }
+    }
+
+    /// Get the name of the encoding used to declare the `charset` of HTML documents
+    pub fn charset(&self) -> &str {
+        match self {
+            OutputEncoding::Utf8 => "UTF-8",
+            OutputEncoding::Utf16Le => "UTF-16LE",
+            OutputEncoding::Utf16Be => "UTF-16BE",
+            OutputEncoding::Latin1 => "ISO-8859-1",
+        }
+    }
+
+    /// Get the byte order mark written at the start of new files, which is empty if the encoding does not use one
+    pub fn bom(&self) -> &[u8] {
+        match self {
+            OutputEncoding::Utf16Le => &[0xFF, 0xFE],
+            OutputEncoding::Utf16Be => &[0xFE, 0xFF],
+            OutputEncoding::Utf8 | OutputEncoding::Latin1 => &[],
+        }
+    }
+
+    /// `true` if the encoding can represent the character, else `false`
+    pub fn can_encode(&self, character: char) -> bool {
+        match self {
+            OutputEncoding::Latin1 => u32::from(character) <= 0xFF,
+            OutputEncoding::Utf8 | OutputEncoding::Utf16Le | OutputEncoding::Utf16Be => true,
+        }
+    }
+
+    /// Append `text` to `out` in this encoding, replacing characters it cannot represent with `substitute`
+    fn encode(&self, text: &str, substitute: char, out: &mut Vec<u8>) {
+        for character in text.chars() {
+            let character = if self.can_encode(character) {
+                character
+            } else {
+                substitute
+            };
+            match self {
+                OutputEncoding::Utf8 => {
+                    out.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes())
+                }
+                OutputEncoding::Utf16Le => character
+                    .encode_utf16(&mut [0; 2])
+                    .iter()
+                    .for_each(|unit| out.extend_from_slice(&unit.to_le_bytes())),
+                OutputEncoding::Utf16Be => character
+                    .encode_utf16(&mut [0; 2])
+                    .iter()
+                    .for_each(|unit| out.extend_from_slice(&unit.to_be_bytes())),
+                // Latin-1 code points map directly to bytes
+                OutputEncoding::Latin1 => out.push(u32::from(character) as u8),
+            }
+        }
+    }
+}
+
+impl Display for OutputEncoding {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputEncoding::Utf8 => write!(fmt, "utf-8"),
+            OutputEncoding::Utf16Le => write!(fmt, "utf-16le"),
+            OutputEncoding::Utf16Be => write!(fmt, "utf-16be"),
+            OutputEncoding::Latin1 => write!(fmt, "latin1"),
+        }
+    }
+}
+
+/// Converts UTF-8 text into an [`OutputEncoding`] as it is written
+///
+/// Writers may split a character across calls, so incomplete characters are held until the rest arrive.
+#[derive(Debug)]
+pub struct Transcoder {
+    /// The encoding to write
+    encoding: OutputEncoding,
+    /// The character written in place of characters the encoding cannot represent
+    substitute: char,
+    /// Bytes at the end of the last write that are not yet a complete character
+    pending: Vec<u8>,
+}
+
+impl Transcoder {
+    pub fn new(encoding: OutputEncoding, substitute: char) -> Self {
+        Self {
+            encoding,
+            substitute,
+            pending: vec![],
+        }
+    }
+
+    /// Convert the next chunk of UTF-8 bytes, returning the encoded bytes that are ready to write
+    ///
+    /// Invalid UTF-8 is written as the substitute character.
+    pub fn transcode(&mut self, buf: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(buf);
+
+        let mut out = Vec::with_capacity(self.pending.len());
+        let mut rest = self.pending.as_slice();
+        while !rest.is_empty() {
+            match from_utf8(rest) {
+                Ok(text) => {
+                    self.encoding.encode(text, self.substitute, &mut out);
+                    rest = &[];
+                }
+                Err(why) => {
+                    let (valid, invalid) = rest.split_at(why.valid_up_to());
+                    self.encoding.encode(
+                        from_utf8(valid).unwrap_or_default(),
+                        self.substitute,
+                        &mut out,
+                    );
+                    match why.error_len() {
+                        Some(len) => {
+                            self.write_substitute(&mut out);
+                            rest = &invalid[len..];
+                        }
+                        // The rest of the character has not been written yet
+                        None => break,
+                    }
+                }
+            }
+        }
+
+        let consumed = self.pending.len() - rest.len();
+        self.pending.drain(..consumed);
+        out
+    }
+
+    /// Return the encoded substitute if a character was left incomplete, since no more bytes will arrive
+    pub fn finish(&mut self) -> Vec<u8> {
+        let mut out = vec![];
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            self.write_substitute(&mut out);
+        }
+        out
+    }
+
+    /// Append the substitute character to `out`
+    fn write_substitute(&self, out: &mut Vec<u8>) {
+        self.encoding
+            .encode(&self.substitute.to_string(), self.substitute, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::encoding::{OutputEncoding, Transcoder};
+
+    #[test]
+    fn can_parse_encoding_any_case() {
+        assert_eq!(
+            OutputEncoding::from_cli("UTF-8"),
+            Some(OutputEncoding::Utf8)
+        );
+        assert_eq!(
+            OutputEncoding::from_cli("utf-16LE"),
+            Some(OutputEncoding::Utf16Le)
+        );
+        assert_eq!(
+            OutputEncoding::from_cli("Latin1"),
+            Some(OutputEncoding::Latin1)
+        );
+    }
+
+    #[test]
+    fn cant_parse_invalid_encoding() {
+        assert_eq!(OutputEncoding::from_cli("utf-32"), None);
+    }
+
+    #[test]
+    fn can_transcode_utf16() {
+        let mut transcoder = Transcoder::new(OutputEncoding::Utf16Le, '?');
+        assert_eq!(
+            transcoder.transcode("hé😀".as_bytes()),
+            [0x68, 0x00, 0xE9, 0x00, 0x3D, 0xD8, 0x00, 0xDE]
+        );
+
+        let mut transcoder = Transcoder::new(OutputEncoding::Utf16Be, '?');
+        assert_eq!(
+            transcoder.transcode("hé".as_bytes()),
+            [0x00, 0x68, 0x00, 0xE9]
+        );
+    }
+
+    #[test]
+    fn can_transcode_latin1_with_substitute() {
+        let mut transcoder = Transcoder::new(OutputEncoding::Latin1, '_');
+        assert_eq!(transcoder.transcode("Café 😀!".as_bytes()), b"Caf\xE9 _!");
+    }
+
+    #[test]
+    fn can_transcode_split_character() {
+        let mut transcoder = Transcoder::new(OutputEncoding::Latin1, '?');
+        let bytes = "é".as_bytes();
+
+        assert_eq!(transcoder.transcode(&bytes[..1]), b"");
+        assert_eq!(transcoder.transcode(&bytes[1..]), b"\xE9");
+        assert_eq!(transcoder.finish(), b"");
+    }
+
+    #[test]
+    fn can_transcode_invalid_utf8() {
+        let mut transcoder = Transcoder::new(OutputEncoding::Latin1, '?');
+        assert_eq!(transcoder.transcode(b"a\xFFb\xC3"), b"a?b");
+        assert_eq!(transcoder.finish(), b"?");
+    }
+}
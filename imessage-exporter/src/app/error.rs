@@ -10,7 +10,7 @@ use std::{
 
 use imessage_database::{error::table::TableError, util::size::format_file_size};
 
-use crate::app::options::OPTION_BYPASS_FREE_SPACE_CHECK;
+use crate::app::options::{OPTION_BYPASS_FREE_SPACE_CHECK, OPTION_COPY_DB, OPTION_DB_PATH};
 
 /// Errors that can happen during the application's runtime
 #[derive(Debug)]
@@ -19,7 +19,13 @@ pub enum RuntimeError {
     CreateError(IoError, PathBuf),
     DiskError(IoError),
     DatabaseError(TableError),
+    EncryptedDatabase(PathBuf),
     NotEnoughAvailableSpace(u64, u64),
+    NotEnoughSpaceForCopy(u64, u64),
+    ExportErrors(usize),
+    VerifyErrors(usize),
+    ValidationErrors(usize),
+    Aborted,
 }
 
 impl Display for RuntimeError {
@@ -29,6 +35,10 @@ impl Display for RuntimeError {
             RuntimeError::CreateError(why, path) => write!(fmt, "{why}: {path:?}"),
             RuntimeError::DiskError(why) => write!(fmt, "{why}"),
             RuntimeError::DatabaseError(why) => write!(fmt, "{why}"),
+            RuntimeError::EncryptedDatabase(path) => write!(
+                fmt,
+                "The database at {path:?} is encrypted or locked, so it cannot be read!\nDatabases from encrypted iOS backups and SQLCipher databases must be decrypted first\nDecrypt the backup with a backup extraction tool, or turn off backup encryption and back up again, then pass the decrypted copy to `--{OPTION_DB_PATH}`\nIf the database is on a FileVault or other encrypted volume, unlock the volume first\n"
+            ),
             RuntimeError::NotEnoughAvailableSpace(estimated_bytes, available_bytes) => {
                 write!(
                     fmt, 
@@ -38,6 +48,28 @@ impl Display for RuntimeError {
                     OPTION_BYPASS_FREE_SPACE_CHECK
                 )
             }
+            RuntimeError::NotEnoughSpaceForCopy(database_bytes, available_bytes) => {
+                write!(
+                    fmt,
+                    "Not enough free disk space to copy the database!\nDatabase size: {}\nTemporary directory space available: {}\nRun without `--{}` to read the database in place\n",
+                    format_file_size(*database_bytes),
+                    format_file_size(*available_bytes),
+                    OPTION_COPY_DB
+                )
+            }
+            RuntimeError::ExportErrors(count) => match count {
+                1 => write!(fmt, "1 error occurred during the export"),
+                _ => write!(fmt, "{count} errors occurred during the export"),
+            },
+            RuntimeError::VerifyErrors(count) => match count {
+                1 => write!(fmt, "1 exported file failed verification"),
+                _ => write!(fmt, "{count} exported files failed verification"),
+            },
+            RuntimeError::ValidationErrors(count) => match count {
+                1 => write!(fmt, "1 message failed validation"),
+                _ => write!(fmt, "{count} messages failed validation"),
+            },
+            RuntimeError::Aborted => write!(fmt, "Export aborted"),
         }
     }
 }
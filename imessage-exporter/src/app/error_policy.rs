@@ -0,0 +1,24 @@
+/*!
+ Contains data structures used to describe how the export reacts to errors.
+*/
+
+use std::fmt::Display;
+
+/// Represents what the app does when an error occurs during an export
+#[derive(PartialEq, Eq, Debug, Default)]
+pub enum ErrorPolicy {
+    /// Log each error, continue exporting, and report the total once the export finishes
+    #[default]
+    BestEffort,
+    /// Abort the export on the first error
+    FailFast,
+}
+
+impl Display for ErrorPolicy {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorPolicy::BestEffort => write!(fmt, "best-effort"),
+            ErrorPolicy::FailFast => write!(fmt, "fail-fast"),
+        }
+    }
+}
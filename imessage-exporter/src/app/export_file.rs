@@ -0,0 +1,105 @@
+/*!
+ Contains the file type that exporters write to.
+*/
+
+use std::{
+    fs::File,
+    io::{Result, Write},
+};
+
+use crate::app::{
+    encoding::{OutputEncoding, Transcoder},
+    gzip::GzEncoder,
+};
+
+/// The destination of an [`ExportFile`], compressed with `gzip` if requested
+enum Sink {
+    Plain(File),
+    Gzip(GzEncoder<File>),
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            Sink::Plain(file) => file.write(buf),
+            Sink::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            Sink::Plain(file) => file.flush(),
+            Sink::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// A file that exported data is written to, compressed with `gzip` and transcoded from UTF-8 if requested
+pub struct ExportFile {
+    sink: Sink,
+    /// Converts written text into the output encoding, unless the output is UTF-8
+    transcoder: Option<Transcoder>,
+}
+
+impl ExportFile {
+    /// Wrap a file, compressing what is written to it if `compress` is set
+    pub fn new(file: File, compress: bool) -> Result<Self> {
+        let sink = if compress {
+            Sink::Gzip(GzEncoder::new(file)?)
+        } else {
+            Sink::Plain(file)
+        };
+        Ok(ExportFile {
+            sink,
+            transcoder: None,
+        })
+    }
+
+    /// Write text to the file in `encoding`, replacing characters it cannot represent with `substitute`
+    ///
+    /// If the file is `empty`, the encoding's byte order mark is written first.
+    pub fn with_encoding(
+        mut self,
+        encoding: OutputEncoding,
+        substitute: char,
+        empty: bool,
+    ) -> Result<Self> {
+        if encoding == OutputEncoding::Utf8 {
+            return Ok(self);
+        }
+        if empty {
+            self.sink.write_all(encoding.bom())?;
+        }
+        self.transcoder = Some(Transcoder::new(encoding, substitute));
+        Ok(self)
+    }
+
+    /// Write any buffered data, including the `gzip` trailer if the file is compressed
+    ///
+    /// Dropping the file also does this, but cannot report errors
+    pub fn finish(mut self) -> Result<()> {
+        if let Some(transcoder) = self.transcoder.as_mut() {
+            self.sink.write_all(&transcoder.finish())?;
+        }
+        match self.sink {
+            Sink::Plain(mut file) => file.flush(),
+            Sink::Gzip(encoder) => encoder.finish().map(|_| ()),
+        }
+    }
+}
+
+impl Write for ExportFile {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self.transcoder.as_mut() {
+            Some(transcoder) => {
+                self.sink.write_all(&transcoder.transcode(buf))?;
+                Ok(buf.len())
+            }
+            None => self.sink.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.sink.flush()
+    }
+}
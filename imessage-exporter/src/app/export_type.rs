@@ -5,7 +5,7 @@
 use std::fmt::Display;
 
 /// Represents the type of file to export iMessage data into
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub enum ExportType {
     /// HTML file export
     Html,
@@ -13,6 +13,16 @@ pub enum ExportType {
     Txt,
     /// JSON file export
     Json,
+    /// PDF file export
+    Pdf,
+    /// CSV file export
+    Csv,
+    /// Text file export in the format of WhatsApp chat exports
+    WhatsApp,
+    /// Markdown file export
+    Markdown,
+    /// Parquet file export
+    Parquet,
 }
 
 impl ExportType {
@@ -22,6 +32,11 @@ impl ExportType {
             "txt" => Some(Self::Txt),
             "html" => Some(Self::Html),
             "json" => Some(Self::Json),
+            "pdf" => Some(Self::Pdf),
+            "csv" => Some(Self::Csv),
+            "whatsapp" => Some(Self::WhatsApp),
+            "markdown" => Some(Self::Markdown),
+            "parquet" => Some(Self::Parquet),
             _ => None,
         }
     }
@@ -32,6 +47,11 @@ impl ExportType {
             ExportType::Html => ".html",
             ExportType::Txt => ".txt",
             ExportType::Json => ".json",
+            ExportType::Pdf => ".pdf",
+            ExportType::Csv => ".csv",
+            ExportType::WhatsApp => ".txt",
+            ExportType::Markdown => ".md",
+            ExportType::Parquet => ".parquet",
         }
     }
 }
@@ -42,6 +62,11 @@ impl Display for ExportType {
             ExportType::Txt => write!(fmt, "txt"),
             ExportType::Html => write!(fmt, "html"),
             ExportType::Json => write!(fmt, "json"),
+            ExportType::Pdf => write!(fmt, "pdf"),
+            ExportType::Csv => write!(fmt, "csv"),
+            ExportType::WhatsApp => write!(fmt, "whatsapp"),
+            ExportType::Markdown => write!(fmt, "markdown"),
+            ExportType::Parquet => write!(fmt, "parquet"),
         }
     }
 }
@@ -73,9 +98,59 @@ mod tests {
         assert!(matches!(ExportType::from_cli("tXt"), Some(ExportType::Txt)));
     }
 
+    #[test]
+    fn can_parse_pdf_any_case() {
+        assert!(matches!(ExportType::from_cli("pdf"), Some(ExportType::Pdf)));
+        assert!(matches!(ExportType::from_cli("PDF"), Some(ExportType::Pdf)));
+        assert!(matches!(ExportType::from_cli("pDf"), Some(ExportType::Pdf)));
+    }
+
+    #[test]
+    fn can_parse_whatsapp_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("whatsapp"),
+            Some(ExportType::WhatsApp)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("WhatsApp"),
+            Some(ExportType::WhatsApp)
+        ));
+    }
+
+    #[test]
+    fn can_parse_markdown_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("markdown"),
+            Some(ExportType::Markdown)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("Markdown"),
+            Some(ExportType::Markdown)
+        ));
+    }
+
+    #[test]
+    fn can_parse_csv_any_case() {
+        assert!(matches!(ExportType::from_cli("csv"), Some(ExportType::Csv)));
+        assert!(matches!(ExportType::from_cli("CSV"), Some(ExportType::Csv)));
+        assert!(matches!(ExportType::from_cli("cSv"), Some(ExportType::Csv)));
+    }
+
+    #[test]
+    fn can_parse_parquet_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("parquet"),
+            Some(ExportType::Parquet)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("Parquet"),
+            Some(ExportType::Parquet)
+        ));
+    }
+
     #[test]
     fn cant_parse_invalid() {
-        assert!(ExportType::from_cli("pdf").is_none());
+        assert!(ExportType::from_cli("xml").is_none());
         assert!(ExportType::from_cli("json").is_none());
         assert!(ExportType::from_cli("").is_none());
     }
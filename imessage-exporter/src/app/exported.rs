@@ -0,0 +1,244 @@
+/*!
+ Contains data structures used to remember which messages were already written to an export directory.
+*/
+
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    fs::File,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use crate::app::error::RuntimeError;
+
+/// Name of the file in the export directory that lists the GUID of each exported message, one per line
+pub const EXPORTED_GUIDS: &str = ".exported_guids";
+
+/// Number of bits the Bloom filter reserves for each GUID
+const BLOOM_BITS_PER_GUID: u64 = 20;
+/// Number of bits the Bloom filter sets for each GUID; optimal for [`BLOOM_BITS_PER_GUID`]
+const BLOOM_HASHES: u64 = 13;
+
+/// Represents how the GUIDs of exported messages are held in memory
+#[derive(PartialEq, Eq, Debug)]
+pub enum SkipExportedMode {
+    /// Keep every GUID, using about 100 bytes per message
+    Exact,
+    /// Keep a Bloom filter of the GUIDs, using under 3 bytes per message, but wrongly skipping fewer than 1 in 10,000 new messages
+    Bloom,
+}
+
+impl SkipExportedMode {
+    /// Create an instance of the enum given user input
+    pub fn from_cli(mode: &str) -> Option<Self> {
+        match mode.to_lowercase().as_str() {
+            "exact" => Some(Self::Exact),
+            "bloom" => Some(Self::Bloom),
+            _ => None,
+        }
+    }
+}
+
+impl Display for SkipExportedMode {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipExportedMode::Exact => write!(fmt, "exact"),
+            SkipExportedMode::Bloom => write!(fmt, "bloom"),
+        }
+    }
+}
+
+/// A fixed size set of GUIDs that may report false positives, but never false negatives
+struct BloomFilter {
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    /// Create a filter large enough to hold the given number of GUIDs
+    fn with_capacity(guids: u64) -> Self {
+        let words = (guids.max(1) * BLOOM_BITS_PER_GUID).div_ceil(64);
+        BloomFilter {
+            bits: vec![0; words as usize],
+        }
+    }
+
+    /// Get the bits that represent a GUID, using double hashing to simulate [`BLOOM_HASHES`] hash functions
+    fn indexes(&self, guid: &str) -> impl Iterator<Item = usize> {
+        let hash = |seed: u8| {
+            let mut hasher = DefaultHasher::new();
+            seed.hash(&mut hasher);
+            guid.hash(&mut hasher);
+            hasher.finish()
+        };
+        let (first, second) = (hash(0), hash(1));
+        let size = self.bits.len() as u64 * 64;
+        (0..BLOOM_HASHES)
+            .map(move |idx| (first.wrapping_add(idx.wrapping_mul(second)) % size) as usize)
+    }
+
+    fn insert(&mut self, guid: &str) {
+        for idx in self.indexes(guid) {
+            self.bits[idx / 64] |= 1 << (idx % 64);
+        }
+    }
+
+    fn contains(&self, guid: &str) -> bool {
+        self.indexes(guid)
+            .all(|idx| self.bits[idx / 64] & (1 << (idx % 64)) != 0)
+    }
+}
+
+/// The in-memory set of exported GUIDs
+enum Guids {
+    Exact(HashSet<String>),
+    Bloom(BloomFilter),
+}
+
+/// GUIDs of the messages written to an export directory, including those written by previous exports
+pub struct ExportedMessages {
+    guids: Guids,
+    /// Appends newly exported GUIDs to [`EXPORTED_GUIDS`]
+    writer: BufWriter<File>,
+}
+
+impl ExportedMessages {
+    /// Load the GUIDs previous exports wrote to `export_path`, leaving room for `new_messages` more
+    pub fn open(
+        export_path: &Path,
+        mode: &SkipExportedMode,
+        new_messages: u64,
+    ) -> Result<Self, RuntimeError> {
+        let path = export_path.join(EXPORTED_GUIDS);
+
+        let mut guids = match mode {
+            SkipExportedMode::Exact => Guids::Exact(HashSet::new()),
+            SkipExportedMode::Bloom => {
+                // The filter cannot grow, so count the existing GUIDs before reading them
+                let previous = match File::open(&path) {
+                    Ok(file) => BufReader::new(file).lines().count() as u64,
+                    Err(_) => 0,
+                };
+                Guids::Bloom(BloomFilter::with_capacity(previous + new_messages))
+            }
+        };
+
+        if let Ok(file) = File::open(&path) {
+            for guid in BufReader::new(file).lines() {
+                let guid = guid.map_err(RuntimeError::DiskError)?;
+                match &mut guids {
+                    Guids::Exact(set) => {
+                        set.insert(guid);
+                    }
+                    Guids::Bloom(filter) => filter.insert(&guid),
+                }
+            }
+        }
+
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+        Ok(ExportedMessages {
+            guids,
+            writer: BufWriter::new(file),
+        })
+    }
+
+    /// Determine if a message with the given GUID was already exported
+    pub fn contains(&self, guid: &str) -> bool {
+        match &self.guids {
+            Guids::Exact(set) => set.contains(guid),
+            Guids::Bloom(filter) => filter.contains(guid),
+        }
+    }
+
+    /// Remember that a message with the given GUID was exported
+    pub fn insert(&mut self, guid: &str) -> Result<(), RuntimeError> {
+        match &mut self.guids {
+            Guids::Exact(set) => {
+                set.insert(guid.to_string());
+            }
+            Guids::Bloom(filter) => filter.insert(guid),
+        }
+        writeln!(self.writer, "{guid}").map_err(RuntimeError::DiskError)
+    }
+
+    /// Write any buffered GUIDs to disk
+    pub fn flush(&mut self) -> Result<(), RuntimeError> {
+        self.writer.flush().map_err(RuntimeError::DiskError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read_to_string, remove_dir_all},
+    };
+
+    use crate::app::exported::{BloomFilter, ExportedMessages, SkipExportedMode, EXPORTED_GUIDS};
+
+    #[test]
+    fn can_parse_mode_any_case() {
+        assert_eq!(
+            SkipExportedMode::from_cli("exact"),
+            Some(SkipExportedMode::Exact)
+        );
+        assert_eq!(
+            SkipExportedMode::from_cli("BLOOM"),
+            Some(SkipExportedMode::Bloom)
+        );
+        assert_eq!(SkipExportedMode::from_cli("fuzzy"), None);
+    }
+
+    #[test]
+    fn can_find_bloom_guids() {
+        let mut filter = BloomFilter::with_capacity(1000);
+        let guids: Vec<String> = (0..1000).map(|idx| format!("GUID-{idx}")).collect();
+        guids.iter().for_each(|guid| filter.insert(guid));
+
+        // A Bloom filter never forgets a GUID it was given
+        assert!(guids.iter().all(|guid| filter.contains(guid)));
+        // But it only rarely claims to know one it was not
+        let false_positives = (1000..11000)
+            .filter(|idx| filter.contains(&format!("GUID-{idx}")))
+            .count();
+        assert!(false_positives < 10);
+    }
+
+    #[test]
+    fn can_remember_guids_across_exports() {
+        for mode in [SkipExportedMode::Exact, SkipExportedMode::Bloom] {
+            let dir = temp_dir().join(format!("imessage-exported-{mode}"));
+            let _ = remove_dir_all(&dir);
+            create_dir_all(&dir).unwrap();
+
+            // First export
+            let mut exported = ExportedMessages::open(&dir, &mode, 2).unwrap();
+            assert!(!exported.contains("A"));
+            exported.insert("A").unwrap();
+            exported.insert("B").unwrap();
+            assert!(exported.contains("A"));
+            exported.flush().unwrap();
+
+            // Second export
+            let mut exported = ExportedMessages::open(&dir, &mode, 1).unwrap();
+            assert!(exported.contains("A"));
+            assert!(exported.contains("B"));
+            assert!(!exported.contains("C"));
+            exported.insert("C").unwrap();
+            exported.flush().unwrap();
+
+            assert_eq!(
+                read_to_string(dir.join(EXPORTED_GUIDS)).unwrap(),
+                "A\nB\nC\n"
+            );
+
+            remove_dir_all(&dir).unwrap();
+        }
+    }
+}
@@ -0,0 +1,377 @@
+/*!
+ Contains a streaming `gzip` encoder used to compress exported files.
+
+ Data is compressed with `DEFLATE` using the fixed Huffman codes from [RFC 1951](https://www.rfc-editor.org/rfc/rfc1951)
+ and wrapped in the container described by [RFC 1952](https://www.rfc-editor.org/rfc/rfc1952).
+*/
+
+use std::io::{Result, Write};
+
+/// Extension appended to the name of compressed files
+pub const GZIP_EXTENSION: &str = "gz";
+
+/// Magic bytes, compression method (`DEFLATE`), no flags, no modification time, no extra flags, unknown OS
+const HEADER: [u8; 10] = [0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF];
+
+/// Amount of input compressed at once; matches never cross a block boundary
+const BLOCK_SIZE: usize = 1 << 16;
+/// Farthest back a match may reference
+const WINDOW_SIZE: usize = 1 << 15;
+/// Shortest match worth encoding
+const MIN_MATCH: usize = 3;
+/// Longest match that can be encoded
+const MAX_MATCH: usize = 258;
+/// Number of earlier positions checked when looking for a match
+const MAX_CHAIN: usize = 64;
+/// Number of bits used to hash the next [`MIN_MATCH`] bytes
+const HASH_BITS: u32 = 15;
+
+/// Shortest length represented by each length code, starting with code 257
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+/// Number of extra bits that follow each length code
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Shortest distance represented by each distance code
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+/// Number of extra bits that follow each distance code
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Lookup table for the CRC-32 used by `gzip`
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut idx = 0;
+    while idx < 256 {
+        let mut crc = idx as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[idx] = crc;
+        idx += 1;
+    }
+    table
+};
+
+/// Update a running CRC-32 with more data
+pub fn crc32(crc: u32, data: &[u8]) -> u32 {
+    !data.iter().fold(!crc, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Packs variable length codes into bytes, least significant bit first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    /// Write the `count` low bits of `value`
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.bits |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Write a Huffman code, which is stored most significant bit first
+    fn write_code(&mut self, code: u32, count: u32) {
+        self.write_bits(code.reverse_bits() >> (32 - count), count);
+    }
+
+    /// Write a literal byte or length code using the fixed Huffman codes
+    fn write_symbol(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    /// Write a match using the fixed Huffman codes
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let code = LENGTH_BASE.partition_point(|&base| base as usize <= length) - 1;
+        self.write_symbol(257 + code as u16);
+        self.write_bits(
+            (length - LENGTH_BASE[code] as usize) as u32,
+            LENGTH_EXTRA[code] as u32,
+        );
+
+        let code = DISTANCE_BASE.partition_point(|&base| base as usize <= distance) - 1;
+        self.write_code(code as u32, 5);
+        self.write_bits(
+            (distance - DISTANCE_BASE[code] as usize) as u32,
+            DISTANCE_EXTRA[code] as u32,
+        );
+    }
+
+    /// Pad the last partial byte with zeros
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.write_bits(0, 8 - self.count);
+        }
+    }
+}
+
+/// Hash the [`MIN_MATCH`] bytes that start at an index
+fn hash(data: &[u8], idx: usize) -> usize {
+    let value = (data[idx] as u32) << 16 | (data[idx + 1] as u32) << 8 | data[idx + 2] as u32;
+    (value.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Compress `data` into a single `DEFLATE` block that uses the fixed Huffman codes
+fn deflate_block(data: &[u8], last: bool, out: &mut BitWriter) {
+    out.write_bits(u32::from(last), 1);
+    out.write_bits(0b01, 2);
+
+    // Most recent position with each hash, and the position before it with the same hash
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; data.len()];
+    let insert = |idx: usize, head: &mut [usize], prev: &mut [usize]| {
+        if idx + MIN_MATCH <= data.len() {
+            let key = hash(data, idx);
+            prev[idx] = head[key];
+            head[key] = idx;
+        }
+    };
+
+    let mut idx = 0;
+    while idx < data.len() {
+        let (mut best_length, mut best_distance) = (0, 0);
+        if idx + MIN_MATCH <= data.len() {
+            let max_length = MAX_MATCH.min(data.len() - idx);
+            let mut candidate = head[hash(data, idx)];
+            let mut chain = 0;
+            while candidate != usize::MAX && idx - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+                let length = data[candidate..]
+                    .iter()
+                    .zip(&data[idx..idx + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best_length {
+                    (best_length, best_distance) = (length, idx - candidate);
+                    if length == max_length {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+
+        if best_length >= MIN_MATCH {
+            out.write_match(best_length, best_distance);
+            for position in idx..idx + best_length {
+                insert(position, &mut head, &mut prev);
+            }
+            idx += best_length;
+        } else {
+            out.write_symbol(data[idx] as u16);
+            insert(idx, &mut head, &mut prev);
+            idx += 1;
+        }
+    }
+
+    // End of block
+    out.write_symbol(256);
+}
+
+/// Compresses everything written to it into a `gzip` stream
+///
+/// The `gzip` trailer is written by [`GzEncoder::finish()`], or when the encoder is dropped.
+pub struct GzEncoder<W: Write> {
+    /// Destination for the compressed stream; `None` once the stream is finished
+    inner: Option<W>,
+    /// Input that has not been compressed yet
+    pending: Vec<u8>,
+    /// Compressed bits that do not fill a whole byte yet
+    output: BitWriter,
+    /// CRC-32 of all input
+    crc: u32,
+    /// Number of bytes of input
+    size: u64,
+}
+
+impl<W: Write> GzEncoder<W> {
+    /// Start a new `gzip` stream
+    pub fn new(mut inner: W) -> Result<Self> {
+        inner.write_all(&HEADER)?;
+        Ok(GzEncoder {
+            inner: Some(inner),
+            pending: Vec::with_capacity(BLOCK_SIZE),
+            output: BitWriter::default(),
+            crc: 0,
+            size: 0,
+        })
+    }
+
+    /// Compress the pending input and write every complete byte
+    fn write_block(&mut self, last: bool) -> Result<()> {
+        if let Some(inner) = self.inner.as_mut() {
+            deflate_block(&self.pending, last, &mut self.output);
+            self.pending.clear();
+            if last {
+                self.output.align();
+            }
+            inner.write_all(&self.output.bytes)?;
+            self.output.bytes.clear();
+        }
+        Ok(())
+    }
+
+    /// Write the final block and the `gzip` trailer, returning the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        self.try_finish()?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    fn try_finish(&mut self) -> Result<()> {
+        if self.inner.is_none() {
+            return Ok(());
+        }
+        self.write_block(true)?;
+        let inner = self.inner.as_mut().unwrap();
+        inner.write_all(&self.crc.to_le_bytes())?;
+        // The size is stored modulo 2^32
+        inner.write_all(&(self.size as u32).to_le_bytes())?;
+        inner.flush()
+    }
+}
+
+impl<W: Write> Write for GzEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let written = buf.len().min(BLOCK_SIZE - self.pending.len());
+        self.pending.extend_from_slice(&buf[..written]);
+        self.crc = crc32(self.crc, &buf[..written]);
+        self.size += written as u64;
+        if self.pending.len() == BLOCK_SIZE {
+            self.write_block(false)?;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if !self.pending.is_empty() {
+            self.write_block(false)?;
+        }
+        match self.inner.as_mut() {
+            Some(inner) => inner.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: Write> Drop for GzEncoder<W> {
+    fn drop(&mut self) {
+        let _ = self.try_finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    use crate::app::gzip::{crc32, GzEncoder};
+
+    /// Compress some data in one go
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![]).unwrap();
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn can_compute_crc32() {
+        assert_eq!(crc32(0, b""), 0);
+        assert_eq!(crc32(0, b"123456789"), 0xCBF4_3926);
+        // The CRC can be computed in pieces
+        assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn can_compress_empty() {
+        assert_eq!(
+            compress(b""),
+            [
+                0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x03, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn can_compress_repetitive_text() {
+        let text = "Hello, world! ".repeat(10_000);
+        let compressed = compress(text.as_bytes());
+        assert!(compressed.len() < text.len() / 50);
+
+        // The trailer holds the checksum and size of the input
+        let trailer = &compressed[compressed.len() - 8..];
+        assert_eq!(trailer[..4], crc32(0, text.as_bytes()).to_le_bytes());
+        assert_eq!(trailer[4..], (text.len() as u32).to_le_bytes());
+    }
+
+    #[test]
+    fn can_decompress_with_gzip() {
+        // Mix repetitive and unique data so there are literals and matches across several blocks
+        let data: Vec<u8> = (0..200_000_u32)
+            .flat_map(|idx| {
+                if idx % 7 == 0 {
+                    idx.to_le_bytes().to_vec()
+                } else {
+                    b"abc".to_vec()
+                }
+            })
+            .collect();
+
+        // Write in small pieces, flushing partway through, to exercise the streaming path
+        let mut encoder = GzEncoder::new(vec![]).unwrap();
+        for (idx, chunk) in data.chunks(1000).enumerate() {
+            encoder.write_all(chunk).unwrap();
+            if idx == 100 {
+                encoder.flush().unwrap();
+            }
+        }
+        let compressed = encoder.finish().unwrap();
+
+        let Ok(mut gzip) = Command::new("gzip")
+            .arg("-dc")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+        else {
+            eprintln!("Skipping test: gzip is not installed");
+            return;
+        };
+        gzip.stdin.take().unwrap().write_all(&compressed).unwrap();
+        let output = gzip.wait_with_output().unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(output.stdout, data);
+    }
+}
@@ -0,0 +1,159 @@
+/*!
+ Contains routines for reading user-provided display names for handles.
+*/
+
+use std::{collections::HashMap, fs::read_to_string, path::Path};
+
+use log::warn;
+
+use crate::app::error::RuntimeError;
+
+/// Read a map of handle identifier to display name from a file
+///
+/// Files ending in `.json` must contain a single object, i.e. `{"+15558675309": "Grandma"}`.
+/// Any other file is read as CSV, one `identifier,name` pair per line; blank lines and lines starting with `#` are ignored.
+pub fn read_handle_map(path: &Path) -> Result<HashMap<String, String>, RuntimeError> {
+    let contents = read_to_string(path).map_err(RuntimeError::DiskError)?;
+    let invalid = |why: String| {
+        RuntimeError::InvalidOptions(format!("Unable to read handle map {path:?}: {why}"))
+    };
+
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+    {
+        return serde_json::from_str(&contents).map_err(|why| invalid(why.to_string()));
+    }
+
+    let mut handle_map = HashMap::new();
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once(',') {
+            Some((identifier, name)) => {
+                handle_map.insert(identifier.trim().to_string(), name.trim().to_string());
+            }
+            None => {
+                return Err(invalid(format!(
+                    "line {} is not in the format `identifier,name`",
+                    idx + 1
+                )))
+            }
+        }
+    }
+    Ok(handle_map)
+}
+
+/// Match the entries in a handle map to participant IDs, warning about entries that match no handle
+///
+/// Participants that share a contact card have their identifiers joined by a space,
+/// so an entry matches if it is the whole identifier or any part of it.
+pub fn resolve_handle_map(
+    handle_map: &HashMap<String, String>,
+    participants: &HashMap<i32, String>,
+) -> HashMap<i32, String> {
+    let mut names = HashMap::new();
+    let mut matched = vec![];
+
+    for (handle_id, identifier) in participants {
+        let entry = handle_map.get_key_value(identifier.as_str()).or_else(|| {
+            identifier
+                .split(' ')
+                .find_map(|part| handle_map.get_key_value(part))
+        });
+        if let Some((key, name)) = entry {
+            names.insert(*handle_id, name.clone());
+            matched.push(key.as_str());
+        }
+    }
+
+    let mut unmatched: Vec<&String> = handle_map
+        .keys()
+        .filter(|key| !matched.contains(&key.as_str()))
+        .collect();
+    unmatched.sort();
+    for identifier in unmatched {
+        warn!("Handle map entry `{identifier}` does not match any handle!");
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, write},
+    };
+
+    use crate::app::handle_map::{read_handle_map, resolve_handle_map};
+
+    #[test]
+    fn can_read_csv() {
+        let dir = temp_dir().join("imessage-handle-map-csv");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("names.csv");
+        write(
+            &path,
+            "# Family\n5558675309, Grandma\n\nsteve@apple.com,Steve, Jr.\n",
+        )
+        .unwrap();
+
+        let actual = read_handle_map(&path).unwrap();
+        let expected = HashMap::from([
+            ("5558675309".to_string(), "Grandma".to_string()),
+            ("steve@apple.com".to_string(), "Steve, Jr.".to_string()),
+        ]);
+        assert_eq!(actual, expected);
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_read_json() {
+        let dir = temp_dir().join("imessage-handle-map-json");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("names.json");
+        write(&path, r#"{"5558675309": "Grandma"}"#).unwrap();
+
+        let actual = read_handle_map(&path).unwrap();
+        let expected = HashMap::from([("5558675309".to_string(), "Grandma".to_string())]);
+        assert_eq!(actual, expected);
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cant_read_invalid_csv() {
+        let dir = temp_dir().join("imessage-handle-map-invalid");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("names.csv");
+        write(&path, "5558675309 Grandma\n").unwrap();
+
+        assert!(read_handle_map(&path).is_err());
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_resolve_handle_map() {
+        let handle_map = HashMap::from([
+            ("5558675309".to_string(), "Grandma".to_string()),
+            ("steve@apple.com".to_string(), "Steve".to_string()),
+            ("+15550000000".to_string(), "Nobody".to_string()),
+        ]);
+        let participants = HashMap::from([
+            (1, "5558675309".to_string()),
+            // Contacts that share a contact card
+            (2, "+15551234567 steve@apple.com".to_string()),
+            (3, "+15559876543".to_string()),
+        ]);
+
+        let actual = resolve_handle_map(&handle_map, &participants);
+        let expected = HashMap::from([(1, "Grandma".to_string()), (2, "Steve".to_string())]);
+        assert_eq!(actual, expected);
+    }
+}
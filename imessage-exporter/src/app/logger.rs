@@ -0,0 +1,73 @@
+/*!
+ Contains the logger that routes status and error messages to stderr.
+*/
+
+use std::{env::var, io::Write, str::FromStr};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// The environment variable used to select the log level, i.e. `RUST_LOG=warn`
+pub const LOG_LEVEL_VAR: &str = "RUST_LOG";
+
+/// Writes log records to stderr, prefixing everything but informational messages with their level
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let mut stderr = std::io::stderr().lock();
+            let _ = match record.level() {
+                Level::Info => writeln!(stderr, "{}", record.args()),
+                level => writeln!(stderr, "{level}: {}", record.args()),
+            };
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+/// Determine the log level from the value of [`LOG_LEVEL_VAR`], defaulting to [`LevelFilter::Info`]
+fn parse_level(level: Option<&str>) -> LevelFilter {
+    level
+        .and_then(|level| LevelFilter::from_str(level.trim()).ok())
+        .unwrap_or(LevelFilter::Info)
+}
+
+/// Install the logger, using the level set in [`LOG_LEVEL_VAR`]
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(parse_level(var(LOG_LEVEL_VAR).ok().as_deref()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::LevelFilter;
+
+    use crate::app::logger::parse_level;
+
+    #[test]
+    fn can_parse_default() {
+        assert_eq!(parse_level(None), LevelFilter::Info);
+    }
+
+    #[test]
+    fn can_parse_level() {
+        assert_eq!(parse_level(Some("warn")), LevelFilter::Warn);
+        assert_eq!(parse_level(Some("DEBUG")), LevelFilter::Debug);
+        assert_eq!(parse_level(Some("off")), LevelFilter::Off);
+    }
+
+    #[test]
+    fn can_parse_invalid() {
+        assert_eq!(parse_level(Some("loud")), LevelFilter::Info);
+    }
+}
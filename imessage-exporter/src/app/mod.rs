@@ -1,7 +1,29 @@
+pub mod attachment_manifest;
+pub mod chat_list;
+pub mod checksums;
+pub mod compatibility;
+pub mod contact_sheet;
+pub mod conversation_order;
+pub mod data_uri;
+pub mod dedupe_strategy;
+pub mod direction;
+pub mod encoding;
 pub mod error;
+pub mod error_policy;
+pub mod export_file;
 pub mod export_type;
-pub mod compatibility;
+pub mod exported;
+pub mod gzip;
+pub mod handle_map;
+pub mod logger;
 pub mod options;
 pub mod progress;
+pub mod reactions;
+pub mod receipts;
 pub mod runtime;
 pub mod sanitizers;
+pub mod stats;
+pub mod validate;
+pub mod vcard;
+pub mod verify;
+pub mod zip;
@@ -3,24 +3,49 @@
 */
 
 
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
+use log::warn;
+
+use chrono::FixedOffset;
 use clap::{crate_version, Arg, ArgAction, ArgMatches, Command};
 
 use imessage_database::{
-    tables::{attachment::DEFAULT_ATTACHMENT_ROOT, table::DEFAULT_PATH_IOS},
+    tables::{
+        attachment::DEFAULT_ATTACHMENT_ROOT,
+        table::{DatabaseAccess, DEFAULT_PATH_IOS},
+    },
     util::{
-        dirs::{default_db_path, home},
+        dates::{is_valid_format, parse_utc_offset},
+        dirs::{default_db_path, expand_home, home},
         platform::Platform,
         query_context::QueryContext,
+        size::{format_file_size, parse_file_size},
     },
 };
 
 use crate::app::{
-    compatibility::attachment_manager::{AttachmentManager, AttachmentManagerMode},
+    attachment_manifest::ATTACHMENT_MANIFEST,
+    checksums::CHECKSUMS,
+    compatibility::{
+        attachment_manager::{
+            AttachmentKind, AttachmentManager, AttachmentManagerMode, AttachmentNaming,
+        },
+        models::{Converter, GifOptimizer, VideoProbe},
+    },
+    conversation_order::ConversationOrder,
+    data_uri::MAX_EMBEDDED_SIZE,
+    dedupe_strategy::DedupeStrategy,
+    direction::MessageDirection,
+    encoding::OutputEncoding,
     error::RuntimeError,
+    error_policy::ErrorPolicy,
     export_type::ExportType,
+    exported::{SkipExportedMode, EXPORTED_GUIDS},
+    gzip::GZIP_EXTENSION,
+    zip::{archive_path, ZIP_EXTENSION},
 };
+use crate::exporters::{html::ARCHIVE, markdown::MarkdownFlavor};
 
 /// Default export directory name
 pub const DEFAULT_OUTPUT_DIR: &str = "imessage_export";
@@ -34,17 +59,82 @@ pub const OPTION_EXPORT_TYPE: &str = "format";
 pub const OPTION_EXPORT_PATH: &str = "export-path";
 pub const OPTION_START_DATE: &str = "start-date";
 pub const OPTION_END_DATE: &str = "end-date";
+pub const OPTION_SINCE: &str = "since";
 pub const OPTION_DISABLE_LAZY_LOADING: &str = "no-lazy";
 pub const OPTION_CUSTOM_NAME: &str = "custom-name";
 pub const OPTION_PLATFORM: &str = "platform";
 pub const OPTION_BYPASS_FREE_SPACE_CHECK: &str = "ignore-disk-warning";
 pub const OPTION_USE_CALLER_ID: &str = "use-caller-id";
 pub const OPTION_CONVERSATION_FILTER: &str = "conversation-filter";
+pub const OPTION_FAIL_FAST: &str = "fail-fast";
+pub const OPTION_BEST_EFFORT: &str = "best-effort";
+pub const OPTION_MESSAGES_PER_PAGE: &str = "messages-per-page";
+pub const OPTION_NO_ORPHANED: &str = "no-orphaned";
+pub const OPTION_SKIP_EXPORTED: &str = "skip-exported";
+pub const OPTION_THUMBNAIL_SIZE: &str = "thumbnail-size";
+pub const OPTION_HANDLE_MAP: &str = "handle-map";
+pub const OPTION_COMPRESS: &str = "compress";
+pub const OPTION_ATTACHMENT_MANIFEST: &str = "attachment-manifest";
+pub const OPTION_KEEP_ORIGINALS: &str = "keep-originals";
+pub const OPTION_DIRECTORY_PER_CHAT: &str = "directory-per-chat";
+pub const OPTION_VCARD: &str = "vcard";
+pub const OPTION_VERIFY: &str = "verify";
+pub const OPTION_RICH_TEXT: &str = "rich-text";
+pub const OPTION_THREADS: &str = "threads";
+pub const OPTION_OUTPUT_ENCODING: &str = "output-encoding";
+pub const OPTION_ENCODING_SUBSTITUTE: &str = "encoding-substitute";
+pub const OPTION_BOM: &str = "bom";
+pub const OPTION_PREVIEW: &str = "preview";
+pub const OPTION_PREVIEW_LAST: &str = "preview-last";
+pub const OPTION_REDACT_ATTACHMENTS: &str = "redact-attachments";
+pub const OPTION_INTERACTIVE: &str = "interactive";
+pub const OPTION_SORT_CONVERSATIONS: &str = "sort-conversations";
+pub const OPTION_SINGLE_FILE: &str = "single-file";
+pub const OPTION_OPTIMIZE_GIFS: &str = "optimize-gifs";
+pub const OPTION_DB_READONLY: &str = "db-readonly";
+pub const OPTION_QUOTE_REPLIES: &str = "quote-replies";
+pub const OPTION_MAX_ATTACHMENT_SIZE: &str = "max-attachment-size";
+pub const OPTION_DATE_FORMAT: &str = "date-format";
+pub const OPTION_ONLY_ATTACHMENTS_OF_TYPE: &str = "only-attachments-of-type";
+pub const OPTION_PARSE_CONTACT_CARDS: &str = "parse-contact-cards";
+pub const OPTION_PARTICIPANTS_MIN: &str = "participants-min";
+pub const OPTION_PARTICIPANTS_MAX: &str = "participants-max";
+pub const OPTION_COPY_DB: &str = "copy-db";
+pub const OPTION_MERGE_SMS_IMESSAGE: &str = "merge-sms-imessage";
+pub const OPTION_REACTIONS_AS_RECORDS: &str = "reactions-as-records";
+pub const OPTION_ATTACHMENT_NAMING: &str = "attachment-naming";
+pub const OPTION_MIN_TEXT_LENGTH: &str = "min-text-length";
+pub const OPTION_DROP_ATTACHMENT_ONLY: &str = "drop-attachment-only";
+pub const OPTION_TIMEZONE: &str = "timezone";
+pub const OPTION_MEDIA_DATES: &str = "media-dates";
+pub const OPTION_FLATTEN_GROUP_NAMES: &str = "flatten-group-names";
+pub const OPTION_CHATS_FROM_FILE: &str = "chats-from-file";
+pub const OPTION_DIRECTION: &str = "direction";
+pub const OPTION_DIAGNOSTIC_JSON: &str = "json";
+pub const OPTION_MARKDOWN_FLAVOR: &str = "markdown-flavor";
+pub const OPTION_CALLER_ID_LABELS: &str = "caller-id-labels";
+pub const OPTION_CHECKSUMS: &str = "checksums";
+pub const OPTION_RENAME_GROUP: &str = "rename-group";
+pub const OPTION_STATS: &str = "stats";
+pub const OPTION_ZIP: &str = "zip";
+pub const OPTION_DEDUPE_STRATEGY: &str = "dedupe-strategy";
+pub const OPTION_VALIDATE_ONLY: &str = "validate-only";
+pub const OPTION_CONTACT_SHEETS: &str = "contact-sheets";
+pub const OPTION_RECEIPTS: &str = "receipts";
 
 // Other CLI Text
-pub const SUPPORTED_FILE_TYPES: &str = "txt, html, json";
+pub const SUPPORTED_FILE_TYPES: &str = "txt, html, json, pdf, csv, whatsapp, markdown, parquet";
 pub const SUPPORTED_PLATFORMS: &str = "macOS, iOS";
 pub const SUPPORTED_ATTACHMENT_MANAGER_MODES: &str = "clone, basic, full, disabled";
+pub const SUPPORTED_SKIP_EXPORTED_MODES: &str = "exact, bloom";
+pub const SUPPORTED_ENCODINGS: &str = "utf-8, utf-16le, utf-16be, latin1";
+pub const SUPPORTED_CONVERSATION_ORDERS: &str = "name, recency, message-count";
+pub const SUPPORTED_DB_ACCESS: &str = "ro, immutable";
+pub const SUPPORTED_ATTACHMENT_KINDS: &str = "image, video, audio";
+pub const SUPPORTED_ATTACHMENT_NAMINGS: &str = "id, guid, original, sequential";
+pub const SUPPORTED_DIRECTIONS: &str = "sent, received, both";
+pub const SUPPORTED_MARKDOWN_FLAVORS: &str = "plain, obsidian";
+pub const SUPPORTED_DEDUPE_STRATEGIES: &str = "none, exact, contact";
 pub const ABOUT: &str = concat!(
     "The `imessage-exporter` binary exports iMessage data to\n",
     "`txt` or `html` formats. It can also run diagnostics\n",
@@ -61,8 +151,10 @@ pub struct Options {
     pub attachment_manager: AttachmentManager,
     /// If true, emit diagnostic information to stdout
     pub diagnostic: bool,
-    /// The type of file we are exporting data to
-    pub export_type: Option<ExportType>,
+    /// If true, diagnostic information is emitted as a JSON object instead of text
+    pub diagnostic_json: bool,
+    /// The types of file we are exporting data to, in the order they are written; empty if we are not exporting
+    pub export_types: Vec<ExportType>,
     /// Where the app will save exported data
     pub export_path: PathBuf,
     /// Query context describing SQL query filters
@@ -79,6 +171,96 @@ pub struct Options {
     pub ignore_disk_space: bool,
     /// An optional filter for conversation participants
     pub conversation_filter: Option<String>,
+    /// How the export reacts to errors
+    pub error_policy: ErrorPolicy,
+    /// If set, split HTML conversations into pages containing at most this many messages
+    pub messages_per_page: Option<usize>,
+    /// If true, drop messages that do not belong to a conversation instead of writing them to an orphaned file
+    pub no_orphaned: bool,
+    /// How to remember messages written by previous exports so they are skipped, if at all
+    pub skip_exported: Option<SkipExportedMode>,
+    /// If set, embed image thumbnails no larger than this many pixels in HTML exports
+    pub thumbnail_size: Option<u32>,
+    /// Optional path to a CSV or JSON file that maps handle identifiers to display names
+    pub handle_map: Option<PathBuf>,
+    /// If true, compress each exported file with gzip
+    pub compress: bool,
+    /// If true, write a CSV that records what happened to each attachment
+    pub attachment_manifest: bool,
+    /// If true, write each conversation and its attachments to its own directory
+    pub directory_per_chat: bool,
+    /// Optional path to a vCard file to read contact names from
+    pub vcard: Option<PathBuf>,
+    /// If true, re-read the exported files to ensure they are valid
+    pub verify: bool,
+    /// If true, include mentions and attachment placeholders from the message body as structured data
+    pub rich_text: bool,
+    /// The text encoding to write exported files in
+    pub output_encoding: OutputEncoding,
+    /// The character written in place of characters the output encoding cannot represent
+    pub encoding_substitute: char,
+    /// If true, start each CSV file with a UTF-8 byte order mark
+    pub bom: bool,
+    /// If set, print at most this many messages from each conversation instead of exporting
+    pub preview: Option<usize>,
+    /// If true, the preview shows the last messages of each conversation instead of the first
+    pub preview_last: bool,
+    /// If true, leave attachments out of the export entirely, keeping only the text of messages
+    pub redact_attachments: bool,
+    /// The order conversations are written in, where the format writes them once all messages are read
+    pub sort_conversations: ConversationOrder,
+    /// If true, write every conversation to a single HTML file with images embedded in it
+    pub single_file: bool,
+    /// How the database is opened; it is never written to
+    pub db_access: DatabaseAccess,
+    /// If set, quote the message a reply responded to above the reply, cut to this many characters
+    pub quote_replies: Option<usize>,
+    /// If set, the `strftime` pattern readable dates are formatted with
+    pub date_format: Option<String>,
+    /// If true, `json` exports read contact cards sent as attachments into a `shared_contact` object
+    pub parse_contact_cards: bool,
+    /// If set, only export conversations with at least this many participants, counting the database owner
+    pub participants_min: Option<usize>,
+    /// If set, only export conversations with at most this many participants, counting the database owner
+    pub participants_max: Option<usize>,
+    /// If true, copy the database, including its write-ahead log, to a temporary directory and read the copy
+    pub copy_db: bool,
+    /// If true, conversations with the same people are merged even if they use different services, i.e. iMessage and SMS
+    pub merge_sms_imessage: bool,
+    /// If true, write every tapback as a record of its own to `reactions.json`
+    pub reactions_as_records: bool,
+    /// If set, skip messages whose text has fewer than this many characters
+    pub min_text_length: Option<usize>,
+    /// If true, skip messages that have attachments but no text
+    pub drop_attachment_only: bool,
+    /// If set, dates are written at this offset from UTC instead of in the host's time zone
+    pub timezone: Option<FixedOffset>,
+    /// If true, `json` exports read when attached photos and videos were created into `media_created`
+    pub media_dates: bool,
+    /// If true, direct messages are named after the other participant even if the chat has a name
+    pub flatten_group_names: bool,
+    /// Optional path to a file that lists the GUIDs or identifiers of the conversations to export
+    pub chats_from_file: Option<PathBuf>,
+    /// The dialect `markdown` exports are written in
+    pub markdown_flavor: MarkdownFlavor,
+    /// Map of the database owner's lowercased addresses to the names their messages are shown with
+    pub caller_id_labels: HashMap<String, String>,
+    /// If true, write the SHA-256 digest of every exported file to `checksums.txt`
+    pub checksums: bool,
+    /// Map of chat GUIDs to names that replace the chat's own name
+    pub group_names: HashMap<String, String>,
+    /// If true, count the messages, words, and characters each participant sent in each conversation
+    pub stats: bool,
+    /// If true, pack the export into a `.zip` archive next to the export path once it is written
+    pub zip: bool,
+    /// How aggressively duplicate contacts and chats are merged
+    pub dedupe_strategy: DedupeStrategy,
+    /// If true, read every message in the database to ensure it can be exported, then exit without writing anything
+    pub validate_only: bool,
+    /// If true, write a page listing the people in each group conversation next to it
+    pub contact_sheets: bool,
+    /// If true, write when each message was sent, delivered, and read to `receipts.json`
+    pub receipts: bool,
 }
 
 impl Options {
@@ -87,130 +269,1049 @@ impl Options {
         let attachment_root: Option<&String> = args.get_one(OPTION_ATTACHMENT_ROOT);
         let attachment_manager_type: Option<&String> = args.get_one(OPTION_ATTACHMENT_MANAGER);
         let diagnostic = args.get_flag(OPTION_DIAGNOSTIC);
+        let diagnostic_json = args.get_flag(OPTION_DIAGNOSTIC_JSON);
         let export_file_type: Option<&String> = args.get_one(OPTION_EXPORT_TYPE);
         let user_export_path: Option<&String> = args.get_one(OPTION_EXPORT_PATH);
         let start_date: Option<&String> = args.get_one(OPTION_START_DATE);
         let end_date: Option<&String> = args.get_one(OPTION_END_DATE);
+        let since: Option<&String> = args.get_one(OPTION_SINCE);
         let no_lazy = args.get_flag(OPTION_DISABLE_LAZY_LOADING);
         let custom_name: Option<&String> = args.get_one(OPTION_CUSTOM_NAME);
         let use_caller_id = args.get_flag(OPTION_USE_CALLER_ID);
         let platform_type: Option<&String> = args.get_one(OPTION_PLATFORM);
         let ignore_disk_space = args.get_flag(OPTION_BYPASS_FREE_SPACE_CHECK);
         let conversation_filter: Option<&String> = args.get_one(OPTION_CONVERSATION_FILTER);
-
-        // Build the export type
-        let export_type: Option<ExportType> = match export_file_type {
-            Some(export_type_str) => {
-                Some(ExportType::from_cli(export_type_str).ok_or(RuntimeError::InvalidOptions(format!(
+        let fail_fast = args.get_flag(OPTION_FAIL_FAST);
+        let best_effort = args.get_flag(OPTION_BEST_EFFORT);
+        let messages_per_page: Option<&String> = args.get_one(OPTION_MESSAGES_PER_PAGE);
+        let no_orphaned = args.get_flag(OPTION_NO_ORPHANED);
+        let skip_exported_mode: Option<&String> = args.get_one(OPTION_SKIP_EXPORTED);
+        let thumbnail_size: Option<&String> = args.get_one(OPTION_THUMBNAIL_SIZE);
+        let handle_map: Option<&String> = args.get_one(OPTION_HANDLE_MAP);
+        let compress = args.get_flag(OPTION_COMPRESS);
+        let attachment_manifest = args.get_flag(OPTION_ATTACHMENT_MANIFEST);
+        let keep_originals = args.get_flag(OPTION_KEEP_ORIGINALS);
+        let directory_per_chat = args.get_flag(OPTION_DIRECTORY_PER_CHAT);
+        let vcard: Option<&String> = args.get_one(OPTION_VCARD);
+        let verify = args.get_flag(OPTION_VERIFY);
+        let rich_text = args.get_flag(OPTION_RICH_TEXT);
+        let threads: Option<&String> = args.get_one(OPTION_THREADS);
+        let output_encoding: Option<&String> = args.get_one(OPTION_OUTPUT_ENCODING);
+        let encoding_substitute: Option<&String> = args.get_one(OPTION_ENCODING_SUBSTITUTE);
+        let bom = args.get_flag(OPTION_BOM);
+        let preview: Option<&String> = args.get_one(OPTION_PREVIEW);
+        let preview_last = args.get_flag(OPTION_PREVIEW_LAST);
+        let redact_attachments = args.get_flag(OPTION_REDACT_ATTACHMENTS);
+        let interactive = args.get_flag(OPTION_INTERACTIVE);
+        let sort_conversations: Option<&String> = args.get_one(OPTION_SORT_CONVERSATIONS);
+        let single_file = args.get_flag(OPTION_SINGLE_FILE);
+        let optimize_gifs = args.get_flag(OPTION_OPTIMIZE_GIFS);
+        let db_access: Option<&String> = args.get_one(OPTION_DB_READONLY);
+        let quote_replies: Option<&String> = args.get_one(OPTION_QUOTE_REPLIES);
+        let max_attachment_size: Option<&String> = args.get_one(OPTION_MAX_ATTACHMENT_SIZE);
+        let date_format: Option<&String> = args.get_one(OPTION_DATE_FORMAT);
+        let only_attachments_of_type: Option<&String> =
+            args.get_one(OPTION_ONLY_ATTACHMENTS_OF_TYPE);
+        let parse_contact_cards = args.get_flag(OPTION_PARSE_CONTACT_CARDS);
+        let participants_min: Option<&String> = args.get_one(OPTION_PARTICIPANTS_MIN);
+        let participants_max: Option<&String> = args.get_one(OPTION_PARTICIPANTS_MAX);
+        let copy_db = args.get_flag(OPTION_COPY_DB);
+        let merge_sms_imessage = args.get_flag(OPTION_MERGE_SMS_IMESSAGE);
+        let reactions_as_records = args.get_flag(OPTION_REACTIONS_AS_RECORDS);
+        let attachment_naming: Option<&String> = args.get_one(OPTION_ATTACHMENT_NAMING);
+        let min_text_length: Option<&String> = args.get_one(OPTION_MIN_TEXT_LENGTH);
+        let drop_attachment_only = args.get_flag(OPTION_DROP_ATTACHMENT_ONLY);
+        let timezone: Option<&String> = args.get_one(OPTION_TIMEZONE);
+        let media_dates = args.get_flag(OPTION_MEDIA_DATES);
+        let flatten_group_names = args.get_flag(OPTION_FLATTEN_GROUP_NAMES);
+        let chats_from_file: Option<&String> = args.get_one(OPTION_CHATS_FROM_FILE);
+        let direction: Option<&String> = args.get_one(OPTION_DIRECTION);
+        let markdown_flavor: Option<&String> = args.get_one(OPTION_MARKDOWN_FLAVOR);
+        let caller_id_labels: Option<&String> = args.get_one(OPTION_CALLER_ID_LABELS);
+        let checksums = args.get_flag(OPTION_CHECKSUMS);
+        let rename_group: Vec<&String> = args
+            .get_many(OPTION_RENAME_GROUP)
+            .map(Iterator::collect)
+            .unwrap_or_default();
+        let stats = args.get_flag(OPTION_STATS);
+        let zip = args.get_flag(OPTION_ZIP);
+        let dedupe_strategy: Option<&String> = args.get_one(OPTION_DEDUPE_STRATEGY);
+        let validate_only = args.get_flag(OPTION_VALIDATE_ONLY);
+        let contact_sheets = args.get_flag(OPTION_CONTACT_SHEETS);
+        let receipts = args.get_flag(OPTION_RECEIPTS);
+
+        // Every problem with the options is collected, so they can all be fixed in one pass
+        let mut problems: Vec<String> = vec![];
+
+        // Build the export types, which may be a list separated by commas
+        let mut export_types: Vec<ExportType> = vec![];
+        for export_type_str in export_file_type.iter().flat_map(|types| types.split(',')) {
+            match ExportType::from_cli(export_type_str.trim()) {
+                Some(export_type) if !export_types.contains(&export_type) => {
+                    export_types.push(export_type);
+                }
+                Some(_) => {}
+                None => problems.push(format!(
                     "{export_type_str} is not a valid export type! Must be one of <{SUPPORTED_FILE_TYPES}>"
-                )))?)
+                )),
             }
-            None => None,
+        }
+        let exports = |export_type: ExportType| export_types.contains(&export_type);
+        let only_exports = |allowed: &[ExportType]| {
+            !export_types.is_empty()
+                && export_types
+                    .iter()
+                    .all(|export_type| allowed.contains(export_type))
         };
 
+        // Ensure the formats of a multi-format export do not overwrite each other
+        if exports(ExportType::Txt) && exports(ExportType::WhatsApp) {
+            problems.push(format!(
+                "`--{OPTION_EXPORT_TYPE}` includes `txt` and `whatsapp`, which both write `.txt` files!"
+            ));
+        }
+        if export_types.len() > 1 && skip_exported_mode.is_some() {
+            problems.push(format!(
+                "Option {OPTION_SKIP_EXPORTED} is enabled, which requires `--{OPTION_EXPORT_TYPE}` to be a single format"
+            ));
+        }
+        // Formats that rewrite their files on each run would be left with only the new messages
+        for export_type in [ExportType::Pdf, ExportType::Json, ExportType::Parquet] {
+            if exports(export_type) && skip_exported_mode.is_some() {
+                problems.push(format!(
+                    "Option {OPTION_SKIP_EXPORTED} is enabled, which requires `--{OPTION_EXPORT_TYPE}` to be a format that is appended to, not `{export_type}`"
+                ));
+            }
+        }
+        if export_types.len() > 1 && preview.is_some() {
+            problems.push(format!(
+                "Option {OPTION_PREVIEW} is enabled, which requires `--{OPTION_EXPORT_TYPE}` to be a single format"
+            ));
+        }
+
         // Ensure an export type is specified if other export options are selected
         if attachment_manager_type.is_some() && export_file_type.is_none() {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Option {OPTION_ATTACHMENT_MANAGER} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
-            )));
+            ));
         }
         if user_export_path.is_some() && export_file_type.is_none() {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Option {OPTION_EXPORT_PATH} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
-            )));
+            ));
         }
         if start_date.is_some() && export_file_type.is_none() {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Option {OPTION_START_DATE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
-            )));
+            ));
         }
         if end_date.is_some() && export_file_type.is_none() {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Option {OPTION_END_DATE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
-            )));
+            ));
+        }
+        if since.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_SINCE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
         }
         if custom_name.is_some() && export_file_type.is_none() {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Option {OPTION_CUSTOM_NAME} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
-            )));
+            ));
         }
         if use_caller_id && export_file_type.is_none() {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Option {OPTION_USE_CALLER_ID} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
-            )));
+            ));
         }
         if conversation_filter.is_some() && export_file_type.is_none() {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Option {OPTION_CONVERSATION_FILTER} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
-            )));
+            ));
+        }
+        if fail_fast && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_FAIL_FAST} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if best_effort && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_BEST_EFFORT} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if messages_per_page.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_MESSAGES_PER_PAGE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if no_orphaned && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_NO_ORPHANED} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if skip_exported_mode.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_SKIP_EXPORTED} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if thumbnail_size.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_THUMBNAIL_SIZE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if handle_map.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_HANDLE_MAP} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if compress && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_COMPRESS} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if attachment_manifest && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_ATTACHMENT_MANIFEST} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if receipts && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_RECEIPTS} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        // The receipts are rewritten on each run, so they must cover every message
+        if receipts && skip_exported_mode.is_some() {
+            problems.push(format!(
+                "`--{OPTION_SKIP_EXPORTED}` is enabled; `--{OPTION_RECEIPTS}` is disallowed"
+            ));
+        }
+        if directory_per_chat && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_DIRECTORY_PER_CHAT} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if vcard.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_VCARD} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if verify && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_VERIFY} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if rich_text && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_RICH_TEXT} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if threads.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_THREADS} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if output_encoding.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_OUTPUT_ENCODING} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if encoding_substitute.is_some() && output_encoding.is_none() {
+            problems.push(format!(
+                "Option {OPTION_ENCODING_SUBSTITUTE} is enabled, which requires `--{OPTION_OUTPUT_ENCODING}`"
+            ));
+        }
+        if bom && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_BOM} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if preview.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_PREVIEW} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if preview_last && preview.is_none() {
+            problems.push(format!(
+                "Option {OPTION_PREVIEW_LAST} is enabled, which requires `--{OPTION_PREVIEW}`"
+            ));
+        }
+        if redact_attachments && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_REDACT_ATTACHMENTS} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        // Other formats write each conversation's file as its messages are read
+        if sort_conversations.is_some() && !exports(ExportType::Json) {
+            problems.push(format!(
+                "Option {OPTION_SORT_CONVERSATIONS} is enabled, which requires `--{OPTION_EXPORT_TYPE}` to include `json`"
+            ));
+        }
+        if single_file && !exports(ExportType::Html) {
+            problems.push(format!(
+                "Option {OPTION_SINGLE_FILE} is enabled, which requires `--{OPTION_EXPORT_TYPE}` to be `html`"
+            ));
+        }
+        if contact_sheets && !exports(ExportType::Html) {
+            problems.push(format!(
+                "Option {OPTION_CONTACT_SHEETS} is enabled, which requires `--{OPTION_EXPORT_TYPE}` to be `html`"
+            ));
+        }
+        if quote_replies.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_QUOTE_REPLIES} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if date_format.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_DATE_FORMAT} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if participants_min.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_PARTICIPANTS_MIN} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if participants_max.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_PARTICIPANTS_MAX} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if min_text_length.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_MIN_TEXT_LENGTH} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if drop_attachment_only && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_DROP_ATTACHMENT_ONLY} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if flatten_group_names && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_FLATTEN_GROUP_NAMES} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if chats_from_file.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_CHATS_FROM_FILE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if diagnostic_json && !diagnostic {
+            problems.push(format!(
+                "Option {OPTION_DIAGNOSTIC_JSON} is enabled, which requires `--{OPTION_DIAGNOSTIC}`"
+            ));
+        }
+        if direction.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_DIRECTION} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if caller_id_labels.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_CALLER_ID_LABELS} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if checksums && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_CHECKSUMS} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if !rename_group.is_empty() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_RENAME_GROUP} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if stats && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_STATS} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        // The counts would only cover the messages written by this run
+        if stats && skip_exported_mode.is_some() {
+            problems.push(format!(
+                "`--{OPTION_SKIP_EXPORTED}` is enabled; `--{OPTION_STATS}` is disallowed"
+            ));
+        }
+        if zip && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_ZIP} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
+        }
+        if timezone.is_some() && export_file_type.is_none() {
+            problems.push(format!(
+                "Option {OPTION_TIMEZONE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+            ));
         }
 
         // Warn the user if they are exporting to a file type for which lazy loading has no effect
-        if no_lazy && export_file_type != Some(&"html".to_string()) {
-            eprintln!(
+        if no_lazy && !exports(ExportType::Html) {
+            warn!(
                 "Option {OPTION_DISABLE_LAZY_LOADING} is enabled, but the format specified is not `html`!"
             );
         }
+        if messages_per_page.is_some() && !exports(ExportType::Html) {
+            warn!(
+                "Option {OPTION_MESSAGES_PER_PAGE} is enabled, but the format specified is not `html`!"
+            );
+        }
+        if thumbnail_size.is_some() && !exports(ExportType::Html) {
+            warn!(
+                "Option {OPTION_THUMBNAIL_SIZE} is enabled, but the format specified is not `html`!"
+            );
+        }
+        if quote_replies.is_some()
+            && !export_types.is_empty()
+            && !exports(ExportType::Txt)
+            && !exports(ExportType::Json)
+            && !exports(ExportType::Markdown)
+        {
+            warn!(
+                "Option {OPTION_QUOTE_REPLIES} is enabled, but the format specified is not `txt`, `json`, or `markdown`!"
+            );
+        }
+        if date_format.is_some() && only_exports(&[ExportType::Json, ExportType::WhatsApp]) {
+            warn!(
+                "Option {OPTION_DATE_FORMAT} is enabled, but the format specified always writes dates the same way!"
+            );
+        }
+        if parse_contact_cards && !exports(ExportType::Json) {
+            warn!(
+                "Option {OPTION_PARSE_CONTACT_CARDS} is enabled, but the format specified is not `json`!"
+            );
+        }
+        if media_dates && !exports(ExportType::Json) {
+            warn!(
+                "Option {OPTION_MEDIA_DATES} is enabled, but the format specified is not `json`!"
+            );
+        }
+        if attachment_manifest && only_exports(&[ExportType::Json]) {
+            warn!(
+                "Option {OPTION_ATTACHMENT_MANIFEST} is enabled, but the format specified is `json`, which does not export attachments!"
+            );
+        }
+        if verify && !export_types.is_empty() && !exports(ExportType::Json) {
+            warn!("Option {OPTION_VERIFY} is enabled, but only `json` exports are verified!");
+        }
+        if output_encoding.is_some()
+            && only_exports(&[ExportType::Json, ExportType::Pdf, ExportType::Parquet])
+        {
+            warn!(
+                "Option {OPTION_OUTPUT_ENCODING} is enabled, but the format specified is always written as UTF-8!"
+            );
+        }
+        if bom && !exports(ExportType::Csv) {
+            warn!("Option {OPTION_BOM} is enabled, but the format specified is not `csv`!");
+        }
+        if markdown_flavor.is_some() && !exports(ExportType::Markdown) {
+            warn!(
+                "Option {OPTION_MARKDOWN_FLAVOR} is enabled, but the format specified is not `markdown`!"
+            );
+        }
+        if stats && !exports(ExportType::Json) {
+            warn!("Option {OPTION_STATS} is enabled, but the format specified is not `json`!");
+        }
+        // The database does not record the device's time zone, so this computer's is the only fallback
+        if timezone.is_none() && export_file_type.is_some() {
+            warn!(
+                "Option {OPTION_TIMEZONE} was not given, so dates are written in this computer's time zone, which may not be the time zone of the device the database came from"
+            );
+        }
+
+        // Validation reads the database without exporting or diagnosing it
+        if validate_only && export_file_type.is_some() {
+            problems.push(format!(
+                "`--{OPTION_VALIDATE_ONLY}` is enabled; `--{OPTION_EXPORT_TYPE}` is disallowed"
+            ));
+        }
+        if validate_only && diagnostic {
+            problems.push(format!(
+                "`--{OPTION_VALIDATE_ONLY}` is enabled; `--{OPTION_DIAGNOSTIC}` is disallowed"
+            ));
+        }
 
         // Ensure that if diagnostics are enabled, no other options are
         if diagnostic && attachment_manager_type.is_some() {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Diagnostics are enabled; {OPTION_ATTACHMENT_MANAGER} is disallowed"
-            )));
+            ));
         }
         if diagnostic && user_export_path.is_some() {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Diagnostics are enabled; {OPTION_EXPORT_PATH} is disallowed"
-            )));
+            ));
         }
         if diagnostic && export_file_type.is_some() {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Diagnostics are enabled; {OPTION_EXPORT_TYPE} is disallowed"
-            )));
+            ));
         }
         if diagnostic && start_date.is_some() {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Diagnostics are enabled; {OPTION_START_DATE} is disallowed"
-            )));
+            ));
         }
         if diagnostic && end_date.is_some() {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Diagnostics are enabled; {OPTION_END_DATE} is disallowed"
-            )));
+            ));
+        }
+        if diagnostic && since.is_some() {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_SINCE} is disallowed"
+            ));
         }
         if diagnostic && use_caller_id {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Diagnostics are enabled; {OPTION_USE_CALLER_ID} is disallowed"
-            )));
+            ));
         }
         if diagnostic && custom_name.is_some() {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Diagnostics are enabled; {OPTION_CUSTOM_NAME} is disallowed"
-            )));
+            ));
         }
         if diagnostic && conversation_filter.is_some() {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "Diagnostics are enabled; {OPTION_CONVERSATION_FILTER} is disallowed"
-            )));
+            ));
+        }
+        if diagnostic && fail_fast {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_FAIL_FAST} is disallowed"
+            ));
+        }
+        if diagnostic && best_effort {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_BEST_EFFORT} is disallowed"
+            ));
+        }
+        if diagnostic && messages_per_page.is_some() {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_MESSAGES_PER_PAGE} is disallowed"
+            ));
+        }
+        if diagnostic && no_orphaned {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_NO_ORPHANED} is disallowed"
+            ));
+        }
+        if diagnostic && skip_exported_mode.is_some() {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_SKIP_EXPORTED} is disallowed"
+            ));
+        }
+        if diagnostic && thumbnail_size.is_some() {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_THUMBNAIL_SIZE} is disallowed"
+            ));
+        }
+        if diagnostic && compress {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_COMPRESS} is disallowed"
+            ));
+        }
+        if diagnostic && attachment_manifest {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_ATTACHMENT_MANIFEST} is disallowed"
+            ));
+        }
+        if diagnostic && receipts {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_RECEIPTS} is disallowed"
+            ));
+        }
+        if diagnostic && directory_per_chat {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_DIRECTORY_PER_CHAT} is disallowed"
+            ));
+        }
+        if diagnostic && vcard.is_some() {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_VCARD} is disallowed"
+            ));
+        }
+        if diagnostic && verify {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_VERIFY} is disallowed"
+            ));
+        }
+        if diagnostic && rich_text {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_RICH_TEXT} is disallowed"
+            ));
+        }
+        if diagnostic && threads.is_some() {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_THREADS} is disallowed"
+            ));
+        }
+        if diagnostic && output_encoding.is_some() {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_OUTPUT_ENCODING} is disallowed"
+            ));
+        }
+        if diagnostic && bom {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_BOM} is disallowed"
+            ));
+        }
+        if diagnostic && preview.is_some() {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_PREVIEW} is disallowed"
+            ));
+        }
+        if diagnostic && redact_attachments {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_REDACT_ATTACHMENTS} is disallowed"
+            ));
+        }
+        if diagnostic && sort_conversations.is_some() {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_SORT_CONVERSATIONS} is disallowed"
+            ));
+        }
+        if diagnostic && single_file {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_SINGLE_FILE} is disallowed"
+            ));
+        }
+        if diagnostic && quote_replies.is_some() {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_QUOTE_REPLIES} is disallowed"
+            ));
+        }
+        if diagnostic && max_attachment_size.is_some() {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_MAX_ATTACHMENT_SIZE} is disallowed"
+            ));
+        }
+        if diagnostic && date_format.is_some() {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_DATE_FORMAT} is disallowed"
+            ));
+        }
+        if diagnostic && only_attachments_of_type.is_some() {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_ONLY_ATTACHMENTS_OF_TYPE} is disallowed"
+            ));
+        }
+        if diagnostic && parse_contact_cards {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_PARSE_CONTACT_CARDS} is disallowed"
+            ));
+        }
+        if diagnostic && media_dates {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_MEDIA_DATES} is disallowed"
+            ));
+        }
+        if diagnostic && (participants_min.is_some() || participants_max.is_some()) {
+            problems.push(format!(
+                "Diagnostics are enabled; {OPTION_PARTICIPANTS_MIN} and {OPTION_PARTICIPANTS_MAX} are disallowed"
+            ));
         }
 
         // Ensure that there are no custom name conflicts
         if custom_name.is_some() && use_caller_id {
-            return Err(RuntimeError::InvalidOptions(format!(
+            problems.push(format!(
                 "`--{OPTION_CUSTOM_NAME}` is enabled; `--{OPTION_USE_CALLER_ID}` is disallowed"
-            )));
+            ));
+        }
+
+        // Ensure that there are no start date conflicts
+        if start_date.is_some() && since.is_some() {
+            problems.push(format!(
+                "`--{OPTION_START_DATE}` is enabled; `--{OPTION_SINCE}` is disallowed"
+            ));
+        }
+
+        // Ensure that compressed files are not verified, since they cannot be read back
+        if compress && verify {
+            problems.push(format!(
+                "`--{OPTION_COMPRESS}` is enabled; `--{OPTION_VERIFY}` is disallowed"
+            ));
+        }
+
+        // Ensure that previews do not write anything to disk
+        if preview.is_some() && attachment_manager_type.is_some() {
+            problems.push(format!(
+                "`--{OPTION_PREVIEW}` is enabled; `--{OPTION_ATTACHMENT_MANAGER}` is disallowed"
+            ));
+        }
+        if preview.is_some() && skip_exported_mode.is_some() {
+            problems.push(format!(
+                "`--{OPTION_PREVIEW}` is enabled; `--{OPTION_SKIP_EXPORTED}` is disallowed"
+            ));
+        }
+        if preview.is_some() && attachment_manifest {
+            problems.push(format!(
+                "`--{OPTION_PREVIEW}` is enabled; `--{OPTION_ATTACHMENT_MANIFEST}` is disallowed"
+            ));
+        }
+        if preview.is_some() && receipts {
+            problems.push(format!(
+                "`--{OPTION_PREVIEW}` is enabled; `--{OPTION_RECEIPTS}` is disallowed"
+            ));
+        }
+        if preview.is_some() && verify {
+            problems.push(format!(
+                "`--{OPTION_PREVIEW}` is enabled; `--{OPTION_VERIFY}` is disallowed"
+            ));
+        }
+        if preview.is_some() && checksums {
+            problems.push(format!(
+                "`--{OPTION_PREVIEW}` is enabled; `--{OPTION_CHECKSUMS}` is disallowed"
+            ));
+        }
+        if preview.is_some() && zip {
+            problems.push(format!(
+                "`--{OPTION_PREVIEW}` is enabled; `--{OPTION_ZIP}` is disallowed"
+            ));
+        }
+
+        // Ensure that archived exports start from an empty directory, which is removed once it is packed
+        if zip && skip_exported_mode.is_some() {
+            problems.push(format!(
+                "`--{OPTION_ZIP}` is enabled; `--{OPTION_SKIP_EXPORTED}` is disallowed"
+            ));
+        }
+
+        // Ensure that redacted attachments are not copied or recorded
+        if redact_attachments && attachment_manager_type.is_some() {
+            problems.push(format!(
+                "`--{OPTION_REDACT_ATTACHMENTS}` is enabled; `--{OPTION_ATTACHMENT_MANAGER}` is disallowed"
+            ));
+        }
+        if redact_attachments && attachment_manifest {
+            problems.push(format!(
+                "`--{OPTION_REDACT_ATTACHMENTS}` is enabled; `--{OPTION_ATTACHMENT_MANIFEST}` is disallowed"
+            ));
+        }
+
+        // Ensure that every conversation is written to the single file, once
+        if single_file && messages_per_page.is_some() {
+            problems.push(format!(
+                "`--{OPTION_SINGLE_FILE}` is enabled; `--{OPTION_MESSAGES_PER_PAGE}` is disallowed"
+            ));
+        }
+        if single_file && directory_per_chat {
+            problems.push(format!(
+                "`--{OPTION_SINGLE_FILE}` is enabled; `--{OPTION_DIRECTORY_PER_CHAT}` is disallowed"
+            ));
+        }
+        if single_file && skip_exported_mode.is_some() {
+            problems.push(format!(
+                "`--{OPTION_SINGLE_FILE}` is enabled; `--{OPTION_SKIP_EXPORTED}` is disallowed"
+            ));
+        }
+        if single_file && contact_sheets {
+            problems.push(format!(
+                "`--{OPTION_SINGLE_FILE}` is enabled; `--{OPTION_CONTACT_SHEETS}` is disallowed"
+            ));
+        }
+
+        // Ensure that only one error policy is selected
+        if fail_fast && best_effort {
+            problems.push(format!(
+                "`--{OPTION_FAIL_FAST}` is enabled; `--{OPTION_BEST_EFFORT}` is disallowed"
+            ));
+        }
+        let error_policy = if fail_fast {
+            ErrorPolicy::FailFast
+        } else {
+            ErrorPolicy::BestEffort
+        };
+
+        // Parse the page size, if provided
+        let messages_per_page = match messages_per_page {
+            Some(limit) => match limit.parse::<usize>() {
+                Ok(limit) if limit > 0 => Some(limit),
+                _ => {
+                    problems.push(format!(
+                        "{limit} is not a valid page size! Must be a positive integer"
+                    ));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        // Parse the preview length, if provided
+        let preview = match preview {
+            Some(limit) => match limit.parse::<usize>() {
+                Ok(limit) if limit > 0 => Some(limit),
+                _ => {
+                    problems.push(format!(
+                        "{limit} is not a valid preview length! Must be a positive integer"
+                    ));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        // Parse the length of quoted replies, if provided
+        let quote_replies = match quote_replies {
+            Some(length) => match length.parse::<usize>() {
+                Ok(length) if length > 0 => Some(length),
+                _ => {
+                    problems.push(format!(
+                        "{length} is not a valid quote length! Must be a positive integer"
+                    ));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        // Parse the range of conversation sizes, if provided
+        let parse_participants = |count: Option<&String>, option: &str| match count {
+            Some(count) => match count.parse::<usize>() {
+                Ok(count) if count > 0 => Ok(Some(count)),
+                _ => Err(format!(
+                    "{count} is not a valid value for {option}! Must be a positive integer"
+                )),
+            },
+            None => Ok(None),
+        };
+        let participants_min = parse_participants(participants_min, OPTION_PARTICIPANTS_MIN)
+            .unwrap_or_else(|why| {
+                problems.push(why);
+                None
+            });
+        let participants_max = parse_participants(participants_max, OPTION_PARTICIPANTS_MAX)
+            .unwrap_or_else(|why| {
+                problems.push(why);
+                None
+            });
+        let min_text_length = match min_text_length {
+            Some(length) => match length.parse::<usize>() {
+                Ok(length) if length > 0 => Some(length),
+                _ => {
+                    problems.push(format!(
+                        "{length} is not a valid value for {OPTION_MIN_TEXT_LENGTH}! Must be a positive integer"
+                    ));
+                    None
+                }
+            },
+            None => None,
+        };
+        if let (Some(min), Some(max)) = (participants_min, participants_max) {
+            if min > max {
+                problems.push(format!(
+                    "{OPTION_PARTICIPANTS_MIN} {min} is larger than {OPTION_PARTICIPANTS_MAX} {max}, so no conversations would be exported!"
+                ));
+            }
+        }
+
+        // Parse the kinds of attachments to copy, if provided
+        let only_kinds = match only_attachments_of_type {
+            Some(kinds) => {
+                let parsed: Option<Vec<AttachmentKind>> = kinds
+                    .split(',')
+                    .map(|kind| AttachmentKind::from_cli(kind.trim()))
+                    .collect();
+                if parsed.is_none() {
+                    problems.push(format!(
+                        "{kinds} is not a valid list of attachment types! Must be one or more of {SUPPORTED_ATTACHMENT_KINDS}, separated by commas"
+                    ));
+                }
+                parsed
+            }
+            None => None,
+        };
+
+        // Determine how copied attachments are named
+        let attachment_naming = match attachment_naming {
+            Some(naming) => AttachmentNaming::from_cli(naming).unwrap_or_else(|| {
+                problems.push(format!(
+                    "{naming} is not a valid attachment naming scheme! Must be one of <{SUPPORTED_ATTACHMENT_NAMINGS}>"
+                ));
+                AttachmentNaming::default()
+            }),
+            None => AttachmentNaming::default(),
+        };
+
+        // Parse the largest attachment to copy, if provided
+        let max_attachment_size = match max_attachment_size {
+            Some(size) => match parse_file_size(size) {
+                Some(size) if size > 0 => Some(size),
+                _ => {
+                    problems.push(format!(
+                        "{size} is not a valid attachment size! Must be a positive size like `50MB`"
+                    ));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        // Ensure the date format can be used to format dates, if provided
+        let date_format = match date_format {
+            Some(pattern) if !is_valid_format(pattern) => {
+                problems.push(format!(
+                    "{pattern} is not a valid date format! Must be a `strftime` pattern like `%d/%m/%Y %H:%M`"
+                ));
+                None
+            }
+            pattern => pattern.cloned(),
+        };
+
+        // Parse the time zone dates are written in, if provided
+        let timezone = match timezone {
+            Some(offset) => parse_utc_offset(offset).or_else(|| {
+                problems.push(format!(
+                    "{offset} is not a valid time zone! Must be an offset from UTC like `+05:30` or `-08:00`, or `UTC`"
+                ));
+                None
+            }),
+            None => None,
+        };
+
+        // Parse the thumbnail size, if provided
+        let thumbnail_size = match thumbnail_size {
+            Some(size) => match size.parse::<u32>() {
+                Ok(size) if size > 0 => Some(size),
+                _ => {
+                    problems.push(format!(
+                        "{size} is not a valid thumbnail size! Must be a positive integer"
+                    ));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        // Parse the number of converter threads, if provided
+        let threads = match threads {
+            Some(count) => match count.parse::<usize>() {
+                Ok(count) if count > 0 => Some(count),
+                _ => {
+                    problems.push(format!(
+                        "{count} is not a valid number of threads! Must be a positive integer"
+                    ));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        // Determine the output encoding; `json` and `pdf` files are always written as UTF-8
+        let output_encoding = match output_encoding {
+            Some(encoding) => OutputEncoding::from_cli(encoding).unwrap_or_else(|| {
+                problems.push(format!(
+                    "{encoding} is not a valid output encoding! Must be one of <{SUPPORTED_ENCODINGS}>"
+                ));
+                OutputEncoding::Utf8
+            }),
+            None => OutputEncoding::Utf8,
+        };
+        // Parse the names to show for each of the database owner's addresses
+        let mut labels = HashMap::new();
+        for pair in caller_id_labels
+            .into_iter()
+            .flat_map(|pairs| pairs.split(','))
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            match pair.split_once('=') {
+                Some((address, label))
+                    if !address.trim().is_empty() && !label.trim().is_empty() =>
+                {
+                    labels.insert(address.trim().to_lowercase(), label.trim().to_string());
+                }
+                _ => problems.push(format!(
+                    "{pair} is not a valid caller ID label! Must be shaped like `address=label`"
+                )),
+            }
+        }
+
+        // Parse the names that replace the names of chats
+        let mut group_names = HashMap::new();
+        for pair in rename_group {
+            match pair.split_once('=') {
+                Some((guid, name)) if !guid.trim().is_empty() && !name.trim().is_empty() => {
+                    group_names.insert(guid.trim().to_string(), name.trim().to_string());
+                }
+                _ => problems.push(format!(
+                    "{pair} is not a valid group name! Must be shaped like `guid=name`"
+                )),
+            }
+        }
+
+        // Determine the dialect of markdown exports
+        let markdown_flavor = match markdown_flavor {
+            Some(flavor) => MarkdownFlavor::from_cli(flavor).unwrap_or_else(|| {
+                problems.push(format!(
+                    "{flavor} is not a valid markdown flavor! Must be one of <{SUPPORTED_MARKDOWN_FLAVORS}>"
+                ));
+                MarkdownFlavor::default()
+            }),
+            None => MarkdownFlavor::default(),
+        };
+
+        let output_encoding =
+            match only_exports(&[ExportType::Json, ExportType::Pdf, ExportType::Parquet]) {
+                true => OutputEncoding::Utf8,
+                false => output_encoding,
+            };
+
+        // Parse the substitute character, which must be a single character the output encoding can represent
+        let encoding_substitute = match encoding_substitute {
+            Some(substitute) => {
+                let mut chars = substitute.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(character), None) if output_encoding.can_encode(character) => character,
+                    _ => {
+                        problems.push(format!(
+                            "{substitute} is not a valid substitute! Must be a single character that {output_encoding} can represent"
+                        ));
+                        '?'
+                    }
+                }
+            }
+            None => '?',
+        };
+
+        // Ensure that a UTF-8 byte order mark is not written to files in another encoding
+        if bom && output_encoding != OutputEncoding::Utf8 {
+            problems.push(format!(
+                "`--{OPTION_OUTPUT_ENCODING}` is `{output_encoding}`; `--{OPTION_BOM}` is disallowed"
+            ));
         }
 
-        // Build query context
+        // Build query context, with dates at midnight in the time zone dates are written in
         let mut query_context = QueryContext::default();
         if let Some(start) = start_date {
-            if let Err(why) = query_context.set_start(start) {
-                return Err(RuntimeError::InvalidOptions(format!("{why}")));
+            let set = match timezone {
+                Some(offset) => query_context.set_start_in(start, &offset),
+                None => query_context.set_start(start),
+            };
+            if let Err(why) = set {
+                problems.push(why.to_string());
             }
         }
         if let Some(end) = end_date {
-            if let Err(why) = query_context.set_end(end) {
-                return Err(RuntimeError::InvalidOptions(format!("{why}")));
+            let set = match timezone {
+                Some(offset) => query_context.set_end_in(end, &offset),
+                None => query_context.set_end(end),
+            };
+            if let Err(why) = set {
+                problems.push(why.to_string());
+            }
+        }
+        if let Some(since) = since {
+            if let Err(why) = query_context.set_since(since) {
+                problems.push(why.to_string());
+            }
+        }
+        if let Some(direction) = direction {
+            match MessageDirection::from_cli(direction) {
+                Some(direction) => direction.apply(&mut query_context),
+                None => problems.push(format!(
+                    "{direction} is not a valid message direction! Must be one of <{SUPPORTED_DIRECTIONS}>"
+                )),
             }
         }
 
@@ -222,49 +1323,270 @@ impl Options {
 
         // Build the Platform
         let platform = match platform_type {
-            Some(platform_str) => Platform::from_cli(platform_str).ok_or(
-                RuntimeError::InvalidOptions(format!(
-                "{platform_str} is not a valid platform! Must be one of <{SUPPORTED_PLATFORMS}>")),
-            )?,
+            Some(platform_str) => Platform::from_cli(platform_str).unwrap_or_else(|| {
+                problems.push(format!(
+                    "{platform_str} is not a valid platform! Must be one of <{SUPPORTED_PLATFORMS}>"
+                ));
+                Platform::determine(&db_path)
+            }),
             None => Platform::determine(&db_path),
         };
 
         // Validate that the custom attachment root exists, if provided
         if let Some(path) = attachment_root {
-            let custom_attachment_path = PathBuf::from(path);
+            let custom_attachment_path = PathBuf::from(expand_home(path));
             if !custom_attachment_path.exists() {
-                return Err(RuntimeError::InvalidOptions(format!(
+                problems.push(format!(
                     "Supplied {OPTION_ATTACHMENT_ROOT} `{path}` does not exist!"
-                )));
+                ));
+            }
+        };
+
+        // Validate that the handle map exists, if provided
+        if let Some(path) = handle_map {
+            if !PathBuf::from(path).exists() {
+                problems.push(format!(
+                    "Supplied {OPTION_HANDLE_MAP} `{path}` does not exist!"
+                ));
+            }
+        };
+
+        // Validate that the chat list exists, if provided
+        if let Some(path) = chats_from_file {
+            if !PathBuf::from(path).exists() {
+                problems.push(format!(
+                    "Supplied {OPTION_CHATS_FROM_FILE} `{path}` does not exist!"
+                ));
+            }
+        };
+
+        // Validate that the vCard file exists, if provided
+        if let Some(path) = vcard {
+            if !PathBuf::from(path).exists() {
+                problems.push(format!("Supplied {OPTION_VCARD} `{path}` does not exist!"));
             }
         };
 
         // Warn the user that custom attachment roots have no effect on iOS backups
         if attachment_root.is_some() && platform == Platform::iOS {
-            eprintln!(
+            warn!(
                 "Option {OPTION_ATTACHMENT_ROOT} is enabled, but the platform is {}, so the root will have no effect!", Platform::iOS
             );
         }
 
         // Determine the attachment manager mode
         let attachment_manager_mode = match attachment_manager_type {
-            Some(manager) => {
-                AttachmentManagerMode::from_cli(manager).ok_or(RuntimeError::InvalidOptions(format!(
+            Some(manager) => AttachmentManagerMode::from_cli(manager).unwrap_or_else(|| {
+                problems.push(format!(
                     "{manager} is not a valid attachment manager mode! Must be one of <{SUPPORTED_ATTACHMENT_MANAGER_MODES}>"
-                )))?
-            }
+                ));
+                AttachmentManagerMode::default()
+            }),
             None => AttachmentManagerMode::default(),
         };
 
-        // Validate the provided export path
-        let export_path = validate_path(user_export_path, &export_type.as_ref())?;
+        // Thumbnails are generated next to copied attachments, so there must be copies
+        if thumbnail_size.is_some() && attachment_manager_mode == AttachmentManagerMode::Disabled {
+            problems.push(format!(
+                "Option {OPTION_THUMBNAIL_SIZE} is enabled, which requires `--{OPTION_ATTACHMENT_MANAGER}` to copy attachments"
+            ));
+        }
+
+        // Only copied attachments can be skipped for their size
+        if max_attachment_size.is_some()
+            && attachment_manager_mode == AttachmentManagerMode::Disabled
+        {
+            problems.push(format!(
+                "Option {OPTION_MAX_ATTACHMENT_SIZE} is enabled, which requires `--{OPTION_ATTACHMENT_MANAGER}` to copy attachments"
+            ));
+        }
+
+        // Only copied attachments are renamed
+        if attachment_naming != AttachmentNaming::default()
+            && attachment_manager_mode == AttachmentManagerMode::Disabled
+        {
+            problems.push(format!(
+                "Option {OPTION_ATTACHMENT_NAMING} is enabled, which requires `--{OPTION_ATTACHMENT_MANAGER}` to copy attachments"
+            ));
+        }
+
+        // Only copied attachments can be filtered by their type
+        if only_attachments_of_type.is_some()
+            && attachment_manager_mode == AttachmentManagerMode::Disabled
+        {
+            problems.push(format!(
+                "Option {OPTION_ONLY_ATTACHMENTS_OF_TYPE} is enabled, which requires `--{OPTION_ATTACHMENT_MANAGER}` to copy attachments"
+            ));
+        }
+
+        // Originals are copied next to converted stickers, so stickers must be converted
+        if keep_originals
+            && !matches!(
+                attachment_manager_mode,
+                AttachmentManagerMode::Basic | AttachmentManagerMode::Full
+            )
+        {
+            problems.push(format!(
+                "Option {OPTION_KEEP_ORIGINALS} is enabled, which requires `--{OPTION_ATTACHMENT_MANAGER}` to be `basic` or `full`"
+            ));
+        }
+
+        // GIFs are optimized when they are converted, so attachments must be converted
+        if optimize_gifs
+            && !matches!(
+                attachment_manager_mode,
+                AttachmentManagerMode::Basic | AttachmentManagerMode::Full
+            )
+        {
+            problems.push(format!(
+                "Option {OPTION_OPTIMIZE_GIFS} is enabled, which requires `--{OPTION_ATTACHMENT_MANAGER}` to be `basic` or `full`"
+            ));
+        }
+
+        // Prompts are only shown when a conversion fails, so attachments must be converted
+        if interactive
+            && !matches!(
+                attachment_manager_mode,
+                AttachmentManagerMode::Basic | AttachmentManagerMode::Full
+            )
+        {
+            problems.push(format!(
+                "Option {OPTION_INTERACTIVE} is enabled, which requires `--{OPTION_ATTACHMENT_MANAGER}` to be `basic` or `full`"
+            ));
+        }
+
+        // Determine how to remember exported messages, if requested
+        let skip_exported = match skip_exported_mode {
+            Some(mode) => {
+                let skip_exported = SkipExportedMode::from_cli(mode);
+                if skip_exported.is_none() {
+                    problems.push(format!(
+                        "{mode} is not a valid skip exported mode! Must be one of <{SUPPORTED_SKIP_EXPORTED_MODES}>"
+                    ));
+                }
+                skip_exported
+            }
+            None => None,
+        };
+
+        // Determine the order conversations are written in
+        let sort_conversations = match sort_conversations {
+            Some(order) => ConversationOrder::from_cli(order).unwrap_or_else(|| {
+                problems.push(format!(
+                    "{order} is not a valid conversation order! Must be one of <{SUPPORTED_CONVERSATION_ORDERS}>"
+                ));
+                ConversationOrder::default()
+            }),
+            None => ConversationOrder::default(),
+        };
+
+        // Determine how duplicate contacts and chats are merged
+        let dedupe_strategy = match dedupe_strategy {
+            Some(strategy) => DedupeStrategy::from_cli(strategy).unwrap_or_else(|| {
+                problems.push(format!(
+                    "{strategy} is not a valid dedupe strategy! Must be one of <{SUPPORTED_DEDUPE_STRATEGIES}>"
+                ));
+                DedupeStrategy::default()
+            }),
+            None => DedupeStrategy::default(),
+        };
+        if merge_sms_imessage && dedupe_strategy == DedupeStrategy::None {
+            problems.push(format!(
+                "Option {OPTION_MERGE_SMS_IMESSAGE} is enabled, which requires `--{OPTION_DEDUPE_STRATEGY}` to merge duplicate contacts"
+            ));
+        }
+
+        // Determine how the database is opened
+        let db_access = match db_access {
+            Some(access) => DatabaseAccess::from_cli(access).unwrap_or_else(|| {
+                problems.push(format!(
+                    "{access} is not a valid database access mode! Must be one of <{SUPPORTED_DB_ACCESS}>"
+                ));
+                DatabaseAccess::default()
+            }),
+            None => DatabaseAccess::default(),
+        };
+
+        // Validate the provided export path; existing export data is expected when skipping exported messages
+        // and is left alone by previews, which do not write anything
+        let overwrite_types = match skip_exported.is_none() && preview.is_none() {
+            true => export_types.as_slice(),
+            false => &[],
+        };
+        let export_path = match validate_path(user_export_path, overwrite_types) {
+            Ok(path) => path,
+            Err(RuntimeError::InvalidOptions(why)) => {
+                problems.push(why);
+                PathBuf::new()
+            }
+            Err(why) => return Err(why),
+        };
+
+        // Names that depend on which attachments a run copies can collide with files an earlier run wrote
+        if matches!(
+            attachment_naming,
+            AttachmentNaming::Original | AttachmentNaming::Sequential
+        ) {
+            if skip_exported.is_some() {
+                problems.push(format!(
+                    "`--{OPTION_SKIP_EXPORTED}` is enabled; `--{OPTION_ATTACHMENT_NAMING} {attachment_naming}` is disallowed"
+                ));
+            } else if export_path
+                .read_dir()
+                .is_ok_and(|mut files| files.next().is_some())
+            {
+                problems.push(format!(
+                    "Option {OPTION_ATTACHMENT_NAMING} is `{attachment_naming}`, but export path {export_path:?} is not empty! Pick a new directory so copied attachments do not replace earlier ones"
+                ));
+            }
+        }
+
+        if zip && preview.is_none() {
+            if export_path.exists() {
+                problems.push(format!(
+                    "Option {OPTION_ZIP} is enabled, but export path {export_path:?} already exists! Pick a new directory to write the export to before it is packed"
+                ));
+            }
+            let archive = archive_path(&export_path);
+            if archive.exists() {
+                problems.push(format!(
+                    "Option {OPTION_ZIP} is enabled, but archive {archive:?} already exists!"
+                ));
+            }
+        }
+
+        if !problems.is_empty() {
+            return Err(RuntimeError::InvalidOptions(problems.join("\n")));
+        }
 
         Ok(Options {
             db_path,
             attachment_root: attachment_root.cloned(),
-            attachment_manager: AttachmentManager::from(attachment_manager_mode),
+            attachment_manager: {
+                let attachment_manager = AttachmentManager::from(attachment_manager_mode);
+                AttachmentManager {
+                    gif_optimizer: if optimize_gifs {
+                        GifOptimizer::determine()
+                    } else {
+                        None
+                    },
+                    video_probe: if media_dates {
+                        VideoProbe::determine()
+                    } else {
+                        None
+                    },
+                    max_size: max_attachment_size,
+                    only_kinds,
+                    keep_originals,
+                    interactive,
+                    threads: threads.unwrap_or(attachment_manager.threads),
+                    naming: attachment_naming,
+                    ..attachment_manager
+                }
+            },
             diagnostic,
-            export_type,
+            diagnostic_json,
+            export_types,
             export_path,
             query_context,
             no_lazy,
@@ -273,6 +1595,51 @@ impl Options {
             platform,
             ignore_disk_space,
             conversation_filter: conversation_filter.cloned(),
+            error_policy,
+            messages_per_page,
+            no_orphaned,
+            skip_exported,
+            thumbnail_size,
+            handle_map: handle_map.map(PathBuf::from),
+            compress,
+            attachment_manifest,
+            directory_per_chat,
+            vcard: vcard.map(PathBuf::from),
+            verify,
+            rich_text,
+            output_encoding,
+            encoding_substitute,
+            bom,
+            preview,
+            preview_last,
+            redact_attachments,
+            sort_conversations,
+            single_file,
+            db_access,
+            quote_replies,
+            date_format,
+            parse_contact_cards,
+            participants_min,
+            participants_max,
+            copy_db,
+            merge_sms_imessage,
+            reactions_as_records,
+            min_text_length,
+            drop_attachment_only,
+            timezone,
+            media_dates,
+            flatten_group_names,
+            chats_from_file: chats_from_file.map(PathBuf::from),
+            markdown_flavor,
+            caller_id_labels: labels,
+            group_names,
+            stats,
+            zip,
+            checksums,
+            dedupe_strategy,
+            validate_only,
+            contact_sheets,
+            receipts,
         })
     }
 
@@ -283,21 +1650,50 @@ impl Options {
             Platform::macOS => self.db_path.clone(),
         }
     }
-}
 
-/// Ensure export path is empty or does not contain files of the existing export type
-///
+    /// Get the directory attachments are read from, or `None` on iOS, where they are stored in the backup
+    pub fn get_attachment_root(&self) -> Option<PathBuf> {
+        match self.platform {
+            Platform::iOS => None,
+            Platform::macOS => Some(PathBuf::from(expand_home(
+                self.attachment_root
+                    .as_deref()
+                    .unwrap_or(DEFAULT_ATTACHMENT_ROOT),
+            ))),
+        }
+    }
+
+    /// Stop copying attachments if the directory they are read from does not exist, like when only the database was copied
+    ///
+    /// Every attachment would otherwise fail to copy on its own, so this warns once instead.
+    /// Attachments are still listed with the messages they were sent with.
+    pub fn disable_missing_attachments(&mut self) {
+        if self.attachment_manager.mode == AttachmentManagerMode::Disabled {
+            return;
+        }
+        if let Some(root) = self.get_attachment_root().filter(|root| !root.exists()) {
+            warn!(
+                "The attachments directory `{}` does not exist, so attachments will be listed but not copied! Use `--{OPTION_ATTACHMENT_ROOT}` if they are stored somewhere else",
+                root.display()
+            );
+            self.attachment_manager.mode = AttachmentManagerMode::Disabled;
+        }
+    }
+}
+
+/// Ensure export path is empty or does not contain files of the existing export types
+///
 /// We have to allocate a `PathBuf` here because it can be created from data owned by this function in the default state
 fn validate_path(
     export_path: Option<&String>,
-    export_type: &Option<&ExportType>,
+    export_types: &[ExportType],
 ) -> Result<PathBuf, RuntimeError> {
     // Build a path from the user-provided data or the default location
     let resolved_path =
         PathBuf::from(export_path.unwrap_or(&format!("{}/{DEFAULT_OUTPUT_DIR}", home())));
 
-    // If there is an export type selected, ensure we do not overwrite files of the same type
-    if let Some(export_type) = export_type {
+    // For each export type selected, ensure we do not overwrite files of the same type
+    for export_type in export_types {
         if resolved_path.exists() {
             // Get the word to use if there is a problem with the specified path
             let path_word = match export_path {
@@ -310,8 +1706,12 @@ fn validate_path(
                 Ok(files) => {
                     let export_type_extension = export_type.to_string();
                     for file in files.flatten() {
-                        if file
-                            .path()
+                        // Compressed exports have the export type's extension before the gzip extension
+                        let mut path = file.path();
+                        if path.extension().is_some_and(|s| s == GZIP_EXTENSION) {
+                            path = path.with_extension("");
+                        }
+                        if path
                             .extension()
                             .is_some_and(|s| s.to_str().unwrap_or("") == export_type_extension)
                         {
@@ -328,7 +1728,7 @@ fn validate_path(
                 }
             }
         }
-    };
+    }
 
     Ok(resolved_path)
 }
@@ -347,11 +1747,18 @@ fn get_command() -> Command {
             .action(ArgAction::SetTrue)
             .display_order(0),
         )
+        .arg(
+            Arg::new(OPTION_DIAGNOSTIC_JSON)
+                .long(OPTION_DIAGNOSTIC_JSON)
+                .help(format!("Print diagnostic information as a JSON object instead of text, i.e. for scripted health checks\nRequires --{OPTION_DIAGNOSTIC}\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(59)
+        )
         .arg(
             Arg::new(OPTION_EXPORT_TYPE)
             .short('f')
             .long(OPTION_EXPORT_TYPE)
-            .help("Specify a single file format to export messages into\n")
+            .help("Specify a file format to export messages into\nSeparate formats with commas, like `json,html`, to export several in one run\nThe caches are built once, but each format reads the messages again\n")
             .display_order(1)
             .value_name(SUPPORTED_FILE_TYPES),
         )
@@ -359,7 +1766,7 @@ fn get_command() -> Command {
             Arg::new(OPTION_ATTACHMENT_MANAGER)
             .short('c')
             .long(OPTION_ATTACHMENT_MANAGER)
-            .help(format!("Specify an optional method to use when copying message attachments\n`clone` will copy all files without converting anything\n`basic` will copy all files and convert HEIC images to JPEG\n`full` will copy all files and convert HEIC files to JPEG, CAF to MP4, and MOV to MP4\nIf omitted, the default is `{}`\nImageMagick is required to convert images on non-macOS platforms\nffmpeg is required to convert audio on non-macOS platforms and video on all platforms\n", AttachmentManagerMode::default()))
+            .help(format!("Specify an optional method to use when copying message attachments\n`clone` will copy all files without converting anything\n`basic` will copy all files and convert HEIC images to JPEG\n`full` will copy all files and convert HEIC files to JPEG, CAF to MP4, and MOV to MP4\nIf omitted, the default is `{}`\nImageMagick is required to convert images on non-macOS platforms\nffmpeg is required to convert audio on non-macOS platforms and video on all platforms\nIf the attachments directory does not exist, attachments are listed but not copied\n", AttachmentManagerMode::default()))
             .display_order(2)
             .value_name(SUPPORTED_ATTACHMENT_MANAGER_MODES),
         )
@@ -375,7 +1782,7 @@ fn get_command() -> Command {
             Arg::new(OPTION_ATTACHMENT_ROOT)
                 .short('r')
                 .long(OPTION_ATTACHMENT_ROOT)
-                .help(format!("Specify an optional custom path to look for attachments in (macOS only)\nOnly use this if attachments are stored separately from the database's default location\nThe default location is {}\n", DEFAULT_ATTACHMENT_ROOT.replacen('~', &home(), 1)))
+                .help(format!("Specify an optional custom path to look for attachments in (macOS only)\nOnly use this if attachments are stored separately from the database's default location\nThe default location is {}\n", expand_home(DEFAULT_ATTACHMENT_ROOT)))
                 .display_order(4)
                 .value_name("path/to/attachments"),
         )
@@ -411,20 +1818,27 @@ fn get_command() -> Command {
                 .display_order(8)
                 .value_name("YYYY-MM-DD"),
         )
+        .arg(
+            Arg::new(OPTION_SINCE)
+                .long(OPTION_SINCE)
+                .help(format!("The relative start date filter, i.e. `30d`, `2w`, `6mo`, or `1y`\nOnly messages sent within this duration of the current time will be included\nConflicts with --{OPTION_START_DATE}\n"))
+                .display_order(9)
+                .value_name("duration"),
+        )
         .arg(
             Arg::new(OPTION_DISABLE_LAZY_LOADING)
                 .short('l')
                 .long(OPTION_DISABLE_LAZY_LOADING)
                 .help("Do not include `loading=\"lazy\"` in HTML export `img` tags\nThis will make pages load slower but PDF generation work\n")
                 .action(ArgAction::SetTrue)
-                .display_order(9),
+                .display_order(10),
         )
         .arg(
             Arg::new(OPTION_CUSTOM_NAME)
                 .short('m')
                 .long(OPTION_CUSTOM_NAME)
                 .help(format!("Specify an optional custom name for the database owner's messages in exports\nConflicts with --{OPTION_USE_CALLER_ID}\n"))
-                .display_order(10)
+                .display_order(11)
         )
         .arg(
             Arg::new(OPTION_USE_CALLER_ID)
@@ -432,7 +1846,7 @@ fn get_command() -> Command {
                 .long(OPTION_USE_CALLER_ID)
                 .help(format!("Use the database owner's caller ID in exports instead of \"Me\"\nConflicts with --{OPTION_CUSTOM_NAME}\n"))
                 .action(ArgAction::SetTrue)
-                .display_order(11)
+                .display_order(12)
         )
         .arg(
             Arg::new(OPTION_BYPASS_FREE_SPACE_CHECK)
@@ -440,7 +1854,7 @@ fn get_command() -> Command {
                 .long(OPTION_BYPASS_FREE_SPACE_CHECK)
                 .help("Bypass the disk space check when exporting data\nBy default, exports will not run if there is not enough free disk space\n")
                 .action(ArgAction::SetTrue)
-                .display_order(12)
+                .display_order(13)
         )
         .arg(
             Arg::new(OPTION_CONVERSATION_FILTER)
@@ -448,7 +1862,475 @@ fn get_command() -> Command {
                 .long(OPTION_CONVERSATION_FILTER)
                 .help("Filter exported conversations by contact numbers or emails\nTo provide multiple filter criteria, use a comma-separated string\nAll conversations with the specified participants are exported, including group conversations\nExample: `-t steve@apple.com,5558675309`\n")
                 .value_name("filter")
-                .display_order(13)
+                .display_order(14)
+        )
+        .arg(
+            Arg::new(OPTION_FAIL_FAST)
+                .long(OPTION_FAIL_FAST)
+                .help(format!("Abort the export with a non-zero exit code on the first error
+Conflicts with --{OPTION_BEST_EFFORT}
+"))
+                .action(ArgAction::SetTrue)
+                .display_order(15)
+        )
+        .arg(
+            Arg::new(OPTION_BEST_EFFORT)
+                .long(OPTION_BEST_EFFORT)
+                .help(format!("Log errors and continue exporting, exiting with a non-zero exit code if any occurred
+This is the default error policy
+Conflicts with --{OPTION_FAIL_FAST}
+"))
+                .action(ArgAction::SetTrue)
+                .display_order(16)
+        )
+        .arg(
+            Arg::new(OPTION_MESSAGES_PER_PAGE)
+                .long(OPTION_MESSAGES_PER_PAGE)
+                .help("Split HTML conversations into pages containing at most this many messages\nPages are saved as `Name-1.html`, `Name-2.html`, etc. and link to each other\nAn `index.html` at the root of the export links to every page of each conversation\nIf omitted, each conversation is exported to a single file\n")
+                .value_name("count")
+                .display_order(17)
+        )
+        .arg(
+            Arg::new(OPTION_NO_ORPHANED)
+                .long(OPTION_NO_ORPHANED)
+                .help("Drop messages that do not belong to any conversation\nBy default, these are exported to an `orphaned` file\n")
+                .action(ArgAction::SetTrue)
+                .display_order(18)
+        )
+        .arg(
+            Arg::new(OPTION_SKIP_EXPORTED)
+                .long(OPTION_SKIP_EXPORTED)
+                .help(format!("Skip messages that a previous export to the same directory already wrote
+Exported message GUIDs are saved to `{EXPORTED_GUIDS}` in the export directory
+`exact` keeps every GUID in memory, using about 100 bytes per message
+`bloom` uses under 3 bytes per message, but wrongly skips fewer than 1 in 10,000 new messages
+Not supported by `pdf`, `json`, or `parquet`, since their files are rewritten on each run
+"))
+                .value_name(SUPPORTED_SKIP_EXPORTED_MODES)
+                .display_order(19)
+        )
+        .arg(
+            Arg::new(OPTION_THUMBNAIL_SIZE)
+                .long(OPTION_THUMBNAIL_SIZE)
+                .help(format!("Embed thumbnails no larger than this many pixels on each side in HTML exports, i.e. 256
+Each thumbnail links to the full size image and is saved next to it
+Requires --{OPTION_ATTACHMENT_MANAGER} to copy attachments
+If omitted, full size images are embedded
+"))
+                .value_name("pixels")
+                .display_order(20)
+        )
+        .arg(
+            Arg::new(OPTION_HANDLE_MAP)
+                .long(OPTION_HANDLE_MAP)
+                .help("Specify an optional CSV or JSON file that maps handle identifiers to display names
+Names in this file take priority over all other names for a handle
+CSV files contain one `identifier,name` pair per line, i.e. `5558675309,Grandma`
+JSON files contain a single object, i.e. `{\"5558675309\": \"Grandma\"}`
+")
+                .value_name("path/to/handle_map")
+                .display_order(21)
+        )
+        .arg(
+            Arg::new(OPTION_COMPRESS)
+                .long(OPTION_COMPRESS)
+                .help(format!("Compress each exported file with gzip, i.e. `orphaned.txt.{GZIP_EXTENSION}`\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(22)
+        )
+        .arg(
+            Arg::new(OPTION_ATTACHMENT_MANIFEST)
+                .long(OPTION_ATTACHMENT_MANIFEST)
+                .help(format!("Write `{ATTACHMENT_MANIFEST}` to the export directory, listing each attachment and whether it was copied, converted, skipped, or failed
+Failed and unconverted attachments include the reason
+"))
+                .action(ArgAction::SetTrue)
+                .display_order(23)
+        )
+        .arg(
+            Arg::new(OPTION_KEEP_ORIGINALS)
+                .long(OPTION_KEEP_ORIGINALS)
+                .help(format!("Keep the original file next to each converted sticker, i.e. `1.png` and `1.heic`\nRequires --{OPTION_ATTACHMENT_MANAGER} to be `basic` or `full`\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(24)
+        )
+        .arg(
+            Arg::new(OPTION_DIRECTORY_PER_CHAT)
+                .long(OPTION_DIRECTORY_PER_CHAT)
+                .help("Write each conversation to its own directory, with its attachments in an `attachments` subdirectory\n")
+                .action(ArgAction::SetTrue)
+                .display_order(25)
+        )
+        .arg(
+            Arg::new(OPTION_VCARD)
+                .long(OPTION_VCARD)
+                .help(format!("Specify an optional vCard file to read contact names from, i.e. one exported from Contacts
+Phone numbers and email addresses on each card are matched to handles
+Names from `--{OPTION_HANDLE_MAP}` take priority over names from this file
+"))
+                .value_name("path/to/contacts.vcf")
+                .display_order(26)
+        )
+        .arg(
+            Arg::new(OPTION_VERIFY)
+                .long(OPTION_VERIFY)
+                .help("After the export, re-read each exported file to ensure it is valid\nOnly `json` exports are verified\n")
+                .action(ArgAction::SetTrue)
+                .display_order(27)
+        )
+        .arg(
+            Arg::new(OPTION_RICH_TEXT)
+                .long(OPTION_RICH_TEXT)
+                .help("Preserve mentions and attachment placeholders from the message body\nMentions are written as `@name`, and `json` exports include them as structured data\n")
+                .action(ArgAction::SetTrue)
+                .display_order(28)
+        )
+        .arg(
+            Arg::new(OPTION_THREADS)
+                .long(OPTION_THREADS)
+                .help("The most attachment converter processes to run at once\nIf omitted, defaults to the number of available CPU cores\nUse `1` to convert attachments one step at a time\n")
+                .value_name("N")
+                .display_order(29)
+        )
+        .arg(
+            Arg::new(OPTION_OUTPUT_ENCODING)
+                .long(OPTION_OUTPUT_ENCODING)
+                .help("The text encoding to write exported files in\nIf omitted, the default is `utf-8`\n`json` and `pdf` exports are always written as `utf-8`\n`utf-16` files start with a byte order mark\n")
+                .value_name(SUPPORTED_ENCODINGS)
+                .display_order(30)
+        )
+        .arg(
+            Arg::new(OPTION_ENCODING_SUBSTITUTE)
+                .long(OPTION_ENCODING_SUBSTITUTE)
+                .help(format!("The character written in place of characters the `--{OPTION_OUTPUT_ENCODING}` cannot represent\nIf omitted, the default is `?`\n"))
+                .value_name("CHAR")
+                .display_order(31)
+        )
+        .arg(
+            Arg::new(OPTION_BOM)
+                .long(OPTION_BOM)
+                .help("Start each `csv` file with a UTF-8 byte order mark\nThis lets Excel detect the encoding, so names and emoji are not garbled\n")
+                .action(ArgAction::SetTrue)
+                .display_order(32)
+        )
+        .arg(
+            Arg::new(OPTION_PREVIEW)
+                .long(OPTION_PREVIEW)
+                .help("Print the first N messages of each conversation as they would be exported, without writing any files\nUse this to check contact names and formatting before a full export\n`pdf` previews are printed as `txt`\n")
+                .value_name("N")
+                .display_order(33)
+        )
+        .arg(
+            Arg::new(OPTION_PREVIEW_LAST)
+                .long(OPTION_PREVIEW_LAST)
+                .help(format!("Print the last messages of each conversation instead of the first\nRequires `--{OPTION_PREVIEW}`\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(34)
+        )
+        .arg(
+            Arg::new(OPTION_REDACT_ATTACHMENTS)
+                .long(OPTION_REDACT_ATTACHMENTS)
+                .help("Leave attachments out of the export entirely, including their names and metadata\nMessages that only contained attachments are written as `<media removed>`\n")
+                .action(ArgAction::SetTrue)
+                .display_order(35)
+        )
+        .arg(
+            Arg::new(OPTION_INTERACTIVE)
+                .long(OPTION_INTERACTIVE)
+                .help(format!("When an attachment cannot be converted, show the converter's error and ask whether to retry, skip and copy the original, or abort the export\nRequires --{OPTION_ATTACHMENT_MANAGER} to be `basic` or `full`\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(36)
+        )
+        .arg(
+            Arg::new(OPTION_SORT_CONVERSATIONS)
+                .long(OPTION_SORT_CONVERSATIONS)
+                .help(format!("Order conversations in `json` exports and `conversations.json` by name, most recent message, or most messages\nRequires --{OPTION_EXPORT_TYPE} to include `json`\nIf omitted, the default is `{}`\n", ConversationOrder::default()))
+                .value_name(SUPPORTED_CONVERSATION_ORDERS)
+                .display_order(37)
+        )
+        .arg(
+            Arg::new(OPTION_SINGLE_FILE)
+                .long(OPTION_SINGLE_FILE)
+                .help(format!("Write every conversation to a single `{ARCHIVE}.html` file, with a menu to switch between them\nImages up to {} are embedded in the file and load as they are scrolled to\nWith --{OPTION_THUMBNAIL_SIZE}, thumbnails are embedded instead of full size images\nRequires --{OPTION_EXPORT_TYPE} to be `html`\n", format_file_size(MAX_EMBEDDED_SIZE)))
+                .action(ArgAction::SetTrue)
+                .display_order(38)
+        )
+        .arg(
+            Arg::new(OPTION_OPTIMIZE_GIFS)
+                .long(OPTION_OPTIMIZE_GIFS)
+                .help(format!("Shrink copied GIFs with `gifsicle` or `ffmpeg`, keeping their animation and transparency\nGIFs are copied unchanged if neither program is installed\nRequires --{OPTION_ATTACHMENT_MANAGER} to be `basic` or `full`\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(39)
+        )
+        .arg(
+            Arg::new(OPTION_DB_READONLY)
+                .long(OPTION_DB_READONLY)
+                .help(format!("Specify how the database is opened; it is never written to\n`ro` reads messages that are still in the write-ahead log, `chat.db-wal`, but fails if another process has locked the database\n`immutable` reads the database even while Messages has it locked, but skips messages that are only in `chat.db-wal`\nTo read the latest messages while Messages is running, use --{OPTION_COPY_DB}\nIf omitted, the default is `ro`\n"))
+                .value_name(SUPPORTED_DB_ACCESS)
+                .display_order(40)
+        )
+        .arg(
+            Arg::new(OPTION_QUOTE_REPLIES)
+                .long(OPTION_QUOTE_REPLIES)
+                .help("Quote the message a reply responded to above the reply, cut to at most this many characters\nIn `json` exports, the quote is saved to `reply_to_text`\nOnly `txt`, `json`, and `markdown` exports quote replies\n")
+                .value_name("length")
+                .display_order(41)
+        )
+        .arg(
+            Arg::new(OPTION_MAX_ATTACHMENT_SIZE)
+                .long(OPTION_MAX_ATTACHMENT_SIZE)
+                .help(format!("Do not copy attachments larger than this size, like `500KB`, `50MB`, or `1.5GB`\nSkipped attachments link to the original file and are listed by --{OPTION_ATTACHMENT_MANIFEST}\nRequires --{OPTION_ATTACHMENT_MANAGER} to copy attachments\n"))
+                .value_name("size")
+                .display_order(42)
+        )
+        .arg(
+            Arg::new(OPTION_DATE_FORMAT)
+                .long(OPTION_DATE_FORMAT)
+                .help("Format readable dates with a `strftime` pattern, like `%d/%m/%Y %H:%M`\nOnly `txt`, `html`, `pdf`, and `csv` exports use the pattern\nIf omitted, the default is `%b %d, %Y %l:%M:%S %p`\n")
+                .value_name("pattern")
+                .display_order(43)
+        )
+        .arg(
+            Arg::new(OPTION_ONLY_ATTACHMENTS_OF_TYPE)
+                .long(OPTION_ONLY_ATTACHMENTS_OF_TYPE)
+                .help(format!("Only copy attachments of these types, separated by commas, like `image,video`\nOther attachments link to the original file and are listed by --{OPTION_ATTACHMENT_MANIFEST}\nRequires --{OPTION_ATTACHMENT_MANAGER} to copy attachments\n"))
+                .value_name(SUPPORTED_ATTACHMENT_KINDS)
+                .display_order(44)
+        )
+        .arg(
+            Arg::new(OPTION_PARSE_CONTACT_CARDS)
+                .long(OPTION_PARSE_CONTACT_CARDS)
+                .help("Read contact cards sent in conversations, saving the contact's name, phone numbers, and email addresses to `shared_contact`\nOnly `json` exports read contact cards\n")
+                .action(ArgAction::SetTrue)
+                .display_order(45)
+        )
+        .arg(
+            Arg::new(OPTION_PARTICIPANTS_MIN)
+                .long(OPTION_PARTICIPANTS_MIN)
+                .help("Only export conversations with at least this many participants\nYou are counted as a participant, so a direct message has 2 participants and a group chat has 3 or more\n")
+                .value_name("count")
+                .display_order(46)
+        )
+        .arg(
+            Arg::new(OPTION_PARTICIPANTS_MAX)
+                .long(OPTION_PARTICIPANTS_MAX)
+                .help("Only export conversations with at most this many participants\nYou are counted as a participant, so `2` exports direct messages and notes to yourself\n")
+                .value_name("count")
+                .display_order(47)
+        )
+        .arg(
+            Arg::new(OPTION_COPY_DB)
+                .long(OPTION_COPY_DB)
+                .help("Copy the database, including messages still in its write-ahead log, `chat.db-wal`, to a temporary directory and read the copy\nThe export includes the latest messages without locking the database Messages is using\n")
+                .action(ArgAction::SetTrue)
+                .display_order(48)
+        )
+        .arg(
+            Arg::new(OPTION_MERGE_SMS_IMESSAGE)
+                .long(OPTION_MERGE_SMS_IMESSAGE)
+                .help("Merge conversations with the same people into a single file, even if they are split between iMessage and SMS
+Messages from the merged conversations are interleaved in the order they were sent
+If omitted, conversations on different services are exported separately
+")
+                .action(ArgAction::SetTrue)
+                .display_order(49)
+        )
+        .arg(
+            Arg::new(OPTION_REACTIONS_AS_RECORDS)
+                .long(OPTION_REACTIONS_AS_RECORDS)
+                .help("Also write every tapback as a record of its own to `reactions.json` in the export directory
+Each record has the `actor`, the `target_guid` of the message reacted to, the `type`, and the `date`
+")
+                .action(ArgAction::SetTrue)
+                .display_order(50)
+        )
+        .arg(
+            Arg::new(OPTION_ATTACHMENT_NAMING)
+                .long(OPTION_ATTACHMENT_NAMING)
+                .help(format!("Specify how copied attachments are named
+`id` names attachments by their row in the database, like `123.jpeg`
+`guid` names attachments by their unique identifier
+`original` names attachments like the file that was sent, numbering duplicate names like `IMG_0001-2.jpeg`
+`sequential` numbers attachments in the order they were sent, starting over in each conversation
+If omitted, the default is `{}`
+Requires --{OPTION_ATTACHMENT_MANAGER} to copy attachments
+`original` and `sequential` require an empty export directory and are not supported with `--{OPTION_SKIP_EXPORTED}`
+", AttachmentNaming::default()))
+                .value_name(SUPPORTED_ATTACHMENT_NAMINGS)
+                .display_order(51)
+        )
+        .arg(
+            Arg::new(OPTION_MIN_TEXT_LENGTH)
+                .long(OPTION_MIN_TEXT_LENGTH)
+                .help(format!("Skip messages whose text has fewer than this many characters, like one-character replies
+Messages that only have attachments are kept unless --{OPTION_DROP_ATTACHMENT_ONLY} is enabled
+The progress bar still counts skipped messages, since their text is only read while exporting
+"))
+                .value_name("length")
+                .display_order(52)
+        )
+        .arg(
+            Arg::new(OPTION_DROP_ATTACHMENT_ONLY)
+                .long(OPTION_DROP_ATTACHMENT_ONLY)
+                .help("Skip messages that have attachments but no text
+The progress bar still counts skipped messages, since their text is only read while exporting
+")
+                .action(ArgAction::SetTrue)
+                .display_order(53)
+        )
+        .arg(
+            Arg::new(OPTION_TIMEZONE)
+                .long(OPTION_TIMEZONE)
+                .help("Write dates at this offset from UTC, like `+05:30` or `-08:00`, instead of in this computer's time zone
+Useful when exporting a database copied from a device that was set to another time zone
+`--start-date` and `--end-date` are read in this time zone too
+If omitted, dates are written in this computer's time zone
+")
+                .value_name("offset")
+                .allow_hyphen_values(true)
+                .display_order(54)
+        )
+        .arg(
+            Arg::new(OPTION_MEDIA_DATES)
+                .long(OPTION_MEDIA_DATES)
+                .help("Read when attached photos and videos were taken from their own metadata, saving the dates to `media_created`
+Photos are read with the image converter and videos with ffprobe, which makes exports slower
+Only `json` exports read the dates
+")
+                .action(ArgAction::SetTrue)
+                .display_order(55)
+        )
+        .arg(
+            Arg::new(OPTION_FLATTEN_GROUP_NAMES)
+                .long(OPTION_FLATTEN_GROUP_NAMES)
+                .help("Name conversations with one other person after that person, even if the conversation has a name
+Group chats keep their names
+If omitted, conversations with a name use it
+")
+                .action(ArgAction::SetTrue)
+                .display_order(56)
+        )
+        .arg(
+            Arg::new(OPTION_CHATS_FROM_FILE)
+                .long(OPTION_CHATS_FROM_FILE)
+                .help(format!("Specify an optional file that lists the conversations to export, one chat GUID or identifier per line
+i.e. `iMessage;+;chat123456789` or `+15558675309`; blank lines and lines starting with `#` are ignored
+Entries that match no conversation are reported and skipped; if none match, the export is aborted
+Narrows any conversations selected by `--{OPTION_CONVERSATION_FILTER}`
+"))
+                .value_name("path/to/chats.txt")
+                .display_order(57)
+        )
+        .arg(
+            Arg::new(OPTION_DIRECTION)
+                .long(OPTION_DIRECTION)
+                .help(format!("Export only the messages you sent, only the messages you received, or both
+If omitted, the default is `{}`
+", MessageDirection::default()))
+                .value_name(SUPPORTED_DIRECTIONS)
+                .display_order(58)
+        )
+        .arg(
+            Arg::new(OPTION_MARKDOWN_FLAVOR)
+                .long(OPTION_MARKDOWN_FLAVOR)
+                .help(format!("Specify the dialect `markdown` exports are written in
+`obsidian` adds YAML frontmatter to each conversation and a note for each person in `people/` that links to their conversations
+If omitted, the default is `{}`
+", MarkdownFlavor::default()))
+                .value_name(SUPPORTED_MARKDOWN_FLAVORS)
+                .display_order(60)
+        )
+        .arg(
+            Arg::new(OPTION_CALLER_ID_LABELS)
+                .long(OPTION_CALLER_ID_LABELS)
+                .help(format!("Show the database owner's messages with a name for the address they were sent from
+Separate addresses from names with `=`, and pairs with commas
+Addresses without a name fall back to `--{OPTION_CUSTOM_NAME}`, `--{OPTION_USE_CALLER_ID}`, or \"Me\"
+Example: `--{OPTION_CALLER_ID_LABELS} \"steve@apple.com=Work,+15558675309=Personal\"`
+"))
+                .value_name("address=name")
+                .display_order(61)
+        )
+        .arg(
+            Arg::new(OPTION_CHECKSUMS)
+                .long(OPTION_CHECKSUMS)
+                .help(format!("Write the SHA-256 digest of every exported file and copied attachment to `{CHECKSUMS}`
+Check the export later with `sha256sum -c {CHECKSUMS}` from the export directory
+"))
+                .action(ArgAction::SetTrue)
+                .display_order(62)
+        )
+        .arg(
+            Arg::new(OPTION_RENAME_GROUP)
+                .long(OPTION_RENAME_GROUP)
+                .help(format!("Name a chat, replacing its own name or the list of its participants
+Separate the chat's GUID from the name with `=`; repeat the option to name several chats
+GUIDs that do not match a chat in the database are reported and ignored
+Example: `--{OPTION_RENAME_GROUP} \"iMessage;+;chat123456789=Book Club\"`
+"))
+                .value_name("guid=name")
+                .action(ArgAction::Append)
+                .display_order(63)
+        )
+        .arg(
+            Arg::new(OPTION_STATS)
+                .long(OPTION_STATS)
+                .help("Count the messages, words, and characters each participant sent in each conversation
+The counts are written to `conversations.json` in `json` exports, where each emoji counts as one character
+Not supported with `--skip-exported`, since the counts would only cover the new messages
+")
+                .action(ArgAction::SetTrue)
+                .display_order(64)
+        )
+        .arg(
+            Arg::new(OPTION_ZIP)
+                .long(OPTION_ZIP)
+                .help(format!("Pack the export into a `.{ZIP_EXTENSION}` archive next to the export path, then remove the export directory
+The export path must not exist yet; the export is written there as a temporary copy first, so it needs twice its size in free space
+"))
+                .action(ArgAction::SetTrue)
+                .display_order(65)
+        )
+        .arg(
+            Arg::new(OPTION_DEDUPE_STRATEGY)
+                .long(OPTION_DEDUPE_STRATEGY)
+                .help(format!("How aggressively duplicate contacts and chats are merged
+`none` keeps every handle and chat separate
+`exact` merges handles with the same phone number or email address, and chats with the same participants
+`contact` also merges the phone numbers and email addresses that belong to the same contact
+If omitted, the default is `{}`
+", DedupeStrategy::default()))
+                .value_name(SUPPORTED_DEDUPE_STRATEGIES)
+                .display_order(66)
+        )
+        .arg(
+            Arg::new(OPTION_VALIDATE_ONLY)
+                .long(OPTION_VALIDATE_ONLY)
+                .help("Read every message in the database to ensure it can be exported, then exit without writing anything
+Messages that cannot be read are listed by GUID
+")
+                .action(ArgAction::SetTrue)
+                .display_order(67)
+        )
+        .arg(
+            Arg::new(OPTION_CONTACT_SHEETS)
+                .long(OPTION_CONTACT_SHEETS)
+                .help("Write a page listing the people in each group conversation, linked from the top of it
+Each person is listed with their handle, how many messages they sent, and the dates of their first and last messages
+Requires `--format` to be `html`
+")
+                .action(ArgAction::SetTrue)
+                .display_order(68)
+        )
+        .arg(
+            Arg::new(OPTION_RECEIPTS)
+                .long(OPTION_RECEIPTS)
+                .help("Also write when each message was sent, delivered, and read to `receipts.json` in the export directory
+Each record has the `sent`, `delivered`, and `read` dates, and the `seconds_to_deliver` and `seconds_to_read` between them
+Not supported with `--skip-exported`, since the file is rewritten on each run
+")
+                .action(ArgAction::SetTrue)
+                .display_order(69)
         )
 }
 
@@ -460,7 +2342,8 @@ impl Options {
             attachment_root: None,
             attachment_manager: AttachmentManager::from(AttachmentManagerMode::Disabled),
             diagnostic: false,
-            export_type: Some(export_type),
+            diagnostic_json: false,
+            export_types: vec![export_type],
             export_path: PathBuf::from("/tmp"),
             query_context: QueryContext::default(),
             no_lazy: false,
@@ -469,6 +2352,51 @@ impl Options {
             platform: Platform::macOS,
             ignore_disk_space: false,
             conversation_filter: None,
+            error_policy: ErrorPolicy::BestEffort,
+            messages_per_page: None,
+            no_orphaned: false,
+            skip_exported: None,
+            thumbnail_size: None,
+            handle_map: None,
+            compress: false,
+            attachment_manifest: false,
+            directory_per_chat: false,
+            vcard: None,
+            verify: false,
+            rich_text: false,
+            output_encoding: OutputEncoding::Utf8,
+            encoding_substitute: '?',
+            bom: false,
+            preview: None,
+            preview_last: false,
+            redact_attachments: false,
+            sort_conversations: ConversationOrder::Recency,
+            single_file: false,
+            db_access: DatabaseAccess::ReadOnly,
+            quote_replies: None,
+            date_format: None,
+            parse_contact_cards: false,
+            participants_min: None,
+            participants_max: None,
+            copy_db: false,
+            merge_sms_imessage: false,
+            reactions_as_records: false,
+            min_text_length: None,
+            drop_attachment_only: false,
+            timezone: None,
+            media_dates: false,
+            flatten_group_names: false,
+            chats_from_file: None,
+            markdown_flavor: MarkdownFlavor::default(),
+            caller_id_labels: HashMap::new(),
+            checksums: false,
+            group_names: HashMap::new(),
+            stats: false,
+            zip: false,
+            dedupe_strategy: DedupeStrategy::Contact,
+            validate_only: false,
+            contact_sheets: false,
+            receipts: false,
         }
     }
 }
@@ -480,17 +2408,42 @@ pub fn from_command_line() -> ArgMatches {
 
 #[cfg(test)]
 mod arg_tests {
-    use std::fs;
+    use std::{collections::HashMap, env::temp_dir, fs};
 
-    use imessage_database::util::{
-        dirs::default_db_path, platform::Platform, query_context::QueryContext,
+    use imessage_database::{
+        tables::table::DatabaseAccess,
+        util::{dirs::default_db_path, platform::Platform, query_context::QueryContext},
     };
 
     use crate::app::{
-        compatibility::attachment_manager::{AttachmentManager, AttachmentManagerMode},
+        compatibility::{
+            attachment_manager::{
+                AttachmentKind, AttachmentManager, AttachmentManagerMode, AttachmentNaming,
+            },
+            models::{Converter, GifOptimizer},
+        },
+        conversation_order::ConversationOrder,
+        dedupe_strategy::DedupeStrategy,
+        encoding::OutputEncoding,
+        error::RuntimeError,
+        error_policy::ErrorPolicy,
         export_type::ExportType,
+        exported::SkipExportedMode,
         options::{get_command, validate_path, Options},
     };
+    use crate::exporters::markdown::MarkdownFlavor;
+
+    /// Build options from sample args, returning every problem they have
+    fn problems(cli_args: Vec<&str>) -> Vec<String> {
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        match Options::from_args(&args) {
+            Err(RuntimeError::InvalidOptions(why)) => why.lines().map(String::from).collect(),
+            Err(why) => panic!("Unexpected error: {why}"),
+            Ok(_) => vec![],
+        }
+    }
 
     #[test]
     fn can_build_option_diagnostic_flag() {
@@ -508,8 +2461,9 @@ mod arg_tests {
             attachment_root: None,
             attachment_manager: AttachmentManager::from(AttachmentManagerMode::Disabled),
             diagnostic: true,
-            export_type: None,
-            export_path: validate_path(None, &None).unwrap(),
+            diagnostic_json: false,
+            export_types: vec![],
+            export_path: validate_path(None, &[]).unwrap(),
             query_context: QueryContext::default(),
             no_lazy: false,
             custom_name: None,
@@ -517,6 +2471,51 @@ mod arg_tests {
             platform: Platform::default(),
             ignore_disk_space: false,
             conversation_filter: None,
+            error_policy: ErrorPolicy::BestEffort,
+            messages_per_page: None,
+            no_orphaned: false,
+            skip_exported: None,
+            thumbnail_size: None,
+            handle_map: None,
+            compress: false,
+            attachment_manifest: false,
+            directory_per_chat: false,
+            vcard: None,
+            verify: false,
+            rich_text: false,
+            output_encoding: OutputEncoding::Utf8,
+            encoding_substitute: '?',
+            bom: false,
+            preview: None,
+            preview_last: false,
+            redact_attachments: false,
+            sort_conversations: ConversationOrder::Recency,
+            single_file: false,
+            db_access: DatabaseAccess::ReadOnly,
+            quote_replies: None,
+            date_format: None,
+            parse_contact_cards: false,
+            participants_min: None,
+            participants_max: None,
+            copy_db: false,
+            merge_sms_imessage: false,
+            reactions_as_records: false,
+            min_text_length: None,
+            drop_attachment_only: false,
+            timezone: None,
+            media_dates: false,
+            flatten_group_names: false,
+            chats_from_file: None,
+            markdown_flavor: MarkdownFlavor::default(),
+            caller_id_labels: HashMap::new(),
+            checksums: false,
+            group_names: HashMap::new(),
+            stats: false,
+            zip: false,
+            dedupe_strategy: DedupeStrategy::Contact,
+            validate_only: false,
+            contact_sheets: false,
+            receipts: false,
         };
 
         assert_eq!(actual, expected);
@@ -564,20 +2563,2812 @@ mod arg_tests {
     #[test]
     fn cant_build_option_diagnostic_flag_with_start_date() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-s", "2020-01-01"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-s", "2020-01-01"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_diagnostic_flag_with_end() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-e", "2020-01-01"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_diagnostic_flag_with_caller_id() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-i"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_export_html() {
+        // Cleanup existing temp data
+        let _ = fs::remove_file("/tmp/orphaned.html");
+
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "-o", "/tmp"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Expected data
+        let tmp_dir = String::from("/tmp");
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Disabled),
+            diagnostic: false,
+            diagnostic_json: false,
+            export_types: vec![ExportType::Html],
+            export_path: validate_path(Some(&tmp_dir), &[]).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            conversation_filter: None,
+            error_policy: ErrorPolicy::BestEffort,
+            messages_per_page: None,
+            no_orphaned: false,
+            skip_exported: None,
+            thumbnail_size: None,
+            handle_map: None,
+            compress: false,
+            attachment_manifest: false,
+            directory_per_chat: false,
+            vcard: None,
+            verify: false,
+            rich_text: false,
+            output_encoding: OutputEncoding::Utf8,
+            encoding_substitute: '?',
+            bom: false,
+            preview: None,
+            preview_last: false,
+            redact_attachments: false,
+            sort_conversations: ConversationOrder::Recency,
+            single_file: false,
+            db_access: DatabaseAccess::ReadOnly,
+            quote_replies: None,
+            date_format: None,
+            parse_contact_cards: false,
+            participants_min: None,
+            participants_max: None,
+            copy_db: false,
+            merge_sms_imessage: false,
+            reactions_as_records: false,
+            min_text_length: None,
+            drop_attachment_only: false,
+            timezone: None,
+            media_dates: false,
+            flatten_group_names: false,
+            chats_from_file: None,
+            markdown_flavor: MarkdownFlavor::default(),
+            caller_id_labels: HashMap::new(),
+            checksums: false,
+            group_names: HashMap::new(),
+            stats: false,
+            zip: false,
+            dedupe_strategy: DedupeStrategy::Contact,
+            validate_only: false,
+            contact_sheets: false,
+            receipts: false,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_option_export_txt_no_lazy() {
+        // Cleanup existing temp data
+        let _ = fs::remove_file("/tmp/orphaned.txt");
+
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-l"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Expected data
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Disabled),
+            diagnostic: false,
+            diagnostic_json: false,
+            export_types: vec![ExportType::Txt],
+            export_path: validate_path(None, &[]).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: true,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            conversation_filter: None,
+            error_policy: ErrorPolicy::BestEffort,
+            messages_per_page: None,
+            no_orphaned: false,
+            skip_exported: None,
+            thumbnail_size: None,
+            handle_map: None,
+            compress: false,
+            attachment_manifest: false,
+            directory_per_chat: false,
+            vcard: None,
+            verify: false,
+            rich_text: false,
+            output_encoding: OutputEncoding::Utf8,
+            encoding_substitute: '?',
+            bom: false,
+            preview: None,
+            preview_last: false,
+            redact_attachments: false,
+            sort_conversations: ConversationOrder::Recency,
+            single_file: false,
+            db_access: DatabaseAccess::ReadOnly,
+            quote_replies: None,
+            date_format: None,
+            parse_contact_cards: false,
+            participants_min: None,
+            participants_max: None,
+            copy_db: false,
+            merge_sms_imessage: false,
+            reactions_as_records: false,
+            min_text_length: None,
+            drop_attachment_only: false,
+            timezone: None,
+            media_dates: false,
+            flatten_group_names: false,
+            chats_from_file: None,
+            markdown_flavor: MarkdownFlavor::default(),
+            caller_id_labels: HashMap::new(),
+            checksums: false,
+            group_names: HashMap::new(),
+            stats: false,
+            zip: false,
+            dedupe_strategy: DedupeStrategy::Contact,
+            validate_only: false,
+            contact_sheets: false,
+            receipts: false,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn cant_build_option_attachment_manager_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-c", "clone"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_export_path_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-o", "~/test"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_start_date_path_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-s", "2020-01-01"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_end_date_path_no_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-e", "2020-01-01"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_date() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "-e", "2020-32-32"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_platform() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-a", "iPad"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "xml"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_custom_name() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-m", "Name"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Expected data
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Disabled),
+            diagnostic: false,
+            diagnostic_json: false,
+            export_types: vec![ExportType::Txt],
+            export_path: validate_path(None, &[]).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: Some("Name".to_string()),
+            use_caller_id: false,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            conversation_filter: None,
+            error_policy: ErrorPolicy::BestEffort,
+            messages_per_page: None,
+            no_orphaned: false,
+            skip_exported: None,
+            thumbnail_size: None,
+            handle_map: None,
+            compress: false,
+            attachment_manifest: false,
+            directory_per_chat: false,
+            vcard: None,
+            verify: false,
+            rich_text: false,
+            output_encoding: OutputEncoding::Utf8,
+            encoding_substitute: '?',
+            bom: false,
+            preview: None,
+            preview_last: false,
+            redact_attachments: false,
+            sort_conversations: ConversationOrder::Recency,
+            single_file: false,
+            db_access: DatabaseAccess::ReadOnly,
+            quote_replies: None,
+            date_format: None,
+            parse_contact_cards: false,
+            participants_min: None,
+            participants_max: None,
+            copy_db: false,
+            merge_sms_imessage: false,
+            reactions_as_records: false,
+            min_text_length: None,
+            drop_attachment_only: false,
+            timezone: None,
+            media_dates: false,
+            flatten_group_names: false,
+            chats_from_file: None,
+            markdown_flavor: MarkdownFlavor::default(),
+            caller_id_labels: HashMap::new(),
+            checksums: false,
+            group_names: HashMap::new(),
+            stats: false,
+            zip: false,
+            dedupe_strategy: DedupeStrategy::Contact,
+            validate_only: false,
+            contact_sheets: false,
+            receipts: false,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_option_caller_id() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-i"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Expected data
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Disabled),
+            diagnostic: false,
+            diagnostic_json: false,
+            export_types: vec![ExportType::Txt],
+            export_path: validate_path(None, &[]).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: true,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            conversation_filter: None,
+            error_policy: ErrorPolicy::BestEffort,
+            messages_per_page: None,
+            no_orphaned: false,
+            skip_exported: None,
+            thumbnail_size: None,
+            handle_map: None,
+            compress: false,
+            attachment_manifest: false,
+            directory_per_chat: false,
+            vcard: None,
+            verify: false,
+            rich_text: false,
+            output_encoding: OutputEncoding::Utf8,
+            encoding_substitute: '?',
+            bom: false,
+            preview: None,
+            preview_last: false,
+            redact_attachments: false,
+            sort_conversations: ConversationOrder::Recency,
+            single_file: false,
+            db_access: DatabaseAccess::ReadOnly,
+            quote_replies: None,
+            date_format: None,
+            parse_contact_cards: false,
+            participants_min: None,
+            participants_max: None,
+            copy_db: false,
+            merge_sms_imessage: false,
+            reactions_as_records: false,
+            min_text_length: None,
+            drop_attachment_only: false,
+            timezone: None,
+            media_dates: false,
+            flatten_group_names: false,
+            chats_from_file: None,
+            markdown_flavor: MarkdownFlavor::default(),
+            caller_id_labels: HashMap::new(),
+            checksums: false,
+            group_names: HashMap::new(),
+            stats: false,
+            zip: false,
+            dedupe_strategy: DedupeStrategy::Contact,
+            validate_only: false,
+            contact_sheets: false,
+            receipts: false,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_option_contact_filter() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-t", "steve@apple.com", "-f", "txt"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Expected data
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Disabled),
+            diagnostic: false,
+            diagnostic_json: false,
+            export_types: vec![ExportType::Txt],
+            export_path: validate_path(None, &[]).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            conversation_filter: Some(String::from("steve@apple.com")),
+            error_policy: ErrorPolicy::BestEffort,
+            messages_per_page: None,
+            no_orphaned: false,
+            skip_exported: None,
+            thumbnail_size: None,
+            handle_map: None,
+            compress: false,
+            attachment_manifest: false,
+            directory_per_chat: false,
+            vcard: None,
+            verify: false,
+            rich_text: false,
+            output_encoding: OutputEncoding::Utf8,
+            encoding_substitute: '?',
+            bom: false,
+            preview: None,
+            preview_last: false,
+            redact_attachments: false,
+            sort_conversations: ConversationOrder::Recency,
+            single_file: false,
+            db_access: DatabaseAccess::ReadOnly,
+            quote_replies: None,
+            date_format: None,
+            parse_contact_cards: false,
+            participants_min: None,
+            participants_max: None,
+            copy_db: false,
+            merge_sms_imessage: false,
+            reactions_as_records: false,
+            min_text_length: None,
+            drop_attachment_only: false,
+            timezone: None,
+            media_dates: false,
+            flatten_group_names: false,
+            chats_from_file: None,
+            markdown_flavor: MarkdownFlavor::default(),
+            caller_id_labels: HashMap::new(),
+            checksums: false,
+            group_names: HashMap::new(),
+            stats: false,
+            zip: false,
+            dedupe_strategy: DedupeStrategy::Contact,
+            validate_only: false,
+            contact_sheets: false,
+            receipts: false,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_option_full() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-c", "full"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Expected data
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Full),
+            diagnostic: false,
+            diagnostic_json: false,
+            export_types: vec![ExportType::Txt],
+            export_path: validate_path(None, &[]).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            conversation_filter: None,
+            error_policy: ErrorPolicy::BestEffort,
+            messages_per_page: None,
+            no_orphaned: false,
+            skip_exported: None,
+            thumbnail_size: None,
+            handle_map: None,
+            compress: false,
+            attachment_manifest: false,
+            directory_per_chat: false,
+            vcard: None,
+            verify: false,
+            rich_text: false,
+            output_encoding: OutputEncoding::Utf8,
+            encoding_substitute: '?',
+            bom: false,
+            preview: None,
+            preview_last: false,
+            redact_attachments: false,
+            sort_conversations: ConversationOrder::Recency,
+            single_file: false,
+            db_access: DatabaseAccess::ReadOnly,
+            quote_replies: None,
+            date_format: None,
+            parse_contact_cards: false,
+            participants_min: None,
+            participants_max: None,
+            copy_db: false,
+            merge_sms_imessage: false,
+            reactions_as_records: false,
+            min_text_length: None,
+            drop_attachment_only: false,
+            timezone: None,
+            media_dates: false,
+            flatten_group_names: false,
+            chats_from_file: None,
+            markdown_flavor: MarkdownFlavor::default(),
+            caller_id_labels: HashMap::new(),
+            checksums: false,
+            group_names: HashMap::new(),
+            stats: false,
+            zip: false,
+            dedupe_strategy: DedupeStrategy::Contact,
+            validate_only: false,
+            contact_sheets: false,
+            receipts: false,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_option_clone() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-c", "clone"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Expected data
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Clone),
+            diagnostic: false,
+            diagnostic_json: false,
+            export_types: vec![ExportType::Txt],
+            export_path: validate_path(None, &[]).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            conversation_filter: None,
+            error_policy: ErrorPolicy::BestEffort,
+            messages_per_page: None,
+            no_orphaned: false,
+            skip_exported: None,
+            thumbnail_size: None,
+            handle_map: None,
+            compress: false,
+            attachment_manifest: false,
+            directory_per_chat: false,
+            vcard: None,
+            verify: false,
+            rich_text: false,
+            output_encoding: OutputEncoding::Utf8,
+            encoding_substitute: '?',
+            bom: false,
+            preview: None,
+            preview_last: false,
+            redact_attachments: false,
+            sort_conversations: ConversationOrder::Recency,
+            single_file: false,
+            db_access: DatabaseAccess::ReadOnly,
+            quote_replies: None,
+            date_format: None,
+            parse_contact_cards: false,
+            participants_min: None,
+            participants_max: None,
+            copy_db: false,
+            merge_sms_imessage: false,
+            reactions_as_records: false,
+            min_text_length: None,
+            drop_attachment_only: false,
+            timezone: None,
+            media_dates: false,
+            flatten_group_names: false,
+            chats_from_file: None,
+            markdown_flavor: MarkdownFlavor::default(),
+            caller_id_labels: HashMap::new(),
+            checksums: false,
+            group_names: HashMap::new(),
+            stats: false,
+            zip: false,
+            dedupe_strategy: DedupeStrategy::Contact,
+            validate_only: false,
+            contact_sheets: false,
+            receipts: false,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn cant_build_option_custom_name_and_caller_id() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-m", "Name", "-i"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_caller_id_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-i"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_custom_name_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-m", "Name"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_contact_filter_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-t", "steve@apple.com"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_fail_fast() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--fail-fast"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Expected data
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Disabled),
+            diagnostic: false,
+            diagnostic_json: false,
+            export_types: vec![ExportType::Txt],
+            export_path: validate_path(None, &[]).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            conversation_filter: None,
+            error_policy: ErrorPolicy::FailFast,
+            messages_per_page: None,
+            no_orphaned: false,
+            skip_exported: None,
+            thumbnail_size: None,
+            handle_map: None,
+            compress: false,
+            attachment_manifest: false,
+            directory_per_chat: false,
+            vcard: None,
+            verify: false,
+            rich_text: false,
+            output_encoding: OutputEncoding::Utf8,
+            encoding_substitute: '?',
+            bom: false,
+            preview: None,
+            preview_last: false,
+            redact_attachments: false,
+            sort_conversations: ConversationOrder::Recency,
+            single_file: false,
+            db_access: DatabaseAccess::ReadOnly,
+            quote_replies: None,
+            date_format: None,
+            parse_contact_cards: false,
+            participants_min: None,
+            participants_max: None,
+            copy_db: false,
+            merge_sms_imessage: false,
+            reactions_as_records: false,
+            min_text_length: None,
+            drop_attachment_only: false,
+            timezone: None,
+            media_dates: false,
+            flatten_group_names: false,
+            chats_from_file: None,
+            markdown_flavor: MarkdownFlavor::default(),
+            caller_id_labels: HashMap::new(),
+            checksums: false,
+            group_names: HashMap::new(),
+            stats: false,
+            zip: false,
+            dedupe_strategy: DedupeStrategy::Contact,
+            validate_only: false,
+            contact_sheets: false,
+            receipts: false,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_option_best_effort() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--best-effort"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.error_policy, ErrorPolicy::BestEffort);
+    }
+
+    #[test]
+    fn can_build_option_messages_per_page() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--messages-per-page",
+            "500",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.messages_per_page, Some(500));
+    }
+
+    #[test]
+    fn cant_build_option_messages_per_page_zero() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--messages-per-page",
+            "0",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_fail_fast_and_best_effort() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--fail-fast",
+            "--best-effort",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_fail_fast_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--fail-fast"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_since() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--since", "30d"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.query_context.start.is_some());
+        assert!(actual.query_context.end.is_none());
+    }
+
+    #[test]
+    fn can_build_option_since_end_date() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--since",
+            "1y",
+            "-e",
+            "2020-01-01",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.query_context.start.is_some());
+        assert!(actual.query_context.end.is_some());
+    }
+
+    #[test]
+    fn cant_build_option_since_start_date() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--since",
+            "30d",
+            "-s",
+            "2020-01-01",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_since_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--since", "30m"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_no_orphaned() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--no-orphaned"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.no_orphaned);
+    }
+
+    #[test]
+    fn cant_build_option_no_orphaned_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--no-orphaned"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_skip_exported() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> =
+            vec!["imessage-exporter", "-f", "txt", "--skip-exported", "bloom"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.skip_exported, Some(SkipExportedMode::Bloom));
+    }
+
+    #[test]
+    fn cant_build_option_skip_exported_pdf() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "pdf",
+            "--skip-exported",
+            "exact",
+        ]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("a format that is appended to, not `pdf`")));
+    }
+
+    #[test]
+    fn cant_build_option_skip_exported_json() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "json",
+            "--skip-exported",
+            "exact",
+        ]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("a format that is appended to, not `json`")));
+    }
+
+    #[test]
+    fn cant_build_option_skip_exported_parquet() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "parquet",
+            "--skip-exported",
+            "exact",
+        ]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("a format that is appended to, not `parquet`")));
+    }
+
+    #[test]
+    fn cant_build_option_skip_exported_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> =
+            vec!["imessage-exporter", "-f", "txt", "--skip-exported", "fuzzy"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_thumbnail_size() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--thumbnail-size",
+            "256",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.thumbnail_size, Some(256));
+    }
+
+    #[test]
+    fn cant_build_option_thumbnail_size_no_copy() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> =
+            vec!["imessage-exporter", "-f", "html", "--thumbnail-size", "256"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_thumbnail_size_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--thumbnail-size",
+            "0",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_handle_map() {
+        let dir = temp_dir().join("imessage-options-handle-map");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("names.csv");
+        fs::write(&path, "5558675309,Grandma\n").unwrap();
+
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--handle-map",
+            path.to_str().unwrap(),
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.handle_map, Some(path));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cant_build_option_handle_map_missing() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--handle-map",
+            "/not/a/real/handle_map.csv",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_compress() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--compress"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.compress);
+    }
+
+    #[test]
+    fn cant_build_option_compress_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--compress"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_attachment_manifest() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "--attachment-manifest"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.attachment_manifest);
+    }
+
+    #[test]
+    fn cant_build_option_attachment_manifest_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--attachment-manifest"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_keep_originals() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "basic",
+            "--keep-originals",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.attachment_manager.keep_originals);
+    }
+
+    #[test]
+    fn cant_build_option_keep_originals_clone() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--keep-originals",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_directory_per_chat() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--directory-per-chat"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.directory_per_chat);
+    }
+
+    #[test]
+    fn cant_build_option_directory_per_chat_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--directory-per-chat"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_vcard_missing() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--vcard",
+            "/does/not/exist.vcf",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_vcard_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--vcard", "contacts.vcf"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_verify() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "json", "--verify"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.verify);
+    }
+
+    #[test]
+    fn cant_build_option_verify_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--verify"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_verify_compress() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "json", "--compress", "--verify"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_rich_text() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "json", "--rich-text"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.rich_text);
+    }
+
+    #[test]
+    fn cant_build_option_rich_text_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--rich-text"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_threads() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "--threads", "1"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.attachment_manager.threads, 1);
+    }
+
+    #[test]
+    fn can_build_option_threads_default() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.attachment_manager.threads >= 1);
+    }
+
+    #[test]
+    fn cant_build_option_threads_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "--threads", "0"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_output_encoding() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--output-encoding",
+            "latin1",
+            "--encoding-substitute",
+            "_",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.output_encoding, OutputEncoding::Latin1);
+        assert_eq!(actual.encoding_substitute, '_');
+    }
+
+    #[test]
+    fn can_build_option_output_encoding_json() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "json",
+            "--output-encoding",
+            "utf-16le",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.output_encoding, OutputEncoding::Utf8);
+    }
+
+    #[test]
+    fn cant_build_option_output_encoding_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--output-encoding",
+            "ebcdic",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_output_encoding_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--output-encoding", "latin1"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_encoding_substitute_unrepresentable() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--output-encoding",
+            "latin1",
+            "--encoding-substitute",
+            "😀",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_encoding_substitute_no_encoding() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--encoding-substitute",
+            "_",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_bom() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "csv", "--bom"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.bom);
+    }
+
+    #[test]
+    fn cant_build_option_bom_other_encoding() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "csv",
+            "--bom",
+            "--output-encoding",
+            "utf-16le",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_bom_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--bom"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_preview() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--preview", "5"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.preview, Some(5));
+        assert!(!actual.preview_last);
+    }
+
+    #[test]
+    fn can_build_option_preview_last() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "json",
+            "--preview",
+            "3",
+            "--preview-last",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.preview, Some(3));
+        assert!(actual.preview_last);
+    }
+
+    #[test]
+    fn cant_build_option_preview_zero() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--preview", "0"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_preview_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--preview", "5"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_preview_last_no_preview() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--preview-last"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_preview_copy_attachments() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--preview",
+            "5",
+            "-c",
+            "clone",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_redact_attachments() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--redact-attachments"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.redact_attachments);
+    }
+
+    #[test]
+    fn cant_build_option_redact_attachments_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--redact-attachments"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_redact_attachments_copy_attachments() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--redact-attachments",
+            "-c",
+            "clone",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_interactive() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "full",
+            "--interactive",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.attachment_manager.interactive);
+    }
+
+    #[test]
+    fn cant_build_option_interactive_clone() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--interactive",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_interactive_no_copy() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--interactive"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_sort_conversations() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "json",
+            "--sort-conversations",
+            "message-count",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.sort_conversations, ConversationOrder::MessageCount);
+    }
+
+    #[test]
+    fn can_build_option_sort_conversations_default() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "json"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.sort_conversations, ConversationOrder::Recency);
+    }
+
+    #[test]
+    fn cant_build_option_sort_conversations_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "json",
+            "--sort-conversations",
+            "size",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_sort_conversations_txt() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--sort-conversations",
+            "name",
+        ]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("to include `json`")));
+    }
+
+    #[test]
+    fn cant_build_option_sort_conversations_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--sort-conversations", "name"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_single_file() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "--single-file"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.single_file);
+    }
+
+    #[test]
+    fn cant_build_option_single_file_txt() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--single-file"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_single_file_paginated() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--single-file",
+            "--messages-per-page",
+            "100",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_report_every_problem() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "xml",
+            "-e",
+            "2020-32-32",
+            "--fail-fast",
+            "--best-effort",
+        ]);
+
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].starts_with("xml is not a valid export type!"));
+        assert_eq!(
+            problems[1],
+            "`--fail-fast` is enabled; `--best-effort` is disallowed"
+        );
+        assert!(problems[2].contains("2020-32-32"));
+    }
+
+    #[test]
+    fn can_report_every_invalid_value() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "sometimes",
+            "--messages-per-page",
+            "0",
+            "--threads",
+            "many",
+        ]);
+
+        assert_eq!(problems.len(), 3);
+        assert!(problems
+            .iter()
+            .any(|why| why.starts_with("0 is not a valid page size!")));
+        assert!(problems
+            .iter()
+            .any(|why| why.starts_with("many is not a valid number of threads!")));
+        assert!(problems
+            .iter()
+            .any(|why| why.starts_with("sometimes is not a valid attachment manager mode!")));
+    }
+
+    #[test]
+    fn can_report_every_option_disallowed_by_diagnostics() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-d",
+            "-f",
+            "txt",
+            "-o",
+            "/tmp/imessage-diagnostics",
+            "-i",
+        ]);
+
+        assert_eq!(problems.len(), 3);
+        assert!(problems
+            .iter()
+            .all(|why| why.starts_with("Diagnostics are enabled;")));
+    }
+
+    #[test]
+    fn can_report_no_problems() {
+        let problems = problems(vec!["imessage-exporter", "-d"]);
+
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn can_build_option_optimize_gifs() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "basic",
+            "--optimize-gifs",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(
+            actual.attachment_manager.gif_optimizer,
+            GifOptimizer::determine()
+        );
+    }
+
+    #[test]
+    fn cant_build_option_optimize_gifs_clone() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--optimize-gifs",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_db_readonly_immutable() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "--db-readonly", "Immutable"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.db_access, DatabaseAccess::Immutable);
+    }
+
+    #[test]
+    fn cant_build_option_db_readonly_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "--db-readonly", "rw"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_quote_replies() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--quote-replies", "80"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.quote_replies, Some(80));
+    }
+
+    #[test]
+    fn cant_build_option_quote_replies_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--quote-replies", "0"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_quote_replies_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--quote-replies", "80"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_max_attachment_size() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-c",
+            "clone",
+            "--max-attachment-size",
+            "50MB",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.attachment_manager.max_size, Some(52428800));
+    }
+
+    #[test]
+    fn cant_build_option_max_attachment_size_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-c",
+            "clone",
+            "--max-attachment-size",
+            "huge",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_max_attachment_size_no_copy() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--max-attachment-size",
+            "50MB",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_date_format() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--date-format",
+            "%d/%m/%Y %H:%M",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.date_format, Some("%d/%m/%Y %H:%M".to_string()));
+    }
+
+    #[test]
+    fn cant_build_option_date_format_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--date-format", "%Q"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_date_format_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--date-format", "%d/%m/%Y"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_only_attachments_of_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-c",
+            "clone",
+            "--only-attachments-of-type",
+            "image, Video",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(
+            actual.attachment_manager.only_kinds,
+            Some(vec![AttachmentKind::Image, AttachmentKind::Video])
+        );
+    }
+
+    #[test]
+    fn cant_build_option_only_attachments_of_type_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-c",
+            "clone",
+            "--only-attachments-of-type",
+            "image,sticker",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_only_attachments_of_type_no_copy() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--only-attachments-of-type",
+            "video",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_parse_contact_cards() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "json", "--parse-contact-cards"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.parse_contact_cards);
+    }
+
+    #[test]
+    fn cant_build_option_parse_contact_cards_diagnostic() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "--parse-contact-cards"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_participants() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--participants-min",
+            "3",
+            "--participants-max",
+            "10",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.participants_min, Some(3));
+        assert_eq!(actual.participants_max, Some(10));
+    }
+
+    #[test]
+    fn cant_build_option_participants_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--participants-max", "0"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_participants_min_over_max() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--participants-min",
+            "3",
+            "--participants-max",
+            "2",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_participants_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--participants-min", "3"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_copy_db() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--copy-db"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.copy_db);
+        assert_eq!(actual.db_access, DatabaseAccess::ReadOnly);
+    }
+
+    #[test]
+    fn can_build_option_copy_db_immutable() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--copy-db",
+            "--db-readonly",
+            "immutable",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // The copy has no write-ahead log, so nothing is skipped by reading it immutably
+        assert!(actual.copy_db);
+        assert_eq!(actual.db_access, DatabaseAccess::Immutable);
+    }
+
+    #[test]
+    fn can_build_option_multiple_export_types() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "json, HTML,json"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(
+            actual.export_types,
+            vec![ExportType::Json, ExportType::Html]
+        );
+    }
+
+    #[test]
+    fn cant_build_option_multiple_export_types_same_extension() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt,whatsapp"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_multiple_export_types_skip_exported() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt,html",
+            "--skip-exported",
+            "exact",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_merge_sms_imessage() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--merge-sms-imessage"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.merge_sms_imessage);
+    }
+
+    #[test]
+    fn can_build_option_reactions_as_records() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "--reactions-as-records"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.reactions_as_records);
+    }
+
+    #[test]
+    fn can_build_option_attachment_naming() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--attachment-naming",
+            "original",
+            "-o",
+            "/tmp/imessage-options-attachment-naming",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.attachment_manager.naming, AttachmentNaming::Original);
+    }
+
+    #[test]
+    fn cant_build_option_attachment_naming_skip_exported() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-c",
+            "clone",
+            "--attachment-naming",
+            "sequential",
+            "--skip-exported",
+            "exact",
+        ]);
+
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("`--attachment-naming sequential` is disallowed")));
+    }
+
+    #[test]
+    fn cant_build_option_attachment_naming_non_empty_export() {
+        let dir = temp_dir().join("imessage-options-attachment-naming-non-empty");
+        fs::create_dir_all(dir.join("attachments")).unwrap();
+
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--attachment-naming",
+            "original",
+            "-o",
+            dir.to_str().unwrap(),
+        ]);
+
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("is not empty")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_build_option_attachment_naming_guid_non_empty_export() {
+        let dir = temp_dir().join("imessage-options-attachment-naming-guid");
+        fs::create_dir_all(dir.join("attachments")).unwrap();
+
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--attachment-naming",
+            "guid",
+            "-o",
+            dir.to_str().unwrap(),
+        ]);
+
+        assert!(problems.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cant_build_option_attachment_naming_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--attachment-naming",
+            "random",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_attachment_naming_no_copy() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--attachment-naming",
+            "sequential",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_min_text_length() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--min-text-length",
+            "3",
+            "--drop-attachment-only",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.min_text_length, Some(3));
+        assert!(actual.drop_attachment_only);
+    }
+
+    #[test]
+    fn cant_build_option_min_text_length_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--min-text-length", "0"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_timezone() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--timezone", "-08:00"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(
+            actual.timezone.map(|offset| offset.local_minus_utc()),
+            Some(-8 * 3600)
+        );
+    }
+
+    #[test]
+    fn can_build_option_timezone_dates() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--start-date",
+            "2020-01-01",
+            "--end-date",
+            "2020-01-02",
+            "--timezone",
+            "UTC",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Midnight UTC on January 1, 2020, as nanoseconds since the iMessage epoch
+        assert_eq!(actual.query_context.start, Some(599_529_600_000_000_000));
+        assert_eq!(actual.query_context.end, Some(599_616_000_000_000_000));
+    }
+
+    #[test]
+    fn cant_build_option_timezone_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--timezone",
+            "Mars/Olympus_Mons",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_media_dates() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "json", "--media-dates"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.media_dates);
+    }
+
+    #[test]
+    fn cant_build_option_media_dates_diagnostic() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "--media-dates"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_flatten_group_names() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--flatten-group-names"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.flatten_group_names);
+    }
+
+    #[test]
+    fn can_build_option_chats_from_file() {
+        let dir = temp_dir().join("imessage-options-chats-from-file");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("chats.txt");
+        fs::write(&path, "iMessage;+;chat123456789\n").unwrap();
+
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--chats-from-file",
+            path.to_str().unwrap(),
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.chats_from_file, Some(path));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cant_build_option_chats_from_file_missing() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--chats-from-file",
+            "/not/a/real/chats.txt",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_direction_sent() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--direction", "sent"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.query_context.from_me, Some(true));
+    }
+
+    #[test]
+    fn can_build_option_direction_received() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--direction", "received"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.query_context.from_me, Some(false));
+    }
+
+    #[test]
+    fn can_build_option_direction_both() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--direction", "both"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.query_context.from_me, None);
+    }
+
+    #[test]
+    fn cant_build_option_direction_invalid() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--direction", "outgoing"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_diagnostic_json() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--diagnostics", "--json"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let actual = Options::from_args(&args).unwrap();
 
-        assert!(actual.is_err());
+        assert!(actual.diagnostic);
+        assert!(actual.diagnostic_json);
     }
 
     #[test]
-    fn cant_build_option_diagnostic_flag_with_end() {
+    fn cant_build_option_diagnostic_json_no_diagnostic() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-e", "2020-01-01"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--json"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -588,89 +5379,92 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_diagnostic_flag_with_caller_id() {
+    fn can_build_option_markdown_flavor() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "-i"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "markdown",
+            "--markdown-flavor",
+            "obsidian",
+        ];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let actual = Options::from_args(&args).unwrap();
 
-        assert!(actual.is_err());
+        assert_eq!(actual.markdown_flavor, MarkdownFlavor::Obsidian);
     }
 
     #[test]
-    fn can_build_option_export_html() {
-        // Cleanup existing temp data
-        let _ = fs::remove_file("/tmp/orphaned.html");
-
+    fn cant_build_option_markdown_flavor_invalid() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "-o", "/tmp"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "markdown",
+            "--markdown-flavor",
+            "notion",
+        ];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args).unwrap();
-
-        // Expected data
-        let tmp_dir = String::from("/tmp");
-        let expected = Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Disabled),
-            diagnostic: false,
-            export_type: Some(ExportType::Html),
-            export_path: validate_path(Some(&tmp_dir), &None).unwrap(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            use_caller_id: false,
-            platform: Platform::default(),
-            ignore_disk_space: false,
-            conversation_filter: None,
-        };
+        let actual = Options::from_args(&args);
 
-        assert_eq!(actual, expected);
+        assert!(actual.is_err());
     }
 
     #[test]
-    fn can_build_option_export_txt_no_lazy() {
-        // Cleanup existing temp data
-        let _ = fs::remove_file("/tmp/orphaned.txt");
-
+    fn can_build_option_caller_id_labels() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-l"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--caller-id-labels",
+            "Steve@Apple.com=Work, +15558675309=Personal",
+        ];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
         let actual = Options::from_args(&args).unwrap();
 
-        // Expected data
-        let expected = Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Disabled),
-            diagnostic: false,
-            export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, &None).unwrap(),
-            query_context: QueryContext::default(),
-            no_lazy: true,
-            custom_name: None,
-            use_caller_id: false,
-            platform: Platform::default(),
-            ignore_disk_space: false,
-            conversation_filter: None,
-        };
+        assert_eq!(
+            actual.caller_id_labels,
+            HashMap::from([
+                ("steve@apple.com".to_string(), "Work".to_string()),
+                ("+15558675309".to_string(), "Personal".to_string()),
+            ])
+        );
+    }
 
-        assert_eq!(actual, expected);
+    #[test]
+    fn cant_build_option_caller_id_labels_invalid() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--caller-id-labels",
+            "steve@apple.com",
+        ]);
+
+        assert_eq!(
+            problems,
+            vec!["steve@apple.com is not a valid caller ID label! Must be shaped like `address=label`"]
+        );
     }
 
     #[test]
-    fn cant_build_option_attachment_manager_no_export_type() {
+    fn cant_build_option_caller_id_labels_no_export() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-c", "clone"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "--caller-id-labels",
+            "steve@apple.com=Work",
+        ];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -681,22 +5475,22 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_export_path_no_export_type() {
+    fn can_build_option_checksums() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-o", "~/test"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--checksums"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let actual = Options::from_args(&args).unwrap();
 
-        assert!(actual.is_err());
+        assert!(actual.checksums);
     }
 
     #[test]
-    fn cant_build_option_start_date_path_no_export_type() {
+    fn cant_build_option_checksums_no_export() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-s", "2020-01-01"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--checksums"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -707,22 +5501,52 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_end_date_path_no_export_type() {
+    fn can_build_option_rename_group() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-e", "2020-01-01"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--rename-group",
+            "iMessage;+;chat123=Book Club",
+            "--rename-group",
+            " chat456 = Family ",
+        ];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let actual = Options::from_args(&args).unwrap();
 
-        assert!(actual.is_err());
+        assert_eq!(
+            actual.group_names,
+            HashMap::from([
+                ("iMessage;+;chat123".to_string(), "Book Club".to_string()),
+                ("chat456".to_string(), "Family".to_string()),
+            ])
+        );
     }
 
     #[test]
-    fn cant_build_option_invalid_date() {
+    fn cant_build_option_rename_group_invalid() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--rename-group",
+            "chat123=",
+        ]);
+
+        assert_eq!(
+            problems,
+            vec!["chat123= is not a valid group name! Must be shaped like `guid=name`"]
+        );
+    }
+
+    #[test]
+    fn cant_build_option_rename_group_no_export() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "-e", "2020-32-32"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--rename-group", "chat123=Book Club"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -733,22 +5557,37 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_invalid_platform() {
+    fn can_build_option_stats() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-a", "iPad"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "json", "--stats"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let actual = Options::from_args(&args).unwrap();
 
-        assert!(actual.is_err());
+        assert!(actual.stats);
     }
 
     #[test]
-    fn cant_build_option_invalid_export_type() {
+    fn cant_build_option_stats_skip_exported() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--skip-exported",
+            "exact",
+            "--stats",
+        ]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("`--stats` is disallowed")));
+    }
+
+    #[test]
+    fn cant_build_option_stats_no_export() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "pdf"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--stats"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -759,198 +5598,225 @@ mod arg_tests {
     }
 
     #[test]
-    fn can_build_option_custom_name() {
+    fn can_build_option_zip() {
+        let tmp_dir = temp_dir()
+            .join("imessage-zip-option")
+            .to_string_lossy()
+            .to_string();
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-m", "Name"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-o", &tmp_dir, "--zip"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
         let actual = Options::from_args(&args).unwrap();
 
-        // Expected data
-        let expected = Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Disabled),
-            diagnostic: false,
-            export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, &None).unwrap(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: Some("Name".to_string()),
-            use_caller_id: false,
-            platform: Platform::default(),
-            ignore_disk_space: false,
-            conversation_filter: None,
-        };
+        assert!(actual.zip);
+    }
 
-        assert_eq!(actual, expected);
+    #[test]
+    fn cant_build_option_zip_existing_dir() {
+        let tmp_dir = temp_dir().to_string_lossy().to_string();
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-o",
+            &tmp_dir,
+            "--zip",
+        ]);
+
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("already exists"));
     }
 
     #[test]
-    fn can_build_option_caller_id() {
+    fn cant_build_option_zip_no_export() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-i"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--zip"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args).unwrap();
-
-        // Expected data
-        let expected = Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Disabled),
-            diagnostic: false,
-            export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, &None).unwrap(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            use_caller_id: true,
-            platform: Platform::default(),
-            ignore_disk_space: false,
-            conversation_filter: None,
-        };
+        let actual = Options::from_args(&args);
 
-        assert_eq!(actual, expected);
+        assert!(actual.is_err());
     }
 
     #[test]
-    fn can_build_option_contact_filter() {
+    fn can_build_option_dedupe_strategy() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-t", "steve@apple.com", "-f", "txt"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-d", "--dedupe-strategy", "exact"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
         let actual = Options::from_args(&args).unwrap();
 
-        // Expected data
-        let expected = Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Disabled),
-            diagnostic: false,
-            export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, &None).unwrap(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            use_caller_id: false,
-            platform: Platform::default(),
-            ignore_disk_space: false,
-            conversation_filter: Some(String::from("steve@apple.com")),
-        };
-
-        assert_eq!(actual, expected);
+        assert_eq!(actual.dedupe_strategy, DedupeStrategy::Exact);
     }
 
     #[test]
-    fn can_build_option_full() {
+    fn can_build_option_dedupe_strategy_default() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-c", "full"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
         let actual = Options::from_args(&args).unwrap();
 
-        // Expected data
-        let expected = Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Full),
-            diagnostic: false,
-            export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, &None).unwrap(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            use_caller_id: false,
-            platform: Platform::default(),
-            ignore_disk_space: false,
-            conversation_filter: None,
-        };
+        assert_eq!(actual.dedupe_strategy, DedupeStrategy::Contact);
+    }
 
-        assert_eq!(actual, expected);
+    #[test]
+    fn cant_build_option_dedupe_strategy_invalid() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--dedupe-strategy",
+            "fuzzy",
+        ]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("fuzzy is not a valid dedupe strategy")));
     }
 
     #[test]
-    fn can_build_option_clone() {
+    fn cant_build_option_dedupe_strategy_none_merge_sms_imessage() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--merge-sms-imessage",
+            "--dedupe-strategy",
+            "none",
+        ]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("--dedupe-strategy")));
+    }
+
+    #[test]
+    fn can_build_option_validate_only() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-c", "clone"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--validate-only"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
         let actual = Options::from_args(&args).unwrap();
 
-        // Expected data
-        let expected = Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::from(AttachmentManagerMode::Clone),
-            diagnostic: false,
-            export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, &None).unwrap(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            use_caller_id: false,
-            platform: Platform::default(),
-            ignore_disk_space: false,
-            conversation_filter: None,
-        };
+        assert!(actual.validate_only);
+    }
 
-        assert_eq!(actual, expected);
+    #[test]
+    fn cant_build_option_validate_only_with_export() {
+        let problems = problems(vec!["imessage-exporter", "-f", "txt", "--validate-only"]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("`--format` is disallowed")));
     }
 
     #[test]
-    fn cant_build_option_custom_name_and_caller_id() {
+    fn cant_build_option_validate_only_with_diagnostics() {
+        let problems = problems(vec!["imessage-exporter", "-d", "--validate-only"]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("`--diagnostics` is disallowed")));
+    }
+
+    #[test]
+    fn can_build_option_contact_sheets() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-m", "Name", "-i"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "--contact-sheets"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let actual = Options::from_args(&args).unwrap();
 
-        assert!(actual.is_err());
+        assert!(actual.contact_sheets);
     }
 
     #[test]
-    fn cant_build_option_caller_id_no_export() {
+    fn cant_build_option_contact_sheets_txt() {
+        let problems = problems(vec!["imessage-exporter", "-f", "txt", "--contact-sheets"]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("Option contact-sheets is enabled")));
+    }
+
+    #[test]
+    fn cant_build_option_contact_sheets_single_file() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--single-file",
+            "--contact-sheets",
+        ]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("`--contact-sheets` is disallowed")));
+    }
+
+    #[test]
+    fn can_build_option_receipts() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-i"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "json", "--receipts"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args);
+        let actual = Options::from_args(&args).unwrap();
 
-        assert!(actual.is_err());
+        assert!(actual.receipts);
     }
 
     #[test]
-    fn cant_build_option_custom_name_no_export() {
-        // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-m", "Name"];
-        let command = get_command();
-        let args = command.get_matches_from(cli_args);
+    fn cant_build_option_receipts_no_export() {
+        let problems = problems(vec!["imessage-exporter", "--receipts"]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("Option receipts is enabled")));
+    }
 
-        // Build the Options
-        let actual = Options::from_args(&args);
+    #[test]
+    fn cant_build_option_receipts_skip_exported() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--skip-exported",
+            "exact",
+            "--receipts",
+        ]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("`--receipts` is disallowed")));
+    }
 
-        assert!(actual.is_err());
+    #[test]
+    fn cant_build_option_receipts_preview() {
+        let problems = problems(vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--preview",
+            "5",
+            "--receipts",
+        ]);
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("`--receipts` is disallowed")));
     }
 
     #[test]
-    fn cant_build_option_contact_filter_no_export() {
+    fn cant_build_option_skip_exported_no_export() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-t", "steve@apple.com"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--skip-exported", "exact"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -963,15 +5829,17 @@ mod arg_tests {
 
 #[cfg(test)]
 mod path_tests {
+    use std::env::temp_dir;
     use std::fs;
     use std::io::Write;
     use std::path::PathBuf;
 
     use crate::app::{
+        compatibility::attachment_manager::{AttachmentManager, AttachmentManagerMode},
         export_type::ExportType,
-        options::{validate_path, DEFAULT_OUTPUT_DIR},
+        options::{validate_path, Options, DEFAULT_OUTPUT_DIR},
     };
-    use imessage_database::util::dirs::home;
+    use imessage_database::util::{dirs::home, platform::Platform};
 
     #[test]
     fn can_validate_empty() {
@@ -980,9 +5848,9 @@ mod path_tests {
 
         let tmp = String::from("/tmp");
         let export_path = Some(&tmp);
-        let export_type = Some(ExportType::Txt);
+        let export_types = [ExportType::Txt];
 
-        let result = validate_path(export_path, &export_type.as_ref());
+        let result = validate_path(export_path, &export_types);
 
         assert_eq!(result.unwrap(), PathBuf::from("/tmp"));
     }
@@ -994,9 +5862,9 @@ mod path_tests {
 
         let tmp = String::from("/tmp");
         let export_path = Some(&tmp);
-        let export_type = Some(ExportType::Txt);
+        let export_types = [ExportType::Txt];
 
-        let result = validate_path(export_path, &export_type.as_ref());
+        let result = validate_path(export_path, &export_types);
 
         let mut tmp = PathBuf::from("/tmp");
         tmp.push("fake1.html");
@@ -1014,9 +5882,9 @@ mod path_tests {
 
         let tmp = String::from("/tmp");
         let export_path = Some(&tmp);
-        let export_type = Some(ExportType::Txt);
+        let export_types = [ExportType::Txt];
 
-        let result = validate_path(export_path, &export_type.as_ref());
+        let result = validate_path(export_path, &export_types);
 
         let mut tmp = PathBuf::from("/tmp");
         tmp.push("fake2.txt");
@@ -1027,16 +5895,75 @@ mod path_tests {
         fs::remove_file(&tmp).unwrap();
     }
 
+    #[test]
+    fn cant_validate_same_type_compressed() {
+        let dir = temp_dir().join("imessage-validate-compressed");
+        fs::create_dir_all(&dir).unwrap();
+        fs::File::create(dir.join("orphaned.txt.gz")).unwrap();
+
+        let export_path = Some(dir.to_string_lossy().to_string());
+        let export_types = [ExportType::Txt];
+
+        let result = validate_path(export_path.as_ref(), &export_types);
+
+        assert!(result.is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn can_validate_none() {
         let export_path = None;
-        let export_type = None;
 
-        let result = validate_path(export_path, &export_type);
+        let result = validate_path(export_path, &[]);
 
         assert_eq!(
             result.unwrap(),
             PathBuf::from(&format!("{}/{DEFAULT_OUTPUT_DIR}", home()))
         );
     }
+
+    #[test]
+    fn can_disable_missing_attachments() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.platform = Platform::macOS;
+        options.attachment_manager = AttachmentManager::from(AttachmentManagerMode::Clone);
+        options.attachment_root = Some("/imessage-exporter/missing/Attachments".to_string());
+
+        options.disable_missing_attachments();
+
+        assert_eq!(
+            options.attachment_manager.mode,
+            AttachmentManagerMode::Disabled
+        );
+    }
+
+    #[test]
+    fn can_keep_existing_attachments() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.platform = Platform::macOS;
+        options.attachment_manager = AttachmentManager::from(AttachmentManagerMode::Clone);
+        options.attachment_root = Some(temp_dir().to_string_lossy().to_string());
+
+        options.disable_missing_attachments();
+
+        assert_eq!(
+            options.attachment_manager.mode,
+            AttachmentManagerMode::Clone
+        );
+    }
+
+    #[test]
+    fn can_keep_ios_attachments() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.platform = Platform::iOS;
+        options.attachment_manager = AttachmentManager::from(AttachmentManagerMode::Clone);
+
+        options.disable_missing_attachments();
+
+        assert_eq!(options.get_attachment_root(), None);
+        assert_eq!(
+            options.attachment_manager.mode,
+            AttachmentManagerMode::Clone
+        );
+    }
 }
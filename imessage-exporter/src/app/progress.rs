@@ -2,22 +2,167 @@
  Defines the export progress bar.
 */
 
+use std::{fmt::Write, time::Duration};
 
-use std::time::Duration;
+use indicatif::{ProgressBar, ProgressState, ProgressStyle};
 
-use indicatif::{ProgressBar, ProgressStyle};
+/// Layout of a progress bar whose total is known, where `{unit}` is what the bar counts
+const TEMPLATE: &str =
+    "{spinner:.green} [{elapsed_precise}] [{bar:.blue}] {human_pos}/{human_len} {unit} ({rate}, ETA: {eta})";
+/// Layout of a progress bar whose total is not known, so there is nothing to estimate the time left from
+const TEMPLATE_UNKNOWN_TOTAL: &str =
+    "{spinner:.green} [{elapsed_precise}] {human_pos} {unit} ({rate})";
 
-pub fn build_progress_bar_export(total_messages: u64) -> ProgressBar {
-    let pb = ProgressBar::new(total_messages);
+/// The progress bar for an export, shared by every format the export writes
+///
+/// Each format reads every message, so the bar counts each message once per format.
+pub struct ExportProgress {
+    bar: ProgressBar,
+    /// The number of messages each format reads
+    messages: u64,
+    /// The number of formats the export writes
+    formats: u64,
+    /// The number of formats that started writing
+    started: u64,
+}
+
+impl ExportProgress {
+    /// Build the progress bar for an export of `total_messages` to `formats` formats
+    ///
+    /// If `hidden`, nothing is drawn, i.e. so interactive prompts are not drawn over.
+    pub fn new(total_messages: u64, formats: u64, hidden: bool) -> Self {
+        ExportProgress {
+            bar: build_progress_bar(total_messages * formats, "messages", hidden),
+            messages: total_messages,
+            formats,
+            started: 0,
+        }
+    }
+
+    /// Start counting the messages of the next format
+    pub fn next_format(&mut self) -> FormatProgress {
+        self.started += 1;
+        FormatProgress {
+            bar: self.bar.clone(),
+            offset: (self.started - 1) * self.messages,
+            messages: self.messages,
+            last: self.started >= self.formats,
+        }
+    }
+}
+
+/// The part of an export's progress bar that counts the messages of one format
+pub struct FormatProgress {
+    bar: ProgressBar,
+    /// The number of messages counted by the formats written before this one
+    offset: u64,
+    /// The number of messages this format reads
+    messages: u64,
+    /// If true, no formats are written after this one
+    last: bool,
+}
+
+impl FormatProgress {
+    /// Set how many of this format's messages were read
+    pub fn set_position(&self, position: u64) {
+        self.bar.set_position(self.offset + position);
+    }
+
+    /// Count more of this format's messages as read
+    pub fn inc(&self, delta: u64) {
+        self.bar.inc(delta);
+    }
+
+    /// Count every message of this format as read, finishing the bar if this is the last format
+    pub fn finish(&self) {
+        if self.last {
+            self.bar.finish();
+        } else {
+            self.bar.set_position(self.offset + self.messages);
+        }
+    }
+}
+
+/// Build a progress bar that counts `unit`, showing the time elapsed, the rate, and the time left
+///
+/// A `total` of 0 means the total is not known, i.e. when it could not be counted, so only a spinner is drawn.
+fn build_progress_bar(total: u64, unit: &'static str, hidden: bool) -> ProgressBar {
+    if hidden {
+        return ProgressBar::hidden();
+    }
+    let (pb, template) = match total {
+        0 => (ProgressBar::new_spinner(), TEMPLATE_UNKNOWN_TOTAL),
+        total => (ProgressBar::new(total), TEMPLATE),
+    };
     pb.set_style(
-        ProgressStyle::default_bar()
-            .template(
-                "{spinner:.green} [{elapsed}] [{bar:.blue}] {human_pos}/{human_len} ({per_sec}, ETA: {eta})",
-            )
+        ProgressStyle::with_template(&template.replace("{unit}", unit))
             .unwrap()
+            .with_key("rate", move |state: &ProgressState, out: &mut dyn Write| {
+                let _ = write!(out, "{}", format_rate(state.per_sec(), unit));
+            })
             .progress_chars("#>-"),
     );
     pb.set_position(0);
     pb.enable_steady_tick(Duration::from_millis(100));
     pb
 }
+
+/// Format how many `unit`s are processed each second, keeping a decimal place for slow rates
+fn format_rate(per_sec: f64, unit: &str) -> String {
+    if per_sec < 10. {
+        format!("{per_sec:.1} {unit}/s")
+    } else {
+        format!("{per_sec:.0} {unit}/s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::progress::{build_progress_bar, format_rate, ExportProgress};
+
+    #[test]
+    fn can_format_slow_rate() {
+        assert_eq!(format_rate(0., "messages"), "0.0 messages/s");
+        assert_eq!(format_rate(2.25, "messages"), "2.2 messages/s");
+    }
+
+    #[test]
+    fn can_format_fast_rate() {
+        assert_eq!(format_rate(1234.56, "messages"), "1235 messages/s");
+    }
+
+    #[test]
+    fn can_build_bar_with_total() {
+        let pb = build_progress_bar(100, "messages", false);
+        assert_eq!(pb.length(), Some(100));
+    }
+
+    #[test]
+    fn can_build_spinner_without_total() {
+        let pb = build_progress_bar(0, "messages", false);
+        assert_eq!(pb.length(), None);
+    }
+
+    #[test]
+    fn can_hide_bar() {
+        assert!(ExportProgress::new(100, 1, true).bar.is_hidden());
+    }
+
+    #[test]
+    fn can_share_bar_between_formats() {
+        let mut progress = ExportProgress::new(10, 2, false);
+        assert_eq!(progress.bar.length(), Some(20));
+
+        let first = progress.next_format();
+        first.set_position(4);
+        first.finish();
+        assert_eq!(progress.bar.position(), 10);
+        assert!(!progress.bar.is_finished());
+
+        let second = progress.next_format();
+        second.set_position(5);
+        assert_eq!(progress.bar.position(), 15);
+        second.finish();
+        assert!(progress.bar.is_finished());
+    }
+}
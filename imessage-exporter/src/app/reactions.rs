@@ -0,0 +1,296 @@
+/*!
+ Contains the `reactions.json` sidecar that lists every tapback as a record of its own.
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use serde_json::{json, Value};
+
+use imessage_database::{message_types::variants::Variant, tables::messages::Message};
+
+use crate::app::{error::RuntimeError, export_file::ExportFile, runtime::Config};
+
+/// Name of the file in the export directory that lists every reaction, before any compression extension
+///
+/// Written when `--reactions-as-records` is set, as a JSON array with one record per tapback.
+pub const REACTIONS: &str = "reactions";
+
+/// Build the record for a tapback, or `None` if the message is not a tapback
+///
+/// Each record has the `actor` who reacted, the `target_guid` and `target_part` of the message part they reacted to,
+/// the tapback `type`, the `date`, and the `conversation_id`, which is `null` for orphaned tapbacks.
+/// Removing a tapback is recorded too, with `removed` set, so counts can subtract reactions that were taken back.
+pub fn format_reaction(config: &Config, message: &Message) -> Option<Value> {
+    let Variant::Tapback(part, added, tapback) = message.variant() else {
+        return None;
+    };
+    let (_, target_guid) = message.clean_associated_guid()?;
+
+    Some(json!({
+        "actor": config.who(message.handle_id, message.is_from_me(), &message.destination_caller_id),
+        "target_guid": target_guid,
+        "target_part": part,
+        "type": tapback.to_string(),
+        "removed": !added,
+        "date": config.format_date(&message.date(&config.offset)),
+        "conversation_id": message.chat_id.map(|chat_id| chat_id.to_string()),
+    }))
+}
+
+/// A tally of the tapbacks in each conversation, by type
+#[derive(Debug, Default)]
+pub struct ReactionCounts {
+    /// Map of chat ID to the number of each type of tapback left in it
+    counts: HashMap<i32, BTreeMap<String, usize>>,
+}
+
+impl ReactionCounts {
+    /// Count a message sent in `chat_id`, if it is a tapback
+    ///
+    /// Removing a tapback takes back one of its type, so the tally only counts the reactions that are left.
+    pub fn add(&mut self, chat_id: i32, message: &Message) {
+        let Variant::Tapback(_, added, tapback) = message.variant() else {
+            return;
+        };
+        let count = self
+            .counts
+            .entry(chat_id)
+            .or_default()
+            .entry(tapback.to_string().to_lowercase())
+            .or_default();
+        if added {
+            *count += 1;
+        } else {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    /// Get the tally of a conversation's tapbacks, leaving out types that were all taken back
+    pub fn get(&self, chat_id: &i32) -> BTreeMap<&str, usize> {
+        self.counts
+            .get(chat_id)
+            .map(|counts| {
+                counts
+                    .iter()
+                    .filter(|(_, count)| **count > 0)
+                    .map(|(kind, count)| (kind.as_str(), *count))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Writes one record per tapback to [`REACTIONS`] as the messages are exported
+///
+/// Tapbacks are recorded as the exporters reach them, so only tapbacks that pass every filter are listed,
+/// in the order they were sent.
+pub struct Reactions {
+    writer: BufWriter<ExportFile>,
+    /// The number of records written so far
+    recorded: usize,
+}
+
+impl Reactions {
+    /// Create the file at `path`, replacing any file a previous export wrote
+    pub fn create(path: &Path, compress: bool) -> Result<Self, RuntimeError> {
+        let file = File::create(path)
+            .and_then(|file| ExportFile::new(file, compress))
+            .map_err(|err| RuntimeError::CreateError(err, path.to_path_buf()))?;
+        let mut writer = BufWriter::new(file);
+        write!(writer, "[").map_err(RuntimeError::DiskError)?;
+
+        Ok(Reactions {
+            writer,
+            recorded: 0,
+        })
+    }
+
+    /// Add a record for a message, if it is a tapback
+    pub fn record(&mut self, config: &Config, message: &Message) -> Result<(), RuntimeError> {
+        let Some(reaction) = format_reaction(config, message) else {
+            return Ok(());
+        };
+
+        let separator = if self.recorded == 0 { "" } else { "," };
+        self.recorded += 1;
+        write!(self.writer, "{separator}\n{reaction}").map_err(RuntimeError::DiskError)
+    }
+
+    /// Close the list of records and write them to disk
+    pub fn finish(&mut self) -> Result<(), RuntimeError> {
+        writeln!(self.writer, "\n]")
+            .and_then(|_| self.writer.flush())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        env::temp_dir,
+        fs::{create_dir_all, read_to_string, remove_dir_all},
+    };
+
+    use serde_json::{json, Value};
+
+    use crate::{
+        app::{
+            export_type::ExportType,
+            reactions::{format_reaction, ReactionCounts, Reactions, REACTIONS},
+        },
+        Config, Options,
+    };
+
+    #[test]
+    fn can_format_reaction() {
+        let options = Options::fake_options(ExportType::Json);
+        let mut config = Config::fake_app(options);
+        config.participants.insert(1, "Sample Contact".to_string());
+
+        let mut message = Config::fake_message();
+        message.handle_id = Some(1);
+        message.chat_id = Some(2);
+        message.associated_message_type = Some(2001);
+        message.associated_message_guid =
+            Some("p:1/A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+
+        let actual = format_reaction(&config, &message).unwrap();
+        assert_eq!(actual["actor"], json!("Sample Contact"));
+        assert_eq!(
+            actual["target_guid"],
+            json!("A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A")
+        );
+        assert_eq!(actual["target_part"], json!(1));
+        assert_eq!(actual["type"], json!("Liked"));
+        assert_eq!(actual["removed"], json!(false));
+        assert_eq!(actual["conversation_id"], json!("2"));
+    }
+
+    #[test]
+    fn can_format_removed_emoji_reaction() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+
+        let mut message = Config::fake_message();
+        message.associated_message_type = Some(3006);
+        message.associated_message_emoji = Some("🔥".to_string());
+        message.associated_message_guid = Some("A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+
+        let actual = format_reaction(&config, &message).unwrap();
+        assert_eq!(actual["type"], json!("🔥"));
+        assert_eq!(actual["removed"], json!(true));
+        // Orphaned tapbacks do not belong to a conversation
+        assert_eq!(actual["conversation_id"], Value::Null);
+    }
+
+    #[test]
+    fn cant_format_reaction_normal_message() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+
+        let message = Config::fake_message();
+        assert!(format_reaction(&config, &message).is_none());
+    }
+
+    #[test]
+    fn can_write_reactions() {
+        let dir = temp_dir().join("imessage-reactions");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join(REACTIONS).with_extension("json");
+
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+
+        let mut loved = Config::fake_message();
+        loved.associated_message_type = Some(2000);
+        loved.associated_message_guid = Some("A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+        let mut liked = Config::fake_message();
+        liked.associated_message_type = Some(2001);
+        liked.associated_message_guid = Some("B44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+
+        let mut reactions = Reactions::create(&path, false).unwrap();
+        reactions.record(&config, &loved).unwrap();
+        reactions.record(&config, &Config::fake_message()).unwrap();
+        reactions.record(&config, &liked).unwrap();
+        reactions.finish().unwrap();
+
+        let actual: Vec<Value> = serde_json::from_str(&read_to_string(&path).unwrap()).unwrap();
+        let targets: Vec<&Value> = actual
+            .iter()
+            .map(|reaction| &reaction["target_guid"])
+            .collect();
+        assert_eq!(
+            targets,
+            [
+                &json!("A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A"),
+                &json!("B44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A")
+            ]
+        );
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_record_reactions_from_first_format() {
+        let dir = temp_dir().join("imessage-reactions-formats");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join(REACTIONS).with_extension("json");
+
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.export_types = vec![ExportType::Txt, ExportType::Html];
+        let config = Config::fake_app(options);
+        config
+            .reactions
+            .replace(Some(Reactions::create(&path, false).unwrap()));
+
+        let mut message = Config::fake_message();
+        message.associated_message_type = Some(2000);
+        message.associated_message_guid = Some("A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+        // Every format exports the same tapback, but it is only recorded once
+        for export_type in [ExportType::Txt, ExportType::Html] {
+            config.export_type.set(Some(export_type));
+            config.record_reaction(&message);
+        }
+        config.reactions.take().unwrap().finish().unwrap();
+
+        let actual: Vec<Value> = serde_json::from_str(&read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(actual.len(), 1);
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_count_reactions() {
+        let mut counts = ReactionCounts::default();
+
+        let mut loved = Config::fake_message();
+        loved.associated_message_type = Some(2000);
+        let mut liked = Config::fake_message();
+        liked.associated_message_type = Some(2001);
+        let mut removed_liked = Config::fake_message();
+        removed_liked.associated_message_type = Some(3001);
+        let mut emoji = Config::fake_message();
+        emoji.associated_message_type = Some(2006);
+        emoji.associated_message_emoji = Some("🔥".to_string());
+
+        counts.add(1, &loved);
+        counts.add(1, &loved);
+        counts.add(1, &liked);
+        counts.add(1, &removed_liked);
+        counts.add(1, &emoji);
+        counts.add(1, &Config::fake_message());
+        counts.add(2, &liked);
+
+        assert_eq!(counts.get(&1), BTreeMap::from([("loved", 2), ("🔥", 1)]));
+        assert_eq!(counts.get(&2), BTreeMap::from([("liked", 1)]));
+        assert!(counts.get(&3).is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+/*!
+ Contains the `receipts.json` sidecar that records when each message was sent, delivered, and read.
+*/
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use serde_json::{json, Value};
+
+use imessage_database::{
+    tables::messages::Message,
+    util::dates::{get_local_time, TIMESTAMP_FACTOR},
+};
+
+use crate::app::{error::RuntimeError, export_file::ExportFile, runtime::Config};
+
+/// Name of the file in the export directory that lists every receipt, before any compression extension
+pub const RECEIPTS: &str = "receipts";
+
+/// Get the number of seconds between two dates from the iMessage table
+///
+/// Returns `None` if either date is missing or the end is before the start, as happens when the devices' clocks disagree.
+fn latency(start: i64, end: i64) -> Option<i64> {
+    if start == 0 || end == 0 {
+        return None;
+    }
+    let seconds = end / TIMESTAMP_FACTOR - start / TIMESTAMP_FACTOR;
+    (seconds >= 0).then_some(seconds)
+}
+
+/// Build the record of when a message was sent, delivered, and read
+///
+/// The database usually only stores when messages sent from this device were delivered, so `seconds_to_deliver`
+/// is typically `null` for messages that were received.
+pub fn format_receipt(config: &Config, message: &Message) -> Value {
+    let format_timestamp =
+        |date: i64| (date != 0).then(|| config.format_date(&get_local_time(&date, &config.offset)));
+
+    json!({
+        "guid": message.guid,
+        "sender": config.who(message.handle_id, message.is_from_me(), &message.destination_caller_id),
+        "is_from_me": message.is_from_me(),
+        "conversation_id": message.chat_id.map(|chat_id| chat_id.to_string()),
+        "sent": config.format_date(&message.date(&config.offset)),
+        "delivered": format_timestamp(message.date_delivered),
+        "read": format_timestamp(message.date_read),
+        "seconds_to_deliver": latency(message.date, message.date_delivered),
+        "seconds_to_read": latency(message.date, message.date_read),
+    })
+}
+
+/// Writes one record per message to [`RECEIPTS`] as the messages are exported
+pub struct Receipts {
+    writer: BufWriter<ExportFile>,
+    /// The number of records written so far
+    recorded: usize,
+}
+
+impl Receipts {
+    /// Create the file at `path`, replacing any file a previous export wrote
+    pub fn create(path: &Path, compress: bool) -> Result<Self, RuntimeError> {
+        let file = File::create(path)
+            .and_then(|file| ExportFile::new(file, compress))
+            .map_err(|err| RuntimeError::CreateError(err, path.to_path_buf()))?;
+        let mut writer = BufWriter::new(file);
+        write!(writer, "[").map_err(RuntimeError::DiskError)?;
+
+        Ok(Receipts {
+            writer,
+            recorded: 0,
+        })
+    }
+
+    /// Add a record for a message, unless it is a tapback
+    ///
+    /// Tapbacks are left out, since they are listed by `--reactions-as-records`.
+    pub fn record(&mut self, config: &Config, message: &Message) -> Result<(), RuntimeError> {
+        if message.is_tapback() {
+            return Ok(());
+        }
+
+        let separator = if self.recorded == 0 { "" } else { "," };
+        self.recorded += 1;
+        write!(
+            self.writer,
+            "{separator}\n{}",
+            format_receipt(config, message)
+        )
+        .map_err(RuntimeError::DiskError)
+    }
+
+    /// Close the list of records and write them to disk
+    pub fn finish(&mut self) -> Result<(), RuntimeError> {
+        writeln!(self.writer, "\n]")
+            .and_then(|_| self.writer.flush())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read_to_string, remove_dir_all},
+    };
+
+    use serde_json::{json, Value};
+
+    use crate::{
+        app::{
+            export_type::ExportType,
+            receipts::{format_receipt, latency, Receipts, RECEIPTS},
+        },
+        Config, Options,
+    };
+
+    #[test]
+    fn can_get_latency() {
+        assert_eq!(latency(674526582885055488, 674526642885055488), Some(60));
+        assert_eq!(latency(674526582885055488, 0), None);
+        assert_eq!(latency(0, 674526582885055488), None);
+        // The recipient's clock was behind
+        assert_eq!(latency(674526642885055488, 674526582885055488), None);
+    }
+
+    #[test]
+    fn can_format_receipt() {
+        let mut options = Options::fake_options(ExportType::Json);
+        options.date_format = Some("%Y".to_string());
+        let config = Config::fake_app(options);
+
+        let mut message = Config::fake_message();
+        message.is_from_me = true;
+        message.chat_id = Some(2);
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        // 5 seconds later
+        message.date_delivered = 674526587885055488;
+        // 2 minutes later
+        message.date_read = 674526702885055488;
+
+        let actual = format_receipt(&config, &message);
+        assert_eq!(actual["is_from_me"], json!(true));
+        assert_eq!(actual["conversation_id"], json!("2"));
+        assert_eq!(actual["sent"], json!("2022"));
+        assert_eq!(actual["delivered"], json!("2022"));
+        assert_eq!(actual["read"], json!("2022"));
+        assert_eq!(actual["seconds_to_deliver"], json!(5));
+        assert_eq!(actual["seconds_to_read"], json!(120));
+    }
+
+    #[test]
+    fn can_format_receipt_unread() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+
+        let mut message = Config::fake_message();
+        message.date = 674526582885055488;
+
+        let actual = format_receipt(&config, &message);
+        // Orphaned messages do not belong to a conversation
+        assert_eq!(actual["conversation_id"], Value::Null);
+        assert_eq!(actual["delivered"], Value::Null);
+        assert_eq!(actual["read"], Value::Null);
+        assert_eq!(actual["seconds_to_deliver"], Value::Null);
+        assert_eq!(actual["seconds_to_read"], Value::Null);
+    }
+
+    #[test]
+    fn can_write_receipts() {
+        let dir = temp_dir().join("imessage-receipts");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join(RECEIPTS).with_extension("json");
+
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+
+        let mut first = Config::fake_message();
+        first.guid = "GUID-1".to_string();
+        let mut second = Config::fake_message();
+        second.guid = "GUID-2".to_string();
+        let mut tapback = Config::fake_message();
+        tapback.guid = "GUID-3".to_string();
+        tapback.associated_message_type = Some(2000);
+
+        let mut receipts = Receipts::create(&path, false).unwrap();
+        receipts.record(&config, &first).unwrap();
+        receipts.record(&config, &second).unwrap();
+        receipts.record(&config, &tapback).unwrap();
+        receipts.finish().unwrap();
+
+        let actual: Vec<Value> = serde_json::from_str(&read_to_string(&path).unwrap()).unwrap();
+        let guids: Vec<&Value> = actual.iter().map(|receipt| &receipt["guid"]).collect();
+        assert_eq!(guids, [&json!("GUID-1"), &json!("GUID-2")]);
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_record_receipts_from_first_format() {
+        let dir = temp_dir().join("imessage-receipts-formats");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join(RECEIPTS).with_extension("json");
+
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.export_types = vec![ExportType::Txt, ExportType::Html];
+        let config = Config::fake_app(options);
+        config
+            .receipts
+            .replace(Some(Receipts::create(&path, false).unwrap()));
+
+        let mut message = Config::fake_message();
+        message.guid = "GUID-1".to_string();
+        // Every format exports the same message, but it is only recorded once
+        for export_type in [ExportType::Txt, ExportType::Html] {
+            config.export_type.set(Some(export_type));
+            config.record_receipt(&message);
+        }
+        config.receipts.take().unwrap().finish().unwrap();
+
+        let actual: Vec<Value> = serde_json::from_str(&read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0]["guid"], json!("GUID-1"));
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_write_empty_receipts() {
+        let dir = temp_dir().join("imessage-receipts-empty");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join(RECEIPTS).with_extension("json");
+
+        let mut receipts = Receipts::create(&path, false).unwrap();
+        receipts.finish().unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "[\n]\n");
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
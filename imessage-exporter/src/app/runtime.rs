@@ -3,38 +3,75 @@
 */
 
 use std::{
+    cell::{Cell, RefCell},
     cmp::min,
-    collections::{BTreeSet, HashMap, HashSet},
-    fs::create_dir_all,
-    path::PathBuf,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    env::temp_dir,
+    fmt::Display,
+    fs::{create_dir_all, remove_dir_all, File},
+    io::BufWriter,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    process,
 };
 
+use chrono::{DateTime, FixedOffset, Local};
 use fdlimit::raise_fd_limit;
 use fs2::available_space;
+use log::{error, info, warn};
 use rusqlite::Connection;
+use serde_json::{json, Value};
 
 use crate::{
     app::{
-        compatibility::attachment_manager::AttachmentManagerMode, error::RuntimeError,
-        export_type::ExportType, options::Options, sanitizers::sanitize_filename,
+        attachment_manifest::{AttachmentManifest, AttachmentRecord},
+        chat_list::{read_chat_list, resolve_chat_list},
+        checksums::Checksums,
+        compatibility::attachment_manager::{AttachmentManagerMode, AttachmentNames},
+        dedupe_strategy::DedupeStrategy,
+        encoding::OutputEncoding,
+        error::RuntimeError,
+        error_policy::ErrorPolicy,
+        export_file::ExportFile,
+        export_type::ExportType,
+        exported::ExportedMessages,
+        gzip::GZIP_EXTENSION,
+        handle_map::{read_handle_map, resolve_handle_map},
+        options::{Options, OPTION_ATTACHMENT_MANAGER, OPTION_CHATS_FROM_FILE},
+        progress::{ExportProgress, FormatProgress},
+        reactions::{Reactions, REACTIONS},
+        receipts::{Receipts, RECEIPTS},
+        sanitizers::sanitize_filename,
+        validate::validate_messages,
+        vcard::{read_vcard, resolve_vcard},
+        verify::verify_export,
+        zip::{archive_path, zip_dir},
     },
-    Exporter, HTML, TXT,
+    exporters::exporter::{generate_text_or_placeholder, Previewer},
+    Exporter, Markdown, Parquet, WhatsApp, CSV, HTML, PDF, TXT,
 };
 
 use imessage_database::{
-    error::table::TableError,
+    error::{message::MessageError, table::TableError},
+    message_types::url::{PreviewImage, URLMessage},
     tables::{
-        attachment::Attachment,
+        attachment::{Attachment, MediaType},
         chat::Chat,
         chat_handle::ChatToHandle,
         handle::Handle,
-        messages::Message,
+        messages::{models::DeliveryStatus, Message},
+        schema::{get_user_version, SchemaVersion},
         table::{
-            get_connection, get_db_size, Cacheable, Deduplicate, Diagnostic, ATTACHMENTS_DIR,
-            MAX_LENGTH, ME, ORPHANED, UNKNOWN,
+            get_connection_with_access, get_db_size, get_snapshot_size, is_encrypted,
+            snapshot_database, Cacheable, Diagnostic, Table, ATTACHMENTS_DIR, MAX_LENGTH, ME,
+            NOTES_TO_SELF, ORPHANED, UNKNOWN,
         },
     },
-    util::{dates::get_offset, size::format_file_size},
+    util::{
+        dates::{format_as, get_local_time, get_offset, DEFAULT_DATE_FORMAT},
+        plist::parse_plist,
+        size::format_file_size,
+    },
 };
 
 use crate::exporters::json::JSONExporter;
@@ -45,13 +82,22 @@ pub struct Config {
     pub chatrooms: HashMap<i32, Chat>,
     // Map of chatroom ID to an internal unique chatroom ID
     pub real_chatrooms: HashMap<i32, i32>,
+    /// Map of internal unique chatroom ID to the chatroom ID that represents it
+    pub canonical_chatrooms: HashMap<i32, i32>,
     /// Map of chatroom ID to chatroom participants
     pub chatroom_participants: HashMap<i32, BTreeSet<i32>>,
     /// Map of participant ID to contact info
     pub participants: HashMap<i32, String>,
+    /// Map of participant ID to a display name from the user's handle map
+    pub contact_names: HashMap<i32, String>,
     /// Map of participant ID to an internal unique participant ID
     pub real_participants: HashMap<i32, i32>,
-    /// Messages that are tapbacks (reactions) to other messages
+    /// Participant IDs that belong to the database owner, who is in their own notes to self
+    pub self_handles: HashSet<i32>,
+    /// Messages that are tapbacks (reactions) to other messages, keyed by the GUID of the message they react to
+    ///
+    /// Built with one query before the export starts, so exporters that stream messages can find a message's
+    /// tapbacks without a second pass. Only tapbacks are held, which are a small share of most databases.
     pub tapbacks: HashMap<String, HashMap<usize, Vec<Message>>>,
     /// App configuration options
     pub options: Options,
@@ -59,17 +105,50 @@ pub struct Config {
     pub offset: i64,
     /// The connection we use to query the database
     pub db: Connection,
+    /// The version of the database schema, which determines what data is available
+    pub schema: SchemaVersion,
+    /// Number of errors encountered during the export
+    pub errors: Cell<usize>,
+    /// Set when the user asks to stop the export from an interactive prompt
+    pub aborted: Cell<bool>,
+    /// Messages already written to the export directory, if we skip them
+    pub exported: RefCell<Option<ExportedMessages>>,
+    /// Records what happened to each attachment, if requested
+    pub attachment_manifest: RefCell<Option<AttachmentManifest>>,
+    /// Records when each message was sent, delivered, and read, if requested
+    pub receipts: RefCell<Option<Receipts>>,
+    /// Records every tapback on its own, if requested
+    pub reactions: RefCell<Option<Reactions>>,
+    /// The digest of each file the export wrote, if requested
+    pub checksums: RefCell<Option<Checksums>>,
+    /// The names given to copied attachments
+    pub attachment_names: RefCell<AttachmentNames>,
+    /// The format being written, since one export can write several
+    pub export_type: Cell<Option<ExportType>>,
+    /// The progress bar shared by every format the export writes
+    pub progress: RefCell<Option<ExportProgress>>,
 }
 
 impl Config {
-    /// Get a deduplicated chat ID or a default value
+    /// Get the chatroom a message belongs to and its deduplicated chat ID
+    ///
+    /// Chats with the same participants, like an iMessage and an SMS chat with the same person, share a
+    /// deduplicated ID. Messages from any of them resolve to the chat with the lowest ID, which is the oldest,
+    /// so merged conversations are written to a single file.
     pub fn conversation(&self, message: &Message) -> Option<(&Chat, &i32)> {
         match message.chat_id.or(message.deleted_from) {
             Some(chat_id) => {
                 if let Some(chatroom) = self.chatrooms.get(&chat_id) {
-                    self.real_chatrooms.get(&chat_id).map(|id| (chatroom, id))
+                    self.real_chatrooms.get(&chat_id).map(|id| {
+                        let canonical = self
+                            .canonical_chatrooms
+                            .get(id)
+                            .and_then(|canonical_id| self.chatrooms.get(canonical_id))
+                            .unwrap_or(chatroom);
+                        (canonical, id)
+                    })
                 } else {
-                    eprintln!("Chat ID {chat_id} does not exist in chat table!");
+                    warn!("Chat ID {chat_id} does not exist in chat table!");
                     None
                 }
             }
@@ -78,6 +157,273 @@ impl Config {
         }
     }
 
+    /// Log an error encountered during the export and add it to the running total
+    ///
+    /// This is used in places that cannot propagate a [`RuntimeError`], like the converters
+    pub fn record_error(&self, why: impl Display) {
+        error!("{why}");
+        self.errors.set(self.errors.get() + 1);
+    }
+
+    /// Determine if a previous export already wrote a message
+    pub fn already_exported(&self, message: &Message) -> bool {
+        self.exported
+            .borrow()
+            .as_ref()
+            .is_some_and(|exported| exported.contains(&message.guid))
+    }
+
+    /// Remember that a message was written, if we are skipping exported messages
+    pub fn mark_exported(&self, guid: &str) -> Result<(), RuntimeError> {
+        match self.exported.borrow_mut().as_mut() {
+            Some(exported) => exported.insert(guid),
+            None => Ok(()),
+        }
+    }
+
+    /// Add an attachment to the attachment manifest, if one is being written
+    pub fn record_attachment(&self, record: &AttachmentRecord) {
+        if let Some(manifest) = self.attachment_manifest.borrow_mut().as_mut() {
+            if let Err(why) = manifest.record(record) {
+                self.record_error(why);
+            }
+        }
+    }
+
+    /// Add a message to the receipts sidecar, if one is being written
+    ///
+    /// Every format exports the same messages, so only the first one records them.
+    pub fn record_receipt(&self, message: &Message) {
+        if self.current_export_type() != self.options.export_types.first().copied() {
+            return;
+        }
+        if let Some(receipts) = self.receipts.borrow_mut().as_mut() {
+            if let Err(why) = receipts.record(self, message) {
+                self.record_error(why);
+            }
+        }
+    }
+
+    /// Add a message to the reactions sidecar, if one is being written and the message is a tapback
+    ///
+    /// Every format exports the same messages, so only the first one records them.
+    pub fn record_reaction(&self, message: &Message) {
+        if self.current_export_type() != self.options.export_types.first().copied() {
+            return;
+        }
+        if let Some(reactions) = self.reactions.borrow_mut().as_mut() {
+            if let Err(why) = reactions.record(self, message) {
+                self.record_error(why);
+            }
+        }
+    }
+
+    /// Hash a file the export wrote, if checksums are being written
+    pub fn record_checksum(&self, path: &Path) {
+        if let Some(checksums) = self.checksums.borrow_mut().as_mut() {
+            if let Err(why) = checksums.add(&self.options.export_path, path) {
+                self.record_error(why);
+            }
+        }
+    }
+
+    /// Decide the fate of an error based on the selected [`ErrorPolicy`]
+    ///
+    /// Under [`ErrorPolicy::FailFast`], the error is returned so the caller can propagate it,
+    /// otherwise it is recorded and the export continues
+    pub fn handle_error(&self, why: RuntimeError) -> Result<(), RuntimeError> {
+        match self.options.error_policy {
+            ErrorPolicy::FailFast => Err(why),
+            ErrorPolicy::BestEffort => {
+                self.record_error(why);
+                Ok(())
+            }
+        }
+    }
+
+    /// Stop the export once the current message is finished
+    pub fn abort(&self) {
+        self.aborted.set(true);
+    }
+
+    /// Abort if the user asked to, or under [`ErrorPolicy::FailFast`], if any errors were recorded since the export started
+    pub fn check_errors(&self) -> Result<(), RuntimeError> {
+        if self.aborted.get() {
+            return Err(RuntimeError::Aborted);
+        }
+        match self.options.error_policy {
+            ErrorPolicy::FailFast if self.errors.get() > 0 => {
+                Err(RuntimeError::ExportErrors(self.errors.get()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the names of the participants in a chat, excluding the database owner
+    ///
+    /// Handles that resolve to the same person are only included once
+    pub fn conversation_participants(&self, chat_id: &i32) -> Vec<&str> {
+        let mut seen = HashSet::new();
+        match self.chatroom_participants.get(chat_id) {
+            Some(participants) => participants
+                .iter()
+                .filter(|id| seen.insert(self.real_participants.get(id).unwrap_or(id)))
+                .map(|id| self.who(Some(*id), false, &None))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Describe the delivery of a message for display next to its timestamp
+    ///
+    /// Messages that failed to send are marked as such; otherwise, show how long it took for the message to be read, if known
+    pub fn delivery_note(&self, message: &Message) -> Option<String> {
+        if message.delivery_status() == DeliveryStatus::Failed {
+            return Some("Not Delivered".to_string());
+        }
+        let time = message
+            .time_until_read(&self.offset)
+            .filter(|time| !time.is_empty())?;
+        let who = if message.is_from_me() {
+            "them"
+        } else {
+            self.options.custom_name.as_deref().unwrap_or("you")
+        };
+        Some(format!("Read by {who} after {time}"))
+    }
+
+    /// Determine if a message does not belong to any conversation, without logging missing chats
+    pub fn is_orphaned(&self, message: &Message) -> bool {
+        match message.chat_id.or(message.deleted_from) {
+            Some(chat_id) => {
+                !(self.chatrooms.contains_key(&chat_id)
+                    && self.real_chatrooms.contains_key(&chat_id))
+            }
+            None => true,
+        }
+    }
+
+    /// Determine if a message is too short to export, once its text is generated
+    ///
+    /// Messages that only have attachments are judged by `--drop-attachment-only` instead of `--min-text-length`;
+    /// announcements have no text of their own, so they are always kept.
+    pub fn is_too_short(&self, message: &Message) -> bool {
+        if message.is_announcement() {
+            return false;
+        }
+        let length = text_length(message);
+        if length == 0 && message.has_attachments() {
+            return self.options.drop_attachment_only;
+        }
+        self.options
+            .min_text_length
+            .is_some_and(|min_length| length < min_length)
+    }
+
+    /// Get the format being written, or the first format requested if the export has not started
+    pub fn current_export_type(&self) -> Option<ExportType> {
+        self.export_type
+            .get()
+            .or_else(|| self.options.export_types.first().copied())
+    }
+
+    /// Get the progress bar for the format being written, which reads `total_messages`
+    ///
+    /// Every format written by one export draws on the same bar, so it shows the combined work.
+    pub fn progress_bar(&self, total_messages: u64) -> FormatProgress {
+        self.progress
+            .borrow_mut()
+            .get_or_insert_with(|| {
+                ExportProgress::new(
+                    total_messages,
+                    self.options.export_types.len().max(1) as u64,
+                    self.options.attachment_manager.interactive,
+                )
+            })
+            .next_format()
+    }
+
+    /// Get the extension of an exported file, adding [`GZIP_EXTENSION`] if the export is compressed
+    pub fn file_extension(&self, extension: &str) -> String {
+        if self.options.compress {
+            return format!("{extension}.{GZIP_EXTENSION}");
+        }
+        extension.to_string()
+    }
+
+    /// Open an exported file for appending, creating it if it does not exist
+    ///
+    /// Text is written in the `--output-encoding`, starting with its byte order mark if the file is new
+    pub fn open_export_file(&self, path: &Path) -> Result<BufWriter<ExportFile>, RuntimeError> {
+        File::options()
+            .append(true)
+            .create(true)
+            .open(path)
+            .and_then(|file| {
+                let empty = file.metadata()?.len() == 0;
+                // `json` and `pdf` files are always written as UTF-8, even alongside formats that are not
+                let encoding = match self.current_export_type() {
+                    Some(ExportType::Json | ExportType::Pdf | ExportType::Parquet) => {
+                        OutputEncoding::Utf8
+                    }
+                    _ => self.options.output_encoding,
+                };
+                ExportFile::new(file, self.options.compress)?.with_encoding(
+                    encoding,
+                    self.options.encoding_substitute,
+                    empty,
+                )
+            })
+            .map(BufWriter::new)
+            .map_err(|err| RuntimeError::CreateError(err, path.to_path_buf()))
+    }
+
+    /// Get the directory a conversation's files are written to, creating it if necessary
+    ///
+    /// If `--directory-per-chat` is enabled, each conversation gets its own directory, named like its file
+    pub fn conversation_dir(&self, filename: &str) -> Result<PathBuf, RuntimeError> {
+        let path = self.chat_dir(filename);
+        if self.options.directory_per_chat {
+            create_dir_all(&path).map_err(|err| RuntimeError::CreateError(err, path.clone()))?;
+        }
+        Ok(path)
+    }
+
+    /// Get the directory the file that contains a message is written to
+    pub fn message_dir(&self, message: &Message) -> PathBuf {
+        if !self.options.directory_per_chat {
+            return self.options.export_path.clone();
+        }
+        // Check for orphans first so missing chats are not logged again
+        match (self.is_orphaned(message), self.conversation(message)) {
+            (false, Some((chatroom, _))) => self.chat_dir(&self.filename(chatroom)),
+            _ => self.chat_dir(ORPHANED),
+        }
+    }
+
+    /// Build the path to a conversation's directory without creating it
+    ///
+    /// The directory is named like the conversation's file, without the file's extension
+    fn chat_dir(&self, filename: &str) -> PathBuf {
+        if self.options.directory_per_chat {
+            return self.options.export_path.join(filename).with_extension("");
+        }
+        self.options.export_path.clone()
+    }
+
+    /// Get the directory to copy a message's attachments to
+    ///
+    /// If `--directory-per-chat` is enabled, this is inside the conversation's directory,
+    /// otherwise it is a subdirectory of the session's [`attachment_path`](Self::attachment_path)
+    pub fn message_attachment_dir(&self, message: &Message) -> PathBuf {
+        if self.options.directory_per_chat {
+            return self.message_dir(message).join(ATTACHMENTS_DIR);
+        }
+        let mut path = self.attachment_path();
+        path.push(self.conversation_attachment_path(message.chat_id));
+        path
+    }
+
     /// Get the attachment path for the current session
     pub fn attachment_path(&self) -> PathBuf {
         let mut path = self.options.export_path.clone();
@@ -102,12 +448,7 @@ impl Config {
     pub fn message_attachment_path(&self, attachment: &Attachment) -> String {
         // Build a relative filepath from the fully qualified one on the `Attachment`
         match &attachment.copied_path {
-            Some(path) => {
-                if let Ok(relative_path) = path.strip_prefix(&self.options.export_path) {
-                    return relative_path.display().to_string();
-                }
-                path.display().to_string()
-            }
+            Some(path) => self.link_path(path),
             None => attachment
                 .resolved_attachment_path(
                     &self.options.platform,
@@ -118,12 +459,140 @@ impl Config {
         }
     }
 
+    /// Copy a conversation's group photo with the other attachments, if the chat has one
+    ///
+    /// Group photos are not sent in a message, so they are handled like an attachment sent to the conversation
+    /// and land in its attachment directory.
+    pub fn conversation_avatar(&self, chatroom: &Chat) -> Option<Attachment> {
+        if self.options.redact_attachments {
+            return None;
+        }
+        let guid = chatroom.group_photo_guid(&self.db)?;
+        let mut attachment = match Attachment::from_guid(&self.db, &guid) {
+            Ok(attachment) => attachment?,
+            Err(why) => {
+                self.record_error(why);
+                return None;
+            }
+        };
+
+        let message = Message {
+            chat_id: Some(chatroom.rowid),
+            ..Default::default()
+        };
+        self.options
+            .attachment_manager
+            .handle_attachment(&message, &mut attachment, self)?;
+        Some(attachment)
+    }
+
+    /// Get the path to the preview image of a link, relative to the file that links to it
+    ///
+    /// Previews stored as one of the message's attachments are handled like any other attachment;
+    /// previews stored inside the message's payload are written next to them.
+    pub fn link_preview(&self, message: &Message) -> Option<String> {
+        if self.options.redact_attachments || !message.is_url() {
+            return None;
+        }
+        let payload = parse_plist(&message.payload_data(&self.db)?).ok()?;
+        match URLMessage::get_preview_image(&payload)? {
+            PreviewImage::Attachment { index, .. } => {
+                let mut attachment = Attachment::from_message(&self.db, message)
+                    .ok()?
+                    .into_iter()
+                    .nth(index)?;
+                self.options.attachment_manager.handle_attachment(
+                    message,
+                    &mut attachment,
+                    self,
+                )?;
+                Some(self.message_attachment_path(&attachment))
+            }
+            PreviewImage::Embedded { data, mime_type } => {
+                let extension = MediaType::Image(mime_type?.strip_prefix("image/")?).extension()?;
+                let path = self
+                    .options
+                    .attachment_manager
+                    .handle_preview_image(message, data, extension, self)?;
+                Some(self.link_path(&path))
+            }
+        }
+    }
+
     /// Get a relative path for the provided file.
     pub fn relative_path(&self, path: PathBuf) -> Option<String> {
-        if let Ok(relative_path) = path.strip_prefix(&self.options.export_path) {
-            return Some(relative_path.display().to_string());
+        Some(self.link_path(&path))
+    }
+
+    /// Build a path to a file in the export that is relative to the file that links to it
+    ///
+    /// If `--directory-per-chat` is enabled, exported files live in their conversation's directory,
+    /// so the conversation directory is removed as well
+    fn link_path(&self, path: &Path) -> String {
+        match path.strip_prefix(&self.options.export_path) {
+            Ok(relative_path) if self.options.directory_per_chat => relative_path
+                .components()
+                .skip(1)
+                .collect::<PathBuf>()
+                .display()
+                .to_string(),
+            Ok(relative_path) => relative_path.display().to_string(),
+            Err(_) => path.display().to_string(),
+        }
+    }
+
+    /// Get the name given to a chat with `--rename-group`, if any
+    fn group_name(&self, chatroom: &Chat) -> Option<&str> {
+        chatroom
+            .guid
+            .as_ref()
+            .and_then(|guid| self.options.group_names.get(guid))
+            .map(String::as_str)
+    }
+
+    /// Get the name of a chat, preferring a name given with `--rename-group` over the one in the database
+    pub fn chat_name<'a>(&'a self, chatroom: &'a Chat) -> Option<&'a str> {
+        self.group_name(chatroom)
+            .or_else(|| chatroom.display_name())
+    }
+
+    /// Get the name a conversation is exported under, if it has one
+    ///
+    /// Direct messages are named after the other participant, if requested, even if the chat has a name.
+    /// A name given with `--rename-group` is always used.
+    fn conversation_name<'a>(&'a self, chatroom: &'a Chat) -> Option<&'a str> {
+        let is_direct_message = self
+            .chatroom_participants
+            .get(&chatroom.rowid)
+            .is_some_and(|participants| participants.len() == 1);
+        if self.options.flatten_group_names && is_direct_message {
+            self.group_name(chatroom)
+        } else {
+            self.chat_name(chatroom)
+        }
+    }
+
+    /// Get the title of a conversation, i.e. for the top of its page
+    ///
+    /// This is the name used by [`filename()`](Self::filename), without the chat ID or extension.
+    pub fn conversation_title(&self, chatroom: &Chat) -> String {
+        match self.conversation_name(chatroom) {
+            Some(name) => name.to_string(),
+            None => self.unnamed_conversation_title(chatroom),
+        }
+    }
+
+    /// Get the title of a conversation without a name: a flat list of its members, or failing that, the unique `chat_identifier` field
+    fn unnamed_conversation_title(&self, chatroom: &Chat) -> String {
+        if let Some(participants) = self.chatroom_participants.get(&chatroom.rowid) {
+            self.filename_from_participants(participants)
+        } else {
+            warn!(
+                "Found error: message chat ID {} has no members!",
+                chatroom.rowid
+            );
+            chatroom.chat_identifier.clone()
         }
-        Some(path.display().to_string())
     }
 
     /// Get a filename for a chat, possibly using cached data.
@@ -132,31 +601,22 @@ impl Config {
     ///
     /// If it does not, first try and make a flat list of its members. Failing that, use the unique `chat_identifier` field.
     pub fn filename(&self, chatroom: &Chat) -> String {
-        let mut filename = match &chatroom.display_name() {
+        let mut filename = match self.conversation_name(chatroom) {
             // If there is a display name, use that
             Some(name) => {
-                format!(
-                    "{} - {}",
-                    &name[..min(MAX_LENGTH, name.len())],
-                    chatroom.rowid
-                )
+                // Names given with `--rename-group` can be any text, so only cut them between characters
+                let end = (0..=min(MAX_LENGTH, name.len()))
+                    .rev()
+                    .find(|idx| name.is_char_boundary(*idx))
+                    .unwrap_or_default();
+                format!("{} - {}", &name[..end], chatroom.rowid)
             }
             // Fallback if there is no name set
-            None => {
-                if let Some(participants) = self.chatroom_participants.get(&chatroom.rowid) {
-                    self.filename_from_participants(participants)
-                } else {
-                    eprintln!(
-                        "Found error: message chat ID {} has no members!",
-                        chatroom.rowid
-                    );
-                    chatroom.chat_identifier.clone()
-                }
-            }
+            None => self.unnamed_conversation_title(chatroom),
         };
 
         // Add the extension to the filename
-        if let Some(export_type) = &self.options.export_type {
+        if let Some(export_type) = self.current_export_type() {
             filename.push_str(export_type.extension());
         }
 
@@ -170,6 +630,10 @@ impl Config {
     /// - Truncated Names
     ///   - Contact 1, Contact 2, ... Contact 13 and 4 others
     fn filename_from_participants(&self, participants: &BTreeSet<i32>) -> String {
+        if self.is_notes_to_self(participants) {
+            return NOTES_TO_SELF.to_string();
+        }
+
         let mut added = 0;
         let mut out_s = String::with_capacity(MAX_LENGTH);
         for participant_id in participants {
@@ -210,30 +674,106 @@ impl Config {
     /// let options = Options::from_args(&args);
     /// let app = Config::new(options).unwrap();
     /// ```
-    pub fn new(options: Options) -> Result<Config, RuntimeError> {
-        let conn = get_connection(&options.get_db_path()).map_err(RuntimeError::DatabaseError)?;
-        eprintln!("Building cache...");
-        eprintln!("  [1/4] Caching chats...");
+    pub fn new(mut options: Options) -> Result<Config, RuntimeError> {
+        // Copying attachments that are not there would fail once for each of them, so only list them
+        options.disable_missing_attachments();
+
+        // SQLite only reports that an encrypted file "is not a database", so explain what is wrong before opening it
+        let source_path = options.get_db_path();
+        if is_encrypted(&source_path) {
+            return Err(RuntimeError::EncryptedDatabase(source_path));
+        }
+        if !options.copy_db {
+            return Config::open(options, &source_path, &source_path);
+        }
+
+        // The copy is removed when the Config is dropped, so it must be removed here if the Config is never built
+        let db_path = copy_database(&source_path)?;
+        Config::open(options, &source_path, &db_path).inspect_err(|_| remove_snapshot())
+    }
+
+    /// Build the caches from the database at `db_path`, which is a copy of `source_path` if `--copy-db` is enabled
+    fn open(options: Options, source_path: &Path, db_path: &Path) -> Result<Config, RuntimeError> {
+        let conn = get_connection_with_access(db_path, options.db_access)
+            .map_err(RuntimeError::DatabaseError)?;
+        let schema = SchemaVersion::detect(&conn)
+            .map_err(|why| classify_database_error(why, source_path))?;
+        info!("Detected database schema: {schema}");
+        if !schema.has_replies() {
+            warn!("This database does not store replies, so they will not be exported!");
+        }
+        info!("Building cache...");
+        info!("  [1/4] Caching chats...");
         let chatrooms = Chat::cache(&conn).map_err(RuntimeError::DatabaseError)?;
-        eprintln!("  [2/4] Caching chatrooms...");
+        info!("  [2/4] Caching chatrooms...");
         let chatroom_participants =
             ChatToHandle::cache(&conn).map_err(RuntimeError::DatabaseError)?;
-        eprintln!("  [3/4] Caching participants...");
-        let participants = Handle::cache(&conn).map_err(RuntimeError::DatabaseError)?;
-        eprintln!("  [4/4] Caching tapbacks...");
+        info!("  [3/4] Caching participants...");
+        // Phone numbers and email addresses of the same contact only share a name when they are merged
+        let participants = match options.dedupe_strategy {
+            DedupeStrategy::Contact => Handle::cache(&conn),
+            DedupeStrategy::None | DedupeStrategy::Exact => Handle::cache_ids(&conn),
+        }
+        .map_err(RuntimeError::DatabaseError)?;
+        let self_handles = Handle::get_self_handles(&conn).map_err(RuntimeError::DatabaseError)?;
+        let mut contact_names = match &options.vcard {
+            Some(path) => resolve_vcard(&read_vcard(path)?, &participants),
+            None => HashMap::new(),
+        };
+        // Names in the handle map take priority over names from contact cards
+        if let Some(path) = &options.handle_map {
+            contact_names.extend(resolve_handle_map(&read_handle_map(path)?, &participants));
+        }
+        info!("  [4/4] Caching tapbacks...");
         let tapbacks = Message::cache(&conn).map_err(RuntimeError::DatabaseError)?;
-        eprintln!("Cache built!");
+        info!("Cache built!");
+
+        // Names given to chats that are not in this database are ignored
+        let known_guids: HashSet<&str> = chatrooms
+            .values()
+            .filter_map(|chatroom| chatroom.guid.as_deref())
+            .collect();
+        for guid in options.group_names.keys() {
+            if !known_guids.contains(guid.as_str()) {
+                warn!("No chat has the GUID {guid}, so it will not be renamed");
+            }
+        }
+
+        let strategy = options.dedupe_strategy;
+        let real_participants = strategy.dedupe::<Handle>(&participants);
+        let real_chatrooms = if options.merge_sms_imessage {
+            strategy.dedupe::<ChatToHandle>(&resolve_chatroom_participants(
+                &chatroom_participants,
+                &real_participants,
+            ))
+        } else {
+            strategy.dedupe::<ChatToHandle>(&chatroom_participants)
+        };
 
         Ok(Config {
             chatrooms,
-            real_chatrooms: ChatToHandle::dedupe(&chatroom_participants),
+            canonical_chatrooms: canonical_chatrooms(&real_chatrooms),
+            real_chatrooms,
             chatroom_participants,
-            real_participants: Handle::dedupe(&participants),
+            real_participants,
             participants,
+            contact_names,
+            self_handles,
             tapbacks,
             options,
             offset: get_offset(),
             db: conn,
+            schema,
+            errors: Cell::new(0),
+            aborted: Cell::new(false),
+            exported: RefCell::new(None),
+            attachment_manifest: RefCell::new(None),
+            receipts: RefCell::new(None),
+            reactions: RefCell::new(None),
+            checksums: RefCell::new(None),
+            attachment_names: RefCell::new(AttachmentNames::default()),
+            export_type: Cell::new(None),
+            progress: RefCell::new(None),
         })
     }
 
@@ -298,7 +838,7 @@ impl Config {
                 }
             }
 
-            eprintln!(
+            info!(
                 "Filtering for {} handle{} across {} chatrooms...",
                 unique_handle_ids.len(),
                 if unique_handle_ids.len() != 1 {
@@ -311,6 +851,124 @@ impl Config {
         }
     }
 
+    /// Limit the export to the chatrooms listed in the chat list file, if provided
+    ///
+    /// Entries that match no chatroom are reported and skipped, but if none match, the export is aborted.
+    /// Chatrooms that are duplicates of a listed chatroom are included, and any chatrooms selected
+    /// by the conversation filter are narrowed to the listed ones.
+    pub(crate) fn resolve_chats_from_file(&mut self) -> Result<(), RuntimeError> {
+        let Some(path) = &self.options.chats_from_file else {
+            return Ok(());
+        };
+
+        let chat_list = read_chat_list(path)?;
+        let mut included_chatrooms =
+            resolve_chat_list(&chat_list, &self.chatrooms, &self.real_chatrooms);
+        if included_chatrooms.is_empty() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "No entries in {} {path:?} match a conversation!",
+                OPTION_CHATS_FROM_FILE
+            )));
+        }
+
+        if let Some(selected_chat_ids) = &self.options.query_context.selected_chat_ids {
+            included_chatrooms.retain(|chat_id| selected_chat_ids.contains(chat_id));
+        }
+
+        if included_chatrooms.is_empty() {
+            warn!("No listed conversations matched the other filters, nothing will be exported!");
+        } else {
+            info!(
+                "Filtering for {} listed chatroom{}...",
+                included_chatrooms.len(),
+                if included_chatrooms.len() != 1 {
+                    "s"
+                } else {
+                    ""
+                }
+            );
+        }
+
+        // An empty selection would disable the filter, so set it directly to match nothing instead
+        self.options.query_context.selected_chat_ids = Some(included_chatrooms);
+        Ok(())
+    }
+
+    /// Limit the export to chatrooms whose number of participants is in the requested range, if requested
+    ///
+    /// The database owner counts as a participant, so direct messages have 2 participants,
+    /// group chats have 3 or more, and notes to self have 1. Handles that belong to the same
+    /// contact are counted once. Any chatrooms selected by the other filters are narrowed to the ones in range.
+    pub(crate) fn resolve_participant_count(&mut self) {
+        let Some(range) = self.participant_range() else {
+            return;
+        };
+
+        let mut included_chatrooms: BTreeSet<i32> = self
+            .chatrooms
+            .keys()
+            .filter(|chat_id| range.contains(&self.participant_count(chat_id)))
+            .copied()
+            .collect();
+
+        if let Some(selected_chat_ids) = &self.options.query_context.selected_chat_ids {
+            included_chatrooms.retain(|chat_id| selected_chat_ids.contains(chat_id));
+        }
+
+        if included_chatrooms.is_empty() {
+            warn!(
+                "No conversations have {} participants, nothing will be exported!",
+                self.describe_participant_range()
+            );
+        } else {
+            info!(
+                "Filtering for {} chatroom{} with {} participants...",
+                included_chatrooms.len(),
+                if included_chatrooms.len() != 1 {
+                    "s"
+                } else {
+                    ""
+                },
+                self.describe_participant_range()
+            );
+        }
+
+        // An empty selection would disable the filter, so set it directly to match nothing instead
+        self.options.query_context.selected_chat_ids = Some(included_chatrooms);
+    }
+
+    /// The range of participant counts to export, or `None` if conversations are not filtered by size
+    fn participant_range(&self) -> Option<RangeInclusive<usize>> {
+        match (self.options.participants_min, self.options.participants_max) {
+            (None, None) => None,
+            (min, max) => Some(min.unwrap_or(0)..=max.unwrap_or(usize::MAX)),
+        }
+    }
+
+    /// Describe the range of participant counts to export, i.e. `at least 3`
+    fn describe_participant_range(&self) -> String {
+        match (self.options.participants_min, self.options.participants_max) {
+            (Some(min), Some(max)) if min == max => format!("exactly {min}"),
+            (Some(min), Some(max)) => format!("{min} to {max}"),
+            (Some(min), None) => format!("at least {min}"),
+            (None, Some(max)) => format!("at most {max}"),
+            (None, None) => "any number of".to_string(),
+        }
+    }
+
+    /// The number of people in a chatroom, counting the database owner and each contact once
+    fn participant_count(&self, chat_id: &i32) -> usize {
+        let others: HashSet<&i32> = self
+            .chatroom_participants
+            .get(chat_id)
+            .into_iter()
+            .flatten()
+            .filter(|id| !self.self_handles.contains(id))
+            .map(|id| self.real_participants.get(id).unwrap_or(id))
+            .collect();
+        others.len() + 1
+    }
+
     /// Ensure there is available disk space for the requested export
     fn ensure_free_space(&self) -> Result<(), RuntimeError> {
         // Export size is usually about 6% the size of the db; we divide by 10 to over-estimate about 10% of the total size
@@ -322,11 +980,14 @@ impl Config {
         let free_space_at_location =
             available_space(&self.options.export_path).map_err(RuntimeError::DiskError)?;
 
+        // `--zip` packs the export after it is written, so the export and its archive are on disk at the same time
+        let copies = if self.options.zip { 2 } else { 1 };
+
         // Validate that there is enough disk space free to write the export
         if let AttachmentManagerMode::Disabled = self.options.attachment_manager.mode {
-            if estimated_export_size >= free_space_at_location {
+            if estimated_export_size * copies >= free_space_at_location {
                 return Err(RuntimeError::NotEnoughAvailableSpace(
-                    estimated_export_size,
+                    estimated_export_size * copies,
                     free_space_at_location,
                 ));
             }
@@ -335,15 +996,15 @@ impl Config {
                 Attachment::get_total_attachment_bytes(&self.db, &self.options.query_context)
                     .map_err(RuntimeError::DatabaseError)?;
             estimated_export_size += total_attachment_size;
-            if (estimated_export_size + total_attachment_size) >= free_space_at_location {
+            if (estimated_export_size + total_attachment_size) * copies >= free_space_at_location {
                 return Err(RuntimeError::NotEnoughAvailableSpace(
-                    estimated_export_size + total_attachment_size,
+                    (estimated_export_size + total_attachment_size) * copies,
                     free_space_at_location,
                 ));
             }
         };
 
-        println!(
+        info!(
             "Estimated export size: {}",
             format_file_size(estimated_export_size)
         );
@@ -353,30 +1014,41 @@ impl Config {
 
     /// Handles diagnostic tests for database
     fn run_diagnostic(&self) -> Result<(), TableError> {
+        if self.options.diagnostic_json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&self.diagnostic_json()?).unwrap()
+            );
+            return Ok(());
+        }
+
         println!("\niMessage Database Diagnostics\n");
         Handle::run_diagnostic(&self.db)?;
         Message::run_diagnostic(&self.db)?;
-        Attachment::run_diagnostic(&self.db, &self.options.db_path, &self.options.platform)?;
+        Attachment::run_diagnostic(
+            &self.db,
+            &self.options.db_path,
+            &self.options.platform,
+            self.options.attachment_root.as_deref(),
+        )?;
         ChatToHandle::run_diagnostic(&self.db)?;
 
         // Global Diagnostics
         println!("Global diagnostic data:");
 
+        println!("    Database schema: {}", self.schema);
+        println!("    Database user version: {}", get_user_version(&self.db)?);
+
         let total_db_size = get_db_size(&self.options.db_path)?;
         println!(
             "    Total database size: {}",
             format_file_size(total_db_size)
         );
 
-        let unique_handles: HashSet<i32> =
-            HashSet::from_iter(self.real_participants.values().cloned());
-        let duplicated_handles = self.participants.len() - unique_handles.len();
+        let (duplicated_handles, duplicated_chats) = self.duplicate_counts();
         if duplicated_handles > 0 {
             println!("    Duplicated contacts: {duplicated_handles}");
         }
-
-        let unique_chats: HashSet<i32> = HashSet::from_iter(self.real_chatrooms.values().cloned());
-        let duplicated_chats = self.chatrooms.len() - unique_chats.len();
         if duplicated_chats > 0 {
             println!("    Duplicated chats: {duplicated_chats}");
         }
@@ -387,6 +1059,69 @@ impl Config {
         Ok(())
     }
 
+    /// Count the contacts and the chats that are duplicates of another one
+    fn duplicate_counts(&self) -> (usize, usize) {
+        let unique_handles: HashSet<i32> =
+            HashSet::from_iter(self.real_participants.values().cloned());
+        let unique_chats: HashSet<i32> = HashSet::from_iter(self.real_chatrooms.values().cloned());
+        (
+            self.participants.len().saturating_sub(unique_handles.len()),
+            self.chatrooms.len().saturating_sub(unique_chats.len()),
+        )
+    }
+
+    /// Collect the same data as [`Config::run_diagnostic`] into a JSON object, i.e. for scripted health checks
+    ///
+    /// Every count is included, even if it is 0; sizes are in bytes.
+    fn diagnostic_json(&self) -> Result<Value, TableError> {
+        let handles = Handle::diagnostic(&self.db)?;
+        let messages = Message::diagnostic(&self.db)?;
+        let attachments = Attachment::diagnostic(
+            &self.db,
+            &self.options.db_path,
+            &self.options.platform,
+            self.options.attachment_root.as_deref(),
+        )?;
+        let threads = ChatToHandle::diagnostic(&self.db)?;
+        let (duplicated_contacts, duplicated_chats) = self.duplicate_counts();
+        let manager = &self.options.attachment_manager;
+
+        Ok(json!({
+            "handles": {
+                "contacts_with_multiple_ids": handles.contacts_with_multiple_ids,
+            },
+            "messages": {
+                "total": messages.total_messages,
+                "without_chat": messages.messages_without_chat,
+                "in_multiple_chats": messages.messages_in_multiple_chats,
+            },
+            "attachments": {
+                "total": attachments.total_attachments,
+                "bytes_in_table": attachments.total_bytes,
+                "bytes_on_disk": attachments.bytes_on_disk,
+                "missing_files": attachments.missing_files,
+                "no_path_provided": attachments.no_path_provided,
+                "no_file_located": attachments.no_file_located(),
+                "expired_audio": attachments.expired_audio,
+            },
+            "threads": {
+                "chats_with_no_handles": threads.chats_with_no_handles,
+            },
+            "database": {
+                "schema": self.schema.to_string(),
+                "user_version": get_user_version(&self.db)?,
+                "size_bytes": get_db_size(&self.options.db_path)?,
+                "duplicated_contacts": duplicated_contacts,
+                "duplicated_chats": duplicated_chats,
+            },
+            "converters": {
+                "image": manager.image_converter.as_ref().map(ToString::to_string),
+                "audio": manager.audio_converter.as_ref().map(ToString::to_string),
+                "video": manager.video_converter.as_ref().map(ToString::to_string),
+            },
+        }))
+    }
+
     /// Start the app given the provided set of options. This will either run
     /// diagnostic tests on the database or export data to the specified file type.
     ///
@@ -404,9 +1139,11 @@ impl Config {
     /// app.start();
     /// ```
     pub fn start(&self) -> Result<(), RuntimeError> {
-        if self.options.diagnostic {
+        if self.options.validate_only {
+            self.run_validation()?;
+        } else if self.options.diagnostic {
             self.run_diagnostic().map_err(RuntimeError::DatabaseError)?;
-        } else if let Some(export_type) = &self.options.export_type {
+        } else if let Some(export_type) = self.options.export_types.first() {
             // Ensure that if we want to filter on things, we have stuff to filter for
             if let Some(filters) = &self.options.conversation_filter {
                 if !self.options.query_context.has_filters() {
@@ -417,14 +1154,46 @@ impl Config {
                 }
             }
 
+            // Ensure the filters select something before we create any files
+            let total_messages = Message::get_count(&self.db, &self.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+            if total_messages == 0 {
+                warn!("No messages matched your filters!");
+                for filter in self.active_filters() {
+                    warn!("    {filter}");
+                }
+                return Ok(());
+            }
+
+            // Print the start of each conversation instead of exporting, if requested
+            if let Some(limit) = self.options.preview {
+                return match export_type {
+                    ExportType::Html => self.run_preview(&HTML::preview(self), limit),
+                    // PDFs are rendered from the text export
+                    ExportType::Txt | ExportType::Pdf => {
+                        self.run_preview(&TXT::preview(self), limit)
+                    }
+                    ExportType::Json => self.run_preview(&JSONExporter::preview(self), limit),
+                    // Parquet files are binary, so their rows are previewed as CSV
+                    ExportType::Csv | ExportType::Parquet => {
+                        self.run_preview(&CSV::preview(self), limit)
+                    }
+                    ExportType::WhatsApp => self.run_preview(&WhatsApp::preview(self), limit),
+                    ExportType::Markdown => self.run_preview(&Markdown::preview(self), limit),
+                };
+            }
+
             // Ensure the path we want to export to exists
             create_dir_all(&self.options.export_path).map_err(RuntimeError::DiskError)?;
 
             // Ensure the path we want to copy attachments to exists, if requested
-            if !matches!(
-                self.options.attachment_manager.mode,
-                AttachmentManagerMode::Disabled
-            ) {
+            // Conversation directories are created as they are written to
+            if !self.options.directory_per_chat
+                && !matches!(
+                    self.options.attachment_manager.mode,
+                    AttachmentManagerMode::Disabled
+                )
+            {
                 create_dir_all(self.attachment_path()).map_err(RuntimeError::DiskError)?;
             }
 
@@ -436,36 +1205,409 @@ impl Config {
             // Ensure we have enough file handles to export
             let _ = raise_fd_limit();
 
-            // Create exporter, pass it data we care about, then kick it off
-            match export_type {
-                ExportType::Html => {
-                    HTML::new(self)?.iter_messages()?;
+            // Load the messages that previous exports wrote, if we skip them
+            if let Some(mode) = &self.options.skip_exported {
+                self.exported.replace(Some(ExportedMessages::open(
+                    &self.options.export_path,
+                    mode,
+                    total_messages,
+                )?));
+            }
+
+            // Some filters can only be applied to messages once they are read, so ensure one of them is left
+            if self.has_export_filters() && !self.has_exportable_messages()? {
+                warn!("No messages matched your filters!");
+                for filter in self.active_filters() {
+                    warn!("    {filter}");
                 }
-                ExportType::Txt => {
-                    TXT::new(self)?.iter_messages()?;
+                return Ok(());
+            }
+
+            // Start the attachment manifest, if requested
+            if self.options.attachment_manifest {
+                self.attachment_manifest
+                    .replace(Some(AttachmentManifest::create(&self.options.export_path)?));
+            }
+
+            // Start the receipts sidecar, if requested
+            if self.options.receipts {
+                let path = self
+                    .options
+                    .export_path
+                    .join(RECEIPTS)
+                    .with_extension(self.file_extension("json"));
+                self.receipts
+                    .replace(Some(Receipts::create(&path, self.options.compress)?));
+            }
+
+            // Start the reactions sidecar, if requested
+            if self.options.reactions_as_records {
+                let path = self
+                    .options
+                    .export_path
+                    .join(REACTIONS)
+                    .with_extension(self.file_extension("json"));
+                self.reactions
+                    .replace(Some(Reactions::create(&path, self.options.compress)?));
+            }
+
+            // Hash attachments as they are copied, if requested
+            if self.options.checksums {
+                self.checksums.replace(Some(Checksums::default()));
+            }
+
+            // Create an exporter for each format, pass it data we care about, then kick it off
+            // The caches are built once and shared by every format
+            for export_type in &self.options.export_types {
+                self.export_type.set(Some(*export_type));
+                match export_type {
+                    ExportType::Html => self.run_export::<HTML>(export_type)?,
+                    ExportType::Txt => self.run_export::<TXT>(export_type)?,
+                    ExportType::Json => self.run_export::<JSONExporter>(export_type)?,
+                    ExportType::Pdf => self.run_export::<PDF>(export_type)?,
+                    ExportType::Csv => self.run_export::<CSV>(export_type)?,
+                    ExportType::WhatsApp => self.run_export::<WhatsApp>(export_type)?,
+                    ExportType::Markdown => self.run_export::<Markdown>(export_type)?,
+                    ExportType::Parquet => self.run_export::<Parquet>(export_type)?,
                 }
-                ExportType::Json => {
-                    JSONExporter::new(self)?.iter_messages()?;
+            }
+
+            // Save the messages that this export wrote
+            if let Some(exported) = self.exported.borrow_mut().as_mut() {
+                exported.flush()?;
+            }
+            if let Some(manifest) = self.attachment_manifest.borrow_mut().as_mut() {
+                manifest.flush()?;
+            }
+            if let Some(mut receipts) = self.receipts.take() {
+                receipts.finish()?;
+            }
+            if let Some(mut reactions) = self.reactions.take() {
+                reactions.finish()?;
+            }
+
+            // Hash the rest of the files once they are closed, since files can be appended to throughout the export
+            if let Some(mut checksums) = self.checksums.take() {
+                info!("Writing checksums...");
+                checksums.add_dir(&self.options.export_path, &self.options.export_path)?;
+                checksums.write(&self.options.export_path)?;
+            }
+
+            // Ensure the files we wrote can be read back, if requested; every format is verified even if one fails
+            let mut verified = Ok(());
+            if self.options.verify {
+                for export_type in &self.options.export_types {
+                    if let Err(why) = self.verify_files(export_type) {
+                        verified = Err(why);
+                    }
                 }
             }
+
+            // Exporters that do not check for errors as they go still finish writing before we stop
+            if self.aborted.get() {
+                return Err(RuntimeError::Aborted);
+            }
+
+            // Pack the export into a single archive, if requested, keeping what a best effort export wrote
+            if self.options.zip {
+                let archive = archive_path(&self.options.export_path);
+                info!("Packing export into {}...", archive.display());
+                zip_dir(&self.options.export_path, &archive)?;
+                remove_dir_all(&self.options.export_path).map_err(RuntimeError::DiskError)?;
+            }
+
+            // Under the best effort policy, the export still fails if anything went wrong
+            if self.errors.get() > 0 {
+                return Err(RuntimeError::ExportErrors(self.errors.get()));
+            }
+            verified?;
         }
-        println!("Done!");
+        info!("Done!");
         Ok(())
     }
 
-    /// Determine who sent a message
-    pub fn who<'a, 'b: 'a>(
+    /// Export every message with `E`, warning about requested features that the format leaves out
+    fn run_export<'a, E: Exporter<'a>>(
         &'a self,
-        handle_id: Option<i32>,
+        export_type: &ExportType,
+    ) -> Result<(), RuntimeError> {
+        if !E::supports_attachments()
+            && !matches!(
+                self.options.attachment_manager.mode,
+                AttachmentManagerMode::Disabled
+            )
+        {
+            warn!(
+                "Option {OPTION_ATTACHMENT_MANAGER} is enabled, but the `{export_type}` format does not export attachments!"
+            );
+        }
+        if !E::supports_reactions() {
+            info!(
+                "The `{export_type}` format does not show tapbacks with the messages they react to"
+            );
+        }
+        E::new(self)?.iter_messages()
+    }
+
+    /// Print at most `limit` rendered messages from each conversation to stdout without writing any files
+    ///
+    /// Messages are read with the same filters as an export, so the preview shows what the export would contain.
+    fn run_preview<'a>(
+        &'a self,
+        previewer: &impl Previewer<'a>,
+        limit: usize,
+    ) -> Result<(), RuntimeError> {
+        let mut statement = Message::stream_rows(&self.db, &self.options.query_context)
+            .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        // Map of conversation file name to its rendered messages, in the order they were sent
+        let mut previews: BTreeMap<String, VecDeque<String>> = BTreeMap::new();
+        for message in messages {
+            let mut msg = match Message::extract(message) {
+                Ok(msg) => msg,
+                Err(why) => {
+                    self.handle_error(RuntimeError::DatabaseError(why))?;
+                    continue;
+                }
+            };
+
+            // Drop messages that do not belong to a conversation, if requested
+            if self.options.no_orphaned && self.is_orphaned(&msg) {
+                continue;
+            }
+
+            let conversation = match self.conversation(&msg) {
+                Some((chatroom, _)) => self.filename(chatroom),
+                None => ORPHANED.to_string(),
+            };
+            let rendered = previews.entry(conversation).or_default();
+
+            // Once a conversation has its first messages, the rest do not need to be rendered
+            if !self.options.preview_last && rendered.len() >= limit {
+                continue;
+            }
+
+            generate_text_or_placeholder(&mut msg, &self.db);
+            if self.is_too_short(&msg) {
+                continue;
+            }
+            match previewer.format_preview(&msg) {
+                Ok(Some(text)) => {
+                    rendered.push_back(text);
+                    if rendered.len() > limit {
+                        rendered.pop_front();
+                    }
+                }
+                Ok(None) => {}
+                Err(why) => self.handle_error(why)?,
+            }
+        }
+
+        for (conversation, rendered) in previews.iter().filter(|(_, rendered)| !rendered.is_empty())
+        {
+            println!("==> {conversation} <==");
+            for text in rendered {
+                println!("{}", text.trim_end());
+            }
+            println!();
+        }
+
+        if self.errors.get() > 0 {
+            return Err(RuntimeError::ExportErrors(self.errors.get()));
+        }
+        Ok(())
+    }
+
+    /// Re-read the files the export wrote, logging any that are not valid
+    fn verify_files(&self, export_type: &ExportType) -> Result<(), RuntimeError> {
+        info!("Verifying exported files...");
+        let report = verify_export(&self.options.export_path, export_type)?;
+        for (path, why) in &report.failed {
+            error!("{} is not valid {export_type}: {why}", path.display());
+        }
+        info!(
+            "Verified {} file{}: {} passed, {} failed",
+            report.passed + report.failed.len(),
+            if report.passed + report.failed.len() != 1 {
+                "s"
+            } else {
+                ""
+            },
+            report.passed,
+            report.failed.len()
+        );
+
+        if !report.failed.is_empty() {
+            return Err(RuntimeError::VerifyErrors(report.failed.len()));
+        }
+        Ok(())
+    }
+
+    /// Read every message in the database without exporting it, logging any that cannot be read
+    ///
+    /// The chats, participants, and tapbacks were already cached when the app was created.
+    fn run_validation(&self) -> Result<(), RuntimeError> {
+        info!("Validating messages...");
+        let report = validate_messages(&self.db, &self.options.query_context)
+            .map_err(RuntimeError::DatabaseError)?;
+        for (guid, why) in &report.failed {
+            error!("Message {guid} cannot be read: {why}");
+        }
+        info!(
+            "Validated {} message{}: {} passed, {} failed",
+            report.passed + report.failed.len(),
+            if report.passed + report.failed.len() != 1 {
+                "s"
+            } else {
+                ""
+            },
+            report.passed,
+            report.failed.len()
+        );
+
+        if !report.failed.is_empty() {
+            return Err(RuntimeError::ValidationErrors(report.failed.len()));
+        }
+        Ok(())
+    }
+
+    /// Describe the filters that limit which messages get exported
+    fn active_filters(&self) -> Vec<String> {
+        let mut filters = vec![];
+        if let Some(start) = self.options.query_context.start {
+            filters.push(format!(
+                "Start date: {}",
+                self.format_date(&get_local_time(&start, &self.offset))
+            ));
+        }
+        if let Some(end) = self.options.query_context.end {
+            filters.push(format!(
+                "End date: {}",
+                self.format_date(&get_local_time(&end, &self.offset))
+            ));
+        }
+        if let Some(conversation_filter) = &self.options.conversation_filter {
+            filters.push(format!("Conversation filter: {conversation_filter}"));
+        }
+        if self.participant_range().is_some() {
+            filters.push(format!(
+                "Participants: {}",
+                self.describe_participant_range()
+            ));
+        }
+        if self.options.no_orphaned {
+            filters.push("Messages without a conversation are dropped".to_string());
+        }
+        if self.options.skip_exported.is_some() {
+            filters.push("Messages a previous export wrote are skipped".to_string());
+        }
+        if let Some(min_length) = self.options.min_text_length {
+            filters.push(format!("Minimum text length: {min_length}"));
+        }
+        if self.options.drop_attachment_only {
+            filters.push("Messages with only attachments are dropped".to_string());
+        }
+        filters
+    }
+
+    /// `true` if any filter is applied to messages after they are read from the database, else `false`
+    fn has_export_filters(&self) -> bool {
+        self.options.no_orphaned
+            || self.options.skip_exported.is_some()
+            || self.options.min_text_length.is_some()
+            || self.options.drop_attachment_only
+    }
+
+    /// Determine if any message selected by the query also passes the filters the exporters apply
+    ///
+    /// Rows are only read until the first one that would be exported, so this is cheap unless nothing matches.
+    /// Rows that cannot be read count as exportable, since the export reports them.
+    fn has_exportable_messages(&self) -> Result<bool, RuntimeError> {
+        let mut statement = Message::stream_rows(&self.db, &self.options.query_context)?;
+        let messages = statement.query_map([], |row| Ok(Message::from_row(row)))?;
+
+        for message in messages {
+            let Ok(mut message) = Message::extract(message) else {
+                return Ok(true);
+            };
+            if (self.options.no_orphaned && self.is_orphaned(&message))
+                || self.already_exported(&message)
+            {
+                continue;
+            }
+            generate_text_or_placeholder(&mut message, &self.db);
+            if !self.is_too_short(&message) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Format a date for reading, with the pattern from `--date-format` if one was given
+    pub fn format_date(&self, date: &Result<DateTime<Local>, MessageError>) -> String {
+        self.format_date_as(
+            date,
+            self.options
+                .date_format
+                .as_deref()
+                .unwrap_or(DEFAULT_DATE_FORMAT),
+        )
+    }
+
+    /// Format a date with a [`strftime`](chrono::format::strftime) pattern, in the time zone from `--timezone` if one was given
+    pub fn format_date_as(
+        &self,
+        date: &Result<DateTime<Local>, MessageError>,
+        pattern: &str,
+    ) -> String {
+        match (date, self.options.timezone) {
+            (Ok(date), Some(_)) => self.localize(date).format(pattern).to_string(),
+            _ => format_as(date, pattern),
+        }
+    }
+
+    /// Move a date to the time zone from `--timezone`, keeping this computer's time zone if none was given
+    pub fn localize(&self, date: &DateTime<Local>) -> DateTime<FixedOffset> {
+        match self.options.timezone {
+            Some(offset) => date.with_timezone(&offset),
+            None => date.fixed_offset(),
+        }
+    }
+
+    /// `true` if every participant is the database owner, i.e. in a conversation with themselves, else `false`
+    fn is_notes_to_self(&self, participants: &BTreeSet<i32>) -> bool {
+        !participants.is_empty() && participants.iter().all(|id| self.self_handles.contains(id))
+    }
+
+    /// Determine who sent a message
+    ///
+    /// Messages the database owner received from themselves, i.e. in notes to self, are also theirs.
+    pub fn who<'a, 'b: 'a>(
+        &'a self,
+        handle_id: Option<i32>,
         is_from_me: bool,
         destination_caller_id: &'b Option<String>,
     ) -> &'a str {
-        if is_from_me {
+        if is_from_me || handle_id.is_some_and(|id| self.self_handles.contains(&id)) {
+            // Databases with several of the owner's addresses can name each one
+            if let Some(label) = destination_caller_id
+                .as_deref()
+                .and_then(|address| self.options.caller_id_labels.get(&address.to_lowercase()))
+            {
+                return label;
+            }
             if self.options.use_caller_id {
                 return destination_caller_id.as_deref().unwrap_or(ME);
             }
             return self.options.custom_name.as_deref().unwrap_or(ME);
         } else if let Some(handle_id) = handle_id {
+            if let Some(name) = self.contact_names.get(&handle_id) {
+                return name;
+            }
             return match self.participants.get(&handle_id) {
                 Some(contact) => contact,
                 None => UNKNOWN,
@@ -475,20 +1617,155 @@ impl Config {
     }
 }
 
+/// Map each deduplicated chat ID to the lowest chat ID that shares it
+///
+/// The lowest ID belongs to the oldest chat, so this does not change as newer duplicates are created.
+fn canonical_chatrooms(real_chatrooms: &HashMap<i32, i32>) -> HashMap<i32, i32> {
+    let mut canonical: HashMap<i32, i32> = HashMap::new();
+    for (chat_id, real_id) in real_chatrooms {
+        canonical
+            .entry(*real_id)
+            .and_modify(|lowest| *lowest = min(*lowest, *chat_id))
+            .or_insert(*chat_id);
+    }
+    canonical
+}
+
+/// Replace each chat's handle IDs with the deduplicated IDs of the people they belong to
+///
+/// The same person has a different handle for each service, so chats with them on iMessage and SMS
+/// only share a participant set once their handles are deduplicated.
+///
+/// A chat with none of its handles resolved is given a set of its own, keyed by the negated chat ID,
+/// since deduplicated IDs are never negative; otherwise every such chat would be merged into one.
+fn resolve_chatroom_participants(
+    chatroom_participants: &HashMap<i32, BTreeSet<i32>>,
+    real_participants: &HashMap<i32, i32>,
+) -> HashMap<i32, BTreeSet<i32>> {
+    chatroom_participants
+        .iter()
+        .map(|(chat_id, participants)| {
+            let resolved: BTreeSet<i32> = participants
+                .iter()
+                .filter_map(|handle_id| real_participants.get(handle_id).copied())
+                .collect();
+            if resolved.is_empty() {
+                (*chat_id, BTreeSet::from([-chat_id - 1]))
+            } else {
+                (*chat_id, resolved)
+            }
+        })
+        .collect()
+}
+
+/// Count the characters of a message's text, ignoring surrounding whitespace and the markers for attachments and apps
+fn text_length(message: &Message) -> usize {
+    let text: String = message
+        .text
+        .as_deref()
+        .unwrap_or_default()
+        .chars()
+        .filter(|letter| !matches!(letter, '\u{FFFC}' | '\u{FFFD}'))
+        .collect();
+    text.trim().chars().count()
+}
+
+/// Report a database that `SQLite` cannot read as a database as [`RuntimeError::EncryptedDatabase`]
+///
+/// The header check in [`Config::new()`] catches most encrypted files; this catches the rest when they are first queried.
+fn classify_database_error(why: TableError, path: &Path) -> RuntimeError {
+    match why {
+        TableError::Schema(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error {
+                code: rusqlite::ErrorCode::NotADatabase,
+                ..
+            },
+            _,
+        )) => RuntimeError::EncryptedDatabase(path.to_path_buf()),
+        why => RuntimeError::DatabaseError(why),
+    }
+}
+
+/// The directory the database is copied to when `--copy-db` is enabled, unique to this process
+fn snapshot_dir() -> PathBuf {
+    temp_dir().join(format!("imessage-exporter-{}", process::id()))
+}
+
+/// Copy the database to [`snapshot_dir()`], returning the path to the copy
+fn copy_database(db_path: &Path) -> Result<PathBuf, RuntimeError> {
+    let free_space = available_space(temp_dir()).map_err(RuntimeError::DiskError)?;
+    copy_database_with_free_space(db_path, free_space)
+}
+
+/// Copy the database to [`snapshot_dir()`], given `free_space` bytes available in the temporary directory
+///
+/// The copy is not started if the temporary directory does not have room for it,
+/// and a copy that fails part way through is removed.
+fn copy_database_with_free_space(db_path: &Path, free_space: u64) -> Result<PathBuf, RuntimeError> {
+    let destination = snapshot_dir();
+    let database_size = get_snapshot_size(db_path).map_err(RuntimeError::DatabaseError)?;
+    if database_size >= free_space {
+        return Err(RuntimeError::NotEnoughSpaceForCopy(
+            database_size,
+            free_space,
+        ));
+    }
+
+    info!(
+        "Copying {} of database files to {}...",
+        format_file_size(database_size),
+        destination.display()
+    );
+    snapshot_database(db_path, &destination).map_err(|why| {
+        let _ = remove_dir_all(&destination);
+        RuntimeError::DatabaseError(why)
+    })
+}
+
+/// Remove the copy of the database made by [`copy_database()`]
+fn remove_snapshot() {
+    if let Err(why) = remove_dir_all(snapshot_dir()) {
+        warn!("Unable to remove the copy of the database: {why}");
+    }
+}
+
+impl Drop for Config {
+    /// Remove the copy of the database, if one was made
+    fn drop(&mut self) {
+        if self.options.copy_db {
+            remove_snapshot();
+        }
+    }
+}
+
 #[cfg(test)]
 impl Config {
     pub fn fake_app(options: Options) -> Config {
-        let connection = get_connection(&options.db_path).unwrap();
+        let connection = get_connection_with_access(&options.db_path, options.db_access).unwrap();
         Config {
             chatrooms: HashMap::new(),
             real_chatrooms: HashMap::new(),
+            canonical_chatrooms: HashMap::new(),
             chatroom_participants: HashMap::new(),
             participants: HashMap::new(),
+            contact_names: HashMap::new(),
             real_participants: HashMap::new(),
+            self_handles: HashSet::new(),
             tapbacks: HashMap::new(),
             options,
             offset: get_offset(),
             db: connection,
+            schema: SchemaVersion::Ventura,
+            errors: Cell::new(0),
+            aborted: Cell::new(false),
+            exported: RefCell::new(None),
+            attachment_manifest: RefCell::new(None),
+            receipts: RefCell::new(None),
+            reactions: RefCell::new(None),
+            checksums: RefCell::new(None),
+            attachment_names: RefCell::new(AttachmentNames::default()),
+            export_type: Cell::new(None),
+            progress: RefCell::new(None),
         }
     }
 
@@ -506,6 +1783,7 @@ impl Config {
             date_delivered: i64::default(),
             is_from_me: false,
             is_read: false,
+            error: 0,
             item_type: 0,
             other_handle: 0,
             share_status: false,
@@ -519,6 +1797,9 @@ impl Config {
             thread_originator_guid: None,
             thread_originator_part: None,
             date_edited: 0,
+            service_center: None,
+            was_downgraded: false,
+            is_audio_message: false,
             associated_message_emoji: None,
             chat_id: None,
             num_attachments: 0,
@@ -532,6 +1813,7 @@ impl Config {
     pub(crate) fn fake_attachment() -> Attachment {
         Attachment {
             rowid: 0,
+            guid: None,
             filename: Some("a/b/c/d.jpg".to_string()),
             uti: Some("public.png".to_string()),
             mime_type: Some("image/png".to_string()),
@@ -541,6 +1823,7 @@ impl Config {
             hide_attachment: 0,
             emoji_description: None,
             copied_path: None,
+            original_path: None,
         }
     }
 }
@@ -548,15 +1831,20 @@ impl Config {
 #[cfg(test)]
 mod filename_tests {
     use crate::{Config, Options};
-    use imessage_database::tables::{chat::Chat, table::MAX_LENGTH};
-    use std::collections::BTreeSet;
+    use imessage_database::tables::{
+        chat::Chat,
+        table::{MAX_LENGTH, NOTES_TO_SELF},
+    };
+    use std::collections::{BTreeSet, HashMap};
 
     fn fake_chat() -> Chat {
         Chat {
             rowid: 0,
             chat_identifier: "Default".to_string(),
+            guid: None,
             service_name: Some(String::new()),
             display_name: None,
+            last_read: None,
         }
     }
 
@@ -564,7 +1852,7 @@ mod filename_tests {
     fn can_create() {
         let mut options = Options::fake_options(crate::app::export_type::ExportType::Html);
         // Disable the export
-        options.export_type = None;
+        options.export_types = vec![];
         let app = Config::fake_app(options);
         app.start().unwrap();
     }
@@ -589,6 +1877,41 @@ mod filename_tests {
         assert!(filename.len() <= MAX_LENGTH);
     }
 
+    #[test]
+    fn can_get_filename_notes_to_self() {
+        let options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        let mut app = Config::fake_app(options);
+
+        // Create participant data for the database owner
+        app.participants.insert(10, "+15555550100".to_string());
+        app.self_handles.insert(10);
+
+        // Add the owner as the only participant
+        let people = BTreeSet::from([10]);
+
+        // Get filename
+        let filename = app.filename_from_participants(&people);
+        assert_eq!(filename, NOTES_TO_SELF);
+    }
+
+    #[test]
+    fn can_get_filename_self_and_others() {
+        let options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        let mut app = Config::fake_app(options);
+
+        // Create participant data
+        app.participants.insert(10, "+15555550100".to_string());
+        app.participants.insert(11, "Person 11".to_string());
+        app.self_handles.insert(10);
+
+        // Add the owner and someone else
+        let people = BTreeSet::from([10, 11]);
+
+        // Get filename
+        let filename = app.filename_from_participants(&people);
+        assert_eq!(filename, "Me, Person 11");
+    }
+
     #[test]
     fn can_get_filename_long_multiple() {
         let options = Options::fake_options(crate::app::export_type::ExportType::Html);
@@ -691,6 +2014,115 @@ mod filename_tests {
         assert_eq!(filename, "Test Chat Name - 0.html");
     }
 
+    #[test]
+    fn can_get_filename_rename_group() {
+        let mut options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        options.group_names = HashMap::from([("chat123".to_string(), "Book Club".to_string())]);
+        let mut app = Config::fake_app(options);
+        app.participants.insert(10, "Person 10".to_string());
+        app.participants.insert(11, "Person 11".to_string());
+        app.chatroom_participants
+            .insert(0, BTreeSet::from([10, 11]));
+
+        // A chat without a name uses the given name instead of its participants
+        let mut chat = fake_chat();
+        chat.guid = Some("chat123".to_string());
+        assert_eq!(app.filename(&chat), "Book Club - 0.html");
+
+        // The given name replaces the chat's own name
+        chat.display_name = Some("Test Chat Name".to_string());
+        assert_eq!(app.chat_name(&chat), Some("Book Club"));
+        assert_eq!(app.filename(&chat), "Book Club - 0.html");
+
+        // Other chats are unchanged
+        chat.guid = Some("chat456".to_string());
+        assert_eq!(app.filename(&chat), "Test Chat Name - 0.html");
+    }
+
+    #[test]
+    fn can_get_filename_rename_group_long_multibyte() {
+        let name = "読書会".repeat(27);
+        assert!(name.len() > MAX_LENGTH);
+        let mut options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        options.group_names = HashMap::from([("chat123".to_string(), name.clone())]);
+        let app = Config::fake_app(options);
+
+        let mut chat = fake_chat();
+        chat.guid = Some("chat123".to_string());
+        let filename = app.filename(&chat);
+
+        // The name is cut at the last whole character that fits
+        let kept = MAX_LENGTH / 3;
+        let expected: String = name.chars().take(kept).collect();
+        assert_eq!(filename, format!("{expected} - 0.html"));
+    }
+
+    #[test]
+    fn can_get_filename_flatten_group_names() {
+        let mut options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        options.flatten_group_names = true;
+        let mut app = Config::fake_app(options);
+        app.participants.insert(10, "Person 10".to_string());
+        app.participants.insert(11, "Person 11".to_string());
+
+        // A direct message with a name is named after the other participant
+        let mut chat = fake_chat();
+        chat.display_name = Some("Test Chat Name".to_string());
+        app.chatroom_participants.insert(0, BTreeSet::from([10]));
+        assert_eq!(app.filename(&chat), "Person 10.html");
+
+        // A group chat keeps its name
+        app.chatroom_participants
+            .insert(0, BTreeSet::from([10, 11]));
+        assert_eq!(app.filename(&chat), "Test Chat Name - 0.html");
+    }
+
+    #[test]
+    fn can_get_conversation_title() {
+        let mut options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        options.flatten_group_names = true;
+        options
+            .group_names
+            .insert("chat123".to_string(), "Book Club".to_string());
+        let mut app = Config::fake_app(options);
+        app.participants.insert(10, "Person 10".to_string());
+        app.participants.insert(11, "Person 11".to_string());
+
+        // A direct message is titled after the other participant, like its file
+        let mut chat = fake_chat();
+        chat.display_name = Some("Test Chat Name".to_string());
+        app.chatroom_participants.insert(0, BTreeSet::from([10]));
+        assert_eq!(app.conversation_title(&chat), "Person 10");
+
+        // A group chat is titled with its name, without the chat ID
+        app.chatroom_participants
+            .insert(0, BTreeSet::from([10, 11]));
+        assert_eq!(app.conversation_title(&chat), "Test Chat Name");
+
+        // A name given with `--rename-group` replaces the chat's own name
+        chat.guid = Some("chat123".to_string());
+        assert_eq!(app.conversation_title(&chat), "Book Club");
+    }
+
+    #[test]
+    fn can_get_filename_current_export_type() {
+        let mut options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        options
+            .export_types
+            .push(crate::app::export_type::ExportType::Json);
+        let app = Config::fake_app(options);
+
+        let mut chat = fake_chat();
+        chat.display_name = Some("Test Chat Name".to_string());
+
+        // Before the export starts, the first format is used
+        assert_eq!(app.filename(&chat), "Test Chat Name - 0.html");
+
+        app.export_type
+            .set(Some(crate::app::export_type::ExportType::Json));
+        assert_eq!(app.filename(&chat), "Test Chat Name - 0.json");
+    }
+
     #[test]
     fn can_get_filename_chat_display_name_short() {
         let options = Options::fake_options(crate::app::export_type::ExportType::Html);
@@ -740,19 +2172,63 @@ mod filename_tests {
         let filename = app.filename(&chat);
         assert_eq!(filename, "Default.html");
     }
+
+    #[test]
+    fn can_get_conversation_participants() {
+        let options = Options::fake_options(crate::app::export_type::ExportType::Json);
+        let mut app = Config::fake_app(options);
+
+        // Create chat
+        let chat = fake_chat();
+
+        // Create participant data, where handles 11 and 12 are the same person
+        app.participants.insert(10, "Person 10".to_string());
+        app.participants.insert(11, "Person 11".to_string());
+        app.participants.insert(12, "Person 11".to_string());
+        app.real_participants.insert(10, 0);
+        app.real_participants.insert(11, 1);
+        app.real_participants.insert(12, 1);
+
+        // Add participants
+        let mut people = BTreeSet::new();
+        people.insert(10);
+        people.insert(11);
+        people.insert(12);
+        app.chatroom_participants.insert(chat.rowid, people);
+
+        // Get participants
+        let participants = app.conversation_participants(&chat.rowid);
+        assert_eq!(participants, vec!["Person 10", "Person 11"]);
+    }
+
+    #[test]
+    fn can_get_conversation_participants_no_chat() {
+        let options = Options::fake_options(crate::app::export_type::ExportType::Json);
+        let app = Config::fake_app(options);
+
+        // Get participants
+        let participants = app.conversation_participants(&0);
+        assert!(participants.is_empty());
+    }
 }
 
 #[cfg(test)]
 mod who_tests {
-    use crate::{Config, Options};
-    use imessage_database::tables::chat::Chat;
+    use crate::{
+        app::runtime::{canonical_chatrooms, resolve_chatroom_participants},
+        Config, Options,
+    };
+    use imessage_database::tables::{chat::Chat, chat_handle::ChatToHandle, table::Deduplicate};
+    use std::collections::{BTreeSet, HashMap, HashSet};
 
     fn fake_chat() -> Chat {
         Chat {
             rowid: 0,
             chat_identifier: "Default".to_string(),
+            guid: None,
             service_name: Some(String::new()),
             display_name: None,
+            last_read: None,
         }
     }
 
@@ -769,6 +2245,34 @@ mod who_tests {
         assert_eq!(who, "Person 10".to_string());
     }
 
+    #[test]
+    fn can_get_who_them_handle_map() {
+        let options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        let mut app = Config::fake_app(options);
+
+        // Create participant data
+        app.participants.insert(10, "5558675309".to_string());
+        app.contact_names.insert(10, "Grandma".to_string());
+
+        // Get participant name
+        let who = app.who(Some(10), false, &None);
+        assert_eq!(who, "Grandma".to_string());
+    }
+
+    #[test]
+    fn can_get_who_self_handle() {
+        let options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        let mut app = Config::fake_app(options);
+
+        // Create participant data for the database owner
+        app.participants.insert(10, "+15555550100".to_string());
+        app.self_handles.insert(10);
+
+        // Get participant name
+        let who = app.who(Some(10), false, &None);
+        assert_eq!(who, "Me".to_string());
+    }
+
     #[test]
     fn can_get_who_them_missing() {
         let options = Options::fake_options(crate::app::export_type::ExportType::Html);
@@ -779,6 +2283,22 @@ mod who_tests {
         assert_eq!(who, "Unknown".to_string());
     }
 
+    #[test]
+    fn can_get_who_me_caller_id_label() {
+        let mut options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        options
+            .caller_id_labels
+            .insert("steve@apple.com".to_string(), "Work".to_string());
+        options.custom_name = Some("Steve".to_string());
+        let app = Config::fake_app(options);
+
+        // Labeled addresses use their label, others fall back to the custom name
+        let labeled = Some("Steve@Apple.com".to_string());
+        assert_eq!(app.who(Some(0), true, &labeled), "Work");
+        let unlabeled = Some("+15558675309".to_string());
+        assert_eq!(app.who(Some(0), true, &unlabeled), "Steve");
+    }
+
     #[test]
     fn can_get_who_me() {
         let options = Options::fake_options(crate::app::export_type::ExportType::Html);
@@ -884,26 +2404,103 @@ mod who_tests {
     }
 
     #[test]
-    fn can_get_chat_invalid() {
+    fn can_get_chat_merged() {
         let options = Options::fake_options(crate::app::export_type::ExportType::Html);
         let mut app = Config::fake_app(options);
 
-        // Create chat
-        let chat = fake_chat();
-        app.chatrooms.insert(chat.rowid, chat);
+        // Create two chats with the same participants
+        app.chatrooms.insert(0, fake_chat());
+        app.chatrooms.insert(
+            1,
+            Chat {
+                rowid: 1,
+                chat_identifier: "Duplicate".to_string(),
+                ..fake_chat()
+            },
+        );
         app.real_chatrooms.insert(0, 0);
+        app.real_chatrooms.insert(1, 0);
+        app.canonical_chatrooms = canonical_chatrooms(&app.real_chatrooms);
 
-        // Create message
+        // Create message in the newer chat
         let mut message = Config::fake_message();
         message.chat_id = Some(1);
 
-        // Get filename
-        let room = app.conversation(&message);
-        assert!(room.is_none());
+        // Resolves to the older chat
+        let (chatroom, id) = app.conversation(&message).unwrap();
+        assert_eq!(chatroom.rowid, 0);
+        assert_eq!(id, &0);
     }
 
     #[test]
-    fn can_get_chat_none() {
+    fn can_get_canonical_chatrooms() {
+        let real_chatrooms = HashMap::from([(4, 0), (2, 0), (3, 1), (7, 1), (5, 2)]);
+        let canonical = canonical_chatrooms(&real_chatrooms);
+        assert_eq!(canonical, HashMap::from([(0, 2), (1, 3), (2, 5)]));
+    }
+
+    #[test]
+    fn can_merge_chatrooms_across_services() {
+        // Handles 1 and 2 are the same person on iMessage and SMS
+        let real_participants = HashMap::from([(1, 0), (2, 0), (3, 1)]);
+        let chatroom_participants = HashMap::from([
+            (10, BTreeSet::from([1])),
+            (11, BTreeSet::from([2])),
+            (12, BTreeSet::from([2, 3])),
+        ]);
+
+        // Without resolving handles, every chat has a different participant set
+        let separate = ChatToHandle::dedupe(&chatroom_participants);
+        assert_ne!(separate.get(&10), separate.get(&11));
+
+        let merged = ChatToHandle::dedupe(&resolve_chatroom_participants(
+            &chatroom_participants,
+            &real_participants,
+        ));
+        assert_eq!(merged.get(&10), merged.get(&11));
+        assert_ne!(merged.get(&10), merged.get(&12));
+    }
+
+    #[test]
+    fn cant_merge_chatrooms_without_resolved_handles() {
+        // Handles 4 and 5 are not in the deduplicated participants
+        let real_participants = HashMap::from([(1, 0)]);
+        let chatroom_participants = HashMap::from([
+            (10, BTreeSet::from([4])),
+            (11, BTreeSet::from([5])),
+            (12, BTreeSet::new()),
+            (13, BTreeSet::from([1])),
+        ]);
+
+        let resolved = resolve_chatroom_participants(&chatroom_participants, &real_participants);
+        assert_eq!(resolved.get(&13), Some(&BTreeSet::from([0])));
+
+        let merged = ChatToHandle::dedupe(&resolved);
+        let unique: HashSet<&i32> = merged.values().collect();
+        assert_eq!(unique.len(), 4);
+    }
+
+    #[test]
+    fn can_get_chat_invalid() {
+        let options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        let mut app = Config::fake_app(options);
+
+        // Create chat
+        let chat = fake_chat();
+        app.chatrooms.insert(chat.rowid, chat);
+        app.real_chatrooms.insert(0, 0);
+
+        // Create message
+        let mut message = Config::fake_message();
+        message.chat_id = Some(1);
+
+        // Get filename
+        let room = app.conversation(&message);
+        assert!(room.is_none());
+    }
+
+    #[test]
+    fn can_get_chat_none() {
         let options = Options::fake_options(crate::app::export_type::ExportType::Html);
         let mut app = Config::fake_app(options);
 
@@ -921,11 +2518,77 @@ mod who_tests {
         let room = app.conversation(&message);
         assert!(room.is_none());
     }
+
+    #[test]
+    fn can_get_orphaned_valid() {
+        let options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        let mut app = Config::fake_app(options);
+
+        // Create chat
+        let chat = fake_chat();
+        app.chatrooms.insert(chat.rowid, chat);
+        app.real_chatrooms.insert(0, 0);
+
+        // Create message
+        let mut message = Config::fake_message();
+        message.chat_id = Some(0);
+
+        assert!(!app.is_orphaned(&message));
+    }
+
+    #[test]
+    fn can_get_orphaned_deleted() {
+        let options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        let mut app = Config::fake_app(options);
+
+        // Create chat
+        let chat = fake_chat();
+        app.chatrooms.insert(chat.rowid, chat);
+        app.real_chatrooms.insert(0, 0);
+
+        // Create message
+        let mut message = Config::fake_message();
+        message.chat_id = None;
+        message.deleted_from = Some(0);
+
+        assert!(!app.is_orphaned(&message));
+    }
+
+    #[test]
+    fn can_get_orphaned_invalid() {
+        let options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        let mut app = Config::fake_app(options);
+
+        // Create chat
+        let chat = fake_chat();
+        app.chatrooms.insert(chat.rowid, chat);
+        app.real_chatrooms.insert(0, 0);
+
+        // Create message
+        let mut message = Config::fake_message();
+        message.chat_id = Some(1);
+
+        assert!(app.is_orphaned(&message));
+    }
+
+    #[test]
+    fn can_get_orphaned_none() {
+        let options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        let app = Config::fake_app(options);
+
+        // Create message
+        let mut message = Config::fake_message();
+        message.chat_id = None;
+        message.deleted_from = None;
+
+        assert!(app.is_orphaned(&message));
+    }
 }
 
 #[cfg(test)]
 mod directory_tests {
     use crate::{Config, Options};
+    use imessage_database::tables::chat::Chat;
     use std::path::PathBuf;
 
     #[test]
@@ -1015,11 +2678,93 @@ mod directory_tests {
         let expected = String::from("a/b/c/d.jpg");
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn can_get_path_copied_directory_per_chat() {
+        let mut options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        // Set an export path
+        options.export_path = PathBuf::from("/Users/ReagentX/exports");
+        options.directory_per_chat = true;
+
+        let app = Config::fake_app(options);
+
+        // Create attachment
+        let mut attachment = Config::fake_attachment();
+        attachment.copied_path = Some(PathBuf::from(
+            "/Users/ReagentX/exports/Grandma - 1/attachments/d.jpg",
+        ));
+
+        // Links are relative to the conversation's directory
+        let result = app.message_attachment_path(&attachment);
+        let expected = String::from("attachments/d.jpg");
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_get_message_attachment_dir() {
+        let mut options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        options.export_path = PathBuf::from("/Users/ReagentX/exports");
+        let mut app = Config::fake_app(options);
+
+        // Create chatroom ID
+        app.real_chatrooms.insert(0, 0);
+
+        let mut message = Config::fake_message();
+        message.chat_id = Some(0);
+
+        assert_eq!(
+            app.message_attachment_dir(&message),
+            PathBuf::from("/Users/ReagentX/exports/attachments/0")
+        );
+    }
+
+    #[test]
+    fn can_get_message_attachment_dir_directory_per_chat() {
+        let mut options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        options.export_path = PathBuf::from("/Users/ReagentX/exports");
+        options.directory_per_chat = true;
+        let mut app = Config::fake_app(options);
+
+        // Create chat
+        app.chatrooms.insert(
+            0,
+            Chat {
+                rowid: 0,
+                chat_identifier: "Default".to_string(),
+                guid: None,
+                service_name: Some(String::new()),
+                display_name: Some("Family".to_string()),
+                last_read: None,
+            },
+        );
+        app.real_chatrooms.insert(0, 0);
+
+        let mut message = Config::fake_message();
+        message.chat_id = Some(0);
+        assert_eq!(
+            app.message_attachment_dir(&message),
+            PathBuf::from("/Users/ReagentX/exports/Family - 0/attachments")
+        );
+
+        // Orphaned messages share a directory
+        message.chat_id = None;
+        assert_eq!(
+            app.message_attachment_dir(&message),
+            PathBuf::from("/Users/ReagentX/exports/orphaned/attachments")
+        );
+    }
 }
 
 #[cfg(test)]
 mod chat_filter_tests {
-    use std::collections::BTreeSet;
+    use std::{
+        collections::BTreeSet,
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, write},
+        path::PathBuf,
+    };
+
+    use imessage_database::tables::chat::Chat;
 
     use crate::{app::export_type::ExportType, Config, Options};
 
@@ -1138,4 +2883,554 @@ mod chat_filter_tests {
             Some(BTreeSet::from([4, 6]))
         );
     }
+
+    fn fake_chat(rowid: i32) -> Chat {
+        Chat {
+            rowid,
+            chat_identifier: format!("Chat {rowid}"),
+            guid: None,
+            service_name: Some(String::new()),
+            display_name: None,
+            last_read: None,
+        }
+    }
+
+    #[test]
+    fn can_filter_chats_from_file() {
+        let dir = temp_dir().join("imessage-runtime-chats-from-file");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("chats.txt");
+        write(&path, "Chat 1\nChat 3\nNot a chat\n").unwrap();
+
+        let mut options = Options::fake_options(ExportType::Html);
+        options.chats_from_file = Some(path);
+        options
+            .query_context
+            .set_selected_chat_ids(BTreeSet::from([2, 3, 4]));
+
+        let mut app = Config::fake_app(options);
+
+        for chat_id in 1..=4 {
+            app.chatrooms.insert(chat_id, fake_chat(chat_id));
+            app.real_chatrooms.insert(chat_id, chat_id);
+        }
+        // Chatroom 2 is a duplicate of chatroom 1
+        app.real_chatrooms.insert(2, 1);
+
+        app.resolve_chats_from_file().unwrap();
+
+        assert_eq!(
+            app.options.query_context.selected_chat_ids,
+            Some(BTreeSet::from([2, 3]))
+        );
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cant_filter_chats_from_file_no_matches() {
+        let dir = temp_dir().join("imessage-runtime-chats-from-file-invalid");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("chats.txt");
+        write(&path, "Not a chat\n").unwrap();
+
+        let mut options = Options::fake_options(ExportType::Html);
+        options.chats_from_file = Some(path);
+
+        let mut app = Config::fake_app(options);
+        app.chatrooms.insert(1, fake_chat(1));
+
+        assert!(app.resolve_chats_from_file().is_err());
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    /// Chatroom 1 is a direct message, 2 is a group chat, 3 is a direct message with a contact
+    /// that has two handles, and 4 is notes to self
+    fn fake_sized_app(options: Options) -> Config {
+        let mut app = Config::fake_app(options);
+
+        app.self_handles.insert(10);
+        app.real_participants.insert(11, 11);
+        app.real_participants.insert(12, 12);
+        app.real_participants.insert(13, 13);
+        app.real_participants.insert(14, 13);
+
+        for (chat_id, people) in [
+            (1, BTreeSet::from([11])),
+            (2, BTreeSet::from([11, 12, 13])),
+            (3, BTreeSet::from([13, 14])),
+            (4, BTreeSet::from([10])),
+        ] {
+            app.chatrooms.insert(chat_id, fake_chat(chat_id));
+            app.chatroom_participants.insert(chat_id, people);
+        }
+        app
+    }
+
+    #[test]
+    fn can_filter_direct_messages() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.participants_max = Some(2);
+
+        let mut app = fake_sized_app(options);
+        app.resolve_participant_count();
+
+        assert_eq!(
+            app.options.query_context.selected_chat_ids,
+            Some(BTreeSet::from([1, 3, 4]))
+        );
+    }
+
+    #[test]
+    fn can_filter_group_chats() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.participants_min = Some(3);
+
+        let mut app = fake_sized_app(options);
+        app.resolve_participant_count();
+
+        assert_eq!(
+            app.options.query_context.selected_chat_ids,
+            Some(BTreeSet::from([2]))
+        );
+    }
+
+    #[test]
+    fn can_filter_participants_with_conversation_filter() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.participants_max = Some(2);
+        options
+            .query_context
+            .set_selected_chat_ids(BTreeSet::from([2, 3]));
+
+        let mut app = fake_sized_app(options);
+        app.resolve_participant_count();
+
+        assert_eq!(
+            app.options.query_context.selected_chat_ids,
+            Some(BTreeSet::from([3]))
+        );
+    }
+
+    #[test]
+    fn can_skip_participant_filter() {
+        let options = Options::fake_options(ExportType::Html);
+
+        let mut app = fake_sized_app(options);
+        app.resolve_participant_count();
+
+        assert_eq!(app.options.query_context.selected_chat_ids, None);
+    }
+
+    #[test]
+    fn can_describe_no_filters() {
+        let options = Options::fake_options(ExportType::Html);
+        let app = Config::fake_app(options);
+
+        assert!(app.active_filters().is_empty());
+    }
+
+    #[test]
+    fn can_describe_participant_filter() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.participants_min = Some(3);
+        let app = Config::fake_app(options);
+
+        assert_eq!(app.active_filters(), vec!["Participants: at least 3"]);
+    }
+
+    #[test]
+    fn can_describe_filters() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.query_context.set_start("2020-01-01").unwrap();
+        options.query_context.set_end("2020-02-01").unwrap();
+        options.conversation_filter = Some(String::from("Person 10"));
+        let app = Config::fake_app(options);
+
+        assert_eq!(
+            app.active_filters(),
+            vec![
+                "Start date: Jan 01, 2020 12:00:00 AM",
+                "End date: Feb 01, 2020 12:00:00 AM",
+                "Conversation filter: Person 10",
+            ]
+        );
+    }
+
+    #[test]
+    fn can_describe_export_filters() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.no_orphaned = true;
+        options.min_text_length = Some(5);
+        let app = Config::fake_app(options);
+
+        assert!(app.has_export_filters());
+        assert_eq!(
+            app.active_filters(),
+            vec![
+                "Messages without a conversation are dropped",
+                "Minimum text length: 5",
+            ]
+        );
+    }
+
+    #[test]
+    fn can_find_exportable_messages() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.db_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+        options.no_orphaned = true;
+        let app = Config::new(options).unwrap();
+
+        assert!(app.has_exportable_messages().unwrap());
+    }
+
+    #[test]
+    fn cant_find_exportable_messages_all_filtered() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.db_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+        options.min_text_length = Some(10_000);
+        let app = Config::new(options).unwrap();
+
+        assert!(!app.has_exportable_messages().unwrap());
+    }
+}
+
+#[cfg(test)]
+mod encoding_tests {
+    use std::{
+        env::temp_dir,
+        fs::{read, remove_file},
+        io::Write,
+    };
+
+    use crate::{
+        app::{encoding::OutputEncoding, export_type::ExportType},
+        Config, Options,
+    };
+
+    #[test]
+    fn can_write_encoded_file() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.output_encoding = OutputEncoding::Utf16Le;
+        let app = Config::fake_app(options);
+
+        let path = temp_dir().join("imessage-encoded-file.txt");
+        let _ = remove_file(&path);
+
+        // The byte order mark is only written when the file is created
+        for _ in 0..2 {
+            let mut file = app.open_export_file(&path).unwrap();
+            file.write_all("é".as_bytes()).unwrap();
+            file.flush().unwrap();
+        }
+
+        assert_eq!(read(&path).unwrap(), [0xFF, 0xFE, 0xE9, 0x00, 0xE9, 0x00]);
+        remove_file(&path).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod database_tests {
+    use std::{
+        env::temp_dir,
+        fs::{remove_file, write},
+        path::PathBuf,
+    };
+
+    use serde_json::json;
+
+    use imessage_database::{error::table::TableError, util::platform::Platform};
+
+    use crate::{
+        app::{
+            error::RuntimeError,
+            export_type::ExportType,
+            runtime::{classify_database_error, copy_database_with_free_space, snapshot_dir},
+        },
+        Config, Options,
+    };
+
+    #[test]
+    fn cant_open_encrypted_database() {
+        let path = temp_dir().join("imessage-encrypted-config.db");
+        write(&path, [0x5Au8; 4096]).unwrap();
+
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.platform = Platform::macOS;
+        options.db_path = path.clone();
+
+        assert!(matches!(
+            Config::new(options),
+            Err(RuntimeError::EncryptedDatabase(encrypted)) if encrypted == path
+        ));
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn cant_copy_database_without_space() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+
+        assert!(matches!(
+            copy_database_with_free_space(&path, 1024),
+            Err(RuntimeError::NotEnoughSpaceForCopy(size, 1024)) if size > 1024
+        ));
+    }
+
+    #[test]
+    fn can_remove_copy_when_config_fails() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.db_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+        options.copy_db = true;
+        // The database is copied before the contact cards are read
+        options.vcard = Some(temp_dir().join("imessage-missing-contacts.vcf"));
+
+        assert!(Config::new(options).is_err());
+        assert!(!snapshot_dir().exists());
+    }
+
+    #[test]
+    fn can_classify_not_a_database() {
+        let why = TableError::Schema(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(26),
+            Some("file is not a database".to_string()),
+        ));
+        let path = temp_dir().join("chat.db");
+
+        assert!(matches!(
+            classify_database_error(why, &path),
+            RuntimeError::EncryptedDatabase(_)
+        ));
+    }
+
+    #[test]
+    fn cant_classify_other_errors() {
+        let why = TableError::CannotConnect("Database not found".to_string());
+        let path = temp_dir().join("chat.db");
+
+        assert!(matches!(
+            classify_database_error(why, &path),
+            RuntimeError::DatabaseError(_)
+        ));
+    }
+
+    #[test]
+    fn can_collect_diagnostic_json() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.db_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+        let app = Config::new(options).unwrap();
+
+        let actual = app.diagnostic_json().unwrap();
+        assert_eq!(actual["messages"]["total"], json!(10));
+        assert_eq!(actual["messages"]["without_chat"], json!(1));
+        assert_eq!(actual["threads"]["chats_with_no_handles"], json!(0));
+        assert_eq!(actual["database"]["schema"], json!(app.schema.to_string()));
+        assert!(actual["database"]["size_bytes"].as_u64().unwrap() > 0);
+        assert!(actual["attachments"]["total"].is_u64());
+    }
+}
+
+#[cfg(test)]
+mod length_filter_tests {
+    use crate::{app::export_type::ExportType, Config, Options};
+
+    #[test]
+    fn can_keep_all_messages_without_filter() {
+        let options = Options::fake_options(ExportType::Txt);
+        let app = Config::fake_app(options);
+
+        let mut message = Config::fake_message();
+        message.text = Some("k".to_string());
+        assert!(!app.is_too_short(&message));
+    }
+
+    #[test]
+    fn can_skip_short_message() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.min_text_length = Some(3);
+        let app = Config::fake_app(options);
+
+        let mut message = Config::fake_message();
+        message.text = Some(" ok ".to_string());
+        assert!(app.is_too_short(&message));
+
+        message.text = Some("yes".to_string());
+        assert!(!app.is_too_short(&message));
+    }
+
+    #[test]
+    fn can_count_text_next_to_attachment() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.min_text_length = Some(3);
+        let app = Config::fake_app(options);
+
+        let mut message = Config::fake_message();
+        message.text = Some("\u{FFFC}ok".to_string());
+        message.num_attachments = 1;
+        assert!(app.is_too_short(&message));
+    }
+
+    #[test]
+    fn can_keep_attachment_only_message() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.min_text_length = Some(3);
+        let app = Config::fake_app(options);
+
+        let mut message = Config::fake_message();
+        message.text = Some("\u{FFFC}".to_string());
+        message.num_attachments = 1;
+        assert!(!app.is_too_short(&message));
+    }
+
+    #[test]
+    fn can_drop_attachment_only_message() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.drop_attachment_only = true;
+        let app = Config::fake_app(options);
+
+        let mut message = Config::fake_message();
+        message.text = Some("\u{FFFC}".to_string());
+        message.num_attachments = 1;
+        assert!(app.is_too_short(&message));
+    }
+
+    #[test]
+    fn can_keep_announcement() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.min_text_length = Some(3);
+        let app = Config::fake_app(options);
+
+        let mut message = Config::fake_message();
+        message.group_title = Some("Group".to_string());
+        assert!(!app.is_too_short(&message));
+    }
+}
+
+#[cfg(test)]
+mod timezone_tests {
+    use chrono::{FixedOffset, TimeZone, Utc};
+
+    use crate::{app::export_type::ExportType, Config, Options};
+
+    #[test]
+    fn can_format_date_in_timezone() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.timezone = FixedOffset::west_opt(8 * 3600);
+        let app = Config::fake_app(options);
+
+        let date = Utc.with_ymd_and_hms(2022, 5, 18, 3, 29, 42).unwrap();
+        assert_eq!(
+            app.format_date_as(&Ok(date.into()), "%Y-%m-%d %H:%M %z"),
+            "2022-05-17 19:29 -0800"
+        );
+    }
+}
+
+#[cfg(test)]
+mod error_policy_tests {
+    use std::{
+        env::temp_dir,
+        fs::{read_dir, remove_dir_all, remove_file},
+        path::{Path, PathBuf},
+    };
+
+    use crate::{
+        app::{
+            error::RuntimeError, error_policy::ErrorPolicy, export_type::ExportType,
+            zip::archive_path,
+        },
+        Config, Options,
+    };
+
+    fn fixture_options(export_path: &Path, error_policy: ErrorPolicy) -> Options {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.db_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+        options.export_path = export_path.to_path_buf();
+        options.ignore_disk_space = true;
+        options.error_policy = error_policy;
+        options
+    }
+
+    #[test]
+    fn can_fail_fast() {
+        let export_path = temp_dir().join("imessage-error-policy-fail-fast");
+        let _ = remove_dir_all(&export_path);
+        let app = Config::new(fixture_options(&export_path, ErrorPolicy::FailFast)).unwrap();
+
+        // The error is returned to the caller instead of being counted
+        let handled = app.handle_error(RuntimeError::InvalidOptions("fake".to_string()));
+        assert!(matches!(handled, Err(RuntimeError::InvalidOptions(_))));
+        assert_eq!(app.errors.get(), 0);
+
+        // Errors recorded elsewhere stop the export at the next check
+        app.record_error("fake");
+        assert!(matches!(
+            app.check_errors(),
+            Err(RuntimeError::ExportErrors(1))
+        ));
+        assert!(matches!(app.start(), Err(RuntimeError::ExportErrors(1))));
+
+        let _ = remove_dir_all(&export_path);
+    }
+
+    #[test]
+    fn can_continue_best_effort() {
+        let export_path = temp_dir().join("imessage-error-policy-best-effort");
+        let _ = remove_dir_all(&export_path);
+        let app = Config::new(fixture_options(&export_path, ErrorPolicy::BestEffort)).unwrap();
+
+        // Errors are counted and the export carries on
+        assert!(app
+            .handle_error(RuntimeError::InvalidOptions("fake".to_string()))
+            .is_ok());
+        assert!(app
+            .handle_error(RuntimeError::InvalidOptions("fake".to_string()))
+            .is_ok());
+        assert!(app.check_errors().is_ok());
+
+        assert!(matches!(app.start(), Err(RuntimeError::ExportErrors(2))));
+        assert!(read_dir(&export_path).unwrap().count() > 0);
+
+        remove_dir_all(&export_path).unwrap();
+    }
+
+    #[test]
+    fn can_zip_best_effort_with_errors() {
+        let export_path = temp_dir().join("imessage-error-policy-zip");
+        let archive = archive_path(&export_path);
+        let _ = remove_dir_all(&export_path);
+        let _ = remove_file(&archive);
+        let mut options = fixture_options(&export_path, ErrorPolicy::BestEffort);
+        options.zip = true;
+        let app = Config::new(options).unwrap();
+
+        assert!(app
+            .handle_error(RuntimeError::InvalidOptions("fake".to_string()))
+            .is_ok());
+
+        // The archive is still written before the errors are reported
+        assert!(matches!(app.start(), Err(RuntimeError::ExportErrors(1))));
+        assert!(archive.exists());
+        assert!(!export_path.exists());
+
+        remove_file(&archive).unwrap();
+    }
 }
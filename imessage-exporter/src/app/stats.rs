@@ -0,0 +1,184 @@
+/*!
+ Contains the per-sender statistics written to `conversations.json` when `--stats` is enabled.
+*/
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use imessage_database::tables::messages::Message;
+
+/// Characters in message text that mark where attachments and apps are rendered, which are not counted
+const REPLACEMENT_CHARS: [char; 2] = ['\u{FFFC}', '\u{FFFD}'];
+/// Joins the characters on either side of it into a single emoji
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+/// How much one participant wrote in a conversation
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SenderStats {
+    /// The number of messages sent, not counting tapbacks
+    pub messages: usize,
+    /// The number of words in the text of those messages
+    pub words: usize,
+    /// The number of characters in the text of those messages, where each emoji counts as one
+    pub characters: usize,
+}
+
+/// The statistics for each sender in each conversation, accumulated as messages are exported
+#[derive(Debug, Default)]
+pub struct MessageStats {
+    /// Map of chat ID to the statistics of each sender, by name
+    stats: HashMap<i32, BTreeMap<String, SenderStats>>,
+}
+
+impl MessageStats {
+    /// Count a message `sender` sent in `chat_id`, once its text is generated
+    pub fn add(&mut self, chat_id: i32, sender: &str, message: &Message) {
+        if message.is_tapback() {
+            return;
+        }
+        let text: String = message
+            .text
+            .as_deref()
+            .unwrap_or_default()
+            .chars()
+            .filter(|letter| !REPLACEMENT_CHARS.contains(letter))
+            .collect();
+        let text = text.trim();
+
+        let stats = self
+            .stats
+            .entry(chat_id)
+            .or_default()
+            .entry(sender.to_string())
+            .or_default();
+        stats.messages += 1;
+        stats.words += text.split_whitespace().count();
+        stats.characters += grapheme_count(text);
+    }
+
+    /// Get the statistics of each sender in a conversation
+    pub fn get(&self, chat_id: &i32) -> Option<&BTreeMap<String, SenderStats>> {
+        self.stats.get(chat_id)
+    }
+}
+
+/// Count the characters a reader would see in `text`
+///
+/// Combining marks, variation selectors, skin tones, and tag characters are counted with the character they modify,
+/// characters joined with a zero width joiner count as one, and pairs of regional indicators count as one flag.
+fn grapheme_count(text: &str) -> usize {
+    let mut count = 0;
+    let mut joined = false;
+    let mut unpaired_indicator = false;
+    for letter in text.chars() {
+        if letter == ZERO_WIDTH_JOINER {
+            joined = true;
+            continue;
+        }
+        if joined || is_extension(letter) {
+            joined = false;
+            continue;
+        }
+        if is_regional_indicator(letter) {
+            unpaired_indicator = !unpaired_indicator;
+            if !unpaired_indicator {
+                continue;
+            }
+        } else {
+            unpaired_indicator = false;
+        }
+        count += 1;
+    }
+    count
+}
+
+/// `true` if `letter` modifies the character before it instead of being drawn on its own
+fn is_extension(letter: char) -> bool {
+    matches!(letter,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{E0020}'..='\u{E007F}'
+        | '\u{E0100}'..='\u{E01EF}'
+    )
+}
+
+/// `true` if `letter` is one half of a flag emoji
+fn is_regional_indicator(letter: char) -> bool {
+    matches!(letter, '\u{1F1E6}'..='\u{1F1FF}')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        app::stats::{grapheme_count, MessageStats, SenderStats},
+        Config,
+    };
+
+    #[test]
+    fn can_count_ascii() {
+        assert_eq!(grapheme_count("Hello world"), 11);
+    }
+
+    #[test]
+    fn can_count_emoji() {
+        // Heart with a variation selector
+        assert_eq!(grapheme_count("\u{2764}\u{FE0F}"), 1);
+        // Thumbs up with a skin tone
+        assert_eq!(grapheme_count("\u{1F44D}\u{1F3FD}"), 1);
+        // Family joined with zero width joiners
+        assert_eq!(
+            grapheme_count("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"),
+            1
+        );
+        // Two flags
+        assert_eq!(grapheme_count("\u{1F1FA}\u{1F1F8}\u{1F1EF}\u{1F1F5}"), 2);
+        // An accent written as a combining mark
+        assert_eq!(grapheme_count("e\u{0301}!"), 2);
+    }
+
+    #[test]
+    fn can_add_messages() {
+        let mut stats = MessageStats::default();
+
+        let mut message = Config::fake_message();
+        message.text = Some(" Hi there \u{1F44B}\u{1F3FB} ".to_string());
+        stats.add(1, "Mom", &message);
+
+        let mut attachment = Config::fake_message();
+        attachment.text = Some("\u{FFFC}".to_string());
+        stats.add(1, "Mom", &attachment);
+
+        let mut tapback = Config::fake_message();
+        tapback.associated_message_type = Some(2000);
+        tapback.associated_message_guid = Some("fake_guid".to_string());
+        stats.add(1, "Mom", &tapback);
+
+        message.text = Some("Hello".to_string());
+        stats.add(1, "Me", &message);
+
+        let actual = stats.get(&1).unwrap();
+        assert_eq!(
+            actual["Mom"],
+            SenderStats {
+                messages: 2,
+                words: 3,
+                characters: 10
+            }
+        );
+        assert_eq!(
+            actual["Me"],
+            SenderStats {
+                messages: 1,
+                words: 1,
+                characters: 5
+            }
+        );
+        assert!(stats.get(&2).is_none());
+    }
+}
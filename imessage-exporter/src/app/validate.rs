@@ -0,0 +1,84 @@
+/*!
+ Contains routines that check every message in the source database can be read before exporting it.
+*/
+
+use rusqlite::Connection;
+
+use imessage_database::{
+    error::{message::MessageError, table::TableError},
+    tables::{
+        messages::Message,
+        table::{Table, UNKNOWN},
+    },
+    util::query_context::QueryContext,
+};
+
+/// The result of reading every message in the database
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Number of messages that were read
+    pub passed: usize,
+    /// GUIDs of the messages that cannot be read, with the reason each one failed
+    pub failed: Vec<(String, String)>,
+}
+
+/// Stream the messages selected by `context`, ensuring each row and the text of each message can be read
+///
+/// Nothing is written; each message is dropped once it is read. Messages without text, like tapbacks,
+/// pass as long as their row can be read.
+pub fn validate_messages(
+    db: &Connection,
+    context: &QueryContext,
+) -> Result<ValidationReport, TableError> {
+    let mut report = ValidationReport::default();
+
+    let mut statement = Message::stream_rows(db, context)?;
+    // Read the GUID separately, so rows that cannot be read can still be reported
+    let messages = statement
+        .query_map([], |row| {
+            Ok((row.get::<_, String>("guid"), Message::from_row(row)))
+        })
+        .map_err(TableError::Messages)?;
+
+    for row in messages {
+        let (guid, message) = row.map_err(TableError::Messages)?;
+        let mut message = match message {
+            Ok(message) => message,
+            Err(why) => {
+                let guid = guid.unwrap_or_else(|_| UNKNOWN.to_string());
+                report.failed.push((guid, why.to_string()));
+                continue;
+            }
+        };
+        match message.generate_text(db) {
+            Err(MessageError::StreamTypedParseError(why)) => {
+                report.failed.push((message.guid, why.to_string()));
+            }
+            _ => report.passed += 1,
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use imessage_database::{tables::table::get_connection, util::query_context::QueryContext};
+
+    use crate::app::validate::validate_messages;
+
+    #[test]
+    fn can_validate_fixture() {
+        let db_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+        let db = get_connection(&db_path).unwrap();
+
+        let report = validate_messages(&db, &QueryContext::default()).unwrap();
+        assert_eq!(report.passed, 10);
+        assert!(report.failed.is_empty());
+    }
+}
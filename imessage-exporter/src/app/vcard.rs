@@ -0,0 +1,300 @@
+/*!
+ Contains routines for reading contacts from vCard files.
+*/
+
+use std::{collections::HashMap, fs::read_to_string, path::Path};
+
+use log::warn;
+
+use crate::app::error::RuntimeError;
+
+/// Phone numbers are compared by their last digits so that country codes and formatting do not matter
+const PHONE_DIGITS: usize = 10;
+
+/// A contact card with a display name and the ways to reach it
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct Card {
+    /// The formatted name, from `FN`
+    formatted_name: Option<String>,
+    /// The name built from the structured `N` property, if there is no `FN`
+    name: Option<String>,
+    /// Phone numbers, as written on the card
+    pub phones: Vec<String>,
+    /// Email addresses, as written on the card
+    pub emails: Vec<String>,
+}
+
+impl Card {
+    /// The name to display for the contact, preferring the formatted name
+    pub fn display_name(&self) -> Option<&str> {
+        self.formatted_name
+            .as_deref()
+            .or(self.name.as_deref())
+            .filter(|name| !name.is_empty())
+    }
+}
+
+/// Read a map of normalized handle identifier to display name from a file containing one or more vCards
+///
+/// Cards without a name or that are not terminated are skipped with a warning.
+pub fn read_vcard(path: &Path) -> Result<HashMap<String, String>, RuntimeError> {
+    let contents = read_to_string(path).map_err(RuntimeError::DiskError)?;
+    Ok(parse_vcards(&contents))
+}
+
+/// Read the first contact from a vCard file, like a contact card shared in a conversation
+///
+/// If the file cannot be read or has no terminated card with a name, phone number, or email address, there is no contact.
+pub fn read_contact_card(path: &Path) -> Option<Card> {
+    let contents = read_to_string(path).ok()?;
+    parse_cards(&contents).into_iter().find(|card| {
+        card.display_name().is_some() || !card.phones.is_empty() || !card.emails.is_empty()
+    })
+}
+
+/// Parse the contents of a vCard file into a map of normalized handle identifier to display name
+fn parse_vcards(contents: &str) -> HashMap<String, String> {
+    let mut contacts = HashMap::new();
+
+    for (idx, card) in parse_cards(contents).iter().enumerate() {
+        match card.display_name() {
+            Some(display_name) => {
+                for identifier in card.phones.iter().chain(&card.emails) {
+                    contacts.insert(normalize_handle(identifier), display_name.to_string());
+                }
+            }
+            None => warn!("vCard {} has no name, skipping it!", idx + 1),
+        }
+    }
+
+    contacts.retain(|identifier, _| !identifier.is_empty());
+    contacts
+}
+
+/// Parse every terminated card in the contents of a vCard file
+fn parse_cards(contents: &str) -> Vec<Card> {
+    let mut cards = vec![];
+    let mut card: Option<Card> = None;
+    let mut cards_read = 0;
+
+    for line in unfold(contents) {
+        let Some((property, value)) = line.split_once(':') else {
+            continue;
+        };
+        // Drop the group and parameters, i.e. `item1.TEL;type=CELL`
+        let name = property.split(';').next().unwrap_or_default();
+        let name = name.rsplit('.').next().unwrap_or_default().to_uppercase();
+        let value = value.trim();
+
+        match (name.as_str(), card.as_mut()) {
+            ("BEGIN", _) if value.eq_ignore_ascii_case("VCARD") => {
+                cards_read += 1;
+                if card.replace(Card::default()).is_some() {
+                    warn!("vCard {} was not terminated, skipping it!", cards_read - 1);
+                }
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VCARD") => {
+                cards.extend(card.take());
+            }
+            ("FN", Some(card)) => card.formatted_name = Some(unescape(value)),
+            ("N", Some(card)) => card.name = Some(structured_name(value)),
+            ("TEL", Some(card)) => card
+                .phones
+                .push(value.trim_start_matches("tel:").to_string()),
+            ("EMAIL", Some(card)) => card.emails.push(value.to_string()),
+            _ => {}
+        }
+    }
+
+    if card.is_some() {
+        warn!("vCard {cards_read} was not terminated, skipping it!");
+    }
+
+    cards
+}
+
+/// Join lines that were folded onto the next line, which start with a space or tab
+fn unfold(contents: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in contents.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Remove vCard escapes from a text value
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", " ")
+        .replace("\\N", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+/// Build a name from the `N` property, which is in the format `family;given;additional;prefix;suffix`
+fn structured_name(value: &str) -> String {
+    let parts: Vec<String> = value.split(';').map(unescape).collect();
+    [parts.get(1), parts.first()]
+        .into_iter()
+        .flatten()
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Normalize a handle identifier so that different spellings of the same handle match
+///
+/// - Email addresses are lowercased
+/// - Phone numbers keep only their last [`PHONE_DIGITS`] digits, i.e. `+1 (555) 867-5309` becomes `5558675309`
+pub fn normalize_handle(identifier: &str) -> String {
+    let identifier = identifier.trim();
+    if identifier.contains('@') {
+        return identifier.to_lowercase();
+    }
+    let digits: Vec<char> = identifier.chars().filter(char::is_ascii_digit).collect();
+    digits[digits.len().saturating_sub(PHONE_DIGITS)..]
+        .iter()
+        .collect()
+}
+
+/// Match contacts read from a vCard file to participant IDs
+///
+/// Participants that share a contact card have their identifiers joined by a space,
+/// so a contact matches if any part of the identifier matches.
+pub fn resolve_vcard(
+    contacts: &HashMap<String, String>,
+    participants: &HashMap<i32, String>,
+) -> HashMap<i32, String> {
+    participants
+        .iter()
+        .filter_map(|(handle_id, identifier)| {
+            identifier
+                .split(' ')
+                .find_map(|part| contacts.get(&normalize_handle(part)))
+                .map(|name| (*handle_id, name.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        env::temp_dir,
+        fs::{remove_file, write},
+    };
+
+    use crate::app::vcard::{
+        normalize_handle, parse_vcards, read_contact_card, resolve_vcard, Card,
+    };
+
+    #[test]
+    fn can_normalize_handle() {
+        assert_eq!(normalize_handle("+1 (555) 867-5309"), "5558675309");
+        assert_eq!(normalize_handle("555.867.5309"), "5558675309");
+        assert_eq!(normalize_handle("867-5309"), "8675309");
+        assert_eq!(normalize_handle(" Steve@Apple.com "), "steve@apple.com");
+    }
+
+    #[test]
+    fn can_parse_vcards() {
+        let contents = "BEGIN:VCARD\r\n\
+                        VERSION:3.0\r\n\
+                        N:Smith;Jane;;;\r\n\
+                        FN:Grandma\r\n\
+                        TEL;type=CELL;type=VOICE:+1 (555) 867-5309\r\n\
+                        item1.EMAIL;type=INTERNET:Grandma@example.com\r\n\
+                        END:VCARD\r\n\
+                        BEGIN:VCARD\r\n\
+                        VERSION:4.0\r\n\
+                        N:Appleseed;Johnny;;;\r\n\
+                        TEL;VALUE=uri:tel:+1-555-123-\r\n \
+                        4567\r\n\
+                        END:VCARD\r\n";
+
+        let actual = parse_vcards(contents);
+        let expected = HashMap::from([
+            ("5558675309".to_string(), "Grandma".to_string()),
+            ("grandma@example.com".to_string(), "Grandma".to_string()),
+            ("5551234567".to_string(), "Johnny Appleseed".to_string()),
+        ]);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_skip_malformed_vcards() {
+        let contents = "BEGIN:VCARD\n\
+                        TEL:5550000000\n\
+                        END:VCARD\n\
+                        BEGIN:VCARD\n\
+                        FN:Unterminated\n\
+                        TEL:5551111111\n\
+                        BEGIN:VCARD\n\
+                        FN:Steve\n\
+                        EMAIL:steve@apple.com\n\
+                        END:VCARD\n";
+
+        let actual = parse_vcards(contents);
+        let expected = HashMap::from([("steve@apple.com".to_string(), "Steve".to_string())]);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_resolve_vcard() {
+        let contacts = HashMap::from([
+            ("5558675309".to_string(), "Grandma".to_string()),
+            ("steve@apple.com".to_string(), "Steve".to_string()),
+        ]);
+        let participants = HashMap::from([
+            (1, "+15558675309".to_string()),
+            // Contacts that share a contact card
+            (2, "+15551234567 Steve@apple.com".to_string()),
+            (3, "+15559876543".to_string()),
+        ]);
+
+        let actual = resolve_vcard(&contacts, &participants);
+        let expected = HashMap::from([(1, "Grandma".to_string()), (2, "Steve".to_string())]);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_read_contact_card() {
+        let path = temp_dir().join("imessage-contact-card.vcf");
+        write(
+            &path,
+            "BEGIN:VCARD\n\
+             N:Appleseed;Johnny;;;\n\
+             TEL;type=CELL:+1 (555) 123-4567\n\
+             EMAIL:johnny@example.com\n\
+             END:VCARD\n",
+        )
+        .unwrap();
+
+        let actual = read_contact_card(&path).unwrap();
+        remove_file(&path).unwrap();
+        assert_eq!(actual.display_name(), Some("Johnny Appleseed"));
+        assert_eq!(actual.phones, ["+1 (555) 123-4567"]);
+        assert_eq!(actual.emails, ["johnny@example.com"]);
+    }
+
+    #[test]
+    fn cant_read_malformed_contact_card() {
+        let path = temp_dir().join("imessage-contact-card-malformed.vcf");
+        write(&path, "BEGIN:VCARD\nFN:Unterminated\n").unwrap();
+
+        let actual: Option<Card> = read_contact_card(&path);
+        remove_file(&path).unwrap();
+        assert_eq!(actual, None);
+    }
+
+    #[test]
+    fn cant_read_missing_contact_card() {
+        let path = temp_dir().join("imessage-contact-card-missing.vcf");
+        assert_eq!(read_contact_card(&path), None);
+    }
+}
@@ -0,0 +1,123 @@
+/*!
+ Contains routines that check the files an export wrote can be read back.
+*/
+
+use std::{
+    fs::{read_dir, read_to_string},
+    path::{Path, PathBuf},
+};
+
+use imessage_database::tables::table::ATTACHMENTS_DIR;
+
+use crate::app::{error::RuntimeError, export_type::ExportType};
+
+/// The result of verifying the files an export wrote
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Number of files that are valid
+    pub passed: usize,
+    /// Files that are not valid, with the reason each one failed
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// Re-read each file of `export_type` in `export_path` and ensure it is valid
+///
+/// Copied attachments are not checked. Only `json` exports are verified, so other
+/// export types always pass.
+pub fn verify_export(
+    export_path: &Path,
+    export_type: &ExportType,
+) -> Result<VerifyReport, RuntimeError> {
+    let mut report = VerifyReport::default();
+    let verify: fn(&str) -> Result<(), String> = match export_type {
+        ExportType::Json => verify_json,
+        _ => return Ok(report),
+    };
+
+    let mut files = vec![];
+    find_files(export_path, &export_type.to_string(), &mut files)?;
+    files.sort();
+
+    for path in files {
+        match read_to_string(&path)
+            .map_err(|why| why.to_string())
+            .and_then(|contents| verify(&contents))
+        {
+            Ok(()) => report.passed += 1,
+            Err(why) => report.failed.push((path, why)),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Collect the files in `dir` and its subdirectories that have the given extension
+fn find_files(dir: &Path, extension: &str, files: &mut Vec<PathBuf>) -> Result<(), RuntimeError> {
+    for entry in read_dir(dir).map_err(RuntimeError::DiskError)? {
+        let path = entry.map_err(RuntimeError::DiskError)?.path();
+        if path.is_dir() {
+            if path.file_name().is_some_and(|name| name != ATTACHMENTS_DIR) {
+                find_files(&path, extension, files)?;
+            }
+        } else if path.extension().is_some_and(|ext| ext == extension) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Ensure a file contains exactly one JSON document, which catches truncated or appended writes
+fn verify_json(contents: &str) -> Result<(), String> {
+    serde_json::from_str::<serde_json::Value>(contents)
+        .map(|_| ())
+        .map_err(|why| why.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, write},
+    };
+
+    use crate::app::{
+        export_type::ExportType,
+        verify::{verify_export, verify_json},
+    };
+
+    #[test]
+    fn can_verify_json() {
+        assert!(verify_json("[{\"guid\":\"a\"}]\n").is_ok());
+    }
+
+    #[test]
+    fn cant_verify_truncated_json() {
+        assert!(verify_json("[{\"guid\":\"a\"},{\"gu").is_err());
+    }
+
+    #[test]
+    fn cant_verify_appended_json() {
+        assert!(verify_json("[{\"guid\":\"a\"}]\n[{\"guid\":\"b\"}]\n").is_err());
+    }
+
+    #[test]
+    fn can_verify_export() {
+        let dir = temp_dir().join("imessage-verify-export");
+        create_dir_all(dir.join("Family - 0")).unwrap();
+        create_dir_all(dir.join("attachments")).unwrap();
+
+        write(dir.join("Grandma.json"), "[]\n").unwrap();
+        write(dir.join("Family - 0").join("Family - 0.json"), "[{}]\n").unwrap();
+        write(dir.join("Broken.json"), "[{}").unwrap();
+        // Attachments and other files are not checked
+        write(dir.join("attachments").join("1.json"), "{").unwrap();
+        write(dir.join("notes.txt"), "{").unwrap();
+
+        let report = verify_export(&dir, &ExportType::Json).unwrap();
+        assert_eq!(report.passed, 2);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, dir.join("Broken.json"));
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,474 @@
+/*!
+ Contains a streaming `zip` writer used to pack an export into a single archive.
+
+ Entries are stored without compression and written as described by the
+ [`.ZIP` File Format Specification](https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT). Each entry's
+ checksum and size follow its data in a data descriptor, so entries are written in one pass. Archives larger
+ than 4 GiB, or with more than 65,535 entries, use the `ZIP64` extensions.
+*/
+
+use std::{
+    fs::{read_dir, File},
+    io::{self, copy, BufWriter, Read, Write},
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Datelike, Local, Timelike};
+
+use crate::app::{error::RuntimeError, gzip::crc32};
+
+/// Extension of the archive an export is packed into
+pub const ZIP_EXTENSION: &str = "zip";
+
+/// Signature that starts each entry's local header
+const LOCAL_HEADER: u32 = 0x0403_4B50;
+/// Signature that starts each entry's data descriptor
+const DATA_DESCRIPTOR: u32 = 0x0807_4B50;
+/// Signature that starts each entry's header in the central directory
+const CENTRAL_HEADER: u32 = 0x0201_4B50;
+/// Signature that starts the `ZIP64` end of central directory record
+const ZIP64_END: u32 = 0x0606_4B50;
+/// Signature that starts the `ZIP64` end of central directory locator
+const ZIP64_LOCATOR: u32 = 0x0706_4B50;
+/// Signature that starts the end of central directory record
+const END: u32 = 0x0605_4B50;
+/// ID of the extra field that holds `ZIP64` sizes and offsets
+const ZIP64_EXTRA: u16 = 0x0001;
+
+/// Version 2.0, which added folders and data descriptors
+const VERSION: u16 = 20;
+/// Version 4.5, which added `ZIP64`
+const VERSION_ZIP64: u16 = 45;
+/// Version made by, where the high byte `3` marks Unix file attributes
+const VERSION_MADE_BY: u16 = 3 << 8 | VERSION_ZIP64;
+/// Flags for each entry: bit 3 for the data descriptor and bit 11 for UTF-8 names
+const FLAGS: u16 = 1 << 3 | 1 << 11;
+/// Unix permissions of each entry, a regular file readable by everyone
+const FILE_MODE: u32 = 0o100_644;
+/// Largest value that fits in a 32 bit field; larger values are stored in the `ZIP64` extra field
+const MAX_U32: u64 = 0xFFFF_FFFF;
+/// Largest number of entries that fits in the end of central directory record
+const MAX_ENTRIES: usize = 0xFFFF;
+
+/// An entry that has been written, kept until the central directory is written
+struct Entry {
+    name: String,
+    crc: u32,
+    size: u64,
+    offset: u64,
+    time: u16,
+    date: u16,
+}
+
+impl Entry {
+    /// `true` if the entry's size or offset does not fit in the 32 bit fields of the central directory
+    fn is_zip64(&self) -> bool {
+        self.size >= MAX_U32 || self.offset >= MAX_U32
+    }
+}
+
+/// Counts the bytes written through it, so offsets in the archive are known without seeking
+struct CountingWriter<W: Write> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Computes the CRC-32 of everything read through it
+struct CrcReader<R: Read> {
+    inner: R,
+    crc: u32,
+}
+
+impl<R: Read> Read for CrcReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.crc = crc32(self.crc, &buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Writes entries into a `zip` archive one after another
+///
+/// The central directory is written by [`ZipWriter::finish()`]; an archive that is not finished cannot be read.
+pub struct ZipWriter<W: Write> {
+    inner: CountingWriter<W>,
+    entries: Vec<Entry>,
+}
+
+impl<W: Write> ZipWriter<W> {
+    /// Start a new archive
+    pub fn new(inner: W) -> Self {
+        ZipWriter {
+            inner: CountingWriter { inner, written: 0 },
+            entries: vec![],
+        }
+    }
+
+    /// Write an entry named `name` with the contents of `reader`, which must be `size` bytes long
+    pub fn add(
+        &mut self,
+        name: &str,
+        reader: impl Read,
+        size: u64,
+        modified: SystemTime,
+    ) -> io::Result<()> {
+        let (time, date) = dos_date_time(&DateTime::<Local>::from(modified));
+        let offset = self.inner.written;
+        let zip64 = size >= MAX_U32;
+
+        // The checksum and sizes are not known until the data is written, so they are left empty here
+        let extra: Vec<u8> = if zip64 {
+            [
+                ZIP64_EXTRA.to_le_bytes().as_slice(),
+                &16_u16.to_le_bytes(),
+                &[0; 16],
+            ]
+            .concat()
+        } else {
+            vec![]
+        };
+        let out = &mut self.inner;
+        out.write_all(&LOCAL_HEADER.to_le_bytes())?;
+        out.write_all(&(if zip64 { VERSION_ZIP64 } else { VERSION }).to_le_bytes())?;
+        out.write_all(&FLAGS.to_le_bytes())?;
+        // Stored without compression
+        out.write_all(&0_u16.to_le_bytes())?;
+        out.write_all(&time.to_le_bytes())?;
+        out.write_all(&date.to_le_bytes())?;
+        out.write_all(&0_u32.to_le_bytes())?;
+        let placeholder = if zip64 { MAX_U32 as u32 } else { 0 };
+        out.write_all(&placeholder.to_le_bytes())?;
+        out.write_all(&placeholder.to_le_bytes())?;
+        out.write_all(&(name.len() as u16).to_le_bytes())?;
+        out.write_all(&(extra.len() as u16).to_le_bytes())?;
+        out.write_all(name.as_bytes())?;
+        out.write_all(&extra)?;
+
+        let mut reader = CrcReader {
+            inner: reader,
+            crc: 0,
+        };
+        let written = copy(&mut reader, out)?;
+        if written != size {
+            return Err(io::Error::other(format!(
+                "{name} changed while it was archived: expected {size} bytes, read {written}"
+            )));
+        }
+
+        out.write_all(&DATA_DESCRIPTOR.to_le_bytes())?;
+        out.write_all(&reader.crc.to_le_bytes())?;
+        if zip64 {
+            out.write_all(&size.to_le_bytes())?;
+            out.write_all(&size.to_le_bytes())?;
+        } else {
+            out.write_all(&(size as u32).to_le_bytes())?;
+            out.write_all(&(size as u32).to_le_bytes())?;
+        }
+
+        self.entries.push(Entry {
+            name: name.to_string(),
+            crc: reader.crc,
+            size,
+            offset,
+            time,
+            date,
+        });
+        Ok(())
+    }
+
+    /// Write the central directory, returning the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        let start = self.inner.written;
+        let out = &mut self.inner;
+        for entry in &self.entries {
+            // Only the fields that do not fit in 32 bits are stored in the extra field, in this order
+            let mut extra = vec![];
+            if entry.size >= MAX_U32 {
+                extra.extend_from_slice(&entry.size.to_le_bytes());
+                extra.extend_from_slice(&entry.size.to_le_bytes());
+            }
+            if entry.offset >= MAX_U32 {
+                extra.extend_from_slice(&entry.offset.to_le_bytes());
+            }
+            if !extra.is_empty() {
+                extra.splice(
+                    0..0,
+                    [
+                        ZIP64_EXTRA.to_le_bytes(),
+                        (extra.len() as u16).to_le_bytes(),
+                    ]
+                    .concat(),
+                );
+            }
+
+            out.write_all(&CENTRAL_HEADER.to_le_bytes())?;
+            out.write_all(&VERSION_MADE_BY.to_le_bytes())?;
+            out.write_all(
+                &(if entry.is_zip64() {
+                    VERSION_ZIP64
+                } else {
+                    VERSION
+                })
+                .to_le_bytes(),
+            )?;
+            out.write_all(&FLAGS.to_le_bytes())?;
+            out.write_all(&0_u16.to_le_bytes())?;
+            out.write_all(&entry.time.to_le_bytes())?;
+            out.write_all(&entry.date.to_le_bytes())?;
+            out.write_all(&entry.crc.to_le_bytes())?;
+            out.write_all(&(entry.size.min(MAX_U32) as u32).to_le_bytes())?;
+            out.write_all(&(entry.size.min(MAX_U32) as u32).to_le_bytes())?;
+            out.write_all(&(entry.name.len() as u16).to_le_bytes())?;
+            out.write_all(&(extra.len() as u16).to_le_bytes())?;
+            // Comment length, disk number, and internal attributes
+            out.write_all(&[0; 6])?;
+            out.write_all(&(FILE_MODE << 16).to_le_bytes())?;
+            out.write_all(&(entry.offset.min(MAX_U32) as u32).to_le_bytes())?;
+            out.write_all(entry.name.as_bytes())?;
+            out.write_all(&extra)?;
+        }
+        let end = self.inner.written;
+        let size = end - start;
+        let count = self.entries.len();
+
+        let out = &mut self.inner;
+        if count >= MAX_ENTRIES || start >= MAX_U32 || size >= MAX_U32 {
+            out.write_all(&ZIP64_END.to_le_bytes())?;
+            // Size of the rest of the record
+            out.write_all(&44_u64.to_le_bytes())?;
+            out.write_all(&VERSION_MADE_BY.to_le_bytes())?;
+            out.write_all(&VERSION_ZIP64.to_le_bytes())?;
+            // This disk and the disk the central directory starts on
+            out.write_all(&[0; 8])?;
+            out.write_all(&(count as u64).to_le_bytes())?;
+            out.write_all(&(count as u64).to_le_bytes())?;
+            out.write_all(&size.to_le_bytes())?;
+            out.write_all(&start.to_le_bytes())?;
+
+            out.write_all(&ZIP64_LOCATOR.to_le_bytes())?;
+            out.write_all(&0_u32.to_le_bytes())?;
+            out.write_all(&end.to_le_bytes())?;
+            // Total number of disks
+            out.write_all(&1_u32.to_le_bytes())?;
+        }
+
+        out.write_all(&END.to_le_bytes())?;
+        // This disk and the disk the central directory starts on
+        out.write_all(&[0; 4])?;
+        out.write_all(&(count.min(MAX_ENTRIES) as u16).to_le_bytes())?;
+        out.write_all(&(count.min(MAX_ENTRIES) as u16).to_le_bytes())?;
+        out.write_all(&(size.min(MAX_U32) as u32).to_le_bytes())?;
+        out.write_all(&(start.min(MAX_U32) as u32).to_le_bytes())?;
+        // Comment length
+        out.write_all(&0_u16.to_le_bytes())?;
+        out.flush()?;
+
+        Ok(self.inner.inner)
+    }
+}
+
+/// Convert a date to the MS-DOS time and date stored in `zip` headers, which start in 1980 and count seconds in twos
+fn dos_date_time(date: &DateTime<Local>) -> (u16, u16) {
+    if date.year() < 1980 {
+        // Midnight on January 1, 1980
+        return (0, 1 << 5 | 1);
+    }
+    let time = (date.hour() << 11 | date.minute() << 5 | (date.second() / 2)) as u16;
+    let day = ((date.year() - 1980).min(127) as u32) << 9 | date.month() << 5 | date.day();
+    (time, day as u16)
+}
+
+/// Build the name of an entry from its path relative to the root of the archive, separating folders with `/`
+fn entry_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Get the path of the archive an export is packed into, which is the export path with [`ZIP_EXTENSION`] appended
+pub fn archive_path(export_path: &Path) -> PathBuf {
+    let mut path = export_path.as_os_str().to_owned();
+    path.push(".");
+    path.push(ZIP_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Pack every file in `dir` and its subdirectories into a new archive at `archive`
+pub fn zip_dir(dir: &Path, archive: &Path) -> Result<(), RuntimeError> {
+    let file = File::create(archive)
+        .map_err(|err| RuntimeError::CreateError(err, archive.to_path_buf()))?;
+    let mut writer = ZipWriter::new(BufWriter::new(file));
+    add_dir(&mut writer, dir, dir)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Add the files in `dir` to an archive in a stable order, named by their path relative to `root`
+fn add_dir<W: Write>(
+    writer: &mut ZipWriter<W>,
+    root: &Path,
+    dir: &Path,
+) -> Result<(), RuntimeError> {
+    let mut paths = read_dir(dir)
+        .map_err(RuntimeError::DiskError)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()
+        .map_err(RuntimeError::DiskError)?;
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            add_dir(writer, root, &path)?;
+            continue;
+        }
+        let file = File::open(&path).map_err(|err| RuntimeError::CreateError(err, path.clone()))?;
+        let metadata = file.metadata().map_err(RuntimeError::DiskError)?;
+        let name = entry_name(path.strip_prefix(root).unwrap_or(&path));
+        writer.add(
+            &name,
+            file,
+            metadata.len(),
+            metadata.modified().unwrap_or_else(|_| SystemTime::now()),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read, remove_dir_all, write},
+        path::Path,
+        process::Command,
+        time::SystemTime,
+    };
+
+    use chrono::{Local, TimeZone};
+
+    use crate::app::zip::{archive_path, dos_date_time, entry_name, zip_dir, ZipWriter};
+
+    #[test]
+    fn can_convert_dos_date_time() {
+        let date = Local.with_ymd_and_hms(2022, 5, 17, 17, 29, 43).unwrap();
+        let (time, day) = dos_date_time(&date);
+        assert_eq!(time, 17 << 11 | 29 << 5 | 21);
+        assert_eq!(day, 42 << 9 | 5 << 5 | 17);
+    }
+
+    #[test]
+    fn can_clamp_dos_date_time() {
+        let date = Local.with_ymd_and_hms(1970, 1, 1, 12, 0, 0).unwrap();
+        assert_eq!(dos_date_time(&date), (0, 1 << 5 | 1));
+    }
+
+    #[test]
+    fn can_build_entry_name() {
+        assert_eq!(
+            entry_name(Path::new("attachments/1/photo.jpeg")),
+            "attachments/1/photo.jpeg"
+        );
+    }
+
+    #[test]
+    fn can_get_archive_path() {
+        assert_eq!(
+            archive_path(Path::new("/Users/ReagentX/exports")),
+            Path::new("/Users/ReagentX/exports.zip")
+        );
+    }
+
+    #[test]
+    fn can_write_empty_archive() {
+        let archive = ZipWriter::new(vec![]).finish().unwrap();
+        // Only the end of central directory record
+        assert_eq!(archive.len(), 22);
+        assert_eq!(archive[..4], [0x50, 0x4B, 0x05, 0x06]);
+    }
+
+    #[test]
+    fn can_write_entry() {
+        let mut writer = ZipWriter::new(vec![]);
+        writer
+            .add("a.txt", b"hello".as_slice(), 5, SystemTime::now())
+            .unwrap();
+        let archive = writer.finish().unwrap();
+
+        // Local header, name, data, and data descriptor
+        assert_eq!(archive[..4], [0x50, 0x4B, 0x03, 0x04]);
+        assert_eq!(archive[30..35], *b"a.txt");
+        assert_eq!(archive[35..40], *b"hello");
+        assert_eq!(archive[40..44], [0x50, 0x4B, 0x07, 0x08]);
+        assert_eq!(archive[44..48], 0x3610_A686_u32.to_le_bytes());
+    }
+
+    #[test]
+    fn cant_write_entry_wrong_size() {
+        let mut writer = ZipWriter::new(vec![]);
+        assert!(writer
+            .add("a.txt", b"hello".as_slice(), 6, SystemTime::now())
+            .is_err());
+    }
+
+    #[test]
+    fn can_unzip_dir() {
+        let dir = temp_dir().join("imessage-zip-test");
+        let _ = remove_dir_all(&dir);
+        let export = dir.join("export");
+        create_dir_all(export.join("attachments").join("1")).unwrap();
+        write(export.join("Book Club - 2.txt"), "Hello\n").unwrap();
+        write(
+            export.join("attachments").join("1").join("photo.jpeg"),
+            [0xFF; 1000],
+        )
+        .unwrap();
+
+        let archive = dir.join("export.zip");
+        zip_dir(&export, &archive).unwrap();
+
+        let Ok(output) = Command::new("unzip")
+            .arg("-q")
+            .arg(&archive)
+            .arg("-d")
+            .arg(dir.join("out"))
+            .output()
+        else {
+            eprintln!("Skipping test: unzip is not installed");
+            return;
+        };
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(
+            read(dir.join("out").join("Book Club - 2.txt")).unwrap(),
+            b"Hello\n"
+        );
+        assert_eq!(
+            read(
+                dir.join("out")
+                    .join("attachments")
+                    .join("1")
+                    .join("photo.jpeg")
+            )
+            .unwrap(),
+            [0xFF; 1000]
+        );
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
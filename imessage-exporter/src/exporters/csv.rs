@@ -0,0 +1,486 @@
+use std::{
+    borrow::Cow,
+    collections::{
+        btree_map::Entry::{Occupied, Vacant},
+        BTreeMap,
+    },
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use csv::WriterBuilder;
+use log::info;
+use serde::Serialize;
+
+use crate::{
+    app::{
+        encoding::UTF8_BOM, error::RuntimeError, export_file::ExportFile,
+        options::OPTION_NO_ORPHANED, runtime::Config,
+    },
+    exporters::exporter::{
+        generate_text_or_placeholder, link_title, redact_attachments, Exporter, Previewer,
+    },
+};
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{
+        messages::Message,
+        table::{Table, ORPHANED},
+    },
+    util::dates::get_local_time,
+};
+
+/// Column names of [`CsvRecord`], in order
+const HEADER: [&str; 15] = [
+    "timestamp",
+    "conversation_id",
+    "guid",
+    "sender",
+    "receiver",
+    "is_from_me",
+    "service",
+    "subject",
+    "text",
+    "attachments",
+    "delivery_status",
+    "date_read",
+    "date_delivered",
+    "deleted",
+    "url",
+];
+
+/// A single row of a CSV export, with one column per field
+#[derive(Debug, Serialize)]
+struct CsvRecord<'a> {
+    /// When the message was sent
+    timestamp: String,
+    /// The ID of the conversation the message belongs to, if any
+    conversation_id: Option<i32>,
+    guid: &'a str,
+    sender: &'a str,
+    receiver: &'a str,
+    is_from_me: bool,
+    service: Option<&'a str>,
+    subject: Option<&'a str>,
+    /// The text of the message, which may contain commas, quotes, and newlines
+    text: Option<Cow<'a, str>>,
+    /// The number of attached files included in the message
+    attachments: i32,
+    delivery_status: String,
+    date_read: Option<String>,
+    date_delivered: Option<String>,
+    deleted: bool,
+    /// The link a message was sent with, when its preview's title is used as the text
+    ///
+    /// This is the last column, so files written before it was added keep their column positions.
+    url: Option<&'a str>,
+}
+
+pub struct CSV<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of resolved chatroom file location to a buffered writer
+    pub files: BTreeMap<String, BufWriter<ExportFile>>,
+    /// Writer instance for orphaned messages, unless they are dropped
+    pub orphaned: Option<BufWriter<ExportFile>>,
+}
+
+impl<'a> Exporter<'a> for CSV<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let orphaned = if config.options.no_orphaned {
+            None
+        } else {
+            let mut orphaned = config.conversation_dir(ORPHANED)?;
+            orphaned.push(ORPHANED);
+            orphaned.set_extension(config.file_extension("csv"));
+
+            Some(CSV::open_file(config, &orphaned)?)
+        };
+
+        Ok(CSV {
+            config,
+            files: BTreeMap::new(),
+            orphaned,
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as csv...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of messages dropped because they do not belong to a conversation
+        let mut dropped_messages = 0;
+
+        // Keep track of messages skipped because a previous export already wrote them
+        let mut skipped_messages = 0;
+
+        // Keep track of messages skipped because their text is too short
+        let mut short_messages = 0;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = self.config.progress_bar(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = match Message::extract(message) {
+                Ok(msg) => msg,
+                Err(why) => {
+                    self.config.handle_error(RuntimeError::DatabaseError(why))?;
+                    current_message += 1;
+                    continue;
+                }
+            };
+
+            // Drop messages that do not belong to a conversation, if requested
+            if self.config.options.no_orphaned && self.config.is_orphaned(&msg) {
+                dropped_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Skip messages that a previous export already wrote, if requested
+            if self.config.already_exported(&msg) {
+                skipped_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Generate the text of the message
+            generate_text_or_placeholder(&mut msg, &self.config.db);
+
+            // Skip messages that are too short, which is only known once their text is generated
+            if self.config.is_too_short(&msg) {
+                short_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Note when the message was delivered and read, and list it on its own if it is a tapback, if requested
+            self.config.record_receipt(&msg);
+            self.config.record_reaction(&msg);
+
+            let config = self.config;
+            let record = CSV::format_record(config, &msg);
+            if let Err(why) = self
+                .get_or_create_file(&msg)
+                .and_then(|file| CSV::write_record(file, &record))
+                .and_then(|_| config.mark_exported(&msg.guid))
+            {
+                self.config.handle_error(why)?;
+            }
+
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        if dropped_messages > 0 {
+            info!("Dropped {dropped_messages} messages that do not belong to a conversation");
+        }
+        if skipped_messages > 0 {
+            info!("Skipped {skipped_messages} messages that a previous export already wrote");
+        }
+        if short_messages > 0 {
+            info!("Skipped {short_messages} messages that are too short");
+        }
+        Ok(())
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<ExportFile>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let mut path = self.config.conversation_dir(entry.key())?;
+                        path.push(entry.key());
+                        path.set_extension(self.config.file_extension("csv"));
+
+                        Ok(entry.insert(CSV::open_file(self.config, &path)?))
+                    }
+                }
+            }
+            None => self
+                .orphaned
+                .as_mut()
+                .ok_or(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_NO_ORPHANED} is enabled, but message {} has no conversation!",
+                    message.rowid
+                ))),
+        }
+    }
+
+    fn supports_attachments() -> bool {
+        // Attachments are only counted
+        false
+    }
+
+    fn supports_reactions() -> bool {
+        // Tapbacks are written as rows of their own
+        false
+    }
+}
+
+impl<'a> Previewer<'a> for CSV<'a> {
+    fn preview(config: &'a Config) -> Self {
+        CSV {
+            config,
+            files: BTreeMap::new(),
+            orphaned: None,
+        }
+    }
+
+    fn format_preview(&self, msg: &Message) -> Result<Option<String>, RuntimeError> {
+        let mut writer = WriterBuilder::new().has_headers(false).from_writer(vec![]);
+        writer
+            .serialize(CSV::format_record(self.config, msg))
+            .map_err(|why| RuntimeError::DiskError(why.into()))?;
+        let row = writer
+            .into_inner()
+            .map_err(|why| RuntimeError::DiskError(why.into_error()))?;
+        Ok(Some(String::from_utf8_lossy(&row).into_owned()))
+    }
+}
+
+impl<'a> CSV<'a> {
+    /// Open a file to append rows to, writing the header if the file is new
+    ///
+    /// If multiple chats use the same name, they share a file, so the header and byte order mark are only written once
+    fn open_file(config: &Config, path: &Path) -> Result<BufWriter<ExportFile>, RuntimeError> {
+        let file_exists = path.exists();
+        let mut file = config.open_export_file(path)?;
+        if !file_exists {
+            if config.options.bom {
+                file.write_all(UTF8_BOM).map_err(RuntimeError::DiskError)?;
+            }
+            WriterBuilder::new()
+                .from_writer(&mut file)
+                .write_record(HEADER)
+                .map_err(|why| RuntimeError::DiskError(why.into()))?;
+        }
+        Ok(file)
+    }
+
+    /// Build the row for a message
+    fn format_record(config: &'a Config, message: &'a Message) -> CsvRecord<'a> {
+        let format_timestamp = |timestamp: i64| {
+            (timestamp != 0)
+                .then(|| config.format_date(&get_local_time(&timestamp, &config.offset)))
+        };
+
+        let sender = config.who(
+            message.handle_id,
+            message.is_from_me,
+            &message.destination_caller_id,
+        );
+        let receiver = if message.is_from_me {
+            config.who(None, false, &message.destination_caller_id)
+        } else {
+            config.who(message.handle_id, true, &message.destination_caller_id)
+        };
+
+        // Leave attachments out of the text and count, if requested
+        let (text, attachments) = if config.options.redact_attachments {
+            (redact_attachments(message).map(Cow::Owned), 0)
+        } else {
+            (
+                message.text.as_deref().map(Cow::Borrowed),
+                message.num_attachments,
+            )
+        };
+
+        // Messages that are only a link read better as the title of the link's preview
+        let preview_title = if config.options.redact_attachments {
+            None
+        } else {
+            link_title(message, &config.db)
+        };
+        let (text, url) = match preview_title {
+            Some(title) => (Some(Cow::Owned(title)), message.text.as_deref()),
+            None => (text, None),
+        };
+
+        CsvRecord {
+            timestamp: format_timestamp(message.date).unwrap_or_default(),
+            conversation_id: message.chat_id.or(message.deleted_from),
+            guid: &message.guid,
+            sender,
+            receiver,
+            is_from_me: message.is_from_me,
+            service: message.service.as_deref(),
+            subject: message.subject.as_deref().filter(|s| !s.is_empty()),
+            text,
+            attachments,
+            delivery_status: message.delivery_status().to_string(),
+            date_read: format_timestamp(message.date_read),
+            date_delivered: format_timestamp(message.date_delivered),
+            deleted: message.is_deleted(),
+            url,
+        }
+    }
+
+    /// Serialize a row to a file, quoting and escaping fields as needed
+    fn write_record(
+        file: &mut BufWriter<ExportFile>,
+        record: &CsvRecord,
+    ) -> Result<(), RuntimeError> {
+        WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(file)
+            .serialize(record)
+            .map_err(|why| RuntimeError::DiskError(why.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{read, remove_file},
+        io::Write,
+    };
+
+    use csv::{ReaderBuilder, WriterBuilder};
+
+    use crate::{
+        app::{encoding::UTF8_BOM, export_type::ExportType, options::Options, runtime::Config},
+        exporters::{
+            csv::{CsvRecord, CSV, HEADER},
+            exporter::{Previewer, MEDIA_REMOVED},
+        },
+    };
+
+    /// Serialize a record the same way the exporter does, including the header
+    fn serialize(record: &CsvRecord) -> String {
+        let mut writer = WriterBuilder::new().from_writer(vec![]);
+        writer.serialize(record).unwrap();
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn can_keep_header_in_sync() {
+        let options = Options::fake_options(ExportType::Csv);
+        let config = Config::fake_app(options);
+        let message = Config::fake_message();
+
+        let output = serialize(&CSV::format_record(&config, &message));
+        assert_eq!(output.lines().next().unwrap(), HEADER.join(","));
+    }
+
+    #[test]
+    fn can_escape_text() {
+        let options = Options::fake_options(ExportType::Csv);
+        let config = Config::fake_app(options);
+        let mut message = Config::fake_message();
+        message.text = Some("Hello, \"world\"\nSee you soon".to_string());
+
+        let output = serialize(&CSV::format_record(&config, &message));
+        assert!(output.contains("\"Hello, \"\"world\"\"\nSee you soon\""));
+
+        // Reading the row back gives the original text
+        let mut reader = ReaderBuilder::new().from_reader(output.as_bytes());
+        let row = reader.records().next().unwrap().unwrap();
+        assert_eq!(
+            &row[HEADER.iter().position(|c| *c == "text").unwrap()],
+            "Hello, \"world\"\nSee you soon"
+        );
+    }
+
+    #[test]
+    fn can_format_record() {
+        let options = Options::fake_options(ExportType::Csv);
+        let config = Config::fake_app(options);
+        let mut message = Config::fake_message();
+        message.is_from_me = true;
+        message.chat_id = Some(3);
+
+        let record = CSV::format_record(&config, &message);
+        assert_eq!(record.sender, "Me");
+        assert_eq!(record.conversation_id, Some(3));
+        assert_eq!(record.delivery_status, "sent");
+        assert_eq!(record.date_read, None);
+    }
+
+    #[test]
+    fn can_format_record_date_format() {
+        let mut options = Options::fake_options(ExportType::Csv);
+        options.date_format = Some("%Y/%m".to_string());
+        let config = Config::fake_app(options);
+        let mut message = Config::fake_message();
+        // May 17, 2022
+        message.date = 674526582885055488;
+
+        let record = CSV::format_record(&config, &message);
+        assert_eq!(record.timestamp, "2022/05");
+    }
+
+    #[test]
+    fn can_format_record_redacted() {
+        let mut options = Options::fake_options(ExportType::Csv);
+        options.redact_attachments = true;
+        let config = Config::fake_app(options);
+        let mut message = Config::fake_message();
+        message.text = Some("\u{FFFC}".to_string());
+        message.num_attachments = 1;
+
+        let record = CSV::format_record(&config, &message);
+        assert_eq!(record.text.as_deref(), Some(MEDIA_REMOVED));
+        assert_eq!(record.attachments, 0);
+    }
+
+    #[test]
+    fn can_format_preview_without_header() {
+        let options = Options::fake_options(ExportType::Csv);
+        let config = Config::fake_app(options);
+        let exporter = CSV::preview(&config);
+        let mut message = Config::fake_message();
+        message.text = Some("Hello, world".to_string());
+
+        let row = exporter.format_preview(&message).unwrap().unwrap();
+        assert!(!row.starts_with("timestamp"));
+        assert!(row.contains("\"Hello, world\""));
+        assert_eq!(row.lines().count(), 1);
+    }
+
+    #[test]
+    fn can_write_bom_once() {
+        let mut options = Options::fake_options(ExportType::Csv);
+        options.bom = true;
+        let config = Config::fake_app(options);
+
+        let path = temp_dir().join("imessage-csv-bom.csv");
+        let _ = remove_file(&path);
+
+        // Chats that share a name open the same file again
+        for _ in 0..2 {
+            CSV::open_file(&config, &path).unwrap().flush().unwrap();
+        }
+
+        let expected = [UTF8_BOM, format!("{}\n", HEADER.join(",")).as_bytes()].concat();
+        assert_eq!(read(&path).unwrap(), expected);
+        remove_file(&path).unwrap();
+    }
+}
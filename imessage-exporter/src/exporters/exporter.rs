@@ -1,26 +1,42 @@
-use std::{borrow::Cow, fs::File, io::BufWriter, marker::Sized};
+use std::{borrow::Cow, io::BufWriter, marker::Sized};
+
+use log::warn;
+use rusqlite::Connection;
 
 use imessage_database::{
-    error::{plist::PlistParseError, table::TableError},
+    error::{message::MessageError, plist::PlistParseError, table::TableError},
     message_types::{
         app::AppMessage,
         app_store::AppStoreMessage,
         collaboration::CollaborationMessage,
-        digital_touch::DigitalTouch,
+        digital_touch::{from_payload, kind_name, DigitalTouch},
         edited::EditedMessage,
         handwriting::HandwrittenMessage,
         music::MusicMessage,
         placemark::PlacemarkMessage,
         text_effects::{Animation, Style, TextEffect, Unit},
         url::URLMessage,
+        variants::{BalloonProvider, CustomBalloon, SystemEvent, URLOverride, Variant},
     },
     tables::{
         attachment::Attachment,
-        messages::{models::AttachmentMeta, Message},
+        messages::{
+            models::{AttachmentMeta, BubbleComponent},
+            Message,
+        },
+        schema::SchemaVersion,
     },
+    util::plist::parse_plist,
 };
 
-use crate::app::{error::RuntimeError, runtime::Config};
+use crate::app::{error::RuntimeError, export_file::ExportFile, runtime::Config};
+
+/// Written in place of a message that only contained attachments when attachments are redacted
+pub const MEDIA_REMOVED: &str = "<media removed>";
+/// Written in place of the text of a message whose body could not be read
+pub const UNREADABLE_MESSAGE: &str = "[unreadable message]";
+/// The character in message text that marks where an attachment is rendered
+const ATTACHMENT_CHAR: char = '\u{FFFC}';
 
 /// Defines behavior for iterating over messages from the iMessage database and managing export files
 pub trait Exporter<'a> {
@@ -34,7 +50,25 @@ pub trait Exporter<'a> {
     fn get_or_create_file(
         &mut self,
         message: &Message,
-    ) -> Result<&mut BufWriter<File>, RuntimeError>;
+    ) -> Result<&mut BufWriter<ExportFile>, RuntimeError>;
+    /// `true` if the export includes the files attached to messages, else `false`
+    fn supports_attachments() -> bool
+    where
+        Self: Sized;
+    /// `true` if the export shows tapbacks with the messages they react to, else `false`
+    fn supports_reactions() -> bool
+    where
+        Self: Sized;
+}
+
+/// Defines behavior for rendering messages to preview an export without writing any files
+pub trait Previewer<'a> {
+    /// Create an exporter that does not open any export files
+    fn preview(config: &'a Config) -> Self
+    where
+        Self: Sized;
+    /// Render a message as the export would write it, or `None` if the export only renders it in the context of another message
+    fn format_preview(&self, msg: &Message) -> Result<Option<String>, RuntimeError>;
 }
 
 /// Defines behavior for formatting message instances to the desired output format
@@ -63,8 +97,8 @@ pub(super) trait Writer<'a> {
     fn format_expressive(&self, msg: &'a Message) -> &'a str;
     /// Format an announcement message
     fn format_announcement(&self, msg: &'a Message) -> String;
-    /// Format a `SharePlay` message
-    fn format_shareplay(&self) -> &str;
+    /// Format a system event, i.e. a FaceTime call
+    fn format_system_event(&self, event: &SystemEvent) -> String;
     /// Format a legacy Shared Location message
     fn format_shared_location(&self, msg: &'a Message) -> &str;
     /// Format an edited message
@@ -77,7 +111,7 @@ pub(super) trait Writer<'a> {
     ) -> Option<String>;
     /// Format some attributed text
     fn format_attributed(&'a self, text: &'a str, attribute: &'a TextEffect) -> Cow<'a, str>;
-    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError>;
+    fn write_to_file(file: &mut BufWriter<ExportFile>, text: &str) -> Result<(), RuntimeError>;
 }
 
 /// Defines behavior for formatting custom balloons to the desired output format
@@ -130,3 +164,315 @@ pub(super) trait TextEffectFormatter {
     /// Format [`Animated`](imessage_database::message_types::text_effects::TextEffect::Animated) message text
     fn format_animated(&self, text: &str, animation: &Animation) -> String;
 }
+
+/// `true` if every part of a message body is an attachment, else `false`
+pub fn is_media_only(parts: &[BubbleComponent]) -> bool {
+    !parts.is_empty()
+        && parts
+            .iter()
+            .all(|part| matches!(part, BubbleComponent::Attachment(_)))
+}
+
+/// Get the text of a message without the markers for its attachments
+///
+/// If the message only contained attachments, the text is [`MEDIA_REMOVED`].
+pub fn redact_attachments(message: &Message) -> Option<String> {
+    if !message.has_attachments() {
+        return message.text.clone();
+    }
+    let text: String = message
+        .text
+        .as_deref()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| *c != ATTACHMENT_CHAR)
+        .collect();
+    if text.trim().is_empty() {
+        return Some(MEDIA_REMOVED.to_string());
+    }
+    Some(text)
+}
+
+/// Generate the text of a message, writing [`UNREADABLE_MESSAGE`] in its place if the body cannot be parsed
+///
+/// Messages that simply have no text, like tapbacks or some app messages, are left alone.
+pub fn generate_text_or_placeholder(message: &mut Message, db: &Connection) {
+    if let Some(MessageError::StreamTypedParseError(why)) = message.generate_text(db).err() {
+        warn!("Unable to read the text of message {}: {why}", message.guid);
+        message.text = Some(UNREADABLE_MESSAGE.to_string());
+    }
+}
+
+/// Get the text of the message a reply responded to, cut to at most `max_length` characters
+///
+/// Attachments are left out of the text, and app messages are summarized. If the message is not a reply,
+/// or the message it responded to is not in the same conversation, there is nothing to quote.
+pub fn reply_context(
+    message: &Message,
+    db: &Connection,
+    schema: SchemaVersion,
+    max_length: usize,
+) -> Option<String> {
+    let mut original = message.get_thread_originator(db, schema).ok()??;
+    generate_text_or_placeholder(&mut original, db);
+    let text = summarize_app(&original, db).or_else(|| redact_attachments(&original))?;
+    Some(truncate(text.trim(), max_length))
+}
+
+/// Cut text to at most `max_length` characters, marking where it was cut with `...`
+fn truncate(text: &str, max_length: usize) -> String {
+    match text.char_indices().nth(max_length) {
+        Some((end, _)) => format!("{}...", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+/// Get the title of a link's preview, if the text of the message is only the link
+///
+/// Text that says more than the link is what the sender wrote, so it is kept and there is no title to use.
+pub fn link_title(message: &Message, db: &Connection) -> Option<String> {
+    if !message.is_url() || !is_only_link(message.text.as_deref().unwrap_or_default()) {
+        return None;
+    }
+    let payload = message.payload_data(db)?;
+    let parsed = parse_plist(&payload).ok()?;
+    let URLOverride::Normal(balloon) = URLMessage::get_url_message_override(&parsed).ok()? else {
+        return None;
+    };
+    balloon
+        .title
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .map(String::from)
+}
+
+/// `true` if the text is a single URL, ignoring attachment placeholders
+fn is_only_link(text: &str) -> bool {
+    let text = text.trim_matches(|c: char| c.is_whitespace() || c == ATTACHMENT_CHAR);
+    text.contains("://") && !text.contains(char::is_whitespace)
+}
+
+/// Describe a Digital Touch message, which cannot be rendered, i.e. `[Digital Touch: sketch]`
+pub fn digital_touch_placeholder(kind: &DigitalTouch) -> String {
+    format!("[Digital Touch: {}]", kind_name(kind))
+}
+
+/// Summarize an app message on one line, i.e. `Apple Pay: $20 Request`
+///
+/// URL previews and handwriting are not app payloads, so they are not summarized.
+/// Digital Touch messages are summarized with [`digital_touch_placeholder`].
+/// If the payload is missing or cannot be parsed, the summary is the name of the app.
+pub fn summarize_app(message: &Message, db: &Connection) -> Option<String> {
+    let Variant::App(balloon) = message.variant() else {
+        return None;
+    };
+    if matches!(balloon, CustomBalloon::URL | CustomBalloon::Handwriting) {
+        return None;
+    }
+    if matches!(balloon, CustomBalloon::DigitalTouch) {
+        let kind = message
+            .raw_payload_data(db)
+            .and_then(|payload| from_payload(&payload))
+            .unwrap_or_default();
+        return Some(digital_touch_placeholder(&kind));
+    }
+    let summary = message
+        .payload_data(db)
+        .and_then(|payload| {
+            let parsed = parse_plist(&payload).ok()?;
+            AppMessage::from_map(&parsed)
+                .ok()
+                .map(|bubble| bubble.summary(&balloon))
+        })
+        .unwrap_or_else(|| balloon.name().to_string());
+    Some(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        app::{export_type::ExportType, options::Options},
+        exporters::{
+            exporter::{
+                generate_text_or_placeholder, is_media_only, is_only_link, link_title,
+                redact_attachments, reply_context, summarize_app, truncate, MEDIA_REMOVED,
+                UNREADABLE_MESSAGE,
+            },
+            json::JSONExporter,
+        },
+        Config, Exporter, Markdown, WhatsApp, CSV, HTML, PDF, TXT,
+    };
+
+    use imessage_database::tables::messages::models::{AttachmentMeta, BubbleComponent};
+    use rusqlite::Connection;
+
+    #[test]
+    fn can_redact_attachment_only_message() {
+        let mut message = Config::fake_message();
+        message.text = Some("\u{FFFC}\u{FFFC}".to_string());
+        message.num_attachments = 2;
+
+        assert_eq!(
+            redact_attachments(&message),
+            Some(MEDIA_REMOVED.to_string())
+        );
+    }
+
+    #[test]
+    fn can_redact_attachment_with_text() {
+        let mut message = Config::fake_message();
+        message.text = Some("\u{FFFC}Check out this photo!".to_string());
+        message.num_attachments = 1;
+
+        assert_eq!(
+            redact_attachments(&message),
+            Some("Check out this photo!".to_string())
+        );
+    }
+
+    #[test]
+    fn can_redact_text_only_message() {
+        let mut message = Config::fake_message();
+        message.text = Some("Hello world".to_string());
+
+        assert_eq!(
+            redact_attachments(&message),
+            Some("Hello world".to_string())
+        );
+    }
+
+    #[test]
+    fn can_summarize_app_without_payload() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+
+        let mut message = Config::fake_message();
+        message.associated_message_type = Some(0);
+        message.balloon_bundle_id =
+            Some("com.apple.PassbookUIService.PeerPaymentMessagesExtension".to_string());
+        assert_eq!(
+            summarize_app(&message, &config.db),
+            Some("Apple Pay".to_string())
+        );
+
+        message.balloon_bundle_id = Some("com.apple.messages.URLBalloonProvider".to_string());
+        assert_eq!(summarize_app(&message, &config.db), None);
+    }
+
+    #[test]
+    fn can_summarize_digital_touch_without_payload() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+
+        let mut message = Config::fake_message();
+        message.associated_message_type = Some(0);
+        message.balloon_bundle_id = Some("com.apple.DigitalTouchBalloonProvider".to_string());
+        assert_eq!(
+            summarize_app(&message, &config.db),
+            Some("[Digital Touch: unknown]".to_string())
+        );
+    }
+
+    #[test]
+    fn can_keep_message_without_text() {
+        let options = Options::fake_options(ExportType::Txt);
+        let config = Config::fake_app(options);
+
+        let mut message = Config::fake_message();
+        generate_text_or_placeholder(&mut message, &config.db);
+
+        assert_eq!(message.text, None);
+    }
+
+    #[test]
+    fn can_replace_unreadable_message() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE message (ROWID INTEGER PRIMARY KEY, attributedBody BLOB);
+             INSERT INTO message VALUES (1, X'DEADBEEF');",
+        )
+        .unwrap();
+
+        let mut message = Config::fake_message();
+        message.rowid = 1;
+        generate_text_or_placeholder(&mut message, &db);
+
+        assert_eq!(message.text.as_deref(), Some(UNREADABLE_MESSAGE));
+    }
+
+    #[test]
+    fn can_truncate_long_text() {
+        assert_eq!(truncate("See you on Saturday", 7), "See you...");
+        assert_eq!(truncate("Café au lait", 4), "Café...");
+    }
+
+    #[test]
+    fn can_keep_short_text() {
+        assert_eq!(truncate("See you", 7), "See you");
+        assert_eq!(truncate("", 7), "");
+    }
+
+    #[test]
+    fn cant_get_reply_context_not_reply() {
+        let options = Options::fake_options(ExportType::Txt);
+        let config = Config::fake_app(options);
+
+        let message = Config::fake_message();
+        assert_eq!(reply_context(&message, &config.db, config.schema, 10), None);
+    }
+
+    #[test]
+    fn can_find_only_link() {
+        assert!(is_only_link(
+            "https://github.com/ReagentX/imessage-exporter"
+        ));
+        assert!(is_only_link(" https://github.com \n"));
+    }
+
+    #[test]
+    fn cant_find_only_link_with_text() {
+        assert!(!is_only_link("Look at this https://github.com"));
+        assert!(!is_only_link("github.com"));
+        assert!(!is_only_link(""));
+    }
+
+    #[test]
+    fn cant_get_link_title_not_url() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+
+        let mut message = Config::fake_message();
+        message.text = Some("https://github.com".to_string());
+        assert_eq!(link_title(&message, &config.db), None);
+    }
+
+    #[test]
+    fn can_report_attachment_support() {
+        assert!(HTML::supports_attachments());
+        assert!(TXT::supports_attachments());
+        assert!(WhatsApp::supports_attachments());
+        assert!(Markdown::supports_attachments());
+        assert!(JSONExporter::supports_attachments());
+        assert!(!CSV::supports_attachments());
+    }
+
+    #[test]
+    fn can_report_reaction_support() {
+        assert!(HTML::supports_reactions());
+        assert!(PDF::supports_reactions());
+        assert!(Markdown::supports_reactions());
+        assert!(!WhatsApp::supports_reactions());
+        assert!(!JSONExporter::supports_reactions());
+    }
+
+    #[test]
+    fn can_get_media_only() {
+        let parts = vec![
+            BubbleComponent::Attachment(AttachmentMeta::default()),
+            BubbleComponent::Attachment(AttachmentMeta::default()),
+        ];
+        assert!(is_media_only(&parts));
+        assert!(!is_media_only(&[]));
+        assert!(!is_media_only(&[BubbleComponent::App]));
+    }
+}
@@ -1,19 +1,26 @@
 use std::{
     borrow::Cow,
     collections::{
-        hash_map::Entry::{Occupied, Vacant},
-        HashMap,
+        btree_map::Entry::{Occupied, Vacant},
+        BTreeMap, HashMap,
     },
-    fs::File,
-    io::{BufWriter, Write},
+    fs::{create_dir_all, remove_dir_all, File},
+    io::{copy, BufWriter, Write},
+    path::{Path, PathBuf},
 };
 
+use log::info;
+
 use crate::{
     app::{
-        error::RuntimeError, progress::build_progress_bar_export, runtime::Config,
+        contact_sheet::ContactSheet, data_uri::data_uri, encoding::OutputEncoding,
+        error::RuntimeError, export_file::ExportFile, options::OPTION_NO_ORPHANED, runtime::Config,
         sanitizers::sanitize_html,
     },
-    exporters::exporter::{BalloonFormatter, Exporter, TextEffectFormatter, Writer},
+    exporters::exporter::{
+        generate_text_or_placeholder, is_media_only, BalloonFormatter, Exporter, Previewer,
+        TextEffectFormatter, Writer, MEDIA_REMOVED,
+    },
 };
 
 use imessage_database::{
@@ -30,10 +37,13 @@ use imessage_database::{
         placemark::PlacemarkMessage,
         text_effects::{Animation, Style, TextEffect, Unit},
         url::URLMessage,
-        variants::{Announcement, BalloonProvider, CustomBalloon, URLOverride, Variant},
+        variants::{
+            Announcement, BalloonProvider, CustomBalloon, SystemEvent, URLOverride, Variant,
+        },
     },
     tables::{
         attachment::{Attachment, MediaType},
+        chat::Chat,
         messages::{
             models::{AttachmentMeta, BubbleComponent},
             Message,
@@ -41,62 +51,101 @@ use imessage_database::{
         table::{Table, FITNESS_RECEIVER, ME, ORPHANED, YOU},
     },
     util::{
-        dates::{format, get_local_time, readable_diff, TIMESTAMP_FACTOR},
+        dates::{get_local_time, readable_diff, TIMESTAMP_FACTOR},
         plist::parse_plist,
     },
 };
 
-const HEADER: &str = "<html>\n<head>\n<meta charset=\"UTF-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">";
+const HEADER: &str = "<html>\n<head>\n";
+const VIEWPORT: &str = "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">";
 const FOOTER: &str = "</body></html>";
 const STYLE: &str = include_str!("resources/style.css");
+/// Switches between conversations and loads embedded images in single file archives
+const ARCHIVE_SCRIPT: &str = include_str!("resources/archive.js");
+/// Name of the file every conversation is written to with `--single-file`
+pub const ARCHIVE: &str = "archive";
+/// Directory conversations are written to before they are combined into the archive
+const ARCHIVE_SECTIONS: &str = ".archive";
+/// Name of the file that links to every page of each paginated conversation
+const INDEX: &str = "index";
 
 pub struct HTML<'a> {
     /// Data that is setup from the application's runtime
     pub config: &'a Config,
     /// Handles to files we want to write messages to
     /// Map of resolved chatroom file location to a buffered writer
-    pub files: HashMap<String, BufWriter<File>>,
-    /// Writer instance for orphaned messages
-    pub orphaned: BufWriter<File>,
+    pub files: BTreeMap<String, BufWriter<ExportFile>>,
+    /// Writer instance for orphaned messages, unless they are dropped
+    pub orphaned: Option<BufWriter<ExportFile>>,
+    /// Map of resolved chatroom file location to the current page number and the number of messages written to it
+    pub pages: HashMap<String, (usize, usize)>,
+    /// Directory conversations are written to before they are combined, if we are writing a single file archive
+    pub sections: Option<PathBuf>,
+    /// Map of resolved chatroom file location to the people in it, if we are writing contact sheets
+    pub people: HashMap<String, ContactSheet>,
 }
 
 impl<'a> Exporter<'a> for HTML<'a> {
     fn new(config: &'a Config) -> Result<Self, RuntimeError> {
-        let mut orphaned = config.options.export_path.clone();
-        orphaned.push(ORPHANED);
-        orphaned.set_extension("html");
-        let file = File::options()
-            .append(true)
-            .create(true)
-            .open(&orphaned)
-            .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+        let sections = if config.options.single_file {
+            let path = config.options.export_path.join(ARCHIVE_SECTIONS);
+            create_dir_all(&path).map_err(|err| RuntimeError::CreateError(err, path.clone()))?;
+            Some(path)
+        } else {
+            None
+        };
+
+        let orphaned = if config.options.no_orphaned {
+            None
+        } else if let Some(sections) = &sections {
+            Some(HTML::open_section(sections, ORPHANED)?)
+        } else {
+            let mut orphaned = config.conversation_dir(ORPHANED)?;
+            orphaned.push(ORPHANED);
+            orphaned.set_extension(config.file_extension("html"));
+            Some(config.open_export_file(&orphaned)?)
+        };
 
         Ok(HTML {
             config,
-            files: HashMap::new(),
-            orphaned: BufWriter::new(file),
+            files: BTreeMap::new(),
+            orphaned,
+            pages: HashMap::new(),
+            sections,
+            people: HashMap::new(),
         })
     }
 
     fn iter_messages(&mut self) -> Result<(), RuntimeError> {
         // Tell the user what we are doing
-        eprintln!(
+        info!(
             "Exporting to {} as html...",
             self.config.options.export_path.display()
         );
 
-        // Write orphaned file headers
-        HTML::write_headers(&mut self.orphaned)?;
+        // Write orphaned file headers; sections of a single file archive share the archive's headers
+        if let Some(orphaned) = self.orphaned.as_mut().filter(|_| self.sections.is_none()) {
+            HTML::write_headers(orphaned, &self.config.options.output_encoding, None)?;
+        }
 
         // Keep track of current message ROWID
         let mut current_message_row = -1;
 
+        // Keep track of messages dropped because they do not belong to a conversation
+        let mut dropped_messages = 0;
+
+        // Keep track of messages skipped because a previous export already wrote them
+        let mut skipped_messages = 0;
+
+        // Keep track of messages skipped because their text is too short
+        let mut short_messages = 0;
+
         // Set up progress bar
         let mut current_message = 0;
         let total_messages =
             Message::get_count(&self.config.db, &self.config.options.query_context)
                 .map_err(RuntimeError::DatabaseError)?;
-        let pb = build_progress_bar_export(total_messages);
+        let pb = self.config.progress_bar(total_messages);
 
         let mut statement =
             Message::stream_rows(&self.config.db, &self.config.options.query_context)
@@ -107,7 +156,14 @@ impl<'a> Exporter<'a> for HTML<'a> {
             .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
 
         for message in messages {
-            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+            let mut msg = match Message::extract(message) {
+                Ok(msg) => msg,
+                Err(why) => {
+                    self.config.handle_error(RuntimeError::DatabaseError(why))?;
+                    current_message += 1;
+                    continue;
+                }
+            };
 
             // Early escape if we try and render the same message GUID twice
             // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
@@ -117,21 +173,66 @@ impl<'a> Exporter<'a> for HTML<'a> {
             }
             current_message_row = msg.rowid;
 
+            // Drop messages that do not belong to a conversation, if requested
+            if self.config.options.no_orphaned && self.config.is_orphaned(&msg) {
+                dropped_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Skip messages that a previous export already wrote, if requested
+            if self.config.already_exported(&msg) {
+                skipped_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
             // Generate the text of the message
-            let _ = msg.generate_text(&self.config.db);
+            generate_text_or_placeholder(&mut msg, &self.config.db);
+
+            // Skip messages that are too short, which is only known once their text is generated
+            if self.config.is_too_short(&msg) {
+                short_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Note when the message was delivered and read, and list it on its own if it is a tapback, if requested
+            self.config.record_receipt(&msg);
+            self.config.record_reaction(&msg);
 
             // Render the announcement in-line
             if msg.is_announcement() {
                 let announcement = self.format_announcement(&msg);
-                HTML::write_to_file(self.get_or_create_file(&msg)?, &announcement)?;
+                if let Err(why) = self
+                    .get_or_create_file(&msg)
+                    .and_then(|file| HTML::write_to_file(file, &announcement))
+                    .and_then(|_| self.config.mark_exported(&msg.guid))
+                {
+                    self.config.handle_error(why)?;
+                }
             }
             // Message replies and tapbacks are rendered in context, so no need to render them separately
             else if !msg.is_tapback() {
-                let message = self
-                    .format_message(&msg, 0)
-                    .map_err(RuntimeError::DatabaseError)?;
-                HTML::write_to_file(self.get_or_create_file(&msg)?, &message)?;
+                match self.format_message(&msg, 0) {
+                    Ok(message) => {
+                        if let Err(why) = self
+                            .get_or_create_file(&msg)
+                            .and_then(|file| HTML::write_to_file(file, &message))
+                            .and_then(|_| self.config.mark_exported(&msg.guid))
+                        {
+                            self.config.handle_error(why)?;
+                        } else {
+                            self.add_to_contact_sheet(&msg);
+                        }
+                    }
+                    Err(why) => self.config.handle_error(RuntimeError::DatabaseError(why))?,
+                }
             }
+
+            // Abort if an attachment failed to copy and we are not exporting on a best effort basis
+            self.config.check_errors()?;
+
             current_message += 1;
             if current_message % 99 == 0 {
                 pb.set_position(current_message);
@@ -139,11 +240,38 @@ impl<'a> Exporter<'a> for HTML<'a> {
         }
         pb.finish();
 
-        eprintln!("Writing HTML footers...");
+        if dropped_messages > 0 {
+            info!("Dropped {dropped_messages} messages that do not belong to a conversation");
+        }
+        if skipped_messages > 0 {
+            info!("Skipped {skipped_messages} messages that a previous export already wrote");
+        }
+        if short_messages > 0 {
+            info!("Skipped {short_messages} messages that are too short");
+        }
+
+        if self.sections.is_some() {
+            info!("Writing {ARCHIVE}.html...");
+            return self.write_archive();
+        }
+
+        info!("Writing HTML footers...");
         for (_, buf) in self.files.iter_mut() {
             HTML::write_to_file(buf, FOOTER)?;
         }
-        HTML::write_to_file(&mut self.orphaned, FOOTER)?;
+        if let Some(orphaned) = &mut self.orphaned {
+            HTML::write_to_file(orphaned, FOOTER)?;
+        }
+
+        if !self.people.is_empty() {
+            info!("Writing contact sheets...");
+            self.write_contact_sheets()?;
+        }
+
+        if !self.pages.is_empty() {
+            info!("Writing {INDEX}.html...");
+            self.write_index()?;
+        }
 
         Ok(())
     }
@@ -152,39 +280,147 @@ impl<'a> Exporter<'a> for HTML<'a> {
     fn get_or_create_file(
         &mut self,
         message: &Message,
-    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+    ) -> Result<&mut BufWriter<ExportFile>, RuntimeError> {
         match self.config.conversation(message) {
             Some((chatroom, _)) => {
                 let filename = self.config.filename(chatroom);
-                match self.files.entry(filename) {
-                    Occupied(entry) => Ok(entry.into_mut()),
-                    Vacant(entry) => {
-                        let mut path = self.config.options.export_path.clone();
-                        path.push(self.config.filename(chatroom));
-                        path.set_extension("html");
+                let title = self.config.conversation_title(chatroom);
+
+                // Determine which page the message belongs on, if we are paginating
+                let page = match self.config.options.messages_per_page {
+                    Some(limit) => Some(self.turn_page(&filename, limit)?),
+                    None => None,
+                };
+
+                // Group conversations link to their contact sheet, if we are writing them
+                let has_contact_sheet = self.has_contact_sheet(chatroom);
+
+                match (self.files.entry(filename), &self.sections) {
+                    (Occupied(entry), _) => Ok(entry.into_mut()),
+                    // Sections of a single file archive are combined once every message is written
+                    (Vacant(entry), Some(sections)) => {
+                        let mut buf = HTML::open_section(sections, entry.key())?;
+                        let _ = HTML::write_to_file(&mut buf, &HTML::format_title(&title));
+                        if let Some(avatar) = self.config.conversation_avatar(chatroom) {
+                            let src = HTML::embed(self.config, &avatar)
+                                .unwrap_or_else(|_| self.config.message_attachment_path(&avatar));
+                            let _ = HTML::write_to_file(&mut buf, &HTML::format_avatar(&src));
+                        }
+                        Ok(entry.insert(buf))
+                    }
+                    (Vacant(entry), None) => {
+                        let extension = self.config.file_extension("html");
+                        let mut path = self.config.conversation_dir(entry.key())?;
+                        match page {
+                            Some(page) => {
+                                path.push(HTML::page_filename(entry.key(), page, &extension))
+                            }
+                            None => {
+                                path.push(entry.key());
+                                path.set_extension(&extension);
+                            }
+                        }
 
                         // If the file already exists, don't write the headers again
                         // This can happen if multiple chats use the same group name
                         let file_exists = path.exists();
 
-                        let file = File::options()
-                            .append(true)
-                            .create(true)
-                            .open(&path)
-                            .map_err(|err| RuntimeError::CreateError(err, path))?;
-
-                        let mut buf = BufWriter::new(file);
+                        let mut buf = self.config.open_export_file(&path)?;
 
                         // Write headers if the file does not exist
                         if !file_exists {
-                            let _ = HTML::write_headers(&mut buf);
+                            let _ = HTML::write_headers(
+                                &mut buf,
+                                &self.config.options.output_encoding,
+                                Some(&title),
+                            );
+                            let _ = HTML::write_to_file(&mut buf, &HTML::format_title(&title));
+
+                            // Show the conversation's group photo, if it has one
+                            if let Some(avatar) = self.config.conversation_avatar(chatroom) {
+                                let _ = HTML::write_to_file(
+                                    &mut buf,
+                                    &HTML::format_avatar(
+                                        &self.config.message_attachment_path(&avatar),
+                                    ),
+                                );
+                            }
+
+                            // Link to the people in the conversation
+                            if has_contact_sheet {
+                                let _ = HTML::write_to_file(
+                                    &mut buf,
+                                    &HTML::contact_sheet_link(entry.key(), &extension),
+                                );
+                            }
+
+                            // Link to the conversation's entry in the index, which is a directory up with `--directory-per-chat`
+                            if page.is_some() {
+                                let root = if self.config.options.directory_per_chat {
+                                    "../"
+                                } else {
+                                    ""
+                                };
+                                let _ = HTML::write_to_file(
+                                    &mut buf,
+                                    &HTML::index_link(entry.key(), root, &extension),
+                                );
+                            }
+
+                            // Link back to the previous pages
+                            if let Some(page) = page.filter(|page| *page > 1) {
+                                let _ = HTML::write_to_file(
+                                    &mut buf,
+                                    &HTML::page_navigation(entry.key(), page, false, &extension),
+                                );
+                            }
                         }
 
                         Ok(entry.insert(buf))
                     }
                 }
             }
-            None => Ok(&mut self.orphaned),
+            None => self
+                .orphaned
+                .as_mut()
+                .ok_or(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_NO_ORPHANED} is enabled, but message {} has no conversation!",
+                    message.rowid
+                ))),
+        }
+    }
+
+    fn supports_attachments() -> bool {
+        true
+    }
+
+    fn supports_reactions() -> bool {
+        true
+    }
+}
+
+impl<'a> Previewer<'a> for HTML<'a> {
+    fn preview(config: &'a Config) -> Self {
+        HTML {
+            config,
+            files: BTreeMap::new(),
+            orphaned: None,
+            pages: HashMap::new(),
+            sections: None,
+            people: HashMap::new(),
+        }
+    }
+
+    fn format_preview(&self, msg: &Message) -> Result<Option<String>, RuntimeError> {
+        // Match the export, which renders announcements in-line and tapbacks with the message they react to
+        if msg.is_announcement() {
+            Ok(Some(self.format_announcement(msg)))
+        } else if !msg.is_tapback() {
+            self.format_message(msg, 0)
+                .map(Some)
+                .map_err(RuntimeError::DatabaseError)
+        } else {
+            Ok(None)
         }
     }
 }
@@ -273,29 +509,37 @@ impl<'a> Writer<'a> for HTML<'a> {
 
         // Useful message metadata
         let message_parts = message.body();
-        let mut attachments = Attachment::from_message(&self.config.db, message)?;
-        let mut replies = message.get_replies(&self.config.db)?;
+        let mut attachments = if self.config.options.redact_attachments {
+            vec![]
+        } else {
+            Attachment::from_message(&self.config.db, message)?
+        };
+        let mut replies = message.get_replies(&self.config.db, self.config.schema)?;
 
         // Index of where we are in the attachment Vector
         let mut attachment_index: usize = 0;
 
+        // Several attachments sent together are shown side by side
+        let is_attachment =
+            |idx: usize| matches!(message_parts.get(idx), Some(BubbleComponent::Attachment(_)));
+        let gallery = attachments.len() > 1;
+
         // Add message subject
-        if let Some(subject) = &message.subject {
-            // Add message sender
+        if let Some(subject) = message.subject.as_deref().filter(|s| !s.is_empty()) {
             self.add_line(
                 &mut formatted_message,
                 &sanitize_html(subject),
-                "<p>Subject: <span class=\"subject\">",
+                "<p><b>Subject:</b> <span class=\"subject\">",
                 "</span></p>",
             );
         }
 
-        // Handle SharePlay
-        if message.is_shareplay() {
+        // Handle system events, i.e. FaceTime calls
+        if let Some(event) = message.get_system_event() {
             self.add_line(
                 &mut formatted_message,
-                self.format_shareplay(),
-                "<span class=\"shareplay\">",
+                &self.format_system_event(&event),
+                "<span class=\"system_event\">",
                 "</span>",
             );
         }
@@ -312,13 +556,15 @@ impl<'a> Writer<'a> for HTML<'a> {
 
         // Generate the message body from it's components
         for (idx, message_part) in message_parts.iter().enumerate() {
-            // Write the part div start
-            self.add_line(
-                &mut formatted_message,
-                "<hr><div class=\"message_part\">",
-                "",
-                "",
-            );
+            // Write the part div start, keeping attachments in a gallery together
+            let part_start = if !(gallery && is_attachment(idx)) {
+                "<hr><div class=\"message_part\">"
+            } else if idx > 0 && is_attachment(idx - 1) {
+                "<div class=\"message_part gallery\">"
+            } else {
+                "<hr><div class=\"message_part gallery\">"
+            };
+            self.add_line(&mut formatted_message, part_start, "", "");
 
             match message_part {
                 BubbleComponent::Text(text_attrs) => {
@@ -381,6 +627,17 @@ impl<'a> Writer<'a> for HTML<'a> {
                         }
                     }
                 }
+                // Leave attachments out, noting where a message only contained attachments
+                BubbleComponent::Attachment(_) if self.config.options.redact_attachments => {
+                    if idx == 0 && is_media_only(&message_parts) {
+                        self.add_line(
+                            &mut formatted_message,
+                            &sanitize_html(MEDIA_REMOVED),
+                            "<span class=\"bubble\">",
+                            "</span>",
+                        );
+                    }
+                }
                 BubbleComponent::Attachment(metadata) => {
                     match attachments.get_mut(attachment_index) {
                         Some(attachment) => {
@@ -451,6 +708,35 @@ impl<'a> Writer<'a> for HTML<'a> {
                 }
             };
 
+            // Handle stickers placed on this part, which are overlaid on the bubble
+            let part_tapbacks = self
+                .config
+                .tapbacks
+                .get(&message.guid)
+                .and_then(|tapbacks_map| tapbacks_map.get(&idx));
+            let (stickers, tapbacks): (Vec<&Message>, Vec<&Message>) = part_tapbacks
+                .into_iter()
+                .flatten()
+                .partition(|tapback| tapback.placed_on().is_some());
+
+            if !stickers.is_empty() {
+                let mut formatted_stickers = String::new();
+                for sticker in stickers {
+                    self.add_line(
+                        &mut formatted_stickers,
+                        &self.format_tapback(sticker)?,
+                        "<div class=\"placed_sticker\">",
+                        "</div>",
+                    );
+                }
+                self.add_line(
+                    &mut formatted_message,
+                    &formatted_stickers,
+                    "<div class=\"placed_stickers\">",
+                    "</div>",
+                );
+            }
+
             // Write the part div end
             self.add_line(&mut formatted_message, "</div>", "", "");
 
@@ -465,45 +751,36 @@ impl<'a> Writer<'a> for HTML<'a> {
             }
 
             // Handle Tapbacks
-            if let Some(tapbacks_map) = self.config.tapbacks.get(&message.guid) {
-                if let Some(tapbacks) = tapbacks_map.get(&idx) {
-                    let mut formatted_tapbacks = String::new();
-
-                    tapbacks
-                        .iter()
-                        .try_for_each(|tapback| -> Result<(), TableError> {
-                            let formatted = self.format_tapback(tapback)?;
-                            if !formatted.is_empty() {
-                                self.add_line(
-                                    &mut formatted_tapbacks,
-                                    &self.format_tapback(tapback)?,
-                                    "<div class=\"tapback\">",
-                                    "</div>",
-                                );
-                            }
-                            Ok(())
-                        })?;
-
-                    if !formatted_tapbacks.is_empty() {
-                        self.add_line(
-                            &mut formatted_message,
-                            "<hr><p>Tapbacks:</p>",
-                            "<div class=\"tapbacks\">",
-                            "",
-                        );
-                        self.add_line(&mut formatted_message, &formatted_tapbacks, "", "");
-                    }
-                    self.add_line(&mut formatted_message, "</div>", "", "");
+            let mut formatted_tapbacks = String::new();
+            for tapback in tapbacks {
+                let formatted = self.format_tapback(tapback)?;
+                if !formatted.is_empty() {
+                    self.add_line(
+                        &mut formatted_tapbacks,
+                        &formatted,
+                        "<div class=\"tapback\">",
+                        "</div>",
+                    );
                 }
             }
 
+            if !formatted_tapbacks.is_empty() {
+                self.add_line(
+                    &mut formatted_message,
+                    "<hr><p>Tapbacks:</p>",
+                    "<div class=\"tapbacks\">",
+                    "",
+                );
+                self.add_line(&mut formatted_message, &formatted_tapbacks, "", "</div>");
+            }
+
             // Handle Replies
             if let Some(replies) = replies.get_mut(&idx) {
                 self.add_line(&mut formatted_message, "<div class=\"replies\">", "", "");
                 replies
                     .iter_mut()
                     .try_for_each(|reply| -> Result<(), TableError> {
-                        let _ = reply.generate_text(&self.config.db);
+                        generate_text_or_placeholder(reply, &self.config.db);
                         if !reply.is_tapback() {
                             // Set indent to 1 so we know this is a recursive call
                             self.add_line(
@@ -555,11 +832,49 @@ impl<'a> Writer<'a> for HTML<'a> {
         let embed_path = self.config.message_attachment_path(attachment);
 
         Ok(match attachment.mime_type() {
+            // Single file archives embed images so they do not depend on any other file
+            MediaType::Image(_) if self.sections.is_some() => {
+                let embedded = match self.config.options.thumbnail_size.and_then(|size| {
+                    self.config
+                        .options
+                        .attachment_manager
+                        .handle_thumbnail(attachment, size)
+                }) {
+                    Some(thumbnail) => data_uri(&thumbnail, &HTML::mime_type(&thumbnail)),
+                    None => HTML::embed(self.config, attachment),
+                };
+                match embedded {
+                    Ok(src) if self.config.options.no_lazy => format!("<img src=\"{src}\">"),
+                    Ok(src) => format!(
+                        "<img data-src=\"{src}\" alt=\"{}\">",
+                        sanitize_html(attachment.filename())
+                    ),
+                    Err(why) => format!(
+                        "<p>Unable to embed {}: {why}</p>",
+                        sanitize_html(attachment.filename())
+                    ),
+                }
+            }
             MediaType::Image(_) => {
-                if self.config.options.no_lazy {
-                    format!("<img src=\"{embed_path}\">")
+                // Embed a thumbnail that links to the full size image, if requested
+                let thumbnail_path = self.config.options.thumbnail_size.and_then(|size| {
+                    self.config
+                        .options
+                        .attachment_manager
+                        .handle_thumbnail(attachment, size)
+                        .and_then(|path| self.config.relative_path(path))
+                });
+                let src = thumbnail_path.as_deref().unwrap_or(&embed_path);
+
+                let image = if self.config.options.no_lazy {
+                    format!("<img src=\"{src}\">")
                 } else {
-                    format!("<img src=\"{embed_path}\" loading=\"lazy\">")
+                    format!("<img src=\"{src}\" loading=\"lazy\">")
+                };
+
+                match thumbnail_path {
+                    Some(_) => format!("<a href=\"{embed_path}\">{image}</a>"),
+                    None => image,
                 }
             }
             MediaType::Video(media_type) => {
@@ -725,10 +1040,13 @@ impl<'a> Writer<'a> for HTML<'a> {
                 ))
             }
             Variant::Sticker(_) => {
-                let mut paths = Attachment::from_message(&self.config.db, msg)?;
                 let who =
                     self.config
                         .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+                if self.config.options.redact_attachments {
+                    return Ok(format!("<span class=\"tapback\">Sticker from {who}</span>"));
+                }
+                let mut paths = Attachment::from_message(&self.config.db, msg)?;
                 // Sticker messages have only one attachment, the sticker image
                 Ok(match paths.get_mut(0) {
                     Some(sticker) => format!(
@@ -776,7 +1094,7 @@ impl<'a> Writer<'a> for HTML<'a> {
         if who == ME {
             who = self.config.options.custom_name.as_deref().unwrap_or("You");
         }
-        let timestamp = format(&msg.date(&self.config.offset));
+        let timestamp = self.config.format_date(&msg.date(&self.config.offset));
 
         match msg.get_announcement() {
             Some(announcement) => match announcement {
@@ -808,8 +1126,8 @@ impl<'a> Writer<'a> for HTML<'a> {
         }
     }
 
-    fn format_shareplay(&self) -> &str {
-        "<hr>SharePlay Message Ended"
+    fn format_system_event(&self, event: &SystemEvent) -> String {
+        format!("<hr>{event}")
     }
 
     fn format_shared_location(&self, msg: &'a Message) -> &str {
@@ -906,7 +1224,7 @@ impl<'a> Writer<'a> for HTML<'a> {
         }
     }
 
-    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+    fn write_to_file(file: &mut BufWriter<ExportFile>, text: &str) -> Result<(), RuntimeError> {
         file.write_all(text.as_bytes())
             .map_err(RuntimeError::DiskError)
     }
@@ -934,8 +1252,13 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
         // Header section
         out_s.push_str("<div class=\"app_header\">");
 
-        // Add preview images
-        balloon.images.iter().for_each(|image| {
+        // Add preview images, preferring the one saved with the message over the ones hosted on the web
+        let saved_preview = self.config.link_preview(msg);
+        let images = match &saved_preview {
+            Some(preview) => vec![preview.as_str()],
+            None => balloon.images.clone(),
+        };
+        images.iter().for_each(|image| {
             out_s.push_str("<img src=\"");
             out_s.push_str(image);
             if self.config.options.no_lazy {
@@ -1247,8 +1570,8 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
 
     fn format_digital_touch(&self, _: &Message, balloon: &DigitalTouch, _: &'a Message) -> String {
         format!(
-            "<div class=\"app_header\"><div class=\"name\">Digital Touch Message</div></div>\n<div class=\"app_footer\"><div class=\"caption\">{:?}</div></div>",
-            balloon
+            "<div class=\"app_header\"><div class=\"name\">Digital Touch Message</div></div>\n<div class=\"app_footer\"><div class=\"caption\">{}</div></div>",
+            digital_touch::kind_name(balloon)
         )
     }
 
@@ -1342,7 +1665,7 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
             // Parse the estimated end time from the message's query string
             let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
             let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
+            let date_string = self.config.format_date(&date_time);
 
             out_s.push_str("<div class=\"app_footer\">");
 
@@ -1357,7 +1680,7 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
             // Parse the estimated end time from the message's query string
             let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
             let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
+            let date_string = self.config.format_date(&date_time);
 
             out_s.push_str("<div class=\"app_footer\">");
 
@@ -1372,7 +1695,7 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
             // Parse the estimated end time from the message's query string
             let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
             let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
+            let date_string = self.config.format_date(&date_time);
 
             out_s.push_str("<div class=\"app_footer\">");
 
@@ -1399,7 +1722,12 @@ impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
 
 impl TextEffectFormatter for HTML<'_> {
     fn format_mention(&self, text: &str, mentioned: &str) -> String {
-        format!("<span title=\"{mentioned}\"><b>{text}</b></span>")
+        let prefix = if self.config.options.rich_text {
+            "@"
+        } else {
+            ""
+        };
+        format!("<span title=\"{mentioned}\"><b>{prefix}{text}</b></span>")
     }
 
     fn format_link(&self, text: &str, url: &str) -> String {
@@ -1440,17 +1768,9 @@ impl TextEffectFormatter for HTML<'_> {
 
 impl HTML<'_> {
     fn get_time(&self, message: &Message) -> String {
-        let mut date = format(&message.date(&self.config.offset));
-        let read_after = message.time_until_read(&self.config.offset);
-        if let Some(time) = read_after {
-            if !time.is_empty() {
-                let who = if message.is_from_me() {
-                    "them"
-                } else {
-                    self.config.options.custom_name.as_deref().unwrap_or("you")
-                };
-                date.push_str(&format!(" (Read by {who} after {time})"));
-            }
+        let mut date = self.config.format_date(&message.date(&self.config.offset));
+        if let Some(note) = self.config.delivery_note(message) {
+            date.push_str(&format!(" ({note})"));
         }
         date
     }
@@ -1464,9 +1784,21 @@ impl HTML<'_> {
         }
     }
 
-    fn write_headers(file: &mut BufWriter<File>) -> Result<(), RuntimeError> {
-        // Write file header
+    fn write_headers(
+        file: &mut BufWriter<ExportFile>,
+        encoding: &OutputEncoding,
+        title: Option<&str>,
+    ) -> Result<(), RuntimeError> {
+        // Write file header, declaring the encoding the file is written in
         HTML::write_to_file(file, HEADER)?;
+        HTML::write_to_file(
+            file,
+            &format!("<meta charset=\"{}\">\n", encoding.charset()),
+        )?;
+        if let Some(title) = title {
+            HTML::write_to_file(file, &format!("<title>{}</title>\n", sanitize_html(title)))?;
+        }
+        HTML::write_to_file(file, VIEWPORT)?;
 
         // Write CSS
         HTML::write_to_file(file, "<style>\n")?;
@@ -1476,6 +1808,398 @@ impl HTML<'_> {
         Ok(())
     }
 
+    /// Open the file a conversation is written to before it is combined into a single file archive
+    ///
+    /// Sections are plain UTF-8; the archive applies any compression or output encoding when they are combined.
+    fn open_section(
+        sections: &Path,
+        filename: &str,
+    ) -> Result<BufWriter<ExportFile>, RuntimeError> {
+        let path = HTML::section_path(sections, filename);
+        File::create(&path)
+            .and_then(|file| ExportFile::new(file, false))
+            .map(BufWriter::new)
+            .map_err(|err| RuntimeError::CreateError(err, path))
+    }
+
+    /// Get the path to the section a conversation is written to
+    ///
+    /// The extension is appended rather than set, since conversation names may contain periods
+    fn section_path(sections: &Path, filename: &str) -> PathBuf {
+        sections.join(format!("{filename}.html"))
+    }
+
+    /// Read an attachment into a data URI so it can be embedded in a single file archive
+    ///
+    /// Copied attachments are read from the export, since they may have been converted to a format browsers can display
+    fn embed(config: &Config, attachment: &Attachment) -> Result<String, String> {
+        let path = match &attachment.copied_path {
+            Some(path) => path.clone(),
+            None => attachment
+                .resolved_attachment_path(
+                    &config.options.platform,
+                    &config.options.db_path,
+                    config.options.attachment_root.as_deref(),
+                )
+                .map(PathBuf::from)
+                .ok_or_else(|| format!("Unable to locate {}", attachment.filename()))?,
+        };
+        // A converted copy, i.e. a HEIC image copied as JPEG, is labelled with the type it was converted to
+        let media_type = match path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(MediaType::from_extension)
+        {
+            Some(MediaType::Unknown) | None => attachment.mime_type(),
+            Some(media_type) => media_type,
+        };
+        data_uri(&path, &media_type.as_mime_type())
+    }
+
+    /// Get the MIME type of a generated file from its extension
+    fn mime_type(path: &Path) -> String {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .map_or(MediaType::Unknown, MediaType::from_extension)
+            .as_mime_type()
+    }
+
+    /// Combine every conversation into a single file archive, with a menu to switch between them
+    ///
+    /// Only the first conversation is shown when the archive is opened.
+    fn write_archive(&mut self) -> Result<(), RuntimeError> {
+        let Some(sections) = self.sections.take() else {
+            return Ok(());
+        };
+
+        // Close the sections so everything written to them can be read back
+        let mut names = vec![];
+        for (name, mut file) in std::mem::take(&mut self.files) {
+            file.flush()?;
+            names.push(name);
+        }
+        if let Some(mut orphaned) = self.orphaned.take() {
+            orphaned.flush()?;
+            names.push(ORPHANED.to_string());
+        }
+
+        let path = self
+            .config
+            .options
+            .export_path
+            .join(ARCHIVE)
+            .with_extension(self.config.file_extension("html"));
+        let mut archive = self.config.open_export_file(&path)?;
+        HTML::write_headers(&mut archive, &self.config.options.output_encoding, None)?;
+        HTML::write_to_file(&mut archive, &HTML::archive_switcher(&names))?;
+
+        for (idx, name) in names.iter().enumerate() {
+            let mut section = File::open(HTML::section_path(&sections, name))?;
+            let hidden = if idx == 0 { "" } else { " hidden" };
+            HTML::write_to_file(
+                &mut archive,
+                &format!("<section class=\"conversation\" id=\"c-{idx}\"{hidden}>\n"),
+            )?;
+            copy(&mut section, &mut archive)?;
+            HTML::write_to_file(&mut archive, "</section>\n")?;
+        }
+
+        HTML::write_to_file(
+            &mut archive,
+            &format!("<script>\n{ARCHIVE_SCRIPT}</script>\n"),
+        )?;
+        HTML::write_to_file(&mut archive, FOOTER)?;
+        archive
+            .into_inner()
+            .map_err(|err| RuntimeError::DiskError(err.into_error()))?
+            .finish()?;
+
+        remove_dir_all(sections)?;
+        Ok(())
+    }
+
+    /// Build the menu used to switch between the conversations in a single file archive
+    fn archive_switcher(names: &[String]) -> String {
+        let mut switcher = String::from(
+            "<nav class=\"switcher\">\n<select onchange=\"showConversation(this.value)\">\n",
+        );
+        for (idx, name) in names.iter().enumerate() {
+            switcher.push_str(&format!(
+                "<option value=\"c-{idx}\">{}</option>\n",
+                sanitize_html(name)
+            ));
+        }
+        switcher.push_str("</select>\n</nav>\n");
+        switcher
+    }
+
+    /// Determine whether a contact sheet is written for a conversation, which is only done for group chats
+    fn has_contact_sheet(&self, chatroom: &Chat) -> bool {
+        self.config.options.contact_sheets
+            && self
+                .config
+                .chatroom_participants
+                .get(&chatroom.rowid)
+                .is_some_and(|participants| participants.len() > 1)
+    }
+
+    /// Count a message toward the contact sheet of the conversation it was written to
+    ///
+    /// The first message from each chat also adds that chat's members, so people who never wrote are listed too.
+    fn add_to_contact_sheet(&mut self, message: &Message) {
+        let Some((chatroom, _)) = self.config.conversation(message) else {
+            return;
+        };
+        if !self.has_contact_sheet(chatroom) {
+            return;
+        }
+        let sheet = self
+            .people
+            .entry(self.config.filename(chatroom))
+            .or_default();
+
+        if let Some(chat_id) = message.chat_id.or(message.deleted_from) {
+            if sheet.add_chat(chat_id) {
+                for handle_id in self
+                    .config
+                    .chatroom_participants
+                    .get(&chat_id)
+                    .into_iter()
+                    .flatten()
+                {
+                    let handle = self
+                        .config
+                        .participants
+                        .get(handle_id)
+                        .map_or("", String::as_str);
+                    sheet.add_participant(self.config.who(Some(*handle_id), false, &None), handle);
+                }
+            }
+        }
+
+        let name = self.config.who(
+            message.handle_id,
+            message.is_from_me,
+            &message.destination_caller_id,
+        );
+        let handle = match message
+            .handle_id
+            .and_then(|id| self.config.participants.get(&id))
+        {
+            Some(handle) if !message.is_from_me => handle.as_str(),
+            _ => message.destination_caller_id.as_deref().unwrap_or(""),
+        };
+        sheet.add_message(name, handle, message.date);
+    }
+
+    /// Write the contact sheet of each group conversation next to it, i.e. `Name-people.html`
+    fn write_contact_sheets(&mut self) -> Result<(), RuntimeError> {
+        let extension = self.config.file_extension("html");
+        for (filename, sheet) in &self.people {
+            let mut path = self.config.conversation_dir(filename)?;
+            path.push(HTML::contact_sheet_filename(filename, &extension));
+
+            let mut buf = self.config.open_export_file(&path)?;
+            HTML::write_headers(&mut buf, &self.config.options.output_encoding, None)?;
+            HTML::write_to_file(&mut buf, &self.format_contact_sheet(filename, sheet))?;
+            HTML::write_to_file(&mut buf, FOOTER)?;
+        }
+        Ok(())
+    }
+
+    /// Build the table of people shown on a contact sheet, with a link back to the conversation
+    fn format_contact_sheet(&self, filename: &str, sheet: &ContactSheet) -> String {
+        let extension = self.config.file_extension("html");
+        let name = filename.trim_end_matches(".html");
+        // Paginated conversations link back to their first page
+        let conversation = match self.config.options.messages_per_page {
+            Some(_) => format!("{name}-1.{extension}"),
+            None => format!("{name}.{extension}"),
+        };
+        let format_date = |date: Option<i64>| {
+            date.map(|date| {
+                self.config
+                    .format_date(&get_local_time(&date, &self.config.offset))
+            })
+            .unwrap_or_default()
+        };
+
+        let mut out_s = format!(
+            "<nav class=\"pages\"><a href=\"{conversation}\">&larr; {}</a></nav>\n",
+            sanitize_html(name)
+        );
+        out_s.push_str("<table class=\"people\">\n<tr><th>Name</th><th>Handle</th><th>Messages</th><th>First message</th><th>Last message</th></tr>\n");
+        for participant in sheet.participants() {
+            out_s.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                sanitize_html(&participant.name),
+                sanitize_html(&participant.handle),
+                participant.messages,
+                format_date(participant.first_date),
+                format_date(participant.last_date),
+            ));
+        }
+        out_s.push_str("</table>\n");
+        out_s
+    }
+
+    /// Build the filename for the contact sheet of a conversation, i.e. `Name-people.html`
+    fn contact_sheet_filename(filename: &str, extension: &str) -> String {
+        format!("{}-people.{extension}", filename.trim_end_matches(".html"))
+    }
+
+    /// Build the link from the top of a group conversation to its contact sheet
+    fn contact_sheet_link(filename: &str, extension: &str) -> String {
+        format!(
+            "<nav class=\"pages\"><a href=\"{}\">People</a></nav>\n",
+            HTML::contact_sheet_filename(filename, extension)
+        )
+    }
+
+    /// Build the name shown at the top of a conversation
+    fn format_title(title: &str) -> String {
+        format!(
+            "<h1 class=\"conversation_title\">{}</h1>\n",
+            sanitize_html(title)
+        )
+    }
+
+    /// Build the group photo shown at the top of a conversation
+    fn format_avatar(path: &str) -> String {
+        format!("<div class=\"avatar\"><img src=\"{path}\" alt=\"Group photo\"></div>\n")
+    }
+
+    /// Get the page number the next message in a paginated conversation belongs on
+    ///
+    /// If the current page is full, write the link to the next page and close it so a new one gets created
+    fn turn_page(&mut self, filename: &str, limit: usize) -> Result<usize, RuntimeError> {
+        let extension = self.config.file_extension("html");
+        let (page, count) = self.pages.entry(filename.to_string()).or_insert((1, 0));
+        if *count >= limit {
+            *page += 1;
+            *count = 0;
+            if let Some(mut buf) = self.files.remove(filename) {
+                HTML::write_to_file(
+                    &mut buf,
+                    &HTML::page_navigation(filename, *page, true, &extension),
+                )?;
+                HTML::write_to_file(&mut buf, FOOTER)?;
+            }
+        }
+        *count += 1;
+        Ok(*page)
+    }
+
+    /// Build the filename for a single page of a paginated conversation, i.e. `Name-2.html`
+    fn page_filename(filename: &str, page: usize, extension: &str) -> String {
+        format!("{}-{page}.{extension}", filename.trim_end_matches(".html"))
+    }
+
+    /// Build the navigation links between the pages of a paginated conversation
+    ///
+    /// Links placed at the end of a page point forward to `page`, otherwise they point back to the pages before it
+    fn page_navigation(filename: &str, page: usize, forward: bool, extension: &str) -> String {
+        if forward {
+            format!(
+                "<nav class=\"pages\"><a href=\"{}\">Page {page} &rarr;</a></nav>\n",
+                HTML::page_filename(filename, page, extension)
+            )
+        } else {
+            let mut links = String::from("<nav class=\"pages\">");
+            // Only link to the first page if it is not also the previous page
+            if page > 2 {
+                links.push_str(&format!(
+                    "<a href=\"{}\">&larr; Page 1</a> ",
+                    HTML::page_filename(filename, 1, extension)
+                ));
+            }
+            links.push_str(&format!(
+                "<a href=\"{}\">&larr; Page {}</a> <span>Page {page}</span></nav>\n",
+                HTML::page_filename(filename, page - 1, extension),
+                page - 1
+            ));
+            links
+        }
+    }
+
+    /// Build the id of a paginated conversation's entry in the index, i.e. `friends-1` for `Friends - 1.html`
+    fn page_anchor(filename: &str) -> String {
+        filename
+            .trim_end_matches(".html")
+            .split(|letter: char| !letter.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join("-")
+            .to_lowercase()
+    }
+
+    /// Build the link from a page of a paginated conversation to its entry in the index
+    ///
+    /// `root` is the path from the page to the root of the export
+    fn index_link(filename: &str, root: &str, extension: &str) -> String {
+        format!(
+            "<nav class=\"pages\"><a href=\"{root}{INDEX}.{extension}#{}\">All conversations</a></nav>\n",
+            HTML::page_anchor(filename)
+        )
+    }
+
+    /// Build the index of paginated conversations, with an entry that links to each page of each conversation
+    fn format_index(&self) -> Result<String, RuntimeError> {
+        let extension = self.config.file_extension("html");
+        let mut conversations: Vec<(&String, usize)> = self
+            .pages
+            .iter()
+            .map(|(filename, (pages, _))| (filename, *pages))
+            .collect();
+        conversations.sort();
+
+        let mut out_s = HTML::format_title("Conversations");
+        out_s.push_str("<ul class=\"index\">\n");
+        for (filename, pages) in conversations {
+            // Pages are linked relative to the index, which is at the root of the export
+            let dir = self.config.conversation_dir(filename)?;
+            let dir = dir
+                .strip_prefix(&self.config.options.export_path)
+                .unwrap_or(&dir);
+            let link = |page| {
+                dir.join(HTML::page_filename(filename, page, &extension))
+                    .display()
+                    .to_string()
+            };
+
+            out_s.push_str(&format!(
+                "<li id=\"{}\"><a href=\"{}\">{}</a> <nav class=\"pages\">",
+                HTML::page_anchor(filename),
+                link(1),
+                sanitize_html(filename.trim_end_matches(".html"))
+            ));
+            for page in 1..=pages {
+                out_s.push_str(&format!("<a href=\"{}\">{page}</a>", link(page)));
+            }
+            out_s.push_str("</nav></li>\n");
+        }
+        out_s.push_str("</ul>\n");
+        Ok(out_s)
+    }
+
+    /// Write the index of paginated conversations to the root of the export, i.e. `index.html`
+    fn write_index(&self) -> Result<(), RuntimeError> {
+        let path = self
+            .config
+            .options
+            .export_path
+            .join(INDEX)
+            .with_extension(self.config.file_extension("html"));
+        let mut buf = self.config.open_export_file(&path)?;
+        HTML::write_headers(
+            &mut buf,
+            &self.config.options.output_encoding,
+            Some("Conversations"),
+        )?;
+        HTML::write_to_file(&mut buf, &self.format_index()?)?;
+        HTML::write_to_file(&mut buf, FOOTER)
+    }
+
     fn edited_to_html(&self, timestamp: &str, text: &str, last: bool) -> String {
         let tag = if last { "tfoot" } else { "tbody" };
         format!("<{tag}><tr><td><span class=\"timestamp\">{timestamp}</span></td><td>{text}</td></tr></{tag}>")
@@ -1511,7 +2235,11 @@ impl HTML<'_> {
 
         // Name
         out_s.push_str("<div class=\"name\">");
-        out_s.push_str(balloon.app_name.unwrap_or(bundle_id));
+        out_s.push_str(
+            balloon
+                .app_name
+                .unwrap_or(CustomBalloon::Application(bundle_id).name()),
+        );
         out_s.push_str("</div>");
 
         // Title
@@ -1586,12 +2314,19 @@ impl HTML<'_> {
 #[cfg(test)]
 mod tests {
     use std::{
-        env::{current_dir, set_var},
+        collections::HashMap,
+        env::{current_dir, set_var, temp_dir},
+        fs::{create_dir_all, read_dir, read_to_string, remove_dir_all, write},
         path::PathBuf,
     };
 
     use crate::{
-        app::export_type::ExportType, exporters::exporter::Writer, Config, Exporter, Options, HTML,
+        app::export_type::ExportType,
+        exporters::{
+            exporter::Writer,
+            html::{ARCHIVE, ARCHIVE_SECTIONS},
+        },
+        Config, Exporter, Options, HTML,
     };
     use imessage_database::{
         tables::{messages::models::AttachmentMeta, table::ME},
@@ -1606,6 +2341,158 @@ mod tests {
         assert_eq!(exporter.files.len(), 0);
     }
 
+    #[test]
+    fn can_write_contact_sheets() {
+        let export_path = temp_dir().join("imessage-contact-sheets");
+        let _ = remove_dir_all(&export_path);
+        create_dir_all(&export_path).unwrap();
+
+        let mut options = Options::fake_options(ExportType::Html);
+        options.db_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+        options.export_path = export_path.clone();
+        options.contact_sheets = true;
+        let config = Config::new(options).unwrap();
+
+        // Drop the exporter so every file is flushed before it is read
+        {
+            let mut exporter = HTML::new(&config).unwrap();
+            exporter.iter_messages().unwrap();
+        }
+
+        // Only the group chat gets a contact sheet
+        let sheets: Vec<String> = read_dir(&export_path)
+            .unwrap()
+            .filter_map(|entry| entry.unwrap().file_name().into_string().ok())
+            .filter(|name| name.ends_with("-people.html"))
+            .collect();
+        assert_eq!(sheets, vec!["Book Club - 2-people.html"]);
+
+        let conversation = read_to_string(export_path.join("Book Club - 2.html")).unwrap();
+        assert!(conversation.contains("<a href=\"Book Club - 2-people.html\">People</a>"));
+
+        // Every member is listed, including the one who never wrote
+        let sheet = read_to_string(export_path.join("Book Club - 2-people.html")).unwrap();
+        assert!(sheet.contains("<a href=\"Book Club - 2.html\">"));
+        assert!(sheet.contains("<td>+15555550102</td><td>1</td>"));
+        assert!(sheet.contains("<td>+15555550101</td><td>0</td>"));
+        assert!(sheet.contains("<td>carol@example.com</td><td>0</td>"));
+        assert!(sheet.contains(&format!("<td>{ME}</td>")));
+
+        remove_dir_all(&export_path).unwrap();
+    }
+
+    #[test]
+    fn can_get_page_filename() {
+        assert_eq!(
+            HTML::page_filename("Friends - 1.html", 2, "html"),
+            "Friends - 1-2.html"
+        );
+    }
+
+    #[test]
+    fn can_get_compressed_page_filename() {
+        assert_eq!(
+            HTML::page_filename("Friends - 1.html", 2, "html.gz"),
+            "Friends - 1-2.html.gz"
+        );
+    }
+
+    #[test]
+    fn can_format_avatar() {
+        assert_eq!(
+            HTML::format_avatar("attachments/0/12.jpeg"),
+            "<div class=\"avatar\"><img src=\"attachments/0/12.jpeg\" alt=\"Group photo\"></div>\n"
+        );
+    }
+
+    #[test]
+    fn can_format_title() {
+        assert_eq!(
+            HTML::format_title("Tom & Jerry"),
+            "<h1 class=\"conversation_title\">Tom &amp; Jerry</h1>\n"
+        );
+    }
+
+    #[test]
+    fn can_format_page_navigation_forward() {
+        assert_eq!(
+            HTML::page_navigation("Friends.html", 2, true, "html"),
+            "<nav class=\"pages\"><a href=\"Friends-2.html\">Page 2 &rarr;</a></nav>\n"
+        );
+    }
+
+    #[test]
+    fn can_format_page_navigation_second_page() {
+        assert_eq!(
+            HTML::page_navigation("Friends.html", 2, false, "html"),
+            "<nav class=\"pages\"><a href=\"Friends-1.html\">&larr; Page 1</a> <span>Page 2</span></nav>\n"
+        );
+    }
+
+    #[test]
+    fn can_format_page_navigation_later_page() {
+        assert_eq!(
+            HTML::page_navigation("Friends.html", 3, false, "html"),
+            "<nav class=\"pages\"><a href=\"Friends-1.html\">&larr; Page 1</a> <a href=\"Friends-2.html\">&larr; Page 2</a> <span>Page 3</span></nav>\n"
+        );
+    }
+
+    #[test]
+    fn can_format_compressed_page_navigation() {
+        assert_eq!(
+            HTML::page_navigation("Friends.html", 2, false, "html.gz"),
+            "<nav class=\"pages\"><a href=\"Friends-1.html.gz\">&larr; Page 1</a> <span>Page 2</span></nav>\n"
+        );
+    }
+
+    #[test]
+    fn can_turn_page() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.messages_per_page = Some(2);
+        let config = Config::fake_app(options);
+        let mut exporter = HTML::new(&config).unwrap();
+
+        assert_eq!(exporter.turn_page("Friends.html", 2).unwrap(), 1);
+        assert_eq!(exporter.turn_page("Friends.html", 2).unwrap(), 1);
+        assert_eq!(exporter.turn_page("Friends.html", 2).unwrap(), 2);
+        assert_eq!(exporter.turn_page("Family.html", 2).unwrap(), 1);
+    }
+
+    #[test]
+    fn can_get_page_anchor() {
+        assert_eq!(HTML::page_anchor("Friends - 1.html"), "friends-1");
+        assert_eq!(HTML::page_anchor("+15555550101.html"), "15555550101");
+    }
+
+    #[test]
+    fn can_format_index_link() {
+        assert_eq!(
+            HTML::index_link("Friends.html", "../", "html"),
+            "<nav class=\"pages\"><a href=\"../index.html#friends\">All conversations</a></nav>\n"
+        );
+    }
+
+    #[test]
+    fn can_format_index() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.messages_per_page = Some(2);
+        let config = Config::fake_app(options);
+        let mut exporter = HTML::new(&config).unwrap();
+
+        for _ in 0..3 {
+            exporter.turn_page("Friends.html", 2).unwrap();
+        }
+        exporter.turn_page("Family.html", 2).unwrap();
+
+        assert_eq!(
+            exporter.format_index().unwrap(),
+            "<h1 class=\"conversation_title\">Conversations</h1>\n<ul class=\"index\">\n<li id=\"family\"><a href=\"Family-1.html\">Family</a> <nav class=\"pages\"><a href=\"Family-1.html\">1</a></nav></li>\n<li id=\"friends\"><a href=\"Friends-1.html\">Friends</a> <nav class=\"pages\"><a href=\"Friends-1.html\">1</a><a href=\"Friends-2.html\">2</a></nav></li>\n</ul>\n"
+        );
+    }
+
     #[test]
     fn can_get_time_valid() {
         // Set timezone to PST for consistent Local time
@@ -1653,6 +2540,28 @@ mod tests {
         assert_eq!(exporter.get_time(&message), "May 17, 2022  6:30:31 PM");
     }
 
+    #[test]
+    fn can_get_time_failed() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = Options::fake_options(ExportType::Html);
+        let config = Config::fake_app(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        // Create fake message
+        let mut message = Config::fake_message();
+        // May 17, 2022  9:30:31 PM
+        message.date = 674530231992568192;
+        message.is_from_me = true;
+        message.error = 22;
+        assert_eq!(
+            exporter.get_time(&message),
+            "May 17, 2022  6:30:31 PM (Not Delivered)"
+        );
+    }
+
     #[test]
     fn can_add_line_no_indent() {
         // Create exporter
@@ -1741,6 +2650,98 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_html_message_with_placed_sticker() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = Options::fake_options(ExportType::Html);
+        let mut config = Config::fake_app(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+
+        let mut sticker = Config::fake_message();
+        sticker.associated_message_type = Some(1000);
+        sticker.associated_message_guid =
+            Some("p:0/A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+        sticker.handle_id = Some(999999);
+
+        let mut tapback = Config::fake_message();
+        tapback.associated_message_type = Some(2000);
+        tapback.associated_message_guid =
+            Some("p:0/A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+        tapback.handle_id = Some(999999);
+
+        config.tapbacks.insert(
+            "A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string(),
+            HashMap::from([(0, vec![sticker, tapback])]),
+        );
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.guid = "A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string();
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n<div class=\"placed_stickers\"><div class=\"placed_sticker\"><span class=\"tapback\">Sticker from Sample Contact not found!</span></div>\n</div>\n</div>\n<div class=\"tapbacks\"><hr><p>Tapbacks:</p>\n<div class=\"tapback\"><span class=\"tapback\"><b>Loved</b> by Sample Contact</span></div>\n</div>\n</div>\n</div>\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_html_from_me_subject() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = Options::fake_options(ExportType::Html);
+        let config = Config::fake_app(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.subject = Some("Greetings".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<p><b>Subject:</b> <span class=\"subject\">Greetings</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_html_from_me_empty_subject() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = Options::fake_options(ExportType::Html);
+        let config = Config::fake_app(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.subject = Some(String::new());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_html_from_me_normal_deleted() {
         // Set timezone to PST for consistent Local time
@@ -1876,7 +2877,7 @@ mod tests {
     }
 
     #[test]
-    fn can_format_html_shareplay() {
+    fn can_format_html_facetime() {
         // Set timezone to PST for consistent Local time
         set_var("TZ", "PST");
 
@@ -1893,7 +2894,7 @@ mod tests {
         message.item_type = 6;
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"received\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<span class=\"shareplay\"><hr>SharePlay Message Ended</span>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\">\n<div class=\"received\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<span class=\"system_event\"><hr>FaceTime call, incoming</span>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
@@ -2313,6 +3314,53 @@ mod tests {
 
         assert_eq!(actual, "<div><audio controls src=\"Audio Message.caf\" type=\"x-caf; codecs=opus\" </audio></div> <hr><span class=\"transcription\">Transcription: Test</span>");
     }
+
+    #[test]
+    fn can_write_archive() {
+        let export_path = temp_dir().join("imessage-html-archive");
+        let _ = remove_dir_all(&export_path);
+        create_dir_all(&export_path).unwrap();
+
+        let mut options = Options::fake_options(ExportType::Html);
+        options.export_path = export_path.clone();
+        options.single_file = true;
+        let config = Config::fake_app(options);
+
+        let mut exporter = HTML::new(&config).unwrap();
+        HTML::write_to_file(exporter.orphaned.as_mut().unwrap(), "<p>Hello</p>\n").unwrap();
+        exporter.write_archive().unwrap();
+
+        let archive = read_to_string(export_path.join(ARCHIVE).with_extension("html")).unwrap();
+        assert!(archive
+            .contains("<section class=\"conversation\" id=\"c-0\">\n<p>Hello</p>\n</section>\n"));
+        // The sections are removed once they are combined
+        assert!(!export_path.join(ARCHIVE_SECTIONS).exists());
+
+        remove_dir_all(&export_path).unwrap();
+    }
+
+    #[test]
+    fn can_embed_converted_attachment() {
+        let dir = temp_dir().join("imessage-html-embed-converted");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("1.jpeg");
+        write(&path, [0xFF, 0xD8, 0xFF]).unwrap();
+
+        let options = Options::fake_options(ExportType::Html);
+        let config = Config::fake_app(options);
+
+        // The original was a HEIC image, but the copy was converted to JPEG
+        let mut attachment = Config::fake_attachment();
+        attachment.mime_type = Some("image/heic".to_string());
+        attachment.copied_path = Some(path);
+
+        assert_eq!(
+            HTML::embed(&config, &attachment).unwrap(),
+            "data:image/jpeg;base64,/9j/"
+        );
+
+        remove_dir_all(&dir).unwrap();
+    }
 }
 
 #[cfg(test)]
@@ -2864,6 +3912,20 @@ mod text_effect_tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_html_mention_rich_text() {
+        // Create exporter
+        let mut options = Options::fake_options(crate::app::export_type::ExportType::Html);
+        options.rich_text = true;
+        let config = Config::fake_app(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let actual = exporter.format_mention("Dad", "+15558675309");
+        let expected = "<span title=\"+15558675309\"><b>@Dad</b></span>";
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_html_otp_end_to_end() {
         // Set timezone to PST for consistent Local time
@@ -1,16 +1,38 @@
-use crate::app::runtime::Config;
+use crate::app::compatibility::converters::metadata::{media_created, MediaCreated};
+use crate::app::conversation_order::ConversationStats;
 use crate::app::error::RuntimeError;
-use serde_json::json;
-use imessage_database::tables::messages::Message;
-use rusqlite::Error as RusqliteError;
-use imessage_database::error::table::TableError;
+use crate::app::export_file::ExportFile;
+use crate::app::options::OPTION_NO_ORPHANED;
+use crate::app::reactions::ReactionCounts;
+use crate::app::runtime::Config;
+use crate::app::stats::MessageStats;
+use crate::app::vcard::read_contact_card;
+use crate::exporters::exporter::{
+    generate_text_or_placeholder, link_title, redact_attachments, reply_context, summarize_app,
+    Previewer,
+};
 use crate::Exporter;
+use chrono::Local;
+use imessage_database::error::table::TableError;
+use imessage_database::message_types::variants::SystemEvent;
+use imessage_database::tables::table::Table;
+use imessage_database::tables::{
+    attachment::{Attachment, MediaType},
+    chat::{Chat, ReadState},
+    messages::{
+        models::{BubbleComponent, Service},
+        Message,
+    },
+    table::ORPHANED,
+};
+use imessage_database::util::dates::{get_local_time, DEFAULT_DATE_FORMAT};
+use log::{info, warn};
+use rusqlite::Error as RusqliteError;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::{BufWriter, Write};
-use std::collections::HashMap;
-use crate::app::progress::build_progress_bar_export;
-use imessage_database::tables::table::Table;
-use imessage_database::util::dates::{format, get_local_time};
+use std::path::Path;
 
 impl From<RusqliteError> for RuntimeError {
     fn from(err: RusqliteError) -> RuntimeError {
@@ -35,9 +57,13 @@ pub struct JSONExporter<'a> {
     pub config: &'a Config,
     /// Handles to files we want to write messages to
     /// Map of resolved chatroom file location to a buffered writer
-    pub files: HashMap<String, BufWriter<File>>,
-    /// Writer instance for orphaned messages
-    pub orphaned: BufWriter<File>,
+    pub files: BTreeMap<String, BufWriter<ExportFile>>,
+    /// Writer instance for orphaned messages, unless they are dropped
+    pub orphaned: Option<BufWriter<ExportFile>>,
+    /// Tally of the tapbacks in each conversation, written to `conversations.json`
+    pub reactions: ReactionCounts,
+    /// What each participant wrote in each conversation, written to `conversations.json` if requested
+    pub stats: MessageStats,
 }
 
 impl<'a> JSONExporter<'a> {
@@ -66,97 +92,1195 @@ impl<'a> JSONExporter<'a> {
             if ts == 0 {
                 "N/A".to_string()
             } else {
-                format(&get_local_time(&ts, &self.config.offset))
+                self.config.format_date_as(
+                    &get_local_time(&ts, &self.config.offset),
+                    DEFAULT_DATE_FORMAT,
+                )
             }
         };
 
-        json!({
+        // Messages that are only a link read better as the title of the link's preview
+        let preview_title = if self.config.options.redact_attachments {
+            None
+        } else {
+            link_title(message, &self.config.db)
+        };
+
+        let mut json_message = json!({
             "timestamp": format_timestamp(message.date),
             "sender": sender,
             "receiver": receiver,
-            "message": message.text,
+            "message": if self.config.options.redact_attachments {
+                redact_attachments(message)
+            } else {
+                preview_title.clone().or_else(|| message.text.clone())
+            },
             "conversation_id": message.chat_id.unwrap_or_default().to_string(),
             "guid": message.guid,
             "service": message.service,
             "is_read": message.is_read,
             "date_read": format_timestamp(message.date_read),
             "date_delivered": format_timestamp(message.date_delivered),
+            "delivery_status": message.delivery_status().to_string(),
             "deleted": message.is_deleted(),
+        });
+
+        // Only include the subject if the message has one
+        if let Some(subject) = message.subject.as_deref().filter(|s| !s.is_empty()) {
+            json_message["subject"] = json!(subject);
+        }
+
+        // Keep the link itself when its title is used as the text
+        if preview_title.is_some() {
+            json_message["url"] = json!(message.text);
+        }
+
+        // Replies point to the message they responded to, quoting it if requested
+        if let Some(reply_to) = &message.thread_originator_guid {
+            json_message["reply_to"] = json!(reply_to);
+            if let Some(quote) = self.config.options.quote_replies.and_then(|max_length| {
+                reply_context(message, &self.config.db, self.config.schema, max_length)
+            }) {
+                json_message["reply_to_text"] = json!(quote);
+            }
+        }
+
+        // SMS messages keep the carrier details that iMessages do not have
+        if let Some(sms) = self.format_sms(message) {
+            json_message["sms"] = sms;
+        }
+
+        // System events have no text of their own, so record what happened
+        if let Some(event) = self.format_system_event(message) {
+            json_message["system_event"] = event;
+        }
+
+        // App messages have no text of their own, so describe what the app sent
+        if let Some(app_message) = summarize_app(message, &self.config.db) {
+            json_message["app_message"] = json!(app_message);
+        }
+
+        // Links point to their saved preview image, if the payload has one
+        if let Some(link_preview) = self.config.link_preview(message) {
+            json_message["link_preview"] = json!(link_preview);
+        }
+
+        // Contact cards are read into the contact they share, if requested
+        if let Some(shared_contact) = self.shared_contact(message) {
+            json_message["shared_contact"] = shared_contact;
+        }
+
+        // Photos and videos keep when they were taken, if requested
+        if let Some(media_created) = self.media_created(message) {
+            json_message["media_created"] = media_created;
+        }
+
+        // Stickers placed on another message point to the message they were placed on
+        if let Some(placed_on) = message.placed_on() {
+            json_message["placed_on"] = json!(placed_on);
+        }
+
+        // Include the structure of the message body, if requested
+        if self.config.options.rich_text {
+            json_message["mentions"] = message
+                .mentions()
+                .iter()
+                .map(|mention| {
+                    json!({
+                        "text": mention.text,
+                        "handle": mention.handle,
+                        "start": mention.start,
+                        "end": mention.end,
+                    })
+                })
+                .collect();
+        }
+
+        // List every file sent in the message, which may be several photos sent together
+        if !self.config.options.redact_attachments
+            && (message.has_attachments() || self.config.options.rich_text)
+        {
+            json_message["attachments"] = self.format_attachments(message);
+        }
+
+        json_message
+    }
+
+    /// Build the `attachments` list of every file sent in a message, in the order they appear
+    ///
+    /// Attachments are copied like in the other formats, so `path` points to the copy if there is one. With
+    /// `--rich-text`, each one also has the transcription the message body stores for it.
+    fn format_attachments(&self, message: &Message) -> serde_json::Value {
+        let body = message.body();
+        let transcriptions: HashMap<&str, &str> = body
+            .iter()
+            .filter_map(|component| match component {
+                BubbleComponent::Attachment(meta) => Some((meta.guid?, meta.transcription?)),
+                _ => None,
+            })
+            .collect();
+        let attachments = match Attachment::from_message(&self.config.db, message) {
+            Ok(attachments) => attachments,
+            Err(why) => {
+                self.config.record_error(why);
+                return json!([]);
+            }
+        };
+        attachments
+            .into_iter()
+            .map(|mut attachment| {
+                // Copy the file, if requested; a file that cannot be copied keeps the path it is stored at
+                self.config.options.attachment_manager.handle_attachment(
+                    message,
+                    &mut attachment,
+                    self.config,
+                );
+                let mut formatted = json!({
+                    "guid": attachment.guid,
+                    "name": attachment.filename(),
+                    "mime_type": attachment.mime_type,
+                    "path": self.config.message_attachment_path(&attachment),
+                });
+                if self.config.options.rich_text {
+                    formatted["transcription"] = json!(attachment
+                        .guid
+                        .as_deref()
+                        .and_then(|guid| transcriptions.get(guid)));
+                }
+                formatted
+            })
+            .collect()
+    }
+
+    /// Build the `shared_contact` object for the first contact card attached to a message
+    ///
+    /// Cards that cannot be read are left as plain attachments, so there is no object for them.
+    fn shared_contact(&self, message: &Message) -> Option<serde_json::Value> {
+        if !self.config.options.parse_contact_cards
+            || self.config.options.redact_attachments
+            || !message.has_attachments()
+        {
+            return None;
+        }
+        Attachment::from_message(&self.config.db, message)
+            .ok()?
+            .iter()
+            .filter(|attachment| {
+                matches!(attachment.mime_type(), MediaType::Text("vcard" | "x-vcard"))
+                    || attachment.extension() == Some("vcf")
+            })
+            .filter_map(|attachment| {
+                attachment.resolved_attachment_path(
+                    &self.config.options.platform,
+                    &self.config.options.db_path,
+                    self.config.options.attachment_root.as_deref(),
+                )
+            })
+            .find_map(|path| read_contact_card(Path::new(&path)))
+            .map(|card| {
+                json!({
+                    "name": card.display_name(),
+                    "phones": card.phones,
+                    "emails": card.emails,
+                })
+            })
+    }
+
+    /// Build the `media_created` list of when each photo or video attached to a message was taken
+    ///
+    /// Attachments whose metadata has no creation date, or cannot be read, are left out.
+    fn media_created(&self, message: &Message) -> Option<serde_json::Value> {
+        if !self.config.options.media_dates
+            || self.config.options.redact_attachments
+            || !message.has_attachments()
+        {
+            return None;
+        }
+        let manager = &self.config.options.attachment_manager;
+        let dates: Vec<serde_json::Value> = Attachment::from_message(&self.config.db, message)
+            .ok()?
+            .iter()
+            .filter_map(|attachment| {
+                let path = attachment.resolved_attachment_path(
+                    &self.config.options.platform,
+                    &self.config.options.db_path,
+                    self.config.options.attachment_root.as_deref(),
+                )?;
+                let date = match media_created(
+                    Path::new(&path),
+                    &attachment.mime_type(),
+                    manager.image_converter.as_ref(),
+                    manager.video_probe.as_ref(),
+                )? {
+                    // Photos have no time zone, so the camera's clock is written as it was
+                    MediaCreated::Camera(date) => date.format(DEFAULT_DATE_FORMAT).to_string(),
+                    MediaCreated::Utc(date) => self
+                        .config
+                        .format_date_as(&Ok(date.with_timezone(&Local)), DEFAULT_DATE_FORMAT),
+                };
+                Some(json!({
+                    "attachment_guid": attachment.guid,
+                    "name": attachment.filename(),
+                    "date": date,
+                }))
+            })
+            .collect();
+        (!dates.is_empty()).then(|| json!(dates))
+    }
+
+    /// Build the `sms` object for a message sent over SMS, or `None` for any other service
+    ///
+    /// Messages are MMS if they have attachments or a subject, or if they were sent to a group;
+    /// group MMS messages also list everyone they were sent to.
+    fn format_sms(&self, message: &Message) -> Option<serde_json::Value> {
+        if !matches!(message.service(), Service::SMS) {
+            return None;
+        }
+        let recipients = message
+            .chat_id
+            .map(|chat_id| self.config.conversation_participants(&chat_id))
+            .unwrap_or_default();
+        let is_group = recipients.len() > 1;
+        let has_subject = message.subject.as_deref().is_some_and(|s| !s.is_empty());
+
+        let mut sms = json!({
+            "service_center": message.service_center,
+            "mms": message.has_attachments() || has_subject || is_group,
+            "downgraded": message.was_downgraded,
+        });
+        if is_group {
+            sms["recipients"] = json!(recipients);
+        }
+        Some(sms)
+    }
+}
+
+impl<'a> JSONExporter<'a> {
+    /// Build the record of a system event, i.e. a FaceTime call
+    ///
+    /// `chat.db` does not store how long calls lasted, so FaceTime calls have no duration.
+    fn format_system_event(&self, message: &Message) -> Option<serde_json::Value> {
+        Some(match message.get_system_event()? {
+            SystemEvent::FaceTime(direction) => json!({
+                "type": "facetime",
+                "direction": direction.to_string(),
+                "participants": message
+                    .chat_id
+                    .map(|chat_id| self.config.conversation_participants(&chat_id))
+                    .unwrap_or_default(),
+            }),
+            SystemEvent::Unknown(item_type) => json!({
+                "type": "unknown",
+                "item_type": item_type,
+            }),
         })
     }
+
+    /// Build the entry for a conversation in `conversations.json`
+    ///
+    /// Participants are always an array of resolved names, even if the conversation has only one.
+    /// `read_states` are the merged states from [`JSONExporter::conversation_read_states()`].
+    fn format_conversation(
+        &self,
+        chat_id: &i32,
+        read_states: &HashMap<i32, ReadState>,
+    ) -> Option<serde_json::Value> {
+        let chatroom = self.config.chatrooms.get(chat_id)?;
+        let read_state = read_states.get(chat_id).copied().unwrap_or_default();
+        let mut conversation = json!({
+            "conversation_id": chat_id.to_string(),
+            "file": self.config.filename(chatroom),
+            "participants": self.config.conversation_participants(chat_id),
+            "unread": read_state.unread,
+            "reactions": self.reactions.get(chat_id),
+        });
+        if let Some(stats) = self.stats.get(chat_id) {
+            conversation["stats"] = json!(stats);
+        }
+        if let Some(name) = self.config.chat_name(chatroom) {
+            conversation["name"] = json!(name);
+        }
+        if let Some(last_read) = read_state.last_read {
+            conversation["last_read"] = json!(self.config.format_date_as(
+                &get_local_time(&last_read, &self.config.offset),
+                DEFAULT_DATE_FORMAT
+            ));
+        }
+        if let Some(avatar) = self.config.conversation_avatar(chatroom) {
+            conversation["avatar"] = json!(self.avatar_path(&avatar));
+        }
+        Some(conversation)
+    }
+
+    /// Combine the read states of the chats merged into each conversation, keyed by chat ID
+    ///
+    /// The date the chat itself was last read is preferred; if the database does not store it,
+    /// the date the most recent message was read is used instead.
+    fn conversation_read_states(
+        &self,
+        read_states: &HashMap<i32, ReadState>,
+    ) -> HashMap<i32, ReadState> {
+        // Chats that share a real chatroom are one conversation; a chat without one is its own
+        let conversation = |chat_id: &i32| match self.config.real_chatrooms.get(chat_id) {
+            Some(real_id) => (true, *real_id),
+            None => (false, *chat_id),
+        };
+
+        let mut merged: HashMap<(bool, i32), (ReadState, Option<i64>)> = HashMap::new();
+        for chat in self.config.chatrooms.values() {
+            let (state, chat_last_read) = merged.entry(conversation(&chat.rowid)).or_default();
+            if let Some(read_state) = read_states.get(&chat.rowid) {
+                state.unread += read_state.unread;
+                state.last_read = state.last_read.max(read_state.last_read);
+            }
+            *chat_last_read = (*chat_last_read).max(chat.last_read);
+        }
+
+        self.config
+            .chatrooms
+            .keys()
+            .filter_map(|chat_id| {
+                let (state, chat_last_read) = merged.get(&conversation(chat_id))?;
+                Some((
+                    *chat_id,
+                    ReadState {
+                        unread: state.unread,
+                        last_read: chat_last_read.or(state.last_read),
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    /// Get the path to a conversation's group photo relative to `conversations.json`, which is written to the root of the export
+    fn avatar_path(&self, avatar: &Attachment) -> String {
+        match avatar
+            .copied_path
+            .as_deref()
+            .map(|path| path.strip_prefix(&self.config.options.export_path))
+        {
+            Some(Ok(relative_path)) => relative_path.display().to_string(),
+            _ => self.config.message_attachment_path(avatar),
+        }
+    }
+
+    /// Write `conversations.json`, which lists the participants of each exported conversation in the given order
+    fn write_conversations(&self, chat_ids: &[i32]) -> Result<(), RuntimeError> {
+        let read_states = self.conversation_read_states(&Chat::read_states(&self.config.db)?);
+        let conversations: Vec<serde_json::Value> = chat_ids
+            .iter()
+            .filter_map(|chat_id| self.format_conversation(chat_id, &read_states))
+            .collect();
+
+        let path = self
+            .config
+            .options
+            .export_path
+            .join("conversations")
+            .with_extension(self.config.file_extension("json"));
+        let mut file = File::create(&path)
+            .and_then(|file| ExportFile::new(file, self.config.options.compress))
+            .map_err(|err| RuntimeError::CreateError(err, path))?;
+        writeln!(file, "{}", serde_json::to_string(&conversations).unwrap())?;
+        Ok(())
+    }
+}
+
+impl JSONExporter<'_> {
+    /// Get the conversations in the order selected by `--sort-conversations`, where `None` is the orphaned messages
+    fn sort_conversations(
+        &self,
+        conversation_map: &BTreeMap<Option<i32>, Vec<(i64, serde_json::Value)>>,
+    ) -> Vec<Option<i32>> {
+        let mut conversations: Vec<(Option<i32>, String, i64, usize)> = conversation_map
+            .iter()
+            .map(|(chat_id, messages)| {
+                let name = chat_id
+                    .and_then(|chat_id| self.config.chatrooms.get(&chat_id))
+                    .map_or_else(
+                        || ORPHANED.to_string(),
+                        |chatroom| self.config.filename(chatroom),
+                    );
+                let last_date = messages
+                    .iter()
+                    .map(|(date, _)| *date)
+                    .max()
+                    .unwrap_or_default();
+                (*chat_id, name, last_date, messages.len())
+            })
+            .collect();
+
+        let order = &self.config.options.sort_conversations;
+        conversations.sort_by(
+            |(_, a_name, a_date, a_count), (_, b_name, b_date, b_count)| {
+                order.compare(
+                    &ConversationStats {
+                        name: a_name,
+                        last_date: *a_date,
+                        messages: *a_count,
+                    },
+                    &ConversationStats {
+                        name: b_name,
+                        last_date: *b_date,
+                        messages: *b_count,
+                    },
+                )
+            },
+        );
+        conversations
+            .into_iter()
+            .map(|(chat_id, ..)| chat_id)
+            .collect()
+    }
+}
+
+/// Sort a conversation's messages by date, returning whether they were out of order
+///
+/// Messages are streamed in chronological order, so this is only a safeguard; messages with the same date keep their order.
+fn sort_by_date(messages: &mut [(i64, serde_json::Value)]) -> bool {
+    if messages.windows(2).all(|pair| pair[0].0 <= pair[1].0) {
+        return false;
+    }
+    messages.sort_by_key(|(date, _)| *date);
+    true
 }
 
 impl<'a> Exporter<'a> for JSONExporter<'a> {
     fn new(config: &'a Config) -> Result<Self, RuntimeError> {
-        let mut orphaned = config.options.export_path.clone();
-        orphaned.push("orphaned");
-        orphaned.set_extension("json");
-        let file = File::options()
-            .append(true)
-            .create(true)
-            .open(&orphaned)
-            .map_err(|err| RuntimeError::CreateError(err, orphaned.clone()))?;
+        let orphaned = if config.options.no_orphaned {
+            None
+        } else {
+            let mut orphaned = config.conversation_dir(ORPHANED)?;
+            orphaned.push(ORPHANED);
+            orphaned.set_extension(config.file_extension("json"));
+            Some(config.open_export_file(&orphaned)?)
+        };
 
         Ok(JSONExporter {
             config,
-            files: HashMap::new(),
-            orphaned: BufWriter::new(file),
+            files: BTreeMap::new(),
+            orphaned,
+            reactions: ReactionCounts::default(),
+            stats: MessageStats::default(),
         })
     }
 
     fn iter_messages(&mut self) -> Result<(), RuntimeError> {
-        eprintln!(
+        info!(
             "Exporting to {} as json...",
             self.config.options.export_path.display()
         );
 
         let total_messages = Message::get_count(&self.config.db, &self.config.options.query_context)?;
-        let pb = build_progress_bar_export(total_messages);
+        let pb = self.config.progress_bar(total_messages);
 
         let mut statement = Message::stream_rows(&self.config.db, &self.config.options.query_context)?;
 
         let messages = statement.query_map([], |row| Ok(Message::from_row(row)))?;
 
-        let mut conversation_map: HashMap<Option<i32>, Vec<serde_json::Value>> = HashMap::new();
+        // Messages are kept with their dates so each conversation's order can be verified before it is written
+        let mut conversation_map: BTreeMap<Option<i32>, Vec<(i64, serde_json::Value)>> =
+            BTreeMap::new();
+        let mut dropped_messages = 0;
+        let mut skipped_messages = 0;
+        let mut short_messages = 0;
 
         for message in messages {
-            let mut msg = Message::extract(message)?;
+            let mut msg = match Message::extract(message) {
+                Ok(msg) => msg,
+                Err(why) => {
+                    self.config.handle_error(RuntimeError::DatabaseError(why))?;
+                    pb.inc(1);
+                    continue;
+                }
+            };
 
-            let _ = msg.generate_text(&self.config.db);
+            // Drop messages that do not belong to a conversation, if requested
+            if self.config.options.no_orphaned && self.config.is_orphaned(&msg) {
+                dropped_messages += 1;
+                pb.inc(1);
+                continue;
+            }
+
+            // Skip messages that a previous export already wrote, if requested
+            if self.config.already_exported(&msg) {
+                skipped_messages += 1;
+                pb.inc(1);
+                continue;
+            }
+
+            generate_text_or_placeholder(&mut msg, &self.config.db);
+
+            // Skip messages that are too short, which is only known once their text is generated
+            if self.config.is_too_short(&msg) {
+                short_messages += 1;
+                pb.inc(1);
+                continue;
+            }
+
+            // Note when the message was delivered and read, and list it on its own if it is a tapback, if requested
+            self.config.record_receipt(&msg);
+            self.config.record_reaction(&msg);
 
             let json_message = self.format_custom(&msg);
 
-            conversation_map.entry(msg.chat_id).or_default().push(json_message);
+            // Deleted messages are grouped with the conversation they were deleted from, and merged chats share a conversation
+            let chat_id = self
+                .config
+                .conversation(&msg)
+                .map(|(chatroom, _)| chatroom.rowid);
+            if let Some(chat_id) = chat_id {
+                self.reactions.add(chat_id, &msg);
+                if self.config.options.stats {
+                    let sender = self.config.who(
+                        msg.handle_id,
+                        msg.is_from_me(),
+                        &msg.destination_caller_id,
+                    );
+                    self.stats.add(chat_id, sender, &msg);
+                }
+            }
+            conversation_map
+                .entry(chat_id)
+                .or_default()
+                .push((msg.date, json_message));
 
             pb.inc(1);
         }
         pb.finish();
 
-        for (chat_id, messages_array) in conversation_map {
-            let writer = self.get_or_create_file(&Message { chat_id, ..Default::default() })?;
-            writeln!(writer, "{}", serde_json::to_string(&messages_array).unwrap())?;
+        if dropped_messages > 0 {
+            info!("Dropped {dropped_messages} messages that do not belong to a conversation");
+        }
+        if skipped_messages > 0 {
+            info!("Skipped {skipped_messages} messages that a previous export already wrote");
+        }
+        if short_messages > 0 {
+            info!("Skipped {short_messages} messages that are too short");
+        }
+
+        let order = self.sort_conversations(&conversation_map);
+        let chat_ids: Vec<i32> = order.iter().flatten().copied().collect();
+        if let Err(why) = self.write_conversations(&chat_ids) {
+            self.config.handle_error(why)?;
+        }
+
+        for chat_id in order {
+            let mut messages = conversation_map.remove(&chat_id).unwrap_or_default();
+            if sort_by_date(&mut messages) {
+                match chat_id {
+                    Some(chat_id) => warn!("Messages in conversation {chat_id} were not in chronological order and have been sorted"),
+                    None => warn!("Orphaned messages were not in chronological order and have been sorted"),
+                }
+            }
+            let messages_array: Vec<serde_json::Value> =
+                messages.into_iter().map(|(_, message)| message).collect();
+
+            let written = self
+                .get_or_create_file(&Message {
+                    chat_id,
+                    ..Default::default()
+                })
+                .and_then(|writer| {
+                    writeln!(
+                        writer,
+                        "{}",
+                        serde_json::to_string(&messages_array).unwrap()
+                    )
+                    .map_err(RuntimeError::DiskError)
+                })
+                .and_then(|_| {
+                    messages_array
+                        .iter()
+                        .filter_map(|message| message["guid"].as_str())
+                        .try_for_each(|guid| self.config.mark_exported(guid))
+                });
+            if let Err(why) = written {
+                self.config.handle_error(why)?;
+            }
         }
 
         Ok(())
     }
 
-    fn get_or_create_file(&mut self, message: &Message) -> Result<&mut BufWriter<File>, RuntimeError> {
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<ExportFile>, RuntimeError> {
         match self.config.conversation(message) {
             Some((chatroom, _)) => {
                 let filename = self.config.filename(chatroom);
-                let path = self.config.options.export_path.join(filename).with_extension("json");
+                let path = self
+                    .config
+                    .conversation_dir(&filename)?
+                    .join(&filename)
+                    .with_extension(self.config.file_extension("json"));
                 if !self.files.contains_key(&path.to_string_lossy().to_string()) {
-                    let file = File::options()
-                        .append(true)
-                        .create(true)
-                        .open(&path)
-                        .map_err(|err| RuntimeError::CreateError(err, path.clone()))?;
-                    let writer = BufWriter::new(file);
+                    let writer = self.config.open_export_file(&path)?;
                     self.files.insert(path.to_string_lossy().to_string(), writer);
                 }
                 Ok(self.files.get_mut(&path.to_string_lossy().to_string()).unwrap())
             }
-            None => Ok(&mut self.orphaned),
+            None => self
+                .orphaned
+                .as_mut()
+                .ok_or(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_NO_ORPHANED} is enabled, but message {} has no conversation!",
+                    message.rowid
+                ))),
+        }
+    }
+
+    fn supports_attachments() -> bool {
+        true
+    }
+
+    fn supports_reactions() -> bool {
+        // Tapbacks are written as messages of their own
+        false
+    }
+}
+
+impl<'a> Previewer<'a> for JSONExporter<'a> {
+    fn preview(config: &'a Config) -> Self {
+        JSONExporter {
+            config,
+            files: BTreeMap::new(),
+            orphaned: None,
+            reactions: ReactionCounts::default(),
+            stats: MessageStats::default(),
+        }
+    }
+
+    fn format_preview(&self, msg: &Message) -> Result<Option<String>, RuntimeError> {
+        Ok(Some(format!("{:#}", self.format_custom(msg))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet, HashMap},
+        env::{current_dir, set_var, temp_dir, var_os},
+        fs::{copy, create_dir_all, read_dir, read_to_string, remove_dir_all, File},
+        io::Read,
+        path::{Path, PathBuf},
+    };
+
+    use imessage_database::{
+        tables::chat::{Chat, ReadState},
+        util::typedstream::parser::TypedStreamReader,
+    };
+    use serde_json::json;
+
+    use crate::{
+        app::export_type::ExportType,
+        exporters::{
+            exporter::Previewer,
+            json::{sort_by_date, JSONExporter},
+        },
+        Config, Exporter, Options,
+    };
+
+    #[test]
+    fn can_keep_ordered_messages() {
+        let mut messages = vec![(1, json!("a")), (2, json!("b")), (2, json!("c"))];
+        assert!(!sort_by_date(&mut messages));
+        assert_eq!(
+            messages,
+            vec![(1, json!("a")), (2, json!("b")), (2, json!("c"))]
+        );
+    }
+
+    #[test]
+    fn can_sort_unordered_messages() {
+        let mut messages = vec![
+            (3, json!("c")),
+            (1, json!("a")),
+            (3, json!("d")),
+            (2, json!("b")),
+        ];
+        assert!(sort_by_date(&mut messages));
+        assert_eq!(
+            messages,
+            vec![
+                (1, json!("a")),
+                (2, json!("b")),
+                (3, json!("c")),
+                (3, json!("d"))
+            ]
+        );
+    }
+
+    #[test]
+    fn can_format_rich_text() {
+        let mut options = Options::fake_options(ExportType::Json);
+        options.rich_text = true;
+        let config = Config::fake_app(options);
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.text = Some("Test Dad ".to_string());
+
+        let typedstream_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/typedstream/Mention");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        message.components = parser.parse().ok();
+
+        let actual = exporter.format_custom(&message);
+        assert_eq!(
+            actual["mentions"],
+            json!([{"text": "Dad", "handle": "+15558675309", "start": 5, "end": 8}])
+        );
+        assert_eq!(actual["attachments"], json!([]));
+    }
+
+    #[test]
+    fn can_format_without_rich_text() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let actual = exporter.format_custom(&Config::fake_message());
+        assert!(actual.get("mentions").is_none());
+        assert!(actual.get("attachments").is_none());
+    }
+
+    #[test]
+    fn can_format_subject() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.subject = Some("Test Subject".to_string());
+
+        let actual = exporter.format_custom(&message);
+        assert_eq!(actual["subject"], json!("Test Subject"));
+    }
+
+    #[test]
+    fn can_format_without_subject() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        assert!(exporter.format_custom(&message).get("subject").is_none());
+
+        message.subject = Some(String::new());
+        assert!(exporter.format_custom(&message).get("subject").is_none());
+    }
+
+    #[test]
+    fn can_format_placed_sticker() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.associated_message_type = Some(1000);
+        message.associated_message_guid =
+            Some("p:0/A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+
+        let actual = exporter.format_custom(&message);
+        assert_eq!(
+            actual["placed_on"],
+            json!("A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A")
+        );
+    }
+
+    #[test]
+    fn can_format_tapback_without_placed_on() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.associated_message_type = Some(2000);
+        message.associated_message_guid =
+            Some("p:0/A44CE9D7-AAAA-BBBB-CCCC-23C54E1A9B6A".to_string());
+
+        let actual = exporter.format_custom(&message);
+        assert!(actual.get("placed_on").is_none());
+    }
+
+    #[test]
+    fn can_format_sms() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.service = Some("SMS".to_string());
+        message.service_center = Some("+12063130004".to_string());
+
+        let actual = exporter.format_custom(&message);
+        assert_eq!(
+            actual["sms"],
+            json!({"service_center": "+12063130004", "mms": false, "downgraded": false})
+        );
+    }
+
+    #[test]
+    fn can_format_group_mms() {
+        let options = Options::fake_options(ExportType::Json);
+        let mut config = Config::fake_app(options);
+        config
+            .chatroom_participants
+            .insert(1, BTreeSet::from([10, 11]));
+        config.participants.insert(10, "Mom".to_string());
+        config.participants.insert(11, "Dad".to_string());
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.service = Some("SMS".to_string());
+        message.chat_id = Some(1);
+        message.was_downgraded = true;
+
+        let actual = exporter.format_custom(&message);
+        assert_eq!(
+            actual["sms"],
+            json!({"service_center": null, "mms": true, "downgraded": true, "recipients": ["Mom", "Dad"]})
+        );
+    }
+
+    #[test]
+    fn can_format_facetime_call() {
+        let options = Options::fake_options(ExportType::Json);
+        let mut config = Config::fake_app(options);
+        config
+            .chatroom_participants
+            .insert(1, BTreeSet::from([10, 11]));
+        config.participants.insert(10, "Mom".to_string());
+        config.participants.insert(11, "Dad".to_string());
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.item_type = 6;
+        message.is_from_me = true;
+        message.chat_id = Some(1);
+
+        let actual = exporter.format_custom(&message);
+        assert_eq!(
+            actual["system_event"],
+            json!({"type": "facetime", "direction": "outgoing", "participants": ["Mom", "Dad"]})
+        );
+    }
+
+    #[test]
+    fn can_format_unknown_system_event() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.item_type = 3;
+
+        let actual = exporter.format_custom(&message);
+        assert_eq!(
+            actual["system_event"],
+            json!({"type": "unknown", "item_type": 3})
+        );
+    }
+
+    #[test]
+    fn can_omit_sms_for_imessage() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.service = Some("iMessage".to_string());
+        message.service_center = Some("+12063130004".to_string());
+
+        let actual = exporter.format_custom(&message);
+        assert!(actual.get("sms").is_none());
+    }
+
+    fn fake_chat(rowid: i32, display_name: Option<&str>) -> Chat {
+        Chat {
+            rowid,
+            chat_identifier: "Default".to_string(),
+            guid: None,
+            service_name: Some(String::new()),
+            display_name: display_name.map(str::to_string),
+            last_read: None,
+        }
+    }
+
+    #[test]
+    fn can_format_conversation_participants() {
+        let options = Options::fake_options(ExportType::Json);
+        let mut config = Config::fake_app(options);
+        config.chatrooms.insert(1, fake_chat(1, Some("Family")));
+        config
+            .chatroom_participants
+            .insert(1, BTreeSet::from([10, 11]));
+        config.participants.insert(10, "Mom".to_string());
+        config.participants.insert(11, "Dad, Jr.".to_string());
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let actual = exporter.format_conversation(&1, &HashMap::new()).unwrap();
+        assert_eq!(actual["participants"], json!(["Mom", "Dad, Jr."]));
+    }
+
+    #[test]
+    fn can_format_conversation_single_participant() {
+        let options = Options::fake_options(ExportType::Json);
+        let mut config = Config::fake_app(options);
+        config.chatrooms.insert(2, fake_chat(2, None));
+        config.chatroom_participants.insert(2, BTreeSet::from([10]));
+        config.participants.insert(10, "Mom".to_string());
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let actual = exporter.format_conversation(&2, &HashMap::new()).unwrap();
+        assert_eq!(actual["participants"], json!(["Mom"]));
+    }
+
+    #[test]
+    fn cant_format_missing_conversation() {
+        let options = Options::fake_options(ExportType::Json);
+        let config = Config::fake_app(options);
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        assert!(exporter.format_conversation(&3, &HashMap::new()).is_none());
+    }
+
+    #[test]
+    fn can_merge_conversation_read_state() {
+        let options = Options::fake_options(ExportType::Json);
+        let mut config = Config::fake_app(options);
+        // Chats 1 and 2 are the same conversation, chat 3 is another one
+        for chat_id in 1..=3 {
+            config.chatrooms.insert(chat_id, fake_chat(chat_id, None));
+        }
+        config.real_chatrooms.insert(1, 0);
+        config.real_chatrooms.insert(2, 0);
+        config.real_chatrooms.insert(3, 1);
+        let read_states = HashMap::from([
+            (
+                1,
+                ReadState {
+                    unread: 2,
+                    last_read: Some(100),
+                },
+            ),
+            (
+                2,
+                ReadState {
+                    unread: 1,
+                    last_read: Some(200),
+                },
+            ),
+            (
+                3,
+                ReadState {
+                    unread: 5,
+                    last_read: None,
+                },
+            ),
+        ]);
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let actual = exporter.conversation_read_states(&read_states);
+        assert_eq!(
+            actual[&1],
+            ReadState {
+                unread: 3,
+                last_read: Some(200)
+            }
+        );
+        assert_eq!(actual[&2], actual[&1]);
+        assert_eq!(
+            actual[&3],
+            ReadState {
+                unread: 5,
+                last_read: None
+            }
+        );
+    }
+
+    #[test]
+    fn can_prefer_chat_last_read() {
+        let options = Options::fake_options(ExportType::Json);
+        let mut config = Config::fake_app(options);
+        let mut chat = fake_chat(1, None);
+        chat.last_read = Some(300);
+        config.chatrooms.insert(1, chat);
+        let read_states = HashMap::from([(
+            1,
+            ReadState {
+                unread: 0,
+                last_read: Some(100),
+            },
+        )]);
+        let exporter = JSONExporter::new(&config).unwrap();
+        let read_states = exporter.conversation_read_states(&read_states);
+
+        let actual = exporter.format_conversation(&1, &read_states).unwrap();
+        assert_eq!(actual["unread"], json!(0));
+        assert_eq!(read_states[&1].last_read, Some(300));
+        assert!(actual.get("last_read").is_some());
+    }
+
+    #[test]
+    fn can_format_conversation_without_avatar() {
+        let options = Options::fake_options(ExportType::Json);
+        let mut config = Config::fake_app(options);
+        config.chatrooms.insert(2, fake_chat(2, None));
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let actual = exporter.format_conversation(&2, &HashMap::new()).unwrap();
+        assert!(actual.get("avatar").is_none());
+    }
+
+    #[test]
+    fn can_format_conversation_stats() {
+        let mut options = Options::fake_options(ExportType::Json);
+        options.stats = true;
+        let mut config = Config::fake_app(options);
+        config.chatrooms.insert(1, fake_chat(1, None));
+        config.participants.insert(0, "Mom".to_string());
+        let mut exporter = JSONExporter::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.text = Some("See you soon".to_string());
+        exporter.stats.add(1, "Mom", &message);
+
+        let actual = exporter.format_conversation(&1, &HashMap::new()).unwrap();
+        assert_eq!(
+            actual["stats"],
+            json!({"Mom": {"messages": 1, "words": 3, "characters": 12}})
+        );
+    }
+
+    #[test]
+    fn can_format_conversation_name() {
+        let mut options = Options::fake_options(ExportType::Json);
+        options.group_names = HashMap::from([("chat123".to_string(), "Book Club".to_string())]);
+        let mut config = Config::fake_app(options);
+        let mut chat = fake_chat(1, None);
+        chat.guid = Some("chat123".to_string());
+        config.chatrooms.insert(1, chat);
+        config.chatrooms.insert(2, fake_chat(2, Some("Family")));
+        config.chatrooms.insert(3, fake_chat(3, None));
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let renamed = exporter.format_conversation(&1, &HashMap::new()).unwrap();
+        assert_eq!(renamed["name"], json!("Book Club"));
+        let named = exporter.format_conversation(&2, &HashMap::new()).unwrap();
+        assert_eq!(named["name"], json!("Family"));
+        let unnamed = exporter.format_conversation(&3, &HashMap::new()).unwrap();
+        assert!(unnamed.get("name").is_none());
+    }
+
+    #[test]
+    fn can_sort_tied_conversations_by_id() {
+        let options = Options::fake_options(ExportType::Json);
+        let mut config = Config::fake_app(options);
+        // Neither chat has a name or participants, so both use the same file name
+        config.chatrooms.insert(2, fake_chat(2, None));
+        config.chatrooms.insert(1, fake_chat(1, None));
+        let exporter = JSONExporter::new(&config).unwrap();
+
+        let mut conversation_map = BTreeMap::new();
+        conversation_map.insert(Some(2), vec![(1, json!("b"))]);
+        conversation_map.insert(Some(1), vec![(1, json!("a"))]);
+
+        assert_eq!(
+            exporter.sort_conversations(&conversation_map),
+            vec![Some(1), Some(2)]
+        );
+    }
+
+    #[test]
+    fn can_format_reply_to_text() {
+        let mut options = Options::fake_options(ExportType::Json);
+        options.db_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+        options.quote_replies = Some(12);
+        let config = Config::new(options).unwrap();
+        let exporter = JSONExporter::preview(&config);
+
+        let mut message = Config::fake_message();
+        message.chat_id = Some(1);
+        message.thread_originator_guid = Some("00000000-0000-4000-8000-000000000001".to_string());
+
+        let actual = exporter.format_custom(&message);
+        assert_eq!(
+            actual["reply_to"],
+            json!("00000000-0000-4000-8000-000000000001")
+        );
+        assert_eq!(actual["reply_to_text"], json!("Are we still..."));
+    }
+
+    /// Get the names of the files in a directory, in order
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Compare every file in an export to the golden files committed for it
+    ///
+    /// Set `UPDATE_GOLDEN=1` to replace the golden files with the export instead.
+    fn assert_golden(export_path: &Path, golden_path: &Path) {
+        if var_os("UPDATE_GOLDEN").is_some() {
+            let _ = remove_dir_all(golden_path);
+            create_dir_all(golden_path).unwrap();
+            for name in file_names(export_path) {
+                copy(export_path.join(&name), golden_path.join(&name)).unwrap();
+            }
+            return;
+        }
+
+        assert_eq!(file_names(export_path), file_names(golden_path));
+        for name in file_names(export_path) {
+            assert_eq!(
+                read_to_string(export_path.join(&name)).unwrap(),
+                read_to_string(golden_path.join(&name)).unwrap(),
+                "{name} does not match its golden file; rerun with UPDATE_GOLDEN=1 to update it"
+            );
         }
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn can_export_fixture_database() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let test_data = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_data");
+        let export_path = temp_dir().join("imessage-golden-json");
+        let _ = remove_dir_all(&export_path);
+        create_dir_all(&export_path).unwrap();
+
+        let mut options = Options::fake_options(ExportType::Json);
+        options.db_path = test_data.join("fixture").join("chat.db");
+        options.export_path = export_path.clone();
+        // Keep attachment paths from depending on the home directory of whoever runs the test
+        options.attachment_root = Some("/attachments".to_string());
+        let config = Config::new(options).unwrap();
+
+        // Drop the exporter so every file is flushed before it is compared
+        {
+            let mut exporter = JSONExporter::new(&config).unwrap();
+            exporter.iter_messages().unwrap();
+        }
+
+        assert_golden(&export_path, &test_data.join("golden").join("json"));
+        remove_dir_all(&export_path).unwrap();
+    }
+}
@@ -0,0 +1,1019 @@
+use std::{
+    collections::{
+        btree_map::Entry::{Occupied, Vacant},
+        BTreeMap, BTreeSet,
+    },
+    fmt::Display,
+    fs::{create_dir_all, remove_dir_all, File},
+    io::{copy, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use log::info;
+
+use crate::{
+    app::{
+        error::RuntimeError, export_file::ExportFile, export_type::ExportType,
+        options::OPTION_NO_ORPHANED, runtime::Config, sanitizers::sanitize_filename,
+    },
+    exporters::exporter::{
+        generate_text_or_placeholder, link_title, reply_context, summarize_app, Exporter,
+        Previewer, MEDIA_REMOVED,
+    },
+};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::variants::{Announcement, Variant},
+    tables::{
+        attachment::{Attachment, MediaType},
+        messages::{
+            models::{BubbleComponent, TextAttributes},
+            Message,
+        },
+        table::{Table, ORPHANED},
+    },
+    util::dates::get_local_time,
+};
+
+/// Name of the directory in the export that holds a note for each person, in the `obsidian` flavor
+pub const PEOPLE_DIR: &str = "people";
+/// Directory new notes are written to in the `obsidian` flavor before their frontmatter is added
+const NOTE_BODIES: &str = ".notes";
+/// Characters in message text that mark where attachments and apps are rendered
+const REPLACEMENT_CHARS: [char; 2] = ['\u{FFFC}', '\u{FFFD}'];
+/// Characters that Markdown would read as formatting, so they are escaped in message text
+const MARKDOWN_CHARS: [char; 9] = ['\\', '`', '*', '[', ']', '<', '>', '#', '|'];
+/// Characters Obsidian does not allow in the name of a note that is linked to
+const NOTE_DISALLOWED_CHARS: [char; 5] = ['[', ']', '#', '^', '|'];
+/// The pattern dates in frontmatter are written in, i.e. `2022-05-17T17:29:42-07:00`
+const FRONTMATTER_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%:z";
+
+/// Represents the dialect of Markdown conversations are written in
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy)]
+pub enum MarkdownFlavor {
+    /// Plain Markdown, one file per conversation
+    #[default]
+    Plain,
+    /// Obsidian notes with YAML frontmatter and `[[wiki-links]]` between people and their conversations
+    Obsidian,
+}
+
+impl MarkdownFlavor {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(flavor: &str) -> Option<Self> {
+        match flavor.to_lowercase().as_str() {
+            "plain" => Some(Self::Plain),
+            "obsidian" => Some(Self::Obsidian),
+            _ => None,
+        }
+    }
+}
+
+impl Display for MarkdownFlavor {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MarkdownFlavor::Plain => write!(fmt, "plain"),
+            MarkdownFlavor::Obsidian => write!(fmt, "obsidian"),
+        }
+    }
+}
+
+/// What a conversation's frontmatter says about its messages, tallied as they are written
+#[derive(Debug, Default)]
+struct Note {
+    /// The number of messages written
+    messages: usize,
+    /// The GUIDs of messages written to a new note, marked as exported once its frontmatter is added
+    pending: Vec<String>,
+    /// The date of the first message
+    first_date: Option<i64>,
+    /// The date of the last message
+    last_date: Option<i64>,
+}
+
+/// Writes each conversation to a Markdown file
+///
+/// In the `obsidian` flavor, each conversation starts with YAML frontmatter that lists its participants,
+/// the dates of its first and last messages, and how many messages it has. Each participant also gets a note
+/// in [`PEOPLE_DIR`] that links to every conversation they appear in.
+///
+/// Messages are written as they are read. Frontmatter can only be built once every message is read, so in the
+/// `obsidian` flavor new notes are written to [`NOTE_BODIES`] and moved to the export with their frontmatter at the end.
+///
+/// Messages are read in a single pass. Tapbacks are written under the message they react to, looked up by
+/// the message's GUID in [`Config::tapbacks`], which is built before the export starts.
+pub struct Markdown<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of resolved chatroom file location to a buffered writer
+    pub files: BTreeMap<String, BufWriter<ExportFile>>,
+    /// Writer instance for orphaned messages, unless they are dropped
+    pub orphaned: Option<BufWriter<ExportFile>>,
+    /// Map of chat ID to what its frontmatter says, in the `obsidian` flavor
+    notes: BTreeMap<Option<i32>, Note>,
+    /// Directory new notes are written to until their frontmatter is added, in the `obsidian` flavor
+    bodies: Option<PathBuf>,
+    /// Map of each note this export created to the path it is moved to, so frontmatter is not added to a file a previous export wrote
+    new_files: BTreeMap<String, PathBuf>,
+}
+
+impl<'a> Exporter<'a> for Markdown<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let orphaned = if config.options.no_orphaned {
+            None
+        } else {
+            let mut orphaned = config.conversation_dir(ORPHANED)?;
+            orphaned.push(ORPHANED);
+            orphaned.set_extension(config.file_extension("md"));
+
+            Some(config.open_export_file(&orphaned)?)
+        };
+
+        let bodies = (config.options.markdown_flavor == MarkdownFlavor::Obsidian)
+            .then(|| config.options.export_path.join(NOTE_BODIES));
+
+        Ok(Markdown {
+            config,
+            files: BTreeMap::new(),
+            orphaned,
+            notes: BTreeMap::new(),
+            bodies,
+            new_files: BTreeMap::new(),
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as markdown...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of messages dropped because they do not belong to a conversation
+        let mut dropped_messages = 0;
+
+        // Keep track of messages skipped because a previous export already wrote them
+        let mut skipped_messages = 0;
+
+        // Keep track of messages skipped because their text is too short
+        let mut short_messages = 0;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = self.config.progress_bar(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = match Message::extract(message) {
+                Ok(msg) => msg,
+                Err(why) => {
+                    self.config.handle_error(RuntimeError::DatabaseError(why))?;
+                    current_message += 1;
+                    continue;
+                }
+            };
+
+            // Drop messages that do not belong to a conversation, if requested
+            if self.config.options.no_orphaned && self.config.is_orphaned(&msg) {
+                dropped_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Skip messages that a previous export already wrote, if requested
+            if self.config.already_exported(&msg) {
+                skipped_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // List the message on its own if it is a tapback, if requested
+            self.config.record_reaction(&msg);
+
+            if !msg.is_tapback() {
+                // Generate the text of the message
+                generate_text_or_placeholder(&mut msg, &self.config.db);
+
+                // Skip messages that are too short, which is only known once their text is generated
+                if self.config.is_too_short(&msg) {
+                    short_messages += 1;
+                    current_message += 1;
+                    continue;
+                }
+
+                // Note when the message was delivered and read, if requested
+                self.config.record_receipt(&msg);
+
+                match self.format_message(&msg) {
+                    Ok(text) => {
+                        if let Err(why) = self.write_message(&msg, &text) {
+                            self.config.handle_error(why)?;
+                        }
+                    }
+                    Err(why) => self.config.handle_error(RuntimeError::DatabaseError(why))?,
+                }
+            }
+
+            // Abort if an attachment failed to copy and we are not exporting on a best effort basis
+            self.config.check_errors()?;
+
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        if dropped_messages > 0 {
+            info!("Dropped {dropped_messages} messages that do not belong to a conversation");
+        }
+        if skipped_messages > 0 {
+            info!("Skipped {skipped_messages} messages that a previous export already wrote");
+        }
+        if short_messages > 0 {
+            info!("Skipped {short_messages} messages that are too short");
+        }
+
+        self.write_notes()
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<ExportFile>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = note_name(&self.config.filename(chatroom));
+                match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let mut path = self.config.conversation_dir(entry.key())?;
+                        path.push(entry.key());
+                        path.set_extension(self.config.file_extension("md"));
+
+                        // New notes get frontmatter, so they are written elsewhere until it is known
+                        match self.bodies.as_ref().filter(|_| !path.exists()) {
+                            Some(bodies) => {
+                                let file = Markdown::open_body(bodies, entry.key())?;
+                                self.new_files.insert(entry.key().clone(), path);
+                                Ok(entry.insert(file))
+                            }
+                            None => Ok(entry.insert(self.config.open_export_file(&path)?)),
+                        }
+                    }
+                }
+            }
+            None => self
+                .orphaned
+                .as_mut()
+                .ok_or(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_NO_ORPHANED} is enabled, but message {} has no conversation!",
+                    message.rowid
+                ))),
+        }
+    }
+
+    fn supports_attachments() -> bool {
+        true
+    }
+
+    fn supports_reactions() -> bool {
+        true
+    }
+}
+
+impl<'a> Previewer<'a> for Markdown<'a> {
+    fn preview(config: &'a Config) -> Self {
+        Markdown {
+            config,
+            files: BTreeMap::new(),
+            orphaned: None,
+            notes: BTreeMap::new(),
+            bodies: None,
+            new_files: BTreeMap::new(),
+        }
+    }
+
+    fn format_preview(&self, msg: &Message) -> Result<Option<String>, RuntimeError> {
+        // Tapbacks are rendered with the message they react to
+        if msg.is_tapback() {
+            return Ok(None);
+        }
+        self.format_message(msg)
+            .map(Some)
+            .map_err(RuntimeError::DatabaseError)
+    }
+}
+
+impl<'a> Markdown<'a> {
+    /// Format a message as a bold sender and date, followed by its text and attachments
+    fn format_message(&self, message: &Message) -> Result<String, TableError> {
+        let date = self.config.format_date(&message.date(&self.config.offset));
+        let who = self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+
+        let mut lines = vec![];
+
+        if let Some(announcement) = message.get_announcement() {
+            lines.push(format!("_{}_", format_announcement(&announcement)));
+        } else if let Some(event) = message.get_system_event() {
+            // System events have no text of their own, so describe the event instead
+            lines.push(format!("_{}_", escape_markdown(&event.to_string())));
+        } else {
+            // Quote the message this one replied to, if requested
+            if let Some(quote) = self.config.options.quote_replies.and_then(|max_length| {
+                reply_context(message, &self.config.db, self.config.schema, max_length)
+            }) {
+                for line in quote.lines() {
+                    lines.push(format!("> {}", escape_markdown(line)));
+                }
+                // A blank line ends the quote, so the reply is not folded into it
+                lines.push(String::new());
+            }
+
+            let redact = self.config.options.redact_attachments;
+            let mut attachments = match message.has_attachments() && !redact {
+                true => Attachment::from_message(&self.config.db, message)?,
+                false => vec![],
+            }
+            .into_iter();
+            let mut has_text = false;
+
+            // Messages that are only a link read better as the title of the link's preview
+            let preview_title = if redact {
+                None
+            } else {
+                link_title(message, &self.config.db)
+            };
+            if let Some(title) = &preview_title {
+                lines.push(format_link(
+                    title,
+                    message.text.as_deref().unwrap_or_default(),
+                ));
+                has_text = true;
+            }
+
+            // Write each part where it appears in the message, so images sent between lines of text stay there
+            for part in message.body() {
+                match part {
+                    // The link was already written under its title
+                    BubbleComponent::Text(_) if preview_title.is_some() => {}
+                    BubbleComponent::Text(text_attrs) => {
+                        has_text |= push_text(&mut lines, &part_text(message, &text_attrs));
+                    }
+                    BubbleComponent::Attachment(_) if redact => {}
+                    BubbleComponent::Attachment(_) => {
+                        if let Some(mut attachment) = attachments.next() {
+                            lines.push(self.format_attachment(message, &mut attachment));
+                        }
+                    }
+                    BubbleComponent::App => {
+                        if preview_title.is_none() {
+                            has_text |=
+                                push_text(&mut lines, message.text.as_deref().unwrap_or_default());
+                        }
+                        if let Some(app_message) = summarize_app(message, &self.config.db) {
+                            lines.push(escape_markdown(&app_message));
+                        }
+                    }
+                    BubbleComponent::Retracted => {}
+                }
+            }
+            if has_text && message.is_edited() {
+                lines.push("_(edited)_".to_string());
+            }
+
+            // Attachments the body does not point to are written after it
+            for mut attachment in attachments {
+                lines.push(self.format_attachment(message, &mut attachment));
+            }
+            if redact && message.has_attachments() {
+                lines.push(escape_markdown(MEDIA_REMOVED));
+            }
+
+            lines.extend(self.format_tapbacks(message));
+        }
+
+        // Lines end with two spaces so Markdown keeps the line breaks
+        let mut rendered = format!("**{}** · {date}  \n", escape_markdown(who));
+        for line in lines {
+            rendered.push_str(&line);
+            rendered.push_str("  \n");
+        }
+        rendered.push('\n');
+        Ok(rendered)
+    }
+
+    /// Describe the tapbacks left on each part of a message, in the order of the parts
+    ///
+    /// Tapbacks that were taken back are not written.
+    fn format_tapbacks(&self, message: &Message) -> Vec<String> {
+        let Some(parts) = self.config.tapbacks.get(&message.guid) else {
+            return vec![];
+        };
+        let mut indexes: Vec<&usize> = parts.keys().collect();
+        indexes.sort();
+
+        indexes
+            .into_iter()
+            .flat_map(|idx| &parts[idx])
+            .filter_map(|tapback| {
+                let who = self.config.who(
+                    tapback.handle_id,
+                    tapback.is_from_me(),
+                    &tapback.destination_caller_id,
+                );
+                match tapback.variant() {
+                    Variant::Tapback(_, true, kind) => Some(format!("{kind} by {who}")),
+                    Variant::Sticker(_) => Some(format!("Sticker from {who}")),
+                    _ => None,
+                }
+            })
+            .map(|line| format!("_{}_", escape_markdown(&line)))
+            .collect()
+    }
+
+    /// Link to an attachment, copying it if requested
+    ///
+    /// Images are embedded; other files are linked to by name.
+    fn format_attachment(&self, message: &Message, attachment: &mut Attachment) -> String {
+        let path = match self.config.options.attachment_manager.handle_attachment(
+            message,
+            attachment,
+            self.config,
+        ) {
+            Some(_) => self.config.message_attachment_path(attachment),
+            None => attachment.filename().to_string(),
+        };
+        let name = escape_markdown(attachment.filename());
+        match attachment.mime_type() {
+            MediaType::Image(_) => format!("![{name}](<{path}>)"),
+            _ => format!("[{name}](<{path}>)"),
+        }
+    }
+
+    /// Write a formatted message to its conversation's file, tallying it for the frontmatter in the `obsidian` flavor
+    fn write_message(&mut self, message: &Message, text: &str) -> Result<(), RuntimeError> {
+        self.get_or_create_file(message)?
+            .write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)?;
+
+        if self.bodies.is_some() {
+            // Merged chats share a note
+            let chatroom = self
+                .config
+                .conversation(message)
+                .map(|(chatroom, _)| chatroom);
+            let pending = chatroom.is_some_and(|chatroom| {
+                self.new_files
+                    .contains_key(&note_name(&self.config.filename(chatroom)))
+            });
+            let note = self
+                .notes
+                .entry(chatroom.map(|chatroom| chatroom.rowid))
+                .or_default();
+            note.messages += 1;
+            note.first_date = note.first_date.or(Some(message.date));
+            note.last_date = Some(message.date);
+            // Messages in a new note are only exported once the note is moved to the export
+            if pending {
+                note.pending.push(message.guid.clone());
+                return Ok(());
+            }
+        }
+        self.config.mark_exported(&message.guid)
+    }
+
+    /// Open the file a new note is written to before its frontmatter is added
+    ///
+    /// Notes are plain UTF-8 until they are moved, which applies any compression or output encoding.
+    fn open_body(bodies: &Path, filename: &str) -> Result<BufWriter<ExportFile>, RuntimeError> {
+        create_dir_all(bodies)
+            .map_err(|err| RuntimeError::CreateError(err, bodies.to_path_buf()))?;
+        let path = Markdown::body_path(bodies, filename);
+        File::create(&path)
+            .and_then(|file| ExportFile::new(file, false))
+            .map(BufWriter::new)
+            .map_err(|err| RuntimeError::CreateError(err, path))
+    }
+
+    /// Get the path to the file a new note is written to before its frontmatter is added
+    ///
+    /// The extension is appended rather than set, since conversation names may contain periods
+    fn body_path(bodies: &Path, filename: &str) -> PathBuf {
+        bodies.join(format!("{filename}.md"))
+    }
+
+    /// In the `obsidian` flavor, add frontmatter to each new note and move it to the export, then write the notes for each person
+    fn write_notes(&mut self) -> Result<(), RuntimeError> {
+        let Some(bodies) = self.bodies.take() else {
+            return Ok(());
+        };
+
+        // Close the new notes so everything written to them can be read back
+        for filename in self.new_files.keys() {
+            if let Some(mut file) = self.files.remove(filename) {
+                file.flush()?;
+            }
+        }
+
+        // Map of person to the names of the conversations they appear in
+        let mut people: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
+        for (chat_id, note) in std::mem::take(&mut self.notes) {
+            // Orphaned messages have no participants, so their note has no frontmatter
+            let Some(filename) = chat_id
+                .and_then(|id| self.config.chatrooms.get(&id))
+                .map(|chatroom| note_name(&self.config.filename(chatroom)))
+            else {
+                continue;
+            };
+
+            // Links name notes without their extension
+            let conversation = filename
+                .strip_suffix(ExportType::Markdown.extension())
+                .unwrap_or(&filename);
+            for person in self.participants(chat_id) {
+                people
+                    .entry(person)
+                    .or_default()
+                    .insert(conversation.to_string());
+            }
+
+            if let Some(path) = self.new_files.get(&filename) {
+                let written = self
+                    .move_note(
+                        &Markdown::body_path(&bodies, &filename),
+                        path,
+                        chat_id,
+                        &note,
+                    )
+                    .and_then(|_| {
+                        note.pending
+                            .iter()
+                            .try_for_each(|guid| self.config.mark_exported(guid))
+                    });
+                if let Err(why) = written {
+                    self.config.handle_error(why)?;
+                }
+            }
+        }
+
+        if bodies.exists() {
+            remove_dir_all(&bodies).map_err(RuntimeError::DiskError)?;
+        }
+
+        if let Err(why) = self.write_people(&people) {
+            self.config.handle_error(why)?;
+        }
+        Ok(())
+    }
+
+    /// Write a new note to the export, starting with its frontmatter
+    fn move_note(
+        &self,
+        body: &Path,
+        path: &Path,
+        chat_id: Option<i32>,
+        note: &Note,
+    ) -> Result<(), RuntimeError> {
+        let mut file = self.config.open_export_file(path)?;
+        file.write_all(self.frontmatter(chat_id, note).as_bytes())?;
+        copy(&mut File::open(body)?, &mut file)?;
+        file.into_inner()
+            .map_err(|err| RuntimeError::DiskError(err.into_error()))?
+            .finish()?;
+        Ok(())
+    }
+
+    /// The names of the people in a conversation, as note names
+    fn participants(&self, chat_id: Option<i32>) -> Vec<String> {
+        chat_id
+            .map(|chat_id| {
+                self.config
+                    .conversation_participants(&chat_id)
+                    .into_iter()
+                    .map(note_name)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Build the YAML frontmatter that describes a conversation's note
+    fn frontmatter(&self, chat_id: Option<i32>, note: &Note) -> String {
+        let mut frontmatter = String::from("---\nparticipants:\n");
+        for person in self.participants(chat_id) {
+            frontmatter.push_str(&format!("  - \"[[{person}]]\"\n"));
+        }
+        for (key, date) in [
+            ("first_message", note.first_date),
+            ("last_message", note.last_date),
+        ] {
+            if let Some(date) =
+                date.and_then(|date| get_local_time(&date, &self.config.offset).ok())
+            {
+                frontmatter.push_str(&format!(
+                    "{key}: {}\n",
+                    self.config.localize(&date).format(FRONTMATTER_DATE_FORMAT)
+                ));
+            }
+        }
+        frontmatter.push_str(&format!("messages: {}\n---\n\n", note.messages));
+        frontmatter
+    }
+
+    /// Write a note for each person that links to the conversations they appear in, replacing any notes a previous export wrote
+    fn write_people(
+        &self,
+        people: &BTreeMap<String, BTreeSet<String>>,
+    ) -> Result<(), RuntimeError> {
+        let dir = self.config.options.export_path.join(PEOPLE_DIR);
+        create_dir_all(&dir).map_err(|err| RuntimeError::CreateError(err, dir.clone()))?;
+
+        for (person, conversations) in people {
+            let mut text = format!("# {person}\n\n## Conversations\n\n");
+            for conversation in conversations {
+                text.push_str(&format!("- [[{conversation}]]\n"));
+            }
+
+            // Names like `steve@apple.com` already have a dot, so the extension is appended instead of replaced
+            let path: PathBuf = dir.join(format!("{person}.{}", self.config.file_extension("md")));
+            let mut file = File::create(&path)
+                .and_then(|file| ExportFile::new(file, self.config.options.compress))
+                .map_err(|err| RuntimeError::CreateError(err, path))?;
+            file.write_all(text.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Describe an announcement in a sentence
+fn format_announcement(announcement: &Announcement) -> String {
+    match announcement {
+        Announcement::NameChange(name) => {
+            format!("Named the conversation {}", escape_markdown(name))
+        }
+        Announcement::PhotoChange => "Changed the group photo".to_string(),
+        Announcement::FullyUnsent => "Unsent a message".to_string(),
+        Announcement::Unknown(num) => format!("Performed unknown action {num}"),
+    }
+}
+
+/// Get the text of one part of a message body from the ranges of its attributes
+///
+/// If the ranges do not fit the message's text, the whole text is used.
+fn part_text(message: &Message, text_attrs: &[TextAttributes]) -> String {
+    let text = message.text.as_deref().unwrap_or_default();
+    let part: String = text_attrs
+        .iter()
+        .filter_map(|attr| text.get(attr.start..attr.end))
+        .collect();
+    if part.is_empty() {
+        text.to_string()
+    } else {
+        part
+    }
+}
+
+/// Add the lines of `text` to `lines`, without the markers for attachments and apps, returning `true` if any were added
+fn push_text(lines: &mut Vec<String>, text: &str) -> bool {
+    let text: String = text
+        .chars()
+        .filter(|c| !REPLACEMENT_CHARS.contains(c))
+        .collect();
+    let text = text.trim();
+    lines.extend(text.lines().map(escape_markdown));
+    !text.is_empty()
+}
+
+/// Link to `url` under the title of its preview, without the markers for attachments and apps
+fn format_link(title: &str, url: &str) -> String {
+    let url = url.trim_matches(|c: char| c.is_whitespace() || REPLACEMENT_CHARS.contains(&c));
+    format!("[{}](<{url}>)", escape_markdown(title))
+}
+
+/// Escape the characters in `text` that Markdown would read as formatting
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if MARKDOWN_CHARS.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Make a name safe to use as a file name and as the target of a `[[wiki-link]]`
+fn note_name(name: &str) -> String {
+    sanitize_filename(name)
+        .chars()
+        .map(|c| match NOTE_DISALLOWED_CHARS.contains(&c) {
+            true => '_',
+            false => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeSet, HashMap},
+        env::{set_var, temp_dir},
+        fs::{create_dir_all, read_to_string, remove_dir_all},
+        path::PathBuf,
+    };
+
+    use imessage_database::tables::chat::Chat;
+
+    use crate::{
+        app::export_type::ExportType,
+        exporters::markdown::{
+            escape_markdown, format_link, note_name, Markdown, MarkdownFlavor, Note, NOTE_BODIES,
+            PEOPLE_DIR,
+        },
+        Config, Exporter, Options,
+    };
+
+    #[test]
+    fn can_parse_flavor() {
+        assert_eq!(
+            MarkdownFlavor::from_cli("Obsidian"),
+            Some(MarkdownFlavor::Obsidian)
+        );
+        assert_eq!(
+            MarkdownFlavor::from_cli("plain"),
+            Some(MarkdownFlavor::Plain)
+        );
+        assert_eq!(MarkdownFlavor::from_cli("notion"), None);
+    }
+
+    #[test]
+    fn can_format_message() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = Options::fake_options(ExportType::Markdown);
+        let mut config = Config::fake_app(options);
+        config.participants.insert(0, "Sample Contact".to_string());
+        let exporter = Markdown::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("# Not a heading\nSecond *line*".to_string());
+
+        let actual = exporter.format_message(&message).unwrap();
+        let expected = "**Sample Contact** · May 17, 2022  5:29:42 PM  \n\\# Not a heading  \nSecond \\*line\\*  \n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_message_image_between_text() {
+        let mut options = Options::fake_options(ExportType::Markdown);
+        options.db_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+        let config = Config::fake_app(options);
+        let exporter = Markdown::new(&config).unwrap();
+
+        // The fixture's message 2 has a single image attachment
+        let mut message = Config::fake_message();
+        message.rowid = 2;
+        message.num_attachments = 1;
+        message.text = Some("Here is\n\u{FFFC}\nthe map".to_string());
+
+        let actual = exporter.format_message(&message).unwrap();
+        let lines: Vec<&str> = actual.lines().map(str::trim_end).collect();
+
+        assert_eq!(lines[1], "Here is");
+        assert!(lines[2].starts_with("![map.png](<"));
+        assert_eq!(lines[3], "the map");
+    }
+
+    #[test]
+    fn can_quote_reply() {
+        let mut options = Options::fake_options(ExportType::Markdown);
+        options.db_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+        options.quote_replies = Some(12);
+        let config = Config::fake_app(options);
+        let exporter = Markdown::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.is_from_me = true;
+        message.chat_id = Some(1);
+        message.text = Some("Saturday works".to_string());
+        message.thread_originator_guid = Some("00000000-0000-4000-8000-000000000001".to_string());
+
+        let actual = exporter.format_message(&message).unwrap();
+        let lines: Vec<&str> = actual.lines().map(str::trim_end).collect();
+
+        assert_eq!(lines[1..4], ["> Are we still...", "", "Saturday works"]);
+    }
+
+    #[test]
+    fn can_format_message_tapbacks() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = Options::fake_options(ExportType::Markdown);
+        let mut config = Config::fake_app(options);
+        config.participants.insert(0, "Sample Contact".to_string());
+
+        let mut loved = Config::fake_message();
+        loved.associated_message_type = Some(2000);
+        loved.associated_message_guid = Some("p:0/fake_guid".to_string());
+        loved.is_from_me = true;
+        let mut removed = Config::fake_message();
+        removed.associated_message_type = Some(3001);
+        removed.associated_message_guid = Some("p:1/fake_guid".to_string());
+        let mut liked = Config::fake_message();
+        liked.associated_message_type = Some(2001);
+        liked.associated_message_guid = Some("p:1/fake_guid".to_string());
+        config.tapbacks.insert(
+            "fake_guid".to_string(),
+            HashMap::from([(1, vec![removed, liked]), (0, vec![loved])]),
+        );
+        let exporter = Markdown::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        message.guid = "fake_guid".to_string();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello".to_string());
+
+        let actual = exporter.format_message(&message).unwrap();
+        let expected = "**Sample Contact** · May 17, 2022  5:29:42 PM  \nHello  \n_Loved by Me_  \n_Liked by Sample Contact_  \n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_name_change() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = Options::fake_options(ExportType::Markdown);
+        let config = Config::fake_app(options);
+        let exporter = Markdown::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.group_title = Some("Family".to_string());
+        message.is_from_me = true;
+
+        let actual = exporter.format_message(&message).unwrap();
+        let expected = "**Me** · May 17, 2022  5:29:42 PM  \n_Named the conversation Family_  \n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_frontmatter() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let mut options = Options::fake_options(ExportType::Markdown);
+        options.markdown_flavor = MarkdownFlavor::Obsidian;
+        let mut config = Config::fake_app(options);
+        config
+            .participants
+            .insert(1, "Sample [Contact]".to_string());
+        config.participants.insert(2, "steve@apple.com".to_string());
+        config
+            .chatroom_participants
+            .insert(1, [1, 2].into_iter().collect());
+        let exporter = Markdown::new(&config).unwrap();
+
+        let note = Note {
+            messages: 2,
+            pending: vec![],
+            // May 17, 2022  8:29:42 PM
+            first_date: Some(674526582885055488),
+            last_date: Some(674526582885055488),
+        };
+
+        let actual = exporter.frontmatter(Some(1), &note);
+        let expected = "---\nparticipants:\n  - \"[[Sample _Contact_]]\"\n  - \"[[steve@apple.com]]\"\nfirst_message: 2022-05-17T17:29:42-07:00\nlast_message: 2022-05-17T17:29:42-07:00\nmessages: 2\n---\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_write_obsidian_note() {
+        let export_path = temp_dir().join("imessage-markdown-obsidian");
+        let _ = remove_dir_all(&export_path);
+        create_dir_all(&export_path).unwrap();
+
+        let mut options = Options::fake_options(ExportType::Markdown);
+        options.export_path = export_path.clone();
+        options.markdown_flavor = MarkdownFlavor::Obsidian;
+        options.no_orphaned = true;
+        let mut config = Config::fake_app(options);
+        config.chatrooms.insert(
+            1,
+            Chat {
+                rowid: 1,
+                chat_identifier: "Friends".to_string(),
+                guid: None,
+                service_name: Some(String::new()),
+                display_name: Some("Friends".to_string()),
+                last_read: None,
+            },
+        );
+        config.real_chatrooms.insert(1, 1);
+        config.participants.insert(2, "Person".to_string());
+        config.chatroom_participants.insert(1, BTreeSet::from([2]));
+
+        let mut message = Config::fake_message();
+        message.chat_id = Some(1);
+
+        let mut exporter = Markdown::new(&config).unwrap();
+        exporter.write_message(&message, "Hello\n").unwrap();
+        exporter.write_message(&message, "World\n").unwrap();
+
+        // The note is held back until its frontmatter is known
+        let path = export_path.join("Friends - 1.md");
+        assert!(!path.exists());
+
+        exporter.write_notes().unwrap();
+
+        let actual = read_to_string(&path).unwrap();
+        assert!(actual.starts_with("---\nparticipants:\n  - \"[[Person]]\"\n"));
+        assert!(actual.ends_with("messages: 2\n---\n\nHello\nWorld\n"));
+        assert!(export_path.join(PEOPLE_DIR).join("Person.md").exists());
+        assert!(!export_path.join(NOTE_BODIES).exists());
+
+        remove_dir_all(&export_path).unwrap();
+    }
+
+    #[test]
+    fn can_format_link() {
+        assert_eq!(
+            format_link("A [Title]", "https://github.com\u{FFFC} "),
+            "[A \\[Title\\]](<https://github.com>)"
+        );
+    }
+
+    #[test]
+    fn can_export_link_title() {
+        let export_path = temp_dir().join("imessage-markdown-link-title");
+        let _ = remove_dir_all(&export_path);
+        create_dir_all(&export_path).unwrap();
+
+        let mut options = Options::fake_options(ExportType::Markdown);
+        options.db_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+        options.export_path = export_path.clone();
+        let config = Config::new(options).unwrap();
+
+        // Drop the exporter so every file is flushed before it is read
+        {
+            let mut exporter = Markdown::new(&config).unwrap();
+            exporter.iter_messages().unwrap();
+        }
+
+        let actual = read_to_string(export_path.join("+15555550101.md")).unwrap();
+        assert!(actual.contains("[Christopher Sardegna](<https://chrissardegna.com>)  \n"));
+        assert!(!actual.contains("https://chrissardegna.com  \n"));
+
+        remove_dir_all(&export_path).unwrap();
+    }
+
+    #[test]
+    fn can_escape_markdown() {
+        assert_eq!(escape_markdown("a_b <c> [d]"), "a_b \\<c\\> \\[d\\]");
+    }
+
+    #[test]
+    fn can_sanitize_note_name() {
+        assert_eq!(
+            note_name("Book Club: [2024] #1 | a/b"),
+            "Book Club_ _2024_ _1 _ a_b"
+        );
+    }
+}
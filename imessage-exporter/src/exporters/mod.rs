@@ -1,4 +1,9 @@
+pub mod csv;
 pub mod exporter;
 pub mod html;
-pub mod txt;
 pub mod json;
+pub mod markdown;
+pub mod parquet;
+pub mod pdf;
+pub mod txt;
+pub mod whatsapp;
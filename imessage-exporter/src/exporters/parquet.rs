@@ -0,0 +1,1077 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+use log::info;
+
+use crate::{
+    app::{error::RuntimeError, export_file::ExportFile, runtime::Config},
+    exporters::exporter::{generate_text_or_placeholder, redact_attachments, Exporter},
+};
+
+use imessage_database::{
+    message_types::variants::Variant,
+    tables::{attachment::Attachment, messages::Message, table::Table},
+    util::dates::TIMESTAMP_FACTOR,
+};
+
+/// Name of the file that lists every message, before any extension
+pub const MESSAGES: &str = "messages";
+/// Name of the file that lists every attachment, keyed by the GUID of the message it was sent with
+pub const ATTACHMENTS: &str = "attachments";
+/// Name of the file that lists every tapback, keyed by the GUID of the message it reacts to
+pub const REACTIONS: &str = "reactions";
+
+/// Columns of [`MESSAGES`], in order
+const MESSAGE_COLUMNS: [Column; 15] = [
+    Column::required("guid", ColumnType::String),
+    Column::optional("conversation_id", ColumnType::Int32),
+    Column::required("timestamp", ColumnType::Timestamp),
+    Column::required("sender", ColumnType::String),
+    Column::required("receiver", ColumnType::String),
+    Column::required("is_from_me", ColumnType::Boolean),
+    Column::optional("service", ColumnType::String),
+    Column::optional("subject", ColumnType::String),
+    Column::optional("text", ColumnType::String),
+    Column::required("attachments", ColumnType::Int32),
+    Column::optional("reply_to_guid", ColumnType::String),
+    Column::required("is_read", ColumnType::Boolean),
+    Column::optional("date_read", ColumnType::Timestamp),
+    Column::optional("date_delivered", ColumnType::Timestamp),
+    Column::required("deleted", ColumnType::Boolean),
+];
+
+/// Columns of [`ATTACHMENTS`], in order
+const ATTACHMENT_COLUMNS: [Column; 7] = [
+    Column::required("message_guid", ColumnType::String),
+    Column::optional("guid", ColumnType::String),
+    Column::required("name", ColumnType::String),
+    Column::optional("mime_type", ColumnType::String),
+    Column::required("total_bytes", ColumnType::Int64),
+    Column::required("is_sticker", ColumnType::Boolean),
+    Column::required("path", ColumnType::String),
+];
+
+/// Columns of [`REACTIONS`], in order
+const REACTION_COLUMNS: [Column; 8] = [
+    Column::required("guid", ColumnType::String),
+    Column::required("target_guid", ColumnType::String),
+    Column::required("target_part", ColumnType::Int32),
+    Column::optional("conversation_id", ColumnType::Int32),
+    Column::required("timestamp", ColumnType::Timestamp),
+    Column::required("sender", ColumnType::String),
+    Column::required("type", ColumnType::String),
+    Column::required("removed", ColumnType::Boolean),
+];
+
+/// Number of rows buffered before they are written as a row group
+const ROW_GROUP_ROWS: usize = 65_536;
+/// Number of bytes of values buffered before they are written as a row group, so long messages do not fill memory
+const ROW_GROUP_BYTES: usize = 64 * 1024 * 1024;
+/// Microseconds in a second, the unit timestamps are stored in
+const MICROS_PER_SECOND: i64 = 1_000_000;
+
+pub struct Parquet<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// File every message is written to
+    pub messages: ParquetFile,
+    /// File the attachments of each message are written to
+    pub attachments: ParquetFile,
+    /// File every tapback is written to
+    pub reactions: ParquetFile,
+}
+
+impl<'a> Exporter<'a> for Parquet<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let create = |name: &str, columns| {
+            let path = config
+                .options
+                .export_path
+                .join(name)
+                .with_extension(config.file_extension("parquet"));
+            ParquetFile::create(path, config.options.compress, columns)
+        };
+
+        Ok(Parquet {
+            config,
+            messages: create(MESSAGES, &MESSAGE_COLUMNS)?,
+            attachments: create(ATTACHMENTS, &ATTACHMENT_COLUMNS)?,
+            reactions: create(REACTIONS, &REACTION_COLUMNS)?,
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as parquet...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Keep track of messages dropped because they do not belong to a conversation
+        let mut dropped_messages = 0;
+
+        // Keep track of messages skipped because a previous export already wrote them
+        let mut skipped_messages = 0;
+
+        // Keep track of messages skipped because their text is too short
+        let mut short_messages = 0;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = self.config.progress_bar(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(RuntimeError::from)?;
+
+        for message in messages {
+            let mut msg = match Message::extract(message) {
+                Ok(msg) => msg,
+                Err(why) => {
+                    self.config.handle_error(RuntimeError::DatabaseError(why))?;
+                    current_message += 1;
+                    continue;
+                }
+            };
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Drop messages that do not belong to a conversation, if requested
+            if self.config.options.no_orphaned && self.config.is_orphaned(&msg) {
+                dropped_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Skip messages that a previous export already wrote, if requested
+            if self.config.already_exported(&msg) {
+                skipped_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Generate the text of the message
+            generate_text_or_placeholder(&mut msg, &self.config.db);
+
+            // Skip messages that are too short, which is only known once their text is generated
+            if self.config.is_too_short(&msg) {
+                short_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Note when the message was delivered and read, and list it on its own if it is a tapback, if requested
+            self.config.record_receipt(&msg);
+            self.config.record_reaction(&msg);
+
+            if let Err(why) = self
+                .write_message(&msg)
+                .map_err(RuntimeError::DiskError)
+                .and_then(|_| self.config.mark_exported(&msg.guid))
+            {
+                self.config.handle_error(why)?;
+            }
+
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        if dropped_messages > 0 {
+            info!("Dropped {dropped_messages} messages that do not belong to a conversation");
+        }
+        if skipped_messages > 0 {
+            info!("Skipped {skipped_messages} messages that a previous export already wrote");
+        }
+        if short_messages > 0 {
+            info!("Skipped {short_messages} messages that are too short");
+        }
+
+        info!("Writing Parquet footers...");
+        self.messages.finish().map_err(RuntimeError::DiskError)?;
+        self.attachments.finish().map_err(RuntimeError::DiskError)?;
+        self.reactions.finish().map_err(RuntimeError::DiskError)?;
+
+        Ok(())
+    }
+
+    /// Every message is written to [`MESSAGES`] through [`ParquetFile`], so this only exposes the underlying file
+    fn get_or_create_file(
+        &mut self,
+        _: &Message,
+    ) -> Result<&mut BufWriter<ExportFile>, RuntimeError> {
+        self.messages
+            .writer
+            .as_mut()
+            .ok_or(RuntimeError::DiskError(io::ErrorKind::BrokenPipe.into()))
+    }
+
+    fn supports_attachments() -> bool {
+        // Attachments are listed, not copied
+        false
+    }
+
+    fn supports_reactions() -> bool {
+        // Tapbacks are written to a file of their own
+        false
+    }
+}
+
+impl<'a> Parquet<'a> {
+    /// Write a message to the file it belongs in, along with any attachments it was sent with
+    fn write_message(&mut self, message: &Message) -> io::Result<()> {
+        if let Variant::Tapback(part, added, tapback) = message.variant() {
+            let Some((_, target_guid)) = message.clean_associated_guid() else {
+                return Ok(());
+            };
+            let tapback = tapback.to_string();
+            return self.reactions.add_row(&[
+                Value::String(&message.guid),
+                Value::String(target_guid),
+                Value::Int32(part as i32),
+                message.chat_id.map_or(Value::Null, Value::Int32),
+                Value::Timestamp(unix_micros(message.date, self.config.offset)),
+                Value::String(self.config.who(
+                    message.handle_id,
+                    message.is_from_me(),
+                    &message.destination_caller_id,
+                )),
+                Value::String(&tapback),
+                Value::Boolean(!added),
+            ]);
+        }
+
+        let redacted = match self.config.options.redact_attachments {
+            true => redact_attachments(message),
+            false => None,
+        };
+        self.messages.add_row(&Parquet::format_row(
+            self.config,
+            message,
+            redacted.as_deref(),
+        ))?;
+
+        // Leave attachments out, if requested
+        if self.config.options.redact_attachments || !message.has_attachments() {
+            return Ok(());
+        }
+        for attachment in Attachment::from_message(&self.config.db, message).unwrap_or_default() {
+            let path = self.config.message_attachment_path(&attachment);
+            self.attachments.add_row(&[
+                Value::String(&message.guid),
+                attachment
+                    .guid
+                    .as_deref()
+                    .map_or(Value::Null, Value::String),
+                Value::String(attachment.filename()),
+                attachment
+                    .mime_type
+                    .as_deref()
+                    .map_or(Value::Null, Value::String),
+                Value::Int64(attachment.total_bytes),
+                Value::Boolean(attachment.is_sticker),
+                Value::String(&path),
+            ])?;
+        }
+        Ok(())
+    }
+
+    /// Build the row of [`MESSAGES`] for a message
+    ///
+    /// `redacted` is the text to use in place of the message's text when attachments are redacted
+    fn format_row<'b>(
+        config: &'b Config,
+        message: &'b Message,
+        redacted: Option<&'b str>,
+    ) -> Vec<Value<'b>> {
+        let timestamp =
+            |date: i64| (date != 0).then(|| Value::Timestamp(unix_micros(date, config.offset)));
+
+        let sender = config.who(
+            message.handle_id,
+            message.is_from_me,
+            &message.destination_caller_id,
+        );
+        let receiver = if message.is_from_me {
+            config.who(None, false, &message.destination_caller_id)
+        } else {
+            config.who(message.handle_id, true, &message.destination_caller_id)
+        };
+
+        // Leave attachments out of the text and count, if requested
+        let (text, attachments) = if config.options.redact_attachments {
+            (redacted, 0)
+        } else {
+            (message.text.as_deref(), message.num_attachments)
+        };
+
+        vec![
+            Value::String(&message.guid),
+            message
+                .chat_id
+                .or(message.deleted_from)
+                .map_or(Value::Null, Value::Int32),
+            Value::Timestamp(unix_micros(message.date, config.offset)),
+            Value::String(sender),
+            Value::String(receiver),
+            Value::Boolean(message.is_from_me),
+            message
+                .service
+                .as_deref()
+                .map_or(Value::Null, Value::String),
+            message
+                .subject
+                .as_deref()
+                .filter(|subject| !subject.is_empty())
+                .map_or(Value::Null, Value::String),
+            text.map_or(Value::Null, Value::String),
+            Value::Int32(attachments),
+            message
+                .thread_originator_guid
+                .as_deref()
+                .map_or(Value::Null, Value::String),
+            Value::Boolean(message.is_read),
+            timestamp(message.date_read).unwrap_or(Value::Null),
+            timestamp(message.date_delivered).unwrap_or(Value::Null),
+            Value::Boolean(message.is_deleted()),
+        ]
+    }
+}
+
+/// Convert a date from the iMessage table to microseconds since the unix epoch
+fn unix_micros(date: i64, offset: i64) -> i64 {
+    date / (TIMESTAMP_FACTOR / MICROS_PER_SECOND) + offset * MICROS_PER_SECOND
+}
+
+/// The kind of data stored in a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    /// `true` or `false`
+    Boolean,
+    /// A 32-bit signed integer
+    Int32,
+    /// A 64-bit signed integer
+    Int64,
+    /// Microseconds since the unix epoch, in UTC
+    Timestamp,
+    /// UTF-8 text
+    String,
+}
+
+impl ColumnType {
+    /// The physical `Type` values of the column are stored as
+    fn physical_type(&self) -> i32 {
+        match self {
+            ColumnType::Boolean => 0,
+            ColumnType::Int32 => 1,
+            ColumnType::Int64 | ColumnType::Timestamp => 2,
+            ColumnType::String => 6,
+        }
+    }
+
+    /// The `ConvertedType` older readers use to interpret the column, if it needs one
+    fn converted_type(&self) -> Option<i32> {
+        match self {
+            ColumnType::String => Some(0),
+            ColumnType::Timestamp => Some(10),
+            _ => None,
+        }
+    }
+}
+
+/// Describes a column of a Parquet file
+#[derive(Debug)]
+pub struct Column {
+    /// The name of the column
+    pub name: &'static str,
+    /// The kind of data the column holds
+    pub kind: ColumnType,
+    /// `true` if the column can hold nulls, else `false`
+    pub optional: bool,
+}
+
+impl Column {
+    /// Describe a column that holds a value in every row
+    pub const fn required(name: &'static str, kind: ColumnType) -> Self {
+        Column {
+            name,
+            kind,
+            optional: false,
+        }
+    }
+
+    /// Describe a column that can hold nulls
+    pub const fn optional(name: &'static str, kind: ColumnType) -> Self {
+        Column {
+            name,
+            kind,
+            optional: true,
+        }
+    }
+
+    /// `true` if the column can hold `value`, else `false`
+    fn accepts(&self, value: &Value) -> bool {
+        match value {
+            Value::Boolean(_) => self.kind == ColumnType::Boolean,
+            Value::Int32(_) => self.kind == ColumnType::Int32,
+            Value::Int64(_) => self.kind == ColumnType::Int64,
+            Value::Timestamp(_) => self.kind == ColumnType::Timestamp,
+            Value::String(_) => self.kind == ColumnType::String,
+            Value::Null => self.optional,
+        }
+    }
+}
+
+/// A single value of a row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value<'a> {
+    Boolean(bool),
+    Int32(i32),
+    Int64(i64),
+    /// Microseconds since the unix epoch, in UTC
+    Timestamp(i64),
+    String(&'a str),
+    Null,
+}
+
+/// The values of a column that are buffered until its row group is written
+#[derive(Debug, Default)]
+struct ColumnBuffer {
+    /// Values that are not null, `PLAIN` encoded
+    values: Vec<u8>,
+    /// Boolean values, which are bit-packed when the row group is written
+    booleans: Vec<bool>,
+    /// Whether each row has a value, if the column is optional
+    defined: Vec<bool>,
+}
+
+/// Where a column chunk was written, for the file's footer
+#[derive(Debug)]
+struct ChunkMeta {
+    /// Byte offset of the chunk's only page
+    offset: i64,
+    /// Number of bytes in the chunk, including the page header
+    size: i64,
+}
+
+/// Where a row group was written, for the file's footer
+#[derive(Debug)]
+struct RowGroupMeta {
+    /// Number of rows in the group
+    rows: i64,
+    /// Each column's chunk of the group, in the order of the columns
+    chunks: Vec<ChunkMeta>,
+}
+
+/// A Parquet file that is written one row group at a time
+///
+/// Rows are buffered by column and written as a row group once enough of them are added, so memory use
+/// does not grow with the number of rows. Each column chunk is a single uncompressed data page with `PLAIN`
+/// encoded values, as described by the [Parquet format](https://parquet.apache.org/docs/file-format/);
+/// the schema and the location of each row group are written as the footer by [`ParquetFile::finish()`].
+pub struct ParquetFile {
+    /// The file the data is written to, until it is finished
+    writer: Option<BufWriter<ExportFile>>,
+    /// Number of bytes written so far; the file may be compressed, so it cannot be asked
+    position: u64,
+    /// The columns of each row
+    columns: &'static [Column],
+    /// Values of the rows that are not written yet, one buffer per column
+    buffers: Vec<ColumnBuffer>,
+    /// Number of rows that are not written yet
+    buffered_rows: usize,
+    /// Number of rows buffered before they are written
+    row_group_size: usize,
+    /// Row groups that are written
+    row_groups: Vec<RowGroupMeta>,
+}
+
+impl ParquetFile {
+    /// Create a new file at the given path, replacing any file that is already there
+    pub fn create(
+        path: PathBuf,
+        compress: bool,
+        columns: &'static [Column],
+    ) -> Result<Self, RuntimeError> {
+        let file = File::create(&path)
+            .and_then(|file| ExportFile::new(file, compress))
+            .map_err(|err| RuntimeError::CreateError(err, path))?;
+        ParquetFile::new(BufWriter::new(file), columns, ROW_GROUP_ROWS)
+            .map_err(RuntimeError::DiskError)
+    }
+
+    fn new(
+        writer: BufWriter<ExportFile>,
+        columns: &'static [Column],
+        row_group_size: usize,
+    ) -> io::Result<Self> {
+        let mut file = ParquetFile {
+            writer: Some(writer),
+            position: 0,
+            columns,
+            buffers: columns.iter().map(|_| ColumnBuffer::default()).collect(),
+            buffered_rows: 0,
+            row_group_size,
+            row_groups: vec![],
+        };
+        file.write_all(b"PAR1")?;
+        Ok(file)
+    }
+
+    /// Write some bytes to the file, keeping track of the position
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.position += bytes.len() as u64;
+        match self.writer.as_mut() {
+            Some(writer) => writer.write_all(bytes),
+            None => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    /// Add a row, with one value for each column in order
+    ///
+    /// Nothing is added if any value does not fit its column.
+    pub fn add_row(&mut self, row: &[Value]) -> io::Result<()> {
+        if row.len() != self.columns.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Row has {} values, but there are {} columns",
+                    row.len(),
+                    self.columns.len()
+                ),
+            ));
+        }
+        if let Some((column, value)) = self
+            .columns
+            .iter()
+            .zip(row)
+            .find(|(column, value)| !column.accepts(value))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Column `{}` cannot hold {value:?}", column.name),
+            ));
+        }
+
+        let mut buffered_bytes = 0;
+        for ((column, buffer), value) in self.columns.iter().zip(&mut self.buffers).zip(row) {
+            match value {
+                Value::Boolean(value) => buffer.booleans.push(*value),
+                Value::Int32(value) => buffer.values.extend_from_slice(&value.to_le_bytes()),
+                Value::Int64(value) | Value::Timestamp(value) => {
+                    buffer.values.extend_from_slice(&value.to_le_bytes())
+                }
+                Value::String(value) => {
+                    buffer
+                        .values
+                        .extend_from_slice(&(value.len() as u32).to_le_bytes());
+                    buffer.values.extend_from_slice(value.as_bytes());
+                }
+                Value::Null => {}
+            }
+            if column.optional {
+                buffer.defined.push(*value != Value::Null);
+            }
+            buffered_bytes += buffer.values.len();
+        }
+        self.buffered_rows += 1;
+
+        if self.buffered_rows >= self.row_group_size || buffered_bytes >= ROW_GROUP_BYTES {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    /// Write the buffered rows as a row group, one page per column
+    fn write_row_group(&mut self) -> io::Result<()> {
+        if self.buffered_rows == 0 {
+            return Ok(());
+        }
+        let rows = self.buffered_rows;
+        let buffers = std::mem::replace(
+            &mut self.buffers,
+            self.columns
+                .iter()
+                .map(|_| ColumnBuffer::default())
+                .collect(),
+        );
+
+        let mut chunks = Vec::with_capacity(buffers.len());
+        for (column, buffer) in self.columns.iter().zip(buffers) {
+            let mut page = vec![];
+            // Optional columns start with the definition level of each row, prefixed with their length
+            if column.optional {
+                let levels = encode_levels(&buffer.defined);
+                page.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+                page.extend_from_slice(&levels);
+            }
+            match column.kind {
+                ColumnType::Boolean => page.extend_from_slice(&pack_booleans(&buffer.booleans)),
+                _ => page.extend_from_slice(&buffer.values),
+            }
+
+            let header = page_header(rows, page.len());
+            let offset = self.position as i64;
+            self.write_all(&header)?;
+            self.write_all(&page)?;
+            chunks.push(ChunkMeta {
+                offset,
+                size: (header.len() + page.len()) as i64,
+            });
+        }
+
+        self.row_groups.push(RowGroupMeta {
+            rows: rows as i64,
+            chunks,
+        });
+        self.buffered_rows = 0;
+        Ok(())
+    }
+
+    /// Write the rows that are still buffered and the footer, then close the file
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.write_row_group()?;
+        let footer = self.footer();
+        self.write_all(&footer)?;
+        self.write_all(&(footer.len() as u32).to_le_bytes())?;
+        self.write_all(b"PAR1")?;
+        match self.writer.take() {
+            Some(writer) => writer
+                .into_inner()
+                .map_err(|err| err.into_error())?
+                .finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Encode the `FileMetaData` that describes the schema and where each row group was written
+    fn footer(&self) -> Vec<u8> {
+        let mut thrift = Thrift::default();
+        thrift.i32(1, 1);
+
+        // The schema is a tree flattened depth first; the root holds every column
+        thrift.list(2, TYPE_STRUCT, self.columns.len() + 1);
+        thrift.begin_element();
+        thrift.binary(4, b"schema");
+        thrift.i32(5, self.columns.len() as i32);
+        thrift.end_struct();
+        for column in self.columns {
+            thrift.begin_element();
+            thrift.i32(1, column.kind.physical_type());
+            thrift.i32(3, i32::from(column.optional));
+            thrift.binary(4, column.name.as_bytes());
+            if let Some(converted_type) = column.kind.converted_type() {
+                thrift.i32(6, converted_type);
+            }
+            match column.kind {
+                ColumnType::String => {
+                    thrift.begin_struct(10);
+                    thrift.begin_struct(1);
+                    thrift.end_struct();
+                    thrift.end_struct();
+                }
+                ColumnType::Timestamp => {
+                    thrift.begin_struct(10);
+                    thrift.begin_struct(8);
+                    thrift.bool(1, true);
+                    thrift.begin_struct(2);
+                    thrift.begin_struct(2);
+                    thrift.end_struct();
+                    thrift.end_struct();
+                    thrift.end_struct();
+                    thrift.end_struct();
+                }
+                _ => {}
+            }
+            thrift.end_struct();
+        }
+
+        thrift.i64(3, self.row_groups.iter().map(|group| group.rows).sum());
+
+        thrift.list(4, TYPE_STRUCT, self.row_groups.len());
+        for group in &self.row_groups {
+            thrift.begin_element();
+            thrift.list(1, TYPE_STRUCT, group.chunks.len());
+            for (column, chunk) in self.columns.iter().zip(&group.chunks) {
+                thrift.begin_element();
+                thrift.i64(2, chunk.offset);
+                thrift.begin_struct(3);
+                thrift.i32(1, column.kind.physical_type());
+                // `PLAIN` values, with `RLE` definition levels if the column is optional
+                if column.optional {
+                    thrift.list(2, TYPE_I32, 2);
+                    thrift.list_i32(0);
+                    thrift.list_i32(3);
+                } else {
+                    thrift.list(2, TYPE_I32, 1);
+                    thrift.list_i32(0);
+                }
+                thrift.list(3, TYPE_BINARY, 1);
+                thrift.list_binary(column.name.as_bytes());
+                // Uncompressed
+                thrift.i32(4, 0);
+                thrift.i64(5, group.rows);
+                thrift.i64(6, chunk.size);
+                thrift.i64(7, chunk.size);
+                thrift.i64(9, chunk.offset);
+                thrift.end_struct();
+                thrift.end_struct();
+            }
+            thrift.i64(2, group.chunks.iter().map(|chunk| chunk.size).sum());
+            thrift.i64(3, group.rows);
+            thrift.end_struct();
+        }
+
+        thrift.binary(
+            6,
+            format!("imessage-exporter version {}", env!("CARGO_PKG_VERSION")).as_bytes(),
+        );
+        thrift.finish()
+    }
+}
+
+/// Encode the `PageHeader` of an uncompressed data page holding `rows` values in `size` bytes
+fn page_header(rows: usize, size: usize) -> Vec<u8> {
+    let mut thrift = Thrift::default();
+    // `DATA_PAGE`
+    thrift.i32(1, 0);
+    thrift.i32(2, size as i32);
+    thrift.i32(3, size as i32);
+    thrift.begin_struct(5);
+    thrift.i32(1, rows as i32);
+    // `PLAIN` values, with `RLE` definition and repetition levels
+    thrift.i32(2, 0);
+    thrift.i32(3, 3);
+    thrift.i32(4, 3);
+    thrift.end_struct();
+    thrift.finish()
+}
+
+/// Encode definition levels with a bit width of 1 as runs of the `RLE` hybrid encoding
+fn encode_levels(defined: &[bool]) -> Vec<u8> {
+    let mut levels = vec![];
+    let mut idx = 0;
+    while idx < defined.len() {
+        let run = defined[idx..]
+            .iter()
+            .take_while(|value| **value == defined[idx])
+            .count();
+        // The lowest bit of the header is clear for runs of repeated values
+        write_varint(&mut levels, (run as u64) << 1);
+        levels.push(u8::from(defined[idx]));
+        idx += run;
+    }
+    levels
+}
+
+/// Pack booleans into bits, starting from the lowest bit of each byte
+fn pack_booleans(values: &[bool]) -> Vec<u8> {
+    values
+        .chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0, |byte, (bit, value)| byte | (u8::from(*value) << bit))
+        })
+        .collect()
+}
+
+/// Write an unsigned integer in as few bytes as possible, 7 bits at a time
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Thrift type of a `true` boolean field
+const TYPE_TRUE: u8 = 1;
+/// Thrift type of a `false` boolean field
+const TYPE_FALSE: u8 = 2;
+/// Thrift type of a 32-bit integer
+const TYPE_I32: u8 = 5;
+/// Thrift type of a 64-bit integer
+const TYPE_I64: u8 = 6;
+/// Thrift type of a string or binary value
+const TYPE_BINARY: u8 = 8;
+/// Thrift type of a list
+const TYPE_LIST: u8 = 9;
+/// Thrift type of a struct
+const TYPE_STRUCT: u8 = 12;
+
+/// Encodes structs with the Thrift compact protocol, which Parquet uses for its metadata
+///
+/// Fields must be written in the order of their IDs. Nested structs are opened with [`Thrift::begin_struct()`]
+/// or [`Thrift::begin_element()`] and closed with [`Thrift::end_struct()`].
+#[derive(Debug, Default)]
+struct Thrift {
+    bytes: Vec<u8>,
+    /// ID of the last field written to the struct being written
+    last_id: i16,
+    /// IDs of the last fields written to the structs that contain the one being written
+    parents: Vec<i16>,
+}
+
+impl Thrift {
+    /// Write a field's header, which holds the difference from the previous field's ID when it is small
+    fn field(&mut self, id: i16, kind: u8) {
+        match id - self.last_id {
+            delta @ 1..=15 => self.bytes.push(((delta as u8) << 4) | kind),
+            _ => {
+                self.bytes.push(kind);
+                write_varint(&mut self.bytes, zigzag(id.into()));
+            }
+        }
+        self.last_id = id;
+    }
+
+    fn bool(&mut self, id: i16, value: bool) {
+        self.field(id, if value { TYPE_TRUE } else { TYPE_FALSE });
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, TYPE_I32);
+        write_varint(&mut self.bytes, zigzag(value.into()));
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, TYPE_I64);
+        write_varint(&mut self.bytes, zigzag(value));
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.field(id, TYPE_BINARY);
+        self.list_binary(value);
+    }
+
+    /// Write the header of a list of `len` elements of type `kind`
+    fn list(&mut self, id: i16, kind: u8, len: usize) {
+        self.field(id, TYPE_LIST);
+        if len < 15 {
+            self.bytes.push(((len as u8) << 4) | kind);
+        } else {
+            self.bytes.push(0xF0 | kind);
+            write_varint(&mut self.bytes, len as u64);
+        }
+    }
+
+    fn list_i32(&mut self, value: i32) {
+        write_varint(&mut self.bytes, zigzag(value.into()));
+    }
+
+    fn list_binary(&mut self, value: &[u8]) {
+        write_varint(&mut self.bytes, value.len() as u64);
+        self.bytes.extend_from_slice(value);
+    }
+
+    /// Start a struct that is a field of the one being written
+    fn begin_struct(&mut self, id: i16) {
+        self.field(id, TYPE_STRUCT);
+        self.begin_element();
+    }
+
+    /// Start a struct that is an element of a list
+    fn begin_element(&mut self) {
+        self.parents.push(self.last_id);
+        self.last_id = 0;
+    }
+
+    fn end_struct(&mut self) {
+        self.bytes.push(0);
+        self.last_id = self.parents.pop().unwrap_or_default();
+    }
+
+    /// End the outermost struct and get its encoding
+    fn finish(mut self) -> Vec<u8> {
+        self.bytes.push(0);
+        self.bytes
+    }
+}
+
+/// Map signed integers to unsigned ones so that small negative numbers stay small
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read, remove_dir_all, remove_file, File},
+        io::BufWriter,
+        path::PathBuf,
+    };
+
+    use imessage_database::util::dates::get_offset;
+
+    use crate::{
+        app::{export_file::ExportFile, export_type::ExportType},
+        exporters::parquet::{
+            encode_levels, pack_booleans, unix_micros, Column, ColumnType, Parquet, ParquetFile,
+            Thrift, Value, ATTACHMENTS, MESSAGES, REACTIONS,
+        },
+        Config, Exporter, Options,
+    };
+
+    /// Columns of the files written by the tests
+    const COLUMNS: [Column; 2] = [
+        Column::required("id", ColumnType::Int32),
+        Column::optional("name", ColumnType::String),
+    ];
+
+    fn open(name: &str, row_group_size: usize) -> (ParquetFile, PathBuf) {
+        let path = temp_dir().join(name);
+        let file = File::create(&path)
+            .and_then(|file| ExportFile::new(file, false))
+            .unwrap();
+        (
+            ParquetFile::new(BufWriter::new(file), &COLUMNS, row_group_size).unwrap(),
+            path,
+        )
+    }
+
+    #[test]
+    fn can_encode_thrift_fields() {
+        let mut thrift = Thrift::default();
+        thrift.i32(1, 1);
+        thrift.bool(2, true);
+        // Fields more than 15 apart store their ID after the type
+        thrift.i64(20, -1);
+        thrift.begin_struct(21);
+        thrift.binary(1, b"id");
+        thrift.end_struct();
+
+        assert_eq!(
+            thrift.finish(),
+            vec![0x15, 0x02, 0x11, 0x06, 0x28, 0x01, 0x1C, 0x18, 0x02, b'i', b'd', 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn can_encode_long_thrift_list() {
+        let mut thrift = Thrift::default();
+        thrift.list(1, 5, 16);
+        assert_eq!(thrift.finish(), vec![0x19, 0xF5, 0x10, 0x00]);
+    }
+
+    #[test]
+    fn can_encode_levels() {
+        assert_eq!(
+            encode_levels(&[true, true, false, true]),
+            vec![0x04, 0x01, 0x02, 0x00, 0x02, 0x01]
+        );
+        assert!(encode_levels(&[]).is_empty());
+    }
+
+    #[test]
+    fn can_pack_booleans() {
+        let values = [true, false, true, true, false, false, false, false, true];
+        assert_eq!(pack_booleans(&values), vec![0b0000_1101, 0b0000_0001]);
+    }
+
+    #[test]
+    fn can_convert_timestamp() {
+        // March 8, 2023 8:26:40 PM UTC
+        assert_eq!(
+            unix_micros(700_000_000_123_456_789, get_offset()),
+            1_678_307_200_123_456
+        );
+    }
+
+    #[test]
+    fn can_write_row_groups() {
+        let (mut file, path) = open("imessage-parquet-groups.parquet", 2);
+        for id in 0..5 {
+            let name = format!("Row {id}");
+            let name = match id % 2 {
+                0 => Value::String(&name),
+                _ => Value::Null,
+            };
+            file.add_row(&[Value::Int32(id), name]).unwrap();
+        }
+        file.finish().unwrap();
+
+        let rows: Vec<i64> = file.row_groups.iter().map(|group| group.rows).collect();
+        assert_eq!(rows, vec![2, 2, 1]);
+
+        let bytes = read(&path).unwrap();
+        assert!(bytes.starts_with(b"PAR1"));
+        assert!(bytes.ends_with(b"PAR1"));
+        // The footer's length is written just before the closing magic number
+        let footer =
+            u32::from_le_bytes(bytes[bytes.len() - 8..bytes.len() - 4].try_into().unwrap());
+        let last_chunk = file.row_groups[2].chunks.last().unwrap();
+        assert_eq!(
+            (last_chunk.offset + last_chunk.size) as usize,
+            bytes.len() - 8 - footer as usize
+        );
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn cant_add_row_wrong_type() {
+        let (mut file, path) = open("imessage-parquet-types.parquet", 2);
+        assert!(file.add_row(&[Value::Null, Value::Null]).is_err());
+        assert!(file
+            .add_row(&[Value::String("1"), Value::String("One")])
+            .is_err());
+        assert!(file.add_row(&[Value::Int32(1)]).is_err());
+
+        // Rejected rows are not partially added
+        assert_eq!(file.buffered_rows, 0);
+        assert!(file.buffers.iter().all(|buffer| buffer.values.is_empty()));
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn can_export_fixture_database() {
+        let export_path = temp_dir().join("imessage-parquet");
+        let _ = remove_dir_all(&export_path);
+        create_dir_all(&export_path).unwrap();
+
+        let mut options = Options::fake_options(ExportType::Parquet);
+        options.db_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+        options.export_path = export_path.clone();
+        let config = Config::new(options).unwrap();
+
+        let mut exporter = Parquet::new(&config).unwrap();
+        exporter.iter_messages().unwrap();
+
+        // Tapbacks are written to their own file, and every other message is a row
+        assert_eq!(exporter.messages.row_groups[0].rows, 9);
+        assert_eq!(exporter.reactions.row_groups[0].rows, 1);
+        assert_eq!(exporter.attachments.row_groups[0].rows, 1);
+        for name in [MESSAGES, ATTACHMENTS, REACTIONS] {
+            let bytes = read(export_path.join(format!("{name}.parquet"))).unwrap();
+            assert!(bytes.starts_with(b"PAR1"));
+        }
+
+        remove_dir_all(&export_path).unwrap();
+    }
+}
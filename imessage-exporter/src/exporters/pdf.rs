@@ -0,0 +1,695 @@
+use std::{
+    collections::{
+        btree_map::Entry::{Occupied, Vacant},
+        BTreeMap,
+    },
+    fs::{read, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use log::info;
+
+use crate::{
+    app::{
+        error::RuntimeError, export_file::ExportFile, options::OPTION_NO_ORPHANED, runtime::Config,
+    },
+    exporters::{
+        exporter::{generate_text_or_placeholder, Exporter, Writer},
+        txt::TXT,
+    },
+};
+
+use imessage_database::tables::{
+    messages::Message,
+    table::{Table, ORPHANED},
+};
+
+/// Width of a US Letter page, in points
+const PAGE_WIDTH: f32 = 612.0;
+/// Height of a US Letter page, in points
+const PAGE_HEIGHT: f32 = 792.0;
+/// Blank space around the edge of each page, in points
+const MARGIN: f32 = 54.0;
+/// Size of the text, in points
+const FONT_SIZE: f32 = 10.0;
+/// Distance between the baselines of two lines of text, in points
+const LINE_HEIGHT: f32 = 12.0;
+/// Number of characters that fit on a line; Courier glyphs are 0.6 em wide
+const LINE_WIDTH: usize = 84;
+/// Tallest an embedded image may be drawn, in points
+const MAX_IMAGE_HEIGHT: f32 = 288.0;
+
+/// Object ID of the document catalog
+const CATALOG_ID: usize = 1;
+/// Object ID of the page tree
+const PAGES_ID: usize = 2;
+/// Object ID of the font used for all text
+const FONT_ID: usize = 3;
+
+pub struct PDF<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Renders messages into the text that gets laid out on each page
+    pub txt: TXT<'a>,
+    /// Handles to files we want to write messages to
+    /// Map of resolved chatroom file location to a document
+    pub files: BTreeMap<String, PdfDocument>,
+    /// Document for orphaned messages, unless they are dropped
+    pub orphaned: Option<PdfDocument>,
+}
+
+impl<'a> PDF<'a> {
+    /// Create a document for the given chat, caching it so we don't need to build it later
+    fn get_or_create_document(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut PdfDocument, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let mut path = self.config.conversation_dir(entry.key())?;
+                        path.push(entry.key());
+                        path.set_extension(self.config.file_extension("pdf"));
+
+                        Ok(entry.insert(PdfDocument::create(path, self.config.options.compress)?))
+                    }
+                }
+            }
+            None => self
+                .orphaned
+                .as_mut()
+                .ok_or(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_NO_ORPHANED} is enabled, but message {} has no conversation!",
+                    message.rowid
+                ))),
+        }
+    }
+
+    /// Lay out some rendered text, embedding any JPEG attachments it references below their path
+    fn write_to_document(&mut self, message: &Message, text: &str) -> Result<(), RuntimeError> {
+        // Attachment paths are relative to the directory the document is written to
+        let export_path = self.config.message_dir(message);
+        let document = self.get_or_create_document(message)?;
+
+        for line in text.strip_suffix('\n').unwrap_or(text).split('\n') {
+            document.add_line(line).map_err(RuntimeError::DiskError)?;
+            if let Some(image) = jpeg_path(line, &export_path).and_then(|path| read(path).ok()) {
+                document
+                    .add_image(&image)
+                    .map_err(RuntimeError::DiskError)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Exporter<'a> for PDF<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let orphaned = if config.options.no_orphaned {
+            None
+        } else {
+            let mut orphaned = config.conversation_dir(ORPHANED)?;
+            orphaned.push(ORPHANED);
+            orphaned.set_extension(config.file_extension("pdf"));
+            Some(PdfDocument::create(orphaned, config.options.compress)?)
+        };
+
+        Ok(PDF {
+            config,
+            txt: TXT {
+                config,
+                files: BTreeMap::new(),
+                orphaned: None,
+            },
+            files: BTreeMap::new(),
+            orphaned,
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as pdf...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Keep track of messages dropped because they do not belong to a conversation
+        let mut dropped_messages = 0;
+
+        // Keep track of messages skipped because a previous export already wrote them
+        let mut skipped_messages = 0;
+
+        // Keep track of messages skipped because their text is too short
+        let mut short_messages = 0;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = self.config.progress_bar(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(RuntimeError::from)?;
+
+        for message in messages {
+            let mut msg = match Message::extract(message) {
+                Ok(msg) => msg,
+                Err(why) => {
+                    self.config.handle_error(RuntimeError::DatabaseError(why))?;
+                    current_message += 1;
+                    continue;
+                }
+            };
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Drop messages that do not belong to a conversation, if requested
+            if self.config.options.no_orphaned && self.config.is_orphaned(&msg) {
+                dropped_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Skip messages that a previous export already wrote, if requested
+            if self.config.already_exported(&msg) {
+                skipped_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Generate the text of the message
+            generate_text_or_placeholder(&mut msg, &self.config.db);
+
+            // Skip messages that are too short, which is only known once their text is generated
+            if self.config.is_too_short(&msg) {
+                short_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Note when the message was delivered and read, and list it on its own if it is a tapback, if requested
+            self.config.record_receipt(&msg);
+            self.config.record_reaction(&msg);
+
+            // Render the announcement in-line
+            if msg.is_announcement() {
+                let announcement = self.txt.format_announcement(&msg);
+                if let Err(why) = self
+                    .write_to_document(&msg, &announcement)
+                    .and_then(|_| self.config.mark_exported(&msg.guid))
+                {
+                    self.config.handle_error(why)?;
+                }
+            }
+            // Message replies and tapbacks are rendered in context, so no need to render them separately
+            else if !msg.is_tapback() {
+                match self.txt.format_message(&msg, 0) {
+                    Ok(message) => {
+                        if let Err(why) = self
+                            .write_to_document(&msg, &message)
+                            .and_then(|_| self.config.mark_exported(&msg.guid))
+                        {
+                            self.config.handle_error(why)?;
+                        }
+                    }
+                    Err(why) => self.config.handle_error(RuntimeError::DatabaseError(why))?,
+                }
+            }
+
+            // Abort if an attachment failed to copy and we are not exporting on a best effort basis
+            self.config.check_errors()?;
+
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        if dropped_messages > 0 {
+            info!("Dropped {dropped_messages} messages that do not belong to a conversation");
+        }
+        if skipped_messages > 0 {
+            info!("Skipped {skipped_messages} messages that a previous export already wrote");
+        }
+        if short_messages > 0 {
+            info!("Skipped {short_messages} messages that are too short");
+        }
+
+        info!("Writing PDF page trees...");
+        for document in std::mem::take(&mut self.files).into_values() {
+            document.finish().map_err(RuntimeError::DiskError)?;
+        }
+        if let Some(orphaned) = self.orphaned.take() {
+            orphaned.finish().map_err(RuntimeError::DiskError)?;
+        }
+
+        Ok(())
+    }
+
+    /// PDF documents must be written through [`PdfDocument`], so this only exposes the underlying file
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<ExportFile>, RuntimeError> {
+        self.get_or_create_document(message)
+            .map(|document| &mut document.writer)
+    }
+
+    fn supports_attachments() -> bool {
+        true
+    }
+
+    fn supports_reactions() -> bool {
+        true
+    }
+}
+
+/// Determine if a line of rendered text is the path to a JPEG that exists on disk
+fn jpeg_path(line: &str, export_path: &Path) -> Option<PathBuf> {
+    let line = line.trim();
+    let lowercase = line.to_lowercase();
+    if !(lowercase.ends_with(".jpg") || lowercase.ends_with(".jpeg")) {
+        return None;
+    }
+    let path = export_path.join(line);
+    path.is_file().then_some(path)
+}
+
+/// Get the width, height, and number of color components of a JPEG from its frame header
+fn jpeg_dimensions(bytes: &[u8]) -> Option<(u16, u16, u8)> {
+    if bytes.get(0..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+
+    let mut idx = 2;
+    while idx + 4 <= bytes.len() {
+        if bytes[idx] != 0xFF {
+            return None;
+        }
+        let marker = bytes[idx + 1];
+        let length = u16::from_be_bytes([bytes[idx + 2], bytes[idx + 3]]) as usize;
+        // Start of frame markers, excluding the Huffman table, arithmetic coding, and restart markers
+        if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+            let frame = bytes.get(idx + 4..idx + 10)?;
+            let height = u16::from_be_bytes([frame[1], frame[2]]);
+            let width = u16::from_be_bytes([frame[3], frame[4]]);
+            return Some((width, height, frame[5]));
+        }
+        idx += 2 + length;
+    }
+    None
+}
+
+/// Encode text as single byte characters in the `WinAnsiEncoding` used by the standard fonts
+///
+/// Characters outside of Latin-1 cannot be drawn with the standard fonts, so they are replaced with `?`
+fn encode_text(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            '\t' => b' ',
+            ' '..='~' => c as u8,
+            '\u{A0}'..='\u{FF}' => c as u32 as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Escape the characters that have meaning inside of a PDF string literal
+fn escape_text(text: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(text.len());
+    for &byte in text {
+        if matches!(byte, b'(' | b')' | b'\\') {
+            escaped.push(b'\\');
+        }
+        escaped.push(byte);
+    }
+    escaped
+}
+
+/// A PDF file that is written one page at a time
+///
+/// Pages, their content, and any images are written as soon as a page fills up;
+/// the page tree and cross-reference table are written by [`PdfDocument::finish()`].
+pub struct PdfDocument {
+    /// The file the document is written to
+    writer: BufWriter<ExportFile>,
+    /// Number of bytes written so far; the file may be compressed, so it cannot be asked
+    position: u64,
+    /// Byte offset of each object, indexed by object ID - 1
+    offsets: Vec<u64>,
+    /// Object IDs of the finished pages
+    pages: Vec<usize>,
+    /// Content stream of the page being built
+    content: Vec<u8>,
+    /// Object IDs of the images drawn on the page being built
+    images: Vec<usize>,
+    /// Baseline of the next line of text on the page being built
+    cursor: f32,
+}
+
+impl PdfDocument {
+    /// Create a new document at the given path, replacing any file that is already there
+    pub fn create(path: PathBuf, compress: bool) -> Result<Self, RuntimeError> {
+        let file = File::create(&path)
+            .and_then(|file| ExportFile::new(file, compress))
+            .map_err(|err| RuntimeError::CreateError(err, path))?;
+        PdfDocument::new(BufWriter::new(file)).map_err(RuntimeError::DiskError)
+    }
+
+    fn new(writer: BufWriter<ExportFile>) -> std::io::Result<Self> {
+        let mut document = PdfDocument {
+            writer,
+            position: 0,
+            offsets: vec![0; FONT_ID],
+            pages: vec![],
+            content: vec![],
+            images: vec![],
+            cursor: PAGE_HEIGHT - MARGIN - FONT_SIZE,
+        };
+        // The binary comment signals that the file contains 8-bit data
+        document.write_all(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n")?;
+        document.write_object(
+            FONT_ID,
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>",
+        )?;
+        Ok(document)
+    }
+
+    /// Reserve the next object ID
+    fn next_id(&mut self) -> usize {
+        self.offsets.push(0);
+        self.offsets.len()
+    }
+
+    /// Write some bytes to the file, keeping track of the position
+    fn write_all(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.position += bytes.len() as u64;
+        self.writer.write_all(bytes)
+    }
+
+    /// Write an indirect object, recording where it starts for the cross-reference table
+    fn write_object(&mut self, id: usize, body: &[u8]) -> std::io::Result<()> {
+        self.offsets[id - 1] = self.position;
+        self.write_all(format!("{id} 0 obj\n").as_bytes())?;
+        self.write_all(body)?;
+        self.write_all(b"\nendobj\n")
+    }
+
+    /// Write a stream object containing the given data
+    fn write_stream(&mut self, id: usize, dictionary: &str, data: &[u8]) -> std::io::Result<()> {
+        let mut body = format!("<< {dictionary} /Length {} >>\nstream\n", data.len()).into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\nendstream");
+        self.write_object(id, &body)
+    }
+
+    /// Add a line of text, wrapping it and starting new pages as needed
+    pub fn add_line(&mut self, line: &str) -> std::io::Result<()> {
+        let encoded = encode_text(line);
+        // Blank lines still take up space
+        let chunks: Vec<&[u8]> = if encoded.is_empty() {
+            vec![&[][..]]
+        } else {
+            encoded.chunks(LINE_WIDTH).collect()
+        };
+
+        for chunk in chunks {
+            if self.cursor < MARGIN {
+                self.finish_page()?;
+            }
+            write!(
+                self.content,
+                "BT /F1 {FONT_SIZE} Tf {MARGIN} {} Td (",
+                self.cursor
+            )?;
+            self.content.extend_from_slice(&escape_text(chunk));
+            self.content.extend_from_slice(b") Tj ET\n");
+            self.cursor -= LINE_HEIGHT;
+        }
+        Ok(())
+    }
+
+    /// Draw a JPEG below the current line, scaled down to fit on the page
+    ///
+    /// Data that is not a JPEG is ignored.
+    pub fn add_image(&mut self, jpeg: &[u8]) -> std::io::Result<()> {
+        let Some((width, height, components)) = jpeg_dimensions(jpeg) else {
+            return Ok(());
+        };
+        let color_space = match components {
+            1 => "/DeviceGray",
+            4 => "/DeviceCMYK",
+            _ => "/DeviceRGB",
+        };
+
+        let scale = (1.0_f32)
+            .min((PAGE_WIDTH - 2.0 * MARGIN) / width.max(1) as f32)
+            .min(MAX_IMAGE_HEIGHT / height.max(1) as f32);
+        let (draw_width, draw_height) = (width as f32 * scale, height as f32 * scale);
+
+        // The image hangs from the top of the line it replaces
+        if self.cursor + FONT_SIZE - draw_height < MARGIN {
+            self.finish_page()?;
+        }
+        let bottom = self.cursor + FONT_SIZE - draw_height;
+
+        let id = self.next_id();
+        self.write_stream(
+            id,
+            &format!(
+                "/Type /XObject /Subtype /Image /Width {width} /Height {height} /ColorSpace {color_space} /BitsPerComponent 8 /Filter /DCTDecode"
+            ),
+            jpeg,
+        )?;
+
+        writeln!(
+            self.content,
+            "q {draw_width} 0 0 {draw_height} {MARGIN} {bottom} cm /Im{} Do Q",
+            self.images.len()
+        )?;
+        self.images.push(id);
+        self.cursor = bottom - LINE_HEIGHT;
+        Ok(())
+    }
+
+    /// Write the page being built and start a new one
+    fn finish_page(&mut self) -> std::io::Result<()> {
+        let content_id = self.next_id();
+        let content = std::mem::take(&mut self.content);
+        self.write_stream(content_id, "", &content)?;
+
+        let images: String = self
+            .images
+            .drain(..)
+            .enumerate()
+            .map(|(idx, id)| format!(" /Im{idx} {id} 0 R"))
+            .collect();
+
+        let page_id = self.next_id();
+        self.write_object(
+            page_id,
+            format!(
+                "<< /Type /Page /Parent {PAGES_ID} 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] /Resources << /Font << /F1 {FONT_ID} 0 R >> /XObject <<{images} >> >> /Contents {content_id} 0 R >>"
+            )
+            .as_bytes(),
+        )?;
+        self.pages.push(page_id);
+        self.cursor = PAGE_HEIGHT - MARGIN - FONT_SIZE;
+        Ok(())
+    }
+
+    /// Write the last page, the page tree, and the cross-reference table
+    pub fn finish(mut self) -> std::io::Result<()> {
+        // Every document needs at least one page
+        if !self.content.is_empty() || self.pages.is_empty() {
+            self.finish_page()?;
+        }
+
+        let kids: String = self
+            .pages
+            .iter()
+            .map(|id| format!("{id} 0 R"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.write_object(
+            PAGES_ID,
+            format!(
+                "<< /Type /Pages /Kids [{kids}] /Count {} >>",
+                self.pages.len()
+            )
+            .as_bytes(),
+        )?;
+        self.write_object(
+            CATALOG_ID,
+            format!("<< /Type /Catalog /Pages {PAGES_ID} 0 R >>").as_bytes(),
+        )?;
+
+        let xref = self.position;
+        writeln!(self.writer, "xref\n0 {}", self.offsets.len() + 1)?;
+        self.writer.write_all(b"0000000000 65535 f \n")?;
+        for offset in &self.offsets {
+            writeln!(self.writer, "{offset:010} 00000 n ")?;
+        }
+        writeln!(
+            self.writer,
+            "trailer\n<< /Size {} /Root {CATALOG_ID} 0 R >>\nstartxref\n{xref}\n%%EOF",
+            self.offsets.len() + 1
+        )?;
+        self.writer
+            .into_inner()
+            .map_err(|err| err.into_error())?
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read, remove_dir_all},
+    };
+
+    use crate::exporters::pdf::{
+        encode_text, escape_text, jpeg_dimensions, jpeg_path, PdfDocument, LINE_WIDTH,
+    };
+
+    /// The smallest JPEG header that describes a 640x480 RGB frame
+    const JPEG_HEADER: [u8; 17] = [
+        0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x02, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x01, 0xE0, 0x02, 0x80,
+        0x03, 0x01,
+    ];
+
+    /// Find the byte offsets listed in a document's cross-reference table
+    fn xref_offsets(pdf: &[u8]) -> Vec<usize> {
+        let text = String::from_utf8_lossy(pdf);
+        let xref = text.rfind("xref\n").unwrap();
+        text[xref..]
+            .lines()
+            .skip(3)
+            .take_while(|line| line.ends_with(" n "))
+            .map(|line| line[..10].parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn can_encode_text() {
+        assert_eq!(encode_text("Hello\tworld"), b"Hello world");
+        assert_eq!(encode_text("Café"), b"Caf\xE9");
+        assert_eq!(encode_text("Hi 👋"), b"Hi ?");
+    }
+
+    #[test]
+    fn can_escape_text() {
+        assert_eq!(escape_text(b"(a\\b)"), b"\\(a\\\\b\\)");
+    }
+
+    #[test]
+    fn can_get_jpeg_dimensions() {
+        assert_eq!(jpeg_dimensions(&JPEG_HEADER), Some((640, 480, 3)));
+    }
+
+    #[test]
+    fn cant_get_dimensions_not_jpeg() {
+        assert_eq!(jpeg_dimensions(b"\x89PNG\r\n\x1a\n"), None);
+        assert_eq!(jpeg_dimensions(&[]), None);
+    }
+
+    #[test]
+    fn can_write_document() {
+        let dir = temp_dir().join("imessage-pdf-document");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("document.pdf");
+
+        let mut document = PdfDocument::create(path.clone(), false).unwrap();
+        // Enough text to wrap and fill more than one page
+        for _ in 0..60 {
+            document.add_line(&"a".repeat(LINE_WIDTH + 1)).unwrap();
+        }
+        document.add_image(&JPEG_HEADER).unwrap();
+        document.finish().unwrap();
+
+        let pdf = read(&path).unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/Count 3"));
+        assert!(text.contains("/Width 640 /Height 480 /ColorSpace /DeviceRGB"));
+
+        // Every object must start where the cross-reference table says it does
+        for (idx, offset) in xref_offsets(&pdf).into_iter().enumerate() {
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", idx + 1).as_bytes()));
+        }
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_write_empty_document() {
+        let dir = temp_dir().join("imessage-pdf-empty");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("empty.pdf");
+
+        PdfDocument::create(path.clone(), false)
+            .unwrap()
+            .finish()
+            .unwrap();
+
+        let text = String::from_utf8_lossy(&read(&path).unwrap()).to_string();
+        assert!(text.contains("/Count 1"));
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_write_compressed_document() {
+        let dir = temp_dir().join("imessage-pdf-compressed");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("compressed.pdf.gz");
+
+        let mut document = PdfDocument::create(path.clone(), true).unwrap();
+        document.add_line("Hello world").unwrap();
+        document.finish().unwrap();
+
+        let compressed = read(&path).unwrap();
+        assert!(compressed.starts_with(&[0x1F, 0x8B]));
+        assert!(!compressed.windows(8).any(|bytes| bytes == b"%PDF-1.4"));
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_find_jpeg_path() {
+        let dir = temp_dir().join("imessage-pdf-jpeg-path");
+        create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("photo.JPG"), JPEG_HEADER).unwrap();
+
+        assert_eq!(
+            jpeg_path("    photo.JPG", &dir),
+            Some(dir.join("photo.JPG"))
+        );
+        assert_eq!(jpeg_path("missing.jpg", &dir), None);
+        assert_eq!(jpeg_path("photo.png", &dir), None);
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,19 @@
+// Show one conversation at a time, so only its images are loaded
+function showConversation(id) {
+	document.querySelectorAll("section.conversation").forEach((section) => {
+		section.hidden = section.id !== id;
+	});
+	window.scrollTo(0, 0);
+}
+
+// Decode embedded images shortly before they scroll into view
+const observer = new IntersectionObserver((entries) => {
+	entries.forEach((entry) => {
+		if (entry.isIntersecting) {
+			entry.target.src = entry.target.dataset.src;
+			entry.target.removeAttribute("data-src");
+			observer.unobserve(entry.target);
+		}
+	});
+}, { rootMargin: "500px" });
+document.querySelectorAll("img[data-src]").forEach((image) => observer.observe(image));
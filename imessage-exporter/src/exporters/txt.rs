@@ -1,20 +1,24 @@
 use std::{
     borrow::Cow,
     collections::{
-        hash_map::Entry::{Occupied, Vacant},
-        HashMap,
+        btree_map::Entry::{Occupied, Vacant},
+        BTreeMap, HashMap,
     },
-    fs::File,
     io::{BufWriter, Write},
     path::PathBuf,
 };
 
+use log::info;
+
 use crate::{
     app::{
         compatibility::attachment_manager::AttachmentManagerMode, error::RuntimeError,
-        progress::build_progress_bar_export, runtime::Config,
+        export_file::ExportFile, options::OPTION_NO_ORPHANED, runtime::Config,
+    },
+    exporters::exporter::{
+        digital_touch_placeholder, generate_text_or_placeholder, is_media_only, reply_context,
+        BalloonFormatter, Exporter, Previewer, Writer, MEDIA_REMOVED,
     },
-    exporters::exporter::{BalloonFormatter, Exporter, Writer},
 };
 
 use imessage_database::{
@@ -31,7 +35,9 @@ use imessage_database::{
         placemark::PlacemarkMessage,
         text_effects::TextEffect,
         url::URLMessage,
-        variants::{Announcement, BalloonProvider, CustomBalloon, URLOverride, Variant},
+        variants::{
+            Announcement, BalloonProvider, CustomBalloon, SystemEvent, URLOverride, Variant,
+        },
     },
     tables::{
         attachment::Attachment,
@@ -42,7 +48,7 @@ use imessage_database::{
         table::{Table, FITNESS_RECEIVER, ME, ORPHANED, YOU},
     },
     util::{
-        dates::{format, get_local_time, readable_diff, TIMESTAMP_FACTOR},
+        dates::{get_local_time, readable_diff, TIMESTAMP_FACTOR},
         plist::parse_plist,
     },
 };
@@ -52,33 +58,33 @@ pub struct TXT<'a> {
     pub config: &'a Config,
     /// Handles to files we want to write messages to
     /// Map of resolved chatroom file location to a buffered writer
-    pub files: HashMap<String, BufWriter<File>>,
-    /// Writer instance for orphaned messages
-    pub orphaned: BufWriter<File>,
+    pub files: BTreeMap<String, BufWriter<ExportFile>>,
+    /// Writer instance for orphaned messages, unless they are dropped
+    pub orphaned: Option<BufWriter<ExportFile>>,
 }
 
 impl<'a> Exporter<'a> for TXT<'a> {
     fn new(config: &'a Config) -> Result<Self, RuntimeError> {
-        let mut orphaned = config.options.export_path.clone();
-        orphaned.push(ORPHANED);
-        orphaned.set_extension("txt");
+        let orphaned = if config.options.no_orphaned {
+            None
+        } else {
+            let mut orphaned = config.conversation_dir(ORPHANED)?;
+            orphaned.push(ORPHANED);
+            orphaned.set_extension(config.file_extension("txt"));
 
-        let file = File::options()
-            .append(true)
-            .create(true)
-            .open(&orphaned)
-            .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+            Some(config.open_export_file(&orphaned)?)
+        };
 
         Ok(TXT {
             config,
-            files: HashMap::new(),
-            orphaned: BufWriter::new(file),
+            files: BTreeMap::new(),
+            orphaned,
         })
     }
 
     fn iter_messages(&mut self) -> Result<(), RuntimeError> {
         // Tell the user what we are doing
-        eprintln!(
+        info!(
             "Exporting to {} as txt...",
             self.config.options.export_path.display()
         );
@@ -86,12 +92,21 @@ impl<'a> Exporter<'a> for TXT<'a> {
         // Keep track of current message ROWID
         let mut current_message_row = -1;
 
+        // Keep track of messages dropped because they do not belong to a conversation
+        let mut dropped_messages = 0;
+
+        // Keep track of messages skipped because a previous export already wrote them
+        let mut skipped_messages = 0;
+
+        // Keep track of messages skipped because their text is too short
+        let mut short_messages = 0;
+
         // Set up progress bar
         let mut current_message = 0;
         let total_messages =
             Message::get_count(&self.config.db, &self.config.options.query_context)
                 .map_err(RuntimeError::DatabaseError)?;
-        let pb = build_progress_bar_export(total_messages);
+        let pb = self.config.progress_bar(total_messages);
 
         let mut statement =
             Message::stream_rows(&self.config.db, &self.config.options.query_context)
@@ -102,7 +117,14 @@ impl<'a> Exporter<'a> for TXT<'a> {
             .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
 
         for message in messages {
-            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+            let mut msg = match Message::extract(message) {
+                Ok(msg) => msg,
+                Err(why) => {
+                    self.config.handle_error(RuntimeError::DatabaseError(why))?;
+                    current_message += 1;
+                    continue;
+                }
+            };
 
             // Early escape if we try and render the same message GUID twice
             // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
@@ -112,27 +134,80 @@ impl<'a> Exporter<'a> for TXT<'a> {
             }
             current_message_row = msg.rowid;
 
+            // Drop messages that do not belong to a conversation, if requested
+            if self.config.options.no_orphaned && self.config.is_orphaned(&msg) {
+                dropped_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Skip messages that a previous export already wrote, if requested
+            if self.config.already_exported(&msg) {
+                skipped_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
             // Generate the text of the message
-            let _ = msg.generate_text(&self.config.db);
+            generate_text_or_placeholder(&mut msg, &self.config.db);
+
+            // Skip messages that are too short, which is only known once their text is generated
+            if self.config.is_too_short(&msg) {
+                short_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Note when the message was delivered and read, and list it on its own if it is a tapback, if requested
+            self.config.record_receipt(&msg);
+            self.config.record_reaction(&msg);
 
             // Render the announcement in-line
             if msg.is_announcement() {
                 let announcement = self.format_announcement(&msg);
-                TXT::write_to_file(self.get_or_create_file(&msg)?, &announcement)?;
+                if let Err(why) = self
+                    .get_or_create_file(&msg)
+                    .and_then(|file| TXT::write_to_file(file, &announcement))
+                    .and_then(|_| self.config.mark_exported(&msg.guid))
+                {
+                    self.config.handle_error(why)?;
+                }
             }
             // Message replies and tapbacks are rendered in context, so no need to render them separately
             else if !msg.is_tapback() {
-                let message = self
-                    .format_message(&msg, 0)
-                    .map_err(RuntimeError::DatabaseError)?;
-                TXT::write_to_file(self.get_or_create_file(&msg)?, &message)?;
+                match self.format_message(&msg, 0) {
+                    Ok(message) => {
+                        if let Err(why) = self
+                            .get_or_create_file(&msg)
+                            .and_then(|file| TXT::write_to_file(file, &message))
+                            .and_then(|_| self.config.mark_exported(&msg.guid))
+                        {
+                            self.config.handle_error(why)?;
+                        }
+                    }
+                    Err(why) => self.config.handle_error(RuntimeError::DatabaseError(why))?,
+                }
             }
+
+            // Abort if an attachment failed to copy and we are not exporting on a best effort basis
+            self.config.check_errors()?;
+
             current_message += 1;
             if current_message % 99 == 0 {
                 pb.set_position(current_message);
             }
         }
         pb.finish();
+
+        if dropped_messages > 0 {
+            info!("Dropped {dropped_messages} messages that do not belong to a conversation");
+        }
+        if skipped_messages > 0 {
+            info!("Skipped {skipped_messages} messages that a previous export already wrote");
+        }
+        if short_messages > 0 {
+            info!("Skipped {short_messages} messages that are too short");
+        }
         Ok(())
     }
 
@@ -140,28 +215,59 @@ impl<'a> Exporter<'a> for TXT<'a> {
     fn get_or_create_file(
         &mut self,
         message: &Message,
-    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+    ) -> Result<&mut BufWriter<ExportFile>, RuntimeError> {
         match self.config.conversation(message) {
             Some((chatroom, _)) => {
                 let filename = self.config.filename(chatroom);
                 match self.files.entry(filename) {
                     Occupied(entry) => Ok(entry.into_mut()),
                     Vacant(entry) => {
-                        let mut path = self.config.options.export_path.clone();
-                        path.push(self.config.filename(chatroom));
-                        path.set_extension("txt");
+                        let mut path = self.config.conversation_dir(entry.key())?;
+                        path.push(entry.key());
+                        path.set_extension(self.config.file_extension("txt"));
 
-                        let file = File::options()
-                            .append(true)
-                            .create(true)
-                            .open(&path)
-                            .map_err(|err| RuntimeError::CreateError(err, path))?;
-
-                        Ok(entry.insert(BufWriter::new(file)))
+                        Ok(entry.insert(self.config.open_export_file(&path)?))
                     }
                 }
             }
-            None => Ok(&mut self.orphaned),
+            None => self
+                .orphaned
+                .as_mut()
+                .ok_or(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_NO_ORPHANED} is enabled, but message {} has no conversation!",
+                    message.rowid
+                ))),
+        }
+    }
+
+    fn supports_attachments() -> bool {
+        true
+    }
+
+    fn supports_reactions() -> bool {
+        true
+    }
+}
+
+impl<'a> Previewer<'a> for TXT<'a> {
+    fn preview(config: &'a Config) -> Self {
+        TXT {
+            config,
+            files: BTreeMap::new(),
+            orphaned: None,
+        }
+    }
+
+    fn format_preview(&self, msg: &Message) -> Result<Option<String>, RuntimeError> {
+        // Match the export, which renders announcements in-line and tapbacks with the message they react to
+        if msg.is_announcement() {
+            Ok(Some(self.format_announcement(msg)))
+        } else if !msg.is_tapback() {
+            self.format_message(msg, 0)
+                .map(Some)
+                .map_err(RuntimeError::DatabaseError)
+        } else {
+            Ok(None)
         }
     }
 }
@@ -195,22 +301,47 @@ impl<'a> Writer<'a> for TXT<'a> {
             );
         }
 
+        // Quote the message this one replied to, if requested
+        let reply_quote = match self.config.options.quote_replies {
+            Some(max_length) if indent.is_empty() => {
+                reply_context(message, &self.config.db, self.config.schema, max_length)
+            }
+            _ => None,
+        };
+        if let Some(quote) = &reply_quote {
+            for line in quote.lines() {
+                self.add_line(&mut formatted_message, &format!("> {line}"), &indent);
+            }
+        }
+
         // Useful message metadata
         let message_parts = message.body();
-        let mut attachments = Attachment::from_message(&self.config.db, message)?;
-        let mut replies = message.get_replies(&self.config.db)?;
+        let mut attachments = if self.config.options.redact_attachments {
+            vec![]
+        } else {
+            Attachment::from_message(&self.config.db, message)?
+        };
+        let mut replies = message.get_replies(&self.config.db, self.config.schema)?;
 
         // Index of where we are in the attachment Vector
         let mut attachment_index: usize = 0;
 
         // Render subject
-        if let Some(subject) = &message.subject {
-            self.add_line(&mut formatted_message, subject, &indent);
+        if let Some(subject) = message.subject.as_deref().filter(|s| !s.is_empty()) {
+            self.add_line(
+                &mut formatted_message,
+                &format!("Subject: {subject}"),
+                &indent,
+            );
         }
 
-        // Handle SharePlay
-        if message.is_shareplay() {
-            self.add_line(&mut formatted_message, self.format_shareplay(), &indent);
+        // Handle system events, i.e. FaceTime calls
+        if let Some(event) = message.get_system_event() {
+            self.add_line(
+                &mut formatted_message,
+                &self.format_system_event(&event),
+                &indent,
+            );
         }
 
         // Handle Shared Location
@@ -267,6 +398,12 @@ impl<'a> Writer<'a> for TXT<'a> {
                         }
                     }
                 }
+                // Leave attachments out, noting where a message only contained attachments
+                BubbleComponent::Attachment(_) if self.config.options.redact_attachments => {
+                    if idx == 0 && is_media_only(&message_parts) {
+                        self.add_line(&mut formatted_message, MEDIA_REMOVED, &indent);
+                    }
+                }
                 BubbleComponent::Attachment(metadata) => {
                     match attachments.get_mut(attachment_index) {
                         Some(attachment) => {
@@ -350,7 +487,7 @@ impl<'a> Writer<'a> for TXT<'a> {
                 replies
                     .iter_mut()
                     .try_for_each(|reply| -> Result<(), TableError> {
-                        let _ = reply.generate_text(&self.config.db);
+                        generate_text_or_placeholder(reply, &self.config.db);
                         if !reply.is_tapback() {
                             self.add_line(
                                 &mut formatted_message,
@@ -363,8 +500,8 @@ impl<'a> Writer<'a> for TXT<'a> {
             }
         }
 
-        // Add a note if the message is a reply
-        if message.is_reply() && indent.is_empty() {
+        // Add a note if the message is a reply and the message it replied to was not quoted
+        if message.is_reply() && indent.is_empty() && reply_quote.is_none() {
             self.add_line(
                 &mut formatted_message,
                 "This message responded to an earlier message.",
@@ -531,10 +668,13 @@ impl<'a> Writer<'a> for TXT<'a> {
                 ))
             }
             Variant::Sticker(_) => {
-                let mut paths = Attachment::from_message(&self.config.db, msg)?;
                 let who =
                     self.config
                         .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+                if self.config.options.redact_attachments {
+                    return Ok(format!("Sticker from {who}"));
+                }
+                let mut paths = Attachment::from_message(&self.config.db, msg)?;
                 // Sticker messages have only one attachment, the sticker image
                 Ok(if let Some(sticker) = paths.get_mut(0) {
                     format!("{} from {who}", self.format_sticker(sticker, msg))
@@ -579,7 +719,7 @@ impl<'a> Writer<'a> for TXT<'a> {
             who = self.config.options.custom_name.as_deref().unwrap_or(YOU);
         }
 
-        let timestamp = format(&msg.date(&self.config.offset));
+        let timestamp = self.config.format_date(&msg.date(&self.config.offset));
 
         match msg.get_announcement() {
             Some(announcement) => match announcement {
@@ -598,8 +738,8 @@ impl<'a> Writer<'a> for TXT<'a> {
         }
     }
 
-    fn format_shareplay(&self) -> &str {
-        "SharePlay Message\nEnded"
+    fn format_system_event(&self, event: &SystemEvent) -> String {
+        event.to_string()
     }
 
     fn format_shared_location(&self, msg: &'a Message) -> &str {
@@ -629,8 +769,9 @@ impl<'a> Writer<'a> for TXT<'a> {
                         match previous_timestamp {
                             // Original message get an absolute timestamp
                             None => {
-                                let parsed_timestamp =
-                                    format(&get_local_time(&event.date, &self.config.offset));
+                                let parsed_timestamp = self
+                                    .config
+                                    .format_date(&get_local_time(&event.date, &self.config.offset));
                                 out_s.push_str(&parsed_timestamp);
                                 out_s.push(' ');
                             }
@@ -687,12 +828,18 @@ impl<'a> Writer<'a> for TXT<'a> {
         None
     }
 
-    fn format_attributed(&'a self, msg: &'a str, _: &'a TextEffect) -> Cow<'a, str> {
-        // There isn't really a way to represent formatted text in a plain text export
-        Cow::Borrowed(msg)
+    fn format_attributed(&'a self, msg: &'a str, effect: &'a TextEffect) -> Cow<'a, str> {
+        match effect {
+            // Mentions are marked so they stand out from the surrounding text, if requested
+            TextEffect::Mention(_) if self.config.options.rich_text => {
+                Cow::Owned(format!("@{msg}"))
+            }
+            // There isn't really a way to represent formatted text in a plain text export
+            _ => Cow::Borrowed(msg),
+        }
     }
 
-    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+    fn write_to_file(file: &mut BufWriter<ExportFile>, text: &str) -> Result<(), RuntimeError> {
         file.write_all(text.as_bytes())
             .map_err(RuntimeError::DiskError)
     }
@@ -879,7 +1026,7 @@ impl<'a> BalloonFormatter<&'a str> for TXT<'a> {
     }
 
     fn format_digital_touch(&self, _: &Message, balloon: &DigitalTouch, indent: &str) -> String {
-        format!("{indent}Digital Touch Message: {:?}", balloon)
+        format!("{indent}{}", digital_touch_placeholder(balloon))
     }
 
     fn format_apple_pay(&self, balloon: &AppMessage, indent: &str) -> String {
@@ -954,7 +1101,7 @@ impl<'a> BalloonFormatter<&'a str> for TXT<'a> {
             // Parse the estimated end time from the message's query string
             let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
             let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
+            let date_string = self.config.format_date(&date_time);
 
             out_s.push_str("\nExpected at ");
             out_s.push_str(&date_string);
@@ -964,7 +1111,7 @@ impl<'a> BalloonFormatter<&'a str> for TXT<'a> {
             // Parse the estimated end time from the message's query string
             let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
             let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
+            let date_string = self.config.format_date(&date_time);
 
             out_s.push_str("\nWas expected at ");
             out_s.push_str(&date_string);
@@ -974,7 +1121,7 @@ impl<'a> BalloonFormatter<&'a str> for TXT<'a> {
             // Parse the estimated end time from the message's query string
             let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
             let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
+            let date_string = self.config.format_date(&date_time);
 
             out_s.push_str("\nChecked in at ");
             out_s.push_str(&date_string);
@@ -995,7 +1142,7 @@ impl<'a> BalloonFormatter<&'a str> for TXT<'a> {
         if let Some(name) = balloon.app_name {
             out_s.push_str(name);
         } else {
-            out_s.push_str(bundle_id);
+            out_s.push_str(CustomBalloon::Application(bundle_id).name());
         }
 
         if !out_s.is_empty() {
@@ -1033,17 +1180,9 @@ impl<'a> BalloonFormatter<&'a str> for TXT<'a> {
 
 impl TXT<'_> {
     fn get_time(&self, message: &Message) -> String {
-        let mut date = format(&message.date(&self.config.offset));
-        let read_after = message.time_until_read(&self.config.offset);
-        if let Some(time) = read_after {
-            if !time.is_empty() {
-                let who = if message.is_from_me() {
-                    "them"
-                } else {
-                    self.config.options.custom_name.as_deref().unwrap_or("you")
-                };
-                date.push_str(&format!(" (Read by {who} after {time})"));
-            }
+        let mut date = self.config.format_date(&message.date(&self.config.offset));
+        if let Some(note) = self.config.delivery_note(message) {
+            date.push_str(&format!(" ({note})"));
         }
         date
     }
@@ -1061,15 +1200,19 @@ impl TXT<'_> {
 mod tests {
     use std::{
         env::{current_dir, set_var},
+        fs::File,
+        io::Read,
         path::PathBuf,
     };
 
     use crate::{
-        app::export_type::ExportType, exporters::exporter::Writer, Config, Exporter, Options, TXT,
+        app::export_type::ExportType,
+        exporters::exporter::{Previewer, Writer},
+        Config, Exporter, Options, TXT,
     };
     use imessage_database::{
         tables::{messages::models::AttachmentMeta, table::ME},
-        util::platform::Platform,
+        util::{platform::Platform, typedstream::parser::TypedStreamReader},
     };
 
     #[test]
@@ -1080,6 +1223,75 @@ mod tests {
         assert_eq!(exporter.files.len(), 0);
     }
 
+    #[test]
+    fn can_create_preview() {
+        let options = Options::fake_options(ExportType::Txt);
+        let config = Config::fake_app(options);
+        let exporter = TXT::preview(&config);
+        assert!(exporter.orphaned.is_none());
+    }
+
+    #[test]
+    fn can_format_preview() {
+        let options = Options::fake_options(ExportType::Txt);
+        let config = Config::fake_app(options);
+        let exporter = TXT::preview(&config);
+
+        let mut message = Config::fake_message();
+        message.text = Some("Hello world".to_string());
+
+        assert_eq!(
+            exporter.format_preview(&message).unwrap(),
+            Some(exporter.format_message(&message, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn can_format_message_redacted_attachment() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.redact_attachments = true;
+        let config = Config::fake_app(options);
+        let exporter = TXT::preview(&config);
+
+        let mut message = Config::fake_message();
+        message.text = Some("\u{FFFC}".to_string());
+        message.num_attachments = 1;
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        assert!(actual.contains("<media removed>"));
+        assert!(!actual.contains("Attachment missing!"));
+    }
+
+    #[test]
+    fn can_format_message_redacted_attachment_with_text() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.redact_attachments = true;
+        let config = Config::fake_app(options);
+        let exporter = TXT::preview(&config);
+
+        let mut message = Config::fake_message();
+        message.text = Some("\u{FFFC}Check out this photo!".to_string());
+        message.num_attachments = 1;
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        assert!(actual.contains("Check out this photo!"));
+        assert!(!actual.contains("<media removed>"));
+        assert!(!actual.contains("Attachment missing!"));
+    }
+
+    #[test]
+    fn cant_format_preview_tapback() {
+        let options = Options::fake_options(ExportType::Txt);
+        let config = Config::fake_app(options);
+        let exporter = TXT::preview(&config);
+
+        let mut message = Config::fake_message();
+        message.associated_message_type = Some(2000);
+        message.associated_message_guid = Some("fake_guid".to_string());
+
+        assert_eq!(exporter.format_preview(&message).unwrap(), None);
+    }
+
     #[test]
     fn can_get_time_valid() {
         // Set timezone to PST for consistent Local time
@@ -1126,6 +1338,28 @@ mod tests {
         assert_eq!(exporter.get_time(&message), "May 17, 2022  6:30:31 PM");
     }
 
+    #[test]
+    fn can_get_time_failed() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = Options::fake_options(ExportType::Txt);
+        let config = Config::fake_app(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        // Create fake message
+        let mut message = Config::fake_message();
+        // May 17, 2022  9:30:31 PM
+        message.date = 674530231992568192;
+        message.is_from_me = true;
+        message.error = 22;
+        assert_eq!(
+            exporter.get_time(&message),
+            "May 17, 2022  6:30:31 PM (Not Delivered)"
+        );
+    }
+
     #[test]
     fn can_add_line_no_indent() {
         // Create exporter
@@ -1177,6 +1411,30 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_txt_from_me_subject() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = Options::fake_options(ExportType::Txt);
+        let config = Config::fake_app(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.subject = Some("Greetings".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "May 17, 2022  5:29:42 PM\nMe\nSubject: Greetings\nHello world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_format_txt_from_me_normal_deleted() {
         // Set timezone to PST for consistent Local time
@@ -1313,7 +1571,7 @@ mod tests {
     }
 
     #[test]
-    fn can_format_txt_shareplay() {
+    fn can_format_txt_facetime() {
         // Set timezone to PST for consistent Local time
         set_var("TZ", "PST");
 
@@ -1330,7 +1588,27 @@ mod tests {
         message.item_type = 6;
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "May 17, 2022  5:29:42 PM\nMe\nSharePlay Message\nEnded\n\n";
+        let expected = "May 17, 2022  5:29:42 PM\nMe\nFaceTime call, incoming\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_txt_unknown_system_event() {
+        // Create exporter
+        let options = Options::fake_options(ExportType::Txt);
+        let mut config = Config::fake_app(options);
+        config.participants.insert(0, ME.to_string());
+
+        let exporter = TXT::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.item_type = 3;
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "May 17, 2022  5:29:42 PM\nMe\nUnsupported system event (type 3)\n\n";
 
         assert_eq!(actual, expected);
     }
@@ -1761,22 +2039,112 @@ mod tests {
 
         assert_eq!(actual, "Audio Message.caf\nTranscription: Test");
     }
+
+    #[test]
+    fn can_format_txt_mention_rich_text() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.rich_text = true;
+        let config = Config::fake_app(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Test Dad ".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let typedstream_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/typedstream/Mention");
+        let mut file = File::open(typedstream_path).unwrap();
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let mut parser = TypedStreamReader::from(&bytes);
+        message.components = parser.parse().ok();
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "May 17, 2022  5:29:42 PM\nMe\nTest @Dad \n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_quote_reply() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.db_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_data")
+            .join("fixture")
+            .join("chat.db");
+        options.quote_replies = Some(12);
+        let config = Config::new(options).unwrap();
+        let exporter = TXT::preview(&config);
+
+        let mut message = Config::fake_message();
+        message.is_from_me = true;
+        message.chat_id = Some(1);
+        message.text = Some("Saturday works".to_string());
+        message.thread_originator_guid = Some("00000000-0000-4000-8000-000000000001".to_string());
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        assert!(actual.contains("Me\n> Are we still...\nSaturday works\n"));
+        assert!(!actual.contains("This message responded to an earlier message."));
+    }
+
+    #[test]
+    fn can_note_reply_without_quote() {
+        let options = Options::fake_options(ExportType::Txt);
+        let config = Config::fake_app(options);
+        let exporter = TXT::preview(&config);
+
+        let mut message = Config::fake_message();
+        message.text = Some("Saturday works".to_string());
+        message.thread_originator_guid = Some("00000000-0000-4000-8000-000000000001".to_string());
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        assert!(actual.contains("This message responded to an earlier message."));
+    }
 }
 
 #[cfg(test)]
 mod balloon_format_tests {
     use std::env::set_var;
 
-    use crate::{exporters::exporter::BalloonFormatter, Config, Exporter, Options, TXT};
+    use crate::{
+        exporters::exporter::{BalloonFormatter, Previewer},
+        Config, Exporter, Options, TXT,
+    };
     use imessage_database::message_types::{
         app::AppMessage,
         app_store::AppStoreMessage,
         collaboration::CollaborationMessage,
+        digital_touch::DigitalTouch,
         music::MusicMessage,
         placemark::{Placemark, PlacemarkMessage},
         url::URLMessage,
     };
 
+    #[test]
+    fn can_format_txt_digital_touch() {
+        // Create exporter
+        let options = Options::fake_options(crate::app::export_type::ExportType::Txt);
+        let config = Config::fake_app(options);
+        let exporter = TXT::preview(&config);
+
+        let message = Config::fake_message();
+        let expected = exporter.format_digital_touch(&message, &DigitalTouch::Sketch, "");
+        let actual = "[Digital Touch: sketch]";
+
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn can_format_txt_url() {
         // Create exporter
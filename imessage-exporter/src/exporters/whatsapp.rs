@@ -0,0 +1,482 @@
+use std::{
+    collections::{
+        btree_map::Entry::{Occupied, Vacant},
+        BTreeMap,
+    },
+    io::{BufWriter, Write},
+};
+
+use log::info;
+
+use crate::{
+    app::{
+        error::RuntimeError, export_file::ExportFile, options::OPTION_NO_ORPHANED, runtime::Config,
+    },
+    exporters::exporter::{
+        generate_text_or_placeholder, summarize_app, Exporter, Previewer, MEDIA_REMOVED,
+    },
+};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::variants::Announcement,
+    tables::{
+        attachment::Attachment,
+        messages::Message,
+        table::{Table, ME, ORPHANED, YOU},
+    },
+};
+
+/// The date format WhatsApp chat exports use, i.e. `15/01/2024, 14:30:05`
+const DATE_FORMAT: &str = "%d/%m/%Y, %H:%M:%S";
+/// Characters in message text that mark where attachments and apps are rendered
+const REPLACEMENT_CHARS: [char; 2] = ['\u{FFFC}', '\u{FFFD}'];
+/// What WhatsApp writes for a message that has no text or attachments it can include
+const MEDIA_OMITTED: &str = "<Media omitted>";
+/// What WhatsApp appends to a message that was edited
+const EDITED: &str = "<This message was edited>";
+
+/// Writes conversations in the line format of WhatsApp's "Export chat" feature
+///
+/// Each line is shaped like `[DD/MM/YYYY, HH:MM:SS] Sender: message`, so tools that read
+/// WhatsApp chat exports can read it. The format is fixed to match their parsers.
+///
+/// WhatsApp exports do not include reactions, so tapbacks are not written.
+pub struct WhatsApp<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of resolved chatroom file location to a buffered writer
+    pub files: BTreeMap<String, BufWriter<ExportFile>>,
+    /// Writer instance for orphaned messages, unless they are dropped
+    pub orphaned: Option<BufWriter<ExportFile>>,
+}
+
+impl<'a> Exporter<'a> for WhatsApp<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let orphaned = if config.options.no_orphaned {
+            None
+        } else {
+            let mut orphaned = config.conversation_dir(ORPHANED)?;
+            orphaned.push(ORPHANED);
+            orphaned.set_extension(config.file_extension("txt"));
+
+            Some(config.open_export_file(&orphaned)?)
+        };
+
+        Ok(WhatsApp {
+            config,
+            files: BTreeMap::new(),
+            orphaned,
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        info!(
+            "Exporting to {} as whatsapp...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of messages dropped because they do not belong to a conversation
+        let mut dropped_messages = 0;
+
+        // Keep track of messages skipped because a previous export already wrote them
+        let mut skipped_messages = 0;
+
+        // Keep track of messages skipped because their text is too short
+        let mut short_messages = 0;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = self.config.progress_bar(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = match Message::extract(message) {
+                Ok(msg) => msg,
+                Err(why) => {
+                    self.config.handle_error(RuntimeError::DatabaseError(why))?;
+                    current_message += 1;
+                    continue;
+                }
+            };
+
+            // Drop messages that do not belong to a conversation, if requested
+            if self.config.options.no_orphaned && self.config.is_orphaned(&msg) {
+                dropped_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // Skip messages that a previous export already wrote, if requested
+            if self.config.already_exported(&msg) {
+                skipped_messages += 1;
+                current_message += 1;
+                continue;
+            }
+
+            // List the message on its own if it is a tapback, if requested
+            self.config.record_reaction(&msg);
+
+            // WhatsApp exports do not include reactions
+            if !msg.is_tapback() {
+                // Generate the text of the message
+                generate_text_or_placeholder(&mut msg, &self.config.db);
+
+                // Skip messages that are too short, which is only known once their text is generated
+                if self.config.is_too_short(&msg) {
+                    short_messages += 1;
+                    current_message += 1;
+                    continue;
+                }
+
+                // Note when the message was delivered and read, if requested
+                self.config.record_receipt(&msg);
+
+                match self.format_message(&msg) {
+                    Ok(lines) => {
+                        if let Err(why) = self
+                            .get_or_create_file(&msg)
+                            .and_then(|file| {
+                                file.write_all(lines.as_bytes())
+                                    .map_err(RuntimeError::DiskError)
+                            })
+                            .and_then(|_| self.config.mark_exported(&msg.guid))
+                        {
+                            self.config.handle_error(why)?;
+                        }
+                    }
+                    Err(why) => self.config.handle_error(RuntimeError::DatabaseError(why))?,
+                }
+            }
+
+            // Abort if an attachment failed to copy and we are not exporting on a best effort basis
+            self.config.check_errors()?;
+
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        if dropped_messages > 0 {
+            info!("Dropped {dropped_messages} messages that do not belong to a conversation");
+        }
+        if skipped_messages > 0 {
+            info!("Skipped {skipped_messages} messages that a previous export already wrote");
+        }
+        if short_messages > 0 {
+            info!("Skipped {short_messages} messages that are too short");
+        }
+        Ok(())
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<ExportFile>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let mut path = self.config.conversation_dir(entry.key())?;
+                        path.push(entry.key());
+                        path.set_extension(self.config.file_extension("txt"));
+
+                        Ok(entry.insert(self.config.open_export_file(&path)?))
+                    }
+                }
+            }
+            None => self
+                .orphaned
+                .as_mut()
+                .ok_or(RuntimeError::InvalidOptions(format!(
+                    "Option {OPTION_NO_ORPHANED} is enabled, but message {} has no conversation!",
+                    message.rowid
+                ))),
+        }
+    }
+
+    fn supports_attachments() -> bool {
+        true
+    }
+
+    fn supports_reactions() -> bool {
+        // WhatsApp exports do not include reactions
+        false
+    }
+}
+
+impl<'a> Previewer<'a> for WhatsApp<'a> {
+    fn preview(config: &'a Config) -> Self {
+        WhatsApp {
+            config,
+            files: BTreeMap::new(),
+            orphaned: None,
+        }
+    }
+
+    fn format_preview(&self, msg: &Message) -> Result<Option<String>, RuntimeError> {
+        // Tapbacks are rendered with the message they react to
+        if msg.is_tapback() {
+            return Ok(None);
+        }
+        self.format_message(msg)
+            .map(Some)
+            .map_err(RuntimeError::DatabaseError)
+    }
+}
+
+impl<'a> WhatsApp<'a> {
+    /// Format a message as one or more lines, one for its text and one for each attachment
+    fn format_message(&self, message: &Message) -> Result<String, TableError> {
+        let timestamp = message
+            .date(&self.config.offset)
+            .map(|date| self.config.localize(&date).format(DATE_FORMAT).to_string())
+            .unwrap_or_default();
+        let who = self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+
+        if let Some(announcement) = message.get_announcement() {
+            return Ok(self.format_announcement(message, &announcement, &timestamp, who));
+        }
+
+        // System events have no text of their own, so describe the event instead
+        if let Some(event) = message.get_system_event() {
+            return Ok(format!("[{timestamp}] {who}: {event}\n"));
+        }
+
+        let mut lines = vec![];
+
+        let text: String = message
+            .text
+            .as_deref()
+            .unwrap_or_default()
+            .chars()
+            .filter(|c| !REPLACEMENT_CHARS.contains(c))
+            .collect();
+        let text = text.trim();
+        if !text.is_empty() {
+            if message.is_edited() {
+                lines.push(format!("{text} {EDITED}"));
+            } else {
+                lines.push(text.to_string());
+            }
+        }
+
+        if let Some(app_message) = summarize_app(message, &self.config.db) {
+            lines.push(app_message);
+        }
+
+        if message.has_attachments() && !self.config.options.redact_attachments {
+            for mut attachment in Attachment::from_message(&self.config.db, message)? {
+                lines.push(format!(
+                    "<attached: {}>",
+                    self.format_attachment(message, &mut attachment)
+                ));
+            }
+        }
+
+        if lines.is_empty() {
+            if message.has_attachments() && self.config.options.redact_attachments {
+                lines.push(MEDIA_REMOVED.to_string());
+            } else {
+                lines.push(MEDIA_OMITTED.to_string());
+            }
+        }
+
+        Ok(lines
+            .iter()
+            .map(|line| format!("[{timestamp}] {who}: {line}\n"))
+            .collect())
+    }
+
+    /// Get the name of an attachment, copying it if requested
+    ///
+    /// Copied attachments are named by their path relative to the export file
+    fn format_attachment(&self, message: &Message, attachment: &mut Attachment) -> String {
+        match self.config.options.attachment_manager.handle_attachment(
+            message,
+            attachment,
+            self.config,
+        ) {
+            Some(_) => self.config.message_attachment_path(attachment),
+            None => attachment.filename().to_string(),
+        }
+    }
+
+    /// Format an announcement like the closest WhatsApp system message
+    fn format_announcement(
+        &self,
+        message: &Message,
+        announcement: &Announcement,
+        timestamp: &str,
+        who: &str,
+    ) -> String {
+        // WhatsApp system messages refer to the database owner as "You"
+        let actor = if who == ME {
+            self.config.options.custom_name.as_deref().unwrap_or(YOU)
+        } else {
+            who
+        };
+
+        match announcement {
+            Announcement::NameChange(name) => {
+                format!("[{timestamp}] {actor} changed the group name to \"{name}\"\n")
+            }
+            Announcement::PhotoChange => {
+                format!("[{timestamp}] {actor} changed this group's icon\n")
+            }
+            Announcement::Unknown(num) => {
+                format!("[{timestamp}] {actor} performed unknown action {num}\n")
+            }
+            Announcement::FullyUnsent if message.is_from_me() => {
+                format!("[{timestamp}] {who}: You deleted this message\n")
+            }
+            Announcement::FullyUnsent => {
+                format!("[{timestamp}] {who}: This message was deleted\n")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::set_var;
+
+    use imessage_database::message_types::edited::{EditStatus, EditedMessage, EditedMessagePart};
+
+    use crate::{
+        app::export_type::ExportType, exporters::whatsapp::WhatsApp, Config, Exporter, Options,
+    };
+
+    #[test]
+    fn can_format_message() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = Options::fake_options(ExportType::WhatsApp);
+        let mut config = Config::fake_app(options);
+        config.participants.insert(0, "Sample Contact".to_string());
+        let exporter = WhatsApp::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+
+        let actual = exporter.format_message(&message).unwrap();
+        let expected = "[17/05/2022, 17:29:42] Sample Contact: Hello world\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_message_multiline_edited() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = Options::fake_options(ExportType::WhatsApp);
+        let config = Config::fake_app(options);
+        let exporter = WhatsApp::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.date_edited = 674526582885055488;
+        message.text = Some("First line\nSecond line".to_string());
+        message.is_from_me = true;
+
+        let actual = exporter.format_message(&message).unwrap();
+        let expected =
+            "[17/05/2022, 17:29:42] Me: First line\nSecond line <This message was edited>\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_message_media_omitted() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = Options::fake_options(ExportType::WhatsApp);
+        let config = Config::fake_app(options);
+        let exporter = WhatsApp::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("\u{FFFD}".to_string());
+        message.is_from_me = true;
+
+        let actual = exporter.format_message(&message).unwrap();
+        let expected = "[17/05/2022, 17:29:42] Me: <Media omitted>\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_name_change() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = Options::fake_options(ExportType::WhatsApp);
+        let config = Config::fake_app(options);
+        let exporter = WhatsApp::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.group_title = Some("Family".to_string());
+        message.is_from_me = true;
+
+        let actual = exporter.format_message(&message).unwrap();
+        let expected = "[17/05/2022, 17:29:42] You changed the group name to \"Family\"\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_unsent() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = Options::fake_options(ExportType::WhatsApp);
+        let mut config = Config::fake_app(options);
+        config.participants.insert(0, "Sample Contact".to_string());
+        let exporter = WhatsApp::new(&config).unwrap();
+
+        let mut message = Config::fake_message();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.edited_parts = Some(EditedMessage {
+            parts: vec![EditedMessagePart {
+                status: EditStatus::Unsent,
+                edit_history: vec![],
+            }],
+        });
+
+        let actual = exporter.format_message(&message).unwrap();
+        let expected = "[17/05/2022, 17:29:42] Sample Contact: This message was deleted\n";
+
+        assert_eq!(actual, expected);
+    }
+}
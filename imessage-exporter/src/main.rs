@@ -3,38 +3,62 @@
 mod app;
 mod exporters;
 
-pub use exporters::{exporter::Exporter, html::HTML, txt::TXT};
+pub use exporters::{
+    csv::CSV, exporter::Exporter, html::HTML, markdown::Markdown, parquet::Parquet, pdf::PDF,
+    txt::TXT, whatsapp::WhatsApp,
+};
+
+use std::process::exit;
+
+use log::error;
 
 use app::{
+    logger,
     options::{from_command_line, Options},
     runtime::Config,
 };
 
 fn main() {
+    // Route status and error messages through the logger
+    logger::init();
+
     // Get args from command line
     let args = from_command_line();
     // Create application options
     let options = Options::from_args(&args);
 
-    // Create app state and start
+    // Create app state and start; invalid options report every problem before exiting
     if let Err(why) = &options {
-        eprintln!("{why}");
+        error!("{why}");
+        exit(1);
     } else {
         match options {
             Ok(options) => match Config::new(options) {
                 Ok(mut app) => {
                     // Resolve the filtered contacts, if provided
                     app.resolve_filtered_handles();
+                    // Limit the export to the conversations in the chat list, if provided
+                    if let Err(why) = app.resolve_chats_from_file() {
+                        error!("Unable to read chat list: {why}");
+                        exit(1);
+                    }
+                    // Limit the export to conversations of the requested size, if requested
+                    app.resolve_participant_count();
 
                     if let Err(why) = app.start() {
-                        eprintln!("Unable to export: {why}");
+                        error!("Unable to export: {why}");
+                        exit(1);
                     }
                 }
                 Err(why) => {
-                    eprintln!("Invalid configuration: {why}");
+                    error!("Invalid configuration: {why}");
+                    exit(1);
                 }
             },
-            Err(why) => eprintln!("Invalid command line options: {why}"),
+            Err(why) => {
+                error!("Invalid command line options: {why}");
+                exit(1);
+            }
         }
     }
 }
@@ -0,0 +1,152 @@
+-- Builds chat.db, a small iMessage database used by the exporter golden file tests
+--
+-- Rebuild the database after editing this file:
+--     rm chat.db && sqlite3 chat.db < chat.sql
+
+CREATE TABLE handle (
+    ROWID INTEGER PRIMARY KEY AUTOINCREMENT UNIQUE,
+    id TEXT NOT NULL,
+    country TEXT,
+    service TEXT NOT NULL,
+    uncanonicalized_id TEXT,
+    person_centric_id TEXT
+);
+
+CREATE TABLE chat (
+    ROWID INTEGER PRIMARY KEY AUTOINCREMENT,
+    guid TEXT UNIQUE NOT NULL,
+    chat_identifier TEXT,
+    service_name TEXT,
+    display_name TEXT,
+    properties BLOB,
+    is_pinned INTEGER DEFAULT 0
+);
+
+CREATE TABLE message (
+    ROWID INTEGER PRIMARY KEY AUTOINCREMENT,
+    guid TEXT UNIQUE NOT NULL,
+    text TEXT,
+    service TEXT,
+    handle_id INTEGER DEFAULT 0,
+    destination_caller_id TEXT,
+    subject TEXT,
+    date INTEGER,
+    date_read INTEGER,
+    date_delivered INTEGER,
+    is_from_me INTEGER DEFAULT 0,
+    is_read INTEGER DEFAULT 0,
+    error INTEGER DEFAULT 0,
+    item_type INTEGER DEFAULT 0,
+    other_handle INTEGER DEFAULT 0,
+    share_status INTEGER DEFAULT 0,
+    share_direction INTEGER DEFAULT 0,
+    group_title TEXT,
+    group_action_type INTEGER DEFAULT 0,
+    associated_message_guid TEXT,
+    associated_message_type INTEGER DEFAULT 0,
+    associated_message_emoji TEXT,
+    balloon_bundle_id TEXT,
+    expressive_send_style_id TEXT,
+    thread_originator_guid TEXT,
+    thread_originator_part TEXT,
+    date_edited INTEGER DEFAULT 0,
+    service_center TEXT,
+    was_downgraded INTEGER DEFAULT 0,
+    is_audio_message INTEGER DEFAULT 0,
+    attributedBody BLOB,
+    message_summary_info BLOB,
+    payload_data BLOB
+);
+
+CREATE TABLE attachment (
+    ROWID INTEGER PRIMARY KEY AUTOINCREMENT,
+    guid TEXT UNIQUE NOT NULL,
+    filename TEXT,
+    uti TEXT,
+    mime_type TEXT,
+    transfer_name TEXT,
+    total_bytes INTEGER DEFAULT 0,
+    is_sticker INTEGER DEFAULT 0,
+    hide_attachment INTEGER DEFAULT 0,
+    emoji_image_short_description TEXT
+);
+
+CREATE TABLE chat_handle_join (
+    chat_id INTEGER REFERENCES chat (ROWID) ON DELETE CASCADE,
+    handle_id INTEGER REFERENCES handle (ROWID) ON DELETE CASCADE,
+    UNIQUE (chat_id, handle_id)
+);
+
+CREATE TABLE chat_message_join (
+    chat_id INTEGER REFERENCES chat (ROWID) ON DELETE CASCADE,
+    message_id INTEGER REFERENCES message (ROWID) ON DELETE CASCADE,
+    message_date INTEGER DEFAULT 0,
+    PRIMARY KEY (chat_id, message_id)
+);
+
+CREATE TABLE message_attachment_join (
+    message_id INTEGER REFERENCES message (ROWID) ON DELETE CASCADE,
+    attachment_id INTEGER REFERENCES attachment (ROWID) ON DELETE CASCADE,
+    UNIQUE (message_id, attachment_id)
+);
+
+CREATE TABLE chat_recoverable_message_join (
+    chat_id INTEGER REFERENCES chat (ROWID) ON DELETE CASCADE,
+    message_id INTEGER REFERENCES message (ROWID) ON DELETE CASCADE,
+    delete_date INTEGER,
+    PRIMARY KEY (chat_id, message_id)
+);
+
+INSERT INTO handle (ROWID, id, country, service, uncanonicalized_id, person_centric_id) VALUES
+    (1, '+15555550101', 'us', 'iMessage', '5555550101', NULL),
+    (2, '+15555550102', 'us', 'SMS', '5555550102', NULL),
+    (3, 'carol@example.com', 'us', 'iMessage', NULL, NULL),
+    (4, '+15555550100', 'us', 'iMessage', '5555550100', NULL);
+
+INSERT INTO chat (ROWID, guid, chat_identifier, service_name, display_name) VALUES
+    (1, 'iMessage;-;+15555550101', '+15555550101', 'iMessage', ''),
+    (2, 'SMS;+;chat100000000000000001', 'chat100000000000000001', 'SMS', 'Book Club'),
+    (3, 'iMessage;-;+15555550100', '+15555550100', 'iMessage', '');
+
+INSERT INTO chat_handle_join (chat_id, handle_id) VALUES
+    (1, 1),
+    (2, 1),
+    (2, 2),
+    (2, 3),
+    (3, 4);
+
+-- A direct conversation with an attachment, a tapback, and a reply, a group SMS conversation, and one orphaned message
+INSERT INTO message (ROWID, guid, text, service, handle_id, date, date_read, date_delivered, is_from_me, is_read, associated_message_guid, associated_message_type, service_center) VALUES
+    (1, '00000000-0000-4000-8000-000000000001', 'Are we still on for Saturday?', 'iMessage', 1, 700000000000000000, 700000030000000000, 0, 0, 1, NULL, 0, NULL),
+    (2, '00000000-0000-4000-8000-000000000002', 'Yes! Here is the map ' || char(65532), 'iMessage', 1, 700000060000000000, 0, 700000061000000000, 1, 1, NULL, 0, NULL),
+    (3, '00000000-0000-4000-8000-000000000003', NULL, 'iMessage', 1, 700000090000000000, 0, 0, 0, 1, 'p:0/00000000-0000-4000-8000-000000000002', 2000, NULL),
+    (4, '00000000-0000-4000-8000-000000000004', 'Welcome to the book club', 'SMS', 2, 700000120000000000, 700000150000000000, 0, 0, 1, NULL, 0, '+12063130004'),
+    (5, '00000000-0000-4000-8000-000000000005', 'Thanks for the invite', 'SMS', 0, 700000180000000000, 0, 700000181000000000, 1, 1, NULL, 0, NULL),
+    (6, '00000000-0000-4000-8000-000000000006', 'This message lost its conversation', 'iMessage', 3, 700000240000000000, 0, 0, 0, 0, NULL, 0, NULL);
+
+INSERT INTO message (ROWID, guid, text, service, handle_id, date, date_read, date_delivered, is_from_me, is_read, thread_originator_guid, thread_originator_part) VALUES
+    (7, '00000000-0000-4000-8000-000000000007', 'Saturday works, see you at noon', 'iMessage', 1, 700000300000000000, 0, 700000301000000000, 1, 1, '00000000-0000-4000-8000-000000000001', '0:0:29');
+
+INSERT INTO message (ROWID, guid, text, service, handle_id, date, date_read, date_delivered, is_from_me, is_read, balloon_bundle_id, payload_data) VALUES
+    (8, '00000000-0000-4000-8000-000000000008', 'https://chrissardegna.com', 'iMessage', 1, 700000360000000000, 700000390000000000, 0, 0, 1, 'com.apple.messages.URLBalloonProvider', X'62706C6973743030D4010203040506070A582476657273696F6E592461726368697665725424746F7058246F626A6563747312000186A05F100F4E534B657965644172636869766572D1080954726F6F748001AF10140B0C1323292A303435363D3E404347484C51545855246E756C6CD30D0E0F1011125F1015726963684C696E6B4973506C616365686F6C6465725F1010726963684C696E6B4D657461646174615624636C6173730880028013D81415160F1718191A1B1C1D1E1F2021225569636F6E735B6F726967696E616C55524C5C69636F6E4D65746164617461557469746C655776657273696F6E5355524C5469636F6E80108003800C80128008100180068009D3240F25262728574E532E626173655B4E532E72656C61746976658000800580045F101968747470733A2F2F63687269737361726465676E612E636F6DD22B2C2D2E5A24636C6173736E616D655824636C6173736573554E5355524CA22D2F584E534F626A656374D3240F252627338000800580075F101A68747470733A2F2F63687269737361726465676E612E636F6D2F5F10144368726973746F70686572205361726465676E61D43738390F3A3A3B3C59696D616765547970655F1026726963684C696E6B496D6167654174746163686D656E7453756273746974757465496E646578584D494D45547970651000800A800B5F1018696D6167652F766E642E6D6963726F736F66742E69636F6ED12B3F5F1021526963684C696E6B496D6167654174746163686D656E7453756273746974757465D318190F204142800D800FD3240F2526274680008005800E5F102568747470733A2F2F63687269737361726465676E612E636F6D2F66617669636F6E2E69636FD22B2C494A5E4C5049636F6E4D65746164617461A24B2F5E4C5049636F6E4D65746164617461D24D0F4E505A4E532E6F626A65637473A11D800C8011D22B2C5253574E534172726179A2522FD22B2C55565E4C504C696E6B4D65746164617461A2572F5E4C504C696E6B4D65746164617461D12B5958526963684C696E6B00080011001A00240029003200370049004C00510053006A00700077008F00A200A900AA00AC00AE00BF00C500D100DE00E400EC00F000F500F700F900FB00FD00FF010101030105010C01140120012201240126014201470152015B01610164016D017401760178017A019701AE01B701C101EA01F301F501F701F902140217023B024202440246024D024F02510253027B0280028F029202A102A602B102B302B502B702BC02C402C702CC02DB02DE02ED02F00000000000000201000000000000005A000000000000000000000000000002F9');
+
+INSERT INTO message (ROWID, guid, text, service, handle_id, destination_caller_id, date, date_read, date_delivered, is_from_me, is_read) VALUES
+    (9, '00000000-0000-4000-8000-000000000009', 'Buy more coffee', 'iMessage', 4, '+15555550100', 700000420000000000, 0, 700000421000000000, 1, 1),
+    (10, '00000000-0000-4000-8000-000000000010', 'Buy more coffee', 'iMessage', 4, '+15555550100', 700000420000000000, 700000421000000000, 0, 0, 1);
+
+INSERT INTO chat_message_join (chat_id, message_id, message_date) VALUES
+    (1, 1, 700000000000000000),
+    (1, 2, 700000060000000000),
+    (1, 3, 700000090000000000),
+    (2, 4, 700000120000000000),
+    (2, 5, 700000180000000000),
+    (1, 7, 700000300000000000),
+    (1, 8, 700000360000000000),
+    (3, 9, 700000420000000000),
+    (3, 10, 700000420000000000);
+
+INSERT INTO attachment (ROWID, guid, filename, uti, mime_type, transfer_name, total_bytes) VALUES
+    (1, 'at_0_00000000-0000-4000-8000-000000000002', '~/Library/Messages/Attachments/00/00/at_0/map.png', 'public.png', 'image/png', 'map.png', 2048);
+
+INSERT INTO message_attachment_join (message_id, attachment_id) VALUES
+    (2, 1);